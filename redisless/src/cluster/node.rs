@@ -22,10 +22,10 @@ pub struct ClusterNode {
 
     #[allow(dead_code)]
     listening_socket_addr: SocketAddr,
-    
+
     #[allow(dead_code)]
     peer_receiver: Receiver<Peer>,
-    
+
     listener_started: bool,
     search_peers_started: bool,
 }
@@ -57,7 +57,7 @@ impl ClusterNode {
             return;
         }
 
-        let _ = match peers_discovery {
+        match peers_discovery {
             PeersDiscovery::Manual(_) => return, // in this case - search peers is not useful
             PeersDiscovery::Automatic(_) => {}
         };
@@ -42,7 +42,7 @@ impl Peer {
                 self.id,
                 BTreeSet::new(),
                 InMemoryLog::new_unbounded(),
-                OsRng::default(),
+                OsRng,
                 CONFIG,
             ),
             self.peers_discovery,
@@ -1,11 +1,10 @@
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
 use crate::cluster::node::ClusterNode;
 use crate::cluster::peer::{Peer, PeersDiscovery, DEFAULT_NODE_LISTENING_PORT};
 use crate::cluster::util::{
     get_ip_addresses, get_local_network_ip_addresses, get_range_from_ip_address, scan_ip_range,
     Range,
 };
-
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
 
 #[test]
 fn start_cluster() {
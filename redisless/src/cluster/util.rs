@@ -88,10 +88,7 @@ pub fn get_range_from_ip_address(ip_address: IpAddr, range: Range) -> Vec<IpAddr
         _ => return vec![],
     };
 
-    ip_addresses
-        .into_iter()
-        .map(IpAddr::V4)
-        .collect()
+    ip_addresses.into_iter().map(IpAddr::V4).collect()
 }
 
 enum ParallelResponse<T> {
@@ -128,7 +125,7 @@ pub fn scan_ip_range(
             let _tx = tx.clone();
             let ports = ports_to_scan.clone();
 
-            let _ = thread_pool.spawn(move || {
+            thread_pool.spawn(move || {
                 let tx = _tx.clone();
                 let ports = ports;
 
@@ -0,0 +1,33 @@
+//! Bounds of the sorted set ranges, parsed alike by `ZCOUNT`, `ZRANGEBYLEX`, `ZRANGE` and
+//! `ZRANGESTORE` with `BYSCORE` or `BYLEX`.
+//!
+//! A score is inclusive unless prefixed by `(`, and `-inf` / `+inf` (or `inf`) are scores
+//! below and above every other, exclusive or not. A member is prefixed by `[` when inclusive
+//! or `(` when exclusive, and `-` / `+` stand below and above every member.
+
+use super::command_error::RedisCommandError;
+use super::util::parse_float;
+use crate::storage::models::{LexBound, ScoreBound};
+
+/// Parse a score like `1.5`, `(1.5` for an exclusive bound, `-inf` or `+inf`
+pub fn parse_score_bound(bytes: Vec<u8>) -> Result<ScoreBound, RedisCommandError> {
+    let (score, exclusive) = match bytes.split_first() {
+        Some((b'(', score)) => (score.to_vec(), true),
+        _ => (bytes, false),
+    };
+    match parse_float(score) {
+        Ok(score) => Ok(ScoreBound { score, exclusive }),
+        Err(_) => Err(RedisCommandError::InvalidScoreRange),
+    }
+}
+
+/// Parse a member like `[a` or `(a` for an exclusive bound, `-` or `+`
+pub fn parse_lex_bound(bytes: Vec<u8>) -> Result<LexBound, RedisCommandError> {
+    match bytes.split_first() {
+        Some((b'-', [])) => Ok(LexBound::Min),
+        Some((b'+', [])) => Ok(LexBound::Max),
+        Some((b'[', member)) => Ok(LexBound::Inclusive(member.to_vec())),
+        Some((b'(', member)) => Ok(LexBound::Exclusive(member.to_vec())),
+        _ => Err(RedisCommandError::SyntaxErr),
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     fmt::{Display, Formatter},
+    io,
     num::ParseIntError,
     str::Utf8Error,
 };
@@ -13,6 +14,9 @@ pub enum RedisCommandError {
     ArgNumber,
     // Overflow when setting the expiry timestamp
     TimeOverflow(TimeOverflow),
+    // the `EX` / `PX` duration of a `SET`, or the one of a `SETEX` / `PSETEX`, with the
+    // lowercase command name
+    InvalidExpireTime(String),
     // Could not convert bytes to UTF8
     BadString(Utf8Error),
     // Could not parse string for a u64
@@ -27,36 +31,325 @@ pub enum RedisCommandError {
     NoSuchKey,
     IndexOutOfRange,
     SyntaxErr,
-}
-
-impl RedisCommandError {
-    pub fn to_vec(&self) -> Vec<u8> {
-        format!("-{}\r\n", self).as_bytes().to_vec()
-    }
+    // Reading or writing a file failed
+    Io(io::Error),
+    BackgroundSaveInProgress,
+    AofRewriteInProgress,
+    // SELECT of a db which does not exist
+    DbIndexOutOfRange,
+    // MOVE of a key to the db it is in
+    SameObject,
+    // a write while the used memory exceeds `maxmemory` and no key can be evicted
+    OutOfMemory,
+    // The number of arguments does not match the arity of the command
+    WrongArity(String),
+    InvalidBit,
+    // `SETRANGE` at a negative offset
+    OffsetOutOfRange,
+    // a string grown past `ServerConfig::proto_max_bulk_len`
+    StringTooLong,
+    BitOpNotSingleSource,
+    InvalidFloat,
+    // `LCS` with both `LEN` and `IDX`
+    LcsLenAndIdx,
+    // the table of an `LCS` would take more than `proto-max-bulk-len` bytes
+    LcsTooLarge,
+    // a `WEIGHTS` of `ZUNION` or `ZINTER` which is not a float
+    InvalidWeight,
+    InvalidScoreRange,
+    // An increment of a score resulted in NaN, e.g. `+inf` + `-inf`
+    ScoreNaN,
+    // The command ran longer than `ServerConfig::command_time_limit`
+    TimeLimitExceeded,
+    NumKeysNotPositive,
+    // numkeys is larger than the number of keys which follow it
+    NumKeysGreaterThanArgs,
+    ZeroRank,
+    NegativeCount,
+    // `ZADD ... INCR` with more than one score / member pair
+    ZAddIncrPairs,
+    // `OBJECT FREQ` without an LFU `ServerConfig::maxmemory_policy`
+    LfuPolicyNotSelected,
+    NotAnInteger,
+    // `INCR` / `DECR` result out of the i64 range, in RESP3 too as nothing is stored past it
+    IncrOverflow,
+    InvalidCursor,
+    // write command of a client sent to a read only replica
+    ReadOnlyReplica,
+    // a command which does not exist, a disabled one, or the original name of a renamed one,
+    // with its first arguments
+    UnknownCommand(String, Vec<String>),
+    // timeout of a blocking command
+    InvalidTimeout,
+    // a timeout in millis which is not an integer
+    InvalidIntegerTimeout,
+    NegativeTimeout,
+    // `WAITAOF` asking for local fsyncs without an append only file
+    WaitAofNoAppendOnly,
+    ValueNotPositive,
+    // `COMMAND GETKEYS` of an unknown command, or with a wrong number of arguments
+    GetKeysInvalidCommand,
+    GetKeysInvalidArgs,
+    NoKeyArguments,
+    // `SORT` without `ALPHA` of an element which is not a number
+    SortNotNumeric,
+    // a command other than the pub/sub ones, sent by a subscribed client
+    SubscribedContext(String),
+    // a command of a client which did not authenticate, while `requirepass` is set
+    NoAuth,
+    // `HELLO` of a client which did not authenticate, without `AUTH`
+    HelloNoAuth,
+    WrongPass,
+    // `AUTH password` while `requirepass` is not set
+    AuthNotConfigured,
+    // `HELLO` of a protocol version other than 2 and 3
+    NoProto,
+    InvalidClientName,
+    // user and command, denied by the ACL of the user
+    NoPerm(String, String),
+    // the rule of an `ACL SETUSER` which does not parse
+    AclSyntax(String),
+    // subcommand and the container command, such as `OBJECT`
+    UnknownSubcommand(String, &'static str),
+    // `CONFIG SET` of a parameter which does not exist
+    UnknownConfigParameter(String),
+    // `CONFIG SET` of a parameter to a value it can't take, with the reason
+    InvalidConfigValue(String, &'static str),
+    // `MULTI` within a transaction
+    NestedMulti,
+    // `EXEC` or `DISCARD` out of a transaction, with the name of the command
+    WithoutMulti(&'static str),
+    // a command which can't be queued in a transaction, such as `SUBSCRIBE`
+    NotAllowedInTransaction,
+    // `EXEC` of a transaction in which a command failed to be queued
+    ExecAbort,
+    // `CLIENT KILL ID` of an id which is not a positive integer
+    InvalidClientId,
+    // `RESTORE` of an existing key without `REPLACE`
+    BusyKey,
+    // `RESTORE` of a payload which is not the one of a `DUMP` of this version
+    BadPayload,
+    // negative ttl of a `RESTORE`
+    InvalidTtl,
+    // negative `IDLETIME` of a `RESTORE`
+    InvalidIdleTime,
+    // `FREQ` of a `RESTORE` out of `0..=255`
+    InvalidFreq,
+    // `numfields` of a `FIELDS numfields field [field ...]` other than the number of fields
+    NumFieldsMismatch,
+    // a count so negative that as many elements could not be replied, as for `SRANDMEMBER`
+    ValueOutOfRange,
+    // `DEBUG INTERNAL_STATS` on a server which does not expose its internals
+    InternalStatsDisabled,
+    // `LIMIT` of a range by index
+    RangeLimitWithoutBy,
+    // `RESTORE` of a payload with a sound version and checksum which can't be decoded
+    BadDataFormat,
+    // the snapshot of a `SHUTDOWN` could not be saved, the server keeps running
+    ShutdownSaveFailed,
 }
 
 impl Display for RedisCommandError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::ArgNumber => {
-                write!(f, "wrong number of arguments for command")
+                write!(f, "ERR wrong number of arguments for command")
+            }
+            Self::TimeOverflow(_) => write!(f, "ERR invalid expire time"),
+            Self::InvalidExpireTime(command) => {
+                write!(f, "ERR invalid expire time in '{}' command", command)
             }
-            Self::TimeOverflow(e) => write!(f, "{:?}", e),
-            Self::BadString(e) => write!(f, "{}", e),
-            Self::IntParse(e) => write!(f, "{}", e),
+            Self::BadString(e) => write!(f, "ERR {}", e),
+            Self::IntParse(_) => write!(f, "ERR value is not an integer or out of range"),
             Self::NotSupported(cmd) => {
-                write!(f, "command {} not supported by redisless", cmd)
+                write!(f, "ERR command {} not supported by redisless", cmd)
             }
-            Self::ProtocolParse(err) => write!(f, "{}", err),
-            Self::InvalidCommand => write!(f, "invalid command"),
-            Self::CommandNotFound => write!(f, "command not found"),
+            Self::ProtocolParse(err) => write!(f, "ERR Protocol error: {}", err),
+            Self::InvalidCommand => write!(f, "ERR invalid command"),
+            Self::CommandNotFound => write!(f, "ERR command not found"),
             Self::WrongTypeOperation => write!(
                 f,
                 "WRONGTYPE Operation against a key holding the wrong kind of value"
             ),
-            Self::NoSuchKey => write!(f, "no such key"),
-            Self::IndexOutOfRange => write!(f, "index out of range"),
-            Self::SyntaxErr => write!(f, "systax error"),
+            Self::NoSuchKey => write!(f, "ERR no such key"),
+            Self::IndexOutOfRange => write!(f, "ERR index out of range"),
+            Self::SyntaxErr => write!(f, "ERR syntax error"),
+            Self::Io(e) => write!(f, "ERR {}", e),
+            Self::BackgroundSaveInProgress => {
+                write!(f, "ERR Background save already in progress")
+            }
+            Self::AofRewriteInProgress => write!(
+                f,
+                "ERR Background append only file rewriting already in progress"
+            ),
+            Self::DbIndexOutOfRange => write!(f, "ERR DB index is out of range"),
+            Self::SameObject => write!(f, "ERR source and destination objects are the same"),
+            Self::OutOfMemory => write!(
+                f,
+                "OOM command not allowed when used memory > 'maxmemory'."
+            ),
+            Self::InvalidBit => write!(f, "ERR The bit argument must be 1 or 0"),
+            Self::OffsetOutOfRange => write!(f, "ERR offset is out of range"),
+            Self::StringTooLong => write!(
+                f,
+                "ERR string exceeds maximum allowed size (proto-max-bulk-len)"
+            ),
+            Self::BitOpNotSingleSource => {
+                write!(f, "ERR BITOP NOT must be called with a single source key")
+            }
+            Self::InvalidFloat => write!(f, "ERR value is not a valid float"),
+            Self::LcsLenAndIdx => write!(
+                f,
+                "ERR If you want both the length and indexes, please just use IDX."
+            ),
+            Self::LcsTooLarge => write!(
+                f,
+                "ERR Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len"
+            ),
+            Self::InvalidWeight => write!(f, "ERR weight value is not a float"),
+            Self::InvalidScoreRange => write!(f, "ERR min or max is not a float"),
+            Self::ScoreNaN => write!(f, "ERR resulting score is not a number (NaN)"),
+            Self::TimeLimitExceeded => write!(f, "ERR command exceeded time limit"),
+            Self::NumKeysNotPositive => write!(f, "ERR numkeys should be greater than 0"),
+            Self::NumKeysGreaterThanArgs => {
+                write!(f, "ERR Number of keys can't be greater than number of args")
+            }
+            Self::ZeroRank => write!(
+                f,
+                "ERR RANK can't be zero: use 1 to start from the first match, 2 from the second ... \
+                 or use negative to start from the end of the list"
+            ),
+            Self::NegativeCount => write!(f, "ERR COUNT can't be negative"),
+            Self::ZAddIncrPairs => {
+                write!(f, "ERR INCR option supports a single increment-element pair")
+            }
+            Self::NotAnInteger => write!(f, "ERR value is not an integer or out of range"),
+            Self::IncrOverflow => write!(f, "ERR increment or decrement would overflow"),
+            Self::InvalidCursor => write!(f, "ERR invalid cursor"),
+            Self::GetKeysInvalidCommand => write!(f, "ERR Invalid command specified"),
+            Self::GetKeysInvalidArgs => {
+                write!(f, "ERR Invalid number of arguments specified for command")
+            }
+            Self::SubscribedContext(cmd) => write!(
+                f,
+                "ERR Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                cmd
+            ),
+            Self::SortNotNumeric => {
+                write!(f, "ERR One or more scores can't be converted into double")
+            }
+            Self::NoKeyArguments => write!(f, "ERR The command has no key arguments"),
+            Self::InvalidTimeout => write!(f, "ERR timeout is not a float or out of range"),
+            Self::NegativeTimeout => write!(f, "ERR timeout is negative"),
+            Self::InvalidIntegerTimeout => {
+                write!(f, "ERR timeout is not an integer or out of range")
+            }
+            Self::WaitAofNoAppendOnly => write!(
+                f,
+                "ERR WAITAOF cannot be used when numlocal is set but appendonly is disabled"
+            ),
+            Self::ValueNotPositive => write!(f, "ERR value is out of range, must be positive"),
+            Self::UnknownCommand(cmd, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| format!("'{}'", arg))
+                    .collect::<Vec<_>>();
+                write!(
+                    f,
+                    "ERR unknown command '{}', with args beginning with: {}",
+                    cmd,
+                    args.join(", ")
+                )
+            }
+            Self::NoAuth => write!(f, "NOAUTH Authentication required."),
+            Self::HelloNoAuth => write!(
+                f,
+                "NOAUTH HELLO must be called with the client already authenticated, otherwise \
+                 the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the \
+                 client and select the RESP protocol version at the same time"
+            ),
+            Self::WrongPass => write!(
+                f,
+                "WRONGPASS invalid username-password pair or user is disabled."
+            ),
+            Self::AuthNotConfigured => write!(
+                f,
+                "ERR AUTH <password> called without any password configured for the default \
+                 user. Are you sure your configuration is correct?"
+            ),
+            Self::NoProto => write!(f, "NOPROTO unsupported protocol version"),
+            Self::InvalidClientName => write!(
+                f,
+                "ERR Client names cannot contain spaces, newlines or special characters."
+            ),
+            Self::NoPerm(user, cmd) => write!(
+                f,
+                "NOPERM User {} has no permissions to run the '{}' command",
+                user, cmd
+            ),
+            Self::UnknownConfigParameter(name) => write!(
+                f,
+                "ERR Unknown option or number of arguments for CONFIG SET - '{}'",
+                name
+            ),
+            Self::InvalidConfigValue(name, reason) => write!(
+                f,
+                "ERR CONFIG SET failed (possibly related to argument '{}') - {}",
+                name, reason
+            ),
+            Self::AclSyntax(rule) => write!(
+                f,
+                "ERR Error in ACL SETUSER modifier '{}': Syntax error",
+                rule
+            ),
+            Self::UnknownSubcommand(subcommand, cmd) => write!(
+                f,
+                "ERR Unknown subcommand or wrong number of arguments for '{}'. Try {} HELP.",
+                subcommand, cmd
+            ),
+            Self::ReadOnlyReplica => {
+                write!(f, "READONLY You can't write against a read only replica.")
+            }
+            Self::LfuPolicyNotSelected => {
+                write!(f, "ERR An LFU maxmemory policy is not selected")
+            }
+            Self::WrongArity(cmd) => {
+                write!(f, "ERR wrong number of arguments for '{}' command", cmd)
+            }
+            Self::NestedMulti => write!(f, "ERR MULTI calls can not be nested"),
+            Self::WithoutMulti(cmd) => write!(f, "ERR {} without MULTI", cmd),
+            Self::NotAllowedInTransaction => {
+                write!(f, "ERR Command not allowed inside a transaction")
+            }
+            Self::ExecAbort => write!(
+                f,
+                "EXECABORT Transaction discarded because of previous errors."
+            ),
+            Self::InvalidClientId => write!(f, "ERR client-id should be greater than 0"),
+            Self::BusyKey => write!(f, "BUSYKEY Target key name already exists."),
+            Self::BadPayload => write!(f, "ERR DUMP payload version or checksum are wrong"),
+            Self::InvalidTtl => write!(f, "ERR Invalid TTL value, must be >= 0"),
+            Self::InvalidIdleTime => write!(f, "ERR Invalid IDLETIME value, must be >= 0"),
+            Self::InvalidFreq => {
+                write!(f, "ERR Invalid FREQ value, must be >= 0 and <= 255")
+            }
+            Self::NumFieldsMismatch => write!(
+                f,
+                "ERR The `numfields` parameter must match the number of arguments"
+            ),
+            Self::ValueOutOfRange => write!(f, "ERR value is out of range"),
+            Self::BadDataFormat => write!(f, "ERR Bad data format"),
+            Self::ShutdownSaveFailed => write!(f, "ERR Errors trying to SHUTDOWN. Check logs."),
+            Self::RangeLimitWithoutBy => write!(
+                f,
+                "ERR syntax error, LIMIT is only supported in combination with either BYSCORE \
+                 or BYLEX"
+            ),
+            Self::InternalStatsDisabled => write!(
+                f,
+                "ERR DEBUG INTERNAL_STATS not allowed, the debug-internal-stats option of the \
+                 server is off"
+            ),
         }
     }
 }
@@ -78,3 +371,9 @@ impl From<ParseIntError> for RedisCommandError {
         Self::IntParse(err)
     }
 }
+
+impl From<io::Error> for RedisCommandError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
@@ -0,0 +1,54 @@
+//! `DEBUG`, whose subcommands mostly serve the tests.
+//!
+//! The subcommands of Redis which only tune its internals are accepted and do nothing, so
+//! that the tools sending them, such as benchmarks, run against this server as well.
+
+use super::command_error::RedisCommandError;
+use super::help::unknown_subcommand;
+use super::util::{get_bytes_vec, parse_float, parse_variation};
+use super::{Command, DebugSubcommand};
+use crate::protocol::Resp;
+
+// uppercase subcommands replied `OK` whatever their arguments, without doing anything
+const NO_OP_SUBCOMMANDS: &[&[u8]] = &[
+    b"CHANGE-REPL-ID",
+    b"DICT-RESIZING",
+    b"JMAP",
+    b"LISTPACK-ENTRIES",
+    b"QUICKLIST-PACKED-THRESHOLD",
+    b"REPLYBUFFER",
+    b"SET-DISABLE-DENY-SCRIPTS",
+    b"SET-SKIP-CHECKSUM-VALIDATION",
+    // without arguments, the fuzzer of the matcher in Redis
+    b"STRINGMATCH-LEN",
+];
+
+/// Parse the arguments of `DEBUG`, v starting with the subcommand
+pub fn parse(v: &[Resp]) -> Result<Command, RedisCommandError> {
+    let subcommand = get_bytes_vec(v.first())?;
+    let name = subcommand.to_ascii_uppercase();
+    match (name.as_slice(), &v[1..]) {
+        (b"OBJECT", [key]) => Ok(Command::Debug(DebugSubcommand::Object(get_bytes_vec(
+            Some(key),
+        )?))),
+        (b"INTERNAL_STATS", []) => Ok(Command::Debug(DebugSubcommand::InternalStats)),
+        (b"RELOAD", []) => Ok(Command::Debug(DebugSubcommand::Reload)),
+        (b"SET-ACTIVE-EXPIRE", [flag]) => {
+            let flag = get_bytes_vec(Some(flag)).and_then(parse_variation)?;
+            Ok(Command::Debug(DebugSubcommand::SetActiveExpire(flag != 0)))
+        }
+        (b"SLEEP", [secs]) => match get_bytes_vec(Some(secs)).and_then(parse_float)? {
+            secs if secs.is_finite() => Ok(Command::Debug(DebugSubcommand::Sleep(secs.max(0.0)))),
+            _ => Err(RedisCommandError::InvalidFloat),
+        },
+        (b"STRINGMATCH-LEN", [pattern, string]) => {
+            Ok(Command::Debug(DebugSubcommand::StringMatch(
+                get_bytes_vec(Some(pattern))?,
+                get_bytes_vec(Some(string))?,
+            )))
+        }
+        (b"HELP", []) => Ok(Command::Help("DEBUG")),
+        (name, _) if NO_OP_SUBCOMMANDS.contains(&name) => Ok(Command::Debug(DebugSubcommand::NoOp)),
+        _ => Err(unknown_subcommand(&subcommand, "DEBUG")),
+    }
+}
@@ -0,0 +1,1230 @@
+//! Documentation of the commands, replied to `COMMAND DOCS` in the format of Redis: a
+//! summary, the group of the command and the schema of its arguments. Only the arguments
+//! the commands accept here are described, which may be fewer than in Redis.
+
+/// Type of an argument, named as in the Redis command docs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgumentType {
+    Key,
+    String,
+    Integer,
+    Double,
+    Pattern,
+    // a keyword standing alone, such as `NX`
+    PureToken,
+    // exactly one of the nested arguments
+    OneOf,
+    // the nested arguments, in sequence
+    Block,
+}
+
+impl ArgumentType {
+    pub fn name(self) -> &'static str {
+        match self {
+            ArgumentType::Key => "key",
+            ArgumentType::String => "string",
+            ArgumentType::Integer => "integer",
+            ArgumentType::Double => "double",
+            ArgumentType::Pattern => "pattern",
+            ArgumentType::PureToken => "pure-token",
+            ArgumentType::OneOf => "oneof",
+            ArgumentType::Block => "block",
+        }
+    }
+}
+
+/// Schema of an argument of a command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Argument {
+    pub name: &'static str,
+    pub kind: ArgumentType,
+    // the keyword preceding the argument, and the keyword itself for a pure token
+    pub token: Option<&'static str>,
+    pub optional: bool,
+    pub multiple: bool,
+    // the arguments of a oneof or of a block
+    pub arguments: &'static [Argument],
+}
+
+impl Argument {
+    const fn new(name: &'static str, kind: ArgumentType) -> Self {
+        Self {
+            name,
+            kind,
+            token: None,
+            optional: false,
+            multiple: false,
+            arguments: &[],
+        }
+    }
+
+    const fn optional(self) -> Self {
+        Self {
+            optional: true,
+            ..self
+        }
+    }
+
+    const fn multiple(self) -> Self {
+        Self {
+            multiple: true,
+            ..self
+        }
+    }
+
+    const fn with_token(self, token: &'static str) -> Self {
+        Self {
+            token: Some(token),
+            ..self
+        }
+    }
+
+    /// Flags of the argument, as Redis reports them
+    pub fn flags(&self) -> Vec<&'static str> {
+        let mut flags = vec![];
+        if self.optional {
+            flags.push("optional");
+        }
+        if self.multiple {
+            flags.push("multiple");
+        }
+        flags
+    }
+}
+
+const fn key(name: &'static str) -> Argument {
+    Argument::new(name, ArgumentType::Key)
+}
+
+const fn string(name: &'static str) -> Argument {
+    Argument::new(name, ArgumentType::String)
+}
+
+const fn integer(name: &'static str) -> Argument {
+    Argument::new(name, ArgumentType::Integer)
+}
+
+const fn double(name: &'static str) -> Argument {
+    Argument::new(name, ArgumentType::Double)
+}
+
+const fn pattern(name: &'static str) -> Argument {
+    Argument::new(name, ArgumentType::Pattern)
+}
+
+// named after its keyword, in lowercase as Redis does
+const fn pure_token(name: &'static str, token: &'static str) -> Argument {
+    Argument::new(name, ArgumentType::PureToken).with_token(token)
+}
+
+const fn one_of(name: &'static str, arguments: &'static [Argument]) -> Argument {
+    Argument {
+        arguments,
+        ..Argument::new(name, ArgumentType::OneOf)
+    }
+}
+
+const fn block(name: &'static str, arguments: &'static [Argument]) -> Argument {
+    Argument {
+        arguments,
+        ..Argument::new(name, ArgumentType::Block)
+    }
+}
+
+// arguments shared by several commands
+const KEY: &[Argument] = &[key("key")];
+const KEYS: &[Argument] = &[key("key").multiple()];
+const NONE: &[Argument] = &[];
+// every container command, documented as its subcommand and the arguments of it
+const SUBCOMMAND: &[Argument] = &[
+    string("subcommand"),
+    string("argument").optional().multiple(),
+];
+const FLUSH: &[Argument] = &[one_of(
+    "flush-type",
+    &[pure_token("async", "ASYNC"), pure_token("sync", "SYNC")],
+)
+.optional()];
+const KEY_SCAN: &[Argument] = &[
+    key("key"),
+    integer("cursor"),
+    pattern("pattern").with_token("MATCH").optional(),
+    integer("count").with_token("COUNT").optional(),
+];
+const KEY_COUNT: &[Argument] = &[key("key"), integer("count").optional()];
+const KEY_ELEMENTS: &[Argument] = &[key("key"), string("element").multiple()];
+const KEY_MEMBERS: &[Argument] = &[key("key"), string("member").multiple()];
+const KEY_VALUE: &[Argument] = &[key("key"), string("value")];
+const KEY_RANGE: &[Argument] = &[key("key"), integer("start"), integer("end")];
+const FIELDS_VALUES: &[Argument] = &[
+    key("key"),
+    block("data", &[string("field"), string("value")]).multiple(),
+];
+const KEYS_VALUES: &[Argument] = &[block("data", &[key("key"), string("value")]).multiple()];
+const BLOCKING_POP: &[Argument] = &[key("key").multiple(), double("timeout")];
+const LIST_SIDE: &[Argument] = &[pure_token("left", "LEFT"), pure_token("right", "RIGHT")];
+const LIST_MOVE: &[Argument] = &[
+    key("source"),
+    key("destination"),
+    one_of("wherefrom", LIST_SIDE),
+    one_of("whereto", LIST_SIDE),
+];
+const BLOCKING_LIST_MOVE: &[Argument] = &[
+    key("source"),
+    key("destination"),
+    one_of("wherefrom", LIST_SIDE),
+    one_of("whereto", LIST_SIDE),
+    double("timeout"),
+];
+const REPLICATE: &[Argument] = &[string("host"), integer("port")];
+const HASH_FIELDS: Argument = block(
+    "fields",
+    &[integer("numfields"), string("field").multiple()],
+)
+.with_token("FIELDS");
+const KEY_HASH_FIELDS: &[Argument] = &[key("key"), HASH_FIELDS];
+const HASH_EXPIRE: &[Argument] = &[
+    key("key"),
+    integer("expiration"),
+    one_of(
+        "condition",
+        &[
+            pure_token("nx", "NX"),
+            pure_token("xx", "XX"),
+            pure_token("gt", "GT"),
+            pure_token("lt", "LT"),
+        ],
+    )
+    .optional(),
+    HASH_FIELDS,
+];
+
+// name, summary, group and arguments of a command
+type Doc = (
+    &'static [u8],
+    &'static str,
+    &'static str,
+    &'static [Argument],
+);
+
+/// Documentation of each command, sorted by name as the command table
+const DOCS: &[Doc] = &[
+    (
+        b"ACL",
+        "A container for Access List Control commands.",
+        "server",
+        SUBCOMMAND,
+    ),
+    (
+        b"APPEND",
+        "Appends a string to the value of a key. Creates the key if it doesn't exist.",
+        "string",
+        KEY_VALUE,
+    ),
+    (
+        b"AUTH",
+        "Authenticates the connection.",
+        "connection",
+        &[string("username").optional(), string("password")],
+    ),
+    (
+        b"BGREWRITEAOF",
+        "Asynchronously rewrites the append-only file to disk.",
+        "server",
+        NONE,
+    ),
+    (
+        b"BGSAVE",
+        "Asynchronously saves the database(s) to disk.",
+        "server",
+        &[pure_token("schedule", "SCHEDULE").optional()],
+    ),
+    (
+        b"BITOP",
+        "Performs bitwise operations on multiple strings, and stores the result.",
+        "bitmap",
+        &[
+            one_of(
+                "operation",
+                &[
+                    pure_token("and", "AND"),
+                    pure_token("or", "OR"),
+                    pure_token("xor", "XOR"),
+                    pure_token("not", "NOT"),
+                ],
+            ),
+            key("destkey"),
+            key("key").multiple(),
+        ],
+    ),
+    (
+        b"BITPOS",
+        "Finds the first set (1) or clear (0) bit in a string.",
+        "bitmap",
+        &[
+            key("key"),
+            integer("bit"),
+            block("range", &[integer("start"), integer("end").optional()]).optional(),
+        ],
+    ),
+    (
+        b"BLMOVE",
+        "Pops an element from a list, pushes it to another list and returns it. Blocks until an \
+         element is available otherwise. Deletes the list if the last element was moved.",
+        "list",
+        BLOCKING_LIST_MOVE,
+    ),
+    (
+        b"BLPOP",
+        "Removes and returns the first element in a list. Blocks until an element is available \
+         otherwise. Deletes the list if the last element was popped.",
+        "list",
+        BLOCKING_POP,
+    ),
+    (
+        b"BRPOP",
+        "Removes and returns the last element in a list. Blocks until an element is available \
+         otherwise. Deletes the list if the last element was popped.",
+        "list",
+        BLOCKING_POP,
+    ),
+    (
+        b"BRPOPLPUSH",
+        "Pops an element from a list, pushes it to another list and returns it. Blocks until an \
+         element is available otherwise. Deletes the list if the last element was popped.",
+        "list",
+        &[key("source"), key("destination"), double("timeout")],
+    ),
+    (
+        b"CAS",
+        "Sets the string value of a key only when its current value is the expected one.",
+        "string",
+        &[key("key"), string("expected"), string("value")],
+    ),
+    (
+        b"CLIENT",
+        "A container for client connection commands.",
+        "connection",
+        SUBCOMMAND,
+    ),
+    (
+        b"COMMAND",
+        "Returns detailed information about all commands.",
+        "server",
+        &[
+            string("subcommand").optional(),
+            string("argument").optional().multiple(),
+        ],
+    ),
+    (
+        b"CONFIG",
+        "A container for server configuration commands.",
+        "server",
+        SUBCOMMAND,
+    ),
+    (
+        b"COPY",
+        "Copies the value of a key to a new key.",
+        "generic",
+        &[
+            key("source"),
+            key("destination"),
+            integer("destination-db").with_token("DB").optional(),
+            pure_token("replace", "REPLACE").optional(),
+        ],
+    ),
+    (
+        b"DBSIZE",
+        "Returns the number of keys in the database.",
+        "server",
+        NONE,
+    ),
+    (
+        b"DEBUG",
+        "A container for debugging commands.",
+        "server",
+        SUBCOMMAND,
+    ),
+    (
+        b"DECR",
+        "Decrements the integer value of a key by one. Uses 0 as initial value if the key \
+         doesn't exist.",
+        "string",
+        KEY,
+    ),
+    (
+        b"DECRBY",
+        "Decrements a number from the integer value of a key. Uses 0 as initial value if the \
+         key doesn't exist.",
+        "string",
+        &[key("key"), integer("decrement")],
+    ),
+    (b"DEL", "Deletes one or more keys.", "generic", KEYS),
+    (b"DISCARD", "Discards a transaction.", "transactions", &[]),
+    (
+        b"DUMP",
+        "Returns a serialized representation of the value stored at a key.",
+        "generic",
+        &[key("key")],
+    ),
+    (
+        b"EXEC",
+        "Executes all commands in a transaction.",
+        "transactions",
+        &[],
+    ),
+    (
+        b"EXISTS",
+        "Determines whether one or more keys exist.",
+        "generic",
+        KEYS,
+    ),
+    (
+        b"EXPIRE",
+        "Sets the expiration time of a key in seconds.",
+        "generic",
+        &[key("key"), integer("seconds")],
+    ),
+    (
+        b"FLUSHALL",
+        "Removes all keys from all databases.",
+        "server",
+        FLUSH,
+    ),
+    (
+        b"FLUSHDB",
+        "Remove all keys from the current database.",
+        "server",
+        FLUSH,
+    ),
+    (b"GET", "Returns the string value of a key.", "string", KEY),
+    (
+        b"GETDEL",
+        "Returns the string value of a key after deleting the key.",
+        "string",
+        KEY,
+    ),
+    (
+        b"GETEX",
+        "Returns the string value of a key after setting its expiration time.",
+        "string",
+        &[
+            key("key"),
+            one_of(
+                "expiration",
+                &[
+                    integer("seconds").with_token("EX"),
+                    integer("milliseconds").with_token("PX"),
+                    integer("unix-time-seconds").with_token("EXAT"),
+                    integer("unix-time-milliseconds").with_token("PXAT"),
+                    pure_token("persist", "PERSIST"),
+                ],
+            )
+            .optional(),
+        ],
+    ),
+    (
+        b"GETRANGE",
+        "Returns a substring of the string stored at a key.",
+        "string",
+        KEY_RANGE,
+    ),
+    (
+        b"GETSET",
+        "Returns the previous string value of a key after setting it to a new value.",
+        "string",
+        KEY_VALUE,
+    ),
+    (
+        b"HELLO",
+        "Handshakes with the Redis server.",
+        "connection",
+        &[block(
+            "arguments",
+            &[
+                integer("protover"),
+                block("auth", &[string("username"), string("password")])
+                    .with_token("AUTH")
+                    .optional(),
+                string("clientname").with_token("SETNAME").optional(),
+            ],
+        )
+        .optional()],
+    ),
+    (
+        b"HEXPIRE",
+        "Sets the expiration time of hash fields, in seconds.",
+        "hash",
+        HASH_EXPIRE,
+    ),
+    (
+        b"HGET",
+        "Returns the value of a field in a hash.",
+        "hash",
+        &[key("key"), string("field")],
+    ),
+    (
+        b"HGETALL",
+        "Returns all fields and values in a hash.",
+        "hash",
+        KEY,
+    ),
+    (b"HKEYS", "Returns all fields in a hash.", "hash", KEY),
+    (
+        b"HMSET",
+        "Sets the values of multiple fields.",
+        "hash",
+        FIELDS_VALUES,
+    ),
+    (
+        b"HPERSIST",
+        "Removes the expiration time of hash fields.",
+        "hash",
+        KEY_HASH_FIELDS,
+    ),
+    (
+        b"HPEXPIRE",
+        "Sets the expiration time of hash fields, in milliseconds.",
+        "hash",
+        HASH_EXPIRE,
+    ),
+    (
+        b"HRANDFIELD",
+        "Returns one or more random fields from a hash.",
+        "hash",
+        &[
+            key("key"),
+            block(
+                "options",
+                &[
+                    integer("count"),
+                    pure_token("withvalues", "WITHVALUES").optional(),
+                ],
+            )
+            .optional(),
+        ],
+    ),
+    (
+        b"HSCAN",
+        "Iterates over fields and values of a hash.",
+        "hash",
+        KEY_SCAN,
+    ),
+    (
+        b"HSET",
+        "Creates or modifies the value of a field in a hash.",
+        "hash",
+        FIELDS_VALUES,
+    ),
+    (
+        b"HTTL",
+        "Returns the TTL in seconds of hash fields.",
+        "hash",
+        KEY_HASH_FIELDS,
+    ),
+    (b"HVALS", "Returns all values in a hash.", "hash", KEY),
+    (
+        b"INCR",
+        "Increments the integer value of a key by one. Uses 0 as initial value if the key \
+         doesn't exist.",
+        "string",
+        KEY,
+    ),
+    (
+        b"INCRBY",
+        "Increments the integer value of a key by a number. Uses 0 as initial value if the key \
+         doesn't exist.",
+        "string",
+        &[key("key"), integer("increment")],
+    ),
+    (
+        b"INFO",
+        "Returns information and statistics about the server.",
+        "server",
+        &[string("section").optional().multiple()],
+    ),
+    (
+        b"KEYS",
+        "Returns all key names that match a pattern.",
+        "generic",
+        &[pattern("pattern")],
+    ),
+    (
+        b"LASTSAVE",
+        "Returns the Unix timestamp of the last successful save to disk.",
+        "server",
+        NONE,
+    ),
+    (
+        b"LATENCY",
+        "A container for latency diagnostics commands.",
+        "server",
+        SUBCOMMAND,
+    ),
+    (
+        b"LCS",
+        "Finds the longest common substring.",
+        "string",
+        &[
+            key("key1"),
+            key("key2"),
+            pure_token("len", "LEN").optional(),
+            pure_token("idx", "IDX").optional(),
+            integer("min-match-len")
+                .with_token("MINMATCHLEN")
+                .optional(),
+            pure_token("withmatchlen", "WITHMATCHLEN").optional(),
+        ],
+    ),
+    (
+        b"LINDEX",
+        "Returns an element from a list by its index.",
+        "list",
+        &[key("key"), integer("index")],
+    ),
+    (
+        b"LINSERT",
+        "Inserts an element before or after another element in a list.",
+        "list",
+        &[
+            key("key"),
+            one_of(
+                "where",
+                &[pure_token("before", "BEFORE"), pure_token("after", "AFTER")],
+            ),
+            string("pivot"),
+            string("element"),
+        ],
+    ),
+    (b"LLEN", "Returns the length of a list.", "list", KEY),
+    (
+        b"LMOVE",
+        "Returns an element after popping it from one list and pushing it to another. Deletes \
+         the list if the last element was moved.",
+        "list",
+        LIST_MOVE,
+    ),
+    (
+        b"LOLWUT",
+        "Displays computer art and the Redis version.",
+        "server",
+        &[integer("version").with_token("VERSION").optional()],
+    ),
+    (
+        b"LPOP",
+        "Returns the first elements in a list after removing it. Deletes the list if the last \
+         element was popped.",
+        "list",
+        KEY_COUNT,
+    ),
+    (
+        b"LPOS",
+        "Returns the index of matching elements in a list.",
+        "list",
+        &[
+            key("key"),
+            string("element"),
+            integer("rank").with_token("RANK").optional(),
+            integer("num-matches").with_token("COUNT").optional(),
+            integer("len").with_token("MAXLEN").optional(),
+        ],
+    ),
+    (
+        b"LPUSH",
+        "Prepends one or more elements to a list. Creates the key if it doesn't exist.",
+        "list",
+        KEY_ELEMENTS,
+    ),
+    (
+        b"LPUSHX",
+        "Prepends one or more elements to a list only when the list exists.",
+        "list",
+        KEY_ELEMENTS,
+    ),
+    (
+        b"LREM",
+        "Removes elements from a list. Deletes the list if the last element was removed.",
+        "list",
+        &[key("key"), integer("count"), string("element")],
+    ),
+    (
+        b"LSET",
+        "Sets the value of an element in a list by its index.",
+        "list",
+        &[key("key"), integer("index"), string("element")],
+    ),
+    (
+        b"LTRIM",
+        "Removes elements from both ends a list. Deletes the list if all elements were trimmed.",
+        "list",
+        &[key("key"), integer("start"), integer("stop")],
+    ),
+    (
+        b"MEMORY",
+        "A container for memory diagnostics commands.",
+        "server",
+        SUBCOMMAND,
+    ),
+    (
+        b"MGET",
+        "Atomically returns the string values of one or more keys.",
+        "string",
+        KEYS,
+    ),
+    (
+        b"MONITOR",
+        "Listens for all requests received by the server in real-time.",
+        "server",
+        NONE,
+    ),
+    (
+        b"MOVE",
+        "Moves a key to another database.",
+        "generic",
+        &[key("key"), integer("db")],
+    ),
+    (
+        b"MSET",
+        "Atomically creates or modifies the string values of one or more keys.",
+        "string",
+        KEYS_VALUES,
+    ),
+    (
+        b"MSETNX",
+        "Atomically modifies the string values of one or more keys only when all keys don't \
+         exist.",
+        "string",
+        KEYS_VALUES,
+    ),
+    (b"MULTI", "Starts a transaction.", "transactions", &[]),
+    (
+        b"OBJECT",
+        "A container for object introspection commands.",
+        "generic",
+        SUBCOMMAND,
+    ),
+    (
+        b"PEXPIRE",
+        "Sets the expiration time of a key in milliseconds.",
+        "generic",
+        &[key("key"), integer("milliseconds")],
+    ),
+    (
+        b"PING",
+        "Returns the server's liveliness response.",
+        "connection",
+        &[string("message").optional()],
+    ),
+    (
+        b"PSETEX",
+        "Sets both string value and expiration time in milliseconds of a key. The key is \
+         created if it doesn't exist.",
+        "string",
+        &[key("key"), integer("milliseconds"), string("value")],
+    ),
+    (
+        b"PSUBSCRIBE",
+        "Listens for messages published to channels that match one or more patterns.",
+        "pubsub",
+        &[pattern("pattern").multiple()],
+    ),
+    (
+        b"PSYNC",
+        "An internal command used in replication.",
+        "server",
+        &[string("replicationid"), integer("offset")],
+    ),
+    (
+        b"PTTL",
+        "Returns the expiration time in milliseconds of a key.",
+        "generic",
+        KEY,
+    ),
+    (
+        b"PUBLISH",
+        "Posts a message to a channel.",
+        "pubsub",
+        &[string("channel"), string("message")],
+    ),
+    (
+        b"PUBSUB",
+        "A container for Pub/Sub commands.",
+        "pubsub",
+        SUBCOMMAND,
+    ),
+    (
+        b"PUNSUBSCRIBE",
+        "Stops listening to messages published to channels that match one or more patterns.",
+        "pubsub",
+        &[pattern("pattern").optional().multiple()],
+    ),
+    (b"QUIT", "Closes the connection.", "connection", NONE),
+    (
+        b"RANDOMKEY",
+        "Returns a random key name from the database.",
+        "generic",
+        NONE,
+    ),
+    (
+        b"REPLICAOF",
+        "Configures a server as replica of another, or promotes it to a master.",
+        "server",
+        REPLICATE,
+    ),
+    (
+        b"RESTORE",
+        "Creates a key from the serialized representation of a value.",
+        "generic",
+        &[
+            key("key"),
+            integer("ttl"),
+            string("serialized-value"),
+            pure_token("replace", "REPLACE").optional(),
+            pure_token("absttl", "ABSTTL").optional(),
+            integer("seconds").with_token("IDLETIME").optional(),
+            integer("frequency").with_token("FREQ").optional(),
+        ],
+    ),
+    (b"ROLE", "Returns the replication role.", "server", NONE),
+    (
+        b"RPOP",
+        "Returns and removes the last elements of a list. Deletes the list if the last element \
+         was popped.",
+        "list",
+        KEY_COUNT,
+    ),
+    (
+        b"RPOPLPUSH",
+        "Returns the last element of a list after removing and pushing it to another list. \
+         Deletes the list if the last element was popped.",
+        "list",
+        &[key("source"), key("destination")],
+    ),
+    (
+        b"RPUSH",
+        "Appends one or more elements to a list. Creates the key if it doesn't exist.",
+        "list",
+        KEY_ELEMENTS,
+    ),
+    (
+        b"RPUSHX",
+        "Appends an element to a list only when the list exists.",
+        "list",
+        KEY_ELEMENTS,
+    ),
+    (
+        b"SADD",
+        "Adds one or more members to a set. Creates the key if it doesn't exist.",
+        "set",
+        KEY_MEMBERS,
+    ),
+    (
+        b"SAVE",
+        "Synchronously saves the database(s) to disk.",
+        "server",
+        NONE,
+    ),
+    (
+        b"SCAN",
+        "Iterates over the key names in the database.",
+        "generic",
+        &[
+            integer("cursor"),
+            pattern("pattern").with_token("MATCH").optional(),
+            integer("count").with_token("COUNT").optional(),
+            string("type").with_token("TYPE").optional(),
+        ],
+    ),
+    (
+        b"SCARD",
+        "Returns the number of members in a set.",
+        "set",
+        KEY,
+    ),
+    (
+        b"SELECT",
+        "Changes the selected database.",
+        "connection",
+        &[integer("index")],
+    ),
+    (
+        b"SET",
+        "Sets the string value of a key, ignoring its type. The key is created if it doesn't \
+         exist.",
+        "string",
+        &[
+            key("key"),
+            string("value"),
+            one_of(
+                "condition",
+                &[pure_token("nx", "NX"), pure_token("xx", "XX")],
+            )
+            .optional(),
+            pure_token("get", "GET").optional(),
+            one_of(
+                "expiration",
+                &[
+                    integer("seconds").with_token("EX"),
+                    integer("milliseconds").with_token("PX"),
+                    pure_token("keepttl", "KEEPTTL"),
+                ],
+            )
+            .optional(),
+        ],
+    ),
+    (
+        b"SETEX",
+        "Sets the string value and expiration time of a key. Creates the key if it doesn't \
+         exist.",
+        "string",
+        &[key("key"), integer("seconds"), string("value")],
+    ),
+    (
+        b"SETNX",
+        "Set the string value of a key only when the key doesn't exist.",
+        "string",
+        KEY_VALUE,
+    ),
+    (
+        b"SETRANGE",
+        "Overwrites a part of a string value with another by an offset. Creates the key if it \
+         doesn't exist.",
+        "string",
+        &[key("key"), integer("offset"), string("value")],
+    ),
+    (
+        b"SHUTDOWN",
+        "Synchronously saves the database(s) to disk and shuts down the Redis server.",
+        "server",
+        &[one_of(
+            "save-selector",
+            &[pure_token("nosave", "NOSAVE"), pure_token("save", "SAVE")],
+        )
+        .optional()],
+    ),
+    (
+        b"SINTERCARD",
+        "Returns the number of members of the intersect of multiple sets.",
+        "set",
+        &[
+            integer("numkeys"),
+            key("key").multiple(),
+            integer("limit").with_token("LIMIT").optional(),
+        ],
+    ),
+    (
+        b"SLAVEOF",
+        "Sets a Redis server as a replica of another, or promotes it to being a master.",
+        "server",
+        REPLICATE,
+    ),
+    (
+        b"SMISMEMBER",
+        "Determines whether multiple members belong to a set.",
+        "set",
+        KEY_MEMBERS,
+    ),
+    (
+        b"SORT",
+        "Sorts the elements in a list, a set, or a sorted set.",
+        "generic",
+        &[
+            key("key"),
+            block("limit", &[integer("offset"), integer("count")])
+                .with_token("LIMIT")
+                .optional(),
+            one_of(
+                "order",
+                &[pure_token("asc", "ASC"), pure_token("desc", "DESC")],
+            )
+            .optional(),
+            pure_token("sorting", "ALPHA").optional(),
+        ],
+    ),
+    (
+        b"SPOP",
+        "Returns one or more random members from a set after removing them. Deletes the set if \
+         the last member was popped.",
+        "set",
+        KEY_COUNT,
+    ),
+    (
+        b"SRANDMEMBER",
+        "Gets one or multiple random members from a set.",
+        "set",
+        KEY_COUNT,
+    ),
+    (
+        b"SREM",
+        "Removes one or more members from a set. Deletes the set if the last member was removed.",
+        "set",
+        KEY_MEMBERS,
+    ),
+    (b"SSCAN", "Iterates over members of a set.", "set", KEY_SCAN),
+    (
+        b"STRLEN",
+        "Returns the length of a string value.",
+        "string",
+        KEY,
+    ),
+    (
+        b"SUBSCRIBE",
+        "Listens for messages published to channels.",
+        "pubsub",
+        &[string("channel").multiple()],
+    ),
+    (
+        b"SUBSTR",
+        "Returns a substring from a string value.",
+        "string",
+        KEY_RANGE,
+    ),
+    (
+        b"SYNC",
+        "An internal command used in replication.",
+        "server",
+        NONE,
+    ),
+    (
+        b"TTL",
+        "Returns the expiration time in seconds of a key.",
+        "generic",
+        KEY,
+    ),
+    (
+        b"TYPE",
+        "Determines the type of value stored at a key.",
+        "generic",
+        KEY,
+    ),
+    (
+        b"UNSUBSCRIBE",
+        "Stops listening to messages posted to channels.",
+        "pubsub",
+        &[string("channel").optional().multiple()],
+    ),
+    (
+        b"WAITAOF",
+        "Blocks until all of the preceding write commands sent by the connection are written \
+         to the append-only file of the master and/or replicas.",
+        "generic",
+        &[
+            integer("numlocal"),
+            integer("numreplicas"),
+            integer("timeout"),
+        ],
+    ),
+    (
+        b"WAITKEY",
+        "Returns the string value of a key, waiting for it to be set when it doesn't exist.",
+        "string",
+        &[key("key"), double("timeout")],
+    ),
+    (
+        b"ZADD",
+        "Adds one or more members to a sorted set, or updates their scores. Creates the key if \
+         it doesn't exist.",
+        "sorted-set",
+        &[
+            key("key"),
+            one_of(
+                "condition",
+                &[pure_token("nx", "NX"), pure_token("xx", "XX")],
+            )
+            .optional(),
+            one_of(
+                "comparison",
+                &[pure_token("gt", "GT"), pure_token("lt", "LT")],
+            )
+            .optional(),
+            pure_token("change", "CH").optional(),
+            pure_token("increment", "INCR").optional(),
+            block("data", &[double("score"), string("member")]).multiple(),
+        ],
+    ),
+    (
+        b"ZCOUNT",
+        "Returns the count of members in a sorted set that have scores within a range.",
+        "sorted-set",
+        &[key("key"), double("min"), double("max")],
+    ),
+    (
+        b"ZDIFF",
+        "Returns the difference between multiple sorted sets.",
+        "sorted-set",
+        &[
+            integer("numkeys"),
+            key("key").multiple(),
+            pure_token("withscores", "WITHSCORES").optional(),
+        ],
+    ),
+    (
+        b"ZINCRBY",
+        "Increments the score of a member in a sorted set.",
+        "sorted-set",
+        &[key("key"), double("increment"), string("member")],
+    ),
+    (
+        b"ZINTER",
+        "Returns the intersect of multiple sorted sets.",
+        "sorted-set",
+        &[
+            integer("numkeys"),
+            key("key").multiple(),
+            integer("weight")
+                .with_token("WEIGHTS")
+                .multiple()
+                .optional(),
+            one_of(
+                "aggregate",
+                &[
+                    pure_token("sum", "SUM"),
+                    pure_token("min", "MIN"),
+                    pure_token("max", "MAX"),
+                ],
+            )
+            .with_token("AGGREGATE")
+            .optional(),
+            pure_token("withscores", "WITHSCORES").optional(),
+        ],
+    ),
+    (
+        b"ZMSCORE",
+        "Returns the score of one or more members in a sorted set.",
+        "sorted-set",
+        KEY_MEMBERS,
+    ),
+    (
+        b"ZPOPMAX",
+        "Returns the highest-scoring members from a sorted set after removing them. Deletes \
+         the sorted set if the last member was popped.",
+        "sorted-set",
+        KEY_COUNT,
+    ),
+    (
+        b"ZPOPMIN",
+        "Returns the lowest-scoring members from a sorted set after removing them. Deletes the \
+         sorted set if the last member was popped.",
+        "sorted-set",
+        KEY_COUNT,
+    ),
+    (
+        b"ZRANDMEMBER",
+        "Returns one or more random members from a sorted set.",
+        "sorted-set",
+        &[
+            key("key"),
+            block(
+                "options",
+                &[
+                    integer("count"),
+                    pure_token("withscores", "WITHSCORES").optional(),
+                ],
+            )
+            .optional(),
+        ],
+    ),
+    (
+        b"ZRANGEBYLEX",
+        "Returns members in a sorted set within a lexicographical range.",
+        "sorted-set",
+        &[
+            key("key"),
+            string("min"),
+            string("max"),
+            block("limit", &[integer("offset"), integer("count")])
+                .with_token("LIMIT")
+                .optional(),
+        ],
+    ),
+    (
+        b"ZRANGESTORE",
+        "Stores a range of members from sorted set in a key.",
+        "sorted-set",
+        &[
+            key("dst"),
+            key("src"),
+            string("min"),
+            string("max"),
+            one_of(
+                "sortby",
+                &[
+                    pure_token("byscore", "BYSCORE"),
+                    pure_token("bylex", "BYLEX"),
+                ],
+            )
+            .optional(),
+            pure_token("rev", "REV").optional(),
+            block("limit", &[integer("offset"), integer("count")])
+                .with_token("LIMIT")
+                .optional(),
+        ],
+    ),
+    (
+        b"ZSCAN",
+        "Iterates over members and scores of a sorted set.",
+        "sorted-set",
+        KEY_SCAN,
+    ),
+    (
+        b"ZSCORE",
+        "Returns the score of a member in a sorted set.",
+        "sorted-set",
+        &[key("key"), string("member")],
+    ),
+    (
+        b"ZUNION",
+        "Returns the union of multiple sorted sets.",
+        "sorted-set",
+        &[
+            integer("numkeys"),
+            key("key").multiple(),
+            integer("weight")
+                .with_token("WEIGHTS")
+                .multiple()
+                .optional(),
+            one_of(
+                "aggregate",
+                &[
+                    pure_token("sum", "SUM"),
+                    pure_token("min", "MIN"),
+                    pure_token("max", "MAX"),
+                ],
+            )
+            .with_token("AGGREGATE")
+            .optional(),
+            pure_token("withscores", "WITHSCORES").optional(),
+        ],
+    ),
+];
+
+/// Documentation of a command by `COMMAND DOCS`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandDoc {
+    // lowercase, as Redis reports it
+    pub name: String,
+    pub summary: &'static str,
+    pub group: &'static str,
+    pub arguments: &'static [Argument],
+}
+
+/// Documentation of command, `None` when it is unknown
+pub fn command_doc(command: &[u8]) -> Option<CommandDoc> {
+    DOCS.iter()
+        .find(|(name, _, _, _)| name.eq_ignore_ascii_case(command))
+        .map(document)
+}
+
+/// Documentation of every command, sorted by name
+pub fn command_docs() -> Vec<CommandDoc> {
+    DOCS.iter().map(document).collect()
+}
+
+fn document(&(name, summary, group, arguments): &Doc) -> CommandDoc {
+    CommandDoc {
+        name: String::from_utf8_lossy(name).to_lowercase(),
+        summary,
+        group,
+        arguments,
+    }
+}
@@ -0,0 +1,136 @@
+//! Help of the container commands, replied to `<command> HELP` in the format of Redis: a
+//! usage line, then each subcommand followed by its indented description.
+
+use super::command_error::RedisCommandError;
+
+// usage and description of each subcommand of a container command
+type Subcommands = &'static [(&'static str, &'static str)];
+
+const CLIENT: Subcommands = &[
+    ("ID", "Return the ID of the current connection."),
+    (
+        "KILL [ID <client-id>] [ADDR <ip:port>]",
+        "Kill the connections matching all the filters, return the number killed.",
+    ),
+    (
+        "REDISLESS-COMPRESS (<threshold>|OFF)",
+        "Compress the bulk strings replied from <threshold> bytes, as RESP3 verbatim strings of the rlz format.",
+    ),
+    (
+        "TRACKING (ON|OFF)",
+        "Control server assisted client side caching.",
+    ),
+];
+
+const CONFIG: Subcommands = &[
+    (
+        "GET <pattern>",
+        "Return parameters matching the glob-like <pattern> and their values.",
+    ),
+    (
+        "SET <directive> <value> [<directive> <value> ...]",
+        "Set the configuration <directive> to <value>.",
+    ),
+];
+
+const DEBUG: Subcommands = &[
+    (
+        "INTERNAL_STATS",
+        "Return the internal counters of the server, when debug-internal-stats is enabled.",
+    ),
+    (
+        "OBJECT <key>",
+        "Show low level info about the <key> and its value.",
+    ),
+    (
+        "RELOAD",
+        "Save the RDB on disk and reload it back to memory.",
+    ),
+    (
+        "SET-ACTIVE-EXPIRE <0|1>",
+        "Setting it to 0 disables expiring keys in the background when they are not accessed.",
+    ),
+    (
+        "SLEEP <seconds>",
+        "Stop the server for <seconds>. Decimals allowed.",
+    ),
+    (
+        "STRINGMATCH-LEN <pattern> <string>",
+        "Return 1 if the glob-style <pattern> matches the <string>, 0 otherwise.",
+    ),
+];
+
+const LATENCY: Subcommands = &[
+    (
+        "HISTORY <event>",
+        "Return time-latency samples for the <event> class.",
+    ),
+    (
+        "LATEST",
+        "Return the latest latency samples for all events.",
+    ),
+    (
+        "RESET [<event> ...]",
+        "Reset latency data of one or more <event> classes, all the events when none is given.",
+    ),
+];
+
+const MEMORY: Subcommands = &[
+    ("DOCTOR", "Return memory problems reports."),
+    (
+        "STATS",
+        "Return information about the memory usage of the server.",
+    ),
+    (
+        "USAGE <key> [SAMPLES <count>]",
+        "Return memory in bytes used by <key> and its value.",
+    ),
+];
+
+const OBJECT: Subcommands = &[
+    (
+        "ENCODING <key>",
+        "Return the kind of internal representation used to store the value of the <key>.",
+    ),
+    (
+        "FREQ <key>",
+        "Return the access frequency index of the <key>, with an LFU maxmemory-policy.",
+    ),
+    (
+        "IDLETIME <key>",
+        "Return the idle time of the <key>, in seconds.",
+    ),
+    (
+        "REFCOUNT <key>",
+        "Return the number of references of the value of the <key>.",
+    ),
+];
+
+/// Lines of the help of command, given by its uppercase name
+pub fn help(command: &str) -> Vec<String> {
+    let subcommands = match command {
+        "CLIENT" => CLIENT,
+        "CONFIG" => CONFIG,
+        "DEBUG" => DEBUG,
+        "LATENCY" => LATENCY,
+        "MEMORY" => MEMORY,
+        "OBJECT" => OBJECT,
+        _ => &[],
+    };
+
+    let mut lines = vec![format!(
+        "{} <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+        command
+    )];
+    for (usage, description) in subcommands.iter().chain(&[("HELP", "Print this help.")]) {
+        lines.push(usage.to_string());
+        lines.push(format!("    {}", description));
+    }
+    lines
+}
+
+/// Error of a subcommand of command which does not exist, or with the wrong number of
+/// arguments
+pub fn unknown_subcommand(subcommand: &[u8], command: &'static str) -> RedisCommandError {
+    RedisCommandError::UnknownSubcommand(String::from_utf8_lossy(subcommand).to_string(), command)
+}
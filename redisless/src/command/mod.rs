@@ -1,14 +1,32 @@
 #[cfg(test)]
 mod tests;
 
+mod bound;
 pub mod command_error;
+mod debug;
+mod docs;
+mod help;
+pub mod options;
+mod rename;
+mod table;
 mod util;
 
 use std::collections::HashSet;
+use std::convert::TryFrom;
 
 use crate::protocol::Resp;
-use crate::storage::models::Expiry;
+use crate::storage::models::{Expiry, LexBound, ScoreBound};
 use command_error::RedisCommandError;
+pub use docs::{command_doc, command_docs, Argument, CommandDoc};
+pub use help::help;
+pub use rename::resolve_renamed;
+pub use table::{command_spec, command_specs, denies_oom, is_write, key_positions, CommandSpec};
+
+use options::{
+    AclRule, ExpireCondition, FlushMode, GetExOption, HelloOptions, KillFilter, LPosOptions,
+    LcsOptions, RestoreOptions, ScanOptions, SetOptions, ShutdownMode, SortOptions, ZAddOptions,
+    ZCombineOptions, ZRangeOptions,
+};
 
 use super::storage::models::RedisString;
 
@@ -18,65 +36,314 @@ type Items = Vec<(Key, Value)>;
 type Keys = Vec<Key>;
 type Values = Vec<Value>;
 type SetValues = HashSet<Value>;
+type ScoreMembers = Vec<(f64, Value)>;
 
 #[derive(Debug, PartialEq)]
 pub enum Command {
     Append(Key, Value),
-    Set(Key, Value),
+    StrLen(Key),
+    Set(Key, Value, SetOptions),
     Setnx(Key, Value),
-    Setex(Key, Expiry, Value),
-    PSetex(Key, Expiry, Value),
     MSet(Items),
     MSetnx(Items),
     Expire(Key, Expiry),
     PExpire(Key, Expiry),
     Get(Key),
     GetSet(Key, Value),
+    GetDel(Key),
+    // the expiry is left as it is without an option
+    GetEx(Key, Option<GetExOption>),
+    // `CAS key expected new`, not a Redis command: set key to new only while it holds
+    // expected, in a single step
+    CompareAndSet(Key, Value, Value),
+    GetRange(Key, i64, i64),
+    // key, offset, and the value written from it
+    SetRange(Key, usize, Value),
+    // the two keys, and the options of the reply
+    Lcs(Key, Key, LcsOptions),
+    // key, bit, start byte, end byte
+    BitPos(Key, u8, Option<i64>, Option<i64>),
+    // operation, destination key, source keys
+    BitOp(BitOperation, Key, Keys),
     MGet(Keys),
     HSet(Key, Items),
     HGet(Key, Key),
+    HGetAll(Key),
+    HKeys(Key),
+    HVals(Key),
+    // key, and the count with the WITHVALUES flag
+    HRandField(Key, Option<(i64, bool)>),
+    // key, the expiry set, its condition and the fields
+    HExpire(Key, Expiry, Option<ExpireCondition>, Keys),
+    HPExpire(Key, Expiry, Option<ExpireCondition>, Keys),
+    HTtl(Key, Keys),
+    HPersist(Key, Keys),
     RPush(Key, Values),
     LPush(Key, Values),
     LLen(Key),
     RPushx(Key, Values),
     LPushx(Key, Values),
-    RPop(Key),
-    LPop(Key),
+    // key, and the count of elements popped, replied as an array when given
+    RPop(Key, Option<u64>),
+    LPop(Key, Option<u64>),
     LIndex(Key, i64),
+    LPos(Key, Value, LPosOptions),
     LSet(Key, i64, Value),
-    LInsert(Key, RedisString, RedisString, Value),
+    // true to insert before the pivot
+    LInsert(Key, bool, Value, Value),
     LTrim(Key, i64, i64),
     LRem(Key, i64, Value),
     RPopLPush(Key, Key),
+    // source, destination, and the ends of the lists the element is moved from and to
+    LMove(Key, Key, Side, Side),
+    // keys, and the timeout in secs, 0 to block forever
+    BLPop(Keys, f64),
+    BRPop(Keys, f64),
+    // `WAITKEY key timeout`, not a command of Redis: the value of the string at key, waiting
+    // for up to timeout secs (0 to wait forever) for a write to set it when it is missing
+    WaitKey(Key, f64),
+    // `LMOVE`, with the timeout in secs of the wait for an element, 0 to block forever
+    BLMove(Key, Key, Side, Side, f64),
+    // source, destination and the timeout of `BLMOVE source destination RIGHT LEFT`
+    BRPopLPush(Key, Key, f64),
     SAdd(Key, SetValues),
     SCard(Key),
+    // keys, and the limit of the count, 0 for no limit
+    SInterCard(Keys, usize),
+    // key, and the members to look up in order
+    SMISMember(Key, Values),
     SRem(Key, SetValues),
+    // key, and the number of members to pop, replied as an array when it is set
+    SPop(Key, Option<u64>),
+    // key, and the count: distinct members when positive, possibly repeated when negative
+    SRandMember(Key, Option<i64>),
+    // key, options, score / member pairs
+    ZAdd(Key, ZAddOptions, ScoreMembers),
+    // key, increment, member
+    ZIncrBy(Key, f64, Value),
+    // key, and the number of members to pop, replied as a flat array when it is set
+    ZPopMin(Key, Option<u64>),
+    ZPopMax(Key, Option<u64>),
+    ZCount(Key, ScoreBound, ScoreBound),
+    // key, and the members whose scores are looked up in order
+    ZMScore(Key, Values),
+    // key and member
+    ZScore(Key, Value),
+    // keys, and how the scores of their members are combined
+    ZUnion(Keys, ZCombineOptions),
+    ZInter(Keys, ZCombineOptions),
+    // keys, and whether the scores are replied along with the members
+    ZDiff(Keys, bool),
+    // key, min, max, and the offset and count of the optional limit
+    ZRangeByLex(Key, LexBound, LexBound, Option<(i64, i64)>),
+    // key, and the count of the members with whether their scores are replied
+    ZRandMember(Key, Option<(i64, bool)>),
+    // destination and source
+    ZRangeStore(Key, Key, ZRangeOptions),
     Del(Key),
+    // key and the index of the db to move it to
+    Move(Key, u64),
+    // source, destination, the index of the db of the destination when it is not the one of
+    // the client, and whether an existing destination is replaced
+    Copy(Key, Key, Option<u64>, bool),
+    Dump(Key),
+    // key, ttl in millis, 0 for none, and the payload of a `DUMP`
+    Restore(Key, u64, Value, RestoreOptions),
     Incr(Key),
     IncrBy(Key, i64),
     Exists(Key),
+    // glob-style pattern
+    Keys(Value),
+    Scan(u64, ScanOptions),
+    HScan(Key, u64, ScanOptions),
+    SScan(Key, u64, ScanOptions),
+    ZScan(Key, u64, ScanOptions),
     Type(Key),
     Ttl(Key),
     Pttl(Key),
-    Info,
+    Object(ObjectSubcommand),
+    Memory(MemorySubcommand),
+    Debug(DebugSubcommand),
+    Client(ClientSubcommand),
+    Config(ConfigSubcommand),
+    Acl(AclSubcommand),
+    Latency(LatencySubcommand),
+    // `HELP` of the container command with this uppercase name, such as `OBJECT`
+    Help(&'static str),
+    Sort(Key, SortOptions),
+    Subscribe(Vec<RedisString>),
+    PSubscribe(Vec<RedisString>),
+    // from every channel, or pattern, when empty
+    Unsubscribe(Vec<RedisString>),
+    PUnsubscribe(Vec<RedisString>),
+    // channel and message
+    Publish(RedisString, RedisString),
+    PubSub(PubSubSubcommand),
+    // `COMMAND GETKEYS`, the full command to extract the keys of
+    GetKeys(Vec<RedisString>),
+    // `COMMAND INFO`, the names of the commands to describe, every command when empty
+    CommandInfo(Vec<RedisString>),
+    // `COMMAND DOCS`, the names of the commands to document, every command when empty
+    CommandDocs(Vec<RedisString>),
+    Save,
+    BgSave,
+    BgRewriteAof,
+    Shutdown(ShutdownMode),
+    // numlocal, numreplicas, and the timeout in millis, 0 to block forever
+    WaitAof(u64, u64, u64),
+    LastSave,
+    // lowercase names of the sections, the default ones when empty
+    Info(Vec<String>),
+    Lolwut,
     Ping,
+    // username, `default` when not given, and password
+    Auth(Option<String>, String),
+    Hello(HelloOptions),
     Quit,
+    // start a transaction, the next commands are queued until `EXEC`
+    Multi,
+    Exec,
+    Discard,
+    // feed every command of the other clients to this one
+    Monitor,
     Dbsize,
+    RandomKey,
+    Select(usize),
+    // follow the primary at host:port
+    ReplicaOf(String, u16),
+    // stop replicating and accept writes again
+    ReplicaOfNoOne,
+    Sync,
+    PSync,
+    Role,
+    FlushDb(FlushMode),
+    FlushAll(FlushMode),
+}
+
+/// End of a list, `LEFT` or `RIGHT`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BitOperation {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ObjectSubcommand {
+    Encoding(Key),
+    RefCount(Key),
+    IdleTime(Key),
+    Freq(Key),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MemorySubcommand {
+    Usage(Key),
+    Stats,
+    Doctor,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ClientSubcommand {
+    // `ON` or `OFF`
+    Tracking(bool),
+    Id,
+    Kill(KillFilter),
+    // length from which the bulk strings replied are compressed, `None` for `OFF`
+    Compress(Option<usize>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigSubcommand {
+    // glob-style pattern of the parameters
+    Get(Vec<u8>),
+    // lowercase parameter names and their values, all set or none
+    Set(Vec<(String, String)>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AclSubcommand {
+    // username and its rules
+    SetUser(String, Vec<AclRule>),
+    GetUser(String),
+    List,
+    WhoAmI,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LatencySubcommand {
+    Latest,
+    // name of the event
+    History(String),
+    // names of the events, every event when empty
+    Reset(Vec<String>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DebugSubcommand {
+    Object(Key),
+    // save a snapshot and replace the databases by its content
+    Reload,
+    // whether the expired keys are removed in the background
+    SetActiveExpire(bool),
+    // block the server for this many secs
+    Sleep(f64),
+    // glob-style pattern and string, whether the pattern matches the string
+    StringMatch(Value, Value),
+    // counters of the internals of the server, for the white-box tests
+    InternalStats,
+    // a subcommand of Redis accepted without doing anything
+    NoOp,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PubSubSubcommand {
+    // channels with a subscriber, matching the glob-style pattern when set
+    Channels(Option<Value>),
+    NumSub(Vec<RedisString>),
+    NumPat,
 }
 
 impl Command {
     pub fn parse(v: Vec<Resp>) -> Result<Self, RedisCommandError> {
+        // a missing argument is reported with the name of the command, as a request which
+        // does not match the arity of the table is
+        let name = match v.first() {
+            Some(Resp::BulkString(name)) => Some(String::from_utf8_lossy(name).to_lowercase()),
+            _ => None,
+        };
+        Command::parse_args(v).map_err(|err| match (err, name) {
+            (RedisCommandError::ArgNumber, Some(name)) => RedisCommandError::WrongArity(name),
+            (err, _) => err,
+        })
+    }
+
+    fn parse_args(v: Vec<Resp>) -> Result<Self, RedisCommandError> {
+        use bound::{parse_lex_bound, parse_score_bound};
+        use help::unknown_subcommand;
         use util::*;
         use Command::*;
         use RedisCommandError::*;
 
+        if let Some(Resp::BulkString(command)) = v.first() {
+            table::check_arity(command, v.len())?;
+        }
+
         match v.first() {
             Some(Resp::BulkString(command)) => match *command {
                 b"SET" | b"set" | b"Set" => {
                     let key = get_bytes_vec(v.get(1))?;
                     let value = get_bytes_vec(v.get(2))?;
+                    let options = parse_set_options(&v[3..])?;
 
-                    Ok(Set(key, value))
+                    Ok(Set(key, value, options))
                 }
                 b"APPEND" | b"append" | b"Append" => {
                     let key = get_bytes_vec(v.get(1))?;
@@ -84,21 +351,26 @@ impl Command {
 
                     Ok(Append(key, value))
                 }
-                b"SETEX" | b"setex" | b"SetEx" | b"Setex" => {
+                b"STRLEN" | b"strlen" | b"StrLen" | b"Strlen" => {
                     let key = get_bytes_vec(v.get(1))?;
-                    let duration = get_bytes_vec(v.get(2)).and_then(parse_duration)?;
-                    let value = get_bytes_vec(v.get(3))?;
-                    let expiry = Expiry::new_from_secs(duration)?;
-
-                    Ok(Setex(key, expiry, value))
+                    Ok(StrLen(key))
                 }
-                b"PSETEX" | b"psetex" | b"PSetEx" | b"PSetex" => {
+                // `SET key value EX seconds` and `SET key value PX milliseconds`, so the three
+                // can't set a key differently
+                b"SETEX" | b"setex" | b"SetEx" | b"Setex" | b"PSETEX" | b"psetex" | b"PSetEx"
+                | b"PSetex" => {
                     let key = get_bytes_vec(v.get(1))?;
-                    let duration = get_bytes_vec(v.get(2)).and_then(parse_duration)?;
+                    let millis = command.eq_ignore_ascii_case(b"PSETEX");
+                    let name = String::from_utf8_lossy(command).to_lowercase();
+                    let expiry = get_bytes_vec(v.get(2))
+                        .and_then(|duration| parse_set_expiry(duration, millis, &name))?;
                     let value = get_bytes_vec(v.get(3))?;
-                    let expiry = Expiry::new_from_millis(duration)?;
+                    let options = SetOptions {
+                        expiry: Some(expiry),
+                        ..Default::default()
+                    };
 
-                    Ok(PSetex(key, expiry, value))
+                    Ok(Set(key, value, options))
                 }
                 b"MSET" | b"MSet" | b"mset" => {
                     // Will not panic with out of bounds, because request has at least length 1,
@@ -107,8 +379,9 @@ impl Command {
                     // We want [(key, value), (key, value), (key, value), ..]
                     let pairs = &v[1..];
                     let chunk_size = 2_usize;
-                    if pairs.is_empty() || pairs.len() % chunk_size != 0 {
-                        return Err(ArgNumber);
+                    // rejected before any write, as Redis does
+                    if pairs.is_empty() || !pairs.len().is_multiple_of(chunk_size) {
+                        return Err(WrongArity("mset".to_string()));
                     }
 
                     let mut items = Vec::<(Key, Value)>::with_capacity(pairs.len());
@@ -128,8 +401,8 @@ impl Command {
                     let pairs = &v[1..];
 
                     let chunk_size = 2_usize;
-                    if pairs.is_empty() || pairs.len() % chunk_size != 0 {
-                        return Err(ArgNumber);
+                    if pairs.is_empty() || !pairs.len().is_multiple_of(chunk_size) {
+                        return Err(WrongArity("msetnx".to_string()));
                     }
 
                     let mut items = Items::with_capacity(pairs.len());
@@ -152,17 +425,26 @@ impl Command {
 
                     Ok(Setnx(key, value))
                 }
+                b"CAS" | b"cas" | b"Cas" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let expected = get_bytes_vec(v.get(2))?;
+                    let new = get_bytes_vec(v.get(3))?;
+
+                    Ok(CompareAndSet(key, expected, new))
+                }
                 b"EXPIRE" | b"expire" | b"Expire" => {
                     let key = get_bytes_vec(v.get(1))?;
-                    let duration = get_bytes_vec(v.get(2)).and_then(parse_duration)?;
-                    let expiry = Expiry::new_from_secs(duration)?;
+                    let duration = get_bytes_vec(v.get(2)).and_then(parse_variation)?;
+                    // a ttl which is not positive is an expiry already past
+                    let expiry = Expiry::new_from_secs(duration.max(0) as u64)?;
 
                     Ok(Expire(key, expiry))
                 }
                 b"PEXPIRE" | b"Pexpire" | b"PExpire" | b"pexpire" => {
                     let key = get_bytes_vec(v.get(1))?;
-                    let duration = get_bytes_vec(v.get(2)).and_then(parse_duration)?;
-                    let expiry = Expiry::new_from_millis(duration)?;
+                    let duration = get_bytes_vec(v.get(2)).and_then(parse_variation)?;
+                    // a ttl which is not positive is an expiry already past
+                    let expiry = Expiry::new_from_millis(duration.max(0) as u64)?;
 
                     Ok(PExpire(key, expiry))
                 }
@@ -170,19 +452,94 @@ impl Command {
                     let key = get_bytes_vec(v.get(1))?;
                     Ok(Get(key))
                 }
+                // SUBSTR is the legacy name of GETRANGE
+                b"GETRANGE" | b"getrange" | b"GetRange" | b"Getrange" | b"SUBSTR" | b"substr"
+                | b"Substr" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let start = get_bytes_vec(v.get(2)).and_then(parse_variation)?;
+                    let end = get_bytes_vec(v.get(3)).and_then(parse_variation)?;
+
+                    Ok(GetRange(key, start, end))
+                }
+                b"LCS" | b"lcs" | b"Lcs" => {
+                    let first = get_bytes_vec(v.get(1))?;
+                    let second = get_bytes_vec(v.get(2))?;
+                    let options = parse_lcs_options(&v[3..])?;
+
+                    Ok(Lcs(first, second, options))
+                }
+                b"SETRANGE" | b"setrange" | b"SetRange" | b"Setrange" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let offset = get_bytes_vec(v.get(2)).and_then(parse_variation)?;
+                    if offset < 0 {
+                        return Err(OffsetOutOfRange);
+                    }
+                    let value = get_bytes_vec(v.get(3))?;
+
+                    Ok(SetRange(key, offset as usize, value))
+                }
+                b"BITPOS" | b"bitpos" | b"BitPos" | b"Bitpos" => {
+                    if v.len() > 5 {
+                        return Err(SyntaxErr);
+                    }
+                    let key = get_bytes_vec(v.get(1))?;
+                    let bit = match get_bytes_vec(v.get(2))?.as_slice() {
+                        b"0" => 0,
+                        b"1" => 1,
+                        _ => return Err(InvalidBit),
+                    };
+                    let start = match v.get(3) {
+                        Some(start) => Some(get_bytes_vec(Some(start)).and_then(parse_variation)?),
+                        None => None,
+                    };
+                    let end = match v.get(4) {
+                        Some(end) => Some(get_bytes_vec(Some(end)).and_then(parse_variation)?),
+                        None => None,
+                    };
+
+                    Ok(BitPos(key, bit, start, end))
+                }
+                b"BITOP" | b"bitop" | b"BitOp" | b"Bitop" => {
+                    let operation = match get_bytes_vec(v.get(1))?.to_ascii_uppercase().as_slice() {
+                        b"AND" => BitOperation::And,
+                        b"OR" => BitOperation::Or,
+                        b"XOR" => BitOperation::Xor,
+                        b"NOT" => BitOperation::Not,
+                        _ => return Err(SyntaxErr),
+                    };
+                    let dest = get_bytes_vec(v.get(2))?;
+                    let sources = v[3..]
+                        .iter()
+                        .map(|key| get_bytes_vec(Some(key)))
+                        .collect::<Result<Vec<Key>, _>>()?;
+                    if operation == BitOperation::Not && sources.len() != 1 {
+                        return Err(BitOpNotSingleSource);
+                    }
+
+                    Ok(BitOp(operation, dest, sources))
+                }
                 b"GETSET" | b"getset" | b"Getset" | b"GetSet" => {
                     let key = get_bytes_vec(v.get(1))?;
                     let value = get_bytes_vec(v.get(2))?;
 
                     Ok(GetSet(key, value))
                 }
+                b"GETDEL" | b"getdel" | b"GetDel" | b"Getdel" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    Ok(GetDel(key))
+                }
+                b"GETEX" | b"getex" | b"GetEx" | b"Getex" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let option = parse_getex_option(&v[2..])?;
+                    Ok(GetEx(key, option))
+                }
                 b"MGET" | b"mget" | b"MGet" => {
                     let keys = &v[1..]; // will never panic
                     if keys.is_empty() {
                         return Err(ArgNumber);
                     }
 
-                    let mut keys_vec = Keys::with_capacity(keys.len());
+                    let mut keys_vec = Vec::<Key>::with_capacity(keys.len());
                     for key in keys {
                         let key = get_bytes_vec(Some(key))?;
                         keys_vec.push(key);
@@ -195,7 +552,7 @@ impl Command {
                     let pairs = &v[2..];
 
                     let chunk_size = 2_usize;
-                    if pairs.is_empty() || pairs.len() % chunk_size != 0 {
+                    if pairs.is_empty() || !pairs.len().is_multiple_of(chunk_size) {
                         return Err(ArgNumber);
                     }
 
@@ -212,6 +569,11 @@ impl Command {
                     }
                     Ok(HSet(hash_key, items))
                 }
+                b"HRANDFIELD" | b"hrandfield" | b"HRandField" | b"Hrandfield" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let count = parse_random_count(&v[2..], Some(b"WITHVALUES"))?;
+                    Ok(HRandField(key, count))
+                }
                 b"HGET" | b"hget" => {
                     //HGet(Key, Key),
                     let hash_key = get_bytes_vec(v.get(1))?;
@@ -219,6 +581,35 @@ impl Command {
 
                     Ok(HGet(hash_key, field_key))
                 }
+                b"HGETALL" | b"hgetall" | b"HGetAll" | b"Hgetall" => {
+                    Ok(HGetAll(get_bytes_vec(v.get(1))?))
+                }
+                b"HEXPIRE" | b"hexpire" | b"HExpire" | b"Hexpire" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let duration = get_bytes_vec(v.get(2)).and_then(parse_duration)?;
+                    let expiry = Expiry::new_from_secs(duration)?;
+                    let (condition, fields) = parse_hexpire_args(&v[3..])?;
+
+                    Ok(HExpire(key, expiry, condition, fields))
+                }
+                b"HPEXPIRE" | b"hpexpire" | b"HPExpire" | b"Hpexpire" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let duration = get_bytes_vec(v.get(2)).and_then(parse_duration)?;
+                    let expiry = Expiry::new_from_millis(duration)?;
+                    let (condition, fields) = parse_hexpire_args(&v[3..])?;
+
+                    Ok(HPExpire(key, expiry, condition, fields))
+                }
+                b"HTTL" | b"httl" | b"HTtl" | b"Httl" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    Ok(HTtl(key, parse_hash_fields(&v[2..])?))
+                }
+                b"HPERSIST" | b"hpersist" | b"HPersist" | b"Hpersist" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    Ok(HPersist(key, parse_hash_fields(&v[2..])?))
+                }
+                b"HKEYS" | b"hkeys" | b"HKeys" | b"Hkeys" => Ok(HKeys(get_bytes_vec(v.get(1))?)),
+                b"HVALS" | b"hvals" | b"HVals" | b"Hvals" => Ok(HVals(get_bytes_vec(v.get(1))?)),
                 b"RPUSH" | b"RPush" | b"Rpush" | b"rpush" => {
                     let key = get_bytes_vec(v.get(1))?;
                     let values = &v[2..];
@@ -271,11 +662,18 @@ impl Command {
                 }
                 b"RPOP" | b"RPop" | b"Rpop" | b"rpop" => {
                     let key = get_bytes_vec(v.get(1))?;
-                    Ok(RPop(key))
+                    Ok(RPop(key, parse_pop_count(&v[2..])?))
                 }
                 b"LPOP" | b"LPop" | b"Lpop" | b"lpop" => {
                     let key = get_bytes_vec(v.get(1))?;
-                    Ok(LPop(key))
+                    Ok(LPop(key, parse_pop_count(&v[2..])?))
+                }
+                b"LPOS" | b"LPos" | b"Lpos" | b"lpos" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let element = get_bytes_vec(v.get(2))?;
+                    let options = parse_lpos_options(&v[3..])?;
+
+                    Ok(LPos(key, element, options))
                 }
                 b"LINDEX" | b"LIndex" | b"Lindex" | b"lindex" => {
                     let key = get_bytes_vec(v.get(1))?;
@@ -291,9 +689,14 @@ impl Command {
                 b"LINSERT" | b"LInsert" | b"Linsert" | b"linsert" => {
                     let key = get_bytes_vec(v.get(1))?;
                     let place = get_bytes_vec(v.get(2))?;
+                    let before = match place.to_ascii_uppercase().as_slice() {
+                        b"BEFORE" => true,
+                        b"AFTER" => false,
+                        _ => return Err(SyntaxErr),
+                    };
                     let pivot = get_bytes_vec(v.get(3))?;
                     let value = get_bytes_vec(v.get(4))?;
-                    Ok(LInsert(key, place, pivot, value))
+                    Ok(LInsert(key, before, pivot, value))
                 }
                 b"LTRIM" | b"LTrim" | b"Ltrim" | b"ltrim" => {
                     let key = get_bytes_vec(v.get(1))?;
@@ -307,6 +710,40 @@ impl Command {
                     let value = get_bytes_vec(v.get(3))?;
                     Ok(LRem(key, count, value))
                 }
+                b"BLPOP" | b"BLPop" | b"Blpop" | b"blpop" => {
+                    let (keys, timeout) = parse_blocking_args(&v[1..])?;
+                    Ok(BLPop(keys, timeout))
+                }
+                b"BRPOP" | b"BRPop" | b"Brpop" | b"brpop" => {
+                    let (keys, timeout) = parse_blocking_args(&v[1..])?;
+                    Ok(BRPop(keys, timeout))
+                }
+                b"WAITKEY" | b"WaitKey" | b"Waitkey" | b"waitkey" => {
+                    // the arity leaves a single key
+                    let (mut keys, timeout) = parse_blocking_args(&v[1..])?;
+                    Ok(WaitKey(keys.remove(0), timeout))
+                }
+                b"LMOVE" | b"LMove" | b"Lmove" | b"lmove" => {
+                    let src = get_bytes_vec(v.get(1))?;
+                    let dest = get_bytes_vec(v.get(2))?;
+                    let from = get_bytes_vec(v.get(3)).and_then(parse_side)?;
+                    let to = get_bytes_vec(v.get(4)).and_then(parse_side)?;
+                    Ok(LMove(src, dest, from, to))
+                }
+                b"BLMOVE" | b"BLMove" | b"Blmove" | b"blmove" => {
+                    let src = get_bytes_vec(v.get(1))?;
+                    let dest = get_bytes_vec(v.get(2))?;
+                    let from = get_bytes_vec(v.get(3)).and_then(parse_side)?;
+                    let to = get_bytes_vec(v.get(4)).and_then(parse_side)?;
+                    let timeout = get_bytes_vec(v.get(5)).and_then(parse_timeout)?;
+                    Ok(BLMove(src, dest, from, to, timeout))
+                }
+                b"BRPOPLPUSH" | b"BRPopLPush" | b"BrpopLpush" | b"brpoplpush" => {
+                    let src = get_bytes_vec(v.get(1))?;
+                    let dest = get_bytes_vec(v.get(2))?;
+                    let timeout = get_bytes_vec(v.get(3)).and_then(parse_timeout)?;
+                    Ok(BRPopLPush(src, dest, timeout))
+                }
                 b"RPOPLPUSH" | b"RPopLPush" | b"RpopLpush" | b"rpoplpush" => {
                     let src = get_bytes_vec(v.get(1))?;
                     let dest = get_bytes_vec(v.get(2))?;
@@ -323,10 +760,32 @@ impl Command {
                     }
                     Ok(SAdd(key, values_set))
                 }
+                b"SINTERCARD" | b"sintercard" | b"SInterCard" | b"Sintercard" => {
+                    let (keys, args) = parse_numkeys(&v[1..])?;
+                    let limit = match args {
+                        [] => 0,
+                        [option, limit]
+                            if get_bytes_vec(Some(option))?.eq_ignore_ascii_case(b"LIMIT") =>
+                        {
+                            get_bytes_vec(Some(limit)).and_then(parse_duration)? as usize
+                        }
+                        _ => return Err(SyntaxErr),
+                    };
+
+                    Ok(SInterCard(keys, limit))
+                }
                 b"SCARD" | b"SCard" | b"Scard" | b"scard" => {
                     let key = get_bytes_vec(v.get(1))?;
                     Ok(SCard(key))
                 }
+                b"SMISMEMBER" | b"smismember" | b"SMIsMember" | b"Smismember" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let members = v[2..]
+                        .iter()
+                        .map(|member| get_bytes_vec(Some(member)))
+                        .collect::<Result<Values, _>>()?;
+                    Ok(SMISMember(key, members))
+                }
                 b"SREM" | b"SRem" | b"Srem" | b"srem" => {
                     let key = get_bytes_vec(v.get(1))?;
                     let values = &v[2..];
@@ -338,6 +797,105 @@ impl Command {
                     }
                     Ok(SRem(key, values_set))
                 }
+                b"SPOP" | b"SPop" | b"Spop" | b"spop" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let count = match v.get(2) {
+                        Some(count) => {
+                            let count = get_bytes_vec(Some(count)).and_then(parse_variation)?;
+                            Some(u64::try_from(count).map_err(|_| ValueNotPositive)?)
+                        }
+                        None => None,
+                    };
+                    Ok(SPop(key, count))
+                }
+                b"SRANDMEMBER" | b"SRandMember" | b"Srandmember" | b"srandmember" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let count = parse_random_count(&v[2..], None)?;
+                    Ok(SRandMember(key, count.map(|(count, _)| count)))
+                }
+                b"ZADD" | b"zadd" | b"ZAdd" | b"Zadd" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let (options, members) = parse_zadd_args(&v[2..])?;
+
+                    Ok(ZAdd(key, options, members))
+                }
+                b"ZINCRBY" | b"zincrby" | b"ZIncrBy" | b"Zincrby" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let increment = get_bytes_vec(v.get(2)).and_then(parse_float)?;
+                    let member = get_bytes_vec(v.get(3))?;
+
+                    Ok(ZIncrBy(key, increment, member))
+                }
+                b"ZPOPMIN" | b"zpopmin" | b"ZPopMin" | b"Zpopmin" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    Ok(ZPopMin(key, parse_pop_count(&v[2..])?))
+                }
+                b"ZPOPMAX" | b"zpopmax" | b"ZPopMax" | b"Zpopmax" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    Ok(ZPopMax(key, parse_pop_count(&v[2..])?))
+                }
+                b"ZUNION" | b"zunion" | b"ZUnion" | b"Zunion" => {
+                    let (keys, args) = parse_numkeys(&v[1..])?;
+                    let options = parse_zcombine_options(args, keys.len())?;
+                    Ok(ZUnion(keys, options))
+                }
+                b"ZINTER" | b"zinter" | b"ZInter" | b"Zinter" => {
+                    let (keys, args) = parse_numkeys(&v[1..])?;
+                    let options = parse_zcombine_options(args, keys.len())?;
+                    Ok(ZInter(keys, options))
+                }
+                b"ZDIFF" | b"zdiff" | b"ZDiff" | b"Zdiff" => {
+                    let (keys, args) = parse_numkeys(&v[1..])?;
+                    let with_scores = match args {
+                        [] => false,
+                        [option]
+                            if get_bytes_vec(Some(option))?.eq_ignore_ascii_case(b"WITHSCORES") =>
+                        {
+                            true
+                        }
+                        _ => return Err(SyntaxErr),
+                    };
+                    Ok(ZDiff(keys, with_scores))
+                }
+                b"ZCOUNT" | b"zcount" | b"ZCount" | b"Zcount" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let min = get_bytes_vec(v.get(2)).and_then(parse_score_bound)?;
+                    let max = get_bytes_vec(v.get(3)).and_then(parse_score_bound)?;
+
+                    Ok(ZCount(key, min, max))
+                }
+                b"ZMSCORE" | b"zmscore" | b"ZMScore" | b"Zmscore" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let members = v[2..]
+                        .iter()
+                        .map(|member| get_bytes_vec(Some(member)))
+                        .collect::<Result<Values, _>>()?;
+                    Ok(ZMScore(key, members))
+                }
+                b"ZSCORE" | b"zscore" | b"ZScore" | b"Zscore" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let member = get_bytes_vec(v.get(2))?;
+                    Ok(ZScore(key, member))
+                }
+                b"ZRANDMEMBER" | b"zrandmember" | b"ZRandMember" | b"Zrandmember" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let count = parse_random_count(&v[2..], Some(b"WITHSCORES"))?;
+                    Ok(ZRandMember(key, count))
+                }
+                b"ZRANGESTORE" | b"zrangestore" | b"ZRangeStore" | b"Zrangestore" => {
+                    let destination = get_bytes_vec(v.get(1))?;
+                    let source = get_bytes_vec(v.get(2))?;
+                    let options = parse_zrange_options(&v[3..])?;
+                    Ok(ZRangeStore(destination, source, options))
+                }
+                b"ZRANGEBYLEX" | b"zrangebylex" | b"ZRangeByLex" | b"Zrangebylex" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let min = get_bytes_vec(v.get(2)).and_then(parse_lex_bound)?;
+                    let max = get_bytes_vec(v.get(3)).and_then(parse_lex_bound)?;
+                    let limit = parse_range_limit(&v[4..])?;
+
+                    Ok(ZRangeByLex(key, min, max, limit))
+                }
 
                 b"DEL" | b"del" | b"Del" => {
                     let key = get_bytes_vec(v.get(1))?;
@@ -359,12 +917,37 @@ impl Command {
                 b"DECRBY" | b"decrby" | b"DecrBy" => {
                     let key = get_bytes_vec(v.get(1))?;
                     let decrement = get_bytes_vec(v.get(2)).and_then(parse_variation)?;
-                    Ok(IncrBy(key, -decrement))
+                    // the i64 minimum has no opposite, as a result out of range it overflows
+                    let increment = decrement
+                        .checked_neg()
+                        .ok_or(RedisCommandError::IncrOverflow)?;
+                    Ok(IncrBy(key, increment))
                 }
                 b"EXISTS" | b"exists" | b"Exists" => {
                     let key = get_bytes_vec(v.get(1))?;
                     Ok(Exists(key))
                 }
+                b"KEYS" | b"keys" | b"Keys" => {
+                    let pattern = get_bytes_vec(v.get(1))?;
+                    Ok(Keys(pattern))
+                }
+                b"SCAN" | b"scan" | b"Scan" => {
+                    let cursor = get_bytes_vec(v.get(1)).and_then(parse_cursor)?;
+                    let options = parse_scan_options(&v[2..])?;
+                    Ok(Scan(cursor, options))
+                }
+                b"HSCAN" | b"hscan" | b"HScan" | b"Hscan" => {
+                    let (key, cursor, options) = parse_key_scan_args(&v[1..])?;
+                    Ok(HScan(key, cursor, options))
+                }
+                b"SSCAN" | b"sscan" | b"SScan" | b"Sscan" => {
+                    let (key, cursor, options) = parse_key_scan_args(&v[1..])?;
+                    Ok(SScan(key, cursor, options))
+                }
+                b"ZSCAN" | b"zscan" | b"ZScan" | b"Zscan" => {
+                    let (key, cursor, options) = parse_key_scan_args(&v[1..])?;
+                    Ok(ZScan(key, cursor, options))
+                }
                 b"TYPE" | b"type" | b"Type" => {
                     let key = get_bytes_vec(v.get(1))?;
                     Ok(Type(key))
@@ -377,15 +960,315 @@ impl Command {
                     let key = get_bytes_vec(v.get(1))?;
                     Ok(Pttl(key))
                 }
-                b"INFO" | b"info" | b"Info" => Ok(Info),
+                b"OBJECT" | b"object" | b"Object" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match (subcommand.to_ascii_uppercase().as_slice(), &v[2..]) {
+                        (b"ENCODING", [key]) => Ok(Object(ObjectSubcommand::Encoding(
+                            get_bytes_vec(Some(key))?,
+                        ))),
+                        (b"REFCOUNT", [key]) => Ok(Object(ObjectSubcommand::RefCount(
+                            get_bytes_vec(Some(key))?,
+                        ))),
+                        (b"IDLETIME", [key]) => Ok(Object(ObjectSubcommand::IdleTime(
+                            get_bytes_vec(Some(key))?,
+                        ))),
+                        (b"FREQ", [key]) => {
+                            Ok(Object(ObjectSubcommand::Freq(get_bytes_vec(Some(key))?)))
+                        }
+                        (b"HELP", []) => Ok(Help("OBJECT")),
+                        _ => Err(unknown_subcommand(&subcommand, "OBJECT")),
+                    }
+                }
+                b"SUBSCRIBE" | b"subscribe" | b"Subscribe" => Ok(Subscribe(get_args(&v[1..])?)),
+                b"PSUBSCRIBE" | b"psubscribe" | b"PSubscribe" | b"Psubscribe" => {
+                    Ok(PSubscribe(get_args(&v[1..])?))
+                }
+                b"UNSUBSCRIBE" | b"unsubscribe" | b"Unsubscribe" => {
+                    Ok(Unsubscribe(get_args(&v[1..])?))
+                }
+                b"PUNSUBSCRIBE" | b"punsubscribe" | b"PUnsubscribe" | b"Punsubscribe" => {
+                    Ok(PUnsubscribe(get_args(&v[1..])?))
+                }
+                b"PUBLISH" | b"publish" | b"Publish" => {
+                    let channel = get_bytes_vec(v.get(1))?;
+                    let message = get_bytes_vec(v.get(2))?;
+                    Ok(Publish(channel, message))
+                }
+                b"PUBSUB" | b"pubsub" | b"PubSub" | b"Pubsub" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match (subcommand.to_ascii_uppercase().as_slice(), &v[2..]) {
+                        (b"CHANNELS", []) => Ok(PubSub(PubSubSubcommand::Channels(None))),
+                        (b"CHANNELS", [pattern]) => {
+                            let pattern = get_bytes_vec(Some(pattern))?;
+                            Ok(PubSub(PubSubSubcommand::Channels(Some(pattern))))
+                        }
+                        (b"NUMSUB", channels) => {
+                            Ok(PubSub(PubSubSubcommand::NumSub(get_args(channels)?)))
+                        }
+                        (b"NUMPAT", []) => Ok(PubSub(PubSubSubcommand::NumPat)),
+                        _ => Err(SyntaxErr),
+                    }
+                }
+                b"SORT" | b"sort" | b"Sort" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let options = parse_sort_options(&v[2..])?;
+                    Ok(Sort(key, options))
+                }
+                b"COMMAND" | b"command" | b"Command" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match subcommand.to_ascii_uppercase().as_slice() {
+                        b"GETKEYS" => {
+                            let args = v[2..]
+                                .iter()
+                                .map(|arg| get_bytes_vec(Some(arg)))
+                                .collect::<Result<_, _>>()?;
+                            Ok(GetKeys(args))
+                        }
+                        b"INFO" => {
+                            let names = v[2..]
+                                .iter()
+                                .map(|arg| get_bytes_vec(Some(arg)))
+                                .collect::<Result<_, _>>()?;
+                            Ok(CommandInfo(names))
+                        }
+                        b"DOCS" => {
+                            let names = v[2..]
+                                .iter()
+                                .map(|arg| get_bytes_vec(Some(arg)))
+                                .collect::<Result<_, _>>()?;
+                            Ok(CommandDocs(names))
+                        }
+                        _ => Err(SyntaxErr),
+                    }
+                }
+                b"MEMORY" | b"memory" | b"Memory" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match (subcommand.to_ascii_uppercase().as_slice(), &v[2..]) {
+                        // the estimate is exact, so the number of samples is not used
+                        (b"USAGE", [key]) => {
+                            Ok(Memory(MemorySubcommand::Usage(get_bytes_vec(Some(key))?)))
+                        }
+                        (b"USAGE", [key, option, count]) => {
+                            if !get_bytes_vec(Some(option))?.eq_ignore_ascii_case(b"SAMPLES") {
+                                return Err(SyntaxErr);
+                            }
+                            parse_variation(get_bytes_vec(Some(count))?)?;
+                            Ok(Memory(MemorySubcommand::Usage(get_bytes_vec(Some(key))?)))
+                        }
+                        (b"STATS", []) => Ok(Memory(MemorySubcommand::Stats)),
+                        (b"DOCTOR", []) => Ok(Memory(MemorySubcommand::Doctor)),
+                        (b"HELP", []) => Ok(Help("MEMORY")),
+                        _ => Err(unknown_subcommand(&subcommand, "MEMORY")),
+                    }
+                }
+                b"CLIENT" | b"client" | b"Client" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match (subcommand.to_ascii_uppercase().as_slice(), &v[2..]) {
+                        // the options of the tracking, such as `BCAST`, are not supported
+                        (b"TRACKING", [mode]) => {
+                            match get_bytes_vec(Some(mode))?.to_ascii_uppercase().as_slice() {
+                                b"ON" => Ok(Client(ClientSubcommand::Tracking(true))),
+                                b"OFF" => Ok(Client(ClientSubcommand::Tracking(false))),
+                                _ => Err(SyntaxErr),
+                            }
+                        }
+                        (b"ID", []) => Ok(Client(ClientSubcommand::Id)),
+                        (b"KILL", filters) => {
+                            Ok(Client(ClientSubcommand::Kill(parse_kill_filter(filters)?)))
+                        }
+                        // not a subcommand of Redis, so a standard client never opts in
+                        (b"REDISLESS-COMPRESS", [threshold]) => {
+                            let threshold = get_bytes_vec(Some(threshold))?;
+                            match threshold.to_ascii_uppercase().as_slice() {
+                                b"OFF" => Ok(Client(ClientSubcommand::Compress(None))),
+                                _ => Ok(Client(ClientSubcommand::Compress(Some(parse_duration(
+                                    threshold,
+                                )?
+                                    as usize)))),
+                            }
+                        }
+                        (b"HELP", []) => Ok(Help("CLIENT")),
+                        _ => Err(unknown_subcommand(&subcommand, "CLIENT")),
+                    }
+                }
+                b"CONFIG" | b"config" | b"Config" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    match (subcommand.to_ascii_uppercase().as_slice(), &v[2..]) {
+                        (b"GET", [pattern]) => {
+                            Ok(Config(ConfigSubcommand::Get(get_bytes_vec(Some(pattern))?)))
+                        }
+                        (b"SET", parameters)
+                            if !parameters.is_empty() && parameters.len() % 2 == 0 =>
+                        {
+                            let parameters = parameters
+                                .chunks(2)
+                                .map(|pair| {
+                                    let name = get_bytes_vec(pair.first())?;
+                                    let value = get_bytes_vec(pair.get(1))?;
+                                    Ok((
+                                        String::from_utf8_lossy(&name).to_lowercase(),
+                                        String::from_utf8_lossy(&value).to_string(),
+                                    ))
+                                })
+                                .collect::<Result<_, RedisCommandError>>()?;
+                            Ok(Config(ConfigSubcommand::Set(parameters)))
+                        }
+                        (b"HELP", []) => Ok(Help("CONFIG")),
+                        _ => Err(unknown_subcommand(&subcommand, "CONFIG")),
+                    }
+                }
+                b"ACL" | b"acl" | b"Acl" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    let string = |arg| {
+                        String::from_utf8(get_bytes_vec(Some(arg))?)
+                            .map_err(|err| RedisCommandError::from(err.utf8_error()))
+                    };
+                    match (subcommand.to_ascii_uppercase().as_slice(), &v[2..]) {
+                        (b"SETUSER", [username, rules @ ..]) => {
+                            let rules = rules
+                                .iter()
+                                .map(|rule| string(rule).and_then(|rule| parse_acl_rule(&rule)))
+                                .collect::<Result<_, _>>()?;
+                            Ok(Acl(AclSubcommand::SetUser(string(username)?, rules)))
+                        }
+                        (b"GETUSER", [username]) => {
+                            Ok(Acl(AclSubcommand::GetUser(string(username)?)))
+                        }
+                        (b"LIST", []) => Ok(Acl(AclSubcommand::List)),
+                        (b"WHOAMI", []) => Ok(Acl(AclSubcommand::WhoAmI)),
+                        _ => Err(SyntaxErr),
+                    }
+                }
+                b"LATENCY" | b"latency" | b"Latency" => {
+                    let subcommand = get_bytes_vec(v.get(1))?;
+                    let event = |arg| {
+                        get_bytes_vec(Some(arg))
+                            .map(|event| String::from_utf8_lossy(&event).to_lowercase())
+                    };
+                    match (subcommand.to_ascii_uppercase().as_slice(), &v[2..]) {
+                        (b"LATEST", []) => Ok(Latency(LatencySubcommand::Latest)),
+                        (b"HISTORY", [name]) => {
+                            Ok(Latency(LatencySubcommand::History(event(name)?)))
+                        }
+                        (b"RESET", names) => {
+                            let names = names.iter().map(event).collect::<Result<_, _>>()?;
+                            Ok(Latency(LatencySubcommand::Reset(names)))
+                        }
+                        (b"HELP", []) => Ok(Help("LATENCY")),
+                        _ => Err(unknown_subcommand(&subcommand, "LATENCY")),
+                    }
+                }
+                b"DEBUG" | b"debug" | b"Debug" => debug::parse(&v[1..]),
+                b"SAVE" | b"save" | b"Save" => Ok(Save),
+                b"BGSAVE" | b"bgsave" | b"BgSave" | b"Bgsave" => Ok(BgSave),
+                b"SHUTDOWN" | b"shutdown" | b"Shutdown" => {
+                    Ok(Shutdown(parse_shutdown_mode(&v[1..])?))
+                }
+                b"BGREWRITEAOF" | b"bgrewriteaof" | b"BgRewriteAof" => Ok(BgRewriteAof),
+                b"WAITAOF" | b"waitaof" | b"WaitAof" | b"Waitaof" => {
+                    let count = |arg| {
+                        let count = get_bytes_vec(arg).and_then(parse_variation)?;
+                        u64::try_from(count).map_err(|_| ValueNotPositive)
+                    };
+                    let numlocal = count(v.get(1))?;
+                    let numreplicas = count(v.get(2))?;
+                    let timeout = get_bytes_vec(v.get(3))
+                        .and_then(parse_variation)
+                        .map_err(|_| InvalidIntegerTimeout)?;
+                    let timeout = u64::try_from(timeout).map_err(|_| NegativeTimeout)?;
+                    Ok(WaitAof(numlocal, numreplicas, timeout))
+                }
+                b"LASTSAVE" | b"lastsave" | b"LastSave" | b"Lastsave" => Ok(LastSave),
+                b"INFO" | b"info" | b"Info" => {
+                    let sections = get_args(&v[1..])?
+                        .iter()
+                        .map(|section| String::from_utf8_lossy(section).to_lowercase())
+                        .collect();
+                    Ok(Info(sections))
+                }
+                b"LOLWUT" | b"lolwut" | b"Lolwut" => {
+                    // `VERSION n` selects the artwork in Redis, there is only one here
+                    if let Some(option) = v.get(1) {
+                        let option = get_bytes_vec(Some(option))?;
+                        if !option.eq_ignore_ascii_case(b"VERSION") || v.len() != 3 {
+                            return Err(SyntaxErr);
+                        }
+                        let _ = get_bytes_vec(v.get(2)).and_then(parse_variation)?;
+                    }
+                    Ok(Lolwut)
+                }
                 b"PING" | b"ping" | b"Ping" => Ok(Ping),
                 b"DBSIZE" | b"dbsize" | b"Dbsize" => Ok(Dbsize),
+                b"RANDOMKEY" | b"randomkey" | b"RandomKey" | b"Randomkey" => Ok(RandomKey),
+                b"MOVE" | b"move" | b"Move" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let db = get_bytes_vec(v.get(2)).and_then(parse_variation)?;
+                    let db = u64::try_from(db).map_err(|_| DbIndexOutOfRange)?;
+                    Ok(Move(key, db))
+                }
+                b"COPY" | b"copy" | b"Copy" => {
+                    let source = get_bytes_vec(v.get(1))?;
+                    let destination = get_bytes_vec(v.get(2))?;
+                    let (db, replace) = parse_copy_options(&v[3..])?;
+                    Ok(Copy(source, destination, db, replace))
+                }
+                b"DUMP" | b"dump" | b"Dump" => Ok(Dump(get_bytes_vec(v.get(1))?)),
+                b"RESTORE" | b"restore" | b"Restore" => {
+                    let key = get_bytes_vec(v.get(1))?;
+                    let ttl = get_bytes_vec(v.get(2)).and_then(parse_variation)?;
+                    let ttl = u64::try_from(ttl).map_err(|_| InvalidTtl)?;
+                    let payload = get_bytes_vec(v.get(3))?;
+                    let options = parse_restore_options(&v[4..])?;
+                    Ok(Restore(key, ttl, payload, options))
+                }
+                b"SELECT" | b"select" | b"Select" => {
+                    let index = get_bytes_vec(v.get(1)).and_then(parse_variation)?;
+                    let index = usize::try_from(index).map_err(|_| DbIndexOutOfRange)?;
+                    Ok(Select(index))
+                }
+                b"REPLICAOF" | b"replicaof" | b"ReplicaOf" | b"Replicaof" | b"SLAVEOF"
+                | b"slaveof" | b"SlaveOf" | b"Slaveof" => {
+                    let host = get_bytes_vec(v.get(1))?;
+                    let port = get_bytes_vec(v.get(2))?;
+                    if host.eq_ignore_ascii_case(b"NO") && port.eq_ignore_ascii_case(b"ONE") {
+                        return Ok(ReplicaOfNoOne);
+                    }
+                    let host = String::from_utf8(host).map_err(|err| err.utf8_error())?;
+                    let port = std::str::from_utf8(&port)?
+                        .parse::<u16>()
+                        .map_err(|_| NotAnInteger)?;
+                    Ok(ReplicaOf(host, port))
+                }
+                b"SYNC" | b"sync" | b"Sync" => Ok(Sync),
+                b"ROLE" | b"role" | b"Role" => Ok(Role),
+                // the replication id and offset only matter to a partial resynchronization,
+                // which is not supported
+                b"PSYNC" | b"psync" | b"PSync" | b"Psync" => Ok(PSync),
+                b"FLUSHDB" | b"flushdb" | b"FlushDb" | b"Flushdb" => {
+                    Ok(FlushDb(parse_flush_mode(&v[1..])?))
+                }
+                b"FLUSHALL" | b"flushall" | b"FlushAll" | b"Flushall" => {
+                    Ok(FlushAll(parse_flush_mode(&v[1..])?))
+                }
+                b"AUTH" | b"auth" | b"Auth" => {
+                    let mut args = get_args(&v[1..])?
+                        .into_iter()
+                        .map(String::from_utf8)
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|err| err.utf8_error())?;
+                    let password = args.pop().ok_or(SyntaxErr)?;
+                    match (args.pop(), args.is_empty()) {
+                        (username, true) => Ok(Auth(username, password)),
+                        (_, false) => Err(SyntaxErr),
+                    }
+                }
+                b"HELLO" | b"hello" | b"Hello" => Ok(Hello(parse_hello_options(&v[1..])?)),
                 b"QUIT" | b"quit" | b"Quit" => Ok(Quit),
-                unsupported_command => Err(NotSupported(
-                    std::str::from_utf8(unsupported_command)
-                        .unwrap()
-                        .to_string(),
-                )),
+                b"MULTI" | b"multi" | b"Multi" => Ok(Multi),
+                b"EXEC" | b"exec" | b"Exec" => Ok(Exec),
+                b"DISCARD" | b"discard" | b"Discard" => Ok(Discard),
+                b"MONITOR" | b"monitor" | b"Monitor" => Ok(Monitor),
+                _ => Err(unknown_command(&v)),
             },
             _ => Err(InvalidCommand),
         }
@@ -0,0 +1,223 @@
+use crate::storage::models::{Expiry, LexBound, ScoreBound};
+
+/// `NX` / `XX` condition of a `SET` or a `ZADD`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SetCondition {
+    // NX: only set the key if it does not already exist
+    NotExists,
+    // XX: only set the key if it already exists
+    Exists,
+}
+
+/// Options accepted by `SET key value [NX|XX] [GET] [EX seconds|PX milliseconds|KEEPTTL]`
+#[derive(Debug, PartialEq, Default)]
+pub struct SetOptions {
+    pub expiry: Option<Expiry>,
+    pub condition: Option<SetCondition>,
+    pub keep_ttl: bool,
+    // return the old value stored at key, or nil when key did not exist
+    pub get: bool,
+}
+
+/// `EX seconds` / `PX milliseconds` / `EXAT unix-time-seconds` /
+/// `PXAT unix-time-milliseconds` / `PERSIST` option of a `GETEX`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GetExOption {
+    Expiry(Expiry),
+    // remove the expiry of the key
+    Persist,
+}
+
+/// `NX` / `XX` / `GT` / `LT` condition of an `HEXPIRE`, on the current expiry of a field
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExpireCondition {
+    // NX: only set the expiry of a field which has none
+    NoExpiry,
+    // XX: only set the expiry of a field which has one
+    HasExpiry,
+    // GT: only set an expiry later than the current one, which a field without is not
+    Greater,
+    // LT: only set an expiry earlier than the current one, which a field without is
+    Less,
+}
+
+/// Options accepted by `HELLO [protover [AUTH username password] [SETNAME clientname]]`
+#[derive(Debug, PartialEq, Default)]
+pub struct HelloOptions {
+    // protocol version to switch to, the current one is kept when `None`
+    pub protocol: Option<u8>,
+    // username and password to authenticate with
+    pub auth: Option<(String, String)>,
+    pub setname: Option<String>,
+}
+
+/// Rule of an `ACL SETUSER`, applied to the user in order
+#[derive(Debug, PartialEq, Clone)]
+pub enum AclRule {
+    On,
+    Off,
+    // `>password` / `<password`
+    AddPassword(String),
+    RemovePassword(String),
+    NoPass,
+    ResetPass,
+    // `+@all` or `allcommands` / `-@all` or `nocommands`
+    AllCommands,
+    NoCommands,
+    // `+command` / `-command`, with the uppercase name of the command
+    Allow(String),
+    Deny(String),
+    Reset,
+}
+
+/// `ASYNC` / `SYNC` modifier of `FLUSHDB` and `FLUSHALL`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FlushMode {
+    Sync,
+    Async,
+}
+
+/// `NOSAVE` / `SAVE` modifier of `SHUTDOWN`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ShutdownMode {
+    // a snapshot is saved only when save points are configured
+    Default,
+    NoSave,
+    Save,
+}
+
+/// Filters of `CLIENT KILL [ID client-id] [ADDR ip:port]`, a connection is killed when it
+/// matches all of them
+#[derive(Debug, PartialEq, Default)]
+pub struct KillFilter {
+    pub id: Option<u64>,
+    // as reported by `MONITOR`, `ip:port` or `unix:<path>`
+    pub addr: Option<String>,
+}
+
+/// Options accepted by `RESTORE key ttl serialized-value [REPLACE] [ABSTTL]
+/// [IDLETIME seconds] [FREQ frequency]`
+#[derive(Debug, PartialEq, Default)]
+pub struct RestoreOptions {
+    // whether an existing key is replaced
+    pub replace: bool,
+    // whether the ttl is the unix time in millis the key expires at, rather than a duration
+    pub absttl: bool,
+    // access metadata of the restored key, only one of them being set
+    pub idletime: Option<u64>,
+    pub freq: Option<u8>,
+}
+
+/// Options accepted by `LPOS key element [RANK rank] [COUNT num-matches]`
+#[derive(Debug, PartialEq, Default)]
+pub struct LPosOptions {
+    // skip the first rank - 1 matches, a negative rank searches from the tail
+    pub rank: Option<i64>,
+    // return up to count matches as an array, 0 for all of them
+    pub count: Option<usize>,
+}
+
+/// Options accepted by `LCS key1 key2 [LEN] [IDX] [MINMATCHLEN len] [WITHMATCHLEN]`
+#[derive(Debug, PartialEq, Default)]
+pub struct LcsOptions {
+    // reply the length of the longest common subsequence rather than the subsequence
+    pub len: bool,
+    // reply the ranges of the matches along with the length
+    pub idx: bool,
+    // shortest match replied by `IDX`, 0 for all of them
+    pub min_match_len: usize,
+    pub with_match_len: bool,
+}
+
+/// `GT` / `LT` condition of a `ZADD`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ScoreComparison {
+    // GT: only update a member if its new score is greater
+    Greater,
+    // LT: only update a member if its new score is less
+    Less,
+}
+
+/// Options accepted by `ZADD key [NX|XX] [GT|LT] [CH] [INCR] score member [score member ...]`
+#[derive(Debug, PartialEq, Default)]
+pub struct ZAddOptions {
+    pub condition: Option<SetCondition>,
+    pub comparison: Option<ScoreComparison>,
+    // return the number of added and updated members, not only the added ones
+    pub changed: bool,
+    // increment the score of a single member, like ZINCRBY
+    pub incr: bool,
+}
+
+/// `AGGREGATE` of the scores a member has in the sorted sets combined by `ZUNION` or
+/// `ZINTER`
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Aggregate {
+    #[default]
+    Sum,
+    Min,
+    Max,
+}
+
+/// Options accepted by `ZUNION` / `ZINTER numkeys key [key ...] [WEIGHTS weight
+/// [weight ...]] [AGGREGATE SUM|MIN|MAX] [WITHSCORES]`
+#[derive(Debug, PartialEq, Default)]
+pub struct ZCombineOptions {
+    // one per key, multiplying its scores before they are aggregated, all 1 when `None`
+    pub weights: Option<Vec<f64>>,
+    pub aggregate: Aggregate,
+    pub with_scores: bool,
+}
+
+/// Members selected by a `ZRANGE`-style range: by their index, by score with `BYSCORE`, or
+/// lexicographically with `BYLEX`
+#[derive(Debug, PartialEq, Clone)]
+pub enum ZRangeBy {
+    // first and last indexes, negative ones counting from the end
+    Index(i64, i64),
+    Score(ScoreBound, ScoreBound),
+    Lex(LexBound, LexBound),
+}
+
+/// Options accepted by `ZRANGESTORE dst src min max [BYSCORE|BYLEX] [REV] [LIMIT offset
+/// count]`
+#[derive(Debug, PartialEq, Clone)]
+pub struct ZRangeOptions {
+    // the scores or members are the min then the max, even with `REV` where they are given
+    // the other way around, and the indexes are the ones of the order of the range
+    pub by: ZRangeBy,
+    // from the highest score down
+    pub rev: bool,
+    // offset and count, only with `BYSCORE` or `BYLEX`
+    pub limit: Option<(i64, i64)>,
+}
+
+/// Options accepted by `SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]`
+#[derive(Debug, PartialEq)]
+pub struct ScanOptions {
+    pub pattern: Option<Vec<u8>>,
+    // number of elements returned by a call, the last batch may be shorter
+    pub count: usize,
+    // only return keys of this type, as named by `TYPE`
+    pub key_type: Option<Vec<u8>>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            pattern: None,
+            count: 10,
+            key_type: None,
+        }
+    }
+}
+
+/// Options accepted by `SORT key [LIMIT offset count] [ASC|DESC] [ALPHA]`
+#[derive(Debug, PartialEq, Default)]
+pub struct SortOptions {
+    // compare the elements as strings rather than as numbers
+    pub alpha: bool,
+    pub descending: bool,
+    // offset and count of the elements returned, applied after sorting
+    pub limit: Option<(i64, i64)>,
+}
@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+
+use super::command_error::RedisCommandError;
+use super::util::unknown_command;
+use crate::protocol::Resp;
+
+/// Replace the name of a renamed command by its original name, so it can be parsed. The
+/// original name of a renamed command, and the name of a disabled one, are unknown.
+///
+/// `renames` maps original names to their new name, or to an empty string for a disabled
+/// command, see `ServerConfig::rename_commands`. Returns whether the command was renamed.
+pub fn resolve_renamed<'a>(
+    v: &mut [Resp<'a>],
+    renames: &'a HashMap<String, String>,
+) -> Result<bool, RedisCommandError> {
+    if renames.is_empty() {
+        return Ok(false);
+    }
+    let name = match v.first() {
+        Some(Resp::BulkString(name)) => *name,
+        _ => return Ok(false),
+    };
+
+    let original = renames.iter().find(|(_, new_name)| {
+        !new_name.is_empty() && new_name.as_bytes().eq_ignore_ascii_case(name)
+    });
+    if let Some((original, _)) = original {
+        v[0] = Resp::BulkString(original.as_bytes());
+        return Ok(true);
+    }
+
+    if renames
+        .keys()
+        .any(|original| original.as_bytes().eq_ignore_ascii_case(name))
+    {
+        return Err(unknown_command(v));
+    }
+    Ok(false)
+}
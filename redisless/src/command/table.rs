@@ -0,0 +1,307 @@
+use super::command_error::RedisCommandError;
+
+/// Flag of the commands which modify the keyspace, and so are propagated to replicas
+pub const WRITE: u8 = 1;
+/// Flag of the commands which only read the keyspace, or do not touch it at all
+pub const READONLY: u8 = 0;
+/// Flag of the write commands which may allocate memory, and so are refused once the used
+/// memory exceeds `maxmemory`, unlike the ones which only remove or expire data
+pub const DENY_OOM: u8 = 2;
+
+/// Position of the key arguments of a command, the command name being at 0
+#[derive(Debug, Clone, Copy)]
+enum KeySpec {
+    // as in the Redis command table, a negative last key counts from the end
+    Range {
+        first: usize,
+        last: i32,
+        step: usize,
+    },
+    // the argument at this position is the number of keys, which follow it
+    NumKeys(usize),
+}
+
+const fn keys(first: usize, last: i32, step: usize) -> KeySpec {
+    KeySpec::Range { first, last, step }
+}
+
+const NO_KEYS: KeySpec = keys(0, 0, 0);
+const ONE_KEY: KeySpec = keys(1, 1, 1);
+// every argument after the command name
+const ALL_KEYS: KeySpec = keys(1, -1, 1);
+
+/// Arity, flags and key arguments of each command.
+///
+/// As in the Redis command table, the arity is the number of arguments, the command name
+/// included. A positive arity is the exact number of arguments and a negative one is the
+/// minimum number of arguments.
+const COMMANDS: &[(&[u8], i32, u8, KeySpec)] = &[
+    (b"ACL", -2, READONLY, NO_KEYS),
+    (b"APPEND", 3, WRITE | DENY_OOM, ONE_KEY),
+    (b"AUTH", -2, READONLY, NO_KEYS),
+    (b"BGREWRITEAOF", 1, READONLY, NO_KEYS),
+    (b"BGSAVE", -1, READONLY, NO_KEYS),
+    (b"BITOP", -4, WRITE | DENY_OOM, keys(2, -1, 1)),
+    (b"BITPOS", -3, READONLY, ONE_KEY),
+    // blocking commands are propagated as the LPOP, RPOP or LMOVE they end up running
+    (b"BLMOVE", 6, READONLY, keys(1, 2, 1)),
+    (b"BLPOP", -3, READONLY, keys(1, -2, 1)),
+    (b"BRPOP", -3, READONLY, keys(1, -2, 1)),
+    (b"BRPOPLPUSH", 4, READONLY, keys(1, 2, 1)),
+    (b"CAS", 4, WRITE | DENY_OOM, ONE_KEY),
+    (b"CLIENT", -2, READONLY, NO_KEYS),
+    (b"COMMAND", -1, READONLY, NO_KEYS),
+    (b"CONFIG", -2, READONLY, NO_KEYS),
+    (b"COPY", -3, WRITE | DENY_OOM, keys(1, 2, 1)),
+    (b"DBSIZE", 1, READONLY, NO_KEYS),
+    (b"DEBUG", -2, READONLY, NO_KEYS),
+    (b"DECR", 2, WRITE | DENY_OOM, ONE_KEY),
+    (b"DECRBY", 3, WRITE | DENY_OOM, ONE_KEY),
+    (b"DEL", -2, WRITE, ALL_KEYS),
+    (b"DISCARD", 1, READONLY, NO_KEYS),
+    (b"DUMP", 2, READONLY, ONE_KEY),
+    (b"EXEC", 1, READONLY, NO_KEYS),
+    (b"EXISTS", -2, READONLY, ALL_KEYS),
+    (b"EXPIRE", 3, WRITE, ONE_KEY),
+    (b"FLUSHALL", -1, WRITE, NO_KEYS),
+    (b"FLUSHDB", -1, WRITE, NO_KEYS),
+    (b"GET", 2, READONLY, ONE_KEY),
+    (b"GETDEL", 2, WRITE, ONE_KEY),
+    (b"GETEX", -2, WRITE, ONE_KEY),
+    (b"GETRANGE", 4, READONLY, ONE_KEY),
+    (b"GETSET", 3, WRITE | DENY_OOM, ONE_KEY),
+    (b"HELLO", -1, READONLY, NO_KEYS),
+    (b"HEXPIRE", -6, WRITE, ONE_KEY),
+    (b"HGET", 3, READONLY, ONE_KEY),
+    (b"HGETALL", 2, READONLY, ONE_KEY),
+    (b"HKEYS", 2, READONLY, ONE_KEY),
+    (b"HMSET", -4, WRITE | DENY_OOM, ONE_KEY),
+    (b"HPERSIST", -5, WRITE, ONE_KEY),
+    (b"HPEXPIRE", -6, WRITE, ONE_KEY),
+    (b"HRANDFIELD", -2, READONLY, ONE_KEY),
+    (b"HSCAN", -3, READONLY, ONE_KEY),
+    (b"HSET", -4, WRITE | DENY_OOM, ONE_KEY),
+    (b"HTTL", -5, READONLY, ONE_KEY),
+    (b"HVALS", 2, READONLY, ONE_KEY),
+    (b"INCR", 2, WRITE | DENY_OOM, ONE_KEY),
+    (b"INCRBY", 3, WRITE | DENY_OOM, ONE_KEY),
+    (b"INFO", -1, READONLY, NO_KEYS),
+    (b"KEYS", 2, READONLY, NO_KEYS),
+    (b"LASTSAVE", 1, READONLY, NO_KEYS),
+    (b"LATENCY", -2, READONLY, NO_KEYS),
+    (b"LCS", -3, READONLY, keys(1, 2, 1)),
+    (b"LINDEX", 3, READONLY, ONE_KEY),
+    (b"LINSERT", 5, WRITE | DENY_OOM, ONE_KEY),
+    (b"LLEN", 2, READONLY, ONE_KEY),
+    (b"LMOVE", 5, WRITE | DENY_OOM, keys(1, 2, 1)),
+    (b"LOLWUT", -1, READONLY, NO_KEYS),
+    (b"LPOP", -2, WRITE, ONE_KEY),
+    (b"LPOS", -3, READONLY, ONE_KEY),
+    (b"LPUSH", -3, WRITE | DENY_OOM, ONE_KEY),
+    (b"LPUSHX", -3, WRITE | DENY_OOM, ONE_KEY),
+    (b"LREM", 4, WRITE, ONE_KEY),
+    (b"LSET", 4, WRITE | DENY_OOM, ONE_KEY),
+    (b"LTRIM", 4, WRITE, ONE_KEY),
+    (b"MEMORY", -2, READONLY, keys(2, 2, 1)),
+    (b"MGET", -2, READONLY, ALL_KEYS),
+    (b"MONITOR", 1, READONLY, NO_KEYS),
+    (b"MOVE", 3, WRITE, ONE_KEY),
+    (b"MSET", -3, WRITE | DENY_OOM, keys(1, -1, 2)),
+    (b"MSETNX", -3, WRITE | DENY_OOM, keys(1, -1, 2)),
+    (b"MULTI", 1, READONLY, NO_KEYS),
+    (b"OBJECT", -2, READONLY, keys(2, 2, 1)),
+    (b"PEXPIRE", 3, WRITE, ONE_KEY),
+    (b"PING", -1, READONLY, NO_KEYS),
+    (b"PSETEX", 4, WRITE | DENY_OOM, ONE_KEY),
+    (b"PSUBSCRIBE", -2, READONLY, NO_KEYS),
+    (b"PSYNC", -3, READONLY, NO_KEYS),
+    (b"PTTL", 2, READONLY, ONE_KEY),
+    (b"PUBLISH", 3, READONLY, NO_KEYS),
+    (b"PUBSUB", -2, READONLY, NO_KEYS),
+    (b"PUNSUBSCRIBE", -1, READONLY, NO_KEYS),
+    (b"QUIT", -1, READONLY, NO_KEYS),
+    (b"RANDOMKEY", 1, READONLY, NO_KEYS),
+    (b"REPLICAOF", 3, READONLY, NO_KEYS),
+    (b"RESTORE", -4, WRITE | DENY_OOM, ONE_KEY),
+    (b"ROLE", 1, READONLY, NO_KEYS),
+    (b"RPOP", -2, WRITE, ONE_KEY),
+    (b"RPOPLPUSH", 3, WRITE | DENY_OOM, keys(1, 2, 1)),
+    (b"RPUSH", -3, WRITE | DENY_OOM, ONE_KEY),
+    (b"RPUSHX", -3, WRITE | DENY_OOM, ONE_KEY),
+    (b"SADD", -3, WRITE | DENY_OOM, ONE_KEY),
+    (b"SAVE", 1, READONLY, NO_KEYS),
+    (b"SCAN", -2, READONLY, NO_KEYS),
+    (b"SCARD", 2, READONLY, ONE_KEY),
+    (b"SELECT", 2, READONLY, NO_KEYS),
+    (b"SET", -3, WRITE | DENY_OOM, ONE_KEY),
+    (b"SETEX", 4, WRITE | DENY_OOM, ONE_KEY),
+    (b"SETNX", 3, WRITE | DENY_OOM, ONE_KEY),
+    (b"SETRANGE", 4, WRITE | DENY_OOM, ONE_KEY),
+    (b"SHUTDOWN", -1, READONLY, NO_KEYS),
+    (b"SINTERCARD", -3, READONLY, KeySpec::NumKeys(1)),
+    (b"SLAVEOF", 3, READONLY, NO_KEYS),
+    (b"SMISMEMBER", -3, READONLY, ONE_KEY),
+    (b"SORT", -2, READONLY, ONE_KEY),
+    (b"SPOP", -2, WRITE, ONE_KEY),
+    (b"SRANDMEMBER", -2, READONLY, ONE_KEY),
+    (b"SREM", -3, WRITE, ONE_KEY),
+    (b"SSCAN", -3, READONLY, ONE_KEY),
+    (b"STRLEN", 2, READONLY, ONE_KEY),
+    (b"SUBSCRIBE", -2, READONLY, NO_KEYS),
+    (b"SUBSTR", 4, READONLY, ONE_KEY),
+    (b"SYNC", 1, READONLY, NO_KEYS),
+    (b"TTL", 2, READONLY, ONE_KEY),
+    (b"TYPE", 2, READONLY, ONE_KEY),
+    (b"UNSUBSCRIBE", -1, READONLY, NO_KEYS),
+    (b"WAITAOF", 4, READONLY, NO_KEYS),
+    (b"WAITKEY", 3, READONLY, ONE_KEY),
+    (b"ZADD", -4, WRITE | DENY_OOM, ONE_KEY),
+    (b"ZCOUNT", 4, READONLY, ONE_KEY),
+    (b"ZDIFF", -3, READONLY, KeySpec::NumKeys(1)),
+    (b"ZINCRBY", 4, WRITE | DENY_OOM, ONE_KEY),
+    (b"ZINTER", -3, READONLY, KeySpec::NumKeys(1)),
+    (b"ZMSCORE", -3, READONLY, ONE_KEY),
+    (b"ZPOPMAX", -2, WRITE, ONE_KEY),
+    (b"ZPOPMIN", -2, WRITE, ONE_KEY),
+    (b"ZRANDMEMBER", -2, READONLY, ONE_KEY),
+    (b"ZRANGEBYLEX", -4, READONLY, ONE_KEY),
+    (b"ZRANGESTORE", -5, WRITE | DENY_OOM, keys(1, 2, 1)),
+    (b"ZSCAN", -3, READONLY, ONE_KEY),
+    (b"ZSCORE", 3, READONLY, ONE_KEY),
+    (b"ZUNION", -3, READONLY, KeySpec::NumKeys(1)),
+];
+
+fn lookup(command: &[u8]) -> Option<&(&[u8], i32, u8, KeySpec)> {
+    COMMANDS
+        .iter()
+        .find(|(name, _, _, _)| name.eq_ignore_ascii_case(command))
+}
+
+pub fn arity(command: &[u8]) -> Option<i32> {
+    lookup(command).map(|(_, arity, _, _)| *arity)
+}
+
+/// Whether the command modifies the keyspace, unknown commands are not
+pub fn is_write(command: &[u8]) -> bool {
+    lookup(command).is_some_and(|(_, _, flags, _)| flags & WRITE != 0)
+}
+
+/// Whether the command may allocate memory, refused when the used memory exceeds
+/// `maxmemory`
+pub fn denies_oom(command: &[u8]) -> bool {
+    lookup(command).is_some_and(|(_, _, flags, _)| flags & DENY_OOM != 0)
+}
+
+/// Description of a command by `COMMAND INFO`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandSpec {
+    // lowercase, as Redis reports it
+    pub name: String,
+    pub arity: i32,
+    pub flags: Vec<&'static str>,
+    // positions of the keys, as in the Redis command table: `0 0 0` without keys, and for
+    // the commands whose keys are counted by an argument
+    pub first_key: i64,
+    pub last_key: i64,
+    pub step: i64,
+}
+
+/// Description of command, `None` when it is unknown
+pub fn command_spec(command: &[u8]) -> Option<CommandSpec> {
+    lookup(command).map(describe)
+}
+
+/// Description of every command, sorted by name
+pub fn command_specs() -> Vec<CommandSpec> {
+    COMMANDS.iter().map(describe).collect()
+}
+
+fn describe(&(name, arity, flags, spec): &(&[u8], i32, u8, KeySpec)) -> CommandSpec {
+    let (first_key, last_key, step) = match spec {
+        KeySpec::Range { first, last, step } => (first as i64, last as i64, step as i64),
+        KeySpec::NumKeys(_) => (0, 0, 0),
+    };
+    let mut command_flags = vec![];
+    if flags & WRITE != 0 {
+        command_flags.push("write");
+    } else if step != 0 || matches!(spec, KeySpec::NumKeys(_)) {
+        // commands which touch no key are not flagged
+        command_flags.push("readonly");
+    }
+    if flags & DENY_OOM != 0 {
+        command_flags.push("denyoom");
+    }
+    if matches!(spec, KeySpec::NumKeys(_)) {
+        command_flags.push("movablekeys");
+    }
+    CommandSpec {
+        name: String::from_utf8_lossy(name).to_lowercase(),
+        arity,
+        flags: command_flags,
+        first_key,
+        last_key,
+        step,
+    }
+}
+
+/// Positions of the key arguments of a full command, as `COMMAND GETKEYS` reports them
+pub fn key_positions(args: &[Vec<u8>]) -> Result<Vec<usize>, RedisCommandError> {
+    let (arity, spec) = match args.first().and_then(|command| lookup(command)) {
+        Some((_, arity, _, spec)) => (*arity, *spec),
+        None => return Err(RedisCommandError::GetKeysInvalidCommand),
+    };
+    let len = args.len();
+    if !matches_arity(arity, len) {
+        return Err(RedisCommandError::GetKeysInvalidArgs);
+    }
+
+    let positions: Vec<usize> = match spec {
+        KeySpec::Range { step: 0, .. } => vec![],
+        KeySpec::Range { first, last, step } => {
+            let last = match last {
+                last if last < 0 => len as i32 + last,
+                last => last,
+            };
+            (first as i32..=last.min(len as i32 - 1))
+                .step_by(step)
+                .map(|position| position as usize)
+                .collect()
+        }
+        KeySpec::NumKeys(position) => {
+            let numkeys = std::str::from_utf8(&args[position])
+                .ok()
+                .and_then(|numkeys| numkeys.parse::<usize>().ok())
+                .filter(|numkeys| position + numkeys < len)
+                .ok_or(RedisCommandError::GetKeysInvalidArgs)?;
+            (position + 1..=position + numkeys).collect()
+        }
+    };
+    match positions.is_empty() {
+        true => Err(RedisCommandError::NoKeyArguments),
+        false => Ok(positions),
+    }
+}
+
+/// Check that a request of `args_len` arguments, the command name included, matches the
+/// arity of the command. Commands missing from the table are not checked.
+pub fn check_arity(command: &[u8], args_len: usize) -> Result<(), RedisCommandError> {
+    let arity = match arity(command) {
+        Some(arity) => arity,
+        None => return Ok(()),
+    };
+
+    match matches_arity(arity, args_len) {
+        true => Ok(()),
+        false => Err(RedisCommandError::WrongArity(
+            String::from_utf8_lossy(command).to_lowercase(),
+        )),
+    }
+}
+
+fn matches_arity(arity: i32, args_len: usize) -> bool {
+    let args_len = args_len as i32;
+    match arity {
+        arity if arity < 0 => args_len >= -arity,
+        arity => args_len == arity,
+    }
+}
@@ -1,5 +1,11 @@
-use crate::command::Command;
+use crate::command::bound::{parse_lex_bound, parse_score_bound};
+use crate::command::command_error::RedisCommandError;
+use crate::command::options::{
+    Aggregate, ExpireCondition, KillFilter, RestoreOptions, SetOptions, ZCombineOptions,
+};
+use crate::command::{ClientSubcommand, Command, LatencySubcommand};
 use crate::protocol::Resp;
+use crate::storage::models::{LexBound, ScoreBound};
 
 #[test]
 fn set_command() {
@@ -12,6 +18,712 @@ fn set_command() {
         ];
 
         let command = Command::parse(resp).unwrap();
-        assert_eq!(command, Command::Set(b"mykey".to_vec(), b"value".to_vec()));
+        assert_eq!(
+            command,
+            Command::Set(b"mykey".to_vec(), b"value".to_vec(), SetOptions::default())
+        );
+    }
+}
+
+#[test]
+fn wrong_arity() {
+    let resp = vec![Resp::BulkString(b"GET")];
+
+    let err = Command::parse(resp).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "ERR wrong number of arguments for 'get' command"
+    );
+}
+
+#[test]
+fn mset_odd_arity() {
+    for command in [&b"mset"[..], b"msetnx"] {
+        let resp = vec![
+            Resp::BulkString(command),
+            Resp::BulkString(b"a"),
+            Resp::BulkString(b"1"),
+            Resp::BulkString(b"b"),
+        ];
+        let err = Command::parse(resp).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "ERR wrong number of arguments for '{}' command",
+                String::from_utf8_lossy(command)
+            )
+        );
+    }
+}
+
+#[test]
+fn pairs_arity() {
+    let parse = |args: &[&'static [u8]]| {
+        let resp = args.iter().map(|arg| Resp::BulkString(arg)).collect();
+        Command::parse(resp).unwrap_err().to_string()
+    };
+    // named as sent, even past the arity of the table
+    assert_eq!(
+        parse(&[b"HSET", b"key", b"field"]),
+        "ERR wrong number of arguments for 'hset' command"
+    );
+    assert_eq!(
+        parse(&[b"hmset", b"key", b"field", b"value", b"field"]),
+        "ERR wrong number of arguments for 'hmset' command"
+    );
+    // as in Redis, a score without its member is a syntax error
+    assert_eq!(
+        parse(&[b"ZADD", b"key", b"1", b"a", b"2"]),
+        "ERR syntax error"
+    );
+}
+
+#[test]
+fn empty_command() {
+    let err = Command::parse(vec![]).unwrap_err();
+    assert!(matches!(err, RedisCommandError::InvalidCommand));
+}
+
+#[test]
+fn unknown_command() {
+    let err = Command::parse(vec![
+        Resp::BulkString(b"foo"),
+        Resp::BulkString(b"bar"),
+        Resp::BulkString(b"baz"),
+    ])
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "ERR unknown command 'foo', with args beginning with: 'bar', 'baz'"
+    );
+
+    // only the first 128 chars of the arguments are reported
+    let long = vec![b'a'; 100];
+    let err = Command::parse(vec![
+        Resp::BulkString(b"foo"),
+        Resp::BulkString(&long),
+        Resp::BulkString(&long),
+        Resp::BulkString(b"ignored"),
+    ])
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        RedisCommandError::UnknownCommand(name, args)
+            if name == "foo" && args.len() == 2 && args[1].len() == 28
+    ));
+}
+
+#[test]
+fn linsert_command() {
+    let parse = |place: &'static [u8]| {
+        Command::parse(vec![
+            Resp::BulkString(b"LINSERT"),
+            Resp::BulkString(b"list"),
+            Resp::BulkString(place),
+            Resp::BulkString(b"pivot"),
+            Resp::BulkString(b"value"),
+        ])
+    };
+
+    assert_eq!(
+        parse(b"before").unwrap(),
+        Command::LInsert(b"list".to_vec(), true, b"pivot".to_vec(), b"value".to_vec())
+    );
+    assert_eq!(
+        parse(b"AFTER").unwrap(),
+        Command::LInsert(
+            b"list".to_vec(),
+            false,
+            b"pivot".to_vec(),
+            b"value".to_vec()
+        )
+    );
+    assert!(matches!(
+        parse(b"BESIDE").unwrap_err(),
+        RedisCommandError::SyntaxErr
+    ));
+}
+
+#[test]
+fn smismember_command() {
+    let command = Command::parse(vec![
+        Resp::BulkString(b"SMISMEMBER"),
+        Resp::BulkString(b"set"),
+        Resp::BulkString(b"b"),
+        Resp::BulkString(b"a"),
+        Resp::BulkString(b"b"),
+    ])
+    .unwrap();
+    assert_eq!(
+        command,
+        Command::SMISMember(
+            b"set".to_vec(),
+            vec![b"b".to_vec(), b"a".to_vec(), b"b".to_vec()]
+        )
+    );
+
+    let err = Command::parse(vec![
+        Resp::BulkString(b"SMISMEMBER"),
+        Resp::BulkString(b"set"),
+    ])
+    .unwrap_err();
+    assert!(matches!(err, RedisCommandError::WrongArity(_)));
+}
+
+#[test]
+fn client_kill_command() {
+    let command = Command::parse(vec![
+        Resp::BulkString(b"CLIENT"),
+        Resp::BulkString(b"kill"),
+        Resp::BulkString(b"ADDR"),
+        Resp::BulkString(b"127.0.0.1:6379"),
+        Resp::BulkString(b"id"),
+        Resp::BulkString(b"7"),
+    ])
+    .unwrap();
+    assert_eq!(
+        command,
+        Command::Client(ClientSubcommand::Kill(KillFilter {
+            id: Some(7),
+            addr: Some("127.0.0.1:6379".to_string()),
+        }))
+    );
+
+    for args in [&[][..], &[&b"ID"[..]], &[b"NAME", b"name"]] {
+        let mut v = vec![Resp::BulkString(b"CLIENT"), Resp::BulkString(b"KILL")];
+        v.extend(args.iter().map(|arg| Resp::BulkString(arg)));
+        let err = Command::parse(v).unwrap_err();
+        assert!(matches!(err, RedisCommandError::SyntaxErr));
+    }
+}
+
+#[test]
+fn hexpire_command() {
+    let parse = |args: &[&'static [u8]]| {
+        let mut v = vec![Resp::BulkString(b"HEXPIRE"), Resp::BulkString(b"key")];
+        v.extend(args.iter().map(|arg| Resp::BulkString(arg)));
+        Command::parse(v)
+    };
+
+    match parse(&[b"10", b"gt", b"FIELDS", b"2", b"a", b"b"]).unwrap() {
+        Command::HExpire(key, _, condition, fields) => {
+            assert_eq!(key, b"key".to_vec());
+            assert_eq!(condition, Some(ExpireCondition::Greater));
+            assert_eq!(fields, vec![b"a".to_vec(), b"b".to_vec()]);
+        }
+        command => panic!("unexpected command {:?}", command),
+    }
+    assert!(matches!(
+        parse(&[b"10", b"FIELDS", b"2", b"a"]),
+        Err(RedisCommandError::NumFieldsMismatch)
+    ));
+    assert!(matches!(
+        parse(&[b"10", b"NX", b"XX", b"1", b"a"]),
+        Err(RedisCommandError::SyntaxErr)
+    ));
+    assert!(matches!(
+        Command::parse(vec![
+            Resp::BulkString(b"HTTL"),
+            Resp::BulkString(b"key"),
+            Resp::BulkString(b"FIELDS"),
+            Resp::BulkString(b"1"),
+        ]),
+        Err(RedisCommandError::WrongArity(_))
+    ));
+}
+
+#[test]
+fn restore_command() {
+    let restore = |args: &[&'static [u8]]| {
+        let mut v = vec![
+            Resp::BulkString(b"RESTORE"),
+            Resp::BulkString(b"key"),
+            Resp::BulkString(b"100"),
+            Resp::BulkString(b"payload"),
+        ];
+        v.extend(args.iter().map(|arg| Resp::BulkString(arg)));
+        Command::parse(v)
+    };
+
+    assert_eq!(
+        restore(&[b"replace", b"IDLETIME", b"60", b"ABSTTL"]).unwrap(),
+        Command::Restore(
+            b"key".to_vec(),
+            100,
+            b"payload".to_vec(),
+            RestoreOptions {
+                replace: true,
+                absttl: true,
+                idletime: Some(60),
+                freq: None,
+            }
+        )
+    );
+    assert!(matches!(
+        restore(&[b"FREQ", b"1", b"IDLETIME", b"1"]),
+        Err(RedisCommandError::SyntaxErr)
+    ));
+    assert!(matches!(
+        restore(&[b"IDLETIME", b"-1"]),
+        Err(RedisCommandError::InvalidIdleTime)
+    ));
+    assert!(matches!(
+        restore(&[b"FREQ", b"256"]),
+        Err(RedisCommandError::InvalidFreq)
+    ));
+}
+
+#[test]
+fn zmscore_command() {
+    let command = Command::parse(vec![
+        Resp::BulkString(b"ZMSCORE"),
+        Resp::BulkString(b"zset"),
+        Resp::BulkString(b"b"),
+        Resp::BulkString(b"missing"),
+        Resp::BulkString(b"a"),
+    ])
+    .unwrap();
+    assert_eq!(
+        command,
+        Command::ZMScore(
+            b"zset".to_vec(),
+            vec![b"b".to_vec(), b"missing".to_vec(), b"a".to_vec()]
+        )
+    );
+
+    let err = Command::parse(vec![
+        Resp::BulkString(b"ZMSCORE"),
+        Resp::BulkString(b"zset"),
+    ])
+    .unwrap_err();
+    assert!(matches!(err, RedisCommandError::WrongArity(_)));
+}
+
+#[test]
+fn zunion_command() {
+    let command = Command::parse(vec![
+        Resp::BulkString(b"ZUNION"),
+        Resp::BulkString(b"2"),
+        Resp::BulkString(b"a"),
+        Resp::BulkString(b"b"),
+        Resp::BulkString(b"WEIGHTS"),
+        Resp::BulkString(b"2"),
+        Resp::BulkString(b"0.5"),
+        Resp::BulkString(b"aggregate"),
+        Resp::BulkString(b"max"),
+    ])
+    .unwrap();
+    assert_eq!(
+        command,
+        Command::ZUnion(
+            vec![b"a".to_vec(), b"b".to_vec()],
+            ZCombineOptions {
+                weights: Some(vec![2.0, 0.5]),
+                aggregate: Aggregate::Max,
+                with_scores: false,
+            }
+        )
+    );
+
+    // one weight per key
+    let err = Command::parse(vec![
+        Resp::BulkString(b"ZINTER"),
+        Resp::BulkString(b"2"),
+        Resp::BulkString(b"a"),
+        Resp::BulkString(b"b"),
+        Resp::BulkString(b"WEIGHTS"),
+        Resp::BulkString(b"2"),
+    ])
+    .unwrap_err();
+    assert!(matches!(err, RedisCommandError::SyntaxErr));
+    let err = Command::parse(vec![
+        Resp::BulkString(b"ZDIFF"),
+        Resp::BulkString(b"3"),
+        Resp::BulkString(b"a"),
+        Resp::BulkString(b"b"),
+    ])
+    .unwrap_err();
+    assert!(matches!(err, RedisCommandError::NumKeysGreaterThanArgs));
+}
+
+#[test]
+fn blpop_command() {
+    let command = Command::parse(vec![
+        Resp::BulkString(b"BLPOP"),
+        Resp::BulkString(b"first"),
+        Resp::BulkString(b"second"),
+        Resp::BulkString(b"0.5"),
+    ])
+    .unwrap();
+    assert_eq!(
+        command,
+        Command::BLPop(vec![b"first".to_vec(), b"second".to_vec()], 0.5)
+    );
+
+    let err = Command::parse(vec![
+        Resp::BulkString(b"BRPOP"),
+        Resp::BulkString(b"key"),
+        Resp::BulkString(b"soon"),
+    ])
+    .unwrap_err();
+    assert!(matches!(err, RedisCommandError::InvalidTimeout));
+}
+
+#[test]
+fn waitkey_command() {
+    let command = Command::parse(vec![
+        Resp::BulkString(b"WAITKEY"),
+        Resp::BulkString(b"key"),
+        Resp::BulkString(b"1.5"),
+    ])
+    .unwrap();
+    assert_eq!(command, Command::WaitKey(b"key".to_vec(), 1.5));
+
+    let err = Command::parse(vec![
+        Resp::BulkString(b"WAITKEY"),
+        Resp::BulkString(b"first"),
+        Resp::BulkString(b"second"),
+        Resp::BulkString(b"1"),
+    ])
+    .unwrap_err();
+    assert!(matches!(err, RedisCommandError::WrongArity(_)));
+}
+
+#[test]
+fn key_positions() {
+    use crate::command::key_positions;
+
+    let args = |args: &[&str]| -> Vec<Vec<u8>> {
+        args.iter().map(|arg| arg.as_bytes().to_vec()).collect()
+    };
+
+    assert_eq!(key_positions(&args(&["GET", "key"])).unwrap(), vec![1]);
+    assert_eq!(
+        key_positions(&args(&["mset", "a", "1", "b", "2"])).unwrap(),
+        vec![1, 3]
+    );
+    assert_eq!(
+        key_positions(&args(&["BITOP", "AND", "dest", "a", "b"])).unwrap(),
+        vec![2, 3, 4]
+    );
+    assert_eq!(
+        key_positions(&args(&["BLPOP", "a", "b", "0"])).unwrap(),
+        vec![1, 2]
+    );
+    assert_eq!(
+        key_positions(&args(&["SINTERCARD", "2", "a", "b", "LIMIT", "1"])).unwrap(),
+        vec![2, 3]
+    );
+
+    assert!(matches!(
+        key_positions(&args(&["PING"])).unwrap_err(),
+        RedisCommandError::NoKeyArguments
+    ));
+    assert!(matches!(
+        key_positions(&args(&["GET"])).unwrap_err(),
+        RedisCommandError::GetKeysInvalidArgs
+    ));
+    assert!(matches!(
+        key_positions(&args(&["SINTERCARD", "3", "a", "b"])).unwrap_err(),
+        RedisCommandError::GetKeysInvalidArgs
+    ));
+    assert!(matches!(
+        key_positions(&args(&["NOPE", "key"])).unwrap_err(),
+        RedisCommandError::GetKeysInvalidCommand
+    ));
+}
+
+#[test]
+fn command_specs() {
+    use crate::command::{command_spec, command_specs};
+
+    let set = command_spec(b"set").unwrap();
+    assert_eq!(set.name, "set");
+    assert_eq!((set.arity, set.flags), (-3, vec!["write", "denyoom"]));
+    // freeing memory is allowed past maxmemory
+    assert_eq!(command_spec(b"DEL").unwrap().flags, vec!["write"]);
+    assert_eq!((set.first_key, set.last_key, set.step), (1, 1, 1));
+
+    let get = command_spec(b"GET").unwrap();
+    assert_eq!((get.arity, get.flags), (2, vec!["readonly"]));
+    assert!(command_spec(b"PING").unwrap().flags.is_empty());
+    let sintercard = command_spec(b"SINTERCARD").unwrap();
+    assert_eq!(sintercard.flags, vec!["readonly", "movablekeys"]);
+    assert_eq!((sintercard.first_key, sintercard.last_key), (0, 0));
+    assert!(command_spec(b"NOPE").is_none());
+
+    let names: Vec<_> = command_specs().into_iter().map(|spec| spec.name).collect();
+    assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+#[test]
+fn pubsub_command() {
+    use crate::command::PubSubSubcommand::*;
+
+    let parse = |args: &[&'static [u8]]| {
+        Command::parse(args.iter().map(|arg| Resp::BulkString(arg)).collect())
+    };
+
+    assert_eq!(
+        parse(&[b"pubsub", b"channels"]).unwrap(),
+        Command::PubSub(Channels(None))
+    );
+    assert_eq!(
+        parse(&[b"PUBSUB", b"CHANNELS", b"n*"]).unwrap(),
+        Command::PubSub(Channels(Some(b"n*".to_vec())))
+    );
+    assert_eq!(
+        parse(&[b"PUBSUB", b"NUMSUB"]).unwrap(),
+        Command::PubSub(NumSub(vec![]))
+    );
+    assert_eq!(
+        parse(&[b"PUBSUB", b"NUMPAT"]).unwrap(),
+        Command::PubSub(NumPat)
+    );
+    assert!(parse(&[b"PUBSUB", b"NUMPAT", b"x"]).is_err());
+}
+
+#[test]
+fn hello_command() {
+    use crate::command::options::HelloOptions;
+
+    let parse = |args: &[&'static [u8]]| {
+        Command::parse(args.iter().map(|arg| Resp::BulkString(arg)).collect())
+    };
+
+    assert_eq!(
+        parse(&[b"HELLO"]).unwrap(),
+        Command::Hello(HelloOptions::default())
+    );
+    assert_eq!(
+        parse(&[b"hello", b"3", b"auth", b"user", b"pass", b"setname", b"app"]).unwrap(),
+        Command::Hello(HelloOptions {
+            protocol: Some(3),
+            auth: Some(("user".to_string(), "pass".to_string())),
+            setname: Some("app".to_string()),
+        })
+    );
+    assert!(matches!(
+        parse(&[b"HELLO", b"4"]).unwrap_err(),
+        RedisCommandError::NoProto
+    ));
+    assert!(matches!(
+        parse(&[b"HELLO", b"3", b"AUTH", b"user"]).unwrap_err(),
+        RedisCommandError::SyntaxErr
+    ));
+}
+
+#[test]
+fn acl_command() {
+    use crate::command::options::AclRule;
+    use crate::command::AclSubcommand;
+    let parse = |args: &[&'static [u8]]| {
+        Command::parse(args.iter().map(|arg| Resp::BulkString(arg)).collect())
+    };
+
+    assert_eq!(
+        parse(&[
+            b"ACL",
+            b"SETUSER",
+            b"alice",
+            b"on",
+            b">Secret",
+            b"+@all",
+            b"-FlushAll"
+        ])
+        .unwrap(),
+        Command::Acl(AclSubcommand::SetUser(
+            "alice".to_string(),
+            vec![
+                AclRule::On,
+                AclRule::AddPassword("Secret".to_string()),
+                AclRule::AllCommands,
+                AclRule::Deny("FLUSHALL".to_string()),
+            ]
+        ))
+    );
+    assert_eq!(
+        parse(&[b"acl", b"whoami"]).unwrap(),
+        Command::Acl(AclSubcommand::WhoAmI)
+    );
+    assert!(matches!(
+        parse(&[b"ACL", b"SETUSER", b"alice", b"+@read"]).unwrap_err(),
+        RedisCommandError::AclSyntax(rule) if rule == "+@read"
+    ));
+    assert!(matches!(
+        parse(&[b"ACL", b"LIST", b"alice"]).unwrap_err(),
+        RedisCommandError::SyntaxErr
+    ));
+}
+
+#[test]
+fn container_help() {
+    use crate::command::help;
+
+    let parse = |args: &[&'static [u8]]| {
+        Command::parse(args.iter().map(|arg| Resp::BulkString(arg)).collect())
+    };
+
+    for command in ["OBJECT", "CONFIG", "CLIENT", "DEBUG"] {
+        let lowercase = command.to_lowercase();
+        assert_eq!(
+            Command::parse(vec![
+                Resp::BulkString(lowercase.as_bytes()),
+                Resp::BulkString(b"help")
+            ])
+            .unwrap(),
+            Command::Help(command)
+        );
+        let lines = help(command);
+        assert!(lines[0].starts_with(command));
+        assert_eq!(lines[lines.len() - 2..], ["HELP", "    Print this help."]);
+    }
+
+    assert!(matches!(
+        parse(&[b"OBJECT", b"FOO", b"key"]).unwrap_err(),
+        RedisCommandError::UnknownSubcommand(subcommand, "OBJECT") if subcommand == "FOO"
+    ));
+    // a known subcommand with the wrong number of arguments
+    assert!(matches!(
+        parse(&[b"OBJECT", b"REFCOUNT"]).unwrap_err(),
+        RedisCommandError::UnknownSubcommand(subcommand, "OBJECT") if subcommand == "REFCOUNT"
+    ));
+    assert!(matches!(
+        parse(&[b"CONFIG", b"HELP", b"more"]).unwrap_err(),
+        RedisCommandError::UnknownSubcommand(_, "CONFIG")
+    ));
+}
+
+#[test]
+fn zpop_command() {
+    let command = Command::parse(vec![
+        Resp::BulkString(b"ZPOPMIN"),
+        Resp::BulkString(b"zset"),
+    ])
+    .unwrap();
+    assert_eq!(command, Command::ZPopMin(b"zset".to_vec(), None));
+
+    let command = Command::parse(vec![
+        Resp::BulkString(b"ZPOPMAX"),
+        Resp::BulkString(b"zset"),
+        Resp::BulkString(b"2"),
+    ])
+    .unwrap();
+    assert_eq!(command, Command::ZPopMax(b"zset".to_vec(), Some(2)));
+
+    let err = Command::parse(vec![
+        Resp::BulkString(b"ZPOPMAX"),
+        Resp::BulkString(b"zset"),
+        Resp::BulkString(b"2"),
+        Resp::BulkString(b"3"),
+    ])
+    .unwrap_err();
+    assert!(matches!(err, RedisCommandError::SyntaxErr));
+}
+
+#[test]
+fn latency_command() {
+    let command = Command::parse(vec![
+        Resp::BulkString(b"LATENCY"),
+        Resp::BulkString(b"history"),
+        Resp::BulkString(b"Command"),
+    ])
+    .unwrap();
+    assert_eq!(
+        command,
+        Command::Latency(LatencySubcommand::History("command".to_string()))
+    );
+
+    let command = Command::parse(vec![
+        Resp::BulkString(b"LATENCY"),
+        Resp::BulkString(b"RESET"),
+    ])
+    .unwrap();
+    assert_eq!(command, Command::Latency(LatencySubcommand::Reset(vec![])));
+
+    let err = Command::parse(vec![
+        Resp::BulkString(b"LATENCY"),
+        Resp::BulkString(b"LATEST"),
+        Resp::BulkString(b"command"),
+    ])
+    .unwrap_err();
+    assert!(matches!(err, RedisCommandError::UnknownSubcommand(..)));
+}
+
+#[test]
+fn move_command() {
+    let command = Command::parse(vec![
+        Resp::BulkString(b"MOVE"),
+        Resp::BulkString(b"key"),
+        Resp::BulkString(b"3"),
+    ])
+    .unwrap();
+    assert_eq!(command, Command::Move(b"key".to_vec(), 3));
+
+    let err = Command::parse(vec![
+        Resp::BulkString(b"MOVE"),
+        Resp::BulkString(b"key"),
+        Resp::BulkString(b"db"),
+    ])
+    .unwrap_err();
+    assert!(matches!(err, RedisCommandError::NotAnInteger));
+}
+
+#[test]
+fn command_docs() {
+    use crate::command::{command_doc, command_docs, command_specs};
+
+    let get = command_doc(b"GET").unwrap();
+    assert_eq!((get.name.as_str(), get.group), ("get", "string"));
+    assert_eq!(get.arguments.len(), 1);
+    assert_eq!(get.arguments[0].name, "key");
+    assert_eq!(get.arguments[0].kind.name(), "key");
+    assert!(command_doc(b"NOPE").is_none());
+
+    // every command of the table is documented
+    let documented: Vec<_> = command_docs().into_iter().map(|doc| doc.name).collect();
+    let commands: Vec<_> = command_specs().into_iter().map(|spec| spec.name).collect();
+    assert_eq!(documented, commands);
+
+    assert_eq!(
+        Command::parse(vec![
+            Resp::BulkString(b"command"),
+            Resp::BulkString(b"docs"),
+            Resp::BulkString(b"get"),
+        ])
+        .unwrap(),
+        Command::CommandDocs(vec![b"get".to_vec()])
+    );
+}
+
+#[test]
+fn range_bounds() {
+    let score = |bound: &[u8]| parse_score_bound(bound.to_vec());
+    let bound = |score, exclusive| ScoreBound { score, exclusive };
+    assert_eq!(score(b"1.5").unwrap(), bound(1.5, false));
+    assert_eq!(score(b"(1.5").unwrap(), bound(1.5, true));
+    assert_eq!(score(b"-3").unwrap(), bound(-3.0, false));
+    assert_eq!(score(b"-inf").unwrap(), bound(f64::NEG_INFINITY, false));
+    assert_eq!(score(b"+inf").unwrap(), bound(f64::INFINITY, false));
+    assert_eq!(score(b"inf").unwrap(), bound(f64::INFINITY, false));
+    assert_eq!(score(b"(-inf").unwrap(), bound(f64::NEG_INFINITY, true));
+    assert_eq!(score(b"(+inf").unwrap(), bound(f64::INFINITY, true));
+    for invalid in [&b""[..], b"(", b"[1", b"nan", b"(nan", b"1.5)", b"\xff"] {
+        assert!(matches!(
+            score(invalid),
+            Err(RedisCommandError::InvalidScoreRange)
+        ));
+    }
+
+    let lex = |bound: &[u8]| parse_lex_bound(bound.to_vec());
+    assert_eq!(lex(b"-").unwrap(), LexBound::Min);
+    assert_eq!(lex(b"+").unwrap(), LexBound::Max);
+    assert_eq!(lex(b"[a").unwrap(), LexBound::Inclusive(b"a".to_vec()));
+    assert_eq!(lex(b"(a").unwrap(), LexBound::Exclusive(b"a".to_vec()));
+    // the empty member, as low as `-` when inclusive
+    assert_eq!(lex(b"[").unwrap(), LexBound::Inclusive(vec![]));
+    assert_eq!(lex(b"(-").unwrap(), LexBound::Exclusive(b"-".to_vec()));
+    for invalid in [&b""[..], b"a", b"--", b"+a", b"-inf"] {
+        assert!(matches!(lex(invalid), Err(RedisCommandError::SyntaxErr)));
     }
 }
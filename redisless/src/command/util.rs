@@ -1,5 +1,15 @@
+use std::convert::TryFrom;
+
+use super::bound::{parse_lex_bound, parse_score_bound};
 use super::command_error::RedisCommandError;
+use super::options::{
+    AclRule, Aggregate, ExpireCondition, FlushMode, GetExOption, HelloOptions, KillFilter,
+    LPosOptions, LcsOptions, RestoreOptions, ScanOptions, ScoreComparison, SetCondition,
+    SetOptions, ShutdownMode, SortOptions, ZAddOptions, ZCombineOptions, ZRangeBy, ZRangeOptions,
+};
+use super::{ScoreMembers, Side};
 use crate::protocol::Resp;
+use crate::storage::models::{parse_i64, parse_u64, Expiry};
 
 pub fn get_bytes_vec(resp: Option<&Resp>) -> Result<Vec<u8>, RedisCommandError> {
     match resp {
@@ -8,12 +18,696 @@ pub fn get_bytes_vec(resp: Option<&Resp>) -> Result<Vec<u8>, RedisCommandError>
     }
 }
 
+// chars of the name and of the arguments reported in the error of an unknown command, as in
+// Redis
+const UNKNOWN_COMMAND_REPORTED_LEN: usize = 128;
+
+/// Error of the unknown command v, reporting its name and its first arguments
+pub fn unknown_command(v: &[Resp]) -> RedisCommandError {
+    let mut args = v.iter().map(|arg| match arg {
+        Resp::String(arg) | Resp::BulkString(arg) => String::from_utf8_lossy(arg).to_string(),
+        _ => String::new(),
+    });
+    let truncated = |arg: String, len: usize| arg.chars().take(len).collect::<String>();
+    let name = truncated(
+        args.next().unwrap_or_default(),
+        UNKNOWN_COMMAND_REPORTED_LEN,
+    );
+
+    let mut reported = vec![];
+    let mut len = 0;
+    for arg in args {
+        if len >= UNKNOWN_COMMAND_REPORTED_LEN {
+            break;
+        }
+        let arg = truncated(arg, UNKNOWN_COMMAND_REPORTED_LEN - len);
+        len += arg.chars().count();
+        reported.push(arg);
+    }
+    RedisCommandError::UnknownCommand(name, reported)
+}
+
+/// Arguments taken as they are, such as the channels of a `SUBSCRIBE`
+pub fn get_args(args: &[Resp]) -> Result<Vec<Vec<u8>>, RedisCommandError> {
+    args.iter().map(|arg| get_bytes_vec(Some(arg))).collect()
+}
+
+// the integer arguments are parsed as strictly as the integers stored in strings
 pub fn parse_duration(bytes: Vec<u8>) -> Result<u64, RedisCommandError> {
-    let duration = std::str::from_utf8(&bytes[..])?;
-    Ok(duration.parse::<u64>()?)
+    parse_u64(&bytes).ok_or(RedisCommandError::NotAnInteger)
 }
 
 pub fn parse_variation(bytes: Vec<u8>) -> Result<i64, RedisCommandError> {
-    let delta = std::str::from_utf8(&bytes[..])?;
-    Ok(delta.parse::<i64>()?)
+    parse_i64(&bytes).ok_or(RedisCommandError::NotAnInteger)
+}
+
+pub fn parse_float(bytes: Vec<u8>) -> Result<f64, RedisCommandError> {
+    let float = std::str::from_utf8(&bytes[..])?;
+    match float.parse::<f64>() {
+        Ok(float) if !float.is_nan() => Ok(float),
+        _ => Err(RedisCommandError::InvalidFloat),
+    }
+}
+
+/// Parse the `key [key ...] timeout` arguments of a blocking command
+pub fn parse_blocking_args(args: &[Resp]) -> Result<(Vec<Vec<u8>>, f64), RedisCommandError> {
+    let (timeout, keys) = match args.split_last() {
+        Some((timeout, keys)) if !keys.is_empty() => (timeout, keys),
+        _ => return Err(RedisCommandError::ArgNumber),
+    };
+    let timeout = get_bytes_vec(Some(timeout)).and_then(parse_timeout)?;
+    let keys = keys
+        .iter()
+        .map(|key| get_bytes_vec(Some(key)))
+        .collect::<Result<_, _>>()?;
+    Ok((keys, timeout))
+}
+
+/// Parse the timeout in secs of a blocking command, 0 to block forever
+pub fn parse_timeout(bytes: Vec<u8>) -> Result<f64, RedisCommandError> {
+    let timeout = match parse_float(bytes) {
+        Ok(timeout) if timeout.is_finite() => timeout,
+        _ => return Err(RedisCommandError::InvalidTimeout),
+    };
+    if timeout < 0.0 {
+        return Err(RedisCommandError::NegativeTimeout);
+    }
+    Ok(timeout)
+}
+
+/// Parse the `LEFT` / `RIGHT` end of a list
+pub fn parse_side(bytes: Vec<u8>) -> Result<Side, RedisCommandError> {
+    match bytes.to_ascii_uppercase().as_slice() {
+        b"LEFT" => Ok(Side::Left),
+        b"RIGHT" => Ok(Side::Right),
+        _ => Err(RedisCommandError::SyntaxErr),
+    }
+}
+
+/// Parse the optional `count` of the pop commands
+pub fn parse_pop_count(args: &[Resp]) -> Result<Option<u64>, RedisCommandError> {
+    match args {
+        [] => Ok(None),
+        [count] => {
+            let count = get_bytes_vec(Some(count)).and_then(parse_variation)?;
+            Ok(Some(
+                u64::try_from(count).map_err(|_| RedisCommandError::ValueNotPositive)?,
+            ))
+        }
+        _ => Err(RedisCommandError::SyntaxErr),
+    }
+}
+
+/// Parse the optional `LIMIT offset count` of the range commands
+pub fn parse_range_limit(args: &[Resp]) -> Result<Option<(i64, i64)>, RedisCommandError> {
+    match args {
+        [] => Ok(None),
+        [option, offset, count] if get_bytes_vec(Some(option))?.eq_ignore_ascii_case(b"LIMIT") => {
+            let offset = get_bytes_vec(Some(offset)).and_then(parse_variation)?;
+            let count = get_bytes_vec(Some(count)).and_then(parse_variation)?;
+            Ok(Some((offset, count)))
+        }
+        _ => Err(RedisCommandError::SyntaxErr),
+    }
+}
+
+/// Parse the optional `count [WITH...]` of `HRANDFIELD`, `SRANDMEMBER` and `ZRANDMEMBER`,
+/// the option being `WITHVALUES` or `WITHSCORES` for the commands which have one
+pub fn parse_random_count(
+    args: &[Resp],
+    with: Option<&[u8]>,
+) -> Result<Option<(i64, bool)>, RedisCommandError> {
+    let (count, with) = match (args, with) {
+        ([], _) => return Ok(None),
+        ([count], _) => (count, false),
+        ([count, option], Some(with))
+            if get_bytes_vec(Some(option))?.eq_ignore_ascii_case(with) =>
+        {
+            (count, true)
+        }
+        _ => return Err(RedisCommandError::SyntaxErr),
+    };
+    let count = get_bytes_vec(Some(count)).and_then(parse_variation)?;
+    // as in Redis, so the reply of a negative count can be allocated
+    if count < -(i64::MAX / 2) {
+        return Err(RedisCommandError::ValueOutOfRange);
+    }
+    Ok(Some((count, with)))
+}
+
+/// Parse the `min max [BYSCORE|BYLEX] [REV] [LIMIT offset count]` of `ZRANGESTORE`
+pub fn parse_zrange_options(args: &[Resp]) -> Result<ZRangeOptions, RedisCommandError> {
+    let (start, stop) = match args {
+        [start, stop, ..] => (get_bytes_vec(Some(start))?, get_bytes_vec(Some(stop))?),
+        _ => return Err(RedisCommandError::ArgNumber),
+    };
+    let (mut by_score, mut by_lex, mut rev, mut limit) = (false, false, false, None);
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        match get_bytes_vec(Some(option))?.to_ascii_uppercase().as_slice() {
+            b"BYSCORE" => by_score = true,
+            b"BYLEX" => by_lex = true,
+            b"REV" => rev = true,
+            b"LIMIT" => {
+                let mut next = || {
+                    let arg = options.next().ok_or(RedisCommandError::SyntaxErr)?;
+                    get_bytes_vec(Some(arg)).and_then(parse_variation)
+                };
+                limit = Some((next()?, next()?));
+            }
+            _ => return Err(RedisCommandError::SyntaxErr),
+        }
+    }
+
+    // with `REV` a range by score or member starts from its max
+    let (min, max) = match rev {
+        true => (stop.clone(), start.clone()),
+        false => (start.clone(), stop.clone()),
+    };
+    let by = match (by_score, by_lex) {
+        (true, true) => return Err(RedisCommandError::SyntaxErr),
+        (true, false) => ZRangeBy::Score(parse_score_bound(min)?, parse_score_bound(max)?),
+        (false, true) => ZRangeBy::Lex(parse_lex_bound(min)?, parse_lex_bound(max)?),
+        (false, false) if limit.is_some() => return Err(RedisCommandError::RangeLimitWithoutBy),
+        (false, false) => ZRangeBy::Index(parse_variation(start)?, parse_variation(stop)?),
+    };
+    Ok(ZRangeOptions { by, rev, limit })
+}
+
+/// Parse the optional `ASYNC` / `SYNC` argument of `FLUSHDB` and `FLUSHALL`
+pub fn parse_flush_mode(args: &[Resp]) -> Result<FlushMode, RedisCommandError> {
+    match args {
+        [] => Ok(FlushMode::Sync),
+        [mode] => match get_bytes_vec(Some(mode))?.to_ascii_uppercase().as_slice() {
+            b"SYNC" => Ok(FlushMode::Sync),
+            b"ASYNC" => Ok(FlushMode::Async),
+            _ => Err(RedisCommandError::SyntaxErr),
+        },
+        _ => Err(RedisCommandError::SyntaxErr),
+    }
+}
+
+/// Parse the optional `NOSAVE` / `SAVE` argument of `SHUTDOWN`
+pub fn parse_shutdown_mode(args: &[Resp]) -> Result<ShutdownMode, RedisCommandError> {
+    match args {
+        [] => Ok(ShutdownMode::Default),
+        [mode] => match get_bytes_vec(Some(mode))?.to_ascii_uppercase().as_slice() {
+            b"NOSAVE" => Ok(ShutdownMode::NoSave),
+            b"SAVE" => Ok(ShutdownMode::Save),
+            _ => Err(RedisCommandError::SyntaxErr),
+        },
+        _ => Err(RedisCommandError::SyntaxErr),
+    }
+}
+
+pub fn parse_set_options(args: &[Resp]) -> Result<SetOptions, RedisCommandError> {
+    let mut options = SetOptions::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let arg = get_bytes_vec(Some(arg))?.to_ascii_uppercase();
+        match arg.as_slice() {
+            b"NX" if options.condition.is_none() => {
+                options.condition = Some(SetCondition::NotExists)
+            }
+            b"XX" if options.condition.is_none() => options.condition = Some(SetCondition::Exists),
+            b"GET" => options.get = true,
+            b"KEEPTTL" if options.expiry.is_none() => options.keep_ttl = true,
+            b"EX" | b"PX" if options.expiry.is_none() && !options.keep_ttl => {
+                let duration = args
+                    .next()
+                    .ok_or(RedisCommandError::SyntaxErr)
+                    .and_then(|d| get_bytes_vec(Some(d)))?;
+                let expiry = parse_set_expiry(duration, arg == b"PX", "set")?;
+                options.expiry = Some(expiry);
+            }
+            _ => return Err(RedisCommandError::SyntaxErr),
+        }
+    }
+
+    Ok(options)
+}
+
+/// Expiry of the `EX` secs or `PX` millis of a `SET`, also the one of a `SETEX` or a
+/// `PSETEX`, so the three refuse the same durations. As in Redis, a duration which is not
+/// positive is refused along with one too long to expire at an instant.
+pub fn parse_set_expiry(
+    bytes: Vec<u8>,
+    millis: bool,
+    command: &str,
+) -> Result<Expiry, RedisCommandError> {
+    let invalid = || RedisCommandError::InvalidExpireTime(command.to_string());
+    let duration = match parse_variation(bytes)? {
+        duration if duration <= 0 => return Err(invalid()),
+        duration => duration as u64,
+    };
+    let expiry = match millis {
+        true => Expiry::new_from_millis(duration),
+        false => Expiry::new_from_secs(duration),
+    };
+    expiry.map_err(|_| invalid())
+}
+
+pub fn parse_getex_option(args: &[Resp]) -> Result<Option<GetExOption>, RedisCommandError> {
+    let option = match args.first() {
+        Some(option) => get_bytes_vec(Some(option))?.to_ascii_uppercase(),
+        None => return Ok(None),
+    };
+    match (option.as_slice(), &args[1..]) {
+        (b"PERSIST", []) => Ok(Some(GetExOption::Persist)),
+        (b"EX", [time]) | (b"PX", [time]) | (b"EXAT", [time]) | (b"PXAT", [time]) => {
+            let time = get_bytes_vec(Some(time)).and_then(parse_duration)?;
+            let expiry = match option.as_slice() {
+                b"EX" => Expiry::new_from_secs(time)?,
+                b"PX" => Expiry::new_from_millis(time)?,
+                // a Unix time, which may be past already
+                b"EXAT" => Expiry::at_secs(time)?,
+                _ => Expiry::at_millis(time)?,
+            };
+            Ok(Some(GetExOption::Expiry(expiry)))
+        }
+        _ => Err(RedisCommandError::SyntaxErr),
+    }
+}
+
+pub fn parse_hello_options(args: &[Resp]) -> Result<HelloOptions, RedisCommandError> {
+    let mut options = HelloOptions::default();
+    let mut args = args.iter();
+
+    let protocol = match args.next() {
+        Some(protocol) => get_bytes_vec(Some(protocol)).and_then(parse_variation)?,
+        None => return Ok(options),
+    };
+    options.protocol = match protocol {
+        2 | 3 => Some(protocol as u8),
+        _ => return Err(RedisCommandError::NoProto),
+    };
+    let next_string = |args: &mut std::slice::Iter<Resp>| {
+        let arg = get_bytes_vec(args.next()).map_err(|_| RedisCommandError::SyntaxErr)?;
+        Ok::<_, RedisCommandError>(String::from_utf8(arg).map_err(|err| err.utf8_error())?)
+    };
+    while let Some(option) = args.next() {
+        match get_bytes_vec(Some(option))?.to_ascii_uppercase().as_slice() {
+            b"AUTH" => {
+                let username = next_string(&mut args)?;
+                let password = next_string(&mut args)?;
+                options.auth = Some((username, password));
+            }
+            b"SETNAME" => options.setname = Some(next_string(&mut args)?),
+            _ => return Err(RedisCommandError::SyntaxErr),
+        }
+    }
+
+    Ok(options)
+}
+
+/// Parse a rule of `ACL SETUSER`, such as `on`, `>password` or `-flushall`
+pub fn parse_acl_rule(rule: &str) -> Result<AclRule, RedisCommandError> {
+    let command = |name: &str| match name.is_empty() || name.starts_with('@') {
+        // the categories other than `@all` are not supported
+        true => Err(RedisCommandError::AclSyntax(rule.to_string())),
+        false => Ok(name.to_uppercase()),
+    };
+    match rule.to_lowercase().as_str() {
+        "on" => return Ok(AclRule::On),
+        "off" => return Ok(AclRule::Off),
+        "nopass" => return Ok(AclRule::NoPass),
+        "resetpass" => return Ok(AclRule::ResetPass),
+        "+@all" | "allcommands" => return Ok(AclRule::AllCommands),
+        "-@all" | "nocommands" => return Ok(AclRule::NoCommands),
+        "reset" => return Ok(AclRule::Reset),
+        _ => {}
+    }
+    // passwords are case sensitive
+    let mut chars = rule.chars();
+    match (chars.next(), chars.as_str()) {
+        (Some('>'), password) => Ok(AclRule::AddPassword(password.to_string())),
+        (Some('<'), password) => Ok(AclRule::RemovePassword(password.to_string())),
+        (Some('+'), name) => Ok(AclRule::Allow(command(name)?)),
+        (Some('-'), name) => Ok(AclRule::Deny(command(name)?)),
+        _ => Err(RedisCommandError::AclSyntax(rule.to_string())),
+    }
+}
+
+pub fn parse_kill_filter(args: &[Resp]) -> Result<KillFilter, RedisCommandError> {
+    // a connection is killed from its id or its address, no filter would kill them all
+    if args.is_empty() {
+        return Err(RedisCommandError::SyntaxErr);
+    }
+    let mut filter = KillFilter::default();
+    let mut args = args.iter();
+
+    while let Some(option) = args.next() {
+        let option = get_bytes_vec(Some(option))?.to_ascii_uppercase();
+        let value = args.next().ok_or(RedisCommandError::SyntaxErr)?;
+        let value = get_bytes_vec(Some(value))?;
+
+        match option.as_slice() {
+            b"ID" => match parse_u64(&value) {
+                Some(id) if id > 0 => filter.id = Some(id),
+                _ => return Err(RedisCommandError::InvalidClientId),
+            },
+            b"ADDR" => filter.addr = Some(String::from_utf8_lossy(&value).to_string()),
+            _ => return Err(RedisCommandError::SyntaxErr),
+        }
+    }
+
+    Ok(filter)
+}
+
+pub fn parse_lpos_options(args: &[Resp]) -> Result<LPosOptions, RedisCommandError> {
+    let mut options = LPosOptions::default();
+    let mut args = args.iter();
+
+    while let Some(option) = args.next() {
+        let option = get_bytes_vec(Some(option))?.to_ascii_uppercase();
+        let value = args.next().ok_or(RedisCommandError::SyntaxErr)?;
+        let value = get_bytes_vec(Some(value)).and_then(parse_variation)?;
+
+        match option.as_slice() {
+            b"RANK" => {
+                if value == 0 {
+                    return Err(RedisCommandError::ZeroRank);
+                }
+                options.rank = Some(value);
+            }
+            b"COUNT" => {
+                if value < 0 {
+                    return Err(RedisCommandError::NegativeCount);
+                }
+                options.count = Some(value as usize);
+            }
+            _ => return Err(RedisCommandError::SyntaxErr),
+        }
+    }
+
+    Ok(options)
+}
+
+pub fn parse_lcs_options(args: &[Resp]) -> Result<LcsOptions, RedisCommandError> {
+    let mut options = LcsOptions::default();
+    let mut args = args.iter();
+
+    while let Some(option) = args.next() {
+        let option = get_bytes_vec(Some(option))?.to_ascii_uppercase();
+        match option.as_slice() {
+            b"LEN" => options.len = true,
+            b"IDX" => options.idx = true,
+            b"MINMATCHLEN" => {
+                let value = args.next().ok_or(RedisCommandError::SyntaxErr)?;
+                // as in Redis, a negative length keeps every match
+                let len = get_bytes_vec(Some(value)).and_then(parse_variation)?;
+                options.min_match_len = len.max(0) as usize;
+            }
+            b"WITHMATCHLEN" => options.with_match_len = true,
+            _ => return Err(RedisCommandError::SyntaxErr),
+        }
+    }
+
+    if options.len && options.idx {
+        return Err(RedisCommandError::LcsLenAndIdx);
+    }
+    Ok(options)
+}
+
+/// Parse the `[DB destination-db] [REPLACE]` options of a `COPY`
+pub fn parse_copy_options(args: &[Resp]) -> Result<(Option<u64>, bool), RedisCommandError> {
+    let (mut db, mut replace) = (None, false);
+    let mut args = args.iter();
+
+    while let Some(option) = args.next() {
+        let option = get_bytes_vec(Some(option))?.to_ascii_uppercase();
+        match option.as_slice() {
+            b"DB" => {
+                let value = args.next().ok_or(RedisCommandError::SyntaxErr)?;
+                let index = get_bytes_vec(Some(value)).and_then(parse_variation)?;
+                let index =
+                    u64::try_from(index).map_err(|_| RedisCommandError::DbIndexOutOfRange)?;
+                db = Some(index);
+            }
+            b"REPLACE" => replace = true,
+            _ => return Err(RedisCommandError::SyntaxErr),
+        }
+    }
+
+    Ok((db, replace))
+}
+
+pub fn parse_restore_options(args: &[Resp]) -> Result<RestoreOptions, RedisCommandError> {
+    let mut options = RestoreOptions::default();
+    let mut args = args.iter();
+
+    while let Some(option) = args.next() {
+        let option = get_bytes_vec(Some(option))?.to_ascii_uppercase();
+        match option.as_slice() {
+            b"REPLACE" => options.replace = true,
+            b"ABSTTL" => options.absttl = true,
+            // as in Redis, the idle time and the frequency are exclusive
+            b"IDLETIME" if options.freq.is_none() => {
+                let value = args.next().ok_or(RedisCommandError::SyntaxErr)?;
+                let secs = get_bytes_vec(Some(value)).and_then(parse_variation)?;
+                let secs = u64::try_from(secs).map_err(|_| RedisCommandError::InvalidIdleTime)?;
+                options.idletime = Some(secs);
+            }
+            b"FREQ" if options.idletime.is_none() => {
+                let value = args.next().ok_or(RedisCommandError::SyntaxErr)?;
+                let freq = get_bytes_vec(Some(value)).and_then(parse_variation)?;
+                let freq = u8::try_from(freq).map_err(|_| RedisCommandError::InvalidFreq)?;
+                options.freq = Some(freq);
+            }
+            _ => return Err(RedisCommandError::SyntaxErr),
+        }
+    }
+
+    Ok(options)
+}
+
+/// Parse the `FIELDS numfields field [field ...]` ending the arguments of a command such as
+/// `HTTL`
+pub fn parse_hash_fields(args: &[Resp]) -> Result<Vec<Vec<u8>>, RedisCommandError> {
+    let token = get_bytes_vec(args.first())?;
+    if !token.eq_ignore_ascii_case(b"FIELDS") {
+        return Err(RedisCommandError::SyntaxErr);
+    }
+    let numfields = get_bytes_vec(args.get(1)).and_then(parse_duration)? as usize;
+    let fields = &args[2..];
+    if numfields != fields.len() {
+        return Err(RedisCommandError::NumFieldsMismatch);
+    }
+
+    fields
+        .iter()
+        .map(|field| get_bytes_vec(Some(field)))
+        .collect()
+}
+
+/// Parse the `[NX|XX|GT|LT] FIELDS numfields field [field ...]` following the expiration of
+/// an `HEXPIRE`
+pub fn parse_hexpire_args(
+    args: &[Resp],
+) -> Result<(Option<ExpireCondition>, Vec<Vec<u8>>), RedisCommandError> {
+    let option = get_bytes_vec(args.first())?.to_ascii_uppercase();
+    let condition = match option.as_slice() {
+        b"NX" => ExpireCondition::NoExpiry,
+        b"XX" => ExpireCondition::HasExpiry,
+        b"GT" => ExpireCondition::Greater,
+        b"LT" => ExpireCondition::Less,
+        _ => return Ok((None, parse_hash_fields(args)?)),
+    };
+
+    Ok((Some(condition), parse_hash_fields(&args[1..])?))
+}
+
+pub fn parse_sort_options(args: &[Resp]) -> Result<SortOptions, RedisCommandError> {
+    let mut options = SortOptions::default();
+    let mut args = args.iter();
+
+    while let Some(option) = args.next() {
+        let option = get_bytes_vec(Some(option))?.to_ascii_uppercase();
+        match option.as_slice() {
+            b"ASC" => options.descending = false,
+            b"DESC" => options.descending = true,
+            b"ALPHA" => options.alpha = true,
+            b"LIMIT" => {
+                let mut next = || {
+                    let value = args.next().ok_or(RedisCommandError::SyntaxErr)?;
+                    get_bytes_vec(Some(value)).and_then(parse_variation)
+                };
+                let offset = next()?;
+                let count = next()?;
+                options.limit = Some((offset, count));
+            }
+            b"BY" | b"GET" | b"STORE" => {
+                let option = String::from_utf8_lossy(&option);
+                return Err(RedisCommandError::NotSupported(format!("SORT {}", option)));
+            }
+            _ => return Err(RedisCommandError::SyntaxErr),
+        }
+    }
+
+    Ok(options)
+}
+
+pub fn parse_cursor(bytes: Vec<u8>) -> Result<u64, RedisCommandError> {
+    std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|cursor| cursor.parse::<u64>().ok())
+        .ok_or(RedisCommandError::InvalidCursor)
+}
+
+pub fn parse_scan_options(args: &[Resp]) -> Result<ScanOptions, RedisCommandError> {
+    let mut options = ScanOptions::default();
+    let mut args = args.iter();
+
+    while let Some(option) = args.next() {
+        let option = get_bytes_vec(Some(option))?.to_ascii_uppercase();
+        let value = args.next().ok_or(RedisCommandError::SyntaxErr)?;
+        let value = get_bytes_vec(Some(value))?;
+
+        match option.as_slice() {
+            b"MATCH" => options.pattern = Some(value),
+            b"COUNT" => match parse_variation(value)? {
+                count if count < 1 => return Err(RedisCommandError::SyntaxErr),
+                count => options.count = count as usize,
+            },
+            b"TYPE" => options.key_type = Some(value),
+            _ => return Err(RedisCommandError::SyntaxErr),
+        }
+    }
+
+    Ok(options)
+}
+
+/// Parse `key cursor [MATCH pattern] [COUNT count]` of `HSCAN`, `SSCAN` and `ZSCAN`, which
+/// do not accept the `TYPE` of `SCAN`
+pub fn parse_key_scan_args(
+    args: &[Resp],
+) -> Result<(Vec<u8>, u64, ScanOptions), RedisCommandError> {
+    let key = get_bytes_vec(args.first())?;
+    let cursor = get_bytes_vec(args.get(1)).and_then(parse_cursor)?;
+    let options = parse_scan_options(args.get(2..).unwrap_or_default())?;
+    if options.key_type.is_some() {
+        return Err(RedisCommandError::SyntaxErr);
+    }
+    Ok((key, cursor, options))
+}
+
+/// Parse the options of a `ZADD` and the score / member pairs which follow them
+pub fn parse_zadd_args(args: &[Resp]) -> Result<(ZAddOptions, ScoreMembers), RedisCommandError> {
+    let mut options = ZAddOptions::default();
+    let mut index = 0;
+
+    while let Some(option) = args.get(index) {
+        let option = get_bytes_vec(Some(option))?.to_ascii_uppercase();
+        match option.as_slice() {
+            b"NX" | b"XX" => {
+                let condition = match option.as_slice() {
+                    b"NX" => SetCondition::NotExists,
+                    _ => SetCondition::Exists,
+                };
+                if options.condition.replace(condition).is_some() {
+                    return Err(RedisCommandError::SyntaxErr);
+                }
+            }
+            b"GT" | b"LT" => {
+                let comparison = match option.as_slice() {
+                    b"GT" => ScoreComparison::Greater,
+                    _ => ScoreComparison::Less,
+                };
+                if options.comparison.replace(comparison).is_some() {
+                    return Err(RedisCommandError::SyntaxErr);
+                }
+            }
+            b"CH" => options.changed = true,
+            b"INCR" => options.incr = true,
+            // the first argument which is not an option is the first score
+            _ => break,
+        }
+        index += 1;
+    }
+
+    // GT and LT only apply to existing members, which NX never updates
+    if options.condition == Some(SetCondition::NotExists) && options.comparison.is_some() {
+        return Err(RedisCommandError::SyntaxErr);
+    }
+
+    let pairs = &args[index..];
+    if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+        return Err(RedisCommandError::SyntaxErr);
+    }
+    if options.incr && pairs.len() > 2 {
+        return Err(RedisCommandError::ZAddIncrPairs);
+    }
+
+    let mut members = Vec::with_capacity(pairs.len() / 2);
+    for pair in pairs.chunks_exact(2) {
+        let score = get_bytes_vec(Some(&pair[0])).and_then(parse_float)?;
+        let member = get_bytes_vec(Some(&pair[1]))?;
+        members.push((score, member));
+    }
+
+    Ok((options, members))
+}
+
+/// Parse the `numkeys key [key ...]` which starts the arguments of a command such as
+/// `SINTERCARD`, returning the keys and the arguments following them
+pub fn parse_numkeys<'a, 'b>(
+    args: &'a [Resp<'b>],
+) -> Result<(Vec<Vec<u8>>, &'a [Resp<'b>]), RedisCommandError> {
+    let numkeys = get_bytes_vec(args.first()).and_then(parse_duration)? as usize;
+    if numkeys == 0 {
+        return Err(RedisCommandError::NumKeysNotPositive);
+    }
+    let args = &args[1..];
+    if numkeys > args.len() {
+        return Err(RedisCommandError::NumKeysGreaterThanArgs);
+    }
+
+    let keys = args[..numkeys]
+        .iter()
+        .map(|key| get_bytes_vec(Some(key)))
+        .collect::<Result<_, _>>()?;
+    Ok((keys, &args[numkeys..]))
+}
+
+/// Parse the options following the `numkeys` keys of a `ZUNION` or a `ZINTER`
+pub fn parse_zcombine_options(
+    args: &[Resp],
+    numkeys: usize,
+) -> Result<ZCombineOptions, RedisCommandError> {
+    let mut options = ZCombineOptions::default();
+    let mut args = args.iter();
+
+    while let Some(option) = args.next() {
+        let option = get_bytes_vec(Some(option))?.to_ascii_uppercase();
+        match option.as_slice() {
+            b"WEIGHTS" => {
+                let weights = (0..numkeys)
+                    .map(|_| {
+                        let weight = args.next().ok_or(RedisCommandError::SyntaxErr)?;
+                        get_bytes_vec(Some(weight))
+                            .and_then(parse_float)
+                            .map_err(|_| RedisCommandError::InvalidWeight)
+                    })
+                    .collect::<Result<_, _>>()?;
+                options.weights = Some(weights);
+            }
+            b"AGGREGATE" => {
+                let aggregate = args.next().ok_or(RedisCommandError::SyntaxErr)?;
+                options.aggregate = match get_bytes_vec(Some(aggregate))?
+                    .to_ascii_uppercase()
+                    .as_slice()
+                {
+                    b"SUM" => Aggregate::Sum,
+                    b"MIN" => Aggregate::Min,
+                    b"MAX" => Aggregate::Max,
+                    _ => return Err(RedisCommandError::SyntaxErr),
+                };
+            }
+            b"WITHSCORES" => options.with_scores = true,
+            _ => return Err(RedisCommandError::SyntaxErr),
+        }
+    }
+
+    Ok(options)
 }
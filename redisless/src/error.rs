@@ -1,4 +1,4 @@
-use std::io::{Error, ErrorKind};
+use std::io::Error;
 
 use rayon::ThreadPoolBuildError;
 
@@ -6,6 +6,6 @@ pub struct MyThreadPoolBuildError(ThreadPoolBuildError);
 
 impl From<MyThreadPoolBuildError> for Error {
     fn from(err: MyThreadPoolBuildError) -> Self {
-        Error::new(ErrorKind::Other, err.0.to_string())
+        Error::other(err.0.to_string())
     }
 }
@@ -16,6 +16,21 @@ mod protocol;
 pub mod server;
 pub mod storage;
 
+// decoding of the RESP frames read from a stream, by a client of any async runtime
+pub use protocol::error::RedisError;
+pub use protocol::{Resp, RespDecoder};
+
+// decompression of the bulk strings of a client which opted in with
+// `CLIENT REDISLESS-COMPRESS`
+pub use protocol::compression::{decompress, COMPRESSED_FORMAT};
+
+// what `benches/dispatch.rs` parses with, not part of the API of the crate
+#[doc(hidden)]
+pub mod bench {
+    pub use crate::command::Command;
+    pub use crate::protocol::{parser::RedisProtocolParser, Resp};
+}
+
 #[allow(clippy::missing_safety_doc)]
 #[no_mangle]
 pub unsafe extern "C" fn redisless_server_new(port: u16) -> *mut Server {
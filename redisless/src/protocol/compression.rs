@@ -0,0 +1,85 @@
+/// Format of the RESP3 verbatim strings of the bulk strings compressed for a client which
+/// opted in with `CLIENT REDISLESS-COMPRESS`, not a format of Redis
+pub const COMPRESSED_FORMAT: &[u8; 3] = b"rlz";
+
+// a match is at least this long, a shorter one is written as literals
+const MIN_MATCH: usize = 4;
+const MAX_MATCH: usize = MIN_MATCH + 0x7f;
+const MAX_LITERALS: usize = 0x80;
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 14;
+
+/// Compress bytes with a byte oriented LZ77, decompressed by `decompress`.
+///
+/// The compressed bytes are a sequence of tokens. A token byte below `0x80` is followed by
+/// that many literal bytes plus one, and a token from `0x80` is a match of its low 7 bits plus
+/// 4 bytes, copied from the output at the distance of the little endian `u16` which follows.
+pub fn compress(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len() / 2);
+    // the last position of each hash of 4 bytes
+    let mut positions = vec![usize::MAX; 1 << HASH_BITS];
+    let mut literals = 0;
+    let mut i = 0;
+    while i + MIN_MATCH <= bytes.len() {
+        let hash = hash(&bytes[i..i + MIN_MATCH]);
+        let candidate = std::mem::replace(&mut positions[hash], i);
+        let len = match candidate {
+            candidate if candidate != usize::MAX && i - candidate <= MAX_OFFSET => bytes[i..]
+                .iter()
+                .zip(&bytes[candidate..])
+                .take(MAX_MATCH)
+                .take_while(|(a, b)| a == b)
+                .count(),
+            _ => 0,
+        };
+        if len < MIN_MATCH {
+            i += 1;
+            continue;
+        }
+        write_literals(&mut out, &bytes[literals..i]);
+        out.push(0x80 | (len - MIN_MATCH) as u8);
+        out.extend_from_slice(&((i - candidate) as u16).to_le_bytes());
+        i += len;
+        literals = i;
+    }
+    write_literals(&mut out, &bytes[literals..]);
+    out
+}
+
+/// Decompress the bytes of `compress`, such as the text of a verbatim string of the
+/// `COMPRESSED_FORMAT`. `None` when they are not compressed bytes.
+pub fn decompress(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(bytes.len() * 2);
+    let mut i = 0;
+    while let Some(&token) = bytes.get(i) {
+        if token < 0x80 {
+            let literals = bytes.get(i + 1..i + 2 + token as usize)?;
+            out.extend_from_slice(literals);
+            i += 2 + token as usize;
+            continue;
+        }
+        let offset = u16::from_le_bytes([*bytes.get(i + 1)?, *bytes.get(i + 2)?]) as usize;
+        if offset == 0 || offset > out.len() {
+            return None;
+        }
+        // a match may overlap the bytes it copies, as the run of a repeated byte does
+        let start = out.len() - offset;
+        for j in 0..(token & 0x7f) as usize + MIN_MATCH {
+            out.push(out[start + j]);
+        }
+        i += 3;
+    }
+    Some(out)
+}
+
+fn write_literals(out: &mut Vec<u8>, literals: &[u8]) {
+    for chunk in literals.chunks(MAX_LITERALS) {
+        out.push((chunk.len() - 1) as u8);
+        out.extend_from_slice(chunk);
+    }
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    (word.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
@@ -0,0 +1,53 @@
+use super::error::RedisError;
+use super::parser::RedisProtocolParser;
+use super::Resp;
+
+/// Incremental decoding of RESP frames from a stream read in chunks, such as the bytes of an
+/// `AsyncRead` of any runtime. The bytes fed are buffered until they hold a complete frame,
+/// and the bytes of a partial frame are kept for the next chunks.
+#[derive(Debug, Default)]
+pub struct RespDecoder {
+    buffer: Vec<u8>,
+    // bytes of the last frame returned, dropped from the buffer once it is no longer
+    // borrowed, by the next call
+    consumed: usize,
+}
+
+impl RespDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append the bytes received next to the buffer
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.drain_consumed();
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// The next complete frame of the buffer, `None` until enough bytes are fed. After an
+    /// error the rest of the buffer can't be trusted, it is dropped.
+    pub fn next_frame(&mut self) -> Option<Result<Resp<'_>, RedisError>> {
+        self.drain_consumed();
+        match RedisProtocolParser::parse(&self.buffer) {
+            Ok((frame, left)) => {
+                self.consumed = self.buffer.len() - left.len();
+                Some(Ok(frame))
+            }
+            Err(err) if err.is_incomplete() => None,
+            Err(err) => {
+                self.consumed = self.buffer.len();
+                Some(Err(err))
+            }
+        }
+    }
+
+    /// Bytes buffered which are not part of a frame returned yet
+    pub fn pending(&self) -> usize {
+        self.buffer.len() - self.consumed
+    }
+
+    fn drain_consumed(&mut self) {
+        self.buffer.drain(..self.consumed);
+        self.consumed = 0;
+    }
+}
@@ -12,6 +12,12 @@ pub enum RedisErrorType {
     NoCrlf,
     // Incorrect format detected
     IncorrectFormat,
+    // The input ends before the end of a bulk string
+    Incomplete,
+    // A bulk string is declared longer than the max bulk length
+    InvalidBulkLength,
+    // An inline request is longer than `PROTO_INLINE_MAX_SIZE`
+    TooBigInlineRequest,
     Other(Box<dyn std::error::Error>),
 }
 
@@ -38,17 +44,52 @@ impl RedisError {
             err_type: RedisErrorType::IncorrectFormat,
         }
     }
+
+    pub fn incomplete() -> Self {
+        Self {
+            err_type: RedisErrorType::Incomplete,
+        }
+    }
+
+    pub fn invalid_bulk_length() -> Self {
+        Self {
+            err_type: RedisErrorType::InvalidBulkLength,
+        }
+    }
+
+    pub fn too_big_inline_request() -> Self {
+        Self {
+            err_type: RedisErrorType::TooBigInlineRequest,
+        }
+    }
+
+    /// Whether the input may be valid once more bytes are received
+    pub fn is_incomplete(&self) -> bool {
+        matches!(
+            self.err_type,
+            RedisErrorType::EmptyInput | RedisErrorType::NoCrlf | RedisErrorType::Incomplete
+        )
+    }
 }
 
-impl<'a> std::fmt::Display for RedisError {
+impl std::fmt::Display for RedisError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self)
+        match &self.err_type {
+            RedisErrorType::UnknownSymbol => write!(f, "unknown symbol"),
+            RedisErrorType::EmptyInput => write!(f, "empty input"),
+            RedisErrorType::NoCrlf => write!(f, "no CRLF found"),
+            RedisErrorType::IncorrectFormat => write!(f, "incorrect format"),
+            RedisErrorType::Incomplete => write!(f, "incomplete input"),
+            RedisErrorType::InvalidBulkLength => write!(f, "invalid bulk length"),
+            RedisErrorType::TooBigInlineRequest => write!(f, "too big inline request"),
+            RedisErrorType::Other(err) => write!(f, "{}", err),
+        }
     }
 }
 
-impl<'a> std::error::Error for RedisError {}
+impl std::error::Error for RedisError {}
 
-impl<'a> From<std::str::Utf8Error> for RedisError {
+impl From<std::str::Utf8Error> for RedisError {
     fn from(from: std::str::Utf8Error) -> Self {
         Self {
             err_type: RedisErrorType::Other(Box::new(from)),
@@ -56,7 +97,7 @@ impl<'a> From<std::str::Utf8Error> for RedisError {
     }
 }
 
-impl<'a> From<std::num::ParseIntError> for RedisError {
+impl From<std::num::ParseIntError> for RedisError {
     fn from(from: std::num::ParseIntError) -> Self {
         Self {
             err_type: RedisErrorType::Other(Box::new(from)),
@@ -1,10 +1,13 @@
 #[cfg(test)]
 mod tests;
 
+pub mod compression;
+mod decoder;
 pub mod error;
 pub mod parser;
 pub mod response;
 
+pub use decoder::RespDecoder;
 use error::RedisError;
 
 pub type Result<'a> = std::result::Result<(Resp<'a>, &'a [u8]), RedisError>;
@@ -16,6 +19,8 @@ const LF: u8 = b'\n';
 pub const OK: &[u8; 5] = b"+OK\r\n";
 pub const PONG: &[u8; 7] = b"+PONG\r\n";
 pub const NIL: &[u8; 5] = b"$-1\r\n";
+// the nil of RESP3
+pub const NULL: &[u8; 3] = b"_\r\n";
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum Resp<'a> {
@@ -24,5 +29,35 @@ pub enum Resp<'a> {
     Integer(&'a [u8]),
     BulkString(&'a [u8]),
     Array(Vec<Resp<'a>>),
+    // `*-1\r\n`
+    NullArray,
     Nil,
 }
+
+impl Resp<'_> {
+    /// Append the canonical RESP2 bytes of the value to bytes. For any well formed value,
+    /// `RedisProtocolParser::parse` reads them back as an equal value.
+    pub fn serialize(&self, bytes: &mut Vec<u8>) {
+        let (symbol, data) = match self {
+            Resp::String(data) => (b'+', data),
+            Resp::Error(data) => (b'-', data),
+            Resp::Integer(data) => (b':', data),
+            Resp::BulkString(data) => {
+                bytes.extend_from_slice(format!("${}\r\n", data.len()).as_bytes());
+                bytes.extend_from_slice(data);
+                bytes.extend_from_slice(&[CR, LF]);
+                return;
+            }
+            Resp::Array(values) => {
+                bytes.extend_from_slice(format!("*{}\r\n", values.len()).as_bytes());
+                values.iter().for_each(|value| value.serialize(bytes));
+                return;
+            }
+            Resp::NullArray => return bytes.extend_from_slice(b"*-1\r\n"),
+            Resp::Nil => return bytes.extend_from_slice(NIL),
+        };
+        bytes.push(symbol);
+        bytes.extend_from_slice(data);
+        bytes.extend_from_slice(&[CR, LF]);
+    }
+}
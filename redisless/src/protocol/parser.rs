@@ -2,18 +2,28 @@ use super::error::RedisError;
 use super::{Resp, Result};
 use super::{CR, LF, NIL_VALUE_SIZE};
 
+// upper bound of the capacity reserved up front for an array, whatever its declared size
+const MAX_ARRAY_PREALLOCATION: usize = 1024;
+/// Longest inline request, as in Redis, so a line without newline is not buffered forever
+pub const PROTO_INLINE_MAX_SIZE: usize = 64 * 1024;
+
 pub struct RedisProtocolParser;
 
 impl RedisProtocolParser {
-    pub fn parse(input: &[u8]) -> Result {
-        if let Some(first) = input.get(0) {
+    pub fn parse(input: &[u8]) -> Result<'_> {
+        RedisProtocolParser::parse_with_max_bulk_len(input, usize::MAX)
+    }
+
+    /// Parse input, rejecting any bulk string declared longer than max_bulk_len bytes
+    pub fn parse_with_max_bulk_len(input: &[u8], max_bulk_len: usize) -> Result<'_> {
+        if let Some(first) = input.first() {
             let first = *first as char;
             let input = &input[1..];
             let (resp, left) = match first {
                 '+' => RedisProtocolParser::parse_simple_string(input)?,
                 ':' => RedisProtocolParser::parse_integers(input)?,
-                '$' => RedisProtocolParser::parse_bulk_strings(input)?,
-                '*' => RedisProtocolParser::parse_arrays(input)?,
+                '$' => RedisProtocolParser::parse_bulk_strings(input, max_bulk_len)?,
+                '*' => RedisProtocolParser::parse_arrays(input, max_bulk_len)?,
                 '-' => RedisProtocolParser::parse_errors(input)?,
                 _ => return Err(RedisError::unknown_symbol()),
             };
@@ -23,6 +33,35 @@ impl RedisProtocolParser {
         }
     }
 
+    /// Parse a request of a client: an array of bulk strings, or an inline request when it
+    /// does not start with `*`
+    pub fn parse_request(input: &[u8], max_bulk_len: usize) -> Result<'_> {
+        match input.first() {
+            Some(b'*') | None => RedisProtocolParser::parse_with_max_bulk_len(input, max_bulk_len),
+            Some(_) => RedisProtocolParser::parse_inline(input),
+        }
+    }
+
+    /// Parse an inline request, its arguments separated by spaces on a line ending with LF
+    /// or CRLF. Unlike Redis, quotes are not interpreted. An empty line is an empty array.
+    pub fn parse_inline(input: &[u8]) -> Result<'_> {
+        let end = match input.iter().position(|byte| *byte == LF) {
+            Some(end) if end <= PROTO_INLINE_MAX_SIZE => end,
+            None if input.len() <= PROTO_INLINE_MAX_SIZE => return Err(RedisError::no_crlf()),
+            _ => return Err(RedisError::too_big_inline_request()),
+        };
+        let line = match input[..end].strip_suffix(&[CR]) {
+            Some(line) => line,
+            None => &input[..end],
+        };
+        let args = line
+            .split(|byte| *byte == b' ' || *byte == b'\t')
+            .filter(|arg| !arg.is_empty())
+            .map(Resp::BulkString)
+            .collect();
+        Ok((Resp::Array(args), &input[end + 1..]))
+    }
+
     fn parse_everything_until_crlf(
         input: &[u8],
     ) -> std::result::Result<(&[u8], &[u8]), RedisError> {
@@ -34,53 +73,68 @@ impl RedisProtocolParser {
         Err(RedisError::no_crlf())
     }
 
-    pub fn parse_simple_string(input: &[u8]) -> Result {
+    pub fn parse_simple_string(input: &[u8]) -> Result<'_> {
         RedisProtocolParser::parse_everything_until_crlf(input).map(|(x, y)| (Resp::String(x), y))
     }
 
-    pub fn parse_errors(input: &[u8]) -> Result {
+    pub fn parse_errors(input: &[u8]) -> Result<'_> {
         RedisProtocolParser::parse_everything_until_crlf(input).map(|(x, y)| (Resp::Error(x), y))
     }
 
-    pub fn parse_integers(input: &[u8]) -> Result {
+    pub fn parse_integers(input: &[u8]) -> Result<'_> {
         RedisProtocolParser::parse_everything_until_crlf(input).map(|(x, y)| (Resp::Integer(x), y))
     }
 
-    pub fn parse_bulk_strings(input: &[u8]) -> Result {
+    pub fn parse_bulk_strings(input: &[u8], max_bulk_len: usize) -> Result<'_> {
         // Check Null Strings.
         if RedisProtocolParser::check_null_value(input) {
             Ok((Resp::Nil, &input[NIL_VALUE_SIZE..]))
         } else {
             let (size_str, input_after_size) =
                 RedisProtocolParser::parse_everything_until_crlf(input)?;
-            let size = std::str::from_utf8(size_str)?.parse::<u64>()? as usize;
-            if RedisProtocolParser::check_crlf_at_index(input_after_size, size) {
-                Ok((
+            let size = std::str::from_utf8(size_str)?.parse::<u64>()?;
+            // checked on the declared size, before waiting for the string itself
+            if size > max_bulk_len as u64 {
+                return Err(RedisError::invalid_bulk_length());
+            }
+            let size = size as usize;
+
+            match RedisProtocolParser::check_crlf_at_index(input_after_size, size) {
+                Some(true) => Ok((
                     Resp::BulkString(&input_after_size[..size]),
                     &input_after_size[size + 2..],
-                ))
-            } else {
-                Err(RedisError::incorrect_format())
+                )),
+                Some(false) => Err(RedisError::incorrect_format()),
+                None => Err(RedisError::incomplete()),
             }
         }
     }
 
-    fn check_crlf_at_index(input: &[u8], index: usize) -> bool {
-        input.len() >= index && input[index] == CR && input[index + 1] == LF
+    /// Whether CRLF is at index, `None` when input is too short to tell yet
+    fn check_crlf_at_index(input: &[u8], index: usize) -> Option<bool> {
+        match (input.get(index), input.get(index + 1)) {
+            (Some(&CR), Some(&LF)) => Some(true),
+            (Some(&CR), None) => None,
+            (Some(_), _) => Some(false),
+            (None, _) => None,
+        }
     }
 
     fn check_null_value(input: &[u8]) -> bool {
         input.len() >= 4 && input[0] == b'-' && input[1] == b'1' && input[2] == CR && input[3] == LF
     }
 
-    pub fn parse_arrays(input: &[u8]) -> Result {
+    pub fn parse_arrays(input: &[u8], max_bulk_len: usize) -> Result<'_> {
+        if RedisProtocolParser::check_null_value(input) {
+            return Ok((Resp::NullArray, &input[NIL_VALUE_SIZE..]));
+        }
         let (size_str, input) = RedisProtocolParser::parse_everything_until_crlf(input)?;
         let size = std::str::from_utf8(size_str)?.parse::<u64>()?;
         let sizes = size as usize;
         let mut left = input;
-        let mut result = Vec::with_capacity(sizes);
+        let mut result = Vec::with_capacity(sizes.min(MAX_ARRAY_PREALLOCATION));
         for _ in 0..sizes {
-            let (element, tmp) = RedisProtocolParser::parse(left)?;
+            let (element, tmp) = RedisProtocolParser::parse_with_max_bulk_len(left, max_bulk_len)?;
             result.push(element);
             left = tmp;
         }
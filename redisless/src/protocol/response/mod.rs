@@ -1,13 +1,37 @@
+use std::io::{self, Write};
+
 use prost::bytes::BufMut;
 
-use super::{NIL, OK, PONG};
+use super::compression::{compress, COMPRESSED_FORMAT};
+use super::{NIL, NULL, OK, PONG};
 use crate::{command::command_error::RedisCommandError, storage::models::RedisString};
 
+// bulk string replies at least this long are written to the connection without being
+// buffered, see `RedisResponse::write_to`
+const STREAMED_VALUE_LEN: usize = 64 * 1024;
+
 pub enum RedisResponseType {
     SimpleString(RedisString),
     BulkString(RedisString),
     Integer(i64),
     Nil,
+    // nested array, e.g. the batch of keys in a `SCAN` reply
+    Array(Vec<RedisResponseType>),
+    // RESP3 map of keys to values, see `RedisResponse::map` for RESP2 clients
+    Map(Vec<(RedisResponseType, RedisResponseType)>),
+    // `-CODE message` line, from a `RedisCommandError`
+    Error(RedisString),
+    // RESP3 out of band message such as a pub/sub one, see `RedisResponse::push` for RESP2
+    // clients
+    Push(Vec<RedisResponseType>),
+    // RESP3 text with its format such as `txt`, see `RedisResponseType::verbatim` for RESP2
+    // clients
+    VerbatimString([u8; 3], RedisString),
+    // RESP3 nil, which `RedisResponse::in_protocol` replies rather than `Nil`
+    Null,
+    // RESP3 floating point number such as a score, see `RedisResponseType::double` for RESP2
+    // clients
+    Double(f64),
 }
 
 pub struct RedisResponse {
@@ -21,27 +45,138 @@ enum RedisResponseInner {
     Okay,
     Pong,
     Quit,
+    // reply already encoded by the command, e.g. the snapshot sent to a replica
+    Raw(Vec<u8>),
 }
 
 impl RedisResponseType {
+    /// A map nested in a reply, for a RESP2 client the array of its keys and values
+    pub fn map(entries: Vec<(RedisResponseType, RedisResponseType)>, resp3: bool) -> Self {
+        match resp3 {
+            true => RedisResponseType::Map(entries),
+            false => RedisResponseType::Array(
+                entries
+                    .into_iter()
+                    .flat_map(|(key, value)| vec![key, value])
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Text of the format such as `txt` or `mkd` for a RESP3 client, and for a RESP2 one a
+    /// bulk string of the text
+    pub fn verbatim(format: &[u8; 3], text: RedisString, resp3: bool) -> Self {
+        match resp3 {
+            true => RedisResponseType::VerbatimString(*format, text),
+            false => RedisResponseType::BulkString(text),
+        }
+    }
+
+    /// A floating point number, for a RESP2 client the bulk string of its digits
+    pub fn double(value: f64, resp3: bool) -> Self {
+        match resp3 {
+            true => RedisResponseType::Double(value),
+            false => RedisResponseType::BulkString(value.to_string().into_bytes()),
+        }
+    }
+
+    // the nils of self, nested ones included, in RESP3
+    fn into_resp3(self) -> Self {
+        use RedisResponseType::*;
+        match self {
+            Nil => Null,
+            Array(responses) => Array(responses.into_iter().map(Self::into_resp3).collect()),
+            Push(responses) => Push(responses.into_iter().map(Self::into_resp3).collect()),
+            Map(entries) => Map(entries
+                .into_iter()
+                .map(|(key, value)| (key.into_resp3(), value.into_resp3()))
+                .collect()),
+            response => response,
+        }
+    }
+
+    // the bulk strings of self at least threshold long, nested ones included, compressed
+    // when that makes them shorter
+    fn into_compressed(self, threshold: usize) -> Self {
+        use RedisResponseType::*;
+        let compressed = |responses: Vec<Self>| {
+            responses
+                .into_iter()
+                .map(|response| response.into_compressed(threshold))
+                .collect()
+        };
+        match self {
+            BulkString(value) if value.len() >= threshold => {
+                let compressed = compress(&value);
+                match compressed.len() < value.len() {
+                    true => VerbatimString(*COMPRESSED_FORMAT, compressed),
+                    false => BulkString(value),
+                }
+            }
+            Array(responses) => Array(compressed(responses)),
+            Push(responses) => Push(compressed(responses)),
+            Map(entries) => Map(entries
+                .into_iter()
+                .map(|(key, value)| (key, value.into_compressed(threshold)))
+                .collect()),
+            response => response,
+        }
+    }
+
     // move out of the enum
     fn to_vec(&self) -> Vec<u8> {
         use RedisResponseType::*;
         match self {
-            SimpleString(s) | BulkString(s) => s.clone(),
+            SimpleString(s) | BulkString(s) | Error(s) => s.clone(),
             Integer(num) => num.to_string().as_bytes().to_vec(),
+            // as the RESP3 specification spells the infinities and NaN
+            Double(value) if value.is_nan() => b"nan".to_vec(),
+            Double(value) => value.to_string().into_bytes(),
             Nil => NIL.to_vec(),
+            Null => NULL.to_vec(),
+            Array(responses) | Push(responses) => {
+                let mut bytes = Vec::<u8>::with_capacity(512);
+                bytes.push(if matches!(self, Push(_)) { b'>' } else { b'*' });
+                bytes.put_slice(responses.len().to_string().as_bytes());
+                bytes.put_slice(b"\r\n");
+                for response in responses {
+                    bytes.append(&mut response.get_formatted());
+                }
+                bytes
+            }
+            VerbatimString(format, text) => {
+                // the length counts the format and its `:`
+                let mut bytes = format!("={}\r\n", format.len() + 1 + text.len()).into_bytes();
+                bytes.extend_from_slice(format);
+                bytes.push(b':');
+                bytes.extend_from_slice(text);
+                bytes.put_slice(b"\r\n");
+                bytes
+            }
+            Map(entries) => {
+                let mut bytes = Vec::<u8>::with_capacity(512);
+                bytes.push(b'%');
+                bytes.put_slice(entries.len().to_string().as_bytes());
+                bytes.put_slice(b"\r\n");
+                for (key, value) in entries {
+                    bytes.append(&mut key.get_formatted());
+                    bytes.append(&mut value.get_formatted());
+                }
+                bytes
+            }
         }
     }
-    /// Move out of self and return bytes analogous to `format!("{}{}{}", symbol, data, CRLF)`
-    pub fn get_formatted(self) -> Vec<u8> {
+    /// Return bytes analogous to `format!("{}{}{}", symbol, data, CRLF)`
+    pub fn get_formatted(&self) -> Vec<u8> {
         use RedisResponseType::*;
 
-        let symbol = match &self {
+        let symbol = match self {
             SimpleString(_) => b'+',
+            Error(_) => b'-',
             BulkString(_) => b'$',
             Integer(_) => b':',
-            Nil => return self.to_vec(),
+            Double(_) => b',',
+            Nil | Null | Array(_) | Map(_) | Push(_) | VerbatimString(..) => return self.to_vec(),
         };
         let mut bytes = self.to_vec();
         let mut reply =
@@ -58,6 +193,15 @@ impl RedisResponseType {
     }
 }
 
+impl From<RedisCommandError> for RedisResponseType {
+    /// The message of the error, which starts with its code such as `ERR` or `WRONGTYPE`
+    fn from(error: RedisCommandError) -> Self {
+        // an argument quoted by the message must not end the line early
+        let message = error.to_string().replace(['\r', '\n'], " ").into_bytes();
+        RedisResponseType::Error(message)
+    }
+}
+
 impl RedisResponse {
     pub fn okay() -> Self {
         Self {
@@ -77,6 +221,62 @@ impl RedisResponse {
     pub fn is_quit(&self) -> bool {
         matches!(self.responses, RedisResponseInner::Quit)
     }
+    pub fn is_error(&self) -> bool {
+        matches!(self.responses, RedisResponseInner::Error(_))
+    }
+
+    /// The error replied, if the reply is one
+    pub fn error_of(&self) -> Option<&RedisCommandError> {
+        match &self.responses {
+            RedisResponseInner::Error(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// The reply in the protocol of the client, RESP3 with resp3, in which a nil is `_`
+    /// rather than `$-1`
+    pub fn in_protocol(self, resp3: bool) -> Self {
+        use RedisResponseInner::*;
+        let responses = match (self.responses, resp3) {
+            (Single(response), true) => Single(response.into_resp3()),
+            (Array(responses), true) => Array(
+                responses
+                    .into_iter()
+                    .map(RedisResponseType::into_resp3)
+                    .collect(),
+            ),
+            (responses, _) => responses,
+        };
+        Self { responses }
+    }
+
+    /// The reply of a client which opted in with `CLIENT REDISLESS-COMPRESS`, in which the
+    /// bulk strings at least threshold long are verbatim strings of the `COMPRESSED_FORMAT`,
+    /// whatever the protocol of the client
+    pub fn compressed(self, threshold: Option<usize>) -> Self {
+        use RedisResponseInner::*;
+        let threshold = match threshold {
+            Some(threshold) => threshold,
+            None => return self,
+        };
+        let responses = match self.responses {
+            Single(response) => Single(response.into_compressed(threshold)),
+            Array(responses) => Array(
+                responses
+                    .into_iter()
+                    .map(|response| response.into_compressed(threshold))
+                    .collect(),
+            ),
+            responses => responses,
+        };
+        Self { responses }
+    }
+
+    pub fn raw(bytes: Vec<u8>) -> Self {
+        Self {
+            responses: RedisResponseInner::Raw(bytes),
+        }
+    }
 
     pub fn single(response: RedisResponseType) -> Self {
         Self {
@@ -90,23 +290,68 @@ impl RedisResponse {
         }
     }
 
+    /// A map for a RESP3 client, and for a RESP2 one the array of its keys and values
+    pub fn map(entries: Vec<(RedisResponseType, RedisResponseType)>, resp3: bool) -> Self {
+        match resp3 {
+            true => Self::single(RedisResponseType::Map(entries)),
+            false => Self::array(
+                entries
+                    .into_iter()
+                    .flat_map(|(key, value)| vec![key, value])
+                    .collect(),
+            ),
+        }
+    }
+
+    /// A push message for a RESP3 client, and for a RESP2 one the array of its elements
+    pub fn push(elements: Vec<RedisResponseType>, resp3: bool) -> Self {
+        match resp3 {
+            true => Self::single(RedisResponseType::Push(elements)),
+            false => Self::array(elements),
+        }
+    }
+
     pub fn error(error: RedisCommandError) -> Self {
         Self {
             responses: RedisResponseInner::Error(error),
         }
     }
 
+    /// Whether the reply is a single bulk string long enough to be written by `write_to`
+    /// rather than buffered
+    pub fn is_streamed(&self) -> bool {
+        matches!(
+            &self.responses,
+            RedisResponseInner::Single(RedisResponseType::BulkString(value))
+                if value.len() >= STREAMED_VALUE_LEN
+        )
+    }
+
+    /// Write the bytes of `reply` to out. A single bulk string is written as its header then
+    /// its value, which is not copied once more into a reply buffer. The value is still the
+    /// copy taken out of the storage, so the lock of its key is not held while it is written.
+    pub fn write_to<W: Write>(self, out: &mut W) -> io::Result<()> {
+        let value = match self.responses {
+            RedisResponseInner::Single(RedisResponseType::BulkString(value)) => value,
+            _ => return out.write_all(&self.reply()),
+        };
+        out.write_all(format!("${}\r\n", value.len()).as_bytes())?;
+        out.write_all(&value)?;
+        out.write_all(b"\r\n")
+    }
+
     pub fn reply(self) -> Vec<u8> {
         use RedisResponseInner::*;
         match self.responses {
             Okay | Quit => OK.to_vec(),
-            Error(e) => e.to_vec(),
+            Error(e) => RedisResponseType::from(e).get_formatted(),
             Pong => PONG.to_vec(),
+            Raw(bytes) => bytes,
             Single(single) => single.get_formatted(),
             Array(responses) => {
                 let mut reply = Vec::<u8>::with_capacity(512);
                 reply.push(b'*');
-                reply.put_slice(&responses.len().to_string().as_bytes().to_vec());
+                reply.put_slice(responses.len().to_string().as_bytes());
                 reply.put_slice(b"\r\n");
                 for response in responses {
                     let mut response = response.get_formatted();
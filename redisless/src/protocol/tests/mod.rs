@@ -1,5 +1,6 @@
 use super::*;
 use crate::protocol::{error::RedisErrorType, parser::RedisProtocolParser};
+use rand::{rngs::StdRng, Rng};
 
 #[test]
 pub fn test_simple_string() -> std::result::Result<(), RedisError> {
@@ -101,3 +102,386 @@ pub fn test_array_of_arrays() -> std::result::Result<(), RedisError> {
     assert!(left.is_empty());
     Ok(())
 }
+
+#[test]
+pub fn test_max_bulk_len() -> std::result::Result<(), RedisError> {
+    // the declared size is rejected before the string itself is received
+    let input = "*2\r\n$3\r\nGET\r\n$1000\r\n".as_bytes();
+    let err = RedisProtocolParser::parse_with_max_bulk_len(input, 10).unwrap_err();
+    assert!(matches!(err.err_type, RedisErrorType::InvalidBulkLength));
+
+    let input = "$10\r\n0123456789\r\n".as_bytes();
+    let (resp, _) = RedisProtocolParser::parse_with_max_bulk_len(input, 10)?;
+    assert_eq!(resp, Resp::BulkString("0123456789".as_bytes()));
+
+    // a partial bulk string may be completed by the next read
+    let input = "$10\r\n01234".as_bytes();
+    let err = RedisProtocolParser::parse(input).unwrap_err();
+    assert!(err.is_incomplete());
+    Ok(())
+}
+
+#[test]
+pub fn test_null_array() -> std::result::Result<(), RedisError> {
+    let input = "*-1\r\n".as_bytes();
+    let (resp, left) = RedisProtocolParser::parse(input)?;
+    assert_eq!(resp, Resp::NullArray);
+    assert!(left.is_empty());
+    Ok(())
+}
+
+#[test]
+pub fn test_serialize() -> std::result::Result<(), RedisError> {
+    let resp = Resp::Array(vec![
+        Resp::String(b"OK"),
+        Resp::Error(b"ERR wrong"),
+        Resp::Integer(b"-42"),
+        Resp::BulkString(b"bulk\r\nstring"),
+        Resp::BulkString(b""),
+        Resp::Array(vec![Resp::Nil, Resp::NullArray]),
+    ]);
+    let mut bytes = vec![];
+    resp.serialize(&mut bytes);
+    assert_eq!(
+        bytes,
+        b"*6\r\n+OK\r\n-ERR wrong\r\n:-42\r\n$12\r\nbulk\r\nstring\r\n$0\r\n\r\n*2\r\n$-1\r\n*-1\r\n"
+            .to_vec()
+    );
+
+    let (parsed, left) = RedisProtocolParser::parse(&bytes)?;
+    assert_eq!(parsed, resp);
+    assert!(left.is_empty());
+    Ok(())
+}
+
+// values of the random frames, borrowed by the `Resp` generated from them
+struct Pool {
+    // no CR nor LF, for simple strings and errors
+    lines: Vec<Vec<u8>>,
+    integers: Vec<Vec<u8>>,
+    // any bytes, for bulk strings
+    binaries: Vec<Vec<u8>>,
+}
+
+impl Pool {
+    fn new(rng: &mut StdRng) -> Self {
+        let mut random_bytes = |allowed: &dyn Fn(u8) -> bool| -> Vec<Vec<u8>> {
+            (0..32)
+                .map(|_| {
+                    let len = rng.gen_range(0..64);
+                    std::iter::repeat_with(|| rng.gen::<u8>())
+                        .filter(|byte| allowed(*byte))
+                        .take(len)
+                        .collect()
+                })
+                .collect()
+        };
+        let lines = random_bytes(&|byte| byte != b'\r' && byte != b'\n');
+        let binaries = random_bytes(&|_| true);
+        let integers = (0..32)
+            .map(|_| rng.gen::<i64>().to_string().into_bytes())
+            .collect();
+        Pool {
+            lines,
+            integers,
+            binaries,
+        }
+    }
+
+    fn random_resp<'a>(&'a self, rng: &mut StdRng, depth: usize) -> Resp<'a> {
+        let pick = |values: &'a [Vec<u8>], rng: &mut StdRng| {
+            values[rng.gen_range(0..values.len())].as_slice()
+        };
+        // arrays are only nested up to depth
+        let kinds = if depth == 0 { 7 } else { 8 };
+        match rng.gen_range(0..kinds) {
+            0 => Resp::String(pick(&self.lines, rng)),
+            1 => Resp::Error(pick(&self.lines, rng)),
+            2 => Resp::Integer(pick(&self.integers, rng)),
+            3 | 4 => Resp::BulkString(pick(&self.binaries, rng)),
+            5 => Resp::Nil,
+            6 => Resp::NullArray,
+            _ => {
+                let len = rng.gen_range(0..8);
+                Resp::Array((0..len).map(|_| self.random_resp(rng, depth - 1)).collect())
+            }
+        }
+    }
+}
+
+#[test]
+pub fn test_serialize_round_trip() -> std::result::Result<(), RedisError> {
+    use rand::SeedableRng;
+
+    // a fixed seed, so a failure can be reproduced
+    let mut rng = StdRng::seed_from_u64(0x5e7_1a11e);
+    let pool = Pool::new(&mut rng);
+    for _ in 0..2000 {
+        let resp = pool.random_resp(&mut rng, 3);
+        let mut bytes = vec![];
+        resp.serialize(&mut bytes);
+
+        let (parsed, left) = RedisProtocolParser::parse(&bytes)?;
+        assert_eq!(parsed, resp);
+        assert!(left.is_empty());
+    }
+    Ok(())
+}
+
+#[test]
+pub fn test_command_errors() -> std::result::Result<(), RedisError> {
+    use crate::command::command_error::RedisCommandError::*;
+    use crate::protocol::response::RedisResponseType;
+    use crate::storage::models::expiry::TimeOverflow;
+
+    let invalid = vec![0xff];
+    let utf8 = std::str::from_utf8(&invalid).unwrap_err();
+    let int = "x".parse::<u64>().unwrap_err();
+    let io = std::io::Error::other("disk full");
+    let errors = vec![
+        (ArgNumber, "ERR wrong number of arguments for command"),
+        (TimeOverflow(TimeOverflow {}), "ERR invalid expire time"),
+        (BadString(utf8), "ERR invalid utf-8 sequence of 1 bytes from index 0"),
+        (IntParse(int), "ERR value is not an integer or out of range"),
+        (
+            NotSupported("FOO".to_string()),
+            "ERR command FOO not supported by redisless",
+        ),
+        (
+            ProtocolParse(RedisError::invalid_bulk_length()),
+            "ERR Protocol error: invalid bulk length",
+        ),
+        (InvalidCommand, "ERR invalid command"),
+        (CommandNotFound, "ERR command not found"),
+        (
+            WrongTypeOperation,
+            "WRONGTYPE Operation against a key holding the wrong kind of value",
+        ),
+        (NoSuchKey, "ERR no such key"),
+        (IndexOutOfRange, "ERR index out of range"),
+        (SyntaxErr, "ERR syntax error"),
+        (Io(io), "ERR disk full"),
+        (
+            BackgroundSaveInProgress,
+            "ERR Background save already in progress",
+        ),
+        (DbIndexOutOfRange, "ERR DB index is out of range"),
+        (
+            WrongArity("get".to_string()),
+            "ERR wrong number of arguments for 'get' command",
+        ),
+        (InvalidBit, "ERR The bit argument must be 1 or 0"),
+        (
+            BitOpNotSingleSource,
+            "ERR BITOP NOT must be called with a single source key",
+        ),
+        (InvalidFloat, "ERR value is not a valid float"),
+        (InvalidScoreRange, "ERR min or max is not a float"),
+        (ScoreNaN, "ERR resulting score is not a number (NaN)"),
+        (TimeLimitExceeded, "ERR command exceeded time limit"),
+        (NumKeysNotPositive, "ERR numkeys should be greater than 0"),
+        (
+            NumKeysGreaterThanArgs,
+            "ERR Number of keys can't be greater than number of args",
+        ),
+        (ZeroRank, "ERR RANK can't be zero: use 1 to start from the first match, 2 from the second ... or use negative to start from the end of the list"),
+        (NegativeCount, "ERR COUNT can't be negative"),
+        (
+            ZAddIncrPairs,
+            "ERR INCR option supports a single increment-element pair",
+        ),
+        (
+            LfuPolicyNotSelected,
+            "ERR An LFU maxmemory policy is not selected",
+        ),
+        (NotAnInteger, "ERR value is not an integer or out of range"),
+        (IncrOverflow, "ERR increment or decrement would overflow"),
+        (InvalidCursor, "ERR invalid cursor"),
+        (
+            ReadOnlyReplica,
+            "READONLY You can't write against a read only replica.",
+        ),
+        (
+            UnknownCommand("foo".to_string(), vec!["bar".to_string(), "baz".to_string()]),
+            "ERR unknown command 'foo', with args beginning with: 'bar', 'baz'",
+        ),
+        (InvalidTimeout, "ERR timeout is not a float or out of range"),
+        (NegativeTimeout, "ERR timeout is negative"),
+        (GetKeysInvalidCommand, "ERR Invalid command specified"),
+        (
+            GetKeysInvalidArgs,
+            "ERR Invalid number of arguments specified for command",
+        ),
+        (NoKeyArguments, "ERR The command has no key arguments"),
+        (
+            SortNotNumeric,
+            "ERR One or more scores can't be converted into double",
+        ),
+        (
+            SubscribedContext("get".to_string()),
+            "ERR Can't execute 'get': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+        ),
+        (NoAuth, "NOAUTH Authentication required."),
+        (HelloNoAuth, "NOAUTH HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time"),
+        (
+            WrongPass,
+            "WRONGPASS invalid username-password pair or user is disabled.",
+        ),
+        (AuthNotConfigured, "ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?"),
+        (NoProto, "NOPROTO unsupported protocol version"),
+        (
+            InvalidClientName,
+            "ERR Client names cannot contain spaces, newlines or special characters.",
+        ),
+        (
+            NoPerm("alice".to_string(), "flushall".to_string()),
+            "NOPERM User alice has no permissions to run the 'flushall' command",
+        ),
+        (
+            AclSyntax("+@read".to_string()),
+            "ERR Error in ACL SETUSER modifier '+@read': Syntax error",
+        ),
+        (
+            UnknownSubcommand("foo".to_string(), "OBJECT"),
+            "ERR Unknown subcommand or wrong number of arguments for 'foo'. Try OBJECT HELP.",
+        ),
+    ];
+
+    for (error, expected) in errors {
+        let bytes = RedisResponseType::from(error).get_formatted();
+        let (resp, left) = RedisProtocolParser::parse(&bytes)?;
+        assert_eq!(resp, Resp::Error(expected.as_bytes()));
+        assert!(left.is_empty());
+        // every message starts with an uppercase code
+        let code = expected.split(' ').next().unwrap();
+        assert!(code.chars().all(|c| c.is_ascii_uppercase()), "{}", code);
+    }
+
+    // a line break quoted from the request is not sent as is
+    let bytes =
+        RedisResponseType::from(UnknownCommand("a\r\nb".to_string(), vec![])).get_formatted();
+    assert_eq!(
+        bytes,
+        b"-ERR unknown command 'a  b', with args beginning with: \r\n".to_vec()
+    );
+    Ok(())
+}
+
+#[test]
+pub fn test_streamed_reply() {
+    use crate::protocol::response::{RedisResponse, RedisResponseType::*};
+
+    assert!(!RedisResponse::single(BulkString(b"small".to_vec())).is_streamed());
+    assert!(!RedisResponse::okay().is_streamed());
+
+    let mut rng: StdRng = rand::SeedableRng::seed_from_u64(7);
+    let value: Vec<u8> = (0..1_000_003).map(|_| rng.gen()).collect();
+    let streamed = RedisResponse::single(BulkString(value.clone()));
+    assert!(streamed.is_streamed());
+    let mut bytes = vec![];
+    streamed.write_to(&mut bytes).unwrap();
+    assert_eq!(bytes, RedisResponse::single(BulkString(value)).reply());
+}
+
+#[test]
+pub fn test_double() {
+    use crate::protocol::response::{RedisResponse, RedisResponseType};
+
+    let reply =
+        |value, resp3| RedisResponse::single(RedisResponseType::double(value, resp3)).reply();
+    assert_eq!(reply(1.5, true), b",1.5\r\n");
+    assert_eq!(reply(-3.0, true), b",-3\r\n");
+    assert_eq!(reply(f64::INFINITY, true), b",inf\r\n");
+    assert_eq!(reply(f64::NEG_INFINITY, true), b",-inf\r\n");
+    assert_eq!(reply(f64::NAN, true), b",nan\r\n");
+    // the bulk string of a RESP2 client, as Redis formats a score
+    assert_eq!(reply(1.5, false), b"$3\r\n1.5\r\n");
+    assert_eq!(reply(f64::NEG_INFINITY, false), b"$4\r\n-inf\r\n");
+}
+
+#[test]
+pub fn test_inline_request() -> std::result::Result<(), RedisError> {
+    use crate::protocol::parser::PROTO_INLINE_MAX_SIZE;
+
+    let input = "SET  key value\r\nGET key\n".as_bytes();
+    let (resp, left) = RedisProtocolParser::parse_request(input, usize::MAX)?;
+    assert_eq!(
+        resp,
+        Resp::Array(vec![
+            Resp::BulkString(b"SET"),
+            Resp::BulkString(b"key"),
+            Resp::BulkString(b"value"),
+        ])
+    );
+    let (resp, left) = RedisProtocolParser::parse_request(left, usize::MAX)?;
+    assert_eq!(
+        resp,
+        Resp::Array(vec![Resp::BulkString(b"GET"), Resp::BulkString(b"key")])
+    );
+    assert!(left.is_empty());
+
+    let (resp, _) = RedisProtocolParser::parse_request(b"\r\n", usize::MAX)?;
+    assert_eq!(resp, Resp::Array(vec![]));
+
+    // a line without newline is waited for, up to the max size of a request
+    let line = vec![b'x'; PROTO_INLINE_MAX_SIZE];
+    let err = RedisProtocolParser::parse_request(&line, usize::MAX).unwrap_err();
+    assert!(err.is_incomplete());
+    let line = vec![b'x'; PROTO_INLINE_MAX_SIZE + 1];
+    let err = RedisProtocolParser::parse_request(&line, usize::MAX).unwrap_err();
+    assert!(matches!(err.err_type, RedisErrorType::TooBigInlineRequest));
+    Ok(())
+}
+
+#[test]
+pub fn test_decoder() {
+    let mut decoder = RespDecoder::new();
+    // a frame split across three chunks, followed by the start of the next one
+    decoder.feed(b"*2\r\n$3\r");
+    assert!(decoder.next_frame().is_none());
+    decoder.feed(b"\nGET\r\n$3");
+    assert!(decoder.next_frame().is_none());
+    decoder.feed(b"\r\nkey\r\n+O");
+    assert_eq!(
+        decoder.next_frame().unwrap().unwrap(),
+        Resp::Array(vec![Resp::BulkString(b"GET"), Resp::BulkString(b"key")])
+    );
+    assert!(decoder.next_frame().is_none());
+    assert_eq!(decoder.pending(), 2);
+
+    decoder.feed(b"K\r\n:1\r\n");
+    assert_eq!(decoder.next_frame().unwrap().unwrap(), Resp::String(b"OK"));
+    assert_eq!(decoder.next_frame().unwrap().unwrap(), Resp::Integer(b"1"));
+    assert!(decoder.next_frame().is_none());
+
+    // the bytes after a malformed frame are dropped
+    decoder.feed(b"?\r\n+OK\r\n");
+    assert!(decoder.next_frame().unwrap().is_err());
+    assert!(decoder.next_frame().is_none());
+    assert_eq!(decoder.pending(), 0);
+}
+
+#[test]
+pub fn test_compression() {
+    use crate::protocol::compression::{compress, decompress};
+
+    let mut rng: StdRng = rand::SeedableRng::seed_from_u64(7);
+    let noise = (0..10_000).map(|_| rng.gen::<u8>()).collect::<Vec<_>>();
+    let text = "the quick brown fox jumps over the lazy dog. ".repeat(1000);
+    for bytes in [
+        &b""[..],
+        b"a",
+        b"abcabcabc",
+        &[0; 1000],
+        &noise,
+        text.as_bytes(),
+    ] {
+        assert_eq!(decompress(&compress(bytes)).as_deref(), Some(bytes));
+    }
+    assert!(compress(text.as_bytes()).len() < text.len() / 10);
+
+    // a match farther than the bytes written so far, or a truncated token
+    assert_eq!(decompress(&[0x80, 1, 0]), None);
+    assert_eq!(decompress(&[2, b'a']), None);
+    assert_eq!(decompress(&[0, b'a', 0x80, 1]), None);
+}
@@ -0,0 +1,213 @@
+//! Users of the server, how the clients authenticate as one of them and the commands each
+//! user may run.
+//!
+//! The `default` user always exists. It has the password of `requirepass`, or none, and
+//! a client which did not authenticate is the default user when it does not need a password.
+//! Only commands are restricted, keys and channels are not.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::RwLock;
+
+use crate::command::command_error::RedisCommandError;
+use crate::command::options::AclRule;
+use crate::protocol::Resp;
+
+use super::Client;
+
+pub const DEFAULT_USER: &str = "default";
+
+// commands a client may always send, to authenticate or to leave
+const ALWAYS_ALLOWED: &[&str] = &["AUTH", "HELLO", "QUIT"];
+
+/// A user, as set by `ACL SETUSER`. A new user is disabled and may not run anything, as
+/// in Redis.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct User {
+    pub enabled: bool,
+    // any password authenticates the user
+    pub nopass: bool,
+    pub passwords: BTreeSet<String>,
+    // with every command allowed, the exceptions are the denied commands, otherwise they
+    // are the allowed ones. The names are uppercase.
+    pub all_commands: bool,
+    pub exceptions: BTreeSet<String>,
+}
+
+impl User {
+    fn apply(&mut self, rule: AclRule) {
+        match rule {
+            AclRule::On => self.enabled = true,
+            AclRule::Off => self.enabled = false,
+            AclRule::AddPassword(password) => {
+                self.passwords.insert(password);
+                self.nopass = false;
+            }
+            AclRule::RemovePassword(password) => {
+                self.passwords.remove(&password);
+            }
+            AclRule::NoPass => {
+                self.passwords.clear();
+                self.nopass = true;
+            }
+            AclRule::ResetPass => {
+                self.passwords.clear();
+                self.nopass = false;
+            }
+            AclRule::AllCommands | AclRule::NoCommands => {
+                self.all_commands = rule == AclRule::AllCommands;
+                self.exceptions.clear();
+            }
+            AclRule::Allow(command) if self.all_commands => {
+                self.exceptions.remove(&command);
+            }
+            AclRule::Deny(command) if !self.all_commands => {
+                self.exceptions.remove(&command);
+            }
+            AclRule::Allow(command) | AclRule::Deny(command) => {
+                self.exceptions.insert(command);
+            }
+            AclRule::Reset => *self = User::default(),
+        }
+    }
+
+    pub fn can_run(&self, command: &str) -> bool {
+        self.all_commands != self.exceptions.contains(command)
+    }
+
+    fn accepts(&self, password: &str) -> bool {
+        self.enabled && (self.nopass || self.passwords.contains(password))
+    }
+
+    /// Flags of the user, as listed by `ACL GETUSER`
+    pub fn flags(&self) -> Vec<&'static str> {
+        let mut flags = vec![if self.enabled { "on" } else { "off" }];
+        if self.nopass {
+            flags.push("nopass");
+        }
+        flags
+    }
+
+    /// Command rules of the user, such as `+@all -flushall`
+    pub fn commands(&self) -> String {
+        let (all, exception) = match self.all_commands {
+            true => ("+@all", '-'),
+            false => ("-@all", '+'),
+        };
+        let mut rules = all.to_string();
+        for command in &self.exceptions {
+            rules.push(' ');
+            rules.push(exception);
+            rules.push_str(&command.to_lowercase());
+        }
+        rules
+    }
+
+    /// Description of the user by `ACL LIST`. The passwords are not reported.
+    pub fn describe(&self, name: &str) -> String {
+        format!(
+            "user {} {} {}",
+            name,
+            self.flags().join(" "),
+            self.commands()
+        )
+    }
+}
+
+/// Users of a server, by name
+pub struct Acl {
+    users: RwLock<BTreeMap<String, User>>,
+}
+
+impl Acl {
+    pub fn new(requirepass: Option<&str>) -> Self {
+        let mut default = User {
+            enabled: true,
+            all_commands: true,
+            ..User::default()
+        };
+        match requirepass {
+            Some(password) => default.apply(AclRule::AddPassword(password.to_string())),
+            None => default.apply(AclRule::NoPass),
+        }
+
+        let mut users = BTreeMap::new();
+        users.insert(DEFAULT_USER.to_string(), default);
+        Acl {
+            users: RwLock::new(users),
+        }
+    }
+
+    /// Create the user if needed, then apply the rules to it in order
+    pub fn set_user(&self, name: &str, rules: Vec<AclRule>) {
+        let mut users = self.users.write().unwrap();
+        let user = users.entry(name.to_string()).or_default();
+        rules.into_iter().for_each(|rule| user.apply(rule));
+    }
+
+    pub fn user(&self, name: &str) -> Option<User> {
+        self.users.read().unwrap().get(name).cloned()
+    }
+
+    /// Every user, sorted by name
+    pub fn users(&self) -> Vec<(String, User)> {
+        let users = self.users.read().unwrap();
+        users
+            .iter()
+            .map(|(name, user)| (name.clone(), user.clone()))
+            .collect()
+    }
+
+    /// Authenticate the client as username, the default user when `None`. A failed
+    /// authentication leaves the client as it was.
+    pub fn authenticate(
+        &self,
+        client: &mut Client,
+        username: Option<&str>,
+        password: &str,
+    ) -> Result<(), RedisCommandError> {
+        let users = self.users.read().unwrap();
+        let name = username.unwrap_or(DEFAULT_USER);
+        match users.get(name) {
+            Some(user) if username.is_none() && user.nopass => {
+                Err(RedisCommandError::AuthNotConfigured)
+            }
+            Some(user) if user.accepts(password) => {
+                client.user = Some(name.to_string());
+                Ok(())
+            }
+            _ => Err(RedisCommandError::WrongPass),
+        }
+    }
+
+    /// Name of the user of the client, `None` until it authenticates unless the default
+    /// user needs no password
+    pub fn whoami(&self, client: &Client) -> Option<String> {
+        if client.user.is_some() {
+            return client.user.clone();
+        }
+        let users = self.users.read().unwrap();
+        match users.get(DEFAULT_USER) {
+            Some(default) if default.enabled && default.nopass => Some(DEFAULT_USER.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Check that the client may run the command of v, before it is parsed
+    pub fn check(&self, client: &Client, v: &[Resp]) -> Result<(), RedisCommandError> {
+        let command = match v.first() {
+            Some(Resp::BulkString(name)) => String::from_utf8_lossy(name).to_uppercase(),
+            _ => return Ok(()),
+        };
+        if ALWAYS_ALLOWED.contains(&command.as_str()) {
+            return Ok(());
+        }
+
+        let name = self.whoami(client).ok_or(RedisCommandError::NoAuth)?;
+        let users = self.users.read().unwrap();
+        match users.get(&name) {
+            Some(user) if user.can_run(&command) => Ok(()),
+            // a deleted user has no permission left
+            _ => Err(RedisCommandError::NoPerm(name, command.to_lowercase())),
+        }
+    }
+}
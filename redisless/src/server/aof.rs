@@ -0,0 +1,273 @@
+//! Append only file (`appendonly`).
+//!
+//! Every write command streamed to the replicas is also appended to the file, as the client
+//! sent it and preceded by a `SELECT` whenever the db changes, and the file is replayed when
+//! the server starts. Expiries are appended as the relative `EXPIRE` or `PEXPIRE` they were
+//! set with, so they start again when the file is replayed.
+//!
+//! `BGREWRITEAOF` rewrites the file in the background, as the fewest commands recreating
+//! the current data set. The writes which run meanwhile are buffered, then appended to the
+//! new file before it replaces the old one.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+
+use crate::command::command_error::RedisCommandError;
+use crate::command::Command;
+use crate::protocol::parser::RedisProtocolParser;
+use crate::protocol::Resp;
+use crate::storage::models::RedisValue;
+use crate::storage::snapshot::SnapshotEntry;
+use crate::storage::Storage;
+
+use super::util::{encode_request, lock_then_release, run_command_and_get_response, Deadline};
+use super::{Client, ServerContext};
+
+// elements of a list, set, hash or sorted set added by each command of a rewritten file
+const REWRITE_ITEMS_PER_COMMAND: usize = 64;
+
+/// Append only file of a server, disabled until it is opened
+#[derive(Default)]
+pub struct Aof {
+    enabled: AtomicBool,
+    rewriting: AtomicBool,
+    state: Mutex<AofState>,
+}
+
+/// The open file, locked while a write runs so the writes are appended in the order they ran
+#[derive(Default)]
+pub struct AofState {
+    file: Option<File>,
+    // db of the last `SELECT` of the file, `None` until one is appended
+    db: Option<usize>,
+    // writes run since the snapshot of the rewrite in progress, with their db
+    rewritten: Option<Vec<(usize, Vec<u8>)>>,
+}
+
+impl Aof {
+    /// Append the next writes to the file at path, created if needed
+    pub fn open(&self, path: &Path) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut state = lock_then_release(&self.state);
+        state.file = Some(file);
+        state.db = None;
+        self.enabled.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Flush the writes appended so far to the disk, `None` when the file is disabled
+    pub fn fsync(&self) -> Option<io::Result<()>> {
+        let state = self.lock()?;
+        state.file.as_ref().map(|file| file.sync_data())
+    }
+
+    /// The file, to hold while a write runs, `None` when the file is disabled
+    pub fn lock(&self) -> Option<MutexGuard<'_, AofState>> {
+        match self.enabled.load(Ordering::SeqCst) {
+            true => Some(lock_then_release(&self.state)),
+            false => None,
+        }
+    }
+}
+
+impl AofState {
+    /// Append the write request, run on db
+    pub fn append(&mut self, db: usize, request: &[u8]) {
+        let mut bytes = vec![];
+        if self.db != Some(db) {
+            bytes.extend_from_slice(&select(db));
+            self.db = Some(db);
+        }
+        bytes.extend_from_slice(request);
+
+        if let Some(rewritten) = &mut self.rewritten {
+            rewritten.push((db, request.to_vec()));
+        }
+        if let Some(file) = &mut self.file {
+            if let Err(err) = file.write_all(&bytes) {
+                log::warn!("writing the append only file failed: {}", err);
+            }
+        }
+    }
+}
+
+fn select(db: usize) -> Vec<u8> {
+    let index = db.to_string();
+    encode_request(&[
+        Resp::BulkString(b"SELECT"),
+        Resp::BulkString(index.as_bytes()),
+    ])
+}
+
+/// Replay the file of the config if it exists, then append the next writes to it
+pub fn start<T: Storage>(context: &ServerContext<T>) {
+    let path = context.config().aof_path();
+    if path.exists() {
+        match load(context, &path) {
+            Ok(commands) => log::info!("{} commands of the append only file replayed", commands),
+            Err(err) => log::warn!("replaying the append only file failed: {}", err),
+        }
+    }
+    if let Err(err) = context.replication.aof.open(&path) {
+        log::warn!("opening the append only file failed: {}", err);
+    }
+}
+
+/// Run every command of the file at path, returning how many ran. A truncated last command
+/// is dropped.
+pub fn load<T: Storage>(context: &ServerContext<T>, path: &Path) -> io::Result<usize> {
+    let bytes = fs::read(path)?;
+    let max_bulk_len = context.config().proto_max_bulk_len;
+    let mut client = Client::default();
+    let mut rest = &bytes[..];
+    let mut commands = 0;
+    while !rest.is_empty() {
+        let (command, left) = match RedisProtocolParser::parse_with_max_bulk_len(rest, max_bulk_len)
+        {
+            Ok((Resp::Array(v), left)) => (Command::parse(v), left),
+            Err(err) if err.is_incomplete() => {
+                log::warn!("the last command of the append only file is truncated");
+                break;
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the append only file is not a list of commands",
+                ))
+            }
+        };
+        let _ = run_command_and_get_response(context, &mut client, command, Deadline::after(None));
+        commands += 1;
+        rest = left;
+    }
+    Ok(commands)
+}
+
+/// Start rewriting the file of the config in the background
+pub fn rewrite<T: Storage>(context: &ServerContext<T>) -> Result<(), RedisCommandError> {
+    let aof = context.replication.aof.clone();
+    if aof.rewriting.swap(true, Ordering::SeqCst) {
+        return Err(RedisCommandError::AofRewriteInProgress);
+    }
+
+    // every write runs with the file locked, so it is either in the snapshot or buffered
+    let databases = {
+        let mut state = aof.lock();
+        if let Some(state) = &mut state {
+            state.rewritten = Some(vec![]);
+        }
+        context.take_snapshot()
+    };
+    let path = context.config().aof_path();
+
+    let _ = thread::spawn(move || {
+        if let Err(err) = rewrite_file(&aof, &path, &databases) {
+            log::warn!("rewriting the append only file failed: {}", err);
+            lock_then_release(&aof.state).rewritten = None;
+        }
+        aof.rewriting.store(false, Ordering::SeqCst);
+    });
+    Ok(())
+}
+
+// write the commands recreating the databases to a temporary file, then the writes buffered
+// meanwhile, and swap it with the file at path
+fn rewrite_file(aof: &Aof, path: &Path, databases: &[Vec<SnapshotEntry>]) -> io::Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".rewrite");
+    let temp_path: PathBuf = path.with_file_name(temp_name);
+
+    let mut file = File::create(&temp_path)?;
+    let mut db = None;
+    for (index, entries) in databases.iter().enumerate() {
+        if entries.is_empty() {
+            continue;
+        }
+        file.write_all(&select(index))?;
+        db = Some(index);
+        for entry in entries {
+            for command in rewrite_commands(entry) {
+                file.write_all(&command)?;
+            }
+        }
+    }
+
+    // no write can run until the new file replaces the old one
+    let mut state = lock_then_release(&aof.state);
+    for (write_db, request) in state.rewritten.take().unwrap_or_default() {
+        if db != Some(write_db) {
+            file.write_all(&select(write_db))?;
+            db = Some(write_db);
+        }
+        file.write_all(&request)?;
+    }
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+
+    if state.file.is_some() {
+        state.file = Some(OpenOptions::new().append(true).open(path)?);
+        state.db = db;
+    }
+    Ok(())
+}
+
+/// Commands recreating the entry, its value added a batch of elements at a time
+pub fn rewrite_commands(entry: &SnapshotEntry) -> Vec<Vec<u8>> {
+    let key = &entry.key[..];
+    let batches = |name: &'static [u8], items: Vec<Vec<u8>>, per_item: usize| {
+        items
+            .chunks(REWRITE_ITEMS_PER_COMMAND * per_item)
+            .map(|chunk| {
+                let mut args = vec![Resp::BulkString(name), Resp::BulkString(key)];
+                args.extend(chunk.iter().map(|item| Resp::BulkString(item)));
+                encode_request(&args)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut commands = match &entry.value {
+        RedisValue::String(value) => vec![encode_request(&[
+            Resp::BulkString(b"SET"),
+            Resp::BulkString(key),
+            Resp::BulkString(value),
+        ])],
+        RedisValue::List(values) => batches(b"RPUSH", values.clone(), 1),
+        RedisValue::Set(values) => {
+            // sorted so a rewrite of the same set is the same file
+            let mut values: Vec<_> = values.iter().cloned().collect();
+            values.sort();
+            batches(b"SADD", values, 1)
+        }
+        RedisValue::Hash(fields) => {
+            let mut fields: Vec<_> = fields.iter().collect();
+            fields.sort();
+            let items = fields
+                .into_iter()
+                .flat_map(|(field, value)| vec![field.clone(), value.clone()])
+                .collect();
+            batches(b"HSET", items, 2)
+        }
+        RedisValue::SortedSet(zset) => {
+            let items = zset
+                .iter()
+                .flat_map(|(member, score)| vec![score.to_string().into_bytes(), member.clone()])
+                .collect();
+            batches(b"ZADD", items, 2)
+        }
+    };
+
+    if let Some(expiry) = entry.expiry {
+        // the entries of a snapshot are not expired, though they may be by now
+        let millis = expiry.duration_left_millis().max(1).to_string();
+        commands.push(encode_request(&[
+            Resp::BulkString(b"PEXPIRE"),
+            Resp::BulkString(key),
+            Resp::BulkString(millis.as_bytes()),
+        ]));
+    }
+    commands
+}
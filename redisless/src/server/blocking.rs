@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use crate::storage::models::RedisString;
+
+use super::util::lock_then_release;
+
+/// Clients of a database blocked until an element is pushed to one of their keys, by a
+/// command such as `BLPOP`, or until any write to their key for `WAITKEY`.
+///
+/// A client registers its keys while holding their stripes of the storage, and a push
+/// notifies while holding the stripe of its key, so no push can fall between the moment a
+/// client finds its keys empty and the moment it starts waiting.
+#[derive(Default)]
+pub struct BlockedClients {
+    condvar: Condvar,
+    // number of clients blocked on each key
+    waiting: Mutex<HashMap<RedisString, usize>>,
+    // number of clients blocked, whatever their keys
+    clients: AtomicUsize,
+}
+
+impl BlockedClients {
+    /// Release the storage guard, locking the stripes of keys, until a push to one of keys
+    /// or until timeout elapses when it is set. It may return early, so the keys must be
+    /// checked again.
+    pub fn wait<S>(&self, keys: &[RedisString], storage: S, timeout: Option<Duration>) {
+        let mut waiting = lock_then_release(&self.waiting);
+        for key in keys {
+            *waiting.entry(key.clone()).or_insert(0) += 1;
+        }
+        // a push now waits for the client to wait before notifying it
+        drop(storage);
+        self.clients.fetch_add(1, Ordering::SeqCst);
+
+        let mut waiting = match timeout {
+            Some(timeout) => self.condvar.wait_timeout(waiting, timeout).unwrap().0,
+            None => self.condvar.wait(waiting).unwrap(),
+        };
+        self.clients.fetch_sub(1, Ordering::SeqCst);
+        for key in keys {
+            if let Some(count) = waiting.get_mut(key) {
+                *count -= 1;
+                if *count == 0 {
+                    waiting.remove(key);
+                }
+            }
+        }
+    }
+
+    /// Number of clients blocked on any key
+    pub fn count(&self) -> usize {
+        self.clients.load(Ordering::SeqCst)
+    }
+
+    /// Wake up the clients blocked on key, to be called with the stripe of key locked
+    pub fn notify(&self, key: &[u8]) {
+        if lock_then_release(&self.waiting).contains_key(key) {
+            self.condvar.notify_all();
+        }
+    }
+}
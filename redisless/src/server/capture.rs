@@ -0,0 +1,52 @@
+//! Capture of the requests received (`command-capture-file`).
+//!
+//! A debugging aid to replay real traffic through the parser, rather than a way to persist
+//! the data set like the append only file: every request is captured as the bytes the client
+//! sent, read commands, inline requests and malformed bytes included. Each request is
+//! appended as `<len: u32> <bytes>`, the length little-endian, and the requests of concurrent
+//! clients are appended whole, in the order they are read.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use super::util::lock_then_release;
+
+/// Capture file of a server, disabled until it is opened
+#[derive(Default)]
+pub struct Capture {
+    enabled: AtomicBool,
+    file: Mutex<Option<File>>,
+}
+
+impl Capture {
+    /// Append the next requests to the file at path, created if needed
+    pub fn open(&self, path: &Path) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        *lock_then_release(&self.file) = Some(file);
+        self.enabled.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Append the bytes of a request, or of the malformed bytes dropped by the parser. A
+    /// failing write disables the capture rather than the commands.
+    pub fn record(&self, bytes: &[u8]) {
+        if bytes.is_empty() || !self.enabled.load(Ordering::SeqCst) {
+            return;
+        }
+        let mut file = lock_then_release(&self.file);
+        let written = match file.as_mut() {
+            Some(file) => file
+                .write_all(&(bytes.len() as u32).to_le_bytes())
+                .and_then(|_| file.write_all(bytes)),
+            None => return,
+        };
+        if let Err(err) = written {
+            log::warn!("writing the command capture file failed: {}", err);
+            *file = None;
+            self.enabled.store(false, Ordering::SeqCst);
+        }
+    }
+}
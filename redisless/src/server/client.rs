@@ -0,0 +1,77 @@
+use crossbeam_channel::Receiver;
+
+use super::pubsub::Subscriber;
+use crate::command::Command;
+use crate::storage::models::RedisString;
+
+/// State of a single connection
+#[derive(Debug, Default)]
+pub struct Client {
+    // unique to the connection, reported by `HELLO`
+    pub id: u64,
+    // address of the peer, or `unix:<path>` of the socket
+    pub addr: String,
+    // index of the database selected with `SELECT`
+    pub db: usize,
+    // set once the client is a replica after a `SYNC`, the write commands to stream to it
+    pub replica: Option<Receiver<Vec<u8>>>,
+    // set by the first `SUBSCRIBE` or `PSUBSCRIBE` of the client
+    pub subscriber: Option<Subscriber>,
+    // set by `CLIENT TRACKING ON`, the keys read are tracked for invalidation
+    pub tracking: bool,
+    // set by `MONITOR`, the commands of the other clients are fed to it
+    pub monitor: bool,
+    // name of the user, set by a successful `AUTH` or `HELLO ... AUTH`
+    pub user: Option<String>,
+    // set by `HELLO 3`, replies are then encoded in RESP3
+    pub resp3: bool,
+    // set by `HELLO ... SETNAME`
+    pub name: Option<String>,
+    // set by `MULTI`, the commands queued until `EXEC` or `DISCARD`
+    pub transaction: Option<Transaction>,
+    // set by a `CLIENT KILL` of its own connection, closed once the reply is written
+    pub killed: bool,
+    // set by `CLIENT REDISLESS-COMPRESS`, the length from which bulk strings are compressed
+    pub compression: Option<usize>,
+    // set by a `SHUTDOWN` which succeeded, the connection is closed without a reply and the
+    // server stops
+    pub shutdown: bool,
+}
+
+/// Commands queued by `MULTI`
+#[derive(Debug, Default)]
+pub struct Transaction {
+    pub queued: Vec<Queued>,
+    // set by a command which failed to be queued, such as an unknown one, so that `EXEC`
+    // discards the transaction
+    pub aborted: bool,
+}
+
+/// Command queued in a transaction, with what is needed to run it as if it was just received
+#[derive(Debug)]
+pub struct Queued {
+    pub command: Command,
+    // as sent to the replicas and the AOF
+    pub request: Vec<u8>,
+    pub write: bool,
+    // see `stats::command_name`
+    pub name: String,
+    // see `command_keys`
+    pub keys: Vec<RedisString>,
+}
+
+impl Client {
+    /// Whether the client is subscribed to a channel or a pattern, and so only accepts the
+    /// pub/sub commands
+    pub fn is_subscribed(&self) -> bool {
+        self.subscriber
+            .as_ref()
+            .is_some_and(|subscriber| subscriber.count() > 0)
+    }
+
+    /// Whether messages may be sent to the client at any time, and not only in reply to a
+    /// command
+    pub fn receives_messages(&self) -> bool {
+        self.is_subscribed() || self.tracking || self.monitor
+    }
+}
@@ -0,0 +1,61 @@
+//! Connections of the server, closed by `CLIENT KILL`.
+//!
+//! Each connection is registered with a closer which shuts its stream down, from the thread
+//! of the killing client: the read blocking the connection returns, and its thread ends as
+//! if the peer had closed it.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::command::options::KillFilter;
+
+use super::util::lock_then_release;
+
+/// Shuts the stream of a connection down
+pub type Closer = Box<dyn Fn() + Send>;
+
+struct Connection {
+    addr: String,
+    closer: Closer,
+}
+
+/// Open connections, by client id
+#[derive(Default)]
+pub struct Clients {
+    connections: Mutex<HashMap<u64, Connection>>,
+}
+
+impl Clients {
+    pub fn add(&self, id: u64, addr: String, closer: Closer) {
+        lock_then_release(&self.connections).insert(id, Connection { addr, closer });
+    }
+
+    pub fn remove(&self, id: u64) {
+        lock_then_release(&self.connections).remove(&id);
+    }
+
+    /// Remove the connections matching filter and close them, but the one of the client
+    /// `own_id`, which must reply before being closed. Return the ids killed.
+    pub fn kill(&self, filter: &KillFilter, own_id: u64) -> Vec<u64> {
+        let mut connections = lock_then_release(&self.connections);
+        let killed = connections
+            .iter()
+            .filter(|(id, connection)| {
+                filter.id.is_none_or(|filter| filter == **id)
+                    && filter
+                        .addr
+                        .as_ref()
+                        .is_none_or(|addr| *addr == connection.addr)
+            })
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in &killed {
+            if let Some(connection) = connections.remove(id) {
+                if *id != own_id {
+                    (connection.closer)();
+                }
+            }
+        }
+        killed
+    }
+}
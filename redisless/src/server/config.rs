@@ -0,0 +1,507 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::command::command_error::RedisCommandError;
+use crate::storage::models::EncodingLimits;
+
+pub const DEFAULT_PORT: u16 = 16379;
+
+/// Max number of logical databases, all of them being allocated when the server starts
+pub const MAX_DATABASES: usize = 65536;
+
+/// Policy selecting the keys to evict once the memory limit is reached (`maxmemory-policy`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaxMemoryPolicy {
+    NoEviction,
+    AllKeysLru,
+    AllKeysLfu,
+    AllKeysRandom,
+    VolatileLru,
+    VolatileLfu,
+    VolatileRandom,
+    VolatileTtl,
+}
+
+impl MaxMemoryPolicy {
+    /// Whether keys are picked by access frequency, the only case `OBJECT FREQ` is available
+    pub fn is_lfu(&self) -> bool {
+        matches!(
+            self,
+            MaxMemoryPolicy::AllKeysLfu | MaxMemoryPolicy::VolatileLfu
+        )
+    }
+
+    /// Name of the policy in the configuration of Redis, such as `allkeys-lru`
+    pub fn name(&self) -> &'static str {
+        match self {
+            MaxMemoryPolicy::NoEviction => "noeviction",
+            MaxMemoryPolicy::AllKeysLru => "allkeys-lru",
+            MaxMemoryPolicy::AllKeysLfu => "allkeys-lfu",
+            MaxMemoryPolicy::AllKeysRandom => "allkeys-random",
+            MaxMemoryPolicy::VolatileLru => "volatile-lru",
+            MaxMemoryPolicy::VolatileLfu => "volatile-lfu",
+            MaxMemoryPolicy::VolatileRandom => "volatile-random",
+            MaxMemoryPolicy::VolatileTtl => "volatile-ttl",
+        }
+    }
+
+    /// Policy of the configuration name of Redis, case insensitive
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            MaxMemoryPolicy::NoEviction,
+            MaxMemoryPolicy::AllKeysLru,
+            MaxMemoryPolicy::AllKeysLfu,
+            MaxMemoryPolicy::AllKeysRandom,
+            MaxMemoryPolicy::VolatileLru,
+            MaxMemoryPolicy::VolatileLfu,
+            MaxMemoryPolicy::VolatileRandom,
+            MaxMemoryPolicy::VolatileTtl,
+        ]
+        .iter()
+        .copied()
+        .find(|policy| policy.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Bounds of the bytes queued to a connection receiving messages, such as a pub/sub
+/// subscriber, past which it is closed. A bound of `0` is unlimited.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputBufferLimit {
+    /// Bytes past which the connection is closed at once
+    pub hard: u64,
+    /// Bytes past which the connection is closed when they stay past it for `soft_secs`
+    pub soft: u64,
+    pub soft_secs: u64,
+}
+
+/// Parameter of a configuration the server can't start with, and the reason
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidConfig {
+    pub name: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration '{}': {}", self.name, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidConfig {}
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// TCP port to listen on, `0` disables the TCP listener
+    pub port: u16,
+    /// Path of the Unix domain socket to listen on, in addition to TCP
+    pub unixsocket: Option<PathBuf>,
+    /// Permissions applied to the Unix domain socket file (e.g. `0o700`)
+    pub unixsocketperm: Option<u32>,
+    /// Protocol version, 2 or 3, of the connections on `port` until a `HELLO` sets theirs
+    pub protocol: u8,
+    /// Protocol version, 2 or 3, of the connections on `unixsocket` until a `HELLO` sets
+    /// theirs
+    pub unixsocket_protocol: u8,
+    /// Directory where the snapshot file is written
+    pub dir: PathBuf,
+    /// Name of the snapshot file written by `SAVE` and `BGSAVE`
+    pub dbfilename: String,
+    /// Save points (`save`): a snapshot is written in the background once at least
+    /// `changes` writes ran within `secs` of the last save, given as `(secs, changes)`.
+    /// Unlike Redis there is none by default. Settable with `CONFIG SET` as
+    /// `"3600 1 300 100"`, an empty string removes them.
+    pub save: Vec<(u64, u64)>,
+    /// Append every write to a file, replayed when the server starts (`appendonly`)
+    pub appendonly: bool,
+    /// Name of the append only file, in `dir`, rewritten by `BGREWRITEAOF`
+    pub appendfilename: String,
+    /// File every request received is appended to, as the raw bytes sent by the client
+    /// prefixed by their length, malformed ones included, to replay them through the parser.
+    /// Not captured when `None`, and unlike the append only file never replayed.
+    pub command_capture_file: Option<PathBuf>,
+    /// Number of logical databases, selected with `SELECT`
+    pub databases: usize,
+    /// Stripes the keyspace of each database is sharded into, each behind its own lock, so
+    /// commands on keys of different stripes run concurrently
+    pub keyspace_stripes: usize,
+    /// Seed of the generator used by commands returning random elements, picked at random
+    /// when `None`. A fixed seed makes their replies reproducible.
+    pub seed: Option<u64>,
+    /// Execution budget of a single command, after which long running commands such as
+    /// `KEYS` are aborted. Unlimited when `None`.
+    pub command_time_limit: Option<Duration>,
+    /// Disable Nagle's algorithm on the TCP connections (`tcp-nodelay`), so small replies
+    /// are not delayed
+    pub tcp_nodelay: bool,
+    /// Idle secs after which TCP keepalive probes are sent to a client (`tcp-keepalive`),
+    /// `0` leaves the keepalive of the system
+    pub tcp_keepalive_secs: u64,
+    /// Max length in bytes of a bulk string sent by a client (`proto-max-bulk-len`)
+    pub proto_max_bulk_len: usize,
+    /// Estimated bytes of used memory past which keys are evicted (`maxmemory`), `0` is
+    /// unlimited
+    pub maxmemory: u64,
+    /// Eviction policy (`maxmemory-policy`), settable with `CONFIG SET`. The `volatile`
+    /// policies only evict the keys with an expiry, an LFU policy also enables `OBJECT FREQ`.
+    pub maxmemory_policy: MaxMemoryPolicy,
+    /// Keys of each database sampled by every round of an eviction (`maxmemory-samples`),
+    /// the more samples the closer to an exact policy
+    pub maxmemory_samples: usize,
+    /// Reject the write commands of clients while replicating a primary (`replica-read-only`)
+    pub replica_read_only: bool,
+    /// Reject the write commands of clients whatever the role of the server (`read-only`),
+    /// such as on a read only cache node. The writes streamed by a primary still apply.
+    pub read_only: bool,
+    /// Version of Redis reported by `INFO server` (`redis_version`), for the clients which
+    /// enable features by the version of the server
+    pub redis_version: String,
+    /// Commands only available under a new name, or disabled when renamed to an empty
+    /// string (`rename-command`), keyed by their original name
+    pub rename_commands: HashMap<String, String>,
+    /// TTL in secs given to the keys of a `SET` without `EX`, `PX` or `KEEPTTL`
+    /// (`default-ttl-secs`), `0` disables it
+    pub default_ttl_secs: u64,
+    /// Keyspace events published to pub/sub (`notify-keyspace-events`), with the flags of
+    /// Redis such as `"KEA"`. Empty disables the notifications.
+    pub notify_keyspace_events: String,
+    /// Password of the default user (`requirepass`), which clients must send with `AUTH`
+    /// or `HELLO` before any other command. The default user needs no password when `None`,
+    /// other users are added with `ACL SETUSER`.
+    pub requirepass: Option<String>,
+    /// Close the connection of a client sending an unknown command, once the error is
+    /// replied. The unknown commands are logged either way.
+    pub strict_unknown_commands: bool,
+    /// On a malformed request, drop the bytes up to the next `*` and read on, instead of
+    /// closing the connection as Redis does, such as behind a proxy garbling the stream.
+    /// The protocol error is replied either way.
+    pub resync_on_protocol_error: bool,
+    /// Reply the counters of the internals of the server to `DEBUG INTERNAL_STATS`, such as
+    /// the keys of each stripe, for white-box tests (`debug-internal-stats`). The command is
+    /// refused otherwise.
+    pub debug_internal_stats: bool,
+    /// Max bytes of a string reported as an `embstr` by `OBJECT ENCODING`, a longer one
+    /// being `raw` (`embstr-size-limit`, not a parameter of Redis where it is fixed to 44),
+    /// settable with `CONFIG SET`
+    pub embstr_size_limit: usize,
+    /// Max fields of a hash reported as a `listpack` by `OBJECT ENCODING`
+    /// (`hash-max-listpack-entries`), settable with `CONFIG SET`
+    pub hash_max_listpack_entries: usize,
+    /// Max bytes of the fields and values of a hash reported as a `listpack`
+    /// (`hash-max-listpack-value`), settable with `CONFIG SET`
+    pub hash_max_listpack_value: usize,
+    /// Max members of a set of integers reported as an `intset` by `OBJECT ENCODING`
+    /// (`set-max-intset-entries`), settable with `CONFIG SET`
+    pub set_max_intset_entries: usize,
+    /// Max members of a set reported as a `listpack` (`set-max-listpack-entries`), settable
+    /// with `CONFIG SET`
+    pub set_max_listpack_entries: usize,
+    /// Max bytes of the members of a set reported as a `listpack`
+    /// (`set-max-listpack-value`), settable with `CONFIG SET`
+    pub set_max_listpack_value: usize,
+    /// Max members of a sorted set reported as a `listpack` (`zset-max-listpack-entries`),
+    /// settable with `CONFIG SET`
+    pub zset_max_listpack_entries: usize,
+    /// Max bytes of the members of a sorted set reported as a `listpack`
+    /// (`zset-max-listpack-value`), settable with `CONFIG SET`
+    pub zset_max_listpack_value: usize,
+    /// Max size of a list reported as a `listpack` (`list-max-listpack-size`), settable with
+    /// `CONFIG SET`: its entries when positive, and from -1 to -5 its bytes, 4KB to 64KB
+    pub list_max_listpack_size: i64,
+    /// Milliseconds from which a command is sampled by `LATENCY`
+    /// (`latency-monitor-threshold`), settable with `CONFIG SET`. `0` disables the sampling.
+    pub latency_monitor_threshold: u64,
+    /// Output buffer limit of the connections receiving messages: the pub/sub subscribers,
+    /// the tracking clients and the monitors (`client-output-buffer-limit pubsub`)
+    pub client_output_buffer_limit_pubsub: OutputBufferLimit,
+    /// Keep the fields of the hashes in the order they were added (`hash-preserve-order`),
+    /// so that `HKEYS`, `HVALS` and `HGETALL` reply in a stable order. The order is not kept
+    /// by the snapshots.
+    pub hash_preserve_order: bool,
+}
+
+impl ServerConfig {
+    pub fn new(port: u16) -> Self {
+        ServerConfig {
+            port,
+            ..Default::default()
+        }
+    }
+
+    /// Check the parameters the server can't start with
+    pub fn validate(&self) -> Result<(), InvalidConfig> {
+        if !(1..=MAX_DATABASES).contains(&self.databases) {
+            return Err(InvalidConfig {
+                name: "databases",
+                reason: format!(
+                    "{} is not between 1 and {} inclusive",
+                    self.databases, MAX_DATABASES
+                ),
+            });
+        }
+        for (name, protocol) in [
+            ("protocol", self.protocol),
+            ("unixsocket-protocol", self.unixsocket_protocol),
+        ] {
+            if !(2..=3).contains(&protocol) {
+                return Err(InvalidConfig {
+                    name,
+                    reason: format!("protocol version {} is not 2 nor 3", protocol),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn snapshot_path(&self) -> PathBuf {
+        self.dir.join(&self.dbfilename)
+    }
+
+    pub fn aof_path(&self) -> PathBuf {
+        self.dir.join(&self.appendfilename)
+    }
+
+    /// Parameters reported by `CONFIG GET`, under their Redis name
+    pub fn parameters(&self) -> Vec<(&'static str, String)> {
+        let yes_no = |enabled| if enabled { "yes" } else { "no" }.to_string();
+        vec![
+            ("port", self.port.to_string()),
+            (
+                "unixsocket",
+                self.unixsocket
+                    .as_ref()
+                    .map_or(String::new(), |path| path.display().to_string()),
+            ),
+            (
+                "unixsocketperm",
+                format!("{:o}", self.unixsocketperm.unwrap_or(0)),
+            ),
+            ("dir", self.dir.display().to_string()),
+            ("dbfilename", self.dbfilename.clone()),
+            (
+                "save",
+                self.save
+                    .iter()
+                    .map(|(secs, changes)| format!("{} {}", secs, changes))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            ("appendonly", yes_no(self.appendonly)),
+            ("appendfilename", self.appendfilename.clone()),
+            (
+                "command-capture-file",
+                self.command_capture_file
+                    .as_ref()
+                    .map_or(String::new(), |path| path.display().to_string()),
+            ),
+            ("databases", self.databases.to_string()),
+            ("keyspace-stripes", self.keyspace_stripes.to_string()),
+            ("tcp-nodelay", yes_no(self.tcp_nodelay)),
+            ("tcp-keepalive", self.tcp_keepalive_secs.to_string()),
+            ("proto-max-bulk-len", self.proto_max_bulk_len.to_string()),
+            ("maxmemory", self.maxmemory.to_string()),
+            ("maxmemory-policy", self.maxmemory_policy.name().to_string()),
+            ("maxmemory-samples", self.maxmemory_samples.to_string()),
+            ("replica-read-only", yes_no(self.replica_read_only)),
+            ("read-only", yes_no(self.read_only)),
+            ("redis-version", self.redis_version.clone()),
+            ("default-ttl-secs", self.default_ttl_secs.to_string()),
+            (
+                "notify-keyspace-events",
+                self.notify_keyspace_events.clone(),
+            ),
+            ("requirepass", self.requirepass.clone().unwrap_or_default()),
+            ("embstr-size-limit", self.embstr_size_limit.to_string()),
+            (
+                "hash-max-listpack-entries",
+                self.hash_max_listpack_entries.to_string(),
+            ),
+            (
+                "hash-max-listpack-value",
+                self.hash_max_listpack_value.to_string(),
+            ),
+            (
+                "set-max-intset-entries",
+                self.set_max_intset_entries.to_string(),
+            ),
+            (
+                "set-max-listpack-entries",
+                self.set_max_listpack_entries.to_string(),
+            ),
+            (
+                "set-max-listpack-value",
+                self.set_max_listpack_value.to_string(),
+            ),
+            (
+                "zset-max-listpack-entries",
+                self.zset_max_listpack_entries.to_string(),
+            ),
+            (
+                "zset-max-listpack-value",
+                self.zset_max_listpack_value.to_string(),
+            ),
+            (
+                "list-max-listpack-size",
+                self.list_max_listpack_size.to_string(),
+            ),
+            (
+                "latency-monitor-threshold",
+                self.latency_monitor_threshold.to_string(),
+            ),
+            (
+                "client-output-buffer-limit",
+                format!(
+                    "pubsub {} {} {}",
+                    self.client_output_buffer_limit_pubsub.hard,
+                    self.client_output_buffer_limit_pubsub.soft,
+                    self.client_output_buffer_limit_pubsub.soft_secs
+                ),
+            ),
+            ("hash-preserve-order", yes_no(self.hash_preserve_order)),
+        ]
+    }
+
+    /// Set the parameter of `CONFIG SET` given by its Redis name. Only some parameters can
+    /// be set once the server is started.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), RedisCommandError> {
+        let integer = || {
+            value.parse::<usize>().map_err(|_| {
+                RedisCommandError::InvalidConfigValue(
+                    name.to_string(),
+                    "argument couldn't be parsed into an integer",
+                )
+            })
+        };
+        match name {
+            "embstr-size-limit" => self.embstr_size_limit = integer()?,
+            "hash-max-listpack-entries" => self.hash_max_listpack_entries = integer()?,
+            "hash-max-listpack-value" => self.hash_max_listpack_value = integer()?,
+            "set-max-intset-entries" => self.set_max_intset_entries = integer()?,
+            "set-max-listpack-entries" => self.set_max_listpack_entries = integer()?,
+            "set-max-listpack-value" => self.set_max_listpack_value = integer()?,
+            "zset-max-listpack-entries" => self.zset_max_listpack_entries = integer()?,
+            "zset-max-listpack-value" => self.zset_max_listpack_value = integer()?,
+            "list-max-listpack-size" => {
+                self.list_max_listpack_size = value.parse::<i64>().map_err(|_| {
+                    RedisCommandError::InvalidConfigValue(
+                        name.to_string(),
+                        "argument couldn't be parsed into an integer",
+                    )
+                })?
+            }
+            "latency-monitor-threshold" => self.latency_monitor_threshold = integer()? as u64,
+            "save" => {
+                self.save = parse_save_points(value).ok_or_else(|| {
+                    RedisCommandError::InvalidConfigValue(
+                        name.to_string(),
+                        "Invalid save parameters",
+                    )
+                })?
+            }
+            "maxmemory-policy" => {
+                self.maxmemory_policy = MaxMemoryPolicy::from_name(value).ok_or_else(|| {
+                    RedisCommandError::InvalidConfigValue(
+                        name.to_string(),
+                        "argument(s) must be one of the following: volatile-lru, allkeys-lru, \
+                         volatile-lfu, allkeys-lfu, volatile-random, allkeys-random, \
+                         volatile-ttl, noeviction",
+                    )
+                })?
+            }
+            _ if self
+                .parameters()
+                .iter()
+                .any(|(parameter, _)| *parameter == name) =>
+            {
+                return Err(RedisCommandError::InvalidConfigValue(
+                    name.to_string(),
+                    "can't set immutable config",
+                ))
+            }
+            _ => return Err(RedisCommandError::UnknownConfigParameter(name.to_string())),
+        }
+        Ok(())
+    }
+
+    pub fn encoding_limits(&self) -> EncodingLimits {
+        EncodingLimits {
+            embstr_size_limit: self.embstr_size_limit,
+            hash_max_listpack_entries: self.hash_max_listpack_entries,
+            hash_max_listpack_value: self.hash_max_listpack_value,
+            set_max_intset_entries: self.set_max_intset_entries,
+            set_max_listpack_entries: self.set_max_listpack_entries,
+            set_max_listpack_value: self.set_max_listpack_value,
+            zset_max_listpack_entries: self.zset_max_listpack_entries,
+            zset_max_listpack_value: self.zset_max_listpack_value,
+            list_max_listpack_size: self.list_max_listpack_size,
+        }
+    }
+}
+
+/// The `(secs, changes)` pairs of `save`, `None` unless the value is an even number of
+/// integers
+fn parse_save_points(value: &str) -> Option<Vec<(u64, u64)>> {
+    let numbers = value
+        .split_whitespace()
+        .map(|number| number.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if numbers.len() % 2 != 0 {
+        return None;
+    }
+    Some(numbers.chunks(2).map(|pair| (pair[0], pair[1])).collect())
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            port: DEFAULT_PORT,
+            unixsocket: None,
+            unixsocketperm: None,
+            protocol: 2,
+            unixsocket_protocol: 2,
+            dir: PathBuf::from("."),
+            dbfilename: String::from("dump.rdb"),
+            save: vec![],
+            appendonly: false,
+            appendfilename: String::from("appendonly.aof"),
+            command_capture_file: None,
+            databases: 16,
+            keyspace_stripes: 16,
+            seed: None,
+            command_time_limit: None,
+            tcp_nodelay: true,
+            tcp_keepalive_secs: 300,
+            proto_max_bulk_len: 512 * 1024 * 1024,
+            maxmemory: 0,
+            maxmemory_policy: MaxMemoryPolicy::NoEviction,
+            maxmemory_samples: 5,
+            replica_read_only: true,
+            read_only: false,
+            redis_version: "7.0.0".to_string(),
+            rename_commands: HashMap::new(),
+            default_ttl_secs: 0,
+            notify_keyspace_events: String::new(),
+            requirepass: None,
+            strict_unknown_commands: false,
+            resync_on_protocol_error: false,
+            debug_internal_stats: false,
+            embstr_size_limit: 44,
+            hash_max_listpack_entries: 128,
+            hash_max_listpack_value: 64,
+            set_max_intset_entries: 512,
+            set_max_listpack_entries: 128,
+            set_max_listpack_value: 64,
+            zset_max_listpack_entries: 128,
+            zset_max_listpack_value: 64,
+            list_max_listpack_size: -2,
+            latency_monitor_threshold: 0,
+            client_output_buffer_limit_pubsub: OutputBufferLimit {
+                hard: 32 * 1024 * 1024,
+                soft: 8 * 1024 * 1024,
+                soft_secs: 60,
+            },
+            hash_preserve_order: false,
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+
+use chrono::Utc;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::storage::snapshot::{self, SnapshotEntry};
+use crate::storage::Storage;
+
+use super::acl::Acl;
+use super::blocking::BlockedClients;
+use super::capture::Capture;
+use super::clients::Clients;
+use super::database::Database;
+use super::expiration::ExpireStats;
+use super::latency::LatencyMonitor;
+use super::monitor::Monitors;
+use super::notifications::KeyspaceEvents;
+use super::pubsub::PubSub;
+use super::replication::Replication;
+use super::stats::CommandStats;
+use super::tracking::Tracking;
+use super::ServerConfig;
+
+/// State shared by every connection of a server
+pub struct ServerContext<T: Storage> {
+    // one storage per logical database, indexed by the `SELECT`ed db
+    pub databases: Vec<Database<T>>,
+    // settable with `CONFIG SET`, see `config`
+    config: RwLock<ServerConfig>,
+    // unix timestamp in secs of the last successful snapshot
+    pub last_save: Arc<AtomicI64>,
+    pub bgsave_in_progress: Arc<AtomicBool>,
+    // write commands run since the last successful snapshot, see `save`
+    pub dirty: Arc<AtomicU64>,
+    // generator behind the commands returning random elements
+    pub rng: Mutex<StdRng>,
+    pub replication: Replication,
+    // clients blocked on the keys of each database
+    pub blocked: Vec<BlockedClients>,
+    pub pubsub: PubSub,
+    // parsed from `notify-keyspace-events`
+    pub keyspace_events: KeyspaceEvents,
+    next_client_id: AtomicU64,
+    // open connections, see `CLIENT KILL`
+    pub clients: Clients,
+    // keys read by the clients with `CLIENT TRACKING` on
+    pub tracking: Tracking,
+    // users, the default one with the password of `requirepass`
+    pub acl: Acl,
+    // connections which ran `MONITOR`
+    pub monitors: Monitors,
+    // requests received, appended to `command-capture-file` when one is set
+    pub capture: Capture,
+    // connections in a transaction, from their `MULTI` to their `EXEC` or `DISCARD`
+    pub transactions: AtomicUsize,
+    // incremented each time the server starts or stops, the background threads spawned by
+    // a start end with the next change, see `is_generation`
+    pub generation: AtomicU64,
+    // set by `SHUTDOWN`, the listener stops as if `Server::stop` was called
+    pub shutdown: AtomicBool,
+    // whether the expired keys are removed in the background, see `DEBUG SET-ACTIVE-EXPIRE`
+    pub active_expire: AtomicBool,
+    // keys removed by the background expiry, see `INFO stats`
+    pub expire_stats: ExpireStats,
+    // calls of each command, see `INFO commandstats`
+    pub stats: CommandStats,
+    // commands which ran for too long, see `LATENCY`
+    pub latency: LatencyMonitor,
+    // 40 random hex chars identifying this run of the server, as in Redis
+    pub run_id: String,
+}
+
+impl<T: Storage + Default> ServerContext<T> {
+    /// `storage` backs the db 0, the other databases start empty
+    pub fn new(storage: T, config: ServerConfig) -> Self {
+        let (stripes, limits) = (config.keyspace_stripes, config.encoding_limits());
+        let ordered = config.hash_preserve_order;
+        let mut databases = vec![Database::new(storage, stripes, limits, ordered)];
+        for _ in 1..config.databases {
+            databases.push(Database::new(T::default(), stripes, limits, ordered));
+        }
+        let blocked = databases
+            .iter()
+            .map(|_| BlockedClients::default())
+            .collect();
+
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        let keyspace_events = KeyspaceEvents::parse(&config.notify_keyspace_events);
+        let acl = Acl::new(config.requirepass.as_deref());
+
+        ServerContext {
+            databases,
+            rng: Mutex::new(rng),
+            config: RwLock::new(config),
+            last_save: Arc::new(AtomicI64::new(Utc::now().timestamp())),
+            bgsave_in_progress: Arc::new(AtomicBool::new(false)),
+            dirty: Arc::new(AtomicU64::new(0)),
+            replication: Replication::default(),
+            blocked,
+            pubsub: PubSub::default(),
+            keyspace_events,
+            next_client_id: AtomicU64::new(1),
+            clients: Clients::default(),
+            tracking: Tracking::default(),
+            acl,
+            monitors: Monitors::default(),
+            capture: Capture::default(),
+            transactions: AtomicUsize::new(0),
+            generation: AtomicU64::new(0),
+            shutdown: AtomicBool::new(false),
+            active_expire: AtomicBool::new(true),
+            expire_stats: ExpireStats::default(),
+            stats: CommandStats::default(),
+            latency: LatencyMonitor::default(),
+            // random even when the seed is set, another run is another server
+            run_id: (0..20)
+                .map(|_| format!("{:02x}", rand::thread_rng().gen::<u8>()))
+                .collect(),
+        }
+    }
+}
+
+impl<T: Storage> ServerContext<T> {
+    pub fn database(&self, index: usize) -> &Database<T> {
+        &self.databases[index]
+    }
+
+    /// Current config of the server. The guard must not be held while running a command,
+    /// which may set the config.
+    pub fn config(&self) -> RwLockReadGuard<'_, ServerConfig> {
+        self.config.read().unwrap()
+    }
+
+    /// Replace the config of the server, as `CONFIG SET` does, and apply it to the
+    /// databases
+    pub fn set_config(&self, config: ServerConfig) {
+        let limits = config.encoding_limits();
+        *self.config.write().unwrap() = config;
+        for db in &self.databases {
+            db.lock_all().set_encoding_limits(limits);
+        }
+    }
+
+    /// Id of a new connection, never given to another one
+    pub fn new_client_id(&self) -> u64 {
+        self.next_client_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Whether the server is still in the generation a background thread was spawned in,
+    /// so still runs since that start
+    pub fn is_generation(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::SeqCst) == generation
+    }
+
+    /// Copy the entries of every database, indexed by db. All of them are locked at once
+    /// so the snapshot is consistent across databases.
+    pub fn take_snapshot(&self) -> Vec<Vec<SnapshotEntry>> {
+        let databases: Vec<_> = self.databases.iter().map(|db| db.lock_all()).collect();
+        databases.iter().map(snapshot::take).collect()
+    }
+
+    /// Write a snapshot of every database to the snapshot file, as `SAVE` does
+    pub fn save(&self) -> io::Result<()> {
+        let dirty = self.dirty.load(Ordering::SeqCst);
+        let entries = self.take_snapshot();
+        snapshot::save(&self.config().snapshot_path(), &entries)?;
+        self.saved(dirty);
+        Ok(())
+    }
+
+    /// Record a successful snapshot, taken once `dirty` writes were counted. The writes
+    /// counted since then are left to the next snapshot, even those it holds.
+    pub fn saved(&self, dirty: u64) {
+        self.last_save
+            .store(Utc::now().timestamp(), Ordering::SeqCst);
+        self.dirty.fetch_sub(dirty, Ordering::SeqCst);
+    }
+
+    /// Replace the content of every database by the one of the snapshot. Databases missing
+    /// from the snapshot end up empty, and the ones this server does not have are dropped.
+    pub fn restore_snapshot(&self, snapshot: Vec<Vec<SnapshotEntry>>) {
+        let mut databases: Vec<_> = self.databases.iter().map(|db| db.lock_all()).collect();
+        databases.iter_mut().for_each(|storage| storage.clear());
+        for (storage, entries) in databases.iter_mut().zip(snapshot) {
+            snapshot::restore(storage, entries);
+        }
+    }
+
+    /// Save a snapshot to path then replace every database by the content loaded back from
+    /// it, as `DEBUG RELOAD` does. The databases stay locked in between, so no write is lost.
+    pub fn reload_snapshot(&self, path: &Path) -> io::Result<()> {
+        let mut databases: Vec<_> = self.databases.iter().map(|db| db.lock_all()).collect();
+        let entries: Vec<_> = databases.iter().map(snapshot::take).collect();
+        snapshot::save(path, &entries)?;
+        let loaded = snapshot::load(path)?;
+        databases.iter_mut().for_each(|storage| storage.clear());
+        for (storage, entries) in databases.iter_mut().zip(loaded) {
+            snapshot::restore(storage, entries);
+        }
+        Ok(())
+    }
+}
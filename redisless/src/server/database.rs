@@ -0,0 +1,374 @@
+//! Logical databases, each with its keyspace sharded into stripes.
+//!
+//! A key belongs to the stripe given by its hash, and each stripe is a storage behind its
+//! own lock, so commands on keys of different stripes run concurrently. A command on
+//! several keys locks their stripes in ascending order, as does a command on the whole
+//! keyspace, so two commands never wait on each other's locks. The databases of a server
+//! are always locked in ascending order too.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::storage::models::{
+    EncodingLimits, Expiry, RedisMeta, RedisSortedSet, RedisString, RedisValue, ValueRef,
+};
+use crate::storage::{snapshot, Storage};
+
+use super::util::{key_hash, lock_then_release, scan_by};
+
+/// A logical database, selected with `SELECT`
+pub struct Database<T: Storage> {
+    stripes: Vec<Mutex<T>>,
+}
+
+impl<T: Storage + Default> Database<T> {
+    /// Database of count stripes, holding the entries of storage
+    pub fn new(mut storage: T, count: usize, limits: EncodingLimits, ordered_hashes: bool) -> Self {
+        let count = count.max(1);
+        storage.set_encoding_limits(limits);
+        storage.set_ordered_hashes(ordered_hashes);
+        let stripes = match count {
+            1 => vec![storage],
+            _ => {
+                let mut stripes: Vec<_> = (0..count).map(|_| T::default()).collect();
+                stripes.iter_mut().for_each(|stripe| {
+                    stripe.set_encoding_limits(limits);
+                    stripe.set_ordered_hashes(ordered_hashes);
+                });
+                for entry in snapshot::take(&storage) {
+                    stripes[stripe_of(&entry.key, count)].insert(
+                        &entry.key,
+                        entry.value,
+                        entry.expiry,
+                    );
+                }
+                stripes
+            }
+        };
+        Database {
+            stripes: stripes.into_iter().map(Mutex::new).collect(),
+        }
+    }
+}
+
+impl<T: Storage> Database<T> {
+    /// Lock the stripe of key, for a command which only reads or writes this key
+    pub fn lock(&self, key: &[u8]) -> MutexGuard<'_, T> {
+        lock_then_release(&self.stripes[stripe_of(key, self.stripes.len())])
+    }
+
+    /// Number of keys of each stripe, the stripes being locked one at a time
+    pub fn stripe_sizes(&self) -> Vec<u64> {
+        self.stripes
+            .iter()
+            .map(|stripe| lock_then_release(stripe).size())
+            .collect()
+    }
+
+    /// Lock the stripes of keys, for a command on all of them
+    pub fn lock_keys<K: AsRef<[u8]>>(&self, keys: impl IntoIterator<Item = K>) -> Locked<'_, T> {
+        let count = self.stripes.len();
+        let indexes: HashSet<_> = keys
+            .into_iter()
+            .map(|key| stripe_of(key.as_ref(), count))
+            .collect();
+        self.lock_stripes(|index| indexes.contains(&index))
+    }
+
+    /// Lock every stripe, for a command on the whole keyspace
+    pub fn lock_all(&self) -> Locked<'_, T> {
+        self.lock_stripes(|_| true)
+    }
+
+    /// One call of `SCAN` over the keys of every stripe, see `util::scan`. The stripes are
+    /// locked one at a time, in ascending order, so writes to the other stripes run during
+    /// the call, and a batch may span several stripes.
+    ///
+    /// The cursor is composite: its high bits are the index of the stripe the iteration is
+    /// in, as few as the stripe count needs, and its low bits the position within this
+    /// stripe, taken from the high bits of the hash of the key. A position is never 0, so
+    /// no cursor but the one of a new iteration and the one of a finished one is 0. Since a
+    /// key never changes stripe and its position does not depend on the other keys of its
+    /// stripe, the resizes of a stripe do not move the keys of the others, nor its own keys
+    /// relative to the cursor: a key present during the whole iteration is returned once,
+    /// or twice when its position collides with another key's.
+    pub fn scan(
+        &self,
+        cursor: u64,
+        pattern: Option<&[u8]>,
+        mut filter: impl FnMut(&mut T, &RedisString) -> bool,
+        count: usize,
+    ) -> (u64, Vec<RedisString>) {
+        let stripes = self.stripes.len();
+        let index_bits = usize::BITS - (stripes - 1).leading_zeros();
+        let position_bits = u64::BITS - index_bits;
+        let mut index = cursor.checked_shr(position_bits).unwrap_or(0) as usize;
+        let mut position = cursor & u64::MAX.checked_shr(index_bits).unwrap_or(0);
+
+        let mut keys = vec![];
+        while index < stripes && keys.len() < count {
+            let mut stripe = lock_then_release(&self.stripes[index]);
+            let entries = stripe.keys().into_iter().map(|key| (key, ()));
+            let (next, batch) = scan_by(
+                entries,
+                position,
+                pattern,
+                |key| filter(&mut stripe, key),
+                count - keys.len(),
+                |key| (key_hash(key) >> index_bits).max(1),
+            );
+            keys.extend(batch.into_iter().map(|(key, _)| key));
+            match next {
+                0 => {
+                    index += 1;
+                    position = 0;
+                }
+                next => position = next,
+            }
+        }
+
+        let cursor = match index < stripes {
+            true => (index as u64).checked_shl(position_bits).unwrap_or(0) | position,
+            false => 0,
+        };
+        (cursor, keys)
+    }
+
+    /// Call f with every key of the database which is not expired and its value, for an
+    /// embedder exporting the keyspace. The keys of a stripe are those it holds once the
+    /// iteration reaches it, which is locked for each batch of them, so the keys written
+    /// meanwhile may not be visited but no key is visited twice.
+    pub fn for_each_entry(&self, batch: usize, mut f: impl FnMut(&[u8], ValueRef<'_>)) {
+        for stripe in &self.stripes {
+            let keys = lock_then_release(stripe).keys();
+            for keys in keys.chunks(batch.max(1)) {
+                let mut stripe = lock_then_release(stripe);
+                for key in keys {
+                    // an expired key is dropped rather than visited
+                    if let Some(value) = stripe.entry(key) {
+                        f(key, value);
+                    }
+                }
+            }
+        }
+    }
+
+    // lock the selected stripes in ascending order
+    fn lock_stripes(&self, selected: impl Fn(usize) -> bool) -> Locked<'_, T> {
+        let guards = self
+            .stripes
+            .iter()
+            .enumerate()
+            .map(|(index, stripe)| match selected(index) {
+                true => Some(lock_then_release(stripe)),
+                false => None,
+            })
+            .collect();
+        Locked { guards }
+    }
+}
+
+// index of the stripe of key among count stripes
+fn stripe_of(key: &[u8], count: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % count as u64) as usize
+}
+
+/// Stripes of a database locked by a multi-key command, seen as a single storage. Only the
+/// keys of the locked stripes may be accessed, and the operations on every key such as
+/// `keys` or `size` only cover the locked stripes.
+pub struct Locked<'a, T: Storage> {
+    // indexed by stripe, `None` for the stripes which are not locked
+    guards: Vec<Option<MutexGuard<'a, T>>>,
+}
+
+impl<'a, T: Storage> Locked<'a, T> {
+    fn stripe(&self, key: &[u8]) -> &T {
+        self.guards[stripe_of(key, self.guards.len())]
+            .as_deref()
+            .expect("the stripe of the key is not locked")
+    }
+
+    fn stripe_mut(&mut self, key: &[u8]) -> &mut T {
+        let index = stripe_of(key, self.guards.len());
+        self.guards[index]
+            .as_deref_mut()
+            .expect("the stripe of the key is not locked")
+    }
+
+    fn locked(&self) -> impl Iterator<Item = &T> {
+        self.guards.iter().filter_map(|guard| guard.as_deref())
+    }
+}
+
+impl<'a, T: Storage> Storage for Locked<'a, T> {
+    fn write(&mut self, key: &[u8], value: &[u8]) {
+        self.stripe_mut(key).write(key, value)
+    }
+
+    fn extend(&mut self, key: &[u8], value: &[u8]) -> u64 {
+        self.stripe_mut(key).extend(key, value)
+    }
+
+    fn set_range(&mut self, key: &[u8], offset: usize, value: &[u8]) -> u64 {
+        self.stripe_mut(key).set_range(key, offset, value)
+    }
+
+    fn expire(&mut self, key: &[u8], expiry: Expiry) -> u32 {
+        self.stripe_mut(key).expire(key, expiry)
+    }
+
+    fn persist(&mut self, key: &[u8]) -> u32 {
+        self.stripe_mut(key).persist(key)
+    }
+
+    fn read(&mut self, key: &[u8]) -> Option<&[u8]> {
+        self.stripe_mut(key).read(key)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> u32 {
+        self.stripe_mut(key).remove(key)
+    }
+
+    fn contains(&mut self, key: &[u8]) -> bool {
+        self.stripe_mut(key).contains(key)
+    }
+
+    fn type_of(&mut self, key: &[u8]) -> &[u8] {
+        self.stripe_mut(key).type_of(key)
+    }
+
+    fn entry(&mut self, key: &[u8]) -> Option<ValueRef<'_>> {
+        self.stripe_mut(key).entry(key)
+    }
+
+    fn lwrite(&mut self, key: &[u8], values: Vec<RedisString>) {
+        self.stripe_mut(key).lwrite(key, values)
+    }
+
+    fn lread(&mut self, key: &[u8]) -> Option<&Vec<RedisString>> {
+        self.stripe_mut(key).lread(key)
+    }
+
+    fn swrite(&mut self, key: &[u8], values: HashSet<RedisString>) {
+        self.stripe_mut(key).swrite(key, values)
+    }
+
+    fn sread(&mut self, key: &[u8]) -> Option<&HashSet<RedisString>> {
+        self.stripe_mut(key).sread(key)
+    }
+
+    fn sremove(&mut self, key: &[u8], members: &[RedisString]) -> u64 {
+        self.stripe_mut(key).sremove(key, members)
+    }
+
+    fn hwrite(&mut self, key: &[u8], value: HashMap<RedisString, RedisString>) {
+        self.stripe_mut(key).hwrite(key, value)
+    }
+
+    fn hread(&mut self, key: &[u8], field_key: &[u8]) -> Option<&[u8]> {
+        self.stripe_mut(key).hread(key, field_key)
+    }
+
+    fn hread_all(&mut self, key: &[u8]) -> Option<&HashMap<RedisString, RedisString>> {
+        self.stripe_mut(key).hread_all(key)
+    }
+
+    fn hset(&mut self, key: &[u8], fields: Vec<(RedisString, RedisString)>) -> u64 {
+        self.stripe_mut(key).hset(key, fields)
+    }
+
+    fn hentries(&mut self, key: &[u8]) -> Option<Vec<(RedisString, RedisString)>> {
+        self.stripe_mut(key).hentries(key)
+    }
+
+    fn hexpiry(&mut self, key: &[u8], field: &[u8]) -> Option<Option<Expiry>> {
+        self.stripe_mut(key).hexpiry(key, field)
+    }
+
+    fn hset_expiry(&mut self, key: &[u8], field: &[u8], expiry: Option<Expiry>) {
+        self.stripe_mut(key).hset_expiry(key, field, expiry)
+    }
+
+    fn zwrite(&mut self, key: &[u8], value: RedisSortedSet) {
+        self.stripe_mut(key).zwrite(key, value)
+    }
+
+    fn zread(&mut self, key: &[u8]) -> Option<&RedisSortedSet> {
+        self.stripe_mut(key).zread(key)
+    }
+
+    fn size(&self) -> u64 {
+        self.locked().map(|stripe| stripe.size()).sum()
+    }
+
+    fn expires(&self) -> u64 {
+        self.locked().map(|stripe| stripe.expires()).sum()
+    }
+
+    fn meta(&self, key: &[u8]) -> Option<&RedisMeta> {
+        self.stripe(key).meta(key)
+    }
+
+    fn meta_mut(&mut self, key: &[u8]) -> Option<&mut RedisMeta> {
+        self.stripe_mut(key).meta_mut(key)
+    }
+
+    fn memory_usage(&self, key: &[u8]) -> Option<u64> {
+        self.stripe(key).memory_usage(key)
+    }
+
+    fn used_memory(&self) -> u64 {
+        self.locked().map(|stripe| stripe.used_memory()).sum()
+    }
+
+    fn keys_overhead(&self) -> u64 {
+        self.locked().map(|stripe| stripe.keys_overhead()).sum()
+    }
+
+    fn keys(&self) -> Vec<RedisString> {
+        self.locked().flat_map(|stripe| stripe.keys()).collect()
+    }
+
+    fn value(&self, key: &[u8]) -> Option<RedisValue> {
+        self.stripe(key).value(key)
+    }
+
+    fn insert(&mut self, key: &[u8], value: RedisValue, expiry: Option<Expiry>) {
+        self.stripe_mut(key).insert(key, value, expiry)
+    }
+
+    fn set_encoding_limits(&mut self, limits: EncodingLimits) {
+        self.guards
+            .iter_mut()
+            .filter_map(|guard| guard.as_deref_mut())
+            .for_each(|stripe| stripe.set_encoding_limits(limits));
+    }
+
+    fn set_ordered_hashes(&mut self, ordered: bool) {
+        self.guards
+            .iter_mut()
+            .filter_map(|guard| guard.as_deref_mut())
+            .for_each(|stripe| stripe.set_ordered_hashes(ordered));
+    }
+
+    fn clear(&mut self) {
+        self.guards
+            .iter_mut()
+            .filter_map(|guard| guard.as_deref_mut())
+            .for_each(|stripe| stripe.clear());
+    }
+
+    fn take_all(&mut self) -> Box<dyn Send> {
+        let stripes: Vec<_> = self
+            .guards
+            .iter_mut()
+            .filter_map(|guard| guard.as_deref_mut())
+            .map(|stripe| stripe.take_all())
+            .collect();
+        Box::new(stripes)
+    }
+}
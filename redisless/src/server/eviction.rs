@@ -0,0 +1,148 @@
+//! Eviction of keys once the used memory exceeds `maxmemory`.
+//!
+//! As in Redis the eviction is approximate: every round of a pass samples
+//! `maxmemory-samples` keys of each database into a pool of candidates, then evicts the best
+//! candidate of the pool. A key is sampled at most once per pass, so the rounds do not keep
+//! drawing the same cold key while other keys are never compared.
+//!
+//! The `volatile` policies only sample the keys with an expiry. When no key is left to evict,
+//! or under `noeviction`, and the used memory still exceeds `maxmemory`, the write commands
+//! which may allocate memory are refused with `OOM`. The others, such as `DEL`, still run so
+//! memory can be freed.
+
+use std::collections::HashSet;
+
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+
+use crate::command::command_error::RedisCommandError;
+use crate::protocol::response::RedisResponse;
+use crate::protocol::Resp;
+use crate::storage::models::{RedisMeta, RedisString};
+use crate::storage::Storage;
+
+use super::notifications::{notify, EventClass};
+use super::util::{encode_request, lock_then_release};
+use super::{MaxMemoryPolicy, ServerContext};
+
+// candidates kept between the rounds of a pass, the best ones
+const EVICTION_POOL_SIZE: usize = 16;
+
+/// Up to count keys of storage other than the excluded ones, drawn at random without
+/// replacement
+pub fn sample_keys<T: Storage>(
+    storage: &T,
+    rng: &mut StdRng,
+    count: usize,
+    excluded: &HashSet<RedisString>,
+) -> Vec<RedisString> {
+    sample_keys_where(storage, rng, count, excluded, |_| true)
+}
+
+// up to count keys of storage kept by keep, other than the excluded ones
+fn sample_keys_where<T: Storage>(
+    storage: &T,
+    rng: &mut StdRng,
+    count: usize,
+    excluded: &HashSet<RedisString>,
+    keep: impl Fn(&[u8]) -> bool,
+) -> Vec<RedisString> {
+    // sorted so a seeded generator draws the same keys whatever the iteration order
+    let mut keys = storage.keys();
+    keys.sort();
+    keys.into_iter()
+        .filter(|key| !excluded.contains(key) && keep(key))
+        .choose_multiple(rng, count)
+}
+
+/// Estimated bytes used by the keys of every database
+pub fn used_memory<T: Storage>(context: &ServerContext<T>) -> u64 {
+    context
+        .databases
+        .iter()
+        .map(|storage| storage.lock_all().used_memory())
+        .sum()
+}
+
+/// Evict keys until the used memory is back under `maxmemory`, returning the number of
+/// keys evicted, or `OutOfMemory` when it is still over `maxmemory` with no key left to
+/// evict or under `noeviction`. A replica does not evict, it removes the keys its primary
+/// evicts.
+pub fn evict<T: Storage>(context: &ServerContext<T>) -> Result<usize, RedisCommandError> {
+    let (maxmemory, policy, samples) = {
+        let config = context.config();
+        (
+            config.maxmemory,
+            config.maxmemory_policy,
+            config.maxmemory_samples,
+        )
+    };
+    if maxmemory == 0 || context.replication.is_replica() {
+        return Ok(0);
+    }
+    // the higher the score, the better the candidate
+    let (score, volatile): (fn(&RedisMeta) -> i64, bool) = match policy {
+        MaxMemoryPolicy::AllKeysLru => (|meta| meta.idle_time_millis(), false),
+        MaxMemoryPolicy::AllKeysLfu => (|meta| -(meta.frequency() as i64), false),
+        MaxMemoryPolicy::AllKeysRandom => (|_| 0, false),
+        MaxMemoryPolicy::VolatileLru => (|meta| meta.idle_time_millis(), true),
+        MaxMemoryPolicy::VolatileLfu => (|meta| -(meta.frequency() as i64), true),
+        MaxMemoryPolicy::VolatileRandom => (|_| 0, true),
+        // the soonest expiry first
+        MaxMemoryPolicy::VolatileTtl => (
+            |meta| {
+                meta.expiry
+                    .map_or(i64::MIN, |expiry| -expiry.duration_left_millis())
+            },
+            true,
+        ),
+        MaxMemoryPolicy::NoEviction if used_memory(context) > maxmemory => {
+            return Err(RedisCommandError::OutOfMemory)
+        }
+        MaxMemoryPolicy::NoEviction => return Ok(0),
+    };
+
+    let mut sampled = vec![HashSet::new(); context.databases.len()];
+    let mut pool: Vec<(i64, usize, RedisString)> = vec![];
+    let mut evicted = 0;
+    while used_memory(context) > maxmemory {
+        for (db, storage) in context.databases.iter().enumerate() {
+            let storage = storage.lock_all();
+            let mut rng = lock_then_release(&context.rng);
+            let keys = sample_keys_where(&storage, &mut rng, samples, &sampled[db], |key| {
+                !volatile || storage.meta(key).is_some_and(|meta| meta.expiry.is_some())
+            });
+            for key in keys {
+                if let Some(meta) = storage.meta(&key) {
+                    pool.push((score(meta), db, key.clone()));
+                }
+                sampled[db].insert(key);
+            }
+        }
+
+        // the best candidate last
+        pool.sort_by_key(|(score, ..)| *score);
+        pool.drain(..pool.len().saturating_sub(EVICTION_POOL_SIZE));
+        match pool.pop() {
+            Some((_, db, key)) => evicted += evict_key(context, db, &key) as usize,
+            // every key was sampled, there is nothing left to evict
+            None => return Err(RedisCommandError::OutOfMemory),
+        }
+    }
+    Ok(evicted)
+}
+
+// remove the key, as a `DEL` streamed to the replicas
+fn evict_key<T: Storage>(context: &ServerContext<T>, db: usize, key: &[u8]) -> u32 {
+    let request = encode_request(&[Resp::BulkString(b"DEL"), Resp::BulkString(key)]);
+    let mut removed = 0;
+    context.replication.run_write(db, &request, || {
+        removed = context.database(db).lock(key).remove(key);
+        RedisResponse::okay()
+    });
+    if removed > 0 {
+        notify(context, db, EventClass::Evicted, "evicted", key);
+        context.tracking.invalidate(db, &[key.to_vec()]);
+    }
+    removed
+}
@@ -0,0 +1,173 @@
+//! Active expiration of the keys.
+//!
+//! An expired key is removed when a command accesses it, and a background cycle also removes
+//! the expired keys nobody accesses. Both notify the `expired` event. As in Redis, the cycle
+//! runs 10 times per second: it samples keys with an expiry in each database, removes the
+//! expired ones, and samples again while more than a quarter of the sampled keys were
+//! expired, within a time budget. `DEBUG SET-ACTIVE-EXPIRE 0` turns the cycle off, and
+//! `INFO stats` reports how the cycles keep up.
+
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::seq::IteratorRandom;
+
+use crate::command::command_error::RedisCommandError;
+use crate::protocol::response::RedisResponse;
+use crate::protocol::Resp;
+use crate::storage::Storage;
+
+use super::notifications::{notify, EventClass};
+use super::util::{encode_request, lock_then_release};
+use super::ServerContext;
+
+const CYCLE_INTERVAL: Duration = Duration::from_millis(100);
+// longest time a cycle may take, a quarter of the interval
+const CYCLE_TIME_LIMIT: Duration = Duration::from_millis(25);
+// keys with an expiry sampled per database at each round of a cycle
+const KEYS_PER_ROUND: usize = 20;
+// percentage of expired keys in a round above which another round is run
+const ACCEPTABLE_STALE_PERCENT: usize = 25;
+
+/// Counters of the cycles, reported by `INFO stats`
+#[derive(Default)]
+pub struct ExpireStats {
+    stats: Mutex<ExpireStat>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct ExpireStat {
+    // keys removed by the cycles, not the ones removed as they are accessed
+    expired_keys: u64,
+    // moving average of the share of the sampled keys which were expired, from 0 to 1
+    stale_ratio: f64,
+    // cycles which stopped at their time limit while keys were still expiring
+    time_cap_reached_count: u64,
+}
+
+impl ExpireStats {
+    // count a cycle which sampled keys, removed the expired ones of them, and stopped at its
+    // time limit when time_cap_reached is set
+    fn record(&self, sampled: usize, removed: usize, time_cap_reached: bool) {
+        let mut stats = lock_then_release(&self.stats);
+        stats.expired_keys += removed as u64;
+        // as in Redis, each cycle weighs 5% of the average
+        let ratio = match sampled {
+            0 => 0.0,
+            sampled => removed as f64 / sampled as f64,
+        };
+        stats.stale_ratio = ratio * 0.05 + stats.stale_ratio * 0.95;
+        if time_cap_reached {
+            stats.time_cap_reached_count += 1;
+        }
+    }
+
+    /// `Stats` section of `INFO`
+    pub fn info(&self) -> String {
+        let stats = *lock_then_release(&self.stats);
+        format!(
+            "# Stats\r\nexpired_keys:{}\r\nexpired_stale_perc:{:.2}\r\n\
+             expired_time_cap_reached_count:{}\r\n",
+            stats.expired_keys,
+            stats.stale_ratio * 100.0,
+            stats.time_cap_reached_count
+        )
+    }
+}
+
+/// Run the cycles in a thread of their own until the server leaves generation, when it
+/// stops
+pub fn spawn_active_expire<T: Storage + Send + 'static>(
+    context: Arc<ServerContext<T>>,
+    generation: u64,
+) {
+    let _ = thread::Builder::new()
+        .name("active-expire".to_string())
+        .spawn(move || loop {
+            thread::sleep(CYCLE_INTERVAL);
+            if !context.is_generation(generation) {
+                break;
+            }
+            if context.active_expire.load(Ordering::SeqCst) {
+                active_expire_cycle(&context);
+            }
+        });
+}
+
+/// Remove expired keys of every database, returning how many were removed. A replica does
+/// not expire keys, it removes the keys its primary expires.
+pub fn active_expire_cycle<T: Storage>(context: &ServerContext<T>) -> usize {
+    if context.replication.is_replica() {
+        return 0;
+    }
+    let start = Instant::now();
+    let (mut total_sampled, mut expired) = (0, 0);
+    let mut time_cap_reached = false;
+    for db in 0..context.databases.len() {
+        loop {
+            let (sampled, removed) = expire_round(context, db);
+            total_sampled += sampled;
+            expired += removed;
+            let stale = sampled > 0 && removed * 100 > sampled * ACCEPTABLE_STALE_PERCENT;
+            if stale && start.elapsed() > CYCLE_TIME_LIMIT {
+                time_cap_reached = true;
+            }
+            if !stale || time_cap_reached {
+                break;
+            }
+        }
+    }
+    context
+        .expire_stats
+        .record(total_sampled, expired, time_cap_reached);
+    expired
+}
+
+// sample keys with an expiry of the db and remove the expired ones, returning the number of
+// sampled keys and of removed keys
+fn expire_round<T: Storage>(context: &ServerContext<T>, db: usize) -> (usize, usize) {
+    let (sampled, expired) = {
+        let storage = context.database(db).lock_all();
+        let sampled = storage
+            .keys()
+            .into_iter()
+            .filter(|key| matches!(storage.meta(key), Some(meta) if meta.expiry.is_some()))
+            .choose_multiple(&mut rand::thread_rng(), KEYS_PER_ROUND);
+        let expired = sampled
+            .iter()
+            .filter(|key| matches!(storage.meta(key), Some(meta) if meta.is_expired()))
+            .cloned()
+            .collect::<Vec<_>>();
+        (sampled.len(), expired)
+    };
+
+    let removed = expired
+        .iter()
+        .map(|key| expire_key(context, db, key) as usize)
+        .sum();
+    (sampled, removed)
+}
+
+/// Remove the expired key, as a `DEL` streamed to the replicas, notifying the `expired` event
+/// and invalidating the key once
+pub fn expire_key<T: Storage>(context: &ServerContext<T>, db: usize, key: &[u8]) -> u32 {
+    let request = encode_request(&[Resp::BulkString(b"DEL"), Resp::BulkString(key)]);
+    let mut removed = 0;
+    context.replication.run_write(db, &request, || {
+        let mut storage = context.database(db).lock(key);
+        // the key may have been set again since it was sampled, then it must not be
+        // removed from the replicas either
+        if !matches!(storage.meta(key), Some(meta) if meta.is_expired()) {
+            return RedisResponse::error(RedisCommandError::NoSuchKey);
+        }
+        removed = storage.remove(key);
+        RedisResponse::okay()
+    });
+    if removed > 0 {
+        notify(context, db, EventClass::Expired, "expired", key);
+        context.tracking.invalidate(db, &[key.to_vec()]);
+    }
+    removed
+}
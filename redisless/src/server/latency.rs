@@ -0,0 +1,109 @@
+//! Latency monitor, `LATENCY`.
+//!
+//! The commands running for at least `latency-monitor-threshold` milliseconds are sampled
+//! under the `command` event. As in Redis, each event keeps the worst latency of each
+//! second, for its last samples, along with the worst latency since it was reset.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Utc;
+
+use super::util::lock_then_release;
+
+// samples kept by an event, the oldest ones dropped first
+const HISTORY_LEN: usize = 160;
+
+/// Event of the commands running for too long
+pub const COMMAND_EVENT: &str = "command";
+
+#[derive(Default)]
+pub struct LatencyMonitor {
+    events: Mutex<HashMap<String, EventHistory>>,
+}
+
+#[derive(Default)]
+struct EventHistory {
+    // unix timestamp in secs and latency in ms, a sample per second
+    samples: VecDeque<(i64, u64)>,
+    // worst latency in ms of every sample
+    max: u64,
+}
+
+/// Latest sample of an event, as replied by `LATENCY LATEST`
+#[derive(Debug, PartialEq)]
+pub struct LatestSample {
+    pub event: String,
+    pub timestamp: i64,
+    pub latency: u64,
+    pub max: u64,
+}
+
+impl LatencyMonitor {
+    /// Sample the latency of event when it reaches the threshold in ms, 0 disabling it
+    pub fn record(&self, event: &str, latency: Duration, threshold: u64) {
+        let latency = latency.as_millis() as u64;
+        if threshold == 0 || latency < threshold {
+            return;
+        }
+        let now = Utc::now().timestamp();
+        let mut events = lock_then_release(&self.events);
+        let history = events.entry(event.to_string()).or_default();
+        history.max = history.max.max(latency);
+        match history.samples.back_mut() {
+            // the worst latency of the second is kept
+            Some((timestamp, worst)) if *timestamp == now => *worst = (*worst).max(latency),
+            _ => {
+                if history.samples.len() == HISTORY_LEN {
+                    history.samples.pop_front();
+                }
+                history.samples.push_back((now, latency));
+            }
+        }
+    }
+
+    /// The latest sample of every event, by event name
+    pub fn latest(&self) -> Vec<LatestSample> {
+        let events = lock_then_release(&self.events);
+        let mut latest: Vec<_> = events
+            .iter()
+            .filter_map(|(event, history)| {
+                let (timestamp, latency) = *history.samples.back()?;
+                Some(LatestSample {
+                    event: event.clone(),
+                    timestamp,
+                    latency,
+                    max: history.max,
+                })
+            })
+            .collect();
+        latest.sort_by(|a, b| a.event.cmp(&b.event));
+        latest
+    }
+
+    /// Timestamps and latencies of the samples of event, the oldest first
+    pub fn history(&self, event: &str) -> Vec<(i64, u64)> {
+        lock_then_release(&self.events)
+            .get(event)
+            .map(|history| history.samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Drop the samples of events, or of every event when empty. Returns the number of
+    /// events dropped.
+    pub fn reset(&self, events: &[String]) -> usize {
+        let mut histories = lock_then_release(&self.events);
+        match events {
+            [] => {
+                let count = histories.len();
+                histories.clear();
+                count
+            }
+            events => events
+                .iter()
+                .filter(|event| histories.remove(event.as_str()).is_some())
+                .count(),
+        }
+    }
+}
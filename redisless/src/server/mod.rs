@@ -1,30 +1,85 @@
-use std::io::ErrorKind;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream};
-use std::sync::{Arc, Mutex};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, SystemTime};
 
 use crossbeam_channel::{Receiver, Sender};
 use mpb::MPB;
-use rayon::ThreadPool;
 use uuid::Uuid;
 
 use util::*;
 
 use crate::cluster::peer::{Peer, PeersDiscovery, DEFAULT_NODE_LISTENING_PORT};
+use crate::storage::models::ValueRef;
 use crate::storage::Storage;
 
 #[cfg(test)]
 mod tests;
 
+mod acl;
+mod aof;
+mod blocking;
+mod capture;
+mod client;
+mod clients;
+mod config;
+mod context;
+mod database;
+mod eviction;
+mod expiration;
+mod latency;
+mod monitor;
+mod notifications;
+mod pubsub;
+mod replication;
+mod save;
+mod socket;
+mod stats;
+mod tracking;
 mod util;
 
+use client::Client;
+use clients::Closer;
+pub use config::{InvalidConfig, MaxMemoryPolicy, OutputBufferLimit, ServerConfig, MAX_DATABASES};
+use context::ServerContext;
+use pubsub::Subscriber;
+
+// how long a client receiving messages is waited for before writing the messages sent to it
+const MESSAGES_READ_TIMEOUT: Duration = Duration::from_millis(10);
+// how long writing a message waits for a client to read, before checking its output buffer
+const MESSAGES_WRITE_TIMEOUT: Duration = Duration::from_millis(10);
+// keys visited by `Server::for_each_key` under a single lock of their stripe
+const KEYSPACE_ITERATION_BATCH: usize = 128;
+
 type CloseConnection = bool;
 type ReceivedDataLength = usize;
 
+/// An embedded server.
+///
+/// It logs through the `log` facade, so it is quiet until the embedding application installs
+/// a logger: the start and stop of the server at the `info` level, the accepted and closed
+/// connections at the `debug` level, the commands refused at the `warn` level and the
+/// commands failing at the `debug` level.
 pub struct Server {
     server_state_bus: MPB<ServerState>,
     cluster_options: ServerClusterOptions,
+    keyspace: Arc<dyn Keyspace>,
+}
+
+// the databases of a server, whatever its storage
+trait Keyspace: Send + Sync {
+    fn for_each_key(&self, db: usize, f: &mut dyn FnMut(&[u8], ValueRef<'_>));
+}
+
+impl<T: Storage + Send> Keyspace for ServerContext<T> {
+    fn for_each_key(&self, db: usize, f: &mut dyn FnMut(&[u8], ValueRef<'_>)) {
+        self.database(db)
+            .for_each_entry(KEYSPACE_ITERATION_BATCH, |key, value| f(key, value));
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
@@ -39,7 +94,9 @@ pub enum ServerState {
 
 #[derive(Debug)]
 pub struct ServerClusterOptions {
+    #[allow(dead_code)]
     group_id: String,
+    #[allow(dead_code)]
     peers_discovery: PeersDiscovery,
     listening_socket_addr: SocketAddr,
 }
@@ -72,30 +129,64 @@ impl Default for ServerClusterOptions {
 }
 
 impl Server {
-    pub fn new<T: Storage + Send + 'static>(storage: T, port: u16) -> Self {
-        Server::new_with_cluster_options(storage, ServerClusterOptions::default(), port)
+    pub fn new<T: Storage + Default + Send + 'static>(storage: T, port: u16) -> Self {
+        Server::new_with_config(storage, ServerConfig::new(port))
     }
 
-    pub fn new_with_cluster_options<T: Storage + Send + 'static>(
+    pub fn new_with_cluster_options<T: Storage + Default + Send + 'static>(
         storage: T,
         cluster_options: ServerClusterOptions,
         port: u16,
     ) -> Self {
+        Server::_new(storage, ServerConfig::new(port), cluster_options)
+    }
+
+    /// # Panics
+    ///
+    /// When the configuration is invalid, see `try_new_with_config`
+    pub fn new_with_config<T: Storage + Default + Send + 'static>(
+        storage: T,
+        config: ServerConfig,
+    ) -> Self {
+        match Server::try_new_with_config(storage, config) {
+            Ok(server) => server,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Server with the given configuration, or the error of a parameter it can't start with
+    pub fn try_new_with_config<T: Storage + Default + Send + 'static>(
+        storage: T,
+        config: ServerConfig,
+    ) -> Result<Self, InvalidConfig> {
+        config.validate()?;
+        Ok(Server::_new(
+            storage,
+            config,
+            ServerClusterOptions::default(),
+        ))
+    }
+
+    fn _new<T: Storage + Default + Send + 'static>(
+        storage: T,
+        config: ServerConfig,
+        cluster_options: ServerClusterOptions,
+    ) -> Self {
+        let context = Arc::new(ServerContext::new(storage, config));
         let s = Server {
             server_state_bus: MPB::new(),
             cluster_options,
+            keyspace: context.clone(),
         };
 
-        s._init_configuration(format!("0.0.0.0:{}", port), storage);
+        s._init_configuration(context);
         s
     }
 
-    fn _init_configuration<A: Into<String>, T: Storage + Send + 'static>(
+    fn _init_configuration<T: Storage + Default + Send + 'static>(
         &self,
-        addr: A,
-        storage: T,
+        context: Arc<ServerContext<T>>,
     ) {
-        let addr = addr.into();
         let state_send = self.server_state_bus.sender();
         let state_recv = self.server_state_bus.receiver();
 
@@ -109,14 +200,22 @@ impl Server {
         let mut cluster_node = peer.into_cluster_node();
 
         let _ = thread::spawn(move || {
-            let addr = addr;
-            let storage = Arc::new(Mutex::new(storage));
+            if context.config().appendonly {
+                aof::start(&context);
+            }
+            let capture_file = context.config().command_capture_file.clone();
+            if let Some(path) = capture_file {
+                if let Err(err) = context.capture.open(&path) {
+                    log::warn!("opening the command capture file failed: {}", err);
+                }
+            }
+            replication::spawn_replica_link(context.clone());
 
             loop {
                 if let Ok(server_state) = state_recv.recv() {
                     if server_state == ServerState::Start {
                         // start local RESP server
-                        start_server(&addr, &state_send, &state_recv, &storage);
+                        start_server(&context, &state_send, &state_recv);
 
                         // start current node listener
                         cluster_node.start_listener();
@@ -139,7 +238,7 @@ impl Server {
         };
 
         let _ = thread::spawn(move || {
-            let _ = thread::sleep(Duration::from_millis(100));
+            thread::sleep(Duration::from_millis(100));
             let _ = send_state_ch.send(change_to);
         });
 
@@ -164,98 +263,339 @@ impl Server {
     pub fn stop(&self) -> Option<ServerState> {
         self.change_state(ServerState::Stop)
     }
+
+    /// Call f with every key of the database db which is not expired and its value, such as
+    /// to export the keyspace without `SCAN`. The keys are visited by batches, each under a
+    /// brief lock of the keys, so f must not wait on a command of a client. The keys written
+    /// during the iteration may not be visited, but no key is visited twice.
+    ///
+    /// # Panics
+    ///
+    /// When db is not below the `databases` of the configuration
+    pub fn for_each_key(&self, db: usize, mut f: impl FnMut(&[u8], ValueRef<'_>)) {
+        self.keyspace.for_each_key(db, &mut f);
+    }
 }
 
-fn start_server<T: Storage + Send + 'static>(
-    addr: &str,
+fn start_server<T: Storage + Default + Send + 'static>(
+    context: &Arc<ServerContext<T>>,
     state_send: &Sender<ServerState>,
     state_recv: &Receiver<ServerState>,
-    storage: &Arc<Mutex<T>>,
 ) {
-    let listener = match TcpListener::bind(addr) {
-        Ok(listener) => {
-            // notify that the server has been started
-            let _ = state_send.send(ServerState::Started);
-            let _ = listener.set_nonblocking(true);
-            listener
-        }
-        Err(_) => {
-            thread::sleep(Duration::from_millis(10));
-            return;
-        }
+    let config = context.config().clone();
+    let tcp_listener = match config.port {
+        0 => None,
+        port => match TcpListener::bind(format!("0.0.0.0:{}", port)) {
+            Ok(listener) => {
+                let _ = listener.set_nonblocking(true);
+                Some(listener)
+            }
+            Err(err) => {
+                log::error!("listening on port {} failed: {}", port, err);
+                thread::sleep(Duration::from_millis(10));
+                return;
+            }
+        },
     };
 
-    let thread_pool = match rayon::ThreadPoolBuilder::new()
-        .thread_name(|_| "request handler".to_string())
-        .build()
-    {
-        Ok(pool) => pool,
-        Err(err) => {
-            panic!("{:?}", err);
-        }
+    #[cfg(unix)]
+    let unix_listener = match &config.unixsocket {
+        Some(path) => match bind_unix_listener(path, config.unixsocketperm) {
+            Ok(listener) => Some(listener),
+            Err(err) => {
+                log::error!("listening on {} failed: {}", path.display(), err);
+                thread::sleep(Duration::from_millis(10));
+                return;
+            }
+        },
+        None => None,
     };
 
+    // the background threads run as long as the server does
+    let generation = context.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    expiration::spawn_active_expire(context.clone(), generation);
+    save::spawn_save_points(context.clone(), generation);
+
+    // notify that the server has been started
+    log::info!("server started on port {}", config.port);
+    let _ = state_send.send(ServerState::Started);
+
     // listen incoming requests
-    for stream in listener.incoming() {
-        match stream {
-            Ok(tcp_stream) => {
-                handle_tcp_stream(tcp_stream, &thread_pool, state_send, state_recv, storage);
-            }
-            Err(err) if err.kind() == ErrorKind::WouldBlock => {
-                thread::sleep(Duration::from_millis(10));
+    loop {
+        let mut accepted = false;
+
+        if let Some(listener) = &tcp_listener {
+            match listener.accept() {
+                Ok((tcp_stream, _)) => {
+                    accepted = true;
+                    let _ = tcp_stream.set_nonblocking(false);
+                    let _ = socket::configure_tcp_stream(&tcp_stream, &config);
+                    let resp3 = config.protocol == 3;
+                    handle_stream(tcp_stream, resp3, state_send, state_recv, context);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
             }
-            Err(_) => {
-                break;
+        }
+
+        #[cfg(unix)]
+        if let Some(listener) = &unix_listener {
+            match listener.accept() {
+                Ok((unix_stream, _)) => {
+                    accepted = true;
+                    let _ = unix_stream.set_nonblocking(false);
+                    let resp3 = config.unixsocket_protocol == 3;
+                    handle_stream(unix_stream, resp3, state_send, state_recv, context);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
             }
         }
 
+        if !accepted {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        if context.shutdown.swap(false, Ordering::SeqCst) {
+            // after a `SHUTDOWN`, notified as a stop would be
+            let _ = state_send.send(ServerState::Stopped);
+            break;
+        }
         if stop_sig_received(state_recv, state_send) {
             // let's gracefully shutdown the server
             break;
         }
     }
+
+    context.generation.fetch_add(1, Ordering::SeqCst);
+    #[cfg(unix)]
+    if let Some(path) = &config.unixsocket {
+        let _ = std::fs::remove_file(path);
+    }
+    log::info!("server stopped on port {}", config.port);
+}
+
+#[cfg(unix)]
+fn bind_unix_listener(
+    path: &std::path::Path,
+    permissions: Option<u32>,
+) -> std::io::Result<UnixListener> {
+    use std::os::unix::fs::PermissionsExt;
+
+    // remove a stale socket file left by a previous run
+    let _ = std::fs::remove_file(path);
+
+    let listener = UnixListener::bind(path)?;
+    listener.set_nonblocking(true)?;
+
+    if let Some(mode) = permissions {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    }
+
+    Ok(listener)
+}
+
+/// Stream of a connection, over TCP or a Unix domain socket
+trait ConnectionStream: Read + Write + Send + 'static {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()>;
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()>;
+    /// Address of the client, as reported by `MONITOR`
+    fn client_addr(&self) -> String;
+    /// Shuts the stream down from another thread, for `CLIENT KILL`
+    fn closer(&self) -> io::Result<Closer>;
+}
+
+impl ConnectionStream for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        TcpStream::set_write_timeout(self, timeout)
+    }
+
+    fn client_addr(&self) -> String {
+        self.peer_addr()
+            .map_or(String::new(), |addr| addr.to_string())
+    }
+
+    fn closer(&self) -> io::Result<Closer> {
+        let stream = self.try_clone()?;
+        Ok(Box::new(move || {
+            let _ = stream.shutdown(Shutdown::Both);
+        }))
+    }
+}
+
+#[cfg(unix)]
+impl ConnectionStream for UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        UnixStream::set_write_timeout(self, timeout)
+    }
+
+    // the client end of a socket has no path, the path of the server is reported instead
+    fn client_addr(&self) -> String {
+        let path = self.local_addr().ok();
+        let path = path.as_ref().and_then(|addr| addr.as_pathname());
+        format!(
+            "unix:{}",
+            path.map_or(String::new(), |path| path.display().to_string())
+        )
+    }
+
+    fn closer(&self) -> io::Result<Closer> {
+        let stream = self.try_clone()?;
+        Ok(Box::new(move || {
+            let _ = stream.shutdown(Shutdown::Both);
+        }))
+    }
 }
 
-fn handle_tcp_stream<T: Storage + Send + 'static>(
-    tcp_stream: TcpStream,
-    thread_pool: &ThreadPool,
+/// Serve the connection on its own thread, so that a connection waiting for requests never
+/// holds back the others. The client speaks RESP3 from the start with resp3, the default
+/// protocol of its listener.
+fn handle_stream<T: Storage + Default + Send + 'static, S: ConnectionStream>(
+    mut stream: S,
+    resp3: bool,
     state_send: &Sender<ServerState>,
     state_recv: &Receiver<ServerState>,
-    storage: &Arc<Mutex<T>>,
+    context: &Arc<ServerContext<T>>,
 ) {
-    let storage = storage.clone();
+    let context = context.clone();
     let state_recv = state_recv.clone();
     let state_send = state_send.clone();
 
-    let _ = thread_pool.spawn(move || {
-        let mut last_update = SystemTime::now();
-
-        loop {
-            let (close_connection, received_data_length) = handle_request(&storage, &tcp_stream);
+    // when the thread can't be created the stream is dropped, which closes the connection
+    let _ = thread::Builder::new()
+        .name("request handler".to_string())
+        .spawn(move || {
+            let mut client = Client {
+                id: context.new_client_id(),
+                addr: stream.client_addr(),
+                resp3,
+                ..Default::default()
+            };
+            log::debug!("client {} connected from {}", client.id, client.addr);
+            match stream.closer() {
+                Ok(closer) => context.clients.add(client.id, client.addr.clone(), closer),
+                // a connection which can't be killed is not served
+                Err(_) => return,
+            }
+            serve_connection(&context, &mut client, &mut stream, &state_send, &state_recv);
+            context.clients.remove(client.id);
+            if client.transaction.is_some() {
+                context.transactions.fetch_sub(1, Ordering::SeqCst);
+            }
+            log::debug!("client {} disconnected", client.id);
+            if let Some(subscriber) = &client.subscriber {
+                context.pubsub.remove(subscriber);
+                if client.tracking {
+                    context.tracking.forget(subscriber);
+                }
+                if client.monitor {
+                    context.monitors.remove(subscriber);
+                }
+            }
+        });
+}
 
-            if received_data_length > 0 {
-                // reset the last time we received data
-                last_update = SystemTime::now();
-            } else {
-                // delay the loop
-                thread::sleep(Duration::from_millis(10));
+// write the messages queued to the connection, a message being written as the client reads
+// it until the output buffer of the client exceeds its limit
+fn write_messages<S: ConnectionStream>(stream: &mut S, subscriber: &Subscriber) -> io::Result<()> {
+    let mut messages = std::iter::from_fn(|| subscriber.next_message()).peekable();
+    if messages.peek().is_none() {
+        return Ok(());
+    }
+    stream.set_write_timeout(Some(MESSAGES_WRITE_TIMEOUT))?;
+    for message in messages {
+        let mut written = 0;
+        while written < message.len() {
+            match stream.write(&message[written..]) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(len) => written += len,
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted
+                    ) && !subscriber.is_overflowed() => {}
+                Err(err) => return Err(err),
             }
+        }
+    }
+    stream.set_write_timeout(None)
+}
+
+fn serve_connection<T: Storage, S: ConnectionStream>(
+    context: &ServerContext<T>,
+    client: &mut Client,
+    stream: &mut S,
+    state_send: &Sender<ServerState>,
+    state_recv: &Receiver<ServerState>,
+) {
+    let mut last_update = SystemTime::now();
+    let mut buffer = vec![];
+    // a subscribed or tracking client is not only waited for, messages are written to it
+    // between reads
+    let mut receives_messages = false;
+
+    loop {
+        let (close_connection, received_data_length) =
+            handle_request(context, client, stream, &mut buffer);
+
+        if received_data_length > 0 {
+            // reset the last time we received data
+            last_update = SystemTime::now();
+        } else if !receives_messages {
+            // delay the loop
+            thread::sleep(Duration::from_millis(10));
+        }
 
-            if stop_sig_received(&state_recv, &state_send) || close_connection {
-                // let's close the connection
+        if let Some(subscriber) = &client.subscriber {
+            if subscriber.is_overflowed() {
+                log::warn!("client {}: closed, output buffer over the limit", client.id);
+                return;
+            }
+            if write_messages(stream, subscriber).is_err() {
                 return;
             }
+        }
 
-            if let Ok(duration) = last_update.duration_since(SystemTime::now()) {
-                if duration.as_secs() >= 300 {
-                    // close the connection after 300 secs of inactivity
-                    return;
-                }
+        // a client waiting for messages does not take the stop signal, which the
+        // listener waits for
+        let stop = received_data_length > 0 && stop_sig_received(state_recv, state_send);
+        if stop || close_connection {
+            // let's close the connection
+            return;
+        }
+
+        if let Some(commands) = client.replica.take() {
+            // after a `SYNC` the connection only streams commands to the replica
+            replication::serve_replica(stream, commands, || {
+                stop_sig_received(state_recv, state_send)
+            });
+            return;
+        }
+
+        if client.receives_messages() != receives_messages {
+            receives_messages = client.receives_messages();
+            let timeout = match receives_messages {
+                true => Some(MESSAGES_READ_TIMEOUT),
+                false => None,
+            };
+            if stream.set_read_timeout(timeout).is_err() {
+                return;
             }
+        }
 
-            if close_connection {
+        if let Ok(duration) = last_update.duration_since(SystemTime::now()) {
+            if duration.as_secs() >= 300 {
+                // close the connection after 300 secs of inactivity
                 return;
             }
         }
-    });
+    }
 }
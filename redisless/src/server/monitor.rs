@@ -0,0 +1,99 @@
+//! `MONITOR`.
+//!
+//! Every command run by the server is fed to the monitoring connections, as a status line
+//! in the format of Redis: `+<timestamp> [<db> <addr>] "<command>" "<arg>" ...`. The lines
+//! are sent to the message queue of each monitor, like the pub/sub messages.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::Utc;
+
+use crate::protocol::Resp;
+
+use super::pubsub::{MessageSender, Subscriber};
+use super::util::lock_then_release;
+use super::Client;
+
+// commands never fed to the monitors, they carry passwords
+const NOT_MONITORED: &[&[u8]] = &[b"AUTH", b"HELLO"];
+
+/// Message queues of the monitoring connections, by subscriber id
+#[derive(Default)]
+pub struct Monitors {
+    senders: Mutex<HashMap<u64, MessageSender>>,
+}
+
+impl Monitors {
+    pub fn add(&self, subscriber: &Subscriber) {
+        lock_then_release(&self.senders).insert(subscriber.id(), subscriber.sender());
+    }
+
+    pub fn remove(&self, subscriber: &Subscriber) {
+        lock_then_release(&self.senders).remove(&subscriber.id());
+    }
+
+    pub fn is_active(&self) -> bool {
+        !lock_then_release(&self.senders).is_empty()
+    }
+
+    /// Send the command v of client to every monitor, unless the client is one of them
+    pub fn feed(&self, client: &Client, v: &[Resp]) {
+        if client.monitor {
+            return;
+        }
+        let args = v
+            .iter()
+            .map(|arg| match arg {
+                Resp::BulkString(arg) | Resp::String(arg) => *arg,
+                _ => &b""[..],
+            })
+            .collect::<Vec<_>>();
+        if let Some(name) = args.first() {
+            if NOT_MONITORED
+                .iter()
+                .any(|skip| skip.eq_ignore_ascii_case(name))
+            {
+                return;
+            }
+        }
+
+        let now = Utc::now();
+        let mut line = format!(
+            "+{}.{:06} [{} {}]",
+            now.timestamp(),
+            now.timestamp_subsec_micros(),
+            client.db,
+            client.addr
+        )
+        .into_bytes();
+        for arg in args {
+            line.push(b' ');
+            quote(arg, &mut line);
+        }
+        line.extend_from_slice(b"\r\n");
+
+        let mut senders = lock_then_release(&self.senders);
+        // the connection of a monitor which can't be sent to is closed
+        senders.retain(|_, sender| sender.send(line.clone()));
+    }
+}
+
+// append arg between double quotes, with its special and non printable bytes escaped as
+// Redis does
+fn quote(arg: &[u8], line: &mut Vec<u8>) {
+    line.push(b'"');
+    for &byte in arg {
+        match byte {
+            b'\\' | b'"' => line.extend_from_slice(&[b'\\', byte]),
+            b'\n' => line.extend_from_slice(b"\\n"),
+            b'\r' => line.extend_from_slice(b"\\r"),
+            b'\t' => line.extend_from_slice(b"\\t"),
+            0x07 => line.extend_from_slice(b"\\a"),
+            0x08 => line.extend_from_slice(b"\\b"),
+            byte if byte.is_ascii_graphic() || byte == b' ' => line.push(byte),
+            byte => line.extend_from_slice(format!("\\x{:02x}", byte).as_bytes()),
+        }
+    }
+    line.push(b'"');
+}
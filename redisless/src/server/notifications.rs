@@ -0,0 +1,115 @@
+//! Keyspace notifications.
+//!
+//! When enabled by `notify-keyspace-events`, every change to a key is published on two
+//! channels: `__keyspace@<db>__:<key>` with the event as message, and
+//! `__keyevent@<db>__:<event>` with the key as message. Handlers notify the mutations they
+//! actually perform, so a command changing nothing publishes nothing. Every notification
+//! also wakes up the clients blocked on the key, such as by `WAITKEY`.
+
+use crate::storage::Storage;
+
+use super::ServerContext;
+
+/// Class of an event, each enabled by its own flag
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventClass {
+    // g: events of any type, such as `del`, `expire` or `persist`
+    Generic,
+    // $
+    String,
+    // l
+    List,
+    // s
+    Set,
+    // h
+    Hash,
+    // z
+    SortedSet,
+    // x: a key expired
+    Expired,
+    // e: a key was evicted
+    Evicted,
+}
+
+impl EventClass {
+    fn flag(self) -> u16 {
+        match self {
+            EventClass::Generic => 1 << 2,
+            EventClass::String => 1 << 3,
+            EventClass::List => 1 << 4,
+            EventClass::Set => 1 << 5,
+            EventClass::Hash => 1 << 6,
+            EventClass::SortedSet => 1 << 7,
+            EventClass::Expired => 1 << 8,
+            EventClass::Evicted => 1 << 9,
+        }
+    }
+}
+
+// K: publish on the `__keyspace@<db>__` channels
+const KEYSPACE: u16 = 1;
+// E: publish on the `__keyevent@<db>__` channels
+const KEYEVENT: u16 = 1 << 1;
+// A: alias of g$lshzxe
+const ALL_CLASSES: u16 = 0b11_1111_1100;
+
+/// Events to publish, parsed from the flags of `notify-keyspace-events`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct KeyspaceEvents(u16);
+
+impl KeyspaceEvents {
+    /// Parse flags such as `"KEA"` or `"Kgx"`, unknown flags are ignored. Nothing is
+    /// published unless `K` or `E` is set along with a class of events.
+    pub fn parse(flags: &str) -> Self {
+        use EventClass::*;
+        let flags = flags.chars().fold(0, |enabled, flag| {
+            enabled
+                | match flag {
+                    'K' => KEYSPACE,
+                    'E' => KEYEVENT,
+                    'A' => ALL_CLASSES,
+                    'g' => Generic.flag(),
+                    '$' => String.flag(),
+                    'l' => List.flag(),
+                    's' => Set.flag(),
+                    'h' => Hash.flag(),
+                    'z' => SortedSet.flag(),
+                    'x' => Expired.flag(),
+                    'e' => Evicted.flag(),
+                    _ => 0,
+                }
+        });
+        KeyspaceEvents(flags)
+    }
+
+    pub fn is_enabled(&self, class: EventClass) -> bool {
+        self.0 & (KEYSPACE | KEYEVENT) != 0 && self.0 & class.flag() != 0
+    }
+}
+
+/// Publish the event of the given class, which happened to key in the db
+pub fn notify<T: Storage>(
+    context: &ServerContext<T>,
+    db: usize,
+    class: EventClass,
+    event: &str,
+    key: &[u8],
+) {
+    // a client waiting for the key reads it again, whether or not the events are published
+    context.blocked[db].notify(key);
+
+    let events = context.keyspace_events;
+    if !events.is_enabled(class) {
+        return;
+    }
+
+    if events.0 & KEYSPACE != 0 {
+        let mut channel = format!("__keyspace@{}__:", db).into_bytes();
+        channel.extend_from_slice(key);
+        context.pubsub.publish(&channel, event.as_bytes());
+    }
+    if events.0 & KEYEVENT != 0 {
+        let channel = format!("__keyevent@{}__:{}", db, event);
+        context.pubsub.publish(channel.as_bytes(), key);
+    }
+}
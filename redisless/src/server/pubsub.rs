@@ -0,0 +1,414 @@
+//! Publish / subscribe.
+//!
+//! Every subscribed connection owns a queue of encoded messages. Publishers only push whole
+//! frames to the queues, and the thread serving the connection is the only one writing them
+//! to its stream, between two reads. So the frames of concurrent publishers never
+//! interleave on a connection, which receives the messages of each publisher in the order
+//! they were published. The messages of a RESP3 connection are push frames,
+//! so it can tell them from the replies, and the ones of a RESP2 connection arrays.
+//!
+//! The bytes queued to a connection count against its output buffer limit
+//! (`client-output-buffer-limit`), so a client which does not read its messages can't grow
+//! the memory of the server without bound. Past the hard limit, or past the soft limit for
+//! long enough, no more messages are queued and the connection is closed.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use chrono::Utc;
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::protocol::response::{RedisResponse, RedisResponseType};
+use crate::storage::models::RedisString;
+
+use super::config::OutputBufferLimit;
+use super::util::glob_match;
+
+// subscribers of a channel or a pattern, by subscriber id, with whether they subscribed with
+// RESP3
+type Subscribers = HashMap<u64, (MessageSender, bool)>;
+
+/// Channels and patterns of a server, with their subscribers
+#[derive(Default)]
+pub struct PubSub {
+    channels: RwLock<HashMap<RedisString, Subscribers>>,
+    patterns: RwLock<HashMap<RedisString, Subscribers>>,
+    next_id: AtomicU64,
+}
+
+/// Subscriptions of a connection, and the queue of the messages sent to it: the messages
+/// published to them, and the invalidations of the keys it tracks
+#[derive(Debug)]
+pub struct Subscriber {
+    id: u64,
+    sender: MessageSender,
+    messages: Receiver<Vec<u8>>,
+    channels: HashSet<RedisString>,
+    patterns: HashSet<RedisString>,
+}
+
+/// Sender into the message queue of a connection, which counts the bytes queued
+#[derive(Debug, Clone)]
+pub struct MessageSender {
+    sender: Sender<Vec<u8>>,
+    buffer: Arc<OutputBuffer>,
+}
+
+#[derive(Debug)]
+struct OutputBuffer {
+    limit: OutputBufferLimit,
+    // bytes queued and not yet taken to be written
+    pending: AtomicU64,
+    // unix timestamp in millis since which the soft limit is exceeded, 0 when it is not
+    soft_since: AtomicI64,
+    // set once a limit is exceeded, the connection is then closed
+    overflowed: AtomicBool,
+}
+
+impl MessageSender {
+    /// Queue message, unless the connection is closed or its output buffer exceeded its
+    /// limit. Returns whether it was queued.
+    pub fn send(&self, message: Vec<u8>) -> bool {
+        let buffer = &self.buffer;
+        if buffer.overflowed.load(Ordering::SeqCst) {
+            return false;
+        }
+        let len = message.len() as u64;
+        if self.sender.send(message).is_err() {
+            return false;
+        }
+
+        let pending = buffer.pending.fetch_add(len, Ordering::SeqCst) + len;
+        let limit = buffer.limit;
+        let soft_exceeded = limit.soft > 0 && pending > limit.soft;
+        let overflowed = match soft_exceeded {
+            _ if limit.hard > 0 && pending > limit.hard => true,
+            true => {
+                let now = Utc::now().timestamp_millis();
+                match buffer
+                    .soft_since
+                    .compare_exchange(0, now, Ordering::SeqCst, Ordering::SeqCst)
+                {
+                    Ok(_) => false,
+                    Err(since) => now - since >= limit.soft_secs as i64 * 1000,
+                }
+            }
+            false => {
+                buffer.soft_since.store(0, Ordering::SeqCst);
+                false
+            }
+        };
+        if overflowed {
+            buffer.overflowed.store(true, Ordering::SeqCst);
+        }
+        true
+    }
+}
+
+impl Subscriber {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Sender into the message queue of the connection
+    pub fn sender(&self) -> MessageSender {
+        self.sender.clone()
+    }
+
+    /// Take the next message queued, to write it to the connection
+    pub fn next_message(&self) -> Option<Vec<u8>> {
+        let message = self.messages.try_recv().ok()?;
+        let buffer = &self.sender.buffer;
+        let pending = buffer
+            .pending
+            .fetch_sub(message.len() as u64, Ordering::SeqCst);
+        if pending - message.len() as u64 <= buffer.limit.soft {
+            buffer.soft_since.store(0, Ordering::SeqCst);
+        }
+        Some(message)
+    }
+
+    /// Whether the output buffer of the connection exceeded its limit, so it must be closed
+    pub fn is_overflowed(&self) -> bool {
+        self.sender.buffer.overflowed.load(Ordering::SeqCst)
+    }
+
+    /// Number of channels and patterns subscribed to, reported by every confirmation
+    pub fn count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+}
+
+impl PubSub {
+    /// Subscriber whose output buffer is bounded by limit
+    pub fn new_subscriber(&self, limit: OutputBufferLimit) -> Subscriber {
+        let (sender, messages) = crossbeam_channel::unbounded();
+        let buffer = OutputBuffer {
+            limit,
+            pending: AtomicU64::new(0),
+            soft_since: AtomicI64::new(0),
+            overflowed: AtomicBool::new(false),
+        };
+        Subscriber {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            sender: MessageSender {
+                sender,
+                buffer: Arc::new(buffer),
+            },
+            messages,
+            channels: HashSet::new(),
+            patterns: HashSet::new(),
+        }
+    }
+
+    /// `SUBSCRIBE`, replying with a confirmation per channel, even one already subscribed to,
+    /// each with the running count of the channels and patterns of the connection. The
+    /// messages are sent in the protocol of the client, given by resp3, when it subscribed.
+    pub fn subscribe(
+        &self,
+        subscriber: &mut Subscriber,
+        channels: Vec<RedisString>,
+        resp3: bool,
+    ) -> Vec<u8> {
+        let mut registry = self.channels.write().unwrap();
+        let mut reply = vec![];
+        for channel in channels {
+            if subscriber.channels.insert(channel.clone()) {
+                registry
+                    .entry(channel.clone())
+                    .or_default()
+                    .insert(subscriber.id, (subscriber.sender.clone(), resp3));
+            }
+            reply.append(&mut confirmation(
+                b"subscribe",
+                Some(channel),
+                subscriber,
+                resp3,
+            ));
+        }
+        reply
+    }
+
+    /// `PSUBSCRIBE`, replying with a confirmation per pattern
+    pub fn psubscribe(
+        &self,
+        subscriber: &mut Subscriber,
+        patterns: Vec<RedisString>,
+        resp3: bool,
+    ) -> Vec<u8> {
+        let mut registry = self.patterns.write().unwrap();
+        let mut reply = vec![];
+        for pattern in patterns {
+            if subscriber.patterns.insert(pattern.clone()) {
+                registry
+                    .entry(pattern.clone())
+                    .or_default()
+                    .insert(subscriber.id, (subscriber.sender.clone(), resp3));
+            }
+            reply.append(&mut confirmation(
+                b"psubscribe",
+                Some(pattern),
+                subscriber,
+                resp3,
+            ));
+        }
+        reply
+    }
+
+    /// `UNSUBSCRIBE`, from every channel when none is given
+    pub fn unsubscribe(
+        &self,
+        subscriber: &mut Subscriber,
+        channels: Vec<RedisString>,
+        resp3: bool,
+    ) -> Vec<u8> {
+        let mut registry = self.channels.write().unwrap();
+        let channels = match channels.is_empty() {
+            true => subscriber.channels.iter().cloned().collect(),
+            false => channels,
+        };
+        unsubscribe(&mut registry, subscriber, channels, false, resp3)
+    }
+
+    /// `PUNSUBSCRIBE`, from every pattern when none is given
+    pub fn punsubscribe(
+        &self,
+        subscriber: &mut Subscriber,
+        patterns: Vec<RedisString>,
+        resp3: bool,
+    ) -> Vec<u8> {
+        let mut registry = self.patterns.write().unwrap();
+        let patterns = match patterns.is_empty() {
+            true => subscriber.patterns.iter().cloned().collect(),
+            false => patterns,
+        };
+        unsubscribe(&mut registry, subscriber, patterns, true, resp3)
+    }
+
+    /// Remove every subscription of a connection which is closed
+    pub fn remove(&self, subscriber: &Subscriber) {
+        for (registry, names) in [
+            (&self.channels, &subscriber.channels),
+            (&self.patterns, &subscriber.patterns),
+        ] {
+            let mut registry = registry.write().unwrap();
+            for name in names {
+                remove_subscriber(&mut registry, name, subscriber.id);
+            }
+        }
+    }
+
+    /// Channels with at least a subscriber, matching pattern when set
+    pub fn channels(&self, pattern: Option<&[u8]>) -> Vec<RedisString> {
+        self.channels
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|channel| pattern.is_none_or(|pattern| glob_match(pattern, channel)))
+            .cloned()
+            .collect()
+    }
+
+    /// Number of subscribers of channel, not counting the pattern ones
+    pub fn numsub(&self, channel: &[u8]) -> usize {
+        self.channels
+            .read()
+            .unwrap()
+            .get(channel)
+            .map_or(0, |subscribers| subscribers.len())
+    }
+
+    /// Number of patterns with at least a subscriber
+    pub fn numpat(&self) -> usize {
+        self.patterns.read().unwrap().len()
+    }
+
+    /// Send message to the subscribers of channel and of the patterns matching it, returning
+    /// the number of subscribers which received it
+    pub fn publish(&self, channel: &[u8], message: &[u8]) -> usize {
+        use RedisResponseType::BulkString;
+        let mut received = 0;
+        let mut gone = vec![];
+
+        {
+            let channels = self.channels.read().unwrap();
+            if let Some(subscribers) = channels.get(channel) {
+                let frame = |resp3| {
+                    RedisResponse::push(
+                        vec![
+                            BulkString(b"message".to_vec()),
+                            BulkString(channel.to_vec()),
+                            BulkString(message.to_vec()),
+                        ],
+                        resp3,
+                    )
+                    .reply()
+                };
+                let frames = [frame(false), frame(true)];
+                for (id, (sender, resp3)) in subscribers {
+                    match sender.send(frames[*resp3 as usize].clone()) {
+                        true => received += 1,
+                        false => gone.push((false, channel.to_vec(), *id)),
+                    }
+                }
+            }
+
+            let patterns = self.patterns.read().unwrap();
+            for (pattern, subscribers) in patterns.iter() {
+                if !glob_match(pattern, channel) {
+                    continue;
+                }
+                let frame = |resp3| {
+                    RedisResponse::push(
+                        vec![
+                            BulkString(b"pmessage".to_vec()),
+                            BulkString(pattern.clone()),
+                            BulkString(channel.to_vec()),
+                            BulkString(message.to_vec()),
+                        ],
+                        resp3,
+                    )
+                    .reply()
+                };
+                let frames = [frame(false), frame(true)];
+                for (id, (sender, resp3)) in subscribers {
+                    match sender.send(frames[*resp3 as usize].clone()) {
+                        true => received += 1,
+                        false => gone.push((true, pattern.clone(), *id)),
+                    }
+                }
+            }
+        }
+
+        // the connection of these subscribers is closed, or about to be
+        for (pattern, name, id) in gone {
+            let registry = if pattern {
+                &self.patterns
+            } else {
+                &self.channels
+            };
+            remove_subscriber(&mut registry.write().unwrap(), &name, id);
+        }
+        received
+    }
+}
+
+fn unsubscribe(
+    registry: &mut HashMap<RedisString, Subscribers>,
+    subscriber: &mut Subscriber,
+    names: Vec<RedisString>,
+    pattern: bool,
+    resp3: bool,
+) -> Vec<u8> {
+    let kind: &[u8] = if pattern {
+        b"punsubscribe"
+    } else {
+        b"unsubscribe"
+    };
+    if names.is_empty() {
+        // nothing to unsubscribe from is still confirmed
+        return confirmation(kind, None, subscriber, resp3);
+    }
+
+    let mut reply = vec![];
+    for name in names {
+        let subscribed = match pattern {
+            true => subscriber.patterns.remove(&name),
+            false => subscriber.channels.remove(&name),
+        };
+        if subscribed {
+            remove_subscriber(registry, &name, subscriber.id);
+        }
+        reply.append(&mut confirmation(kind, Some(name), subscriber, resp3));
+    }
+    reply
+}
+
+// a channel or a pattern is dropped with its last subscriber
+fn remove_subscriber(registry: &mut HashMap<RedisString, Subscribers>, name: &[u8], id: u64) {
+    if let Some(subscribers) = registry.get_mut(name) {
+        subscribers.remove(&id);
+        if subscribers.is_empty() {
+            registry.remove(name);
+        }
+    }
+}
+
+fn confirmation(
+    kind: &[u8],
+    name: Option<RedisString>,
+    subscriber: &Subscriber,
+    resp3: bool,
+) -> Vec<u8> {
+    use RedisResponseType::*;
+    RedisResponse::push(
+        vec![
+            BulkString(kind.to_vec()),
+            name.map_or(Nil, BulkString),
+            Integer(subscriber.count() as i64),
+        ],
+        resp3,
+    )
+    .reply()
+}
@@ -0,0 +1,347 @@
+//! Replication of a primary by replicas.
+//!
+//! A replica connects to its primary and sends `PSYNC ? -1`. The primary replies
+//! `+FULLRESYNC <replid> 0`, then the snapshot of its databases as a bulk string, and then
+//! streams every write command it runs, as the client sent it. Partial resynchronization
+//! is not supported: a replica which loses the link to its primary syncs in full again.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, Sender};
+use rand::Rng;
+
+use crate::command::command_error::RedisCommandError;
+use crate::command::Command;
+use crate::protocol::parser::RedisProtocolParser;
+use crate::protocol::response::RedisResponse;
+use crate::protocol::Resp;
+use crate::storage::snapshot::{self, SnapshotEntry};
+use crate::storage::Storage;
+
+use super::aof::Aof;
+use super::util::{command_keys, lock_then_release, run_command_and_get_response, Deadline};
+use super::{Client, ServerContext};
+
+// how often a blocked replication link checks that it is still wanted
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// delay before a replica tries again to reach an unreachable primary
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Replication state of a server, both as a primary and as a replica
+pub struct Replication {
+    // id of the data set of this server, sent to replicas on a full sync
+    pub replid: String,
+    // primary replicated by this server, set by `REPLICAOF`
+    primary: Mutex<Option<(String, u16)>>,
+    // incremented by every `REPLICAOF`, so the link to a previous primary stops
+    generation: AtomicU64,
+    // whether the link to the primary is synced and streaming its writes
+    link_up: AtomicBool,
+    // write commands to stream to each replica. Writes take the read lock while they run
+    // and a full sync takes the write lock, so no write falls between the snapshot and the
+    // start of the stream.
+    replicas: RwLock<Vec<Sender<Vec<u8>>>>,
+    // db selected in the stream sent to the replicas, `None` until a `SELECT` is sent.
+    // It is also held while a write runs, to stream writes in the order they ran.
+    stream_db: Mutex<Option<usize>>,
+    // the writes are also appended to the append only file, once it is opened
+    pub aof: Arc<Aof>,
+}
+
+impl Default for Replication {
+    fn default() -> Self {
+        Replication {
+            // 40 hex characters, as the replication ids of Redis
+            replid: (0..20)
+                .map(|_| format!("{:02x}", rand::thread_rng().gen::<u8>()))
+                .collect(),
+            primary: Mutex::new(None),
+            generation: AtomicU64::new(0),
+            link_up: AtomicBool::new(false),
+            replicas: RwLock::new(vec![]),
+            stream_db: Mutex::new(None),
+            aof: Arc::new(Aof::default()),
+        }
+    }
+}
+
+impl Replication {
+    pub fn is_replica(&self) -> bool {
+        lock_then_release(&self.primary).is_some()
+    }
+
+    /// Primary replicated by this server, and whether the link to it is up
+    pub fn primary(&self) -> Option<(String, u16, bool)> {
+        lock_then_release(&self.primary)
+            .clone()
+            .map(|(host, port)| (host, port, self.link_up.load(Ordering::SeqCst)))
+    }
+
+    /// Number of replicas streamed the write commands
+    pub fn replica_count(&self) -> usize {
+        // an empty message is only sent to leave out the replicas which are gone
+        self.replicas
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|replica| replica.send(vec![]).is_ok())
+            .count()
+    }
+
+    /// Replicate the given primary, or stop replicating when `None`. The data set is kept
+    /// until a full sync with the new primary replaces it.
+    pub fn set_primary(&self, primary: Option<(String, u16)>) {
+        *lock_then_release(&self.primary) = primary;
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.link_up.store(false, Ordering::SeqCst);
+    }
+
+    /// Run the write command request with run, and stream it to the replicas and append it
+    /// to the append only file if it succeeds
+    pub fn run_write(
+        &self,
+        db: usize,
+        request: &[u8],
+        run: impl FnOnce() -> RedisResponse,
+    ) -> RedisResponse {
+        let replicas = self.replicas.read().unwrap();
+        let mut aof = self.aof.lock();
+        if replicas.is_empty() && aof.is_none() {
+            return run();
+        }
+
+        let mut stream_db = lock_then_release(&self.stream_db);
+        let response = run();
+        if response.is_error() {
+            return response;
+        }
+        if let Some(aof) = &mut aof {
+            aof.append(db, request);
+        }
+        drop(aof);
+        if replicas.is_empty() {
+            return response;
+        }
+
+        let mut bytes = vec![];
+        if *stream_db != Some(db) {
+            let index = db.to_string();
+            let select = format!("*2\r\n$6\r\nSELECT\r\n${}\r\n{}\r\n", index.len(), index);
+            bytes.extend_from_slice(select.as_bytes());
+            *stream_db = Some(db);
+        }
+        bytes.extend_from_slice(request);
+
+        let mut disconnected = false;
+        for replica in replicas.iter() {
+            disconnected |= replica.send(bytes.clone()).is_err();
+        }
+        drop(stream_db);
+        drop(replicas);
+
+        if disconnected {
+            // an empty message is only sent to find out the replicas which are gone
+            self.replicas
+                .write()
+                .unwrap()
+                .retain(|replica| replica.send(vec![]).is_ok());
+        }
+        response
+    }
+
+    /// Register a new replica, returning the snapshot it starts from and the write commands
+    /// which follow it
+    pub fn add_replica(
+        &self,
+        take_snapshot: impl FnOnce() -> Vec<Vec<SnapshotEntry>>,
+    ) -> (Vec<Vec<SnapshotEntry>>, Receiver<Vec<u8>>) {
+        let mut replicas = self.replicas.write().unwrap();
+        let snapshot = take_snapshot();
+        // the new replica starts on the db 0, the others are told again
+        *lock_then_release(&self.stream_db) = None;
+
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        replicas.push(sender);
+        (snapshot, receiver)
+    }
+}
+
+/// Stream the write commands to a replica until it disconnects or stop returns true
+pub fn serve_replica<S: Write>(
+    stream: &mut S,
+    commands: Receiver<Vec<u8>>,
+    mut stop: impl FnMut() -> bool,
+) {
+    loop {
+        match commands.recv_timeout(POLL_INTERVAL) {
+            Ok(bytes) => {
+                if stream.write_all(&bytes).is_err() {
+                    return;
+                }
+            }
+            Err(err) if err.is_timeout() => {}
+            Err(_) => return,
+        }
+        if stop() {
+            return;
+        }
+    }
+}
+
+/// Start the thread replicating the primary set by `REPLICAOF`, for the whole life of the
+/// server
+pub fn spawn_replica_link<T: Storage + Send + 'static>(context: Arc<ServerContext<T>>) {
+    let _ = thread::Builder::new()
+        .name("replication".to_string())
+        .spawn(move || loop {
+            let generation = context.replication.generation.load(Ordering::SeqCst);
+            let primary = lock_then_release(&context.replication.primary).clone();
+            match primary {
+                Some((host, port)) => {
+                    // the link only returns when it breaks or is not wanted anymore
+                    let _ = replicate(&context, &host, port, generation);
+                    context.replication.link_up.store(false, Ordering::SeqCst);
+                    if is_current(&context, generation) {
+                        thread::sleep(RETRY_INTERVAL);
+                    }
+                }
+                None => thread::sleep(POLL_INTERVAL),
+            }
+        });
+}
+
+fn is_current<T: Storage>(context: &ServerContext<T>, generation: u64) -> bool {
+    context.replication.generation.load(Ordering::SeqCst) == generation
+}
+
+fn replicate<T: Storage>(
+    context: &ServerContext<T>,
+    host: &str,
+    port: u16,
+    generation: u64,
+) -> std::io::Result<()> {
+    let mut stream = TcpStream::connect((host, port))?;
+    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+    stream.write_all(b"*3\r\n$5\r\nPSYNC\r\n$1\r\n?\r\n$2\r\n-1\r\n")?;
+
+    let mut link = Link {
+        stream,
+        buffer: vec![],
+        generation,
+    };
+
+    // +FULLRESYNC <replid> <offset>
+    let line = link.read_line(context)?;
+    if !line.starts_with(b"+FULLRESYNC") {
+        return Err(bad_reply());
+    }
+
+    let snapshot = link.read_snapshot(context)?;
+    context.restore_snapshot(snapshot);
+    context.replication.link_up.store(true, Ordering::SeqCst);
+
+    // the commands of the primary are run like the ones of a client, their replies dropped
+    let mut client = Client::default();
+    let max_bulk_len = context.config().proto_max_bulk_len;
+    loop {
+        loop {
+            // the keys written by the primary are invalidated for the clients tracking them
+            let mut keys = vec![];
+            let (command, consumed) =
+                match RedisProtocolParser::parse_with_max_bulk_len(&link.buffer, max_bulk_len) {
+                    Ok((Resp::Array(v), left)) => {
+                        if context.tracking.is_active() {
+                            keys = command_keys(&v);
+                        }
+                        (Command::parse(v), link.buffer.len() - left.len())
+                    }
+                    Ok((_, left)) => (
+                        Err(RedisCommandError::InvalidCommand),
+                        link.buffer.len() - left.len(),
+                    ),
+                    Err(err) if err.is_incomplete() => break,
+                    Err(_) => return Err(bad_reply()),
+                };
+            link.buffer.drain(..consumed);
+            let db = client.db;
+            let _ =
+                run_command_and_get_response(context, &mut client, command, Deadline::after(None));
+            if !keys.is_empty() {
+                context.tracking.invalidate(db, &keys);
+            }
+        }
+        link.read_more(context)?;
+    }
+}
+
+fn bad_reply() -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, "unexpected reply of the primary")
+}
+
+/// Connection of a replica to its primary
+struct Link {
+    stream: TcpStream,
+    // bytes received and not consumed yet
+    buffer: Vec<u8>,
+    generation: u64,
+}
+
+impl Link {
+    /// Read into the buffer, failing once the link is not wanted anymore
+    fn read_more<T: Storage>(&mut self, context: &ServerContext<T>) -> std::io::Result<()> {
+        let mut chunk = [0; 16 * 1024];
+        loop {
+            if !is_current(context, self.generation) {
+                return Err(std::io::Error::new(
+                    ErrorKind::Interrupted,
+                    "replication stopped",
+                ));
+            }
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(received) => {
+                    self.buffer.extend_from_slice(&chunk[..received]);
+                    return Ok(());
+                }
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Consume a line of the buffer, without its CRLF
+    fn read_line<T: Storage>(&mut self, context: &ServerContext<T>) -> std::io::Result<Vec<u8>> {
+        loop {
+            if let Some(end) = self.buffer.windows(2).position(|bytes| bytes == b"\r\n") {
+                let line = self.buffer[..end].to_vec();
+                self.buffer.drain(..end + 2);
+                return Ok(line);
+            }
+            self.read_more(context)?;
+        }
+    }
+
+    /// Consume a framed snapshot of the buffer, see `snapshot::encode_framed`
+    fn read_snapshot<T: Storage>(
+        &mut self,
+        context: &ServerContext<T>,
+    ) -> std::io::Result<Vec<Vec<SnapshotEntry>>> {
+        let (header_len, len) = loop {
+            if let Some(header) = snapshot::decode_frame_header(&self.buffer)? {
+                break header;
+            }
+            self.read_more(context)?;
+        };
+        while self.buffer.len() < header_len + len {
+            self.read_more(context)?;
+        }
+        let databases = snapshot::decode(&self.buffer[header_len..header_len + len])?;
+        self.buffer.drain(..header_len + len);
+        Ok(databases)
+    }
+}
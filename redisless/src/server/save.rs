@@ -0,0 +1,68 @@
+//! Save points, the snapshots written in the background once enough writes ran.
+//!
+//! As in Redis, the save points are checked 10 times per second. A save point `(secs,
+//! changes)` is reached once at least `changes` write commands ran and the last snapshot is
+//! at least `secs` old. A failed snapshot is only tried again after a delay.
+
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::storage::Storage;
+
+use super::ServerContext;
+
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+// before trying again after a failed snapshot
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Check the save points in a thread of their own until the server leaves generation, when
+/// it stops
+pub fn spawn_save_points<T: Storage + Send + 'static>(
+    context: Arc<ServerContext<T>>,
+    generation: u64,
+) {
+    let _ = thread::Builder::new()
+        .name("save-points".to_string())
+        .spawn(move || {
+            let mut failed_at: Option<Instant> = None;
+            loop {
+                thread::sleep(CHECK_INTERVAL);
+                if !context.is_generation(generation) {
+                    break;
+                }
+                let retry = failed_at.is_none_or(|at| at.elapsed() >= RETRY_DELAY);
+                if !retry || !save_point_reached(&context) {
+                    continue;
+                }
+                // the snapshot is written by this thread, as a `BGSAVE` would
+                if context.bgsave_in_progress.swap(true, Ordering::SeqCst) {
+                    continue;
+                }
+                let saved = context.save();
+                context.bgsave_in_progress.store(false, Ordering::SeqCst);
+                failed_at = match saved {
+                    Ok(()) => None,
+                    Err(err) => {
+                        log::warn!("background saving failed: {}", err);
+                        Some(Instant::now())
+                    }
+                };
+            }
+        });
+}
+
+/// Whether a save point is reached, never without a write since the last snapshot
+fn save_point_reached<T: Storage>(context: &ServerContext<T>) -> bool {
+    let dirty = context.dirty.load(Ordering::SeqCst);
+    let since_save = Utc::now().timestamp() - context.last_save.load(Ordering::SeqCst);
+    dirty > 0
+        && context
+            .config()
+            .save
+            .iter()
+            .any(|&(secs, changes)| dirty >= changes && since_save >= secs as i64)
+}
@@ -0,0 +1,95 @@
+//! Options of the accepted TCP sockets (`tcp-nodelay` and `tcp-keepalive`).
+//!
+//! `TCP_NODELAY` is set through the standard library, which does not expose the keepalive
+//! options: they are set with `setsockopt` on Unix, and left to the system elsewhere.
+
+use std::io;
+use std::net::TcpStream;
+
+use super::ServerConfig;
+
+/// Apply the socket options of the config to an accepted connection
+pub fn configure_tcp_stream(stream: &TcpStream, config: &ServerConfig) -> io::Result<()> {
+    stream.set_nodelay(config.tcp_nodelay)?;
+    if config.tcp_keepalive_secs > 0 {
+        set_keepalive(stream, config.tcp_keepalive_secs)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_keepalive(stream: &TcpStream, secs: u64) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = stream.as_raw_fd();
+    let secs = secs.min(libc::c_int::MAX as u64) as libc::c_int;
+    setsockopt(fd, libc::SOL_SOCKET, libc::SO_KEEPALIVE, 1)?;
+
+    // as in Redis, the peer is probed 3 times within the interval once it is idle for it
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        setsockopt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, secs)?;
+        setsockopt(
+            fd,
+            libc::IPPROTO_TCP,
+            libc::TCP_KEEPINTVL,
+            (secs / 3).max(1),
+        )?;
+        setsockopt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPCNT, 3)?;
+    }
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    setsockopt(fd, libc::IPPROTO_TCP, libc::TCP_KEEPALIVE, secs)?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_keepalive(_stream: &TcpStream, _secs: u64) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn setsockopt(
+    fd: libc::c_int,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: libc::c_int,
+) -> io::Result<()> {
+    // SAFETY: the option is read from a c_int which outlives the call, of the given size
+    let result = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    match result {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Value of an integer option of the socket, to check the applied options
+#[cfg(all(test, unix))]
+pub fn getsockopt(stream: &TcpStream, level: libc::c_int, name: libc::c_int) -> io::Result<i32> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut value: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: value and len outlive the call, len is the size of value
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            level,
+            name,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    match result {
+        0 => Ok(value),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
@@ -0,0 +1,99 @@
+//! Statistics of the commands, reported by `INFO commandstats`.
+//!
+//! A command is counted under its lowercase name, and the subcommand of a container command
+//! under `<command>|<subcommand>` as in Redis, such as `config|get`. The calls refused before
+//! they run, such as with the wrong number of arguments, are only counted as rejected, while
+//! the calls failing as they run are counted as calls too.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::protocol::Resp;
+
+use super::util::lock_then_release;
+
+// commands whose subcommands are counted apart
+const CONTAINERS: &[&str] = &[
+    "acl", "client", "command", "config", "latency", "memory", "object", "pubsub",
+];
+
+#[derive(Default)]
+pub struct CommandStats {
+    stats: Mutex<HashMap<String, CommandStat>>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct CommandStat {
+    calls: u64,
+    usec: u64,
+    rejected_calls: u64,
+    failed_calls: u64,
+}
+
+/// How a call of a command ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    // replied without an error
+    Succeeded,
+    // refused before running, not counted as a call
+    Rejected,
+    // replied an error as it ran
+    Failed,
+}
+
+impl CommandStats {
+    /// Count a call of the command named name, which ran for elapsed
+    pub fn record(&self, name: String, elapsed: Duration, outcome: Outcome) {
+        let mut stats = lock_then_release(&self.stats);
+        let stat = stats.entry(name).or_default();
+        match outcome {
+            Outcome::Rejected => stat.rejected_calls += 1,
+            outcome => {
+                stat.calls += 1;
+                stat.usec += elapsed.as_micros() as u64;
+                if outcome == Outcome::Failed {
+                    stat.failed_calls += 1;
+                }
+            }
+        }
+    }
+
+    /// `Commandstats` section of `INFO`, a line per command called, by name
+    pub fn info(&self) -> String {
+        let stats = lock_then_release(&self.stats);
+        let mut names: Vec<_> = stats.keys().collect();
+        names.sort();
+
+        let mut info = String::from("# Commandstats\r\n");
+        for name in names {
+            let stat = stats[name];
+            let usec_per_call = match stat.calls {
+                0 => 0.0,
+                calls => stat.usec as f64 / calls as f64,
+            };
+            info.push_str(&format!(
+                "cmdstat_{}:calls={},usec={},usec_per_call={:.2},rejected_calls={},failed_calls={}\r\n",
+                name, stat.calls, stat.usec, usec_per_call, stat.rejected_calls, stat.failed_calls
+            ));
+        }
+        info
+    }
+}
+
+/// Name the command v is counted under, such as `get` or `config|get`
+pub fn command_name(v: &[Resp]) -> String {
+    let arg = |index: usize| match v.get(index) {
+        Some(Resp::BulkString(arg)) | Some(Resp::String(arg)) => {
+            Some(String::from_utf8_lossy(arg).to_lowercase())
+        }
+        _ => None,
+    };
+    let name = arg(0).unwrap_or_default();
+    match arg(1) {
+        Some(subcommand) if CONTAINERS.contains(&name.as_str()) => {
+            format!("{}|{}", name, subcommand)
+        }
+        _ => name,
+    }
+}
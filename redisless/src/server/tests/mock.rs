@@ -0,0 +1,232 @@
+//! A store of strings only, to run commands against another `Storage` than the in memory
+//! one. The values of the other types are not kept, and read as missing.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::storage::models::{
+    EncodingLimits, Expiry, RedisMeta, RedisSortedSet, RedisString, RedisType, RedisValue, ValueRef,
+};
+use crate::storage::Storage;
+
+/// Number of strings written to any `StringStorage`, to tell it was the one used
+pub static WRITES: AtomicU64 = AtomicU64::new(0);
+
+/// Number of keyspaces taken by `take_all` dropped so far, each a while after it is dropped
+pub static DROPS: AtomicU64 = AtomicU64::new(0);
+pub const DROP_TIME: Duration = Duration::from_millis(500);
+
+// keys taken from a storage, slow to drop as millions of keys would be
+struct Keyspace(HashMap<RedisString, (RedisString, RedisMeta)>);
+
+impl Drop for Keyspace {
+    fn drop(&mut self) {
+        if !self.0.is_empty() {
+            sleep(DROP_TIME);
+            DROPS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct StringStorage {
+    strings: HashMap<RedisString, (RedisString, RedisMeta)>,
+}
+
+impl StringStorage {
+    // the string at key, removed once expired
+    fn live(&mut self, key: &[u8]) -> Option<&mut (RedisString, RedisMeta)> {
+        if self.strings.get(key)?.1.is_expired() {
+            self.strings.remove(key);
+            return None;
+        }
+        self.strings.get_mut(key)
+    }
+}
+
+impl Storage for StringStorage {
+    fn write(&mut self, key: &[u8], value: &[u8]) {
+        WRITES.fetch_add(1, Ordering::SeqCst);
+        let meta = RedisMeta::new(RedisType::String, None);
+        self.strings.insert(key.to_vec(), (value.to_vec(), meta));
+    }
+
+    fn extend(&mut self, key: &[u8], value: &[u8]) -> u64 {
+        match self.live(key) {
+            Some((string, _)) => {
+                string.extend_from_slice(value);
+                string.len() as u64
+            }
+            None => {
+                self.write(key, value);
+                value.len() as u64
+            }
+        }
+    }
+
+    fn set_range(&mut self, key: &[u8], offset: usize, value: &[u8]) -> u64 {
+        let mut string = self.read(key).map(<[u8]>::to_vec).unwrap_or_default();
+        if string.len() < offset + value.len() {
+            string.resize(offset + value.len(), 0);
+        }
+        string[offset..offset + value.len()].copy_from_slice(value);
+        self.write(key, &string);
+        string.len() as u64
+    }
+
+    fn expire(&mut self, key: &[u8], expiry: Expiry) -> u32 {
+        match self.live(key) {
+            Some((_, meta)) => {
+                meta.expiry = Some(expiry);
+                1
+            }
+            None => 0,
+        }
+    }
+
+    fn persist(&mut self, key: &[u8]) -> u32 {
+        match self.live(key).and_then(|(_, meta)| meta.expiry.take()) {
+            Some(_) => 1,
+            None => 0,
+        }
+    }
+
+    fn read(&mut self, key: &[u8]) -> Option<&[u8]> {
+        let (string, meta) = self.live(key)?;
+        meta.touch();
+        Some(string)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> u32 {
+        self.strings.remove(key).is_some() as u32
+    }
+
+    fn contains(&mut self, key: &[u8]) -> bool {
+        self.live(key).is_some()
+    }
+
+    fn type_of(&mut self, key: &[u8]) -> &[u8] {
+        match self.contains(key) {
+            true => b"string",
+            false => b"none",
+        }
+    }
+
+    fn entry(&mut self, key: &[u8]) -> Option<ValueRef<'_>> {
+        self.read(key).map(ValueRef::String)
+    }
+
+    fn lwrite(&mut self, _key: &[u8], _values: Vec<RedisString>) {}
+
+    fn lread(&mut self, _key: &[u8]) -> Option<&Vec<RedisString>> {
+        None
+    }
+
+    fn swrite(&mut self, _key: &[u8], _values: HashSet<RedisString>) {}
+
+    fn sread(&mut self, _key: &[u8]) -> Option<&HashSet<RedisString>> {
+        None
+    }
+
+    fn sremove(&mut self, _key: &[u8], _members: &[RedisString]) -> u64 {
+        0
+    }
+
+    fn hwrite(&mut self, _key: &[u8], _value: HashMap<RedisString, RedisString>) {}
+
+    fn hread(&mut self, _key: &[u8], _field_key: &[u8]) -> Option<&[u8]> {
+        None
+    }
+
+    fn hread_all(&mut self, _key: &[u8]) -> Option<&HashMap<RedisString, RedisString>> {
+        None
+    }
+
+    fn hset(&mut self, _key: &[u8], _fields: Vec<(RedisString, RedisString)>) -> u64 {
+        0
+    }
+
+    fn hentries(&mut self, _key: &[u8]) -> Option<Vec<(RedisString, RedisString)>> {
+        None
+    }
+
+    fn hexpiry(&mut self, _key: &[u8], _field: &[u8]) -> Option<Option<Expiry>> {
+        None
+    }
+
+    fn hset_expiry(&mut self, _key: &[u8], _field: &[u8], _expiry: Option<Expiry>) {}
+
+    fn zwrite(&mut self, _key: &[u8], _value: RedisSortedSet) {}
+
+    fn zread(&mut self, _key: &[u8]) -> Option<&RedisSortedSet> {
+        None
+    }
+
+    fn size(&self) -> u64 {
+        self.strings.len() as u64
+    }
+
+    fn expires(&self) -> u64 {
+        self.strings
+            .values()
+            .filter(|(_, meta)| meta.expiry.is_some())
+            .count() as u64
+    }
+
+    fn meta(&self, key: &[u8]) -> Option<&RedisMeta> {
+        self.strings.get(key).map(|(_, meta)| meta)
+    }
+
+    fn meta_mut(&mut self, key: &[u8]) -> Option<&mut RedisMeta> {
+        self.strings.get_mut(key).map(|(_, meta)| meta)
+    }
+
+    fn memory_usage(&self, key: &[u8]) -> Option<u64> {
+        let (string, _) = self.strings.get(key)?;
+        Some((key.len() + string.len()) as u64)
+    }
+
+    fn used_memory(&self) -> u64 {
+        self.strings
+            .keys()
+            .filter_map(|key| self.memory_usage(key))
+            .sum()
+    }
+
+    fn keys_overhead(&self) -> u64 {
+        0
+    }
+
+    fn keys(&self) -> Vec<RedisString> {
+        self.strings.keys().cloned().collect()
+    }
+
+    fn value(&self, key: &[u8]) -> Option<RedisValue> {
+        let (string, _) = self.strings.get(key)?;
+        Some(RedisValue::String(string.clone()))
+    }
+
+    fn insert(&mut self, key: &[u8], value: RedisValue, expiry: Option<Expiry>) {
+        self.remove(key);
+        if let RedisValue::String(string) = value {
+            self.write(key, &string);
+            if let Some(expiry) = expiry {
+                self.expire(key, expiry);
+            }
+        }
+    }
+
+    fn set_encoding_limits(&mut self, _limits: EncodingLimits) {}
+
+    fn set_ordered_hashes(&mut self, _ordered: bool) {}
+
+    fn clear(&mut self) {
+        drop(self.take_all());
+    }
+
+    fn take_all(&mut self) -> Box<dyn Send> {
+        Box::new(Keyspace(std::mem::take(&mut self.strings)))
+    }
+}
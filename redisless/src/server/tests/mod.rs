@@ -1,12 +1,23 @@
 use redis::{Commands, Connection, RedisResult};
-use std::{thread::sleep, time::Duration};
+use std::{
+    collections::HashMap,
+    thread::sleep,
+    time::{Duration, Instant},
+};
 
-use crate::server::ServerState;
+mod mock;
+
+use crate::server::{MaxMemoryPolicy, OutputBufferLimit, ServerConfig, ServerState};
 use crate::storage::in_memory::InMemoryStorage;
 use crate::Server;
 
 fn get_redis_client_connection(port: u16) -> (Server, Connection) {
-    let server = Server::new(InMemoryStorage::default(), port);
+    get_redis_client_connection_with_config(ServerConfig::new(port))
+}
+
+fn get_redis_client_connection_with_config(config: ServerConfig) -> (Server, Connection) {
+    let port = config.port;
+    let server = Server::new_with_config(InMemoryStorage::default(), config);
     assert_eq!(server.start(), Some(ServerState::Started));
 
     let redis_client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
@@ -37,6 +48,18 @@ fn test_incr_decr_commands() {
     let value: u32 = con.get("63").unwrap();
     assert_eq!(value, 79_u32);
 
+    // as in Redis, the integers are not padded, signed with a + or led by zeros
+    for value in &[" 10", "10 ", "+10", "010"] {
+        let _: () = con.set("padded", *value).unwrap();
+        let err = con.incr::<_, _, i64>("padded", 1).unwrap_err();
+        assert_eq!(
+            err.detail(),
+            Some("value is not an integer or out of range")
+        );
+    }
+    let err = con.incr::<_, _, i64>("n", " 1").unwrap_err();
+    assert_eq!(err.code(), Some("ERR"));
+
     assert_eq!(server.stop(), Some(ServerState::Stopped));
 }
 
@@ -147,6 +170,27 @@ fn expire_and_ttl() {
     let ttl: i32 = con.pttl("key").unwrap();
     assert_eq!(ttl, -2);
 
+    // a ttl in seconds still expires at a millisecond precision
+    let _: () = con.set("key", "value").unwrap();
+    let _: u32 = con.expire("key", 10).unwrap();
+    let ttl: i64 = con.pttl("key").unwrap();
+    assert!((9000..=10000).contains(&ttl));
+    let ttl: i64 = con.ttl("key").unwrap();
+    assert_eq!(ttl, 10);
+    let _: () = con.del("key").unwrap();
+
+    // a ttl too large for an instant is refused
+    let _: () = con.set("key", "value").unwrap();
+    let err = redis::cmd("EXPIRE")
+        .arg("key")
+        .arg(i64::MAX)
+        .query::<u32>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.detail(), Some("invalid expire time"));
+    let ttl: i32 = con.ttl("key").unwrap();
+    assert_eq!(ttl, -1);
+    let _: () = con.del("key").unwrap();
+
     // SETEX
     let duration: usize = 2;
     let _: () = con.set_ex("key", "value", duration).unwrap();
@@ -190,6 +234,78 @@ fn get_set() {
     let x: String = con.get("key2").unwrap();
     assert_eq!(x, "value2");
 
+    // a key of another type is neither overwritten nor deleted
+    let _: () = con.rpush("list", "x").unwrap();
+    let err = con.getset::<_, _, String>("list", "value").unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+    let err = redis::cmd("GETDEL")
+        .arg("list")
+        .query::<String>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+    let keytype: String = redis::cmd("TYPE").arg("list").query(&mut con).unwrap();
+    assert_eq!(keytype, "list");
+    assert_eq!(con.lpop("list"), Ok("x".to_string()));
+
+    // a missing key is nil, and GETDEL leaves it missing
+    let x: Option<String> = redis::cmd("GETDEL").arg("key3").query(&mut con).unwrap();
+    assert_eq!(x, None);
+    assert_eq!(con.exists("key3"), Ok(false));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn set_clears_ttl() {
+    let (server, mut con) = get_redis_client_connection(3455);
+
+    let _: () = con.set_ex("key", "value", 100).unwrap();
+    let _: () = con.set("key", "other").unwrap();
+    assert_eq!(con.ttl::<_, i64>("key"), Ok(-1));
+
+    let _: () = con.set_ex("key", "value", 100).unwrap();
+    let _: () = redis::cmd("SET")
+        .arg("key")
+        .arg("other")
+        .arg("KEEPTTL")
+        .query(&mut con)
+        .unwrap();
+    assert!(con.ttl::<_, i64>("key").unwrap() > 0);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn streamed_large_value() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, mut con) = get_redis_client_connection(3456);
+    let value = (0..4 * 1024 * 1024)
+        .map(|i| b'a' + (i % 26) as u8)
+        .collect::<Vec<u8>>();
+    let _: () = con.set("big", value.as_slice()).unwrap();
+
+    let mut stream = TcpStream::connect("127.0.0.1:3456").unwrap();
+    // pipelined, so the small replies around the streamed ones stay in order
+    stream
+        .write_all(
+            b"*1\r\n$4\r\nPING\r\n*2\r\n$3\r\nGET\r\n$3\r\nbig\r\n\
+              *4\r\n$8\r\nGETRANGE\r\n$3\r\nbig\r\n$1\r\n1\r\n$2\r\n-2\r\n*1\r\n$4\r\nPING\r\n",
+        )
+        .unwrap();
+
+    let mut expected = format!("+PONG\r\n${}\r\n", value.len()).into_bytes();
+    expected.extend_from_slice(&value);
+    expected.extend_from_slice(format!("\r\n${}\r\n", value.len() - 2).as_bytes());
+    expected.extend_from_slice(&value[1..value.len() - 1]);
+    expected.extend_from_slice(b"\r\n+PONG\r\n");
+    let mut reply = vec![0; expected.len()];
+    stream.read_exact(&mut reply).unwrap();
+    assert!(reply == expected);
+
     assert_eq!(server.stop(), Some(ServerState::Stopped));
 }
 
@@ -212,6 +328,212 @@ fn dbsize() {
     assert_eq!(server.stop(), Some(ServerState::Stopped));
 }
 
+#[test]
+#[serial]
+fn info_keyspace() {
+    let (server, mut con) = get_redis_client_connection(3464);
+    let info = |con: &mut Connection| -> String { redis::cmd("INFO").query(con).unwrap() };
+
+    assert_eq!(info(&mut con), "# Keyspace\r\n");
+    let _: () = con.set("key", "value").unwrap();
+    let _: () = con.set_ex("volatile", "value", 100).unwrap();
+    let info = info(&mut con);
+    assert!(info.contains("db0:keys=2,expires=1,avg_ttl=0\r\n"));
+    // the empty databases are omitted
+    assert!(!info.contains("db1:"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn info_commandstats() {
+    let (server, mut con) = get_redis_client_connection(3471);
+    let info = |con: &mut Connection, section: &str| -> String {
+        redis::cmd("INFO").arg(section).query(con).unwrap()
+    };
+
+    for _ in 0..5 {
+        let _: Option<String> = con.get("key").unwrap();
+    }
+    let _: Vec<String> = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("port")
+        .query(&mut con)
+        .unwrap();
+    let _ = redis::cmd("GET").query::<()>(&mut con);
+    let _ = redis::cmd("INCR").arg("key").query::<()>(&mut con);
+    let _: () = con.set("text", "abc").unwrap();
+    let _ = redis::cmd("INCR").arg("text").query::<()>(&mut con);
+
+    let stats = info(&mut con, "commandstats");
+    assert!(stats.starts_with("# Commandstats\r\n"));
+    let line = |name: &str| -> String {
+        let prefix = format!("cmdstat_{}:", name);
+        stats
+            .lines()
+            .find(|line| line.starts_with(&prefix))
+            .unwrap_or_default()
+            .to_string()
+    };
+    // the GET with the wrong number of arguments is only rejected
+    assert!(line("get").starts_with("cmdstat_get:calls=5,usec="));
+    assert!(line("get").ends_with(",rejected_calls=1,failed_calls=0"));
+    assert!(line("config|get").starts_with("cmdstat_config|get:calls=1,"));
+    assert!(line("incr").starts_with("cmdstat_incr:calls=2,"));
+    assert!(line("incr").ends_with(",rejected_calls=0,failed_calls=1"));
+    // the keyspace is the only default section
+    assert!(!info(&mut con, "keyspace").contains("cmdstat_"));
+    let default: String = redis::cmd("INFO").query(&mut con).unwrap();
+    assert!(default.starts_with("# Keyspace\r\n") && !default.contains("cmdstat_"));
+    assert!(info(&mut con, "everything").contains("cmdstat_info:calls="));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn latency_monitor() {
+    let (server, mut con) = get_redis_client_connection(3472);
+    let latest = |con: &mut Connection| -> Vec<redis::Value> {
+        redis::cmd("LATENCY").arg("LATEST").query(con).unwrap()
+    };
+
+    // the sampling is disabled by default
+    let _: () = redis::cmd("DEBUG")
+        .arg("SLEEP")
+        .arg("0.1")
+        .query(&mut con)
+        .unwrap();
+    assert!(latest(&mut con).is_empty());
+
+    let _: () = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("latency-monitor-threshold")
+        .arg("50")
+        .query(&mut con)
+        .unwrap();
+    let _: Option<String> = con.get("key").unwrap();
+    assert!(latest(&mut con).is_empty());
+    let _: () = redis::cmd("DEBUG")
+        .arg("SLEEP")
+        .arg("0.1")
+        .query(&mut con)
+        .unwrap();
+
+    let samples = latest(&mut con);
+    assert_eq!(samples.len(), 1);
+    // event, timestamp, latest and max latencies
+    let (timestamp, latency) = match &samples[0] {
+        redis::Value::Bulk(sample) => match &sample[..] {
+            [redis::Value::Data(event), redis::Value::Int(timestamp), redis::Value::Int(latency), redis::Value::Int(max)] =>
+            {
+                assert_eq!(event, b"command");
+                assert_eq!(max, latency);
+                (*timestamp, *latency)
+            }
+            sample => panic!("unexpected sample {:?}", sample),
+        },
+        sample => panic!("unexpected sample {:?}", sample),
+    };
+    assert!((timestamp - chrono::Utc::now().timestamp()).abs() <= 1);
+    assert!(latency >= 100);
+
+    let history: Vec<Vec<i64>> = redis::cmd("LATENCY")
+        .arg("HISTORY")
+        .arg("command")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(history, vec![vec![timestamp, latency]]);
+
+    let reset: i64 = redis::cmd("LATENCY")
+        .arg("RESET")
+        .arg("other")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(reset, 0);
+    let reset: i64 = redis::cmd("LATENCY").arg("RESET").query(&mut con).unwrap();
+    assert_eq!(reset, 1);
+    assert!(latest(&mut con).is_empty());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn active_expire() {
+    let (server, mut con) = get_redis_client_connection(3453);
+    let dbsize = |con: &mut Connection| -> u64 { redis::cmd("DBSIZE").query(con).unwrap() };
+
+    let _: () = redis::cmd("DEBUG")
+        .arg("SET-ACTIVE-EXPIRE")
+        .arg("0")
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.pset_ex("key", "value", 50).unwrap();
+    sleep(Duration::from_millis(300));
+    // only removed once accessed
+    assert_eq!(dbsize(&mut con), 1);
+    let value: Option<String> = con.get("key").unwrap();
+    assert_eq!(value, None);
+    assert_eq!(dbsize(&mut con), 0);
+
+    let _: () = redis::cmd("DEBUG")
+        .arg("SET-ACTIVE-EXPIRE")
+        .arg("1")
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.pset_ex("key", "value", 50).unwrap();
+    let _: () = con.set("persistent", "value").unwrap();
+    let start = Instant::now();
+    while dbsize(&mut con) > 1 && start.elapsed() < Duration::from_secs(2) {
+        sleep(Duration::from_millis(50));
+    }
+    assert_eq!(dbsize(&mut con), 1);
+    let value: Option<String> = con.get("persistent").unwrap();
+    assert_eq!(value.as_deref(), Some("value"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn unknown_command() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, mut con) = get_redis_client_connection(3454);
+    let err = redis::cmd("foo")
+        .arg("bar")
+        .arg("baz")
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("ERR"));
+    assert_eq!(
+        err.detail(),
+        Some("unknown command 'foo', with args beginning with: 'bar', 'baz'")
+    );
+    // the connection stays open
+    let _: () = con.set("key", "value").unwrap();
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    let config = ServerConfig {
+        strict_unknown_commands: true,
+        ..ServerConfig::new(3454)
+    };
+    let (server, _) = get_redis_client_connection_with_config(config);
+    let mut stream = TcpStream::connect("127.0.0.1:3454").unwrap();
+    stream.write_all(b"*1\r\n$3\r\nfoo\r\n").unwrap();
+    let mut reply = vec![];
+    // the error is replied, then the connection is closed
+    stream.read_to_end(&mut reply).unwrap();
+    assert_eq!(
+        reply,
+        b"-ERR unknown command 'foo', with args beginning with: \r\n".to_vec()
+    );
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
 #[test]
 #[serial]
 fn mset() {
@@ -438,6 +760,18 @@ fn lindex_lset_linsert() {
     assert_eq!(b, 5);
     let c: i64 = con.linsert_before("listkey", "val10", "val9").unwrap();
     assert_eq!(c, -1);
+    assert_eq!(con.lindex::<_, String>("listkey", 1).unwrap(), "val1");
+    assert_eq!(con.lindex::<_, String>("listkey", 4).unwrap(), "val4");
+    let d: i64 = con.linsert_after("missing", "val1", "val2").unwrap();
+    assert_eq!(d, 0);
+    assert!(!con.exists::<_, bool>("missing").unwrap());
+    assert!(redis::cmd("LINSERT")
+        .arg("listkey")
+        .arg("BESIDE")
+        .arg("val1")
+        .arg("val2")
+        .query::<i64>(&mut con)
+        .is_err());
     assert_eq!(server.stop(), Some(ServerState::Stopped));
 }
 
@@ -499,6 +833,31 @@ fn sadd_scard_srem() {
     assert_eq!(server.stop(), Some(ServerState::Stopped));
 }
 
+#[test]
+#[serial]
+fn smismember() {
+    let (server, mut con) = get_redis_client_connection(3451);
+    let smismember = |con: &mut Connection, key: &str, members: &[&str]| {
+        redis::cmd("SMISMEMBER")
+            .arg(key)
+            .arg(members)
+            .query::<Vec<i64>>(con)
+    };
+
+    let _: () = con.sadd("set", &["a", "c"]).unwrap();
+    assert_eq!(
+        smismember(&mut con, "set", &["a", "b", "c", "a"]),
+        Ok(vec![1, 0, 1, 1])
+    );
+    assert_eq!(smismember(&mut con, "missing", &["a", "b"]), Ok(vec![0, 0]));
+
+    let _: () = con.set("string", "a").unwrap();
+    let err = smismember(&mut con, "string", &["a"]).unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
 #[test]
 #[serial]
 fn start_and_stop_server() {
@@ -533,3 +892,6542 @@ fn append() {
 
     assert_eq!(server.stop(), Some(ServerState::Stopped));
 }
+
+#[test]
+#[serial]
+fn set_with_get_option() {
+    let (server, mut con) = get_redis_client_connection(3400);
+
+    let _: () = con.set("key", "v1").unwrap();
+    let x: String = redis::cmd("SET")
+        .arg("key")
+        .arg("v2")
+        .arg("GET")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "v1");
+    let x: String = con.get("key").unwrap();
+    assert_eq!(x, "v2");
+
+    let x: Option<String> = redis::cmd("SET")
+        .arg("new_key")
+        .arg("v1")
+        .arg("GET")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+    let x: String = con.get("new_key").unwrap();
+    assert_eq!(x, "v1");
+
+    let _: u32 = con.rpush("list_key", "v1").unwrap();
+    let x: RedisResult<Option<String>> = redis::cmd("SET")
+        .arg("list_key")
+        .arg("v2")
+        .arg("GET")
+        .query(&mut con);
+    assert!(x.is_err());
+    let l: i64 = con.llen("list_key").unwrap();
+    assert_eq!(l, 1);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[cfg(unix)]
+#[test]
+#[serial]
+fn ping_over_unix_socket() {
+    use std::io::{Read, Write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixStream;
+
+    let path = std::env::temp_dir().join("redisless-test.sock");
+    let config = ServerConfig {
+        port: 0,
+        unixsocket: Some(path.clone()),
+        unixsocketperm: Some(0o700),
+        ..Default::default()
+    };
+    let server = Server::new_with_config(InMemoryStorage::default(), config);
+    assert_eq!(server.start(), Some(ServerState::Started));
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o700);
+
+    let mut stream = UnixStream::connect(&path).unwrap();
+    let _ = stream.write(b"*1\r\n$4\r\nPING\r\n");
+    let mut pong_res = [0; 7];
+    let _ = stream.read(&mut pong_res);
+    assert_eq!(pong_res, b"+PONG\r\n"[..]);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn object_refcount() {
+    let (server, mut con) = get_redis_client_connection(3401);
+
+    let _: () = con.set("shared", "42").unwrap();
+    let _: () = con.set("key", "value").unwrap();
+
+    let x: i64 = redis::cmd("OBJECT")
+        .arg("REFCOUNT")
+        .arg("shared")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, i32::MAX as i64);
+    let x: i64 = redis::cmd("OBJECT")
+        .arg("REFCOUNT")
+        .arg("key")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    let x: RedisResult<i64> = redis::cmd("OBJECT")
+        .arg("REFCOUNT")
+        .arg("missing")
+        .query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn object_idletime() {
+    let (server, mut con) = get_redis_client_connection(3402);
+
+    let _: () = con.set("key", "value").unwrap();
+    sleep(Duration::from_millis(1100));
+
+    let x: i64 = redis::cmd("OBJECT")
+        .arg("IDLETIME")
+        .arg("key")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+
+    // reading the key resets its idle time
+    let _: String = con.get("key").unwrap();
+    let x: i64 = redis::cmd("OBJECT")
+        .arg("IDLETIME")
+        .arg("key")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+
+    let x: RedisResult<i64> = redis::cmd("OBJECT")
+        .arg("IDLETIME")
+        .arg("missing")
+        .query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn lolwut() {
+    let (server, mut con) = get_redis_client_connection(3403);
+
+    let x: String = redis::cmd("LOLWUT").query(&mut con).unwrap();
+    assert!(x.starts_with("RedisLess ver. "));
+    let x: String = redis::cmd("LOLWUT")
+        .arg("VERSION")
+        .arg(5)
+        .query(&mut con)
+        .unwrap();
+    assert!(x.starts_with("RedisLess ver. "));
+    let x: RedisResult<String> = redis::cmd("LOLWUT").arg("FOO").query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn bgsave_and_lastsave() {
+    let dir = std::env::temp_dir().join("redisless-bgsave");
+    let _ = std::fs::create_dir_all(&dir);
+    let config = ServerConfig {
+        dir: dir.clone(),
+        ..ServerConfig::new(3404)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+
+    let _: () = con.set("key", "value").unwrap();
+    let first_save: i64 = redis::cmd("LASTSAVE").query(&mut con).unwrap();
+    sleep(Duration::from_millis(1100));
+
+    let x: String = redis::cmd("BGSAVE").query(&mut con).unwrap();
+    assert_eq!(x, "Background saving started");
+
+    let mut last_save = first_save;
+    for _ in 0..50 {
+        last_save = redis::cmd("LASTSAVE").query(&mut con).unwrap();
+        if last_save > first_save {
+            break;
+        }
+        sleep(Duration::from_millis(20));
+    }
+    assert!(last_save > first_save);
+    assert!(dir.join("dump.rdb").exists());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn debug_reload() {
+    use crate::storage::snapshot::{self, SnapshotEntry};
+
+    let dir = std::env::temp_dir().join("redisless-debug-reload");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = ServerConfig {
+        dir: dir.clone(),
+        ..ServerConfig::new(3467)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    // the entries of every db saved by SAVE, sorted by key
+    let saved = |con: &mut Connection| -> Vec<Vec<SnapshotEntry>> {
+        let _: () = redis::cmd("SAVE").query(con).unwrap();
+        let mut databases = snapshot::load(&dir.join("dump.rdb")).unwrap();
+        for entries in &mut databases {
+            entries.sort_by(|a, b| a.key.cmp(&b.key));
+        }
+        databases
+    };
+
+    let _: () = con.set("string", "value").unwrap();
+    let _: () = con.set("integer", 42).unwrap();
+    let _: u64 = con.rpush("list", &["a", "b", "c"]).unwrap();
+    let _: u64 = con.sadd("set", &["x", "y"]).unwrap();
+    let _: () = con.hset("hash", "field", "value").unwrap();
+    let _: u64 = con.zadd("zset", "member", 1.5).unwrap();
+    let _: () = con.set_ex("volatile", "value", 100).unwrap();
+    let _: () = redis::cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let _: () = con.set("other", "db").unwrap();
+    let before = saved(&mut con);
+
+    let reply: String = redis::cmd("DEBUG").arg("RELOAD").query(&mut con).unwrap();
+    assert_eq!(reply, "OK");
+    assert_eq!(saved(&mut con), before);
+    // the reloaded values are served as before
+    let other: String = con.get("other").unwrap();
+    assert_eq!(other, "db");
+    let _: () = redis::cmd("SELECT").arg(0).query(&mut con).unwrap();
+    let kind: String = redis::cmd("TYPE").arg("zset").query(&mut con).unwrap();
+    assert_eq!(kind, "zset");
+    let ttl: i64 = con.ttl("volatile").unwrap();
+    assert!(ttl > 0 && ttl <= 100);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn bgrewriteaof() {
+    use crate::protocol::parser::RedisProtocolParser;
+
+    let dir = std::env::temp_dir().join("redisless-aof");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("appendonly.aof");
+    let config = |port| ServerConfig {
+        dir: dir.clone(),
+        appendonly: true,
+        ..ServerConfig::new(port)
+    };
+    let commands = |path: &std::path::Path| {
+        let bytes = std::fs::read(path).unwrap();
+        let mut rest = &bytes[..];
+        let mut count = 0;
+        while let Ok((_, left)) = RedisProtocolParser::parse(rest) {
+            count += 1;
+            rest = left;
+        }
+        count
+    };
+
+    let (server, mut con) = get_redis_client_connection_with_config(config(3462));
+    for i in 0..20 {
+        let _: () = con.set("counter", i).unwrap();
+    }
+    let _: u64 = con.rpush("list", &["a", "b", "c"]).unwrap();
+    let _: String = con.lpop("list").unwrap();
+    let _: () = con.sadd("set", "member").unwrap();
+    let _: () = con.hset("hash", "field", "value").unwrap();
+    let _: () = con.zadd("zset", "member", 1.5).unwrap();
+    let _: () = con.set_ex("volatile", "value", 100).unwrap();
+    let _: () = con.set("gone", "value").unwrap();
+    let _: () = con.del("gone").unwrap();
+    let _: () = redis::cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let _: () = con.set("other", "db").unwrap();
+    let before = commands(&path);
+
+    let reply: String = redis::cmd("BGREWRITEAOF").query(&mut con).unwrap();
+    assert_eq!(reply, "Background append only file rewriting started");
+    let start = Instant::now();
+    while commands(&path) >= before && start.elapsed() < Duration::from_secs(5) {
+        sleep(Duration::from_millis(20));
+    }
+    assert!(commands(&path) < before);
+    // the writes following the rewrite are appended to the new file
+    let _: () = con.set("after", "rewrite").unwrap();
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    // a new server replays the file
+    let (server, mut con) = get_redis_client_connection_with_config(config(3463));
+    let counter: i64 = con.get("counter").unwrap();
+    assert_eq!(counter, 19);
+    let len: u64 = con.llen("list").unwrap();
+    assert_eq!(len, 2);
+    let first: String = con.lindex("list", 0).unwrap();
+    assert_eq!(first, "b");
+    let members: Vec<bool> = redis::cmd("SMISMEMBER")
+        .arg("set")
+        .arg("member")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(members, vec![true]);
+    let value: String = con.hget("hash", "field").unwrap();
+    assert_eq!(value, "value");
+    let count: u64 = con.zcount("zset", 1.5, 1.5).unwrap();
+    assert_eq!(count, 1);
+    let ttl: i64 = con.ttl("volatile").unwrap();
+    assert!(ttl > 0 && ttl <= 100);
+    let exists: bool = con.exists("gone").unwrap();
+    assert!(!exists);
+    let _: () = redis::cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let other: String = con.get("other").unwrap();
+    assert_eq!(other, "db");
+    let after: String = con.get("after").unwrap();
+    assert_eq!(after, "rewrite");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn flushdb_and_flushall() {
+    let (server, mut con) = get_redis_client_connection(3405);
+
+    let _: () = con.set("key0", "value").unwrap();
+    let _: () = redis::cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let _: () = con.set("key1", "value").unwrap();
+    let _: () = con.set("key2", "value").unwrap();
+    let x: i64 = redis::cmd("DBSIZE").query(&mut con).unwrap();
+    assert_eq!(x, 2);
+
+    let x: redis::RedisResult<()> = redis::cmd("SELECT").arg(16).query(&mut con);
+    assert!(x.is_err());
+
+    // FLUSHDB only empties the selected db
+    let _: () = redis::cmd("SELECT").arg(2).query(&mut con).unwrap();
+    let _: () = con.set("key3", "value").unwrap();
+    let _: () = redis::cmd("FLUSHDB").query(&mut con).unwrap();
+    let x: i64 = redis::cmd("DBSIZE").query(&mut con).unwrap();
+    assert_eq!(x, 0);
+    let _: () = redis::cmd("SELECT").arg(0).query(&mut con).unwrap();
+    let x: i64 = redis::cmd("DBSIZE").query(&mut con).unwrap();
+    assert_eq!(x, 1);
+
+    let x: String = redis::cmd("FLUSHALL").arg("ASYNC").query(&mut con).unwrap();
+    assert_eq!(x, "OK");
+    for db in 0..3 {
+        let _: () = redis::cmd("SELECT").arg(db).query(&mut con).unwrap();
+        let x: i64 = redis::cmd("DBSIZE").query(&mut con).unwrap();
+        assert_eq!(x, 0);
+    }
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn getrange_and_substr() {
+    let (server, mut con) = get_redis_client_connection(3406);
+
+    let _: () = con.set("key", "This is a string").unwrap();
+
+    let ranges = [
+        (0, 3),
+        (-3, -1),
+        (0, -1),
+        (10, 100),
+        (-100, 3),
+        (5, 2),
+        (-1, -5),
+    ];
+    for (start, end) in ranges.iter() {
+        let x: String = redis::cmd("GETRANGE")
+            .arg("key")
+            .arg(*start)
+            .arg(*end)
+            .query(&mut con)
+            .unwrap();
+        let y: String = redis::cmd("SUBSTR")
+            .arg("key")
+            .arg(*start)
+            .arg(*end)
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(x, y);
+    }
+
+    let x: String = redis::cmd("SUBSTR")
+        .arg("key")
+        .arg(-3)
+        .arg(-1)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "ing");
+    let x: String = redis::cmd("GETRANGE")
+        .arg("missing")
+        .arg(0)
+        .arg(-1)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn bitpos() {
+    let (server, mut con) = get_redis_client_connection(3407);
+
+    let _: () = con.set("key", &b"\xff\xf0\x00"[..]).unwrap();
+    let x: i64 = redis::cmd("BITPOS")
+        .arg("key")
+        .arg(0)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 12);
+    let x: i64 = redis::cmd("BITPOS")
+        .arg("key")
+        .arg(1)
+        .arg(-2)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 8);
+    let x: i64 = redis::cmd("BITPOS")
+        .arg("key")
+        .arg(1)
+        .arg(2)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, -1);
+
+    // looking for a clear bit in a string made of set bits
+    let _: () = con.set("ones", &b"\xff\xff\xff"[..]).unwrap();
+    let x: i64 = redis::cmd("BITPOS")
+        .arg("ones")
+        .arg(0)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 24);
+    let x: i64 = redis::cmd("BITPOS")
+        .arg("ones")
+        .arg(0)
+        .arg(1)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 24);
+    // with an explicit end the padding bits are not considered
+    let x: i64 = redis::cmd("BITPOS")
+        .arg("ones")
+        .arg(0)
+        .arg(0)
+        .arg(-1)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, -1);
+
+    let x: i64 = redis::cmd("BITPOS")
+        .arg("missing")
+        .arg(0)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+    let x: i64 = redis::cmd("BITPOS")
+        .arg("missing")
+        .arg(1)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, -1);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn bitop() {
+    let (server, mut con) = get_redis_client_connection(3408);
+
+    let _: () = con.set("a", &b"\x0f\xff"[..]).unwrap();
+    let _: () = con.set("b", &b"\xff"[..]).unwrap();
+
+    let x: i64 = redis::cmd("BITOP")
+        .arg("XOR")
+        .arg("dest")
+        .arg("a")
+        .arg("b")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 2);
+    // b is zero extended to the length of a
+    let x: Vec<u8> = redis::cmd("GETRANGE")
+        .arg("dest")
+        .arg(0)
+        .arg(-1)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, b"\xf0\xff".to_vec());
+
+    let x: redis::RedisResult<i64> = redis::cmd("BITOP")
+        .arg("NOT")
+        .arg("dest")
+        .arg("a")
+        .arg("b")
+        .query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn hrandfield() {
+    let sample = |port: u16| {
+        let config = ServerConfig {
+            seed: Some(42),
+            ..ServerConfig::new(port)
+        };
+        let (server, mut con) = get_redis_client_connection_with_config(config);
+        let key_value_pairs = &[("a", "1"), ("b", "2"), ("c", "3")];
+        let _: () = con.hset_multiple("hash", key_value_pairs).unwrap();
+
+        let field: String = redis::cmd("HRANDFIELD")
+            .arg("hash")
+            .query(&mut con)
+            .unwrap();
+        assert!(["a", "b", "c"].contains(&field.as_str()));
+
+        // a positive count never returns more than the distinct fields
+        let mut fields: Vec<String> = redis::cmd("HRANDFIELD")
+            .arg("hash")
+            .arg(5)
+            .query(&mut con)
+            .unwrap();
+        fields.sort();
+        assert_eq!(fields, vec!["a", "b", "c"]);
+
+        let repeated: Vec<String> = redis::cmd("HRANDFIELD")
+            .arg("hash")
+            .arg(-5)
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(repeated.len(), 5);
+
+        let pairs: Vec<(String, String)> = redis::cmd("HRANDFIELD")
+            .arg("hash")
+            .arg(2)
+            .arg("WITHVALUES")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(pairs.len(), 2);
+        for (field, value) in &pairs {
+            assert!(key_value_pairs.contains(&(field.as_str(), value.as_str())));
+        }
+
+        let _: () = con.set("string", "value").unwrap();
+        let x: redis::RedisResult<String> = redis::cmd("HRANDFIELD").arg("string").query(&mut con);
+        assert!(x.is_err());
+
+        assert_eq!(server.stop(), Some(ServerState::Stopped));
+        repeated
+    };
+
+    // the same seed picks the same fields
+    assert_eq!(sample(3409), sample(3410));
+}
+
+#[test]
+#[serial]
+fn zpopmin_zpopmax() {
+    let (server, mut con) = get_redis_client_connection(3469);
+    let zpop = |con: &mut Connection, name: &str, key: &str, count: Option<u64>| -> Vec<String> {
+        let mut command = redis::cmd(name);
+        command.arg(key);
+        if let Some(count) = count {
+            command.arg(count);
+        }
+        command.query(con).unwrap()
+    };
+
+    let _: u64 = con
+        .zadd_multiple("zset", &[(1, "one"), (2, "two"), (3, "three")])
+        .unwrap();
+    // without a count, a single member and its score
+    assert_eq!(zpop(&mut con, "ZPOPMIN", "zset", None), vec!["one", "1"]);
+    assert_eq!(zpop(&mut con, "ZPOPMAX", "zset", None), vec!["three", "3"]);
+    assert_eq!(
+        con.zcount::<_, _, _, u64>("zset", "-inf", "+inf").unwrap(),
+        1
+    );
+
+    // at most count members, and the key removed once empty
+    assert_eq!(zpop(&mut con, "ZPOPMAX", "zset", Some(5)), vec!["two", "2"]);
+    assert!(!con.exists::<_, bool>("zset").unwrap());
+    assert!(zpop(&mut con, "ZPOPMIN", "zset", None).is_empty());
+
+    let _: u64 = con
+        .zadd_multiple("scores", &[(1.5, "a"), (2.5, "b"), (0.5, "c")])
+        .unwrap();
+    assert_eq!(
+        zpop(&mut con, "ZPOPMIN", "scores", Some(2)),
+        vec!["c", "0.5", "a", "1.5"]
+    );
+
+    let _: () = con.set("string", "value").unwrap();
+    let err = redis::cmd("ZPOPMIN")
+        .arg("string")
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zincrby_and_zcount() {
+    let (server, mut con) = get_redis_client_connection(3411);
+
+    let zincrby = |con: &mut Connection, increment: &str, member: &str| -> String {
+        redis::cmd("ZINCRBY")
+            .arg("board")
+            .arg(increment)
+            .arg(member)
+            .query(con)
+            .unwrap()
+    };
+    assert_eq!(zincrby(&mut con, "1", "a"), "1");
+    assert_eq!(zincrby(&mut con, "1.5", "a"), "2.5");
+    assert_eq!(zincrby(&mut con, "5", "b"), "5");
+    assert_eq!(zincrby(&mut con, "-2", "c"), "-2");
+
+    let zcount = |con: &mut Connection, min: &str, max: &str| -> i64 {
+        redis::cmd("ZCOUNT")
+            .arg("board")
+            .arg(min)
+            .arg(max)
+            .query(con)
+            .unwrap()
+    };
+    assert_eq!(zcount(&mut con, "-inf", "+inf"), 3);
+    assert_eq!(zcount(&mut con, "2.5", "5"), 2);
+    assert_eq!(zcount(&mut con, "(2.5", "5"), 1);
+    assert_eq!(zcount(&mut con, "(2.5", "(5"), 0);
+    assert_eq!(zcount(&mut con, "-inf", "0"), 1);
+
+    let x: redis::RedisResult<i64> = redis::cmd("ZCOUNT")
+        .arg("board")
+        .arg("nope")
+        .arg("1")
+        .query(&mut con);
+    assert!(x.is_err());
+
+    let _: () = con.set("string", "value").unwrap();
+    let x: redis::RedisResult<String> = redis::cmd("ZINCRBY")
+        .arg("string")
+        .arg(1)
+        .arg("a")
+        .query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn keys() {
+    let (server, mut con) = get_redis_client_connection(3412);
+
+    for key in &["hello", "hallo", "hxllo", "hllo", "heeeello", "other"] {
+        let _: () = con.set(*key, "value").unwrap();
+    }
+
+    let keys_matching = |con: &mut Connection, pattern: &str| -> Vec<String> {
+        let mut keys: Vec<String> = con.keys(pattern).unwrap();
+        keys.sort();
+        keys
+    };
+    assert_eq!(
+        keys_matching(&mut con, "h?llo"),
+        vec!["hallo", "hello", "hxllo"]
+    );
+    assert_eq!(
+        keys_matching(&mut con, "h*llo"),
+        vec!["hallo", "heeeello", "hello", "hllo", "hxllo"]
+    );
+    assert_eq!(keys_matching(&mut con, "h[ae]llo"), vec!["hallo", "hello"]);
+    assert_eq!(keys_matching(&mut con, "h[^e]llo"), vec!["hallo", "hxllo"]);
+    assert_eq!(keys_matching(&mut con, "h[a-b]llo"), vec!["hallo"]);
+    assert_eq!(keys_matching(&mut con, "*").len(), 6);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn command_time_limit() {
+    let config = ServerConfig {
+        command_time_limit: Some(Duration::from_nanos(1)),
+        ..ServerConfig::new(3413)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+
+    for i in 0..1000 {
+        let _: () = con.set(format!("key{}", i), i).unwrap();
+    }
+
+    let err = con.keys::<_, Vec<String>>("*").unwrap_err();
+    assert_eq!(err.code(), Some("ERR"));
+    assert_eq!(err.detail(), Some("command exceeded time limit"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn proto_max_bulk_len() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let config = ServerConfig {
+        proto_max_bulk_len: 128 * 1024,
+        ..ServerConfig::new(3414)
+    };
+    let (server, con) = get_redis_client_connection_with_config(config);
+    drop(con);
+
+    // values larger than a single read of the connection are received in several parts
+    let mut stream = TcpStream::connect("127.0.0.1:3414").unwrap();
+    let value = "x".repeat(100 * 1024);
+    let request = format!(
+        "*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n${}\r\n{}\r\n",
+        value.len(),
+        value
+    );
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut reply = [0; 5];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(&reply, b"+OK\r\n");
+
+    stream
+        .write_all(b"*4\r\n$8\r\nGETRANGE\r\n$3\r\nkey\r\n$1\r\n0\r\n$2\r\n-1\r\n")
+        .unwrap();
+    let expected = format!("${}\r\n{}\r\n", value.len(), value);
+    let mut reply = vec![0; expected.len()];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(reply, expected.as_bytes());
+
+    // the declared length is rejected without waiting for the string
+    stream
+        .write_all(b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$999999999\r\n")
+        .unwrap();
+    let mut reply = String::new();
+    let _ = stream.read_to_string(&mut reply);
+    assert_eq!(reply, "-ERR Protocol error: invalid bulk length\r\n");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn null_array_request() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, con) = get_redis_client_connection(3415);
+    drop(con);
+
+    // a null array is answered by an error, and the connection stays usable
+    let mut stream = TcpStream::connect("127.0.0.1:3415").unwrap();
+    stream.write_all(b"*-1\r\n*1\r\n$4\r\nPING\r\n").unwrap();
+    let expected = b"-ERR invalid command\r\n+PONG\r\n";
+    let mut reply = vec![0; expected.len()];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(reply, expected.to_vec());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn partial_request_at_close() {
+    use std::io::{Read, Write};
+    use std::net::{Shutdown, TcpStream};
+
+    let (server, mut con) = get_redis_client_connection(3490);
+
+    // the server closes its end once the client stops writing in the middle of a request,
+    // without replying
+    let mut stream = TcpStream::connect("127.0.0.1:3490").unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+        .write_all(b"*1\r\n$4\r\nPING\r\n*2\r\n$3\r\nGE")
+        .unwrap();
+    stream.shutdown(Shutdown::Write).unwrap();
+    let mut reply = vec![];
+    stream.read_to_end(&mut reply).unwrap();
+    assert_eq!(reply, b"+PONG\r\n".to_vec());
+
+    let pong: String = redis::cmd("PING").query(&mut con).unwrap();
+    assert_eq!(pong, "PONG");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn sintercard() {
+    let (server, mut con) = get_redis_client_connection(3416);
+
+    let _: () = con.sadd("a", &["1", "2", "3", "4"]).unwrap();
+    let _: () = con.sadd("b", &["2", "3", "4", "5"]).unwrap();
+    let _: () = con.sadd("c", &["3", "4", "5", "6"]).unwrap();
+
+    let x: i64 = redis::cmd("SINTERCARD")
+        .arg(3)
+        .arg("a")
+        .arg("b")
+        .arg("c")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 2);
+    let x: i64 = redis::cmd("SINTERCARD")
+        .arg(2)
+        .arg("a")
+        .arg("b")
+        .arg("LIMIT")
+        .arg(1)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 1);
+    // LIMIT 0 means no limit
+    let x: i64 = redis::cmd("SINTERCARD")
+        .arg(2)
+        .arg("a")
+        .arg("b")
+        .arg("LIMIT")
+        .arg(0)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 3);
+    let x: i64 = redis::cmd("SINTERCARD")
+        .arg(2)
+        .arg("a")
+        .arg("missing")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 0);
+
+    let x: RedisResult<i64> = redis::cmd("SINTERCARD")
+        .arg(3)
+        .arg("a")
+        .arg("b")
+        .query(&mut con);
+    assert!(x.is_err());
+    let x: RedisResult<i64> = redis::cmd("SINTERCARD")
+        .arg(1)
+        .arg("a")
+        .arg("b")
+        .query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn lpos() {
+    let (server, mut con) = get_redis_client_connection(3417);
+
+    let _: () = con
+        .rpush("list", &["a", "b", "c", "1", "2", "3", "c", "c"])
+        .unwrap();
+
+    let x: i64 = redis::cmd("LPOS")
+        .arg("list")
+        .arg("c")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 2);
+    let x: i64 = redis::cmd("LPOS")
+        .arg("list")
+        .arg("c")
+        .arg("RANK")
+        .arg(2)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 6);
+    let x: i64 = redis::cmd("LPOS")
+        .arg("list")
+        .arg("c")
+        .arg("RANK")
+        .arg(-1)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, 7);
+    let x: Vec<i64> = redis::cmd("LPOS")
+        .arg("list")
+        .arg("c")
+        .arg("COUNT")
+        .arg(0)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![2, 6, 7]);
+    let x: Vec<i64> = redis::cmd("LPOS")
+        .arg("list")
+        .arg("c")
+        .arg("RANK")
+        .arg(-1)
+        .arg("COUNT")
+        .arg(2)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, vec![7, 6]);
+    let x: Option<i64> = redis::cmd("LPOS")
+        .arg("list")
+        .arg("z")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+
+    let _: () = con.set("string", "value").unwrap();
+    let x: RedisResult<i64> = redis::cmd("LPOS").arg("string").arg("c").query(&mut con);
+    assert!(x.is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zrangebylex() {
+    let (server, mut con) = get_redis_client_connection(3452);
+    let zrangebylex = |con: &mut Connection, args: &[&str]| -> RedisResult<Vec<String>> {
+        redis::cmd("ZRANGEBYLEX").arg("words").arg(args).query(con)
+    };
+
+    let _: () = con
+        .zadd_multiple("words", &[(0, "d"), (0, "b"), (0, "a"), (0, "c"), (0, "e")])
+        .unwrap();
+    assert_eq!(
+        zrangebylex(&mut con, &["-", "[c"]),
+        Ok(vec!["a".to_string(), "b".to_string(), "c".to_string()])
+    );
+    assert_eq!(
+        zrangebylex(&mut con, &["(b", "(e"]),
+        Ok(vec!["c".to_string(), "d".to_string()])
+    );
+    assert_eq!(
+        zrangebylex(&mut con, &["[d", "+"]),
+        Ok(vec!["d".to_string(), "e".to_string()])
+    );
+    assert_eq!(
+        zrangebylex(&mut con, &["-", "+", "LIMIT", "1", "2"]),
+        Ok(vec!["b".to_string(), "c".to_string()])
+    );
+    assert_eq!(zrangebylex(&mut con, &["+", "-"]), Ok(vec![]));
+
+    let err = zrangebylex(&mut con, &["a", "[c"]).unwrap_err();
+    assert_eq!(err.detail(), Some("syntax error"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zadd_options() {
+    let (server, mut con) = get_redis_client_connection(3418);
+
+    let zadd = |con: &mut Connection, args: &[&str]| -> RedisResult<i64> {
+        redis::cmd("ZADD").arg("board").arg(args).query(con)
+    };
+    let zscore = |con: &mut Connection, member: &str| -> String {
+        redis::cmd("ZINCRBY")
+            .arg("board")
+            .arg(0)
+            .arg(member)
+            .query(con)
+            .unwrap()
+    };
+
+    assert_eq!(zadd(&mut con, &["10", "a", "20", "b"]), Ok(2));
+
+    // GT only updates when the score increases, and still adds new members
+    assert_eq!(
+        zadd(&mut con, &["GT", "5", "a", "30", "b", "1", "c"]),
+        Ok(1)
+    );
+    assert_eq!(zscore(&mut con, "a"), "10");
+    assert_eq!(zscore(&mut con, "b"), "30");
+
+    // CH counts the updated members along with the added ones
+    assert_eq!(
+        zadd(&mut con, &["CH", "11", "a", "30", "b", "2", "d"]),
+        Ok(2)
+    );
+    assert_eq!(zadd(&mut con, &["XX", "CH", "12", "a", "3", "e"]), Ok(1));
+    assert_eq!(zadd(&mut con, &["NX", "100", "a", "4", "e"]), Ok(1));
+    assert_eq!(zscore(&mut con, "a"), "12");
+
+    let x: String = redis::cmd("ZADD")
+        .arg("board")
+        .arg("INCR")
+        .arg("3")
+        .arg("a")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, "15");
+    let x: Option<String> = redis::cmd("ZADD")
+        .arg("board")
+        .arg("LT")
+        .arg("INCR")
+        .arg("1")
+        .arg("a")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(x, None);
+
+    assert!(zadd(&mut con, &["NX", "XX", "1", "a"]).is_err());
+    assert!(zadd(&mut con, &["GT", "LT", "1", "a"]).is_err());
+    assert!(zadd(&mut con, &["NX", "GT", "1", "a"]).is_err());
+    assert!(zadd(&mut con, &["INCR", "1", "a", "2", "b"]).is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn object_freq() {
+    let config = ServerConfig {
+        maxmemory_policy: MaxMemoryPolicy::AllKeysLfu,
+        ..ServerConfig::new(3419)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    let freq = |con: &mut Connection, key: &str| -> RedisResult<i64> {
+        redis::cmd("OBJECT").arg("FREQ").arg(key).query(con)
+    };
+
+    let _: () = con.set("key", "value").unwrap();
+    let initial = freq(&mut con, "key").unwrap();
+    for _ in 0..10 {
+        let _: String = con.get("key").unwrap();
+    }
+    assert!(freq(&mut con, "key").unwrap() > initial);
+
+    assert!(freq(&mut con, "missing").is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    // the counter is only reported under an LFU policy
+    let (server, mut con) = get_redis_client_connection(3420);
+    let _: () = con.set("key", "value").unwrap();
+    let err = freq(&mut con, "key").unwrap_err();
+    assert_eq!(err.code(), Some("ERR"));
+    assert_eq!(
+        err.detail(),
+        Some("An LFU maxmemory policy is not selected")
+    );
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn concurrent_incr() {
+    const THREADS: usize = 16;
+    const INCREMENTS: usize = 200;
+
+    let (server, mut con) = get_redis_client_connection(3421);
+
+    let handles: Vec<_> = (0..THREADS)
+        .map(|i| {
+            std::thread::spawn(move || {
+                let client = redis::Client::open("redis://127.0.0.1:3421/").unwrap();
+                let mut con = client.get_connection().unwrap();
+                for _ in 0..INCREMENTS {
+                    let _: i64 = con.incr("counter", 1).unwrap();
+                    // every thread also has a key of its own
+                    let _: i64 = con.incr(format!("counter{}", i), 2).unwrap();
+                }
+            })
+        })
+        .collect();
+    handles
+        .into_iter()
+        .for_each(|handle| handle.join().unwrap());
+
+    let counter: usize = con.get("counter").unwrap();
+    assert_eq!(counter, THREADS * INCREMENTS);
+    for i in 0..THREADS {
+        let counter: usize = con.get(format!("counter{}", i)).unwrap();
+        assert_eq!(counter, 2 * INCREMENTS);
+    }
+
+    // the expiry of the key is kept
+    let _: () = con.set_ex("ttl", "1", 100).unwrap();
+    let _: i64 = con.incr("ttl", 1).unwrap();
+    assert!(con.ttl::<_, i64>("ttl").unwrap() > 0);
+
+    let _: () = con.set("text", "abc").unwrap();
+    let err = con.incr::<_, _, i64>("text", 1).unwrap_err();
+    assert_eq!(err.code(), Some("ERR"));
+    assert_eq!(
+        err.detail(),
+        Some("value is not an integer or out of range")
+    );
+
+    let _: () = con.set("max", i64::MAX).unwrap();
+    let err = con.incr::<_, _, i64>("max", 1).unwrap_err();
+    assert_eq!(err.code(), Some("ERR"));
+    assert_eq!(err.detail(), Some("increment or decrement would overflow"));
+
+    // the type is checked before the value is parsed
+    let _: () = con.rpush("list", "x").unwrap();
+    let _: () = con.hset("hash", "field", "1").unwrap();
+    for key in ["list", "hash"] {
+        for command in [&["INCR"][..], &["INCRBY", "2"], &["DECR"], &["DECRBY", "2"]] {
+            let err = redis::cmd(command[0])
+                .arg(key)
+                .arg(&command[1..])
+                .query::<i64>(&mut con)
+                .unwrap_err();
+            assert_eq!(err.code(), Some("WRONGTYPE"), "{:?}", command);
+        }
+    }
+    assert_eq!(con.lindex::<_, String>("list", 0).unwrap(), "x");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn scan_type() {
+    let (server, mut con) = get_redis_client_connection(3422);
+
+    for i in 0..20 {
+        let _: () = con.set(format!("string{}", i), i).unwrap();
+        let _: () = con.hset(format!("hash{}", i), "field", i).unwrap();
+    }
+
+    let scan_all = |con: &mut Connection, args: &[&str]| -> Vec<String> {
+        let mut cursor = 0;
+        let mut keys = vec![];
+        loop {
+            let (next, mut batch): (u64, Vec<String>) =
+                redis::cmd("SCAN").arg(cursor).arg(args).query(con).unwrap();
+            keys.append(&mut batch);
+            match next {
+                0 => break,
+                next => cursor = next,
+            }
+        }
+        keys.sort();
+        keys.dedup();
+        keys
+    };
+
+    let mut hashes: Vec<String> = (0..20).map(|i| format!("hash{}", i)).collect();
+    hashes.sort();
+    assert_eq!(scan_all(&mut con, &["TYPE", "hash", "COUNT", "3"]), hashes);
+    assert_eq!(scan_all(&mut con, &["TYPE", "HASH"]), hashes);
+    assert_eq!(scan_all(&mut con, &[]).len(), 40);
+
+    // the type filter applies on top of the pattern
+    assert_eq!(
+        scan_all(&mut con, &["MATCH", "*1", "TYPE", "string"]),
+        vec!["string1", "string11"]
+    );
+    assert_eq!(
+        scan_all(&mut con, &["MATCH", "string*", "TYPE", "hash"]),
+        Vec::<String>::new()
+    );
+
+    // an unknown type matches nothing
+    assert_eq!(
+        scan_all(&mut con, &["TYPE", "unknown"]),
+        Vec::<String>::new()
+    );
+
+    // batches hold up to COUNT keys of the requested type
+    let (_, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+        .arg(0)
+        .arg("COUNT")
+        .arg(5)
+        .arg("TYPE")
+        .arg("hash")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(batch.len(), 5);
+    assert!(batch.iter().all(|key| key.starts_with("hash")));
+
+    assert!(redis::cmd("SCAN")
+        .arg("nope")
+        .query::<(u64, Vec<String>)>(&mut con)
+        .is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn hscan_sscan_zscan() {
+    let (server, mut con) = get_redis_client_connection(3423);
+
+    let fields: Vec<(String, i32)> = (0..15).map(|i| (format!("field{}", i), i)).collect();
+    let _: () = con.hset_multiple("hash", &fields).unwrap();
+    for i in 0..15 {
+        let _: () = con.sadd("set", format!("member{}", i)).unwrap();
+        let _: () = con.zadd("zset", format!("member{}", i), i).unwrap();
+    }
+
+    let scan_all = |con: &mut Connection, command: &str, args: &[&str]| -> Vec<String> {
+        let mut cursor = 0;
+        let mut items = vec![];
+        loop {
+            let (next, mut batch): (u64, Vec<String>) = redis::cmd(command)
+                .arg(args[0])
+                .arg(cursor)
+                .arg(&args[1..])
+                .query(con)
+                .unwrap();
+            items.append(&mut batch);
+            match next {
+                0 => break,
+                next => cursor = next,
+            }
+        }
+        items
+    };
+
+    // fields and values are interleaved
+    let fields = scan_all(&mut con, "HSCAN", &["hash", "COUNT", "4"]);
+    let mut pairs: Vec<(String, String)> = fields
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect();
+    pairs.sort();
+    pairs.dedup();
+    let mut expected: Vec<(String, String)> = (0..15)
+        .map(|i| (format!("field{}", i), i.to_string()))
+        .collect();
+    expected.sort();
+    assert_eq!(pairs, expected);
+
+    let mut members = scan_all(&mut con, "SSCAN", &["set", "MATCH", "member1*"]);
+    members.sort();
+    members.dedup();
+    assert_eq!(
+        members,
+        vec!["member1", "member10", "member11", "member12", "member13", "member14"]
+    );
+
+    // members and scores are interleaved
+    let members = scan_all(&mut con, "ZSCAN", &["zset", "MATCH", "*3", "COUNT", "2"]);
+    let mut pairs: Vec<(String, String)> = members
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect();
+    pairs.sort();
+    pairs.dedup();
+    assert_eq!(
+        pairs,
+        vec![
+            ("member13".to_string(), "13".to_string()),
+            ("member3".to_string(), "3".to_string())
+        ]
+    );
+
+    // a missing key is an empty collection
+    let (cursor, batch): (u64, Vec<String>) = redis::cmd("HSCAN")
+        .arg("missing")
+        .arg(0)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!((cursor, batch), (0, vec![]));
+
+    assert!(redis::cmd("SSCAN")
+        .arg("set")
+        .arg(0)
+        .arg("TYPE")
+        .arg("set")
+        .query::<(u64, Vec<String>)>(&mut con)
+        .is_err());
+    assert!(redis::cmd("ZSCAN")
+        .arg("hash")
+        .arg(0)
+        .query::<(u64, Vec<String>)>(&mut con)
+        .is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn replicaof() {
+    let (primary, mut primary_con) = get_redis_client_connection(3424);
+    let (replica, mut replica_con) = get_redis_client_connection(3425);
+
+    let wait_for = |con: &mut Connection, key: &str| -> Option<String> {
+        for _ in 0..50 {
+            if let Some(value) = con.get::<_, Option<String>>(key).unwrap() {
+                return Some(value);
+            }
+            sleep(Duration::from_millis(100));
+        }
+        None
+    };
+
+    let _: () = primary_con.set("before", "sync").unwrap();
+    let _: () = redis::cmd("REPLICAOF")
+        .arg("127.0.0.1")
+        .arg(3424)
+        .query(&mut replica_con)
+        .unwrap();
+    assert_eq!(
+        wait_for(&mut replica_con, "before"),
+        Some("sync".to_string())
+    );
+
+    // the writes which follow the sync are streamed, in every db
+    let _: () = primary_con.set("after", "sync").unwrap();
+    let _: () = primary_con.rpush("list", &["a", "b"]).unwrap();
+    let _: () = redis::cmd("SELECT").arg(3).query(&mut primary_con).unwrap();
+    let _: () = primary_con.set("other", "db").unwrap();
+    // "other" is the last write, the others were applied before it
+    let _: () = redis::cmd("SELECT").arg(3).query(&mut replica_con).unwrap();
+    assert_eq!(wait_for(&mut replica_con, "other"), Some("db".to_string()));
+    let _: () = redis::cmd("SELECT").arg(0).query(&mut replica_con).unwrap();
+    assert_eq!(
+        replica_con.get::<_, String>("after").unwrap(),
+        "sync".to_string()
+    );
+    assert_eq!(replica_con.llen::<_, i64>("list").unwrap(), 2);
+    assert_eq!(replica_con.lindex::<_, String>("list", 1).unwrap(), "b");
+
+    let role: (String, String, u16, String, i64) =
+        redis::cmd("ROLE").query(&mut replica_con).unwrap();
+    assert_eq!(
+        role,
+        (
+            "slave".to_string(),
+            "127.0.0.1".to_string(),
+            3424,
+            "connected".to_string(),
+            0
+        )
+    );
+
+    // writes of clients are rejected by a replica, reads are served
+    let err = replica_con.set::<_, _, ()>("key", "value").unwrap_err();
+    assert_eq!(err.code(), Some("READONLY"));
+    assert!(replica_con.exists::<_, bool>("after").unwrap());
+
+    // a former replica accepts writes again and keeps its data
+    let _: () = redis::cmd("SLAVEOF")
+        .arg("NO")
+        .arg("ONE")
+        .query(&mut replica_con)
+        .unwrap();
+    let _: () = replica_con.set("key", "value").unwrap();
+    assert!(replica_con.exists::<_, bool>("after").unwrap());
+
+    assert_eq!(replica.stop(), Some(ServerState::Stopped));
+    assert_eq!(primary.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn replica_full_sync() {
+    let (primary, mut primary_con) = get_redis_client_connection(3426);
+    let (replica, mut replica_con) = get_redis_client_connection(3427);
+
+    let _: () = primary_con.set_ex("string", "value", 100).unwrap();
+    let _: () = primary_con.rpush("list", &["a", "b"]).unwrap();
+    let _: () = primary_con.sadd("set", "member").unwrap();
+    let _: () = primary_con.hset("hash", "field", "value").unwrap();
+    let _: () = primary_con.zadd("zset", "member", 1.5).unwrap();
+    let _: () = redis::cmd("SELECT").arg(2).query(&mut primary_con).unwrap();
+    let _: () = primary_con.set("db2", "value").unwrap();
+
+    // the data set of the replica is replaced by the one of the primary
+    let _: () = replica_con.set("stale", "value").unwrap();
+    let _: () = redis::cmd("REPLICAOF")
+        .arg("127.0.0.1")
+        .arg(3426)
+        .query(&mut replica_con)
+        .unwrap();
+
+    let mut synced = false;
+    for _ in 0..50 {
+        if replica_con.exists::<_, bool>("zset").unwrap() {
+            synced = true;
+            break;
+        }
+        sleep(Duration::from_millis(100));
+    }
+    assert!(synced);
+
+    assert!(!replica_con.exists::<_, bool>("stale").unwrap());
+    assert!(replica_con.ttl::<_, i64>("string").unwrap() > 0);
+    assert_eq!(replica_con.llen::<_, i64>("list").unwrap(), 2);
+    assert_eq!(replica_con.scard::<_, i64>("set").unwrap(), 1);
+    assert_eq!(
+        replica_con.hget::<_, _, String>("hash", "field").unwrap(),
+        "value"
+    );
+    assert_eq!(
+        replica_con
+            .zcount::<_, _, _, i64>("zset", 1.5, 1.5)
+            .unwrap(),
+        1
+    );
+    let _: () = redis::cmd("SELECT").arg(2).query(&mut replica_con).unwrap();
+    assert_eq!(replica_con.get::<_, String>("db2").unwrap(), "value");
+
+    assert_eq!(replica.stop(), Some(ServerState::Stopped));
+    assert_eq!(primary.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn rename_command() {
+    let mut rename_commands = HashMap::new();
+    rename_commands.insert("FLUSHALL".to_string(), "".to_string());
+    let config = ServerConfig {
+        rename_commands,
+        ..ServerConfig::new(3428)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+
+    let _: () = con.set("key", "value").unwrap();
+    let err = redis::cmd("FLUSHALL").query::<()>(&mut con).unwrap_err();
+    assert_eq!(err.code(), Some("ERR"));
+    assert_eq!(
+        err.detail(),
+        Some("unknown command 'FLUSHALL', with args beginning with: ")
+    );
+    assert!(redis::cmd("flushall").query::<()>(&mut con).is_err());
+    assert!(con.exists::<_, bool>("key").unwrap());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    // a renamed command is only known under its new name
+    let mut rename_commands = HashMap::new();
+    rename_commands.insert("FLUSHALL".to_string(), "nuke-everything".to_string());
+    let config = ServerConfig {
+        rename_commands,
+        ..ServerConfig::new(3429)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+
+    let _: () = con.set("key", "value").unwrap();
+    assert!(redis::cmd("FLUSHALL").query::<()>(&mut con).is_err());
+    assert!(con.exists::<_, bool>("key").unwrap());
+
+    let _: () = redis::cmd("NUKE-EVERYTHING").query(&mut con).unwrap();
+    assert!(!con.exists::<_, bool>("key").unwrap());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn memory_usage() {
+    let (server, mut con) = get_redis_client_connection(3430);
+    let usage = |con: &mut Connection, key: &str| -> Option<i64> {
+        redis::cmd("MEMORY")
+            .arg("USAGE")
+            .arg(key)
+            .query(con)
+            .unwrap()
+    };
+
+    let _: () = con.set("key", "value").unwrap();
+    let _: () = con.set("other", "a much longer value").unwrap();
+    let small = usage(&mut con, "key").unwrap();
+    assert!(small > "keyvalue".len() as i64);
+    assert!(usage(&mut con, "other").unwrap() > small);
+    assert_eq!(usage(&mut con, "missing"), None);
+
+    let _: () = con.del("key").unwrap();
+    assert_eq!(usage(&mut con, "key"), None);
+
+    // the number of samples is accepted, and does not change the estimate
+    let samples: i64 = redis::cmd("MEMORY")
+        .arg("USAGE")
+        .arg("other")
+        .arg("SAMPLES")
+        .arg(5)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(Some(samples), usage(&mut con, "other"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn blpop_brpop() {
+    let (server, mut con) = get_redis_client_connection(3431);
+
+    // an element already there is popped at once, from the first non empty key
+    let _: () = con.rpush("second", &["a", "b"]).unwrap();
+    let popped: (String, String) = con.blpop(&["first", "second"], 1).unwrap();
+    assert_eq!(popped, ("second".to_string(), "a".to_string()));
+    let popped: (String, String) = con.brpop(&["first", "second"], 1).unwrap();
+    assert_eq!(popped, ("second".to_string(), "b".to_string()));
+    assert!(!con.exists::<_, bool>("second").unwrap());
+
+    let start = std::time::Instant::now();
+    let popped: Option<(String, String)> = redis::cmd("BLPOP")
+        .arg("empty")
+        .arg(0.2)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(popped, None);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    // a blocked client is woken up by a push from another connection
+    let blocked = std::thread::spawn(|| {
+        let client = redis::Client::open("redis://127.0.0.1:3431/").unwrap();
+        let mut con = client.get_connection().unwrap();
+        let start = std::time::Instant::now();
+        let popped: (String, String) = con.brpop("queue", 0).unwrap();
+        (popped, start.elapsed())
+    });
+    sleep(Duration::from_millis(200));
+    let _: () = con.lpush("queue", "job").unwrap();
+    let (popped, elapsed) = blocked.join().unwrap();
+    assert_eq!(popped, ("queue".to_string(), "job".to_string()));
+    assert!(elapsed >= Duration::from_millis(200));
+    assert!(!con.exists::<_, bool>("queue").unwrap());
+
+    let _: () = con.set("string", "value").unwrap();
+    assert!(con.blpop::<_, ()>("string", 1).is_err());
+    let err = redis::cmd("BLPOP")
+        .arg("queue")
+        .arg(-1)
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.detail(), Some("timeout is negative"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn waitkey() {
+    let (server, mut con) = get_redis_client_connection(3465);
+    let waitkey = |con: &mut Connection, key: &str, timeout: f64| -> Option<String> {
+        redis::cmd("WAITKEY")
+            .arg(key)
+            .arg(timeout)
+            .query(con)
+            .unwrap()
+    };
+
+    // a string already there is replied at once
+    let _: () = con.set("ready", "value").unwrap();
+    assert_eq!(waitkey(&mut con, "ready", 1.0), Some("value".to_string()));
+
+    let start = std::time::Instant::now();
+    assert_eq!(waitkey(&mut con, "absent", 0.2), None);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    // a blocked client is woken up by a write from another connection
+    let blocked = std::thread::spawn(move || {
+        let client = redis::Client::open("redis://127.0.0.1:3465/").unwrap();
+        let mut con = client.get_connection().unwrap();
+        let start = std::time::Instant::now();
+        (waitkey(&mut con, "later", 0.0), start.elapsed())
+    });
+    sleep(Duration::from_millis(200));
+    let _: () = con.set("later", "written").unwrap();
+    let (value, elapsed) = blocked.join().unwrap();
+    assert_eq!(value, Some("written".to_string()));
+    assert!(elapsed >= Duration::from_millis(200));
+
+    let _: () = con.rpush("list", "a").unwrap();
+    let err = redis::cmd("WAITKEY")
+        .arg("list")
+        .arg(1)
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn set_nx_ex_lock() {
+    use std::sync::{Arc, Barrier};
+
+    let (server, mut con) = get_redis_client_connection(3466);
+    let acquire = |con: &mut Connection| -> Option<String> {
+        redis::cmd("SET")
+            .arg("lock")
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(30)
+            .query(con)
+            .unwrap()
+    };
+
+    // two callers racing for the lock, round after round: exactly one of them gets it, and the
+    // lock is released between the rounds
+    const ROUNDS: usize = 50;
+    let barrier = Arc::new(Barrier::new(3));
+    let racers = (0..2)
+        .map(|_| {
+            let barrier = barrier.clone();
+            std::thread::spawn(move || {
+                let client = redis::Client::open("redis://127.0.0.1:3466/").unwrap();
+                let mut con = client.get_connection().unwrap();
+                (0..ROUNDS)
+                    .map(|_| {
+                        barrier.wait();
+                        let acquired = acquire(&mut con);
+                        barrier.wait();
+                        acquired
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect::<Vec<_>>();
+    for _ in 0..ROUNDS {
+        barrier.wait();
+        barrier.wait();
+        let ttl: i64 = con.ttl("lock").unwrap();
+        assert!(ttl > 0 && ttl <= 30);
+        let _: () = con.del("lock").unwrap();
+    }
+    let results: Vec<_> = racers.into_iter().map(|r| r.join().unwrap()).collect();
+    for round in 0..ROUNDS {
+        let mut replies: Vec<_> = results.iter().map(|r| r[round].clone()).collect();
+        replies.sort();
+        assert_eq!(replies, vec![None, Some("OK".to_string())]);
+    }
+
+    // the winner's lock has the ttl of the option, and the loser gets nil
+    assert_eq!(acquire(&mut con), Some("OK".to_string()));
+    let ttl: i64 = con.ttl("lock").unwrap();
+    assert!(ttl > 0 && ttl <= 30);
+    assert_eq!(acquire(&mut con), None);
+    // SETNX takes the same path
+    let set: u64 = con.set_nx("lock", 2).unwrap();
+    assert_eq!(set, 0);
+    let _: () = con.del("lock").unwrap();
+    let set: u64 = con.set_nx("lock", 2).unwrap();
+    assert_eq!(set, 1);
+    assert_eq!(con.get::<_, i64>("lock").unwrap(), 2);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn logged_command_errors() {
+    use std::sync::Mutex;
+
+    // the records logged by every server of the tests, once the logger is installed
+    static RECORDS: Mutex<Vec<(log::Level, String)>> = Mutex::new(Vec::new());
+    struct Capture;
+    impl log::Log for Capture {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+        fn log(&self, record: &log::Record) {
+            let line = record.args().to_string();
+            RECORDS.lock().unwrap().push((record.level(), line));
+        }
+        fn flush(&self) {}
+    }
+    static CAPTURE: Capture = Capture;
+    if log::set_logger(&CAPTURE).is_ok() {
+        log::set_max_level(log::LevelFilter::Trace);
+    }
+    let logged = |text: &str| -> Option<log::Level> {
+        let records = RECORDS.lock().unwrap();
+        records
+            .iter()
+            .find(|(_, line)| line.contains(text))
+            .map(|(level, _)| *level)
+    };
+
+    let (server, mut con) = get_redis_client_connection(3468);
+    assert_eq!(
+        logged("server started on port 3468"),
+        Some(log::Level::Info)
+    );
+
+    let err = redis::cmd("GETRANGE")
+        .arg("key")
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("ERR"));
+    assert_eq!(
+        logged("getrange: ERR wrong number of arguments for 'getrange' command"),
+        Some(log::Level::Warn)
+    );
+
+    let _: () = con.set("text", "abc").unwrap();
+    assert!(con.incr::<_, _, i64>("text", 1).is_err());
+    assert_eq!(
+        logged("incrby: ERR value is not an integer or out of range"),
+        Some(log::Level::Debug)
+    );
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn default_ttl() {
+    let config = ServerConfig {
+        default_ttl_secs: 100,
+        ..ServerConfig::new(3432)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+
+    let _: () = con.set("plain", "value").unwrap();
+    let ttl: i64 = con.ttl("plain").unwrap();
+    assert!(ttl > 0 && ttl <= 100);
+
+    // an explicit expiry wins over the default one
+    let _: () = con.set_ex("explicit", "value", 1000).unwrap();
+    assert!(con.ttl::<_, i64>("explicit").unwrap() > 100);
+
+    let _: () = redis::cmd("SET")
+        .arg("kept")
+        .arg("value")
+        .arg("EX")
+        .arg(500)
+        .query(&mut con)
+        .unwrap();
+    let _: () = redis::cmd("SET")
+        .arg("kept")
+        .arg("other")
+        .arg("KEEPTTL")
+        .query(&mut con)
+        .unwrap();
+    assert!(con.ttl::<_, i64>("kept").unwrap() > 100);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    // without a default, a plain SET has no expiry
+    let (server, mut con) = get_redis_client_connection(3433);
+    let _: () = con.set("plain", "value").unwrap();
+    assert_eq!(con.ttl::<_, i64>("plain").unwrap(), -1);
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn integer_replies() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    use crate::protocol::Resp;
+
+    let (server, con) = get_redis_client_connection(3434);
+    drop(con);
+
+    let requests: &[&[&[u8]]] = &[
+        &[b"SET", b"counter", b"41"],
+        &[b"INCR", b"counter"],
+        &[b"EXISTS", b"counter"],
+        &[b"DEL", b"counter"],
+        &[b"EXISTS", b"counter"],
+        &[b"SET", b"string", b"hello"],
+        &[b"STRLEN", b"string"],
+        &[b"STRLEN", b"missing"],
+    ];
+    let mut stream = TcpStream::connect("127.0.0.1:3434").unwrap();
+    for request in requests {
+        let request = Resp::Array(request.iter().map(|arg| Resp::BulkString(arg)).collect());
+        let mut bytes = vec![];
+        request.serialize(&mut bytes);
+        stream.write_all(&bytes).unwrap();
+    }
+
+    // counts and counters are integers, not bulk strings holding digits
+    let expected = b"+OK\r\n:42\r\n:1\r\n:1\r\n:0\r\n+OK\r\n:5\r\n:0\r\n";
+    let mut reply = vec![0; expected.len()];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&reply),
+        String::from_utf8_lossy(expected)
+    );
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn command_getkeys() {
+    let (server, mut con) = get_redis_client_connection(3435);
+
+    let keys: Vec<String> = redis::cmd("COMMAND")
+        .arg("GETKEYS")
+        .arg("MSET")
+        .arg("first")
+        .arg("1")
+        .arg("second")
+        .arg("2")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(keys, vec!["first", "second"]);
+
+    let err = redis::cmd("COMMAND")
+        .arg("GETKEYS")
+        .arg("PING")
+        .query::<Vec<String>>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("ERR"));
+    assert_eq!(err.detail(), Some("The command has no key arguments"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn command_info() {
+    // name, arity, flags, first key, last key and step
+    type Info = (String, i64, Vec<String>, i64, i64, i64);
+    let (server, mut con) = get_redis_client_connection(3460);
+
+    let info: Vec<Option<Info>> = redis::cmd("COMMAND")
+        .arg("INFO")
+        .arg("set")
+        .arg("nope")
+        .arg("GET")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(
+        info,
+        vec![
+            Some((
+                "set".to_string(),
+                -3,
+                vec!["write".to_string(), "denyoom".to_string()],
+                1,
+                1,
+                1
+            )),
+            None,
+            Some(("get".to_string(), 2, vec!["readonly".to_string()], 1, 1, 1)),
+        ]
+    );
+
+    // every command without names
+    let all: Vec<redis::Value> = redis::cmd("COMMAND").arg("INFO").query(&mut con).unwrap();
+    assert!(all.len() > 50);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn sort() {
+    let (server, mut con) = get_redis_client_connection(3436);
+    let sort = |con: &mut Connection, key: &str, options: &[&str]| -> RedisResult<Vec<String>> {
+        redis::cmd("SORT").arg(key).arg(options).query(con)
+    };
+
+    let _: () = con
+        .rpush("numbers", &["3", "10", "1", "2.5", "-4"])
+        .unwrap();
+    assert_eq!(
+        sort(&mut con, "numbers", &[]).unwrap(),
+        vec!["-4", "1", "2.5", "3", "10"]
+    );
+    assert_eq!(
+        sort(&mut con, "numbers", &["DESC", "LIMIT", "1", "2"]).unwrap(),
+        vec!["3", "2.5"]
+    );
+    // strings compare byte by byte
+    assert_eq!(
+        sort(&mut con, "numbers", &["ALPHA", "LIMIT", "0", "3"]).unwrap(),
+        vec!["-4", "1", "10"]
+    );
+    assert_eq!(
+        sort(&mut con, "numbers", &["LIMIT", "4", "-1"]).unwrap(),
+        vec!["10"]
+    );
+
+    let _: () = con.sadd("words", &["pear", "apple", "fig"]).unwrap();
+    assert_eq!(
+        sort(&mut con, "words", &["ALPHA", "DESC"]).unwrap(),
+        vec!["pear", "fig", "apple"]
+    );
+    let err = sort(&mut con, "words", &[]).unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("One or more scores can't be converted into double")
+    );
+
+    assert!(sort(&mut con, "missing", &[]).unwrap().is_empty());
+    let _: () = con.set("string", "value").unwrap();
+    assert!(sort(&mut con, "string", &[]).is_err());
+    assert!(sort(&mut con, "numbers", &["BY", "weight_*"]).is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn subscribed_context() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, mut con) = get_redis_client_connection(3437);
+    let mut stream = TcpStream::connect("127.0.0.1:3437").unwrap();
+    let read_exact = |stream: &mut TcpStream, expected: &[u8]| {
+        let mut reply = vec![0; expected.len()];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&reply),
+            String::from_utf8_lossy(expected)
+        );
+    };
+
+    stream
+        .write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$4\r\nnews\r\n")
+        .unwrap();
+    read_exact(
+        &mut stream,
+        b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n",
+    );
+
+    // only the pub/sub commands are accepted once subscribed
+    stream
+        .write_all(b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n")
+        .unwrap();
+    read_exact(
+        &mut stream,
+        b"-ERR Can't execute 'get': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context\r\n",
+    );
+
+    let received: i64 = con.publish("news", "hello").unwrap();
+    assert_eq!(received, 1);
+    read_exact(
+        &mut stream,
+        b"*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$5\r\nhello\r\n",
+    );
+
+    stream
+        .write_all(b"*2\r\n$11\r\nUNSUBSCRIBE\r\n$4\r\nnews\r\n")
+        .unwrap();
+    read_exact(
+        &mut stream,
+        b"*3\r\n$11\r\nunsubscribe\r\n$4\r\nnews\r\n:0\r\n",
+    );
+    stream
+        .write_all(b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n")
+        .unwrap();
+    read_exact(&mut stream, b"$-1\r\n");
+    assert_eq!(con.publish::<_, _, i64>("news", "hello").unwrap(), 0);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn resp3_push_messages() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, mut con) = get_redis_client_connection(3457);
+    let mut stream = TcpStream::connect("127.0.0.1:3457").unwrap();
+    let read_exact = |stream: &mut TcpStream, expected: &[u8]| {
+        let mut reply = vec![0; expected.len()];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&reply),
+            String::from_utf8_lossy(expected)
+        );
+    };
+
+    // the reply of HELLO is skipped up to the one of the PING sent after it
+    stream
+        .write_all(b"*2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n*1\r\n$4\r\nPING\r\n")
+        .unwrap();
+    let mut reply = vec![];
+    while !reply.ends_with(b"+PONG\r\n") {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).unwrap();
+        reply.push(byte[0]);
+    }
+
+    stream
+        .write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$4\r\nnews\r\n")
+        .unwrap();
+    read_exact(
+        &mut stream,
+        b">3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n",
+    );
+    assert_eq!(con.publish::<_, _, i64>("news", "hello"), Ok(1));
+    read_exact(
+        &mut stream,
+        b">3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$5\r\nhello\r\n",
+    );
+
+    // the replies of a RESP3 subscriber are told from the messages, it runs any command
+    stream
+        .write_all(b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n")
+        .unwrap();
+    read_exact(&mut stream, b"_\r\n");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn pubsub_introspection() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, mut con) = get_redis_client_connection(3438);
+    let subscribe = |command: &[u8], expected: &[u8]| {
+        let mut stream = TcpStream::connect("127.0.0.1:3438").unwrap();
+        stream.write_all(command).unwrap();
+        let mut reply = vec![0; expected.len()];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(reply, expected);
+        stream
+    };
+
+    let subscribe_news = b"*2\r\n$9\r\nSUBSCRIBE\r\n$4\r\nnews\r\n";
+    let subscribed = b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n";
+    let first = subscribe(subscribe_news, subscribed);
+    let second = subscribe(subscribe_news, subscribed);
+    let _pattern = subscribe(
+        b"*2\r\n$10\r\nPSUBSCRIBE\r\n$2\r\nn*\r\n",
+        b"*3\r\n$10\r\npsubscribe\r\n$2\r\nn*\r\n:1\r\n",
+    );
+
+    let numsub: (String, i64, String, i64) = redis::cmd("PUBSUB")
+        .arg("NUMSUB")
+        .arg("news")
+        .arg("sports")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(numsub, ("news".to_string(), 2, "sports".to_string(), 0));
+
+    let numpat: i64 = redis::cmd("PUBSUB").arg("NUMPAT").query(&mut con).unwrap();
+    assert_eq!(numpat, 1);
+
+    let channels: Vec<String> = redis::cmd("PUBSUB")
+        .arg("CHANNELS")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(channels, vec!["news".to_string()]);
+    let channels: Vec<String> = redis::cmd("PUBSUB")
+        .arg("CHANNELS")
+        .arg("s*")
+        .query(&mut con)
+        .unwrap();
+    assert!(channels.is_empty());
+
+    // a channel goes away with its last subscriber
+    drop(first);
+    drop(second);
+    let mut channels: Vec<String> = vec!["news".to_string()];
+    for _ in 0..100 {
+        channels = redis::cmd("PUBSUB")
+            .arg("CHANNELS")
+            .query(&mut con)
+            .unwrap();
+        if channels.is_empty() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert!(channels.is_empty());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn compare_and_set() {
+    const THREADS: usize = 16;
+
+    let (server, mut con) = get_redis_client_connection(3439);
+    let cas = |con: &mut Connection, expected: &str, new: &str| -> i64 {
+        redis::cmd("CAS")
+            .arg("lock")
+            .arg(expected)
+            .arg(new)
+            .query(con)
+            .unwrap()
+    };
+
+    // a missing key holds nothing to compare to
+    assert_eq!(cas(&mut con, "free", "owner"), 0);
+    assert_eq!(con.exists::<_, i64>("lock").unwrap(), 0);
+
+    let _: () = con.set("lock", "free").unwrap();
+    let handles: Vec<_> = (0..THREADS)
+        .map(|i| {
+            std::thread::spawn(move || {
+                let client = redis::Client::open("redis://127.0.0.1:3439/").unwrap();
+                let mut con = client.get_connection().unwrap();
+                cas(&mut con, "free", &format!("owner{}", i))
+            })
+        })
+        .collect();
+    let swapped: Vec<i64> = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect();
+
+    // only one of the racing clients takes the lock, and the lock names it
+    assert_eq!(swapped.iter().sum::<i64>(), 1);
+    let winner = swapped.iter().position(|swapped| *swapped == 1).unwrap();
+    let owner: String = con.get("lock").unwrap();
+    assert_eq!(owner, format!("owner{}", winner));
+
+    let _: () = con.rpush("list", "free").unwrap();
+    let err = redis::cmd("CAS")
+        .arg("list")
+        .arg("free")
+        .arg("owner")
+        .query::<i64>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn debug_object() {
+    let (server, mut con) = get_redis_client_connection(3440);
+    let debug_object = |con: &mut Connection, key: &str| {
+        redis::cmd("DEBUG")
+            .arg("OBJECT")
+            .arg(key)
+            .query::<String>(con)
+    };
+    let field = |line: &str, name: &str| {
+        line.split(' ')
+            .find_map(|field| field.strip_prefix(&format!("{}:", name)))
+            .map(|value| value.to_string())
+    };
+
+    let _: () = con.set("string", "value").unwrap();
+    let line = debug_object(&mut con, "string").unwrap();
+    assert_eq!(field(&line, "refcount").as_deref(), Some("1"));
+    assert_eq!(field(&line, "encoding").as_deref(), Some("embstr"));
+    // the length prefix and the value
+    assert_eq!(field(&line, "serializedlength").as_deref(), Some("9"));
+    assert_eq!(field(&line, "ql_nodes"), None);
+
+    let _: () = con.set("integer", "12").unwrap();
+    let line = debug_object(&mut con, "integer").unwrap();
+    assert_eq!(field(&line, "encoding").as_deref(), Some("int"));
+    assert_eq!(field(&line, "refcount").as_deref(), Some("2147483647"));
+
+    let _: u64 = con.rpush("list", &["a", "b", "c"]).unwrap();
+    let line = debug_object(&mut con, "list").unwrap();
+    assert_eq!(field(&line, "encoding").as_deref(), Some("listpack"));
+    assert_eq!(field(&line, "serializedlength").as_deref(), Some("19"));
+    assert_eq!(field(&line, "ql_nodes"), None);
+
+    let _: u64 = con.rpush("long", "x".repeat(9000)).unwrap();
+    let line = debug_object(&mut con, "long").unwrap();
+    assert_eq!(field(&line, "encoding").as_deref(), Some("quicklist"));
+    assert_eq!(field(&line, "ql_nodes").as_deref(), Some("1"));
+    assert_eq!(field(&line, "ql_avg_node").as_deref(), Some("1.00"));
+
+    // nodes of 8 KB by default
+    let elements = vec!["x".repeat(1000); 100];
+    let _: u64 = con.rpush("nodes", elements).unwrap();
+    let line = debug_object(&mut con, "nodes").unwrap();
+    assert_eq!(field(&line, "encoding").as_deref(), Some("quicklist"));
+    let nodes: u64 = field(&line, "ql_nodes").unwrap().parse().unwrap();
+    assert_eq!(nodes, 13);
+    assert_eq!(field(&line, "ql_avg_node").as_deref(), Some("7.69"));
+    let _: () = redis::cmd("CONFIG")
+        .arg(&["SET", "list-max-listpack-size", "10"])
+        .query(&mut con)
+        .unwrap();
+    let line = debug_object(&mut con, "nodes").unwrap();
+    assert_eq!(field(&line, "ql_nodes").as_deref(), Some("10"));
+
+    let err = debug_object(&mut con, "missing").unwrap_err();
+    // the client takes the first word of the error as its code
+    assert_eq!(err.detail(), Some("no such key"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn debug_no_op_subcommands() {
+    let (server, mut con) = get_redis_client_connection(3458);
+
+    let reply: RedisResult<String> = redis::cmd("DEBUG")
+        .arg("quicklist-packed-threshold")
+        .arg("100")
+        .query(&mut con);
+    assert_eq!(reply.as_deref(), Ok("OK"));
+    let reply: RedisResult<String> = redis::cmd("DEBUG").arg("JMAP").query(&mut con);
+    assert_eq!(reply.as_deref(), Ok("OK"));
+
+    let err = redis::cmd("DEBUG")
+        .arg("SEGFAULT")
+        .query::<String>(&mut con)
+        .unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("Unknown subcommand or wrong number of arguments for 'SEGFAULT'. Try DEBUG HELP.")
+    );
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn keyspace_stripes() {
+    use crate::storage::Storage;
+
+    let port = 3459;
+    let mut storage = InMemoryStorage::default();
+    storage.write(b"preloaded", b"value");
+    let config = ServerConfig {
+        port,
+        keyspace_stripes: 4,
+        ..ServerConfig::default()
+    };
+    let server = Server::new_with_config(storage, config);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let client = redis::Client::open(format!("redis://127.0.0.1:{}/", port)).unwrap();
+    let mut con = client.get_connection().unwrap();
+
+    let stripes: Vec<String> = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("keyspace-stripes")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(stripes, vec!["keyspace-stripes", "4"]);
+    // the entries of the storage are spread across the stripes
+    let value: String = con.get("preloaded").unwrap();
+    assert_eq!(value, "value");
+
+    // single key and multi-key commands on keys of every stripe, from many connections
+    let handles: Vec<_> = (0..8)
+        .map(|client_index| {
+            let mut con = client.get_connection().unwrap();
+            std::thread::spawn(move || {
+                for _ in 0..100 {
+                    let _: i64 = con.incr(format!("counter:{}", client_index), 1).unwrap();
+                    let _: () = con
+                        .set_multiple(&[
+                            (format!("a:{}", client_index), "x"),
+                            (format!("b:{}", client_index), "y"),
+                        ])
+                        .unwrap();
+                    let values: Vec<String> = con
+                        .get(vec![format!("b:{}", client_index), "preloaded".to_string()])
+                        .unwrap();
+                    assert_eq!(values, vec!["y", "value"]);
+                }
+            })
+        })
+        .collect();
+    handles
+        .into_iter()
+        .for_each(|handle| handle.join().unwrap());
+
+    for client_index in 0..8 {
+        let counter: i64 = con.get(format!("counter:{}", client_index)).unwrap();
+        assert_eq!(counter, 100);
+    }
+    let size: i64 = redis::cmd("DBSIZE").query(&mut con).unwrap();
+    assert_eq!(size, 25);
+    let keys: Vec<String> = con.keys("*").unwrap();
+    assert_eq!(keys.len(), 25);
+
+    // a client blocked on keys of several stripes is woken by a push to any of them
+    let mut blocked = client.get_connection().unwrap();
+    let handle = std::thread::spawn(move || {
+        let popped: Vec<String> = redis::cmd("BLPOP")
+            .arg("list:1")
+            .arg("list:2")
+            .arg("list:3")
+            .arg(5)
+            .query(&mut blocked)
+            .unwrap();
+        popped
+    });
+    sleep(Duration::from_millis(100));
+    let _: () = con.rpush("list:3", "element").unwrap();
+    assert_eq!(handle.join().unwrap(), vec!["list:3", "element"]);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn object_encoding() {
+    let (server, mut con) = get_redis_client_connection(3450);
+    let object = |con: &mut Connection, subcommand: &str, key: &str| {
+        redis::cmd("OBJECT")
+            .arg(subcommand)
+            .arg(key)
+            .query::<redis::Value>(con)
+    };
+
+    let _: () = con.set("key", "12345").unwrap();
+    assert_eq!(
+        object(&mut con, "ENCODING", "key"),
+        Ok(redis::Value::Data(b"int".to_vec()))
+    );
+
+    let _: () = con.append("key", "x").unwrap();
+    assert_eq!(
+        object(&mut con, "ENCODING", "key"),
+        Ok(redis::Value::Data(b"raw".to_vec()))
+    );
+    // an appended integer is no longer shared
+    let _: () = con.set("shared", "12").unwrap();
+    assert_eq!(
+        object(&mut con, "REFCOUNT", "shared"),
+        Ok(redis::Value::Int(2147483647))
+    );
+    let _: () = con.append("shared", "3").unwrap();
+    assert_eq!(
+        object(&mut con, "REFCOUNT", "shared"),
+        Ok(redis::Value::Int(1))
+    );
+
+    let _: () = con.set("key", "value").unwrap();
+    assert_eq!(
+        object(&mut con, "ENCODING", "key"),
+        Ok(redis::Value::Data(b"embstr".to_vec()))
+    );
+    let _: () = con.sadd("set", "a").unwrap();
+    assert_eq!(
+        object(&mut con, "ENCODING", "set"),
+        Ok(redis::Value::Data(b"listpack".to_vec()))
+    );
+
+    let err = object(&mut con, "ENCODING", "missing").unwrap_err();
+    assert_eq!(err.detail(), Some("no such key"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn hash_encoding() {
+    let (server, mut con) = get_redis_client_connection(3461);
+    let encoding = |con: &mut Connection, key: &str| -> String {
+        redis::cmd("OBJECT")
+            .arg("ENCODING")
+            .arg(key)
+            .query(con)
+            .unwrap()
+    };
+
+    let _: () = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("hash-max-listpack-entries")
+        .arg("2")
+        .query(&mut con)
+        .unwrap();
+    let entries: Vec<String> = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("hash-max-listpack-entries")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(entries, vec!["hash-max-listpack-entries", "2"]);
+
+    let _: () = con.hset("hash", "a", "1").unwrap();
+    let _: () = con.hset("hash", "b", "2").unwrap();
+    assert_eq!(encoding(&mut con, "hash"), "listpack");
+    let _: () = con.hset("hash", "c", "3").unwrap();
+    assert_eq!(encoding(&mut con, "hash"), "hashtable");
+
+    // a long value converts the hash too
+    let _: () = con.hset("long", "field", "x".repeat(65)).unwrap();
+    assert_eq!(encoding(&mut con, "long"), "hashtable");
+
+    let err = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("hash-max-listpack-value")
+        .arg("many")
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some(
+            "CONFIG SET failed (possibly related to argument 'hash-max-listpack-value') - \
+             argument couldn't be parsed into an integer"
+        )
+    );
+    let err = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("port")
+        .arg("1234")
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some(
+            "CONFIG SET failed (possibly related to argument 'port') - can't set immutable config"
+        )
+    );
+    let err = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("nope")
+        .arg("1")
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("Unknown option or number of arguments for CONFIG SET - 'nope'")
+    );
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn role() {
+    let (server, mut con) = get_redis_client_connection(3441);
+
+    let role: (String, i64, Vec<Vec<String>>) = redis::cmd("ROLE").query(&mut con).unwrap();
+    assert_eq!(role, ("master".to_string(), 0, vec![]));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn client_tracking() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, mut con) = get_redis_client_connection(3442);
+    let mut tracking = TcpStream::connect("127.0.0.1:3442").unwrap();
+    let read_exact = |stream: &mut TcpStream, expected: &[u8]| {
+        let mut reply = vec![0; expected.len()];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&reply),
+            String::from_utf8_lossy(expected)
+        );
+    };
+    let invalidated = |keys: &[u8]| {
+        let mut message = b"*3\r\n$7\r\nmessage\r\n$20\r\n__redis__:invalidate\r\n".to_vec();
+        message.extend_from_slice(keys);
+        message
+    };
+
+    let _: () = con.set("key", "value").unwrap();
+    tracking
+        .write_all(b"*3\r\n$6\r\nCLIENT\r\n$8\r\nTRACKING\r\n$2\r\nON\r\n")
+        .unwrap();
+    read_exact(&mut tracking, b"+OK\r\n");
+    tracking
+        .write_all(b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n")
+        .unwrap();
+    read_exact(&mut tracking, b"$5\r\nvalue\r\n");
+
+    // a write of another connection invalidates the key, once
+    let _: () = con.set("key", "other").unwrap();
+    let _: () = con.set("key", "again").unwrap();
+    read_exact(&mut tracking, &invalidated(b"*1\r\n$3\r\nkey\r\n"));
+
+    // so does an expiration, here when the expired key is accessed
+    let _: () = redis::cmd("DEBUG")
+        .arg("SET-ACTIVE-EXPIRE")
+        .arg("0")
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.pset_ex("expiring", "value", 50).unwrap();
+    tracking
+        .write_all(b"*2\r\n$3\r\nGET\r\n$8\r\nexpiring\r\n")
+        .unwrap();
+    read_exact(&mut tracking, b"$5\r\nvalue\r\n");
+    sleep(Duration::from_millis(100));
+    tracking
+        .write_all(b"*2\r\n$3\r\nGET\r\n$8\r\nexpiring\r\n")
+        .unwrap();
+    read_exact(&mut tracking, b"$-1\r\n");
+    read_exact(&mut tracking, &invalidated(b"*1\r\n$8\r\nexpiring\r\n"));
+
+    // and a flush, of all the keys at once
+    tracking
+        .write_all(b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n")
+        .unwrap();
+    read_exact(&mut tracking, b"$5\r\nagain\r\n");
+    let _: () = redis::cmd("FLUSHDB").query(&mut con).unwrap();
+    read_exact(&mut tracking, &invalidated(b"$-1\r\n"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn keyspace_notifications() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let config = ServerConfig {
+        notify_keyspace_events: "Eg".to_string(),
+        ..ServerConfig::new(3443)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    let mut subscriber = TcpStream::connect("127.0.0.1:3443").unwrap();
+    let read_exact = |stream: &mut TcpStream, expected: &[u8]| {
+        let mut reply = vec![0; expected.len()];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&reply),
+            String::from_utf8_lossy(expected)
+        );
+    };
+    let event = |event: &str, key: &str| {
+        let channel = format!("__keyevent@0__:{}", event);
+        format!(
+            "*4\r\n$8\r\npmessage\r\n$16\r\n__keyevent@0__:*\r\n${}\r\n{}\r\n${}\r\n{}\r\n",
+            channel.len(),
+            channel,
+            key.len(),
+            key
+        )
+        .into_bytes()
+    };
+
+    subscriber
+        .write_all(b"*2\r\n$10\r\nPSUBSCRIBE\r\n$16\r\n__keyevent@0__:*\r\n")
+        .unwrap();
+    read_exact(
+        &mut subscriber,
+        b"*3\r\n$10\r\npsubscribe\r\n$16\r\n__keyevent@0__:*\r\n:1\r\n",
+    );
+
+    // the string events are not enabled, so the SET is not notified
+    let _: () = con.set("key", "value").unwrap();
+    let value: String = redis::cmd("GETDEL").arg("key").query(&mut con).unwrap();
+    assert_eq!(value, "value");
+    read_exact(&mut subscriber, &event("del", "key"));
+
+    // a key without an expiry is not persisted
+    let _: () = con.set("persistent", "value").unwrap();
+    let value: String = redis::cmd("GETEX")
+        .arg("persistent")
+        .arg("PERSIST")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(value, "value");
+
+    let _: () = con.set_ex("volatile", "value", 100).unwrap();
+    read_exact(&mut subscriber, &event("expire", "volatile"));
+    let value: String = redis::cmd("GETEX")
+        .arg("volatile")
+        .arg("PERSIST")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(value, "value");
+    read_exact(&mut subscriber, &event("persist", "volatile"));
+    assert_eq!(con.ttl::<_, i64>("volatile").unwrap(), -1);
+
+    let _: () = redis::cmd("GETEX")
+        .arg("volatile")
+        .arg("EX")
+        .arg(100)
+        .query(&mut con)
+        .unwrap();
+    read_exact(&mut subscriber, &event("expire", "volatile"));
+    assert!(con.ttl::<_, i64>("volatile").unwrap() > 0);
+
+    // nothing happens to a missing key
+    let value: Option<String> = redis::cmd("GETDEL").arg("key").query(&mut con).unwrap();
+    assert_eq!(value, None);
+    let _: () = con.del("volatile").unwrap();
+    read_exact(&mut subscriber, &event("del", "volatile"));
+
+    let _: () = con.rpush("list", "a").unwrap();
+    let err = redis::cmd("GETDEL")
+        .arg("list")
+        .query::<String>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn hello_auth() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let config = ServerConfig {
+        requirepass: Some("secret".to_string()),
+        ..ServerConfig::new(3444)
+    };
+    let server = Server::new_with_config(InMemoryStorage::default(), config);
+    assert_eq!(server.start(), Some(ServerState::Started));
+
+    let mut stream = TcpStream::connect("127.0.0.1:3444").unwrap();
+    let read_exact = |stream: &mut TcpStream, expected: &[u8]| {
+        let mut reply = vec![0; expected.len()];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&reply),
+            String::from_utf8_lossy(expected)
+        );
+    };
+    let get = b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n";
+
+    stream.write_all(get).unwrap();
+    read_exact(&mut stream, b"-NOAUTH Authentication required.\r\n");
+
+    // a failed authentication leaves the connection unauthenticated
+    stream
+        .write_all(
+            b"*5\r\n$5\r\nHELLO\r\n$1\r\n3\r\n$4\r\nAUTH\r\n$7\r\ndefault\r\n$5\r\nwrong\r\n",
+        )
+        .unwrap();
+    read_exact(
+        &mut stream,
+        b"-WRONGPASS invalid username-password pair or user is disabled.\r\n",
+    );
+    stream.write_all(get).unwrap();
+    read_exact(&mut stream, b"-NOAUTH Authentication required.\r\n");
+
+    stream
+        .write_all(b"*7\r\n$5\r\nHELLO\r\n$1\r\n3\r\n$4\r\nAUTH\r\n$7\r\ndefault\r\n$6\r\nsecret\r\n$7\r\nSETNAME\r\n$3\r\napp\r\n")
+        .unwrap();
+    let version = env!("CARGO_PKG_VERSION");
+    let hello = format!(
+        "%7\r\n$6\r\nserver\r\n$9\r\nredisless\r\n$7\r\nversion\r\n${}\r\n{}\r\n\
+         $5\r\nproto\r\n:3\r\n$2\r\nid\r\n",
+        version.len(),
+        version
+    );
+    read_exact(&mut stream, hello.as_bytes());
+    // the id of the connection, then the end of the map
+    let mut id = vec![];
+    let mut byte = [0];
+    while !id.ends_with(b"\r\n") {
+        stream.read_exact(&mut byte).unwrap();
+        id.push(byte[0]);
+    }
+    assert!(id.starts_with(b":"));
+    read_exact(
+        &mut stream,
+        b"$4\r\nmode\r\n$10\r\nstandalone\r\n$4\r\nrole\r\n$6\r\nmaster\r\n$7\r\nmodules\r\n*0\r\n",
+    );
+
+    // a nil in RESP3
+    stream.write_all(get).unwrap();
+    read_exact(&mut stream, b"_\r\n");
+
+    // AUTH alone, as sent by a RESP2 client
+    let client = redis::Client::open("redis://:secret@127.0.0.1:3444/").unwrap();
+    let mut con = client.get_connection().unwrap();
+    let _: () = con.set("key", "value").unwrap();
+    let client = redis::Client::open("redis://:wrong@127.0.0.1:3444/").unwrap();
+    assert!(client.get_connection().is_err());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn acl_users() {
+    let server = Server::new(InMemoryStorage::default(), 3445);
+    assert_eq!(server.start(), Some(ServerState::Started));
+
+    let redis_client = redis::Client::open("redis://127.0.0.1:3445/").unwrap();
+    let mut con = redis_client.get_connection().unwrap();
+
+    let whoami: String = redis::cmd("ACL").arg("WHOAMI").query(&mut con).unwrap();
+    assert_eq!(whoami, "default");
+    let _: () = redis::cmd("ACL")
+        .arg(&["SETUSER", "alice", "on", ">secret", "+@all", "-flushall"])
+        .query(&mut con)
+        .unwrap();
+
+    let users: Vec<String> = redis::cmd("ACL").arg("LIST").query(&mut con).unwrap();
+    assert_eq!(
+        users,
+        vec![
+            "user alice on +@all -flushall",
+            "user default on nopass +@all"
+        ]
+    );
+    let user: Vec<redis::Value> = redis::cmd("ACL")
+        .arg(&["GETUSER", "alice"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(user.len(), 4);
+    let user: Option<Vec<String>> = redis::cmd("ACL")
+        .arg(&["GETUSER", "bob"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(user, None);
+
+    let err = redis::cmd("AUTH")
+        .arg(&["alice", "wrong"])
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("WRONGPASS"));
+    let _: () = redis::cmd("AUTH")
+        .arg(&["alice", "secret"])
+        .query(&mut con)
+        .unwrap();
+    let whoami: String = redis::cmd("ACL").arg("WHOAMI").query(&mut con).unwrap();
+    assert_eq!(whoami, "alice");
+
+    let _: () = con.set("key", "value").unwrap();
+    let err = redis::cmd("FLUSHALL").query::<()>(&mut con).unwrap_err();
+    assert_eq!(err.code(), Some("NOPERM"));
+    assert_eq!(
+        err.detail(),
+        Some("User alice has no permissions to run the 'flushall' command")
+    );
+    let exists: bool = con.exists("key").unwrap();
+    assert!(exists);
+
+    // rules of a later SETUSER apply to the authenticated clients
+    let _: () = redis::cmd("ACL")
+        .arg(&["SETUSER", "alice", "-@all", "+get"])
+        .query(&mut con)
+        .unwrap();
+    let value: String = con.get("key").unwrap();
+    assert_eq!(value, "value");
+    let err = con.set::<_, _, ()>("key", "other").unwrap_err();
+    assert_eq!(err.code(), Some("NOPERM"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn config_get_and_help() {
+    let (server, mut con) = get_redis_client_connection(3446);
+
+    let help: Vec<String> = redis::cmd("OBJECT").arg("HELP").query(&mut con).unwrap();
+    assert!(!help.is_empty());
+    assert!(help.contains(&"REFCOUNT <key>".to_string()));
+    let help: Vec<String> = redis::cmd("CONFIG").arg("help").query(&mut con).unwrap();
+    assert!(help.contains(&"GET <pattern>".to_string()));
+
+    let err = redis::cmd("CONFIG")
+        .arg("REWRITE")
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("Unknown subcommand or wrong number of arguments for 'REWRITE'. Try CONFIG HELP.")
+    );
+
+    let port: Vec<String> = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("PORT")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(port, vec!["port", "3446"]);
+    let parameters: Vec<String> = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("maxmemory-*")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(
+        parameters,
+        vec!["maxmemory-policy", "noeviction", "maxmemory-samples", "5"]
+    );
+    let none: Vec<String> = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("missing")
+        .query(&mut con)
+        .unwrap();
+    assert!(none.is_empty());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn eviction_samples_lru() {
+    use crate::storage::Storage;
+
+    // every key of the test takes the same estimated memory
+    let mut storage = InMemoryStorage::default();
+    storage.write(b"key1", b"value");
+    let usage = storage.used_memory();
+
+    let config = ServerConfig {
+        maxmemory: 5 * usage + usage / 2,
+        maxmemory_policy: MaxMemoryPolicy::AllKeysLru,
+        // all the keys are sampled, so the least recently used one is evicted
+        maxmemory_samples: 10,
+        ..ServerConfig::new(3447)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+
+    for key in ["key1", "key2", "key3", "key4", "key5"] {
+        let _: () = con.set(key, "value").unwrap();
+        sleep(Duration::from_millis(5));
+    }
+    for key in ["key1", "key3", "key4", "key5"] {
+        let _: String = con.get(key).unwrap();
+        sleep(Duration::from_millis(5));
+    }
+    let _: () = con.set("key6", "value").unwrap();
+
+    // the keys are evicted before the next command runs
+    let exists: bool = con.exists("key2").unwrap();
+    assert!(!exists);
+    let size: u64 = redis::cmd("DBSIZE").query(&mut con).unwrap();
+    assert_eq!(size, 5);
+    for key in ["key1", "key3", "key4", "key5", "key6"] {
+        let exists: bool = con.exists(key).unwrap();
+        assert!(exists);
+    }
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+fn sample_keys_without_replacement() {
+    use crate::server::eviction::sample_keys;
+    use crate::storage::Storage;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::collections::HashSet;
+
+    let mut storage = InMemoryStorage::default();
+    for i in 0..10 {
+        storage.write(format!("key{}", i).as_bytes(), b"value");
+    }
+    let mut rng = StdRng::seed_from_u64(42);
+
+    let first: HashSet<_> = sample_keys(&storage, &mut rng, 5, &HashSet::new())
+        .into_iter()
+        .collect();
+    assert_eq!(first.len(), 5);
+    // the keys already sampled are not drawn again
+    let second = sample_keys(&storage, &mut rng, 10, &first);
+    assert_eq!(second.len(), 5);
+    assert!(second.iter().all(|key| !first.contains(key)));
+}
+
+#[test]
+#[serial]
+fn randomkey() {
+    let (server, mut con) = get_redis_client_connection(3448);
+
+    let key: Option<String> = redis::cmd("RANDOMKEY").query(&mut con).unwrap();
+    assert_eq!(key, None);
+
+    let _: () = con.set("key", "value").unwrap();
+    let _: () = con.set("expiring", "value").unwrap();
+    let _: () = con.pexpire("expiring", 10).unwrap();
+    sleep(Duration::from_millis(20));
+    for _ in 0..5 {
+        let key: String = redis::cmd("RANDOMKEY").query(&mut con).unwrap();
+        assert_eq!(key, "key");
+    }
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn monitor() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, mut con) = get_redis_client_connection(3449);
+
+    let mut monitor = TcpStream::connect("127.0.0.1:3449").unwrap();
+    let read_line = |stream: &mut TcpStream| {
+        let mut line = vec![];
+        let mut byte = [0];
+        while !line.ends_with(b"\r\n") {
+            stream.read_exact(&mut byte).unwrap();
+            line.push(byte[0]);
+        }
+        String::from_utf8(line).unwrap()
+    };
+    monitor.write_all(b"*1\r\n$7\r\nMONITOR\r\n").unwrap();
+    assert_eq!(read_line(&mut monitor), "+OK\r\n");
+
+    // the commands of the monitor itself are not fed to it
+    monitor.write_all(b"*1\r\n$4\r\nPING\r\n").unwrap();
+    assert_eq!(read_line(&mut monitor), "+PONG\r\n");
+
+    let _: () = con.set("key", "a \"quoted\"\nvalue").unwrap();
+    let line = read_line(&mut monitor);
+    assert!(line.starts_with('+'), "{}", line);
+    assert!(line.contains(" [0 127.0.0.1:"), "{}", line);
+    assert!(
+        line.ends_with("] \"SET\" \"key\" \"a \\\"quoted\\\"\\nvalue\"\r\n"),
+        "{}",
+        line
+    );
+
+    // fails without a requirepass, but is run all the same
+    let _ = redis::cmd("AUTH").arg("password").query::<()>(&mut con);
+    let _: () = redis::cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let _: Option<String> = con.get("key").unwrap();
+    // AUTH is not fed, it carries a password
+    assert!(read_line(&mut monitor).ends_with("] \"SELECT\" \"1\"\r\n"));
+    assert!(read_line(&mut monitor).contains(" [1 127.0.0.1:"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[cfg(unix)]
+#[test]
+fn tcp_socket_options() {
+    use crate::server::socket::{configure_tcp_stream, getsockopt};
+    use std::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (stream, _) = listener.accept().unwrap();
+
+    let config = ServerConfig {
+        tcp_keepalive_secs: 120,
+        ..ServerConfig::default()
+    };
+    assert!(config.tcp_nodelay);
+    configure_tcp_stream(&stream, &config).unwrap();
+    assert!(stream.nodelay().unwrap());
+    let keepalive = getsockopt(&stream, libc::SOL_SOCKET, libc::SO_KEEPALIVE).unwrap();
+    assert_ne!(keepalive, 0);
+    #[cfg(target_os = "linux")]
+    {
+        let idle = getsockopt(&stream, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE).unwrap();
+        assert_eq!(idle, 120);
+        let interval = getsockopt(&stream, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL).unwrap();
+        assert_eq!(interval, 40);
+    }
+
+    let config = ServerConfig {
+        tcp_nodelay: false,
+        ..ServerConfig::default()
+    };
+    configure_tcp_stream(&stream, &config).unwrap();
+    assert!(!stream.nodelay().unwrap());
+}
+
+#[test]
+#[serial]
+fn set_encoding() {
+    let (server, mut con) = get_redis_client_connection(3470);
+    let encoding = |con: &mut Connection, key: &str| -> String {
+        redis::cmd("OBJECT")
+            .arg("ENCODING")
+            .arg(key)
+            .query(con)
+            .unwrap()
+    };
+
+    let _: u64 = con.sadd("set", &[1, 2, 3]).unwrap();
+    assert_eq!(encoding(&mut con, "set"), "intset");
+    let _: u64 = con.sadd("set", "member").unwrap();
+    assert_eq!(encoding(&mut con, "set"), "listpack");
+    // the conversion is one-way
+    let _: u64 = con.srem("set", "member").unwrap();
+    assert_eq!(encoding(&mut con, "set"), "listpack");
+
+    let _: () = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("set-max-intset-entries")
+        .arg("2")
+        .query(&mut con)
+        .unwrap();
+    let _: u64 = con.sadd("integers", &[1, 2]).unwrap();
+    assert_eq!(encoding(&mut con, "integers"), "intset");
+    let _: u64 = con.sadd("integers", 3).unwrap();
+    assert_eq!(encoding(&mut con, "integers"), "listpack");
+    let _: u64 = con.sadd("long", "x".repeat(65)).unwrap();
+    assert_eq!(encoding(&mut con, "long"), "hashtable");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn move_key() {
+    let (server, mut con) = get_redis_client_connection(3473);
+    let r#move = |con: &mut Connection, key: &str, db: i64| -> RedisResult<i64> {
+        redis::cmd("MOVE").arg(key).arg(db).query(con)
+    };
+
+    let _: () = con.set_ex("volatile", "value", 100).unwrap();
+    assert_eq!(r#move(&mut con, "volatile", 1).unwrap(), 1);
+    let exists: bool = con.exists("volatile").unwrap();
+    assert!(!exists);
+    assert_eq!(r#move(&mut con, "missing", 1).unwrap(), 0);
+
+    // a key already in the destination is left as is, in both databases
+    let _: () = con.set("taken", "source").unwrap();
+    let _: () = redis::cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let value: String = con.get("volatile").unwrap();
+    assert_eq!(value, "value");
+    let ttl: i64 = con.ttl("volatile").unwrap();
+    assert!(ttl > 0 && ttl <= 100);
+    let _: () = con.set("taken", "destination").unwrap();
+    let _: () = redis::cmd("SELECT").arg(0).query(&mut con).unwrap();
+    assert_eq!(r#move(&mut con, "taken", 1).unwrap(), 0);
+    let value: String = con.get("taken").unwrap();
+    assert_eq!(value, "source");
+    let _: () = redis::cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let value: String = con.get("taken").unwrap();
+    assert_eq!(value, "destination");
+
+    let err = r#move(&mut con, "taken", 1).unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("source and destination objects are the same")
+    );
+    for db in &[16, -1] {
+        let err = r#move(&mut con, "taken", *db).unwrap_err();
+        assert_eq!(err.detail(), Some("DB index is out of range"));
+    }
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn read_only_server() {
+    let config = ServerConfig {
+        read_only: true,
+        ..ServerConfig::new(3474)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+
+    let err = con.set::<_, _, ()>("key", "value").unwrap_err();
+    assert_eq!(err.code(), Some("READONLY"));
+    assert_eq!(
+        err.detail(),
+        Some("You can't write against a read only replica.")
+    );
+    let err = con.del::<_, ()>("key").unwrap_err();
+    assert_eq!(err.code(), Some("READONLY"));
+    let value: Option<String> = con.get("key").unwrap();
+    assert_eq!(value, None);
+    let config: Vec<String> = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("read-only")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(config, vec!["read-only", "yes"]);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn slow_subscriber_disconnected() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let config = ServerConfig {
+        client_output_buffer_limit_pubsub: OutputBufferLimit {
+            hard: 256 * 1024,
+            soft: 0,
+            soft_secs: 0,
+        },
+        ..ServerConfig::new(3475)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+
+    // subscribed, then never reading its messages
+    let mut subscriber = TcpStream::connect("127.0.0.1:3475").unwrap();
+    subscriber
+        .write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$7\r\nchannel\r\n")
+        .unwrap();
+    let mut confirmation = [0; 36];
+    subscriber.read_exact(&mut confirmation).unwrap();
+    assert_eq!(
+        &confirmation[..],
+        &b"*3\r\n$9\r\nsubscribe\r\n$7\r\nchannel\r\n:1\r\n"[..]
+    );
+
+    // once the socket buffers are full the messages queue up, until the limit
+    let message = "x".repeat(16 * 1024);
+    let published = (0..8192)
+        .map(|_| con.publish::<_, _, i64>("channel", &message).unwrap())
+        .position(|received| received == 0);
+    assert!(published.is_some());
+    let numsub: (String, i64) = redis::cmd("PUBSUB")
+        .arg("NUMSUB")
+        .arg("channel")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(numsub.1, 0);
+
+    // the connection is closed once the messages written are read
+    subscriber
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut messages = vec![];
+    subscriber.read_to_end(&mut messages).unwrap();
+    assert!(messages.len() < published.unwrap() * message.len());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn subscription_confirmations() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, _) = get_redis_client_connection(3476);
+    let mut stream = TcpStream::connect("127.0.0.1:3476").unwrap();
+    let mut send = |request: &[u8], expected: &[u8]| {
+        stream.write_all(request).unwrap();
+        let mut reply = vec![0; expected.len()];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&reply),
+            String::from_utf8_lossy(expected)
+        );
+    };
+
+    // a frame per channel, with the running count of the subscriptions
+    send(
+        b"*3\r\n$9\r\nSUBSCRIBE\r\n$1\r\na\r\n$1\r\nb\r\n",
+        b"*3\r\n$9\r\nsubscribe\r\n$1\r\na\r\n:1\r\n\
+          *3\r\n$9\r\nsubscribe\r\n$1\r\nb\r\n:2\r\n",
+    );
+    // the patterns count as well, and a channel subscribed twice is confirmed twice
+    send(
+        b"*2\r\n$10\r\nPSUBSCRIBE\r\n$2\r\nc*\r\n",
+        b"*3\r\n$10\r\npsubscribe\r\n$2\r\nc*\r\n:3\r\n",
+    );
+    send(
+        b"*2\r\n$9\r\nSUBSCRIBE\r\n$1\r\na\r\n",
+        b"*3\r\n$9\r\nsubscribe\r\n$1\r\na\r\n:3\r\n",
+    );
+    send(
+        b"*2\r\n$11\r\nUNSUBSCRIBE\r\n$1\r\na\r\n",
+        b"*3\r\n$11\r\nunsubscribe\r\n$1\r\na\r\n:2\r\n",
+    );
+    send(
+        b"*1\r\n$11\r\nUNSUBSCRIBE\r\n",
+        b"*3\r\n$11\r\nunsubscribe\r\n$1\r\nb\r\n:1\r\n",
+    );
+    send(
+        b"*1\r\n$12\r\nPUNSUBSCRIBE\r\n",
+        b"*3\r\n$12\r\npunsubscribe\r\n$2\r\nc*\r\n:0\r\n",
+    );
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn scan_under_concurrent_writes() {
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    let (server, mut con) = get_redis_client_connection(3477);
+    let stable: HashSet<String> = (0..500).map(|i| format!("stable{}", i)).collect();
+    for key in &stable {
+        let _: () = con.set(key, 1).unwrap();
+    }
+
+    // the keyspace keeps growing and shrinking, so its maps are resized as the scans run
+    let done = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let done = done.clone();
+        std::thread::spawn(move || {
+            let client = redis::Client::open("redis://127.0.0.1:3477/").unwrap();
+            let mut con = client.get_connection().unwrap();
+            let mut round = 0;
+            while !done.load(Ordering::SeqCst) {
+                let keys: Vec<_> = (0..2000).map(|i| format!("churn{}-{}", round, i)).collect();
+                for chunk in keys.chunks(100) {
+                    let pairs: Vec<_> = chunk.iter().map(|key| (key.as_str(), 1)).collect();
+                    let _: () = con.set_multiple(&pairs).unwrap();
+                }
+                let mut pipe = redis::pipe();
+                for key in &keys {
+                    pipe.del(key).ignore();
+                }
+                let _: () = pipe.query(&mut con).unwrap();
+                round += 1;
+            }
+        })
+    };
+
+    for _ in 0..5 {
+        let mut cursor = 0;
+        let mut returned = HashSet::new();
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("COUNT")
+                .arg(7)
+                .query(&mut con)
+                .unwrap();
+            returned.extend(batch);
+            match next {
+                0 => break,
+                next => cursor = next,
+            }
+        }
+        assert!(stable.is_subset(&returned));
+        assert!(returned
+            .iter()
+            .all(|key| stable.contains(key) || key.starts_with("churn")));
+    }
+
+    done.store(true, Ordering::SeqCst);
+    writer.join().unwrap();
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn eviction_volatile_ttl() {
+    use crate::storage::Storage;
+
+    let mut storage = InMemoryStorage::default();
+    storage.write(b"key1", b"value");
+    let usage = storage.used_memory();
+
+    let config = ServerConfig {
+        maxmemory: 4 * usage + usage / 2,
+        maxmemory_policy: MaxMemoryPolicy::VolatileTtl,
+        maxmemory_samples: 10,
+        ..ServerConfig::new(3478)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+
+    let _: () = con.set("key1", "value").unwrap();
+    let _: () = con.set_ex("key2", "value", 100).unwrap();
+    let _: () = con.set_ex("key3", "value", 10).unwrap();
+    let _: () = con.set_ex("key4", "value", 50).unwrap();
+    // the key with the soonest expiry goes first, and a key without expiry never does
+    let _: () = con.set("key5", "value").unwrap();
+    let exists: bool = con.exists("key3").unwrap();
+    assert!(!exists);
+    let _: () = con.set("key6", "value").unwrap();
+    let exists: bool = con.exists("key4").unwrap();
+    assert!(!exists);
+    let _: () = con.set("key7", "value").unwrap();
+    let _: () = con.set("key8", "value").unwrap();
+    let keys: Vec<String> = con.keys("*").unwrap();
+    assert_eq!(keys.len(), 5);
+    assert!(!keys.contains(&"key2".to_string()));
+
+    // nothing is left to evict, the writes are refused but the reads still run
+    let err = con.set::<_, _, ()>("key9", "value").unwrap_err();
+    assert_eq!(err.code(), Some("OOM"));
+    let value: String = con.get("key1").unwrap();
+    assert_eq!(value, "value");
+
+    let _: () = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("maxmemory-policy")
+        .arg("allkeys-lru")
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.set("key9", "value").unwrap();
+    let size: u64 = redis::cmd("DBSIZE").query(&mut con).unwrap();
+    assert_eq!(size, 4);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn hash_preserve_order() {
+    let config = ServerConfig {
+        hash_preserve_order: true,
+        ..ServerConfig::new(3479)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+
+    let _: () = con
+        .hset_multiple("hash", &[("z", "1"), ("a", "2"), ("m", "3")])
+        .unwrap();
+    let _: () = con.hset("hash", "b", "4").unwrap();
+    // overwriting a field keeps its place
+    let _: () = con.hset("hash", "a", "5").unwrap();
+
+    let fields: Vec<String> = con.hkeys("hash").unwrap();
+    assert_eq!(fields, vec!["z", "a", "m", "b"]);
+    let values: Vec<String> = con.hvals("hash").unwrap();
+    assert_eq!(values, vec!["1", "5", "3", "4"]);
+    let entries: Vec<String> = con.hgetall("hash").unwrap();
+    assert_eq!(entries, vec!["z", "1", "a", "5", "m", "3", "b", "4"]);
+
+    let fields: Vec<String> = con.hkeys("missing").unwrap();
+    assert!(fields.is_empty());
+    let _: () = con.set("string", "value").unwrap();
+    let err = con.hkeys::<_, Vec<String>>("string").unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn command_docs() {
+    let (server, mut con) = get_redis_client_connection(3480);
+
+    let field = |entries: &[redis::Value], name: &str| -> Option<redis::Value> {
+        entries
+            .chunks(2)
+            .find(|entry| entry[0] == redis::Value::Data(name.as_bytes().to_vec()))
+            .map(|entry| entry[1].clone())
+    };
+
+    // unknown commands are left out
+    let docs: Vec<redis::Value> = redis::cmd("COMMAND")
+        .arg("DOCS")
+        .arg("get")
+        .arg("nope")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(docs.len(), 2);
+    assert_eq!(docs[0], redis::Value::Data(b"get".to_vec()));
+    let doc = match &docs[1] {
+        redis::Value::Bulk(doc) => doc.clone(),
+        other => panic!("unexpected doc {:?}", other),
+    };
+    assert_eq!(
+        field(&doc, "group"),
+        Some(redis::Value::Data(b"string".to_vec()))
+    );
+    let arguments = match field(&doc, "arguments") {
+        Some(redis::Value::Bulk(arguments)) => arguments,
+        other => panic!("unexpected arguments {:?}", other),
+    };
+    let key = match &arguments[..] {
+        [redis::Value::Bulk(key)] => key.clone(),
+        other => panic!("unexpected arguments {:?}", other),
+    };
+    assert_eq!(
+        field(&key, "name"),
+        Some(redis::Value::Data(b"key".to_vec()))
+    );
+    assert_eq!(
+        field(&key, "type"),
+        Some(redis::Value::Data(b"key".to_vec()))
+    );
+
+    let docs: Vec<redis::Value> = redis::cmd("COMMAND").arg("DOCS").query(&mut con).unwrap();
+    assert!(docs.len() > 100);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn mset_all_or_nothing() {
+    let (server, mut con) = get_redis_client_connection(3481);
+
+    // an odd number of arguments is refused before any key is written
+    let err = redis::cmd("MSET")
+        .arg("a")
+        .arg("1")
+        .arg("b")
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("ERR"));
+    let exists: bool = con.exists("a").unwrap();
+    assert!(!exists);
+
+    let _: () = con.set("b", "old").unwrap();
+    let set: u32 = con.mset_nx(&[("a", "1"), ("b", "2")]).unwrap();
+    assert_eq!(set, 0);
+    let exists: bool = con.exists("a").unwrap();
+    assert!(!exists);
+    let value: String = con.get("b").unwrap();
+    assert_eq!(value, "old");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn list_encoding() {
+    let (server, mut con) = get_redis_client_connection(3482);
+    let encoding = |con: &mut Connection, key: &str| -> String {
+        redis::cmd("OBJECT")
+            .arg("ENCODING")
+            .arg(key)
+            .query(con)
+            .unwrap()
+    };
+
+    let _: () = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("list-max-listpack-size")
+        .arg("3")
+        .query(&mut con)
+        .unwrap();
+    let size: Vec<String> = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("list-max-listpack-size")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(size, vec!["list-max-listpack-size", "3"]);
+
+    let _: u64 = con.rpush("list", &["a", "b", "c"]).unwrap();
+    assert_eq!(encoding(&mut con, "list"), "listpack");
+    let _: u64 = con.lpush("list", "d").unwrap();
+    assert_eq!(encoding(&mut con, "list"), "quicklist");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn inline_requests() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, con) = get_redis_client_connection(3483);
+    drop(con);
+
+    let mut stream = TcpStream::connect("127.0.0.1:3483").unwrap();
+    stream.write_all(b"SET key value\r\n\r\nGET key\n").unwrap();
+    let mut reply = [0; 16];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(&reply, b"+OK\r\n$5\r\nvalue\r\n");
+
+    // a line with no newline is not buffered past the max size of an inline request
+    stream.write_all(&vec![b'x'; 70 * 1024]).unwrap();
+    let mut reply = String::new();
+    let _ = stream.read_to_string(&mut reply);
+    assert_eq!(reply, "-ERR Protocol error: too big inline request\r\n");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn info_server() {
+    let config = ServerConfig {
+        redis_version: "6.2.7".to_string(),
+        ..ServerConfig::new(3484)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    let field = |info: &str, name: &str| -> Option<String> {
+        info.lines()
+            .find_map(|line| line.strip_prefix(&format!("{}:", name)))
+            .map(|value| value.to_string())
+    };
+
+    let info: String = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    assert!(info.starts_with("# Server\r\n"));
+    assert_eq!(field(&info, "redis_version").as_deref(), Some("6.2.7"));
+    assert_eq!(field(&info, "tcp_port").as_deref(), Some("3484"));
+    let run_id = field(&info, "run_id").unwrap();
+    assert_eq!(run_id.len(), 40);
+    assert!(run_id.chars().all(|c| c.is_ascii_hexdigit()));
+    for name in &["multiplexing_api", "atomicvar_api"] {
+        assert!(field(&info, name).is_some_and(|value| !value.is_empty()));
+    }
+    assert_eq!(field(&info, "io_threads_active").as_deref(), Some("0"));
+
+    // the run id is the same for the whole run
+    let info: String = redis::cmd("INFO").arg("all").query(&mut con).unwrap();
+    assert_eq!(field(&info, "run_id"), Some(run_id));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn blpop_under_concurrent_pushes() {
+    let (server, mut con) = get_redis_client_connection(3485);
+    const CONSUMERS: usize = 4;
+    const PRODUCERS: usize = 2;
+    const PER_CONSUMER: usize = 250;
+    let per_producer = CONSUMERS * PER_CONSUMER / PRODUCERS;
+
+    // each push races with the consumers finding the list empty and starting to wait, a
+    // lost wakeup leaves a consumer blocked until its timeout
+    let consumers: Vec<_> = (0..CONSUMERS)
+        .map(|_| {
+            std::thread::spawn(|| {
+                let client = redis::Client::open("redis://127.0.0.1:3485/").unwrap();
+                let mut con = client.get_connection().unwrap();
+                (0..PER_CONSUMER)
+                    .map(|_| {
+                        let popped: Option<(String, String)> = con.blpop("queue", 10).unwrap();
+                        popped.expect("an element was pushed but not popped").1
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let producers: Vec<_> = (0..PRODUCERS)
+        .map(|producer| {
+            std::thread::spawn(move || {
+                let client = redis::Client::open("redis://127.0.0.1:3485/").unwrap();
+                let mut con = client.get_connection().unwrap();
+                for i in 0..per_producer {
+                    let _: () = con.lpush("queue", format!("{}-{}", producer, i)).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for producer in producers {
+        producer.join().unwrap();
+    }
+    let mut popped: Vec<String> = consumers
+        .into_iter()
+        .flat_map(|consumer| consumer.join().unwrap())
+        .collect();
+    popped.sort();
+    let mut pushed: Vec<String> = (0..PRODUCERS)
+        .flat_map(|producer| (0..per_producer).map(move |i| format!("{}-{}", producer, i)))
+        .collect();
+    pushed.sort();
+    assert_eq!(popped, pushed);
+    assert!(!con.exists::<_, bool>("queue").unwrap());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn byte_commands_on_integers() {
+    let (server, mut con) = get_redis_client_connection(3486);
+    let encoding = |con: &mut Connection, key: &str| -> String {
+        redis::cmd("OBJECT")
+            .arg("ENCODING")
+            .arg(key)
+            .query(con)
+            .unwrap()
+    };
+
+    // a read leaves the integer as it is, as in Redis
+    let _: () = con.set("key", 12345).unwrap();
+    let range: String = con.getrange("key", 0, 2).unwrap();
+    assert_eq!(range, "123");
+    assert_eq!(encoding(&mut con, "key"), "int");
+
+    let len: u64 = con.append("key", "6").unwrap();
+    assert_eq!(len, 6);
+    assert_eq!(encoding(&mut con, "key"), "raw");
+
+    let _: () = con.set("key", 12345).unwrap();
+    let len: u64 = con.setrange("key", 1, "99").unwrap();
+    assert_eq!(len, 5);
+    let value: String = con.get("key").unwrap();
+    assert_eq!(value, "19945");
+    assert_eq!(encoding(&mut con, "key"), "raw");
+
+    // a missing string is padded with zero bytes, and an empty value creates nothing
+    let len: u64 = con.setrange("padded", 2, "ab").unwrap();
+    assert_eq!(len, 4);
+    let value: String = con.get("padded").unwrap();
+    assert_eq!(value, "\0\0ab");
+    let len: u64 = con.setrange("missing", 2, "").unwrap();
+    assert_eq!(len, 0);
+    assert!(!con.exists::<_, bool>("missing").unwrap());
+
+    // the expiry is kept
+    let _: () = con.set_ex("expiring", "value", 100).unwrap();
+    let _: u64 = con.setrange("expiring", 0, "V").unwrap();
+    let ttl: i64 = con.ttl("expiring").unwrap();
+    assert!(ttl > 0);
+
+    let err = con.setrange::<_, _, u64>("key", -1, "x").unwrap_err();
+    assert_eq!(err.detail(), Some("offset is out of range"));
+    let _: u64 = con.rpush("list", "a").unwrap();
+    let err = con.setrange::<_, _, u64>("list", 0, "x").unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zunion_zinter_zdiff() {
+    let (server, mut con) = get_redis_client_connection(3487);
+    let _: u64 = con
+        .zadd_multiple("first", &[(1, "a"), (2, "b"), (3, "c")])
+        .unwrap();
+    let _: u64 = con
+        .zadd_multiple("second", &[(10, "b"), (20, "c"), (30, "d")])
+        .unwrap();
+
+    // the scores are weighted, then summed, and the result is ordered by score
+    let union: Vec<String> = redis::cmd("ZUNION")
+        .arg(2)
+        .arg("first")
+        .arg("second")
+        .arg("WEIGHTS")
+        .arg(2)
+        .arg(0.5)
+        .arg("WITHSCORES")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(union, vec!["a", "2", "b", "9", "d", "15", "c", "16"]);
+    let union: Vec<String> = redis::cmd("ZUNION")
+        .arg(2)
+        .arg("first")
+        .arg("second")
+        .arg("WEIGHTS")
+        .arg(2)
+        .arg(0.5)
+        .arg("AGGREGATE")
+        .arg("MAX")
+        .arg("WITHSCORES")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(union, vec!["a", "2", "b", "5", "c", "10", "d", "15"]);
+
+    let inter: Vec<String> = redis::cmd("ZINTER")
+        .arg(2)
+        .arg("first")
+        .arg("second")
+        .arg("AGGREGATE")
+        .arg("MIN")
+        .arg("WITHSCORES")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(inter, vec!["b", "2", "c", "3"]);
+
+    // a set counts as a sorted set whose members all score 1
+    let _: u64 = con.sadd("set", "a").unwrap();
+    let diff: Vec<String> = redis::cmd("ZDIFF")
+        .arg(3)
+        .arg("first")
+        .arg("second")
+        .arg("set")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(diff, Vec::<String>::new());
+    let diff: Vec<String> = redis::cmd("ZDIFF")
+        .arg(2)
+        .arg("first")
+        .arg("set")
+        .arg("WITHSCORES")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(diff, vec!["b", "2", "c", "3"]);
+
+    let err = redis::cmd("ZUNION")
+        .arg(1)
+        .arg("first")
+        .arg("WEIGHTS")
+        .arg("heavy")
+        .query::<Vec<String>>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.detail(), Some("weight value is not a float"));
+    let _: () = con.set("string", "value").unwrap();
+    let err = redis::cmd("ZINTER")
+        .arg(2)
+        .arg("first")
+        .arg("string")
+        .query::<Vec<String>>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn lcs() {
+    let (server, mut con) = get_redis_client_connection(3488);
+    let _: () = con.set("first", "ohmytext").unwrap();
+    let _: () = con.set("second", "mynewtext").unwrap();
+    let lcs = |con: &mut Connection, options: &[&str]| -> redis::RedisResult<redis::Value> {
+        redis::cmd("LCS")
+            .arg("first")
+            .arg("second")
+            .arg(options)
+            .query(con)
+    };
+
+    let common: String = redis::cmd("LCS")
+        .arg("first")
+        .arg("second")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(common, "mytext");
+    let len: i64 = redis::cmd("LCS")
+        .arg("first")
+        .arg("second")
+        .arg("LEN")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(len, 6);
+
+    // the matches go from the last to the first, followed by the length
+    use redis::Value::{Bulk, Data, Int};
+    let range = |start, end| Bulk(vec![Int(start), Int(end)]);
+    let idx = lcs(&mut con, &["IDX"]).unwrap();
+    assert_eq!(
+        idx,
+        Bulk(vec![
+            Data(b"matches".to_vec()),
+            Bulk(vec![
+                Bulk(vec![range(4, 7), range(5, 8)]),
+                Bulk(vec![range(2, 3), range(0, 1)]),
+            ]),
+            Data(b"len".to_vec()),
+            Int(6),
+        ])
+    );
+    let idx = lcs(&mut con, &["IDX", "MINMATCHLEN", "4", "WITHMATCHLEN"]).unwrap();
+    assert_eq!(
+        idx,
+        Bulk(vec![
+            Data(b"matches".to_vec()),
+            Bulk(vec![Bulk(vec![range(4, 7), range(5, 8), Int(4)])]),
+            Data(b"len".to_vec()),
+            Int(6),
+        ])
+    );
+
+    // a missing key is an empty string
+    let common: String = redis::cmd("LCS")
+        .arg("first")
+        .arg("missing")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(common, "");
+
+    let err = lcs(&mut con, &["LEN", "IDX"]).unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("If you want both the length and indexes, please just use IDX.")
+    );
+    let _: u64 = con.rpush("list", "a").unwrap();
+    let err = redis::cmd("LCS")
+        .arg("first")
+        .arg("list")
+        .query::<String>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn copy_key() {
+    let (server, mut con) = get_redis_client_connection(3489);
+    let select = |con: &mut Connection, db: i64| {
+        let _: () = redis::cmd("SELECT").arg(db).query(con).unwrap();
+    };
+
+    let _: () = con.set_ex("source", "value", 100).unwrap();
+    let copied: i64 = redis::cmd("COPY")
+        .arg("source")
+        .arg("copy")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(copied, 1);
+    let value: String = con.get("copy").unwrap();
+    assert_eq!(value, "value");
+    let ttl: i64 = con.ttl("copy").unwrap();
+    assert!(ttl > 0 && ttl <= 100);
+
+    // into db1, leaving the source in db0 as it is
+    let _: u64 = con.rpush("list", &["a", "b"]).unwrap();
+    let copied: i64 = redis::cmd("COPY")
+        .arg("list")
+        .arg("list")
+        .arg("DB")
+        .arg(1)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(copied, 1);
+    let values: (String, String) = (
+        con.lindex("list", 0).unwrap(),
+        con.lindex("list", 1).unwrap(),
+    );
+    assert_eq!(values, ("a".to_string(), "b".to_string()));
+    select(&mut con, 1);
+    let values: (String, String) = (
+        con.lindex("list", 0).unwrap(),
+        con.lindex("list", 1).unwrap(),
+    );
+    assert_eq!(values, ("a".to_string(), "b".to_string()));
+
+    // an existing destination is only overwritten with REPLACE
+    let _: () = con.set("taken", "destination").unwrap();
+    select(&mut con, 0);
+    let _: () = con.set("taken", "source").unwrap();
+    let copy = |con: &mut Connection, replace: bool| -> RedisResult<i64> {
+        let mut cmd = redis::cmd("COPY");
+        cmd.arg("taken").arg("taken").arg("DB").arg(1);
+        if replace {
+            cmd.arg("REPLACE");
+        }
+        cmd.query(con)
+    };
+    assert_eq!(copy(&mut con, false).unwrap(), 0);
+    select(&mut con, 1);
+    let value: String = con.get("taken").unwrap();
+    assert_eq!(value, "destination");
+    select(&mut con, 0);
+    assert_eq!(copy(&mut con, true).unwrap(), 1);
+    select(&mut con, 1);
+    let value: String = con.get("taken").unwrap();
+    assert_eq!(value, "source");
+
+    let missing: i64 = redis::cmd("COPY")
+        .arg("missing")
+        .arg("copy")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(missing, 0);
+    let err = redis::cmd("COPY")
+        .arg("taken")
+        .arg("taken")
+        .arg("DB")
+        .arg(1)
+        .query::<i64>(&mut con)
+        .unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("source and destination objects are the same")
+    );
+    let err = redis::cmd("COPY")
+        .arg("taken")
+        .arg("other")
+        .arg("DB")
+        .arg(16)
+        .query::<i64>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.detail(), Some("DB index is out of range"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn info_expire_stats() {
+    let (server, mut con) = get_redis_client_connection(3491);
+    let field = |con: &mut Connection, name: &str| -> String {
+        let info: String = redis::cmd("INFO").arg("stats").query(con).unwrap();
+        info.lines()
+            .find_map(|line| line.strip_prefix(&format!("{}:", name)))
+            .map(|value| value.to_string())
+            .unwrap()
+    };
+    assert_eq!(field(&mut con, "expired_keys"), "0");
+
+    for index in 0..200 {
+        let _: () = con.pset_ex(format!("key:{}", index), "value", 50).unwrap();
+    }
+    let _: () = con.set("persistent", "value").unwrap();
+    let start = Instant::now();
+    while field(&mut con, "expired_keys") != "200" && start.elapsed() < Duration::from_secs(3) {
+        sleep(Duration::from_millis(50));
+    }
+    // the keys were only removed by the cycles, none of them was accessed
+    assert_eq!(field(&mut con, "expired_keys"), "200");
+    let dbsize: u64 = redis::cmd("DBSIZE").query(&mut con).unwrap();
+    assert_eq!(dbsize, 1);
+    let stale: f64 = field(&mut con, "expired_stale_perc").parse().unwrap();
+    assert!(stale > 0.0 && stale <= 100.0);
+    let time_cap_reached = field(&mut con, "expired_time_cap_reached_count");
+    assert!(time_cap_reached.parse::<u64>().is_ok());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn waitaof() {
+    let dir = std::env::temp_dir().join("redisless-waitaof");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let waitaof = |con: &mut Connection, numlocal: i64| -> RedisResult<Vec<i64>> {
+        redis::cmd("WAITAOF")
+            .arg(numlocal)
+            .arg(0)
+            .arg(100)
+            .query(con)
+    };
+
+    let config = ServerConfig {
+        dir: dir.clone(),
+        appendonly: true,
+        ..ServerConfig::new(3492)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    let _: () = con.set("key", "value").unwrap();
+    // the write is on the disk once acknowledged
+    assert_eq!(waitaof(&mut con, 1).unwrap(), vec![1, 0]);
+    let aof = std::fs::read(dir.join("appendonly.aof")).unwrap();
+    assert!(aof.ends_with(b"$3\r\nkey\r\n$5\r\nvalue\r\n"));
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    let (server, mut con) = get_redis_client_connection(3493);
+    let err = waitaof(&mut con, 1).unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("WAITAOF cannot be used when numlocal is set but appendonly is disabled")
+    );
+    assert_eq!(waitaof(&mut con, 0).unwrap(), vec![0, 0]);
+    let err = redis::cmd("WAITAOF")
+        .arg(0)
+        .arg(0)
+        .arg(-1)
+        .query::<Vec<i64>>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.detail(), Some("timeout is negative"));
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn setex_as_set_with_expiry() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let config = ServerConfig {
+        notify_keyspace_events: "E$g".to_string(),
+        ..ServerConfig::new(3494)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    let mut subscriber = TcpStream::connect("127.0.0.1:3494").unwrap();
+    subscriber
+        .write_all(b"*2\r\n$10\r\nPSUBSCRIBE\r\n$16\r\n__keyevent@0__:*\r\n")
+        .unwrap();
+    let subscribed = b"*3\r\n$10\r\npsubscribe\r\n$16\r\n__keyevent@0__:*\r\n:1\r\n";
+    let mut reply = vec![0; subscribed.len()];
+    subscriber.read_exact(&mut reply).unwrap();
+    assert_eq!(reply, subscribed.to_vec());
+    let events = |subscriber: &mut TcpStream, key: &str| {
+        let expected: Vec<u8> = ["set", "expire"]
+            .iter()
+            .flat_map(|event| {
+                format!(
+                    "*4\r\n$8\r\npmessage\r\n$16\r\n__keyevent@0__:*\r\n${}\r\n__keyevent@0__:{}\r\n${}\r\n{}\r\n",
+                    event.len() + 15,
+                    event,
+                    key.len(),
+                    key
+                )
+                .into_bytes()
+            })
+            .collect();
+        let mut reply = vec![0; expected.len()];
+        subscriber.read_exact(&mut reply).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&reply),
+            String::from_utf8_lossy(&expected)
+        );
+    };
+    // the ttl is only checked to be in range, two reads may fall on both sides of a second
+    let state = |con: &mut Connection, key: &str| -> (String, String) {
+        let value: String = con.get(key).unwrap();
+        let encoding: String = redis::cmd("OBJECT")
+            .arg("ENCODING")
+            .arg(key)
+            .query(con)
+            .unwrap();
+        let ttl: i64 = con.pttl(key).unwrap();
+        assert!((9000..=10000).contains(&ttl));
+        (value, encoding)
+    };
+
+    let _: () = redis::cmd("SETEX")
+        .arg("setex")
+        .arg(10)
+        .arg("value")
+        .query(&mut con)
+        .unwrap();
+    events(&mut subscriber, "setex");
+    let _: () = redis::cmd("SET")
+        .arg("set")
+        .arg("value")
+        .arg("EX")
+        .arg(10)
+        .query(&mut con)
+        .unwrap();
+    events(&mut subscriber, "set");
+    let _: () = redis::cmd("PSETEX")
+        .arg("psetex")
+        .arg(10000)
+        .arg("value")
+        .query(&mut con)
+        .unwrap();
+    events(&mut subscriber, "psetex");
+    assert_eq!(state(&mut con, "setex"), state(&mut con, "set"));
+    assert_eq!(state(&mut con, "psetex"), state(&mut con, "set"));
+
+    // a duration which is not positive is refused the same way by the three
+    for (command, args) in [
+        ("SET", vec!["key", "value", "EX", "0"]),
+        ("SET", vec!["key", "value", "PX", "-5"]),
+        ("SETEX", vec!["key", "0", "value"]),
+        ("PSETEX", vec!["key", "-5", "value"]),
+    ] {
+        let err = redis::cmd(command)
+            .arg(args)
+            .query::<()>(&mut con)
+            .unwrap_err();
+        let expected = format!(
+            "invalid expire time in '{}' command",
+            command.to_lowercase()
+        );
+        assert_eq!(err.detail(), Some(expected.as_str()));
+    }
+    assert!(!con.exists::<_, bool>("key").unwrap());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn info_replication() {
+    let (server, mut con) = get_redis_client_connection(3495);
+    let field = |info: &str, name: &str| -> Option<String> {
+        info.lines()
+            .find_map(|line| line.strip_prefix(&format!("{}:", name)))
+            .map(|value| value.to_string())
+    };
+
+    let info: String = redis::cmd("INFO")
+        .arg("replication")
+        .query(&mut con)
+        .unwrap();
+    assert!(info.starts_with("# Replication\r\n"));
+    assert_eq!(field(&info, "role").as_deref(), Some("master"));
+    assert_eq!(field(&info, "connected_slaves").as_deref(), Some("0"));
+    let replid = field(&info, "master_replid").unwrap();
+    assert_eq!(replid.len(), 40);
+    assert!(replid.chars().all(|c| c.is_ascii_hexdigit()));
+
+    // not part of the default sections
+    let info: String = redis::cmd("INFO").query(&mut con).unwrap();
+    assert_eq!(field(&info, "role"), None);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn resync_on_protocol_error() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let config = ServerConfig {
+        resync_on_protocol_error: true,
+        ..ServerConfig::new(3496)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+
+    // the garbage is dropped up to the next `*`, and the connection stays open
+    let mut stream = TcpStream::connect("127.0.0.1:3496").unwrap();
+    stream
+        .write_all(b"*x\r\n$3\r\nfoo\r\n*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n")
+        .unwrap();
+    let expected = "-ERR Protocol error: invalid digit found in string\r\n+OK\r\n";
+    let mut reply = vec![0; expected.len()];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(String::from_utf8_lossy(&reply), expected);
+
+    stream.write_all(b"*1\r\n$4\r\nPING\r\n").unwrap();
+    let mut reply = [0; 7];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(&reply, b"+PONG\r\n");
+
+    let value: String = con.get("key").unwrap();
+    assert_eq!(value, "value");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn type_simple_string() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, mut con) = get_redis_client_connection(3497);
+    let _: () = con.set("key", "value").unwrap();
+    let _: () = con.rpush("list", "a").unwrap();
+
+    let mut stream = TcpStream::connect("127.0.0.1:3497").unwrap();
+    let mut type_of = |key: &str| {
+        let request = format!("*2\r\n$4\r\nTYPE\r\n${}\r\n{}\r\n", key.len(), key);
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut reply = vec![];
+        while !reply.ends_with(b"\r\n") {
+            let mut byte = [0; 1];
+            stream.read_exact(&mut byte).unwrap();
+            reply.push(byte[0]);
+        }
+        String::from_utf8(reply).unwrap()
+    };
+    assert_eq!(type_of("key"), "+string\r\n");
+    assert_eq!(type_of("list"), "+list\r\n");
+    assert_eq!(type_of("missing"), "+none\r\n");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zmscore() {
+    let (server, mut con) = get_redis_client_connection(3498);
+    let _: () = con
+        .zadd_multiple("zset", &[(1.5, "a"), (2.0, "b")])
+        .unwrap();
+
+    let scores: Vec<Option<String>> = redis::cmd("ZMSCORE")
+        .arg("zset")
+        .arg(&["b", "missing", "a", "b"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(
+        scores,
+        vec![
+            Some("2".to_string()),
+            None,
+            Some("1.5".to_string()),
+            Some("2".to_string())
+        ]
+    );
+
+    // every member of a missing key is nil
+    let scores: Vec<Option<String>> = redis::cmd("ZMSCORE")
+        .arg("missing")
+        .arg(&["a", "b"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(scores, vec![None, None]);
+
+    let _: () = con.set("string", "value").unwrap();
+    let err = redis::cmd("ZMSCORE")
+        .arg("string")
+        .arg("a")
+        .query::<Vec<Option<String>>>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn databases_count_validated() {
+    use crate::server::{InvalidConfig, MAX_DATABASES};
+
+    for databases in [0, MAX_DATABASES + 1] {
+        let config = ServerConfig {
+            databases,
+            ..ServerConfig::new(3499)
+        };
+        let err = Server::try_new_with_config(InMemoryStorage::default(), config)
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            InvalidConfig {
+                name: "databases",
+                ..
+            }
+        ));
+    }
+
+    // SELECT takes the indexes below the count
+    let config = ServerConfig {
+        databases: 2,
+        ..ServerConfig::new(3499)
+    };
+    let server = Server::try_new_with_config(InMemoryStorage::default(), config).unwrap();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let mut con = redis::Client::open("redis://127.0.0.1:3499/")
+        .unwrap()
+        .get_connection()
+        .unwrap();
+    let _: () = redis::cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let err = redis::cmd("SELECT")
+        .arg(2)
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.detail(), Some("DB index is out of range"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn multi_refuses_subscribe() {
+    let (server, mut con) = get_redis_client_connection(3500);
+    let run = |con: &mut Connection, args: &[&str]| -> RedisResult<redis::Value> {
+        redis::cmd(args[0]).arg(&args[1..]).query(con)
+    };
+
+    let err = run(&mut con, &["EXEC"]).unwrap_err();
+    assert_eq!(err.detail(), Some("EXEC without MULTI"));
+    let err = run(&mut con, &["DISCARD"]).unwrap_err();
+    assert_eq!(err.detail(), Some("DISCARD without MULTI"));
+
+    assert_eq!(run(&mut con, &["MULTI"]), Ok(redis::Value::Okay));
+    let err = run(&mut con, &["MULTI"]).unwrap_err();
+    assert_eq!(err.detail(), Some("MULTI calls can not be nested"));
+    let queued = redis::Value::Status("QUEUED".to_string());
+    assert_eq!(run(&mut con, &["SET", "a", "1"]), Ok(queued.clone()));
+    // refused at once, and not queued
+    let err = run(&mut con, &["SUBSCRIBE", "foo"]).unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("Command not allowed inside a transaction")
+    );
+    let err = run(&mut con, &["PSUBSCRIBE", "f*"]).unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("Command not allowed inside a transaction")
+    );
+    assert_eq!(run(&mut con, &["GET", "a"]), Ok(queued));
+    let replies: Vec<String> = redis::cmd("EXEC").query(&mut con).unwrap();
+    assert_eq!(replies, vec!["OK", "1"]);
+    // the connection was not subscribed
+    let value: String = con.get("a").unwrap();
+    assert_eq!(value, "1");
+
+    // nothing runs once discarded
+    assert_eq!(run(&mut con, &["MULTI"]), Ok(redis::Value::Okay));
+    let _ = run(&mut con, &["SET", "a", "2"]).unwrap();
+    assert_eq!(run(&mut con, &["DISCARD"]), Ok(redis::Value::Okay));
+    let value: String = con.get("a").unwrap();
+    assert_eq!(value, "1");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn exec_errors() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, mut con) = get_redis_client_connection(3501);
+    let run = |con: &mut Connection, args: &[&str]| -> RedisResult<redis::Value> {
+        redis::cmd(args[0]).arg(&args[1..]).query(con)
+    };
+
+    // a command failing as it runs is replied its error, the others still run. The error
+    // is read from the stream, redis-rs fails the whole array on an error element.
+    let mut stream = TcpStream::connect("127.0.0.1:3501").unwrap();
+    stream
+        .write_all(b"MULTI\r\nSET string value\r\nINCR string\r\nSET other value\r\nEXEC\r\n")
+        .unwrap();
+    let expected = "+OK\r\n+QUEUED\r\n+QUEUED\r\n+QUEUED\r\n\
+                    *3\r\n+OK\r\n-ERR value is not an integer or out of range\r\n+OK\r\n";
+    let mut reply = vec![0; expected.len()];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(String::from_utf8_lossy(&reply), expected);
+    let value: String = con.get("other").unwrap();
+    assert_eq!(value, "value");
+
+    // a command failing to be queued discards the whole transaction
+    for failing in [
+        &["INCR"][..],
+        &["WATCH", "string"],
+        &["SET", "a", "b", "XX", "NX"],
+    ] {
+        assert_eq!(run(&mut con, &["MULTI"]), Ok(redis::Value::Okay));
+        let _ = run(&mut con, &["SET", "queued", "value"]).unwrap();
+        assert!(run(&mut con, failing).is_err());
+        let err = run(&mut con, &["EXEC"]).unwrap_err();
+        assert_eq!(err.code(), Some("EXECABORT"));
+        assert_eq!(
+            err.detail(),
+            Some("Transaction discarded because of previous errors.")
+        );
+        let exists: bool = con.exists("queued").unwrap();
+        assert!(!exists);
+    }
+
+    // the connection is out of the transaction
+    let err = run(&mut con, &["EXEC"]).unwrap_err();
+    assert_eq!(err.detail(), Some("EXEC without MULTI"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn resp3_verbatim_strings() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, con) = get_redis_client_connection(3502);
+    drop(con);
+    let read_line = |stream: &mut TcpStream| {
+        let mut line = vec![];
+        while !line.ends_with(b"\r\n") {
+            let mut byte = [0];
+            stream.read_exact(&mut byte).unwrap();
+            line.push(byte[0]);
+        }
+        String::from_utf8(line).unwrap()
+    };
+    let lolwut = format!("RedisLess ver. {}\n", env!("CARGO_PKG_VERSION"));
+
+    // a RESP2 client gets a bulk string
+    let mut stream = TcpStream::connect("127.0.0.1:3502").unwrap();
+    stream.write_all(b"*1\r\n$6\r\nLOLWUT\r\n").unwrap();
+    assert_eq!(read_line(&mut stream), format!("${}\r\n", lolwut.len()));
+
+    // the reply of HELLO is skipped up to the one of the PING sent after it
+    let mut stream = TcpStream::connect("127.0.0.1:3502").unwrap();
+    stream
+        .write_all(b"*2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n*1\r\n$4\r\nPING\r\n")
+        .unwrap();
+    while read_line(&mut stream) != "+PONG\r\n" {}
+
+    stream.write_all(b"*1\r\n$6\r\nLOLWUT\r\n").unwrap();
+    let expected = format!("={}\r\ntxt:{}\r\n", lolwut.len() + 4, lolwut);
+    let mut reply = vec![0; expected.len()];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(String::from_utf8_lossy(&reply), expected);
+
+    stream
+        .write_all(b"*2\r\n$4\r\nINFO\r\n$6\r\nserver\r\n")
+        .unwrap();
+    let header = read_line(&mut stream);
+    assert!(header.starts_with('='));
+    let len: usize = header[1..header.len() - 2].parse().unwrap();
+    let mut reply = vec![0; len + 2];
+    stream.read_exact(&mut reply).unwrap();
+    assert!(reply.starts_with(b"txt:# Server\r\n"));
+    assert!(reply.ends_with(b"\r\n"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn save_points() {
+    use crate::storage::snapshot;
+
+    let dir = std::env::temp_dir().join("redisless-save-points");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = ServerConfig {
+        dir: dir.clone(),
+        save: vec![(3600, 1000), (0, 3)],
+        ..ServerConfig::new(3503)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    let path = dir.join("dump.rdb");
+
+    let _: () = con.set("a", "1").unwrap();
+    let _: () = con.set("b", "2").unwrap();
+    sleep(Duration::from_millis(300));
+    assert!(!path.exists());
+
+    // the third write reaches the save point
+    let _: () = con.set("c", "3").unwrap();
+    for _ in 0..50 {
+        if path.exists() {
+            break;
+        }
+        sleep(Duration::from_millis(20));
+    }
+    let loaded = snapshot::load(&path).unwrap();
+    assert_eq!(loaded[0].len(), 3);
+
+    // the writes are counted again from the snapshot
+    sleep(Duration::from_millis(200));
+    std::fs::remove_file(&path).unwrap();
+    let _: () = con.set("d", "4").unwrap();
+    sleep(Duration::from_millis(300));
+    assert!(!path.exists());
+
+    let _: () = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("save")
+        .arg("")
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.set("e", "5").unwrap();
+    let _: () = con.set("f", "6").unwrap();
+    sleep(Duration::from_millis(300));
+    assert!(!path.exists());
+
+    let _: () = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("save")
+        .arg("60 10 0 1")
+        .query(&mut con)
+        .unwrap();
+    let save: Vec<String> = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("save")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(save, vec!["save", "60 10 0 1"]);
+    let err = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("save")
+        .arg("60")
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert!(err.detail().unwrap().contains("Invalid save parameters"));
+    for _ in 0..50 {
+        if path.exists() {
+            break;
+        }
+        sleep(Duration::from_millis(20));
+    }
+    assert_eq!(snapshot::load(&path).unwrap()[0].len(), 6);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+#[serial]
+fn client_kill() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use crate::command::options::KillFilter;
+    use crate::server::clients::Clients;
+
+    // the registry closes the connection killed and forgets it
+    let clients = Clients::default();
+    let closed = [
+        Arc::new(AtomicBool::new(false)),
+        Arc::new(AtomicBool::new(false)),
+    ];
+    for (id, closed) in (1..).zip(&closed) {
+        let closed = closed.clone();
+        let closer = Box::new(move || closed.store(true, Ordering::SeqCst));
+        clients.add(id, format!("127.0.0.1:{}", 5000 + id), closer);
+    }
+    let filter = KillFilter {
+        id: Some(2),
+        addr: None,
+    };
+    assert_eq!(clients.kill(&filter, 1), vec![2]);
+    assert!(!closed[0].load(Ordering::SeqCst));
+    assert!(closed[1].load(Ordering::SeqCst));
+    assert!(clients.kill(&filter, 1).is_empty());
+    let filter = KillFilter {
+        id: None,
+        addr: Some("127.0.0.1:5001".to_string()),
+    };
+    assert_eq!(clients.kill(&filter, 1), vec![1]);
+    // the connection killing itself is closed by its thread once it replied
+    assert!(!closed[0].load(Ordering::SeqCst));
+
+    let (server, mut con) = get_redis_client_connection(3504);
+    let mut target = TcpStream::connect("127.0.0.1:3504").unwrap();
+    target.write_all(b"CLIENT ID\r\n").unwrap();
+    let mut reply = [0; 64];
+    let len = target.read(&mut reply).unwrap();
+    let id = String::from_utf8_lossy(&reply[1..len - 2]).to_string();
+
+    // a killed connection is closed by the server
+    let killed: i64 = redis::cmd("CLIENT")
+        .arg(&["KILL", "ID", &id])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(killed, 1);
+    assert_eq!(target.read(&mut reply).unwrap(), 0);
+    let killed: i64 = redis::cmd("CLIENT")
+        .arg(&["KILL", "ID", &id])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(killed, 0);
+
+    // by its address, all the filters must match
+    let mut target = TcpStream::connect("127.0.0.1:3504").unwrap();
+    let addr = target.local_addr().unwrap().to_string();
+    // served once it replied
+    target.write_all(b"PING\r\n").unwrap();
+    target.read_exact(&mut reply[..7]).unwrap();
+    let killed: i64 = redis::cmd("CLIENT")
+        .arg(&["KILL", "ID", &id, "ADDR", &addr])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(killed, 0);
+    let killed: i64 = redis::cmd("CLIENT")
+        .arg(&["KILL", "ADDR", &addr])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(killed, 1);
+    assert_eq!(target.read(&mut reply).unwrap(), 0);
+
+    // killing its own connection replies before closing it
+    let mut own = TcpStream::connect("127.0.0.1:3504").unwrap();
+    let addr = own.local_addr().unwrap().to_string();
+    own.write_all(format!("CLIENT KILL ADDR {}\r\nPING\r\n", addr).as_bytes())
+        .unwrap();
+    let mut reply = vec![];
+    own.read_to_end(&mut reply).unwrap();
+    assert_eq!(reply, b":1\r\n");
+
+    let err = redis::cmd("CLIENT")
+        .arg(&["KILL", "ID", "0"])
+        .query::<i64>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.detail(), Some("client-id should be greater than 0"));
+    let pong: String = redis::cmd("PING").query(&mut con).unwrap();
+    assert_eq!(pong, "PONG");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn copy_sorted_set_scores() {
+    let dir = std::env::temp_dir().join("redisless-copy-sorted-set");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = ServerConfig {
+        dir: dir.clone(),
+        ..ServerConfig::new(3505)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+
+    let scores = [
+        ("negative", "-1.5"),
+        ("zero", "0"),
+        ("fraction", "0.1"),
+        ("tiny", "5e-324"),
+        ("infinite", "inf"),
+        ("negative-infinite", "-inf"),
+    ];
+    let mut zadd = redis::cmd("ZADD");
+    zadd.arg("zset");
+    for &(member, score) in &scores {
+        zadd.arg(score).arg(member);
+    }
+    let _: u64 = zadd.query(&mut con).unwrap();
+    let copied: bool = redis::cmd("COPY")
+        .arg(&["zset", "copy"])
+        .query(&mut con)
+        .unwrap();
+    assert!(copied);
+
+    // the scores of the copy are the bits of the source, before and after a round trip
+    // through the snapshot
+    let score = |con: &mut Connection, key: &str, member: &str| -> f64 {
+        let score: String = redis::cmd("ZSCORE").arg(&[key, member]).query(con).unwrap();
+        score.parse().unwrap()
+    };
+    for reload in [false, true] {
+        if reload {
+            let _: String = redis::cmd("DEBUG").arg("RELOAD").query(&mut con).unwrap();
+        }
+        for (member, expected) in &scores {
+            let expected = expected.parse::<f64>().unwrap().to_bits();
+            assert_eq!(score(&mut con, "zset", member).to_bits(), expected);
+            assert_eq!(score(&mut con, "copy", member).to_bits(), expected);
+        }
+    }
+
+    // and its members are in the same order
+    let popped: Vec<String> = redis::cmd("ZPOPMIN")
+        .arg(&["copy", "10"])
+        .query(&mut con)
+        .unwrap();
+    let members = popped
+        .iter()
+        .step_by(2)
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        members,
+        [
+            "negative-infinite",
+            "negative",
+            "zero",
+            "tiny",
+            "fraction",
+            "infinite"
+        ]
+    );
+
+    let score: Option<String> = redis::cmd("ZSCORE")
+        .arg(&["zset", "missing"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(score, None);
+    let err = redis::cmd("ZSCORE")
+        .arg(&["missing"])
+        .query::<String>(&mut con)
+        .unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("wrong number of arguments for 'zscore' command")
+    );
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+#[serial]
+fn debug_stringmatch_len() {
+    let (server, mut con) = get_redis_client_connection(3506);
+    let matches = |con: &mut Connection, pattern: &str, string: &str| -> bool {
+        let matched: i64 = redis::cmd("DEBUG")
+            .arg(&["STRINGMATCH-LEN", pattern, string])
+            .query(con)
+            .unwrap();
+        matched == 1
+    };
+
+    assert!(matches(&mut con, "h[a-c]llo", "hbllo"));
+    assert!(!matches(&mut con, "h[a-c]llo", "hdllo"));
+    // reversed ranges, negated classes and escapes within a class
+    assert!(matches(&mut con, "h[c-a]llo", "hallo"));
+    assert!(matches(&mut con, "h[^a-c]llo", "hdllo"));
+    assert!(!matches(&mut con, "h[^a-c]llo", "hallo"));
+    assert!(matches(&mut con, "h[\\]]llo", "h]llo"));
+    // an escaped star only matches a star
+    assert!(matches(&mut con, "h\\*llo", "h*llo"));
+    assert!(!matches(&mut con, "h\\*llo", "hello"));
+    // a star at the end matches anything left, including nothing
+    assert!(matches(&mut con, "hello*", "hello"));
+    assert!(matches(&mut con, "hello*", "hello world"));
+    assert!(matches(&mut con, "h*o**", "hello"));
+    assert!(!matches(&mut con, "hello?", "hello"));
+    // a trailing backslash matches itself
+    assert!(matches(&mut con, "hello\\", "hello\\"));
+    assert!(matches(&mut con, "", ""));
+    assert!(!matches(&mut con, "", "hello"));
+
+    // without arguments, as the fuzzer of Redis, it does nothing
+    let reply: String = redis::cmd("DEBUG")
+        .arg("STRINGMATCH-LEN")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(reply, "OK");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn object_encoding_embstr() {
+    let (server, mut con) = get_redis_client_connection(3507);
+    let encoding = |con: &mut Connection, len: usize| -> String {
+        let _: () = con.set("string", "x".repeat(len)).unwrap();
+        redis::cmd("OBJECT")
+            .arg(&["ENCODING", "string"])
+            .query(con)
+            .unwrap()
+    };
+
+    assert_eq!(encoding(&mut con, 10), "embstr");
+    assert_eq!(encoding(&mut con, 44), "embstr");
+    assert_eq!(encoding(&mut con, 45), "raw");
+    assert_eq!(encoding(&mut con, 100), "raw");
+
+    // the threshold applies to the strings set afterwards
+    let _: () = redis::cmd("CONFIG")
+        .arg(&["SET", "embstr-size-limit", "8"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(encoding(&mut con, 8), "embstr");
+    assert_eq!(encoding(&mut con, 10), "raw");
+    // an integer is still an int, whatever its length
+    let _: () = con.set("string", "1234567890").unwrap();
+    let reply: String = redis::cmd("OBJECT")
+        .arg(&["ENCODING", "string"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(reply, "int");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn read_commands_wrong_type() {
+    let (server, mut con) = get_redis_client_connection(3508);
+    let _: () = con.set("string", "value").unwrap();
+    let _: u64 = con.rpush("list", "a").unwrap();
+
+    for (command, key) in [
+        (&["HGET", "field"][..], "string"),
+        (&["LLEN"], "string"),
+        (&["LINDEX", "0"], "string"),
+        (&["LPOS", "a"], "string"),
+        (&["SCARD"], "string"),
+        (&["SMISMEMBER", "a"], "string"),
+        (&["ZSCORE", "a"], "string"),
+        (&["ZMSCORE", "a"], "string"),
+        (&["ZCOUNT", "-inf", "+inf"], "string"),
+        (&["STRLEN"], "list"),
+        (&["GETRANGE", "0", "-1"], "list"),
+    ] {
+        let err = redis::cmd(command[0])
+            .arg(key)
+            .arg(&command[1..])
+            .query::<redis::Value>(&mut con)
+            .unwrap_err();
+        assert_eq!(err.code(), Some("WRONGTYPE"), "{:?}", command);
+    }
+
+    // and their replies for a missing key
+    for (command, expected) in [
+        (&["HGET", "missing", "field"][..], redis::Value::Nil),
+        (&["LLEN", "missing"], redis::Value::Int(0)),
+        (&["LINDEX", "missing", "0"], redis::Value::Nil),
+        (&["SCARD", "missing"], redis::Value::Int(0)),
+        (&["ZSCORE", "missing", "a"], redis::Value::Nil),
+        (&["ZCOUNT", "missing", "-inf", "+inf"], redis::Value::Int(0)),
+        (&["STRLEN", "missing"], redis::Value::Int(0)),
+        (
+            &["GETRANGE", "missing", "0", "-1"],
+            redis::Value::Data(vec![]),
+        ),
+    ] {
+        let reply: redis::Value = redis::cmd(command[0])
+            .arg(&command[1..])
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(reply, expected, "{:?}", command);
+    }
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn dump_and_restore() {
+    let (server, mut con) = get_redis_client_connection(3509);
+    let dump = |con: &mut Connection, key: &str| -> Option<Vec<u8>> {
+        redis::cmd("DUMP").arg(key).query(con).unwrap()
+    };
+    let restore = |con: &mut Connection, key: &str, ttl: i64, payload: &[u8], options: &[&str]| {
+        redis::cmd("RESTORE")
+            .arg(key)
+            .arg(ttl)
+            .arg(payload)
+            .arg(options)
+            .query::<String>(con)
+    };
+    let idletime = |con: &mut Connection, key: &str| -> i64 {
+        redis::cmd("OBJECT")
+            .arg(&["IDLETIME", key])
+            .query(con)
+            .unwrap()
+    };
+
+    let _: u64 = con.rpush("list", &["a", "b", "c"]).unwrap();
+    let payload = dump(&mut con, "list").unwrap();
+    assert_eq!(dump(&mut con, "missing"), None);
+
+    // the restored key keeps the idle time it had where it was dumped
+    assert_eq!(
+        restore(&mut con, "restored", 0, &payload, &["IDLETIME", "100"]).as_deref(),
+        Ok("OK")
+    );
+    let idle = idletime(&mut con, "restored");
+    assert!((100..=101).contains(&idle), "{}", idle);
+    assert_eq!(dump(&mut con, "restored").as_ref(), Some(&payload));
+    let len: u64 = con.llen("restored").unwrap();
+    assert_eq!(len, 3);
+    let ttl: i64 = con.ttl("restored").unwrap();
+    assert_eq!(ttl, -1);
+
+    let err = restore(&mut con, "restored", 0, &payload, &[]).unwrap_err();
+    assert_eq!(err.code(), Some("BUSYKEY"));
+    assert_eq!(
+        restore(&mut con, "restored", 10_000, &payload, &["REPLACE"]).as_deref(),
+        Ok("OK")
+    );
+    let ttl: i64 = con.ttl("restored").unwrap();
+    assert!((9..=10).contains(&ttl));
+    // an absolute ttl in the past only removes the key
+    assert_eq!(
+        restore(&mut con, "restored", 1, &payload, &["REPLACE", "ABSTTL"]).as_deref(),
+        Ok("OK")
+    );
+    let exists: bool = con.exists("restored").unwrap();
+    assert!(!exists);
+
+    // the idle time and the frequency are exclusive
+    let err = restore(
+        &mut con,
+        "other",
+        0,
+        &payload,
+        &["IDLETIME", "1", "FREQ", "1"],
+    )
+    .unwrap_err();
+    assert_eq!(err.detail(), Some("syntax error"));
+    let err = restore(&mut con, "other", 0, &payload, &["FREQ", "256"]).unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("Invalid FREQ value, must be >= 0 and <= 255")
+    );
+    let err = restore(&mut con, "other", -1, &payload, &[]).unwrap_err();
+    assert_eq!(err.detail(), Some("Invalid TTL value, must be >= 0"));
+    let mut corrupted = payload.clone();
+    corrupted[5] ^= 1;
+    let err = restore(&mut con, "other", 0, &corrupted, &[]).unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("DUMP payload version or checksum are wrong")
+    );
+    // a payload of the right version and checksum, but of an unknown type
+    let mut unknown = payload[..payload.len() - 8].to_vec();
+    unknown[0] = 0x7f;
+    let checksum = crate::storage::snapshot::crc64(&unknown);
+    unknown.extend_from_slice(&checksum.to_le_bytes());
+    let err = restore(&mut con, "other", 0, &unknown, &[]).unwrap_err();
+    assert_eq!(err.detail(), Some("Bad data format"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    // the frequency is restored under an LFU policy
+    let config = ServerConfig {
+        maxmemory_policy: MaxMemoryPolicy::AllKeysLfu,
+        ..ServerConfig::new(3510)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    assert_eq!(
+        restore(&mut con, "restored", 0, &payload, &["FREQ", "100"]).as_deref(),
+        Ok("OK")
+    );
+    let freq: i64 = redis::cmd("OBJECT")
+        .arg(&["FREQ", "restored"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(freq, 100);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn hash_field_expiry() {
+    let (server, mut con) = get_redis_client_connection(3511);
+    let hexpire = |con: &mut Connection, name: &str, ttl: u64, args: &[&str]| {
+        redis::cmd(name)
+            .arg("hash")
+            .arg(ttl)
+            .arg(args)
+            .query::<Vec<i64>>(con)
+            .unwrap()
+    };
+    let httl = |con: &mut Connection, fields: &[&str]| {
+        redis::cmd("HTTL")
+            .arg("hash")
+            .arg("FIELDS")
+            .arg(fields.len())
+            .arg(fields)
+            .query::<Vec<i64>>(con)
+            .unwrap()
+    };
+
+    let _: () = con
+        .hset_multiple("hash", &[("a", "1"), ("b", "2"), ("c", "3")])
+        .unwrap();
+    assert_eq!(
+        hexpire(&mut con, "HEXPIRE", 100, &["FIELDS", "2", "a", "missing"]),
+        vec![1, -2]
+    );
+    assert_eq!(httl(&mut con, &["a", "b", "missing"]), vec![100, -1, -2]);
+
+    // the conditions on the current expiry of the fields
+    assert_eq!(
+        hexpire(&mut con, "HEXPIRE", 50, &["NX", "FIELDS", "2", "a", "b"]),
+        vec![0, 1]
+    );
+    assert_eq!(
+        hexpire(&mut con, "HEXPIRE", 200, &["GT", "FIELDS", "2", "a", "c"]),
+        vec![1, 0]
+    );
+    assert_eq!(
+        hexpire(&mut con, "HEXPIRE", 10, &["XX", "FIELDS", "2", "a", "c"]),
+        vec![1, 0]
+    );
+    assert_eq!(httl(&mut con, &["a", "b", "c"]), vec![10, 50, -1]);
+
+    let persisted: Vec<i64> = redis::cmd("HPERSIST")
+        .arg(&["hash", "FIELDS", "3", "a", "c", "missing"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(persisted, vec![1, -1, -2]);
+    assert_eq!(httl(&mut con, &["a"]), vec![-1]);
+
+    // a field is removed once its ttl elapses, and a zero ttl deletes it at once
+    assert_eq!(
+        hexpire(&mut con, "HPEXPIRE", 100, &["FIELDS", "1", "a"]),
+        vec![1]
+    );
+    assert_eq!(
+        hexpire(&mut con, "HEXPIRE", 0, &["FIELDS", "1", "b"]),
+        vec![2]
+    );
+    let value: Option<String> = con.hget("hash", "a").unwrap();
+    assert_eq!(value.as_deref(), Some("1"));
+    sleep(Duration::from_millis(200));
+    let value: Option<String> = con.hget("hash", "a").unwrap();
+    assert_eq!(value, None);
+    let fields: Vec<String> = con.hkeys("hash").unwrap();
+    assert_eq!(fields, vec!["c"]);
+
+    // a field set again loses its expiry
+    assert_eq!(
+        hexpire(&mut con, "HEXPIRE", 100, &["FIELDS", "1", "c"]),
+        vec![1]
+    );
+    let _: () = con.hset("hash", "c", "4").unwrap();
+    assert_eq!(httl(&mut con, &["c"]), vec![-1]);
+
+    // the key goes with its last field
+    assert_eq!(
+        hexpire(&mut con, "HPEXPIRE", 50, &["FIELDS", "1", "c"]),
+        vec![1]
+    );
+    sleep(Duration::from_millis(100));
+    let exists: bool = con.exists("hash").unwrap();
+    assert!(!exists);
+    assert_eq!(httl(&mut con, &["c"]), vec![-2]);
+
+    let _: () = con.set("string", "value").unwrap();
+    let err = redis::cmd("HTTL")
+        .arg(&["string", "FIELDS", "1", "a"])
+        .query::<Vec<i64>>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn getex_absolute_time() {
+    let (server, mut con) = get_redis_client_connection(3512);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap();
+
+    let _: () = con.set("key", "value").unwrap();
+    let value: String = redis::cmd("GETEX")
+        .arg("key")
+        .arg("PXAT")
+        .arg(now.as_millis() as u64 + 100_000)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(value, "value");
+    let ttl: i64 = con.ttl("key").unwrap();
+    assert!((99..=100).contains(&ttl), "ttl {}", ttl);
+
+    // a time already past deletes the key, whose value is still returned
+    let value: String = redis::cmd("GETEX")
+        .arg("key")
+        .arg("EXAT")
+        .arg(now.as_secs() - 10)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(value, "value");
+    let exists: bool = con.exists("key").unwrap();
+    assert!(!exists);
+
+    let _: () = con.set("key", "value").unwrap();
+    let err = redis::cmd("GETEX")
+        .arg(&["key", "EXAT", "1", "PX", "100"])
+        .query::<String>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.detail(), Some("syntax error"));
+    let ttl: i64 = con.ttl("key").unwrap();
+    assert_eq!(ttl, -1);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn memory_stats_and_doctor() {
+    let (server, mut con) = get_redis_client_connection(3513);
+    let stats = |con: &mut Connection| -> HashMap<String, redis::Value> {
+        redis::cmd("MEMORY").arg("STATS").query(con).unwrap()
+    };
+    let integer = |stats: &HashMap<String, redis::Value>, name: &str| match stats.get(name) {
+        Some(redis::Value::Int(value)) => *value,
+        value => panic!("unexpected {} {:?}", name, value),
+    };
+    let doctor =
+        |con: &mut Connection| -> String { redis::cmd("MEMORY").arg("DOCTOR").query(con).unwrap() };
+
+    let empty = stats(&mut con);
+    assert_eq!(integer(&empty, "keys.count"), 0);
+    assert_eq!(integer(&empty, "dataset.bytes"), 0);
+    assert!(doctor(&mut con).contains("this instance is empty"));
+
+    let _: () = con.set("small", "value").unwrap();
+    let before = stats(&mut con);
+    let _: () = con.set("large", "x".repeat(10_000)).unwrap();
+    let after = stats(&mut con);
+    assert_eq!(integer(&after, "keys.count"), 2);
+    assert!(integer(&after, "dataset.bytes") >= integer(&before, "dataset.bytes") + 10_000);
+    assert_eq!(
+        integer(&after, "total.allocated"),
+        integer(&after, "dataset.bytes") + integer(&after, "overhead.total")
+    );
+    let usage: i64 = redis::cmd("MEMORY")
+        .arg(&["USAGE", "large"])
+        .query(&mut con)
+        .unwrap();
+    assert!(integer(&after, "total.allocated") > usage);
+    assert!(doctor(&mut con).contains("can't find any memory issue"));
+
+    // many small keys take more room for their structures than for their values
+    for i in 0..100 {
+        let _: () = con.set(format!("key{}", i), i).unwrap();
+    }
+    let _: () = con.del("large").unwrap();
+    assert!(doctor(&mut con).contains("High overhead"));
+
+    let err = redis::cmd("MEMORY")
+        .arg(&["STATS", "extra"])
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::ResponseError);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn other_storage() {
+    use mock::{StringStorage, WRITES};
+    use std::sync::atomic::Ordering;
+
+    let server = Server::new(StringStorage::default(), 3514);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let client = redis::Client::open("redis://127.0.0.1:3514/").unwrap();
+    let mut con = client.get_connection().unwrap();
+
+    // the commands are run against the storage the server was given
+    let writes = WRITES.load(Ordering::SeqCst);
+    let _: () = con.set("key", "value").unwrap();
+    assert_eq!(WRITES.load(Ordering::SeqCst), writes + 1);
+    let value: String = con.get("key").unwrap();
+    assert_eq!(value, "value");
+    let _: () = con.append("key", "s").unwrap();
+    let value: String = con.get("key").unwrap();
+    assert_eq!(value, "values");
+    let size: u64 = redis::cmd("DBSIZE").query(&mut con).unwrap();
+    assert_eq!(size, 1);
+
+    let _: () = con.del("key").unwrap();
+    let value: Option<String> = con.get("key").unwrap();
+    assert_eq!(value, None);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn flush_async() {
+    use mock::{StringStorage, DROPS, DROP_TIME};
+    use std::sync::atomic::Ordering;
+
+    let server = Server::new(StringStorage::default(), 3515);
+    assert_eq!(server.start(), Some(ServerState::Started));
+    let client = redis::Client::open("redis://127.0.0.1:3515/").unwrap();
+    let mut con = client.get_connection().unwrap();
+    let fill = |con: &mut Connection| {
+        for i in 0..100 {
+            let _: () = con.set(format!("key{}", i), i).unwrap();
+        }
+    };
+
+    // the keys are gone at once, and dropped afterwards
+    fill(&mut con);
+    let drops = DROPS.load(Ordering::SeqCst);
+    let start = Instant::now();
+    let _: () = redis::cmd("FLUSHALL").arg("ASYNC").query(&mut con).unwrap();
+    assert!(start.elapsed() < DROP_TIME);
+    assert_eq!(DROPS.load(Ordering::SeqCst), drops);
+    let size: u64 = redis::cmd("DBSIZE").query(&mut con).unwrap();
+    assert_eq!(size, 0);
+    let dropped = Instant::now() + 10 * DROP_TIME;
+    while DROPS.load(Ordering::SeqCst) == drops {
+        assert!(Instant::now() < dropped, "the keys were never dropped");
+        sleep(Duration::from_millis(10));
+    }
+
+    fill(&mut con);
+    let drops = DROPS.load(Ordering::SeqCst);
+    let _: () = redis::cmd("FLUSHDB").arg("ASYNC").query(&mut con).unwrap();
+    assert_eq!(DROPS.load(Ordering::SeqCst), drops);
+    let size: u64 = redis::cmd("DBSIZE").query(&mut con).unwrap();
+    assert_eq!(size, 0);
+
+    // while they are dropped before the reply of a SYNC flush
+    fill(&mut con);
+    let drops = DROPS.load(Ordering::SeqCst);
+    let _: () = redis::cmd("FLUSHALL").arg("SYNC").query(&mut con).unwrap();
+    assert!(DROPS.load(Ordering::SeqCst) > drops);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn spop_srandmember_distribution() {
+    let config = ServerConfig {
+        seed: Some(42),
+        ..ServerConfig::new(3516)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    let members = ["a", "b", "c", "d", "e"];
+    let _: () = con.sadd("set", &members[..]).unwrap();
+    // each member is expected draws / 5 times, allowed a fifth more or less
+    let assert_uniform = |counts: &HashMap<String, usize>, draws: usize| {
+        assert_eq!(counts.len(), members.len(), "{:?}", counts);
+        let expected = draws / members.len();
+        for (member, count) in counts {
+            assert!(
+                count.abs_diff(expected) < expected / 5,
+                "{} drawn {} times out of {}",
+                member,
+                count,
+                draws
+            );
+        }
+    };
+
+    let mut counts = HashMap::new();
+    for _ in 0..1000 {
+        let picked: Vec<String> = redis::cmd("SRANDMEMBER")
+            .arg(&["set", "2"])
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(picked.len(), 2);
+        assert_ne!(picked[0], picked[1]);
+        for member in picked {
+            *counts.entry(member).or_insert(0) += 1;
+        }
+    }
+    assert_uniform(&counts, 2000);
+
+    let repeated: Vec<String> = redis::cmd("SRANDMEMBER")
+        .arg(&["set", "-5000"])
+        .query(&mut con)
+        .unwrap();
+    let mut counts = HashMap::new();
+    for member in repeated {
+        *counts.entry(member).or_insert(0) += 1;
+    }
+    assert_uniform(&counts, 5000);
+    let all: Vec<String> = redis::cmd("SRANDMEMBER")
+        .arg(&["set", "10"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(all.len(), members.len());
+
+    let mut counts = HashMap::new();
+    for _ in 0..1000 {
+        let popped: Vec<String> = redis::cmd("SPOP")
+            .arg(&["set", "2"])
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(con.scard::<_, usize>("set").unwrap(), 3);
+        let _: () = con.sadd("set", &popped[..]).unwrap();
+        for member in popped {
+            *counts.entry(member).or_insert(0) += 1;
+        }
+    }
+    assert_uniform(&counts, 2000);
+
+    // the set goes with its last member
+    let popped: Vec<String> = redis::cmd("SPOP")
+        .arg(&["set", "10"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(popped.len(), members.len());
+    assert!(!con.exists::<_, bool>("set").unwrap());
+    let popped: Option<String> = redis::cmd("SPOP").arg("set").query(&mut con).unwrap();
+    assert_eq!(popped, None);
+    let err = redis::cmd("SPOP")
+        .arg(&["set", "-1"])
+        .query::<Vec<String>>(&mut con)
+        .unwrap_err();
+    assert_eq!(
+        err.detail(),
+        Some("value is out of range, must be positive")
+    );
+
+    // a missing set has no member, and a count too negative to be replied is refused
+    let picked: Vec<String> = redis::cmd("SRANDMEMBER")
+        .arg(&["set", "-3"])
+        .query(&mut con)
+        .unwrap();
+    assert!(picked.is_empty());
+    let _: () = con.sadd("set", "a").unwrap();
+    let err = redis::cmd("SRANDMEMBER")
+        .arg("set")
+        .arg(i64::MIN)
+        .query::<Vec<String>>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.detail(), Some("value is out of range"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+#[cfg(unix)]
+fn default_protocol_per_listener() {
+    use crate::server::InvalidConfig;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::os::unix::net::UnixStream;
+
+    // read until the replies end with expected
+    fn read_until<S: Read>(stream: &mut S, expected: &[u8]) {
+        let mut replies = vec![];
+        let mut chunk = [0; 256];
+        while !replies.ends_with(expected) {
+            let len = stream.read(&mut chunk).unwrap();
+            assert!(len > 0, "{}", String::from_utf8_lossy(&replies));
+            replies.extend_from_slice(&chunk[..len]);
+        }
+    }
+    const GET: &[u8] = b"*2\r\n$3\r\nGET\r\n$7\r\nmissing\r\n";
+
+    let path = std::env::temp_dir().join("redisless-protocol.sock");
+    let config = ServerConfig {
+        protocol: 3,
+        unixsocket: Some(path.clone()),
+        ..ServerConfig::new(3517)
+    };
+    let server = Server::new_with_config(InMemoryStorage::default(), config);
+    assert_eq!(server.start(), Some(ServerState::Started));
+
+    // RESP3 without a HELLO on the TCP listener, until a HELLO sets RESP2
+    let mut tcp = TcpStream::connect("127.0.0.1:3517").unwrap();
+    tcp.write_all(GET).unwrap();
+    let mut reply = [0; 3];
+    tcp.read_exact(&mut reply).unwrap();
+    assert_eq!(&reply, b"_\r\n");
+    tcp.write_all(b"*2\r\n$5\r\nHELLO\r\n$1\r\n2\r\n").unwrap();
+    tcp.write_all(GET).unwrap();
+    read_until(&mut tcp, b"$-1\r\n");
+
+    // while the Unix socket keeps the RESP2 default
+    let mut unix = UnixStream::connect(&path).unwrap();
+    unix.write_all(GET).unwrap();
+    let mut reply = [0; 5];
+    unix.read_exact(&mut reply).unwrap();
+    assert_eq!(&reply, b"$-1\r\n");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    let config = ServerConfig {
+        unixsocket_protocol: 1,
+        ..ServerConfig::new(3517)
+    };
+    let err = Server::try_new_with_config(InMemoryStorage::default(), config)
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err,
+        InvalidConfig {
+            name: "unixsocket-protocol",
+            ..
+        }
+    ));
+}
+
+#[test]
+#[serial]
+fn scan_stripes_under_concurrent_writes() {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    // a stripe count which is not a power of two, so the index bits of the cursor are not
+    // all used
+    let config = ServerConfig {
+        keyspace_stripes: 5,
+        ..ServerConfig::new(3518)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    let stable: HashSet<String> = (0..500).map(|i| format!("stable{}", i)).collect();
+    for key in &stable {
+        let _: () = con.set(key, 1).unwrap();
+    }
+
+    // each stripe keeps growing and shrinking, while the scans lock one stripe at a time
+    let done = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let done = done.clone();
+        std::thread::spawn(move || {
+            let client = redis::Client::open("redis://127.0.0.1:3518/").unwrap();
+            let mut con = client.get_connection().unwrap();
+            let mut round = 0;
+            while !done.load(Ordering::SeqCst) {
+                let keys: Vec<_> = (0..1000).map(|i| format!("churn{}-{}", round, i)).collect();
+                for key in &keys {
+                    let _: () = con.set(key, 1).unwrap();
+                }
+                let mut pipe = redis::pipe();
+                for key in &keys {
+                    pipe.del(key).ignore();
+                }
+                let _: () = pipe.query(&mut con).unwrap();
+                round += 1;
+            }
+        })
+    };
+
+    for count in [1, 7, 100] {
+        let mut cursor = 0;
+        let mut returned: HashMap<String, usize> = HashMap::new();
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("COUNT")
+                .arg(count)
+                .query(&mut con)
+                .unwrap();
+            assert!(batch.len() <= count);
+            for key in batch {
+                *returned.entry(key).or_default() += 1;
+            }
+            match next {
+                0 => break,
+                // the 3 high bits are the stripe index
+                next => {
+                    assert!(next >> 61 < 5);
+                    cursor = next;
+                }
+            }
+        }
+        assert!(stable.iter().all(|key| returned.get(key) == Some(&1)));
+        assert!(returned
+            .keys()
+            .all(|key| stable.contains(key) || key.starts_with("churn")));
+    }
+
+    done.store(true, Ordering::SeqCst);
+    writer.join().unwrap();
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn passive_expiry_notification() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let config = ServerConfig {
+        notify_keyspace_events: "Ex".to_string(),
+        ..ServerConfig::new(3519)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    let mut subscriber = TcpStream::connect("127.0.0.1:3519").unwrap();
+    // a missing event fails the test rather than blocking it
+    subscriber
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let read_exact = |stream: &mut TcpStream, expected: &[u8]| {
+        let mut reply = vec![0; expected.len()];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&reply),
+            String::from_utf8_lossy(expected)
+        );
+    };
+    let expired = |key: &str| {
+        format!(
+            "*3\r\n$7\r\nmessage\r\n$22\r\n__keyevent@0__:expired\r\n${}\r\n{}\r\n",
+            key.len(),
+            key
+        )
+        .into_bytes()
+    };
+
+    subscriber
+        .write_all(b"*2\r\n$9\r\nSUBSCRIBE\r\n$22\r\n__keyevent@0__:expired\r\n")
+        .unwrap();
+    read_exact(
+        &mut subscriber,
+        b"*3\r\n$9\r\nsubscribe\r\n$22\r\n__keyevent@0__:expired\r\n:1\r\n",
+    );
+
+    let _: () = redis::cmd("DEBUG")
+        .arg("SET-ACTIVE-EXPIRE")
+        .arg("0")
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.pset_ex("first", "value", 50).unwrap();
+    let _: () = con.pset_ex("second", "value", 50).unwrap();
+    sleep(Duration::from_millis(100));
+
+    // the first access removes the key, and notifies it once
+    assert_eq!(con.get::<_, Option<String>>("first").unwrap(), None);
+    assert_eq!(con.get::<_, Option<String>>("first").unwrap(), None);
+    assert!(!con.exists::<_, bool>("first").unwrap());
+    read_exact(&mut subscriber, &expired("first"));
+
+    // so the next event is the one of another key
+    assert!(!con.exists::<_, bool>("second").unwrap());
+    read_exact(&mut subscriber, &expired("second"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn background_threads_end_with_server() {
+    let dir = std::env::temp_dir().join("redisless-stopped-save-points");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = ServerConfig {
+        dir: dir.clone(),
+        save: vec![(2, 1)],
+        ..ServerConfig::new(3520)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    let path = dir.join("dump.rdb");
+
+    // the save point is reached once stopped, where no thread checks it anymore
+    let _: () = con.set("key", "value").unwrap();
+    drop(con);
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+    sleep(Duration::from_millis(2500));
+    assert!(!path.exists());
+
+    // a restarted server checks it again
+    assert_eq!(server.start(), Some(ServerState::Started));
+    for _ in 0..50 {
+        if path.exists() {
+            break;
+        }
+        sleep(Duration::from_millis(20));
+    }
+    assert!(path.exists());
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+#[serial]
+fn subscription_count_across_channels_and_patterns() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, _) = get_redis_client_connection(3521);
+    let mut stream = TcpStream::connect("127.0.0.1:3521").unwrap();
+    let mut send = |request: &[u8], expected: &[u8]| {
+        stream.write_all(request).unwrap();
+        let mut reply = vec![0; expected.len()];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&reply),
+            String::from_utf8_lossy(expected)
+        );
+    };
+
+    send(
+        b"*2\r\n$9\r\nSUBSCRIBE\r\n$1\r\na\r\n",
+        b"*3\r\n$9\r\nsubscribe\r\n$1\r\na\r\n:1\r\n",
+    );
+    send(
+        b"*2\r\n$10\r\nPSUBSCRIBE\r\n$2\r\nb*\r\n",
+        b"*3\r\n$10\r\npsubscribe\r\n$2\r\nb*\r\n:2\r\n",
+    );
+    send(
+        b"*2\r\n$11\r\nUNSUBSCRIBE\r\n$1\r\na\r\n",
+        b"*3\r\n$11\r\nunsubscribe\r\n$1\r\na\r\n:1\r\n",
+    );
+    send(
+        b"*2\r\n$12\r\nPUNSUBSCRIBE\r\n$2\r\nb*\r\n",
+        b"*3\r\n$12\r\npunsubscribe\r\n$2\r\nb*\r\n:0\r\n",
+    );
+
+    // without any subscription left, the connection runs the other commands again
+    send(b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n", b"$-1\r\n");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn random_members_with_negative_counts() {
+    let config = ServerConfig {
+        seed: Some(42),
+        ..ServerConfig::new(3522)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    let members = ["a", "b", "c"];
+    let _: () = con
+        .hset_multiple("hash", &[("a", "1"), ("b", "2"), ("c", "3")])
+        .unwrap();
+    let _: () = con.sadd("set", &members[..]).unwrap();
+    let _: () = con
+        .zadd_multiple("zset", &[(1, "a"), (2, "b"), (3, "c")])
+        .unwrap();
+    let _: () = con.hset("one-hash", "a", "1").unwrap();
+    let _: () = con.sadd("one-set", "a").unwrap();
+    let _: () = con.zadd("one-zset", "a", 1).unwrap();
+
+    for (command, key) in [
+        ("HRANDFIELD", "hash"),
+        ("SRANDMEMBER", "set"),
+        ("ZRANDMEMBER", "zset"),
+    ] {
+        // more draws than members, so some of them are repeated
+        let picked: Vec<String> = redis::cmd(command)
+            .arg(&[key, "-5"])
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(picked.len(), 5, "{}", command);
+        assert!(picked
+            .iter()
+            .all(|member| members.contains(&member.as_str())));
+        let distinct: Vec<String> = redis::cmd(command)
+            .arg(&[key, "5"])
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(distinct.len(), 3, "{}", command);
+
+        let one = format!("one-{}", key);
+        let repeated: Vec<String> = redis::cmd(command)
+            .arg(&[one.as_str(), "-4"])
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(repeated, vec!["a"; 4], "{}", command);
+        let missing: Vec<String> = redis::cmd(command)
+            .arg(&["missing", "-4"])
+            .query(&mut con)
+            .unwrap();
+        assert!(missing.is_empty(), "{}", command);
+        let err = redis::cmd(command)
+            .arg(&[key, "-9223372036854775807"])
+            .query::<Vec<String>>(&mut con)
+            .unwrap_err();
+        assert_eq!(err.code(), Some("ERR"), "{}", command);
+    }
+
+    let pairs: Vec<(String, f64)> = redis::cmd("ZRANDMEMBER")
+        .arg(&["zset", "-4", "WITHSCORES"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(pairs.len(), 4);
+    for (member, score) in pairs {
+        assert_eq!(
+            score,
+            (members.iter().position(|m| *m == member).unwrap() + 1) as f64
+        );
+    }
+    let member: String = redis::cmd("ZRANDMEMBER")
+        .arg("one-zset")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(member, "a");
+    let err = redis::cmd("ZRANDMEMBER")
+        .arg("set")
+        .query::<String>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn noeviction_refuses_allocating_writes() {
+    use crate::storage::Storage;
+
+    let mut storage = InMemoryStorage::default();
+    storage.write(b"key1", b"value");
+    let usage = storage.used_memory();
+
+    let config = ServerConfig {
+        maxmemory: 2 * usage + usage / 2,
+        maxmemory_policy: MaxMemoryPolicy::NoEviction,
+        ..ServerConfig::new(3523)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+
+    // the key past maxmemory is written, nothing is evicted, then the writes are refused
+    for key in ["key1", "key2", "key3"] {
+        let _: () = con.set(key, "value").unwrap();
+    }
+    let err = con.set::<_, _, ()>("key4", "value").unwrap_err();
+    assert_eq!(err.code(), Some("OOM"));
+    let err = con.lpush::<_, _, ()>("list", "value").unwrap_err();
+    assert_eq!(err.code(), Some("OOM"));
+    let size: u64 = redis::cmd("DBSIZE").query(&mut con).unwrap();
+    assert_eq!(size, 3);
+
+    // the reads still run, and so do the commands which free memory
+    let value: String = con.get("key1").unwrap();
+    assert_eq!(value, "value");
+    let _: () = con.expire("key2", 100).unwrap();
+    let deleted: u64 = con.del("key1").unwrap();
+    assert_eq!(deleted, 1);
+    let _: () = con.set("key4", "value").unwrap();
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn sorted_set_encoding() {
+    let (server, mut con) = get_redis_client_connection(3524);
+    let encoding = |con: &mut Connection, key: &str| -> String {
+        redis::cmd("OBJECT")
+            .arg("ENCODING")
+            .arg(key)
+            .query(con)
+            .unwrap()
+    };
+
+    let _: () = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("zset-max-listpack-entries")
+        .arg("2")
+        .query(&mut con)
+        .unwrap();
+    let entries: Vec<String> = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("zset-max-listpack-entries")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(entries, vec!["zset-max-listpack-entries", "2"]);
+
+    let _: u64 = con.zadd_multiple("zset", &[(1, "a"), (2, "b")]).unwrap();
+    assert_eq!(encoding(&mut con, "zset"), "listpack");
+    let _: u64 = con.zadd("zset", "c", 3).unwrap();
+    assert_eq!(encoding(&mut con, "zset"), "skiplist");
+    // the conversion is one-way
+    let _: Vec<String> = redis::cmd("ZPOPMAX").arg("zset").query(&mut con).unwrap();
+    assert_eq!(encoding(&mut con, "zset"), "skiplist");
+
+    let _: u64 = con.zadd("long", "x".repeat(65), 1).unwrap();
+    assert_eq!(encoding(&mut con, "long"), "skiplist");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn debug_internal_stats() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    type Stats = (String, i64, String, Vec<i64>, String, i64, String, i64);
+    let stats = |con: &mut Connection| -> RedisResult<Stats> {
+        redis::cmd("DEBUG").arg("INTERNAL_STATS").query(con)
+    };
+
+    // refused unless the server is configured to expose its internals
+    let (server, mut con) = get_redis_client_connection(3525);
+    let err = stats(&mut con).unwrap_err();
+    assert!(err.to_string().contains("debug-internal-stats"), "{}", err);
+    drop(con);
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    let config = ServerConfig {
+        keyspace_stripes: 4,
+        debug_internal_stats: true,
+        ..ServerConfig::new(3525)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    for key in ["a", "b", "c"] {
+        let _: () = con.set(key, "value").unwrap();
+    }
+    let (_, shards, _, shard_keys, _, blocked, _, transactions) = stats(&mut con).unwrap();
+    assert_eq!(shards, 4);
+    assert_eq!(shard_keys.len(), 4);
+    assert_eq!(shard_keys.iter().sum::<i64>(), 3);
+    assert_eq!((blocked, transactions), (0, 0));
+
+    let mut stream = TcpStream::connect("127.0.0.1:3525").unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(b"*1\r\n$5\r\nMULTI\r\n").unwrap();
+    let mut reply = [0; 5];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(&reply, b"+OK\r\n");
+    let (.., transactions) = stats(&mut con).unwrap();
+    assert_eq!(transactions, 1);
+    stream.write_all(b"*1\r\n$7\r\nDISCARD\r\n").unwrap();
+    stream.read_exact(&mut reply).unwrap();
+    let (.., transactions) = stats(&mut con).unwrap();
+    assert_eq!(transactions, 0);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn zrangestore() {
+    let (server, mut con) = get_redis_client_connection(3526);
+    let store = |con: &mut Connection, args: &[&str]| -> RedisResult<u64> {
+        redis::cmd("ZRANGESTORE").arg(args).query(con)
+    };
+    // every member, there is no ZCARD nor ZRANGE
+    let members = |con: &mut Connection| -> Vec<String> {
+        redis::cmd("ZRANGEBYLEX")
+            .arg(&["dst", "-", "+"])
+            .query(con)
+            .unwrap()
+    };
+    let _: u64 = con
+        .zadd_multiple("src", &[(1, "a"), (2, "b"), (3, "c"), (4, "d")])
+        .unwrap();
+
+    assert_eq!(
+        store(&mut con, &["dst", "src", "2", "(4", "BYSCORE"]),
+        Ok(2)
+    );
+    assert_eq!(
+        con.zcount::<_, _, _, u64>("dst", "-inf", "+inf").unwrap(),
+        2
+    );
+    assert_eq!(members(&mut con), vec!["b", "c"]);
+
+    // by index, from the highest score with REV, and by member with a limit
+    assert_eq!(store(&mut con, &["dst", "src", "0", "1", "REV"]), Ok(2));
+    assert_eq!(members(&mut con), vec!["c", "d"]);
+    assert_eq!(
+        store(
+            &mut con,
+            &["dst", "src", "+", "[b", "BYLEX", "REV", "LIMIT", "1", "2"]
+        ),
+        Ok(2)
+    );
+    assert_eq!(members(&mut con), vec!["b", "c"]);
+
+    // an empty range removes the destination, whatever its type
+    let _: () = con.set("string", "value").unwrap();
+    assert_eq!(
+        store(&mut con, &["string", "src", "5", "6", "BYSCORE"]),
+        Ok(0)
+    );
+    assert!(!con.exists::<_, bool>("string").unwrap());
+    assert_eq!(store(&mut con, &["dst", "missing", "0", "-1"]), Ok(0));
+    assert!(!con.exists::<_, bool>("dst").unwrap());
+
+    let err = store(&mut con, &["dst", "src", "0", "1", "LIMIT", "0", "1"]).unwrap_err();
+    assert!(
+        err.to_string().contains("LIMIT is only supported"),
+        "{}",
+        err
+    );
+    let err = store(
+        &mut con,
+        &["dst", "string-missing", "0", "1", "BYSCORE", "BYLEX"],
+    );
+    assert_eq!(err.unwrap_err().kind(), redis::ErrorKind::ResponseError);
+    let _: () = con.set("string", "value").unwrap();
+    let err = store(&mut con, &["dst", "string", "0", "1"]).unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn lmove_blmove() {
+    let (server, mut con) = get_redis_client_connection(3527);
+    let lmove = |con: &mut Connection, args: &[&str]| -> RedisResult<Option<String>> {
+        redis::cmd("LMOVE").arg(args).query(con)
+    };
+    let list = |con: &mut Connection, key: &str| -> Vec<String> {
+        let len: isize = con.llen(key).unwrap();
+        (0..len)
+            .map(|index| con.lindex(key, index).unwrap())
+            .collect()
+    };
+
+    let _: () = con.rpush("src", &["a", "b", "c"]).unwrap();
+    assert_eq!(
+        lmove(&mut con, &["src", "dst", "LEFT", "RIGHT"]),
+        Ok(Some("a".into()))
+    );
+    assert_eq!(
+        lmove(&mut con, &["src", "dst", "right", "left"]),
+        Ok(Some("c".into()))
+    );
+    assert_eq!(list(&mut con, "dst"), vec!["c", "a"]);
+    // a list moved onto itself is rotated
+    assert_eq!(
+        lmove(&mut con, &["dst", "dst", "LEFT", "RIGHT"]),
+        Ok(Some("c".into()))
+    );
+    assert_eq!(list(&mut con, "dst"), vec!["a", "c"]);
+    assert_eq!(
+        lmove(&mut con, &["missing", "dst", "LEFT", "LEFT"]),
+        Ok(None)
+    );
+    let err = lmove(&mut con, &["src", "dst", "UP", "LEFT"]).unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::ResponseError);
+
+    // an element already there is moved at once
+    let moved: Option<String> = redis::cmd("BLMOVE")
+        .arg(&["src", "dst", "LEFT", "LEFT", "1"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(moved.as_deref(), Some("b"));
+    assert!(!con.exists::<_, bool>("src").unwrap());
+    let moved: Option<String> = redis::cmd("BLMOVE")
+        .arg(&["src", "dst", "LEFT", "LEFT", "0.2"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(moved, None);
+
+    // a blocked client is woken up by a push from another connection
+    let blocked = std::thread::spawn(|| {
+        let client = redis::Client::open("redis://127.0.0.1:3527/").unwrap();
+        let mut con = client.get_connection().unwrap();
+        let moved: Option<String> = redis::cmd("BLMOVE")
+            .arg(&["queue", "done", "RIGHT", "LEFT", "5"])
+            .query(&mut con)
+            .unwrap();
+        moved
+    });
+    sleep(Duration::from_millis(200));
+    let _: () = con.lpush("queue", "job").unwrap();
+    assert_eq!(blocked.join().unwrap().as_deref(), Some("job"));
+    assert!(!con.exists::<_, bool>("queue").unwrap());
+    assert_eq!(list(&mut con, "done"), vec!["job"]);
+
+    let _: () = con.set("string", "value").unwrap();
+    let err = redis::cmd("BLMOVE")
+        .arg(&["done", "string", "LEFT", "LEFT", "1"])
+        .query::<Option<String>>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn compressed_replies() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, mut con) = get_redis_client_connection(3528);
+    let value = "a compressible value, ".repeat(1000);
+    let _: () = con.set("key", &value).unwrap();
+    let _: () = con.set("short", "value").unwrap();
+
+    let mut stream = TcpStream::connect("127.0.0.1:3528").unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    // the header line, then the bytes of its length
+    let read_reply = |stream: &mut TcpStream| -> (u8, Vec<u8>) {
+        let mut header = vec![];
+        while !header.ends_with(b"\r\n") {
+            let mut byte = [0];
+            stream.read_exact(&mut byte).unwrap();
+            header.push(byte[0]);
+        }
+        let len: usize = std::str::from_utf8(&header[1..header.len() - 2])
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut data = vec![0; len + 2];
+        stream.read_exact(&mut data).unwrap();
+        data.truncate(len);
+        (header[0], data)
+    };
+
+    // a standard client gets the value as it is
+    stream.write_all(b"GET key\r\n").unwrap();
+    assert_eq!(read_reply(&mut stream), (b'$', value.clone().into_bytes()));
+
+    stream
+        .write_all(b"CLIENT REDISLESS-COMPRESS 1024\r\n")
+        .unwrap();
+    let mut ok = [0; 5];
+    stream.read_exact(&mut ok).unwrap();
+    assert_eq!(&ok, b"+OK\r\n");
+    stream.write_all(b"GET key\r\n").unwrap();
+    let (symbol, data) = read_reply(&mut stream);
+    assert_eq!(symbol, b'=');
+    assert_eq!(&data[..4], b"rlz:");
+    assert!(data.len() < value.len());
+    assert_eq!(
+        crate::decompress(&data[4..]),
+        Some(value.clone().into_bytes())
+    );
+    // under the threshold, a value is not compressed
+    stream.write_all(b"GET short\r\n").unwrap();
+    assert_eq!(read_reply(&mut stream), (b'$', b"value".to_vec()));
+
+    stream
+        .write_all(b"CLIENT REDISLESS-COMPRESS OFF\r\n")
+        .unwrap();
+    stream.read_exact(&mut ok).unwrap();
+    stream.write_all(b"GET key\r\n").unwrap();
+    assert_eq!(read_reply(&mut stream), (b'$', value.into_bytes()));
+
+    let err = redis::cmd("CLIENT")
+        .arg(&["REDISLESS-COMPRESS", "many"])
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("ERR"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn incrby_overflow() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, mut con) = get_redis_client_connection(3529);
+    let near_max = i64::MAX - 10;
+    let incr_by = |con: &mut Connection, command: &str, by: i64| -> RedisResult<i64> {
+        redis::cmd(command).arg("key").arg(by).query(con)
+    };
+
+    assert_eq!(incr_by(&mut con, "INCRBY", near_max), Ok(near_max));
+    let err = incr_by(&mut con, "INCRBY", near_max).unwrap_err();
+    assert_eq!(err.detail(), Some("increment or decrement would overflow"));
+    // the value is left as it was
+    assert_eq!(con.get("key"), Ok(near_max));
+
+    let _: () = con.set("key", -near_max).unwrap();
+    let err = incr_by(&mut con, "DECRBY", near_max).unwrap_err();
+    assert_eq!(err.detail(), Some("increment or decrement would overflow"));
+    // the opposite of the minimum is out of range too
+    let _: () = con.set("key", 0).unwrap();
+    let err = incr_by(&mut con, "DECRBY", i64::MIN).unwrap_err();
+    assert_eq!(err.detail(), Some("increment or decrement would overflow"));
+    assert_eq!(con.get("key"), Ok(0));
+
+    // a RESP3 client gets the error as well, rather than a big number
+    let _: () = con.set("key", near_max).unwrap();
+    let mut stream = TcpStream::connect("127.0.0.1:3529").unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(b"HELLO 3\r\nPING\r\n").unwrap();
+    let mut reply = vec![];
+    while !reply.ends_with(b"+PONG\r\n") {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).unwrap();
+        reply.push(byte[0]);
+    }
+    let request = format!("INCRBY key {}\r\n", near_max);
+    stream.write_all(request.as_bytes()).unwrap();
+    let expected = b"-ERR increment or decrement would overflow\r\n";
+    let mut reply = vec![0; expected.len()];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(reply, expected);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn for_each_key() {
+    use crate::storage::models::ValueRef;
+
+    let config = ServerConfig {
+        keyspace_stripes: 4,
+        ..ServerConfig::new(3530)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    for i in 0..1000 {
+        let _: () = con.set(format!("key{}", i), i).unwrap();
+    }
+    let _: () = con.rpush("list", &["a", "b"]).unwrap();
+    let _: () = con.set_ex("ttl", "value", 100).unwrap();
+    let _: () = con.pset_ex("expired", "value", 1).unwrap();
+    let _: () = redis::cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let _: () = con.set("other", "value").unwrap();
+    sleep(Duration::from_millis(10));
+
+    let mut visited = HashMap::new();
+    server.for_each_key(0, |key, value| {
+        *visited.entry(key.to_vec()).or_insert(0) += 1;
+        match (key, value) {
+            (b"list", ValueRef::List(values)) => assert_eq!(values.len(), 2),
+            (b"list", value) => panic!("unexpected value {:?}", value),
+            (_, ValueRef::String(_)) => {}
+            (_, value) => panic!("unexpected value {:?}", value),
+        }
+    });
+    assert_eq!(visited.len(), 1002);
+    assert!(visited.values().all(|count| *count == 1));
+    assert!(visited.contains_key(&b"ttl"[..]));
+    assert!(!visited.contains_key(&b"expired"[..]));
+    assert!(!visited.contains_key(&b"other"[..]));
+
+    let mut keys = vec![];
+    server.for_each_key(1, |key, _| keys.push(key.to_vec()));
+    assert_eq!(keys, vec![b"other".to_vec()]);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn concurrent_publishers() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    use crate::{Resp, RespDecoder};
+
+    const PUBLISHERS: usize = 4;
+    const MESSAGES: usize = 250;
+    let (server, _) = get_redis_client_connection(3531);
+    let mut subscriber = TcpStream::connect("127.0.0.1:3531").unwrap();
+    subscriber
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    subscriber.write_all(b"SUBSCRIBE news\r\n").unwrap();
+    let subscribed = b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n";
+    let mut reply = vec![0; subscribed.len()];
+    subscriber.read_exact(&mut reply).unwrap();
+    assert_eq!(reply, subscribed);
+
+    // long messages holding CRLFs, so a frame interleaved with another would not parse
+    let message = |publisher: usize, index: usize| {
+        format!("{}:{}:{}", publisher, index, "\r\n-".repeat(1000 + index))
+    };
+    let publishers: Vec<_> = (0..PUBLISHERS)
+        .map(|publisher| {
+            std::thread::spawn(move || {
+                let client = redis::Client::open("redis://127.0.0.1:3531/").unwrap();
+                let mut con = client.get_connection().unwrap();
+                for index in 0..MESSAGES {
+                    let _: u64 = con.publish("news", message(publisher, index)).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    let mut decoder = RespDecoder::new();
+    // index of the next message expected from each publisher
+    let mut next = [0; PUBLISHERS];
+    let mut chunk = [0; 16 * 1024];
+    loop {
+        while let Some(frame) = decoder.next_frame() {
+            let payload = match frame.unwrap() {
+                Resp::Array(elements) => match elements.as_slice() {
+                    [Resp::BulkString(b"message"), Resp::BulkString(b"news"), Resp::BulkString(payload)] => {
+                        String::from_utf8(payload.to_vec()).unwrap()
+                    }
+                    elements => panic!("unexpected message {:?}", elements),
+                },
+                frame => panic!("unexpected frame {:?}", frame),
+            };
+            let publisher: usize = payload.split(':').next().unwrap().parse().unwrap();
+            // the messages of a publisher are received in the order they were published
+            assert_eq!(payload, message(publisher, next[publisher]));
+            next[publisher] += 1;
+        }
+        if next.iter().sum::<usize>() == PUBLISHERS * MESSAGES {
+            break;
+        }
+        let len = subscriber.read(&mut chunk).unwrap();
+        assert!(len > 0);
+        decoder.feed(&chunk[..len]);
+    }
+    assert_eq!(next, [MESSAGES; PUBLISHERS]);
+    assert_eq!(decoder.pending(), 0);
+    for publisher in publishers {
+        publisher.join().unwrap();
+    }
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn emptied_collections_are_removed() {
+    let (server, mut con) = get_redis_client_connection(3532);
+    let removed = |con: &mut Connection, key: &str| {
+        assert_eq!(con.exists(key), Ok(false), "{}", key);
+        let keytype: String = redis::cmd("TYPE").arg(key).query(con).unwrap();
+        assert_eq!(keytype, "none");
+        let size: u64 = redis::cmd("DBSIZE").query(con).unwrap();
+        assert_eq!(size, 0);
+    };
+
+    let _: () = con.rpush("list", &["a", "a"]).unwrap();
+    let _: u64 = con.lrem("list", -2, "a").unwrap();
+    removed(&mut con, "list");
+    let _: () = con.rpush("list", "a").unwrap();
+    let _: String = con.lpop("list").unwrap();
+    removed(&mut con, "list");
+    let _: () = con.rpush("list", &["a", "b"]).unwrap();
+    let _: () = con.ltrim("list", 2, 3).unwrap();
+    removed(&mut con, "list");
+
+    let _: () = con.sadd("set", &["a", "b"]).unwrap();
+    let _: u64 = con.srem("set", &["a", "b"]).unwrap();
+    removed(&mut con, "set");
+    let _: () = con.sadd("set", "a").unwrap();
+    let _: String = con.spop("set").unwrap();
+    removed(&mut con, "set");
+
+    let _: () = con.hset("hash", "field", "value").unwrap();
+    let _: Vec<i64> = redis::cmd("HEXPIRE")
+        .arg(&["hash", "0", "FIELDS", "1", "field"])
+        .query(&mut con)
+        .unwrap();
+    removed(&mut con, "hash");
+
+    let _: () = con.zadd("zset", "a", 1).unwrap();
+    let _: Vec<String> = con.zpopmin("zset", 1).unwrap();
+    removed(&mut con, "zset");
+    let _: () = con.zadd("zset", "a", 1).unwrap();
+    let _: u64 = redis::cmd("ZRANGESTORE")
+        .arg(&["zset", "zset", "5", "10"])
+        .query(&mut con)
+        .unwrap();
+    removed(&mut con, "zset");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn brpoplpush() {
+    let (server, mut con) = get_redis_client_connection(3533);
+    let brpoplpush = |con: &mut Connection, timeout: &str| -> Option<String> {
+        redis::cmd("BRPOPLPUSH")
+            .arg(&["queue", "processing", timeout])
+            .query(con)
+            .unwrap()
+    };
+
+    // the tail of the source is moved to the head of the destination
+    let _: () = con.rpush("queue", &["a", "b"]).unwrap();
+    let _: () = con.rpush("processing", "c").unwrap();
+    assert_eq!(brpoplpush(&mut con, "1").as_deref(), Some("b"));
+    assert_eq!(con.lindex("processing", 0), Ok("b".to_string()));
+    assert_eq!(brpoplpush(&mut con, "1").as_deref(), Some("a"));
+    assert!(!con.exists::<_, bool>("queue").unwrap());
+    assert_eq!(brpoplpush(&mut con, "0.2"), None);
+
+    // a blocked client is woken up by a push from another connection
+    let blocked = std::thread::spawn(move || {
+        let client = redis::Client::open("redis://127.0.0.1:3533/").unwrap();
+        let mut con = client.get_connection().unwrap();
+        brpoplpush(&mut con, "5")
+    });
+    sleep(Duration::from_millis(200));
+    let _: () = con.lpush("queue", "job").unwrap();
+    assert_eq!(blocked.join().unwrap().as_deref(), Some("job"));
+    assert!(!con.exists::<_, bool>("queue").unwrap());
+    assert_eq!(con.llen("processing"), Ok(4));
+    assert_eq!(con.lindex("processing", 0), Ok("job".to_string()));
+
+    let err = redis::cmd("BRPOPLPUSH")
+        .arg(&["queue", "processing", "-1"])
+        .query::<Option<String>>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.code(), Some("ERR"));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn expire_not_positive_deletes() {
+    let (server, mut con) = get_redis_client_connection(3534);
+    let expire = |con: &mut Connection, command: &str, key: &str, ttl: i64| -> RedisResult<u32> {
+        redis::cmd(command).arg(key).arg(ttl).query(con)
+    };
+
+    let _: () = con.set("key", "v").unwrap();
+    assert_eq!(expire(&mut con, "EXPIRE", "key", -1), Ok(1));
+    assert_eq!(con.exists("key"), Ok(false));
+    assert_eq!(expire(&mut con, "EXPIRE", "key", -1), Ok(0));
+
+    let _: () = con.rpush("list", "a").unwrap();
+    assert_eq!(expire(&mut con, "PEXPIRE", "list", 0), Ok(1));
+    assert_eq!(con.exists("list"), Ok(false));
+    let _: () = con.set("key", "v").unwrap();
+    assert_eq!(expire(&mut con, "PEXPIRE", "key", i64::MIN), Ok(1));
+    let size: u64 = redis::cmd("DBSIZE").query(&mut con).unwrap();
+    assert_eq!(size, 0);
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn list_pop_count() {
+    let (server, mut con) = get_redis_client_connection(3535);
+    let pop = |con: &mut Connection, command: &str, key: &str, count: i64| {
+        redis::cmd(command)
+            .arg(key)
+            .arg(count)
+            .query::<redis::Value>(con)
+    };
+
+    let _: () = con.rpush("list", &["a", "b", "c", "d"]).unwrap();
+    for command in ["LPOP", "RPOP"] {
+        // an empty array for a count of 0, and the list is left as it was
+        assert_eq!(
+            pop(&mut con, command, "list", 0),
+            Ok(redis::Value::Bulk(vec![]))
+        );
+        // a missing key is nil, even with a count
+        assert_eq!(pop(&mut con, command, "missing", 5), Ok(redis::Value::Nil));
+        let err = pop(&mut con, command, "list", -1).unwrap_err();
+        assert_eq!(
+            err.detail(),
+            Some("value is out of range, must be positive")
+        );
+    }
+    assert_eq!(con.llen("list"), Ok(4));
+
+    let popped: Vec<String> = redis::cmd("LPOP")
+        .arg(&["list", "2"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(popped, vec!["a", "b"]);
+    // no more elements than the list holds
+    let popped: Vec<String> = redis::cmd("RPOP")
+        .arg(&["list", "5"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(popped, vec!["d", "c"]);
+    assert_eq!(con.exists("list"), Ok(false));
+    // without a count, a single element or nil
+    let _: () = con.rpush("list", "a").unwrap();
+    assert_eq!(con.rpop("list"), Ok(Some("a".to_string())));
+    assert_eq!(con.lpop("list"), Ok(None::<String>));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn command_capture_file() {
+    use crate::protocol::parser::RedisProtocolParser;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let path = std::env::temp_dir().join("redisless-capture.bin");
+    let _ = std::fs::remove_file(&path);
+    let config = ServerConfig {
+        command_capture_file: Some(path.clone()),
+        ..ServerConfig::new(3536)
+    };
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    let _: () = con.set("key", "value").unwrap();
+    let _: () = con.rpush("list", &["a", "b", "c"]).unwrap();
+    // a read is captured too, even though it changes nothing
+    assert_eq!(con.get("key"), Ok("value".to_string()));
+    let _: () = con.incr("counter", 2).unwrap();
+
+    let mut stream = TcpStream::connect("127.0.0.1:3536").unwrap();
+    let mut reply = [0; 64];
+    stream.write_all(b"SET inline value\r\n").unwrap();
+    let n = stream.read(&mut reply).unwrap();
+    assert_eq!(&reply[..n], b"+OK\r\n");
+    // a malformed frame is captured as the bytes the parser gave up on
+    stream.write_all(b"*1\r\n$x\r\n").unwrap();
+    let n = stream.read(&mut reply).unwrap();
+    assert!(reply[..n].starts_with(b"-ERR Protocol error"));
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+
+    let bytes = std::fs::read(&path).unwrap();
+    let mut records = vec![];
+    let mut rest = &bytes[..];
+    while !rest.is_empty() {
+        let len = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        records.push(rest[4..4 + len].to_vec());
+        rest = &rest[4 + len..];
+    }
+    assert_eq!(records.len(), 6);
+    assert_eq!(records[4], b"SET inline value\r\n");
+    assert_eq!(records[5], b"*1\r\n$x\r\n");
+    let parsable = records
+        .iter()
+        .filter(|record| RedisProtocolParser::parse_request(record, usize::MAX).is_ok())
+        .collect::<Vec<_>>();
+    assert_eq!(parsable.len(), 5);
+
+    // replayed to another server, the parsable requests give the same data set
+    let (server, mut con) = get_redis_client_connection(3536);
+    let mut stream = TcpStream::connect("127.0.0.1:3536").unwrap();
+    for record in parsable {
+        stream.write_all(record).unwrap();
+        let _ = stream.read(&mut reply).unwrap();
+    }
+    assert_eq!(con.get("key"), Ok("value".to_string()));
+    assert_eq!(con.get("inline"), Ok("value".to_string()));
+    assert_eq!(con.get("counter"), Ok(2));
+    assert_eq!(con.llen("list"), Ok(3));
+    // the server without a capture file captured nothing
+    assert_eq!(std::fs::read(&path).unwrap().len(), bytes.len());
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+#[serial]
+fn resp3_maps() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, mut con) = get_redis_client_connection(3537);
+    let _: () = con
+        .hset_multiple("hash", &[("a", "1"), ("b", "2")])
+        .unwrap();
+    // a RESP2 client still gets the flat arrays
+    let entries: Vec<String> = redis::cmd("HGETALL").arg("hash").query(&mut con).unwrap();
+    assert_eq!(entries.len(), 4);
+    let parameter: Vec<String> = redis::cmd("CONFIG")
+        .arg(&["GET", "port"])
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(parameter, vec!["port", "3537"]);
+
+    let mut stream = TcpStream::connect("127.0.0.1:3537").unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(b"HELLO 3\r\nPING\r\n").unwrap();
+    let mut reply = vec![];
+    while !reply.ends_with(b"+PONG\r\n") {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).unwrap();
+        reply.push(byte[0]);
+    }
+    let mut read_reply = |request: &[u8], len| {
+        stream.write_all(request).unwrap();
+        let mut reply = vec![0; len];
+        stream.read_exact(&mut reply).unwrap();
+        reply
+    };
+
+    // the fields are in no particular order
+    let reply = read_reply(b"HGETALL hash\r\n", 32);
+    assert!(reply.starts_with(b"%2\r\n"));
+    for pair in [b"$1\r\na\r\n$1\r\n1\r\n", b"$1\r\nb\r\n$1\r\n2\r\n"] {
+        assert!(reply.windows(pair.len()).any(|window| window == pair));
+    }
+    let expected = b"%1\r\n$4\r\nport\r\n$4\r\n3537\r\n";
+    assert_eq!(read_reply(b"CONFIG GET port\r\n", expected.len()), expected);
+    // an empty map for a missing hash or an unknown parameter
+    assert_eq!(read_reply(b"HGETALL missing\r\n", 4), b"%0\r\n");
+    assert_eq!(read_reply(b"CONFIG GET unknown\r\n", 4), b"%0\r\n");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn shutdown_saves() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let dir = std::env::temp_dir().join("redisless-shutdown");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = ServerConfig {
+        dir: dir.clone(),
+        save: vec![(3600, 1)],
+        ..ServerConfig::new(3538)
+    };
+    let path = config.snapshot_path();
+    // the connection is closed without a reply, then the server stops listening
+    let shutdown = |request: &[u8]| {
+        let mut stream = TcpStream::connect("127.0.0.1:3538").unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream.write_all(request).unwrap();
+        let mut reply = vec![];
+        stream.read_to_end(&mut reply).unwrap();
+        assert_eq!(reply, b"+PONG\r\n");
+        for _ in 0..100 {
+            if TcpStream::connect("127.0.0.1:3538").is_err() {
+                return;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        panic!("the server still listens after SHUTDOWN");
+    };
+
+    let (server, mut con) = get_redis_client_connection_with_config(config);
+    let err = redis::cmd("SHUTDOWN")
+        .arg("LATER")
+        .query::<String>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.detail(), Some("syntax error"));
+    let _: () = con.set("key", "value").unwrap();
+    // with a save point, a snapshot is written before the server stops
+    shutdown(b"PING\r\nSHUTDOWN\r\n");
+    assert!(path.exists());
+    // the other clients are disconnected
+    assert!(con.set::<_, _, ()>("key", "other").is_err());
+
+    // NOSAVE skips the snapshot even with a save point
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(server.start(), Some(ServerState::Started));
+    shutdown(b"PING\r\nSHUTDOWN NOSAVE\r\n");
+    assert!(!path.exists());
+
+    // without a save point only SAVE writes a snapshot
+    let config = ServerConfig {
+        dir: dir.clone(),
+        ..ServerConfig::new(3538)
+    };
+    let (server, _) = get_redis_client_connection_with_config(config);
+    shutdown(b"PING\r\nSHUTDOWN\r\n");
+    assert!(!path.exists());
+    assert_eq!(server.start(), Some(ServerState::Started));
+    shutdown(b"PING\r\nSHUTDOWN SAVE\r\n");
+    assert!(path.exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+#[serial]
+fn pipelined_transaction() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, mut con) = get_redis_client_connection(3539);
+    let mut stream = TcpStream::connect("127.0.0.1:3539").unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let read_reply = |stream: &mut TcpStream, expected: &[u8]| {
+        let mut reply = vec![0; expected.len()];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&reply),
+            String::from_utf8_lossy(expected)
+        );
+    };
+
+    // pipelined, every command is replied in order
+    stream
+        .write_all(b"MULTI\r\nSET a 1\r\nINCR a\r\nEXEC\r\n")
+        .unwrap();
+    read_reply(
+        &mut stream,
+        b"+OK\r\n+QUEUED\r\n+QUEUED\r\n*2\r\n+OK\r\n:2\r\n",
+    );
+
+    // one at a time, each queued command is replied before the next is sent
+    for (request, expected) in [
+        (&b"MULTI\r\n"[..], &b"+OK\r\n"[..]),
+        (b"INCR a\r\n", b"+QUEUED\r\n"),
+        (b"GET a\r\n", b"+QUEUED\r\n"),
+        (b"EXEC\r\n", b"*2\r\n:3\r\n$1\r\n3\r\n"),
+    ] {
+        stream.write_all(request).unwrap();
+        read_reply(&mut stream, expected);
+    }
+
+    // as a client library sends an atomic pipeline
+    let (value, incremented): (String, i64) = redis::pipe()
+        .atomic()
+        .set("b", 1)
+        .ignore()
+        .incr("b", 1)
+        .ignore()
+        .get("b")
+        .incr("b", 1)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!((value.as_str(), incremented), ("2", 3));
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
+
+#[test]
+#[serial]
+fn resp3_doubles() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let (server, mut con) = get_redis_client_connection(3540);
+    let _: () = con.zadd("zset", "member", 1.5).unwrap();
+    // a RESP2 client still gets bulk strings
+    assert_eq!(con.zscore("zset", "member"), Ok("1.5".to_string()));
+
+    let mut stream = TcpStream::connect("127.0.0.1:3540").unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(b"HELLO 3\r\nPING\r\n").unwrap();
+    let mut reply = vec![];
+    while !reply.ends_with(b"+PONG\r\n") {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).unwrap();
+        reply.push(byte[0]);
+    }
+    let read_reply = |stream: &mut TcpStream, request: &[u8], expected: &[u8]| {
+        stream.write_all(request).unwrap();
+        let mut reply = vec![0; expected.len()];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&reply),
+            String::from_utf8_lossy(expected)
+        );
+    };
+
+    read_reply(&mut stream, b"ZSCORE zset member\r\n", b",1.5\r\n");
+    read_reply(&mut stream, b"ZINCRBY zset 2 member\r\n", b",3.5\r\n");
+    read_reply(&mut stream, b"ZADD zset INCR -inf member\r\n", b",-inf\r\n");
+    read_reply(
+        &mut stream,
+        b"ZMSCORE zset member missing\r\n",
+        b"*2\r\n,-inf\r\n_\r\n",
+    );
+    read_reply(&mut stream, b"ZSCORE zset missing\r\n", b"_\r\n");
+
+    assert_eq!(server.stop(), Some(ServerState::Stopped));
+}
@@ -0,0 +1,112 @@
+//! Client side caching.
+//!
+//! A client which turned `CLIENT TRACKING` on is told when a key it read may have changed, so
+//! it can drop the value it cached. Every key read by such a client is remembered until it is
+//! invalidated once: the client has to read it again to be told about the next change.
+//!
+//! Invalidations are sent as messages of the `__redis__:invalidate` channel, the way Redis
+//! sends them to a RESP2 client, into the message queue of the connection.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::protocol::response::{RedisResponse, RedisResponseType};
+use crate::storage::models::RedisString;
+
+use super::pubsub::{MessageSender, Subscriber};
+use super::util::lock_then_release;
+
+const INVALIDATE_CHANNEL: &[u8] = b"__redis__:invalidate";
+
+// message queues of the clients which read a key, by subscriber id
+type Readers = HashMap<u64, MessageSender>;
+
+/// Keys read by the tracking clients, by database
+#[derive(Default)]
+pub struct Tracking {
+    // by db and key
+    keys: Mutex<HashMap<(usize, RedisString), Readers>>,
+}
+
+impl Tracking {
+    /// Whether a key is tracked at all, so that the writes have something to invalidate
+    pub fn is_active(&self) -> bool {
+        !lock_then_release(&self.keys).is_empty()
+    }
+
+    /// Remember that the client of subscriber read keys from the db
+    pub fn track(&self, db: usize, keys: Vec<RedisString>, subscriber: &Subscriber) {
+        let mut tracked = lock_then_release(&self.keys);
+        for key in keys {
+            tracked
+                .entry((db, key))
+                .or_default()
+                .insert(subscriber.id(), subscriber.sender());
+        }
+    }
+
+    /// Tell the clients which read keys from the db that they changed
+    pub fn invalidate(&self, db: usize, keys: &[RedisString]) {
+        let mut tracked = lock_then_release(&self.keys);
+        if tracked.is_empty() {
+            return;
+        }
+
+        // every client is sent a single message with all of its keys
+        let mut invalidated: HashMap<u64, (MessageSender, Vec<RedisString>)> = HashMap::new();
+        for key in keys {
+            if let Some(clients) = tracked.remove(&(db, key.clone())) {
+                for (id, sender) in clients {
+                    invalidated
+                        .entry(id)
+                        .or_insert_with(|| (sender, vec![]))
+                        .1
+                        .push(key.clone());
+                }
+            }
+        }
+        for (sender, keys) in invalidated.into_values() {
+            let keys = keys
+                .into_iter()
+                .map(RedisResponseType::BulkString)
+                .collect();
+            sender.send(invalidation(RedisResponseType::Array(keys)));
+        }
+    }
+
+    /// Tell the clients which read keys from the db, or from any db when `None`, that all
+    /// of them changed, as after a flush
+    pub fn invalidate_all(&self, db: Option<usize>) {
+        let mut tracked = lock_then_release(&self.keys);
+        let mut invalidated = HashMap::new();
+        tracked.retain(|(key_db, _), clients| {
+            if db.is_some_and(|db| db != *key_db) {
+                return true;
+            }
+            invalidated.extend(clients.drain());
+            false
+        });
+        for sender in invalidated.into_values() {
+            sender.send(invalidation(RedisResponseType::Nil));
+        }
+    }
+
+    /// Forget the keys read by the client of subscriber, which stopped tracking
+    pub fn forget(&self, subscriber: &Subscriber) {
+        lock_then_release(&self.keys).retain(|_, clients| {
+            clients.remove(&subscriber.id());
+            !clients.is_empty()
+        });
+    }
+}
+
+// keys is the array of the invalidated keys, or nil when all of them are
+fn invalidation(keys: RedisResponseType) -> Vec<u8> {
+    use RedisResponseType::BulkString;
+    RedisResponse::array(vec![
+        BulkString(b"message".to_vec()),
+        BulkString(INVALIDATE_CHANNEL.to_vec()),
+        keys,
+    ])
+    .reply()
+}
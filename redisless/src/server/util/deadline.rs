@@ -0,0 +1,23 @@
+use std::time::{Duration, Instant};
+
+/// Point in time after which a command should give up, see `ServerConfig::command_time_limit`
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    at: Option<Instant>,
+}
+
+impl Deadline {
+    /// No deadline when time_limit is `None`
+    pub fn after(time_limit: Option<Duration>) -> Self {
+        Deadline {
+            at: time_limit.map(|limit| Instant::now() + limit),
+        }
+    }
+
+    pub fn is_exceeded(&self) -> bool {
+        match self.at {
+            Some(at) => Instant::now() >= at,
+            None => false,
+        }
+    }
+}
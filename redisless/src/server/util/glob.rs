@@ -0,0 +1,83 @@
+/// Match string against a glob-style pattern, with the same rules as Redis:
+///
+/// - `*` matches any sequence of bytes, `?` matches a single byte
+/// - `[abc]`, `[a-z]` and `[^abc]` match a byte of (or not of) the class
+/// - `\` escapes the next byte
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => string.is_empty(),
+        Some((b'*', rest)) => {
+            // consecutive stars match the same as a single one
+            let rest = trim_leading_stars(rest);
+            if rest.is_empty() {
+                return true;
+            }
+            (0..=string.len()).any(|start| glob_match(rest, &string[start..]))
+        }
+        Some((b'?', rest)) => match string.split_first() {
+            Some((_, string)) => glob_match(rest, string),
+            None => false,
+        },
+        Some((b'[', rest)) => match string.split_first() {
+            Some((byte, string)) => match match_class(rest, *byte) {
+                (true, rest) => glob_match(rest, string),
+                (false, _) => false,
+            },
+            None => false,
+        },
+        Some((b'\\', rest)) if !rest.is_empty() => match string.split_first() {
+            Some((byte, string)) => *byte == rest[0] && glob_match(&rest[1..], string),
+            None => false,
+        },
+        Some((expected, rest)) => match string.split_first() {
+            Some((byte, string)) => byte == expected && glob_match(rest, string),
+            None => false,
+        },
+    }
+}
+
+fn trim_leading_stars(mut pattern: &[u8]) -> &[u8] {
+    while let Some((b'*', rest)) = pattern.split_first() {
+        pattern = rest;
+    }
+    pattern
+}
+
+/// Match byte against the class starting right after a `[`, and return the pattern left
+/// after the closing `]`. An unterminated class extends to the end of the pattern.
+fn match_class(pattern: &[u8], byte: u8) -> (bool, &[u8]) {
+    let (negate, mut pattern) = match pattern.split_first() {
+        Some((b'^', rest)) => (true, rest),
+        _ => (false, pattern),
+    };
+
+    let mut matched = false;
+    loop {
+        match pattern {
+            [] => break,
+            [b']', rest @ ..] => {
+                pattern = rest;
+                break;
+            }
+            [b'\\', escaped, rest @ ..] => {
+                matched |= *escaped == byte;
+                pattern = rest;
+            }
+            [start, b'-', end, rest @ ..] if *end != b']' => {
+                let (low, high) = if start <= end {
+                    (*start, *end)
+                } else {
+                    (*end, *start)
+                };
+                matched |= (low..=high).contains(&byte);
+                pattern = rest;
+            }
+            [class_byte, rest @ ..] => {
+                matched |= *class_byte == byte;
+                pattern = rest;
+            }
+        }
+    }
+
+    (matched != negate, pattern)
+}
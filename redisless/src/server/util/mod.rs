@@ -1,27 +1,50 @@
+mod deadline;
+mod glob;
 mod run_command;
+mod sample;
+mod scan;
 // re-export run_command
 use crossbeam_channel::{Receiver, Sender};
+pub use deadline::Deadline;
+pub use glob::glob_match;
 pub use run_command::*;
+pub use sample::{sample, sample_one};
+pub use scan::{key_hash, scan, scan_by};
 
-use crate::server::ServerState;
+use crate::server::client::Queued;
+use crate::server::eviction::evict;
+use crate::server::expiration::expire_key;
+use crate::server::latency::COMMAND_EVENT;
+use crate::server::stats::{self, Outcome};
+use crate::server::{Client, ServerContext, ServerState};
 
 use std::{
-    io::{BufReader, Read, Write},
-    net::TcpStream,
-    sync::{Arc, Mutex, MutexGuard},
+    io::{ErrorKind, Read, Write},
+    sync::{atomic::Ordering, Mutex, MutexGuard},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use crate::{
-    command::{command_error::RedisCommandError, Command},
-    protocol::{self, parser::RedisProtocolParser, Resp},
-    storage::Storage,
+    command::{
+        command_error::RedisCommandError, denies_oom, is_write, key_positions, resolve_renamed,
+        Command,
+    },
+    protocol::{
+        self,
+        parser::RedisProtocolParser,
+        response::{RedisResponse, RedisResponseType},
+        Resp,
+    },
+    storage::{models::RedisString, Storage},
 };
 
 use super::{CloseConnection, ReceivedDataLength};
 
-pub fn lock_then_release<T: Storage>(storage: &Arc<Mutex<T>>) -> MutexGuard<T> {
+// bytes read from a connection at once
+const READ_CHUNK_SIZE: usize = 16 * 1024;
+
+pub fn lock_then_release<T>(storage: &Mutex<T>) -> MutexGuard<'_, T> {
     loop {
         match storage.lock() {
             Ok(storage) => {
@@ -46,51 +69,408 @@ pub fn stop_sig_received(recv: &Receiver<ServerState>, sender: &Sender<ServerSta
     false
 }
 
-pub fn get_command(bytes: &[u8; 512]) -> Result<Command, RedisCommandError> {
-    match RedisProtocolParser::parse(bytes) {
-        Ok((Resp::Array(v), _)) => match Command::parse(v) {
-            Ok(command) => Ok(command),
-            Err(err) => Err(err),
-        },
-        Err(err) => Err(RedisCommandError::ProtocolParse(err)),
-        _ => Err(RedisCommandError::CommandNotFound),
-    }
-}
+pub fn handle_request<T: Storage, S: Read + Write>(
+    context: &ServerContext<T>,
+    client: &mut Client,
+    stream: &mut S,
+    buffer: &mut Vec<u8>,
+) -> (CloseConnection, ReceivedDataLength) {
+    let mut chunk = [0; READ_CHUNK_SIZE];
+    let received = match stream.read(&mut chunk) {
+        // the read timeout of a subscribed client, nothing was received
+        Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+            return (false, 0);
+        }
+        Ok(0) | Err(_) => {
+            // the client closed the connection. Every complete command of the buffer already
+            // ran, what is left is a request cut short, which is dropped.
+            if !buffer.is_empty() {
+                log::warn!(
+                    "client {}: closed within a request, {} bytes dropped",
+                    client.id,
+                    buffer.len()
+                );
+                buffer.clear();
+            }
+            return (true, 0);
+        }
+        Ok(received) => received,
+    };
+    // the buffer only grows with the bytes actually received, never with a declared length
+    buffer.extend_from_slice(&chunk[..received]);
+
+    let max_bulk_len = context.config().proto_max_bulk_len;
+    let mut reply = vec![];
+    let mut quit = false;
+
+    // copied, the config is not held while the commands run
+    let renames = context.config().rename_commands.clone();
 
-fn get_bytes_from_request(stream: &TcpStream) -> ([u8; 512], usize) {
-    let mut buf_reader = BufReader::new(stream);
-    let mut buf = [0; 512];
-    let mut buf_length = 0_usize;
+    // run every complete command of the buffer, a partial one waits for the next read
+    while !quit {
+        // a renamed write command is sent to replicas under its original name
+        let mut original_request = None;
+        // keys of the command, removed before it runs once expired
+        let mut keys = vec![];
+        // name of a command sent as an array, see `stats::command_name`
+        let mut name = None;
+        // an inline request is sent to the AOF and the replicas as an array
+        let inline = !buffer.is_empty() && buffer[0] != b'*';
+        let (command, consumed, write) =
+            match RedisProtocolParser::parse_request(buffer, max_bulk_len) {
+                // as in Redis, an empty request gets no reply
+                Ok((Resp::Array(v), left)) if v.is_empty() => {
+                    let consumed = buffer.len() - left.len();
+                    context.capture.record(&buffer[..consumed]);
+                    buffer.drain(..consumed);
+                    continue;
+                }
+                Ok((Resp::Array(mut v), left)) => {
+                    let consumed = buffer.len() - left.len();
+                    // the permissions of the user apply to the original name of the command
+                    let resolved = resolve_renamed(&mut v, &renames)
+                        .and_then(|renamed| context.acl.check(client, &v).map(|_| renamed));
+                    name = Some(stats::command_name(&v));
+                    match resolved {
+                        // a RESP3 client tells the messages from the replies, it may run
+                        // any command
+                        Ok(_)
+                            if client.is_subscribed()
+                                && !client.resp3
+                                && !allowed_when_subscribed(&v) =>
+                        {
+                            let name = match v.first() {
+                                Some(Resp::BulkString(name)) => name,
+                                _ => &b""[..],
+                            };
+                            let name = String::from_utf8_lossy(name).to_lowercase();
+                            (
+                                Err(RedisCommandError::SubscribedContext(name)),
+                                consumed,
+                                false,
+                            )
+                        }
+                        Ok(renamed) => {
+                            let write =
+                                matches!(v.first(), Some(Resp::BulkString(name)) if is_write(name));
+                            if (renamed || inline) && write {
+                                original_request = Some(encode_request(&v));
+                            }
+                            keys = command_keys(&v);
+                            if context.monitors.is_active() {
+                                context.monitors.feed(client, &v);
+                            }
+                            (Command::parse(v), consumed, write)
+                        }
+                        Err(err) => (Err(err), consumed, false),
+                    }
+                }
+                Ok((Resp::NullArray, left)) => (
+                    Err(RedisCommandError::InvalidCommand),
+                    buffer.len() - left.len(),
+                    false,
+                ),
+                Ok((_, left)) => (
+                    Err(RedisCommandError::CommandNotFound),
+                    buffer.len() - left.len(),
+                    false,
+                ),
+                Err(err) if err.is_incomplete() => break,
+                Err(err) if context.config().resync_on_protocol_error => {
+                    // the next request is taken to start at the next `*`
+                    let next = buffer[1..]
+                        .iter()
+                        .position(|byte| *byte == b'*')
+                        .map_or(buffer.len(), |position| position + 1);
+                    log::warn!("client {}: {}, {} bytes dropped", client.id, err, next);
+                    let res = RedisResponse::error(RedisCommandError::ProtocolParse(err));
+                    reply.append(&mut res.reply());
+                    context.capture.record(&buffer[..next]);
+                    buffer.drain(..next);
+                    continue;
+                }
+                Err(err) => {
+                    log::warn!("client {}: {}", client.id, err);
+                    // the rest of the stream can not be trusted after a protocol error
+                    let res = RedisResponse::error(RedisCommandError::ProtocolParse(err));
+                    reply.append(&mut res.reply());
+                    context.capture.record(buffer);
+                    buffer.clear();
+                    quit = true;
+                    break;
+                }
+            };
+        let request: Vec<u8> = buffer.drain(..consumed).collect();
+        context.capture.record(&request);
+        let request = original_request.unwrap_or(request);
 
-    while let Ok(s) = buf_reader.read(&mut buf) {
-        buf_length += s;
+        let name = name.unwrap_or_default();
+
+        // in a transaction the commands are queued until `EXEC`, the ones which can't run
+        // are refused at once
+        let command = match (command, &mut client.transaction) {
+            (Ok(command), Some(transaction)) if !controls_transaction(&command) => {
+                if !queueable(&command) {
+                    Err(RedisCommandError::NotAllowedInTransaction)
+                } else {
+                    transaction.queued.push(Queued {
+                        command,
+                        request,
+                        write,
+                        name,
+                        keys,
+                    });
+                    let res =
+                        RedisResponse::single(RedisResponseType::SimpleString(b"QUEUED".to_vec()));
+                    reply.append(&mut res.reply());
+                    continue;
+                }
+            }
+            (Err(err), Some(transaction)) => {
+                transaction.aborted = true;
+                Err(err)
+            }
+            (command, _) => command,
+        };
 
-        if s < 512 {
+        let unknown = matches!(command, Err(RedisCommandError::UnknownCommand(..)));
+        let res = match command {
+            Ok(Command::Exec) if client.transaction.is_some() => exec(context, client, name),
+            command => run_request(context, client, &request, command, write, name, keys),
+        };
+        quit = res.is_quit()
+            || client.killed
+            || client.shutdown
+            || (unknown && context.config().strict_unknown_commands);
+        if client.shutdown {
             break;
         }
+        if res.is_streamed() {
+            // the replies of the previous commands go first
+            let _ = stream.write_all(&reply);
+            reply.clear();
+            let _ = res.write_to(stream);
+        } else {
+            reply.append(&mut res.reply());
+        }
     }
 
-    (buf, buf_length)
+    let _ = stream.write_all(&reply);
+
+    (quit, received)
 }
 
-pub fn handle_request<T: Storage>(
-    storage: &Arc<Mutex<T>>,
-    mut stream: &TcpStream,
-) -> (CloseConnection, ReceivedDataLength) {
-    let (buf, buf_length) = get_bytes_from_request(stream);
+/// Run a command received from the client, or queued by its transaction, and record it
+fn run_request<T: Storage>(
+    context: &ServerContext<T>,
+    client: &mut Client,
+    request: &[u8],
+    command: Result<Command, RedisCommandError>,
+    write: bool,
+    name: String,
+    keys: Vec<RedisString>,
+) -> RedisResponse {
+    let db = client.db;
+    if !keys.is_empty() {
+        expire_accessed(context, db, &keys);
+    }
+    // the commands which free memory still run once nothing is left to evict
+    let command = match evict(context) {
+        Err(err) if denies_oom(name.as_bytes()) => Err(err),
+        _ => command,
+    };
 
-    match buf.get(0) {
-        Some(x) if *x == 0 => {
-            return (false, buf_length);
+    let unknown = matches!(command, Err(RedisCommandError::UnknownCommand(..)));
+    let refused = command.is_err();
+    let deadline = Deadline::after(context.config().command_time_limit);
+    let start = Instant::now();
+    let res = match write {
+        true => run_write_command(context, client, request, command, deadline),
+        false => run_command_and_get_response(context, client, command, deadline),
+    };
+    let outcome = match (refused, res.error_of()) {
+        (true, _) => Outcome::Rejected,
+        (false, Some(_)) => Outcome::Failed,
+        (false, None) => Outcome::Succeeded,
+    };
+    if let Some(err) = res.error_of() {
+        // a command which can't run is a mistake of the client, unlike a failing one
+        let level = match refused {
+            true => log::Level::Warn,
+            false => log::Level::Debug,
+        };
+        log::log!(level, "client {}: {}: {}", client.id, name, err);
+    }
+    // every command is sampled as the `command` event of the latency monitor, which
+    // keeps the ones past `latency-monitor-threshold`
+    if !unknown && !name.is_empty() {
+        let elapsed = start.elapsed();
+        let threshold = context.config().latency_monitor_threshold;
+        context.latency.record(COMMAND_EVENT, elapsed, threshold);
+        context.stats.record(name, elapsed, outcome);
+    }
+    if !keys.is_empty() && !res.is_error() {
+        match (write, &client.subscriber) {
+            (true, _) => context.tracking.invalidate(db, &keys),
+            (false, Some(subscriber)) if client.tracking => {
+                context.tracking.track(db, keys, subscriber)
+            }
+            _ => {}
         }
-        _ => {}
     }
+    res.in_protocol(client.resp3).compressed(client.compression)
+}
 
-    let res = run_command_and_get_response(storage, &buf);
-    let quit = res.is_quit();
-    let reply = res.reply();
-    //eprintln!("?{}", std::str::from_utf8(&reply).unwrap());
-    let _ = stream.write(&reply);
+/// Run the commands queued by the transaction of the client, replied as the array of their
+/// replies, an error being the reply of the command which failed and not of `EXEC`. The
+/// commands of other clients may run in between.
+fn exec<T: Storage>(
+    context: &ServerContext<T>,
+    client: &mut Client,
+    name: String,
+) -> RedisResponse {
+    let start = Instant::now();
+    let transaction = client.transaction.take().unwrap_or_default();
+    context.transactions.fetch_sub(1, Ordering::SeqCst);
+    if transaction.aborted {
+        context.stats.record(name, start.elapsed(), Outcome::Failed);
+        return RedisResponse::error(RedisCommandError::ExecAbort);
+    }
+    let queued = transaction.queued;
+    let mut reply = format!("*{}\r\n", queued.len()).into_bytes();
+    for queued in queued {
+        let Queued {
+            command,
+            request,
+            write,
+            name,
+            keys,
+        } = queued;
+        let res = run_request(context, client, &request, Ok(command), write, name, keys);
+        reply.append(&mut res.reply());
+    }
+    context
+        .stats
+        .record(name, start.elapsed(), Outcome::Succeeded);
+    RedisResponse::raw(reply)
+}
 
-    (quit, 1)
+/// Whether the command starts, runs or discards a transaction, so it is never queued
+fn controls_transaction(command: &Command) -> bool {
+    matches!(command, Command::Multi | Command::Exec | Command::Discard)
+}
+
+/// Whether the command may be queued in a transaction, unlike the ones changing the state
+/// of the connection for good
+fn queueable(command: &Command) -> bool {
+    !matches!(
+        command,
+        Command::Subscribe(..)
+            | Command::PSubscribe(..)
+            | Command::Unsubscribe(..)
+            | Command::PUnsubscribe(..)
+            | Command::Monitor
+            | Command::Sync
+            | Command::PSync
+    )
+}
+
+/// Run a command modifying the keyspace, which replicas must run as well
+fn run_write_command<T: Storage>(
+    context: &ServerContext<T>,
+    client: &mut Client,
+    request: &[u8],
+    command: Result<Command, RedisCommandError>,
+    deadline: Deadline,
+) -> RedisResponse {
+    if rejects_writes(context) {
+        return RedisResponse::error(RedisCommandError::ReadOnlyReplica);
+    }
+
+    let db = client.db;
+    let res = context.replication.run_write(db, request, || {
+        run_command_and_get_response(context, client, command, deadline)
+    });
+    if !res.is_error() {
+        // counted for the save points
+        context.dirty.fetch_add(1, Ordering::SeqCst);
+    }
+    res
+}
+
+/// Keys of the command, none when it is unknown or takes no key
+pub fn command_keys(v: &[Resp]) -> Vec<RedisString> {
+    let args: Option<Vec<_>> = v
+        .iter()
+        .map(|arg| match arg {
+            Resp::BulkString(arg) => Some(arg.to_vec()),
+            _ => None,
+        })
+        .collect();
+    let args = args.unwrap_or_default();
+    match key_positions(&args) {
+        Ok(positions) => positions
+            .into_iter()
+            .map(|position| args[position].clone())
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Remove the keys of a command which expired before it runs, as the expiration cycle does,
+/// so their `expired` events are notified once, whether the cycle is on or not. A replica
+/// leaves removing them to its primary and only invalidates them.
+fn expire_accessed<T: Storage>(context: &ServerContext<T>, db: usize, keys: &[RedisString]) {
+    let expired: Vec<_> = {
+        let storage = context.database(db).lock_keys(keys);
+        keys.iter()
+            .filter(|key| storage.meta(key).is_some_and(|meta| meta.is_expired()))
+            .cloned()
+            .collect()
+    };
+    if expired.is_empty() {
+        return;
+    }
+    match context.replication.is_replica() {
+        true => context.tracking.invalidate(db, &expired),
+        false => expired.iter().for_each(|key| {
+            expire_key(context, db, key);
+        }),
+    }
+}
+
+/// Whether a subscribed client may send the command
+fn allowed_when_subscribed(v: &[Resp]) -> bool {
+    const ALLOWED: &[&[u8]] = &[
+        b"SUBSCRIBE",
+        b"PSUBSCRIBE",
+        b"SSUBSCRIBE",
+        b"UNSUBSCRIBE",
+        b"PUNSUBSCRIBE",
+        b"SUNSUBSCRIBE",
+        b"PING",
+        b"QUIT",
+        b"RESET",
+    ];
+    match v.first() {
+        Some(Resp::BulkString(name)) => ALLOWED
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(name)),
+        _ => false,
+    }
+}
+
+/// Whether the write commands of clients are rejected, on a read only server or replica
+fn rejects_writes<T: Storage>(context: &ServerContext<T>) -> bool {
+    let config = context.config();
+    config.read_only || (config.replica_read_only && context.replication.is_replica())
+}
+
+/// Encode the arguments of a request as the array a client sends
+pub fn encode_request(v: &[Resp]) -> Vec<u8> {
+    let mut request = format!("*{}\r\n", v.len()).into_bytes();
+    for arg in v {
+        arg.serialize(&mut request);
+    }
+    request
 }
@@ -1,64 +1,160 @@
 use std::{
     collections::{HashMap, HashSet},
-    sync::{Arc, Mutex},
+    convert::TryFrom,
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
 };
 
+use chrono::Utc;
+use rand::seq::IteratorRandom;
+
 use crate::{
-    command::Command,
+    command::{
+        command_doc, command_docs, command_spec, command_specs, help, key_positions,
+        options::{
+            Aggregate, ExpireCondition, FlushMode, GetExOption, KillFilter, ScoreComparison,
+            SetCondition, SetOptions, ShutdownMode, SortOptions, ZCombineOptions, ZRangeBy,
+            ZRangeOptions,
+        },
+        AclSubcommand, Argument, BitOperation, ClientSubcommand, Command, CommandDoc, CommandSpec,
+        ConfigSubcommand, DebugSubcommand, LatencySubcommand, MemorySubcommand, ObjectSubcommand,
+        PubSubSubcommand, Side,
+    },
     protocol::response::{RedisResponse, RedisResponseType},
-    storage::{models::RedisString, Storage},
+    storage::{
+        models::{parse_i64, Encoding, Expiry, RedisSortedSet, RedisString, RedisValue, ValueRef},
+        snapshot::{self, BadDump},
+        Storage,
+    },
 };
 
 use super::*;
+use crate::server::aof;
+use crate::server::client::Transaction;
+use crate::server::database::Database;
+use crate::server::eviction::sample_keys;
+use crate::server::notifications::{notify, EventClass};
+use crate::server::pubsub::Subscriber;
+
+// integers in `0..SHARED_INTEGERS` are shared objects in Redis
+const SHARED_INTEGERS: i64 = 10000;
+/// `SET`, with the stripe of key locked so its condition is checked and the key written at
+/// once. Returns whether the key was set, and its old value when `GET` asks for it.
+fn set<T: Storage>(
+    context: &ServerContext<T>,
+    db: usize,
+    storage: &mut T,
+    key: &[u8],
+    value: &[u8],
+    options: &SetOptions,
+) -> Result<(bool, Option<RedisString>), RedisCommandError> {
+    let exists = storage.contains(key);
+    if options.get && exists && storage.type_of(key) != b"string" {
+        return Err(RedisCommandError::WrongTypeOperation);
+    }
+
+    let old_value = match options.get {
+        true => storage.read(key).map(|value| value.to_vec()),
+        false => None,
+    };
+
+    let should_set = match options.condition {
+        Some(SetCondition::NotExists) => !exists,
+        Some(SetCondition::Exists) => exists,
+        None => true,
+    };
+    if !should_set {
+        return Ok((false, old_value));
+    }
+
+    let ttl = match options.keep_ttl {
+        true => storage.meta(key).and_then(|meta| meta.expiry),
+        false => match context.config().default_ttl_secs {
+            0 => None,
+            secs => Some(Expiry::new_from_secs(secs)?),
+        },
+    };
+
+    // the write drops the expiry of the previous value, so the key only has the one of the
+    // options, the kept one, or the default one
+    storage.write(key, value);
+    notify(context, db, EventClass::String, "set", key);
+    if let Some(expiry) = options.expiry.or(ttl) {
+        storage.expire(key, expiry);
+        if options.expiry.is_some() {
+            notify(context, db, EventClass::Generic, "expire", key);
+        }
+    }
+    Ok((true, old_value))
+}
+
+// refcount Redis reports for shared objects
+const SHARED_REFCOUNT: i64 = i32::MAX as i64;
+// number of keys a scanning command goes through between two checks of its deadline
+const DEADLINE_CHECK_INTERVAL: usize = 256;
 
 pub fn run_command_and_get_response<T: Storage>(
-    storage: &Arc<Mutex<T>>,
-    bytes: &[u8; 512],
+    context: &ServerContext<T>,
+    client: &mut Client,
+    command: Result<Command, RedisCommandError>,
+    deadline: Deadline,
 ) -> RedisResponse {
     use protocol::response::RedisResponseType::*;
-    let command = get_command(bytes);
+    let storage = context.database(client.db);
     let response = match command {
         Ok(command) => match command {
-            Command::Set(k, v) => {
-                lock_then_release(storage).write(k.as_slice(), v.as_slice());
-                RedisResponse::okay()
+            Command::Set(k, v, options) => {
+                let mut storage = storage.lock(&k);
+                match set(context, client.db, &mut *storage, &k, &v, &options) {
+                    Ok((_, Some(old_value))) => RedisResponse::single(BulkString(old_value)),
+                    Ok((_, None)) if options.get => RedisResponse::single(Nil),
+                    Ok((true, None)) => RedisResponse::okay(),
+                    Ok((false, None)) => RedisResponse::single(Nil),
+                    Err(err) => RedisResponse::error(err),
+                }
             }
             Command::Append(k, v) => {
-                let len = lock_then_release(storage).extend(k.as_slice(), v.as_slice());
+                let len = storage.lock(&k).extend(k.as_slice(), v.as_slice());
+                notify(context, client.db, EventClass::String, "append", &k);
                 RedisResponse::single(Integer(len as i64))
             }
-            Command::Setex(k, expiry, v) | Command::PSetex(k, expiry, v) => {
-                let mut storage = lock_then_release(storage);
-
-                storage.write(k.as_slice(), v.as_slice());
-                storage.expire(k.as_slice(), expiry);
-
-                RedisResponse::okay()
-            }
+            Command::StrLen(k) => match storage.lock(&k).entry(&k) {
+                Some(ValueRef::String(value)) => RedisResponse::single(Integer(value.len() as i64)),
+                None => RedisResponse::single(Integer(0)),
+                Some(_) => RedisResponse::error(RedisCommandError::WrongTypeOperation),
+            },
             Command::Setnx(k, v) => {
-                let mut storage = lock_then_release(storage);
-                match storage.contains(&k[..]) {
-                    // Key exists, will not re set key
-                    true => RedisResponse::single(Integer(0)),
-                    // Key does not exist, will set key
-                    false => {
-                        storage.write(&k, &v);
-                        RedisResponse::single(Integer(1))
-                    }
+                // a `SET key value NX`, so the two can't set a key differently
+                let options = SetOptions {
+                    condition: Some(SetCondition::NotExists),
+                    ..Default::default()
+                };
+                let mut storage = storage.lock(&k);
+                match set(context, client.db, &mut *storage, &k, &v, &options) {
+                    Ok((true, _)) => RedisResponse::single(Integer(1)),
+                    Ok((false, _)) => RedisResponse::single(Integer(0)),
+                    Err(err) => RedisResponse::error(err),
                 }
             }
             Command::MSet(items) => {
-                let mut storage = lock_then_release(storage);
-                items.iter().for_each(|(k, v)| storage.write(k, v));
+                let mut storage = storage.lock_keys(items.iter().map(|(k, _)| k));
+                for (k, v) in &items {
+                    storage.write(k, v);
+                    notify(context, client.db, EventClass::String, "set", k);
+                }
                 RedisResponse::okay()
             }
             Command::MSetnx(items) => {
                 // Either set all or not set any at all if any already exist
-                let mut storage = lock_then_release(storage);
+                let mut storage = storage.lock_keys(items.iter().map(|(k, _)| k));
                 match items.iter().all(|(key, _)| !storage.contains(key)) {
                     // None of the keys already exist in the storage
                     true => {
-                        items.iter().for_each(|(k, v)| storage.write(k, v));
+                        for (k, v) in &items {
+                            storage.write(k, v);
+                            notify(context, client.db, EventClass::String, "set", k);
+                        }
                         RedisResponse::single(Integer(1))
                     }
                     // Some key exists, don't write any of the keys
@@ -66,109 +162,436 @@ pub fn run_command_and_get_response<T: Storage>(
                 }
             }
             Command::Expire(k, expiry) | Command::PExpire(k, expiry) => {
-                let e = lock_then_release(storage).expire(k.as_slice(), expiry);
+                let mut storage = storage.lock(&k);
+                // as in Redis, a time already past deletes the key
+                if expiry.duration_left_millis() <= 0 {
+                    let existed = storage.contains(&k);
+                    if existed {
+                        storage.remove(&k);
+                        notify(context, client.db, EventClass::Generic, "del", &k);
+                    }
+                    return RedisResponse::single(Integer(existed as i64));
+                }
+                let e = storage.expire(k.as_slice(), expiry);
+                if e == 1 {
+                    notify(context, client.db, EventClass::Generic, "expire", &k);
+                }
                 RedisResponse::single(Integer(e as i64))
             }
-            Command::Get(k) => match lock_then_release(storage).read(k.as_slice()) {
-                Some(value) => RedisResponse::single(SimpleString(value.to_vec())),
+            Command::Get(k) => match storage.lock(&k).read(k.as_slice()) {
+                Some(value) => RedisResponse::single(BulkString(value.to_vec())),
                 None => RedisResponse::single(Nil),
             },
             Command::GetSet(k, v) => {
-                let mut storage = lock_then_release(storage);
-
+                let mut storage = storage.lock(&k);
+                // a key of another type is left as it is
+                let keytype = storage.type_of(&k);
+                if keytype != b"string" && keytype != b"none" {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
                 let response = match storage.read(k.as_slice()) {
-                    Some(value) => RedisResponse::single(SimpleString(value.to_vec())),
+                    Some(value) => RedisResponse::single(BulkString(value.to_vec())),
                     None => RedisResponse::single(Nil),
                 };
                 storage.write(k.as_slice(), v.as_slice());
+                notify(context, client.db, EventClass::String, "set", &k);
                 response
             }
+            Command::GetDel(k) => {
+                let mut storage = storage.lock(&k);
+                let keytype = storage.type_of(&k);
+                if keytype != b"string" && keytype != b"none" {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+                let value = match storage.read(&k) {
+                    Some(value) => value.to_vec(),
+                    None => return RedisResponse::single(Nil),
+                };
+                storage.remove(&k);
+                notify(context, client.db, EventClass::Generic, "del", &k);
+                RedisResponse::single(BulkString(value))
+            }
+            Command::GetEx(k, option) => {
+                let mut storage = storage.lock(&k);
+                let keytype = storage.type_of(&k);
+                if keytype != b"string" && keytype != b"none" {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+                let value = match storage.read(&k) {
+                    Some(value) => value.to_vec(),
+                    None => return RedisResponse::single(Nil),
+                };
+                match option {
+                    // as in Redis, a time already past deletes the key
+                    Some(GetExOption::Expiry(expiry)) if expiry.duration_left_millis() <= 0 => {
+                        storage.remove(&k);
+                        notify(context, client.db, EventClass::Generic, "del", &k);
+                    }
+                    Some(GetExOption::Expiry(expiry)) => {
+                        storage.expire(&k, expiry);
+                        notify(context, client.db, EventClass::Generic, "expire", &k);
+                    }
+                    // a key without an expiry is not persisted again
+                    Some(GetExOption::Persist) if storage.persist(&k) == 1 => {
+                        notify(context, client.db, EventClass::Generic, "persist", &k);
+                    }
+                    Some(GetExOption::Persist) | None => {}
+                }
+                RedisResponse::single(BulkString(value))
+            }
+            Command::CompareAndSet(k, expected, new) => {
+                // the storage stays locked from the comparison to the write
+                let mut storage = storage.lock(&k);
+                let keytype = storage.type_of(&k);
+                if keytype != b"string" && keytype != b"none" {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+                match storage.read(&k) {
+                    // the expiry is dropped, as by a `SET`
+                    Some(value) if value == expected.as_slice() => {
+                        storage.write(&k, &new);
+                        RedisResponse::single(Integer(1))
+                    }
+                    _ => RedisResponse::single(Integer(0)),
+                }
+            }
+            Command::GetRange(k, start, end) => {
+                let mut storage = storage.lock(&k);
+                let value = match storage.entry(&k) {
+                    Some(ValueRef::String(value)) => value,
+                    None => &[],
+                    Some(_) => return RedisResponse::error(RedisCommandError::WrongTypeOperation),
+                };
+                RedisResponse::single(BulkString(get_range(value, start, end).to_vec()))
+            }
+            Command::SetRange(k, offset, value) => {
+                let mut storage = storage.lock(&k);
+                let keytype = storage.type_of(&k);
+                if keytype != "string".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+                // an empty value changes nothing, and creates no key
+                if value.is_empty() {
+                    let len = storage.read(&k).map_or(0, |value| value.len());
+                    return RedisResponse::single(Integer(len as i64));
+                }
+                if offset + value.len() > context.config().proto_max_bulk_len {
+                    return RedisResponse::error(RedisCommandError::StringTooLong);
+                }
+                let len = storage.set_range(&k, offset, &value);
+                notify(context, client.db, EventClass::String, "setrange", &k);
+                RedisResponse::single(Integer(len as i64))
+            }
+            Command::Lcs(first, second, options) => {
+                let mut storage = storage.lock_keys([&first, &second]);
+                for key in [&first, &second] {
+                    let keytype = storage.type_of(key);
+                    if keytype != "string".as_bytes() && keytype != "none".as_bytes() {
+                        return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                    }
+                }
+                let a = storage.read(&first).unwrap_or_default().to_vec();
+                let b = storage.read(&second).unwrap_or_default();
+                let cells = (a.len() + 1).checked_mul(b.len() + 1);
+                let max_cells = context.config().proto_max_bulk_len / std::mem::size_of::<u32>();
+                if cells.is_none_or(|cells| cells > max_cells) {
+                    return RedisResponse::error(RedisCommandError::LcsTooLarge);
+                }
+
+                let (common, matches) = lcs(&a, b);
+                if !options.idx {
+                    return match options.len {
+                        true => RedisResponse::single(Integer(common.len() as i64)),
+                        false => RedisResponse::single(BulkString(common)),
+                    };
+                }
+                let matches = matches
+                    .into_iter()
+                    .filter(|((a_start, a_end), _)| a_end - a_start + 1 >= options.min_match_len)
+                    .map(|((a_start, a_end), (b_start, b_end))| {
+                        let mut entry = vec![
+                            Array(vec![Integer(a_start as i64), Integer(a_end as i64)]),
+                            Array(vec![Integer(b_start as i64), Integer(b_end as i64)]),
+                        ];
+                        if options.with_match_len {
+                            entry.push(Integer((a_end - a_start + 1) as i64));
+                        }
+                        Array(entry)
+                    })
+                    .collect();
+                RedisResponse::map(
+                    vec![
+                        (BulkString(b"matches".to_vec()), Array(matches)),
+                        (BulkString(b"len".to_vec()), Integer(common.len() as i64)),
+                    ],
+                    client.resp3,
+                )
+            }
+            Command::BitPos(k, bit, start, end) => {
+                let mut storage = storage.lock(&k);
+                let keytype = storage.type_of(&k);
+                if keytype != "string".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+                let value = storage.read(&k).unwrap_or_default();
+                RedisResponse::single(Integer(bit_pos(value, bit, start, end)))
+            }
+            Command::BitOp(operation, dest, sources) => {
+                let mut storage = storage.lock_keys(sources.iter().chain(Some(&dest)));
+                let mut values = Vec::with_capacity(sources.len());
+                for key in &sources {
+                    let keytype = storage.type_of(key);
+                    if keytype != "string".as_bytes() && keytype != "none".as_bytes() {
+                        return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                    }
+                    values.push(storage.read(key).unwrap_or_default().to_vec());
+                }
+
+                let result = bit_op(operation, &values);
+                if result.is_empty() {
+                    storage.remove(&dest);
+                } else {
+                    storage.insert(&dest, RedisValue::String(result.clone()), None);
+                }
+                RedisResponse::single(Integer(result.len() as i64))
+            }
             Command::MGet(keys) => {
-                let mut storage = lock_then_release(storage);
+                let mut storage = storage.lock_keys(&keys);
                 let mut responses = Vec::<RedisResponseType>::with_capacity(keys.len());
                 for key in keys {
                     let response = match storage.read(key.as_slice()) {
-                        Some(value) => RedisResponseType::SimpleString(value.to_vec()),
+                        Some(value) => RedisResponseType::BulkString(value.to_vec()),
                         None => RedisResponseType::Nil,
                     };
                     responses.push(response);
                 }
                 RedisResponse::array(responses)
             }
-            Command::HSet(map_key, items) => {
-                let mut hash_map = HashMap::<RedisString, RedisString>::with_capacity(items.len());
+            Command::HRandField(key, count) => {
+                let mut storage = storage.lock(&key);
+                let keytype = storage.type_of(&key);
+                if keytype != "hash".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
 
-                for (k, v) in items {
-                    hash_map.insert(k.to_vec(), v.to_vec());
+                // fields are sorted so a seeded generator picks the same ones whatever the
+                // iteration order of the hash
+                let mut entries = match storage.hread_all(&key) {
+                    Some(hash) => hash.iter().collect::<Vec<_>>(),
+                    None => vec![],
+                };
+                entries.sort();
+                let mut rng = lock_then_release(&context.rng);
+
+                let (count, with_values) = match count {
+                    Some(count) => count,
+                    None => {
+                        return match sample_one(entries.into_iter(), &mut *rng) {
+                            Some((field, _)) => RedisResponse::single(BulkString(field.to_vec())),
+                            None => RedisResponse::single(Nil),
+                        };
+                    }
+                };
+                let picked = sample(entries.into_iter(), count, &mut *rng);
+
+                let mut responses = Vec::with_capacity(picked.len() * 2);
+                for (field, value) in picked {
+                    responses.push(BulkString(field.to_vec()));
+                    if with_values {
+                        responses.push(BulkString(value.to_vec()));
+                    }
+                }
+                RedisResponse::array(responses)
+            }
+            Command::HSet(map_key, items) => {
+                let mut storage = storage.lock(&map_key);
+                let keytype = storage.type_of(&map_key);
+                if keytype != "hash".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
                 }
 
-                let mut storage = lock_then_release(storage);
-                storage.hwrite(&map_key, hash_map);
+                // the fields are added to the ones of the hash
+                storage.hset(&map_key, items);
                 RedisResponse::okay()
             }
-            Command::HGet(map_key, field_key) => {
-                match lock_then_release(storage).hread(map_key.as_slice(), field_key.as_slice()) {
-                    Some(value) => RedisResponse::single(SimpleString(value.to_vec())),
+            Command::HGetAll(ref key) | Command::HKeys(ref key) | Command::HVals(ref key) => {
+                let mut storage = storage.lock(key);
+                let keytype = storage.type_of(key);
+                if keytype != "hash".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+                let entries = storage.hentries(key).unwrap_or_default();
+                match command {
+                    Command::HGetAll(_) => RedisResponse::map(
+                        entries
+                            .into_iter()
+                            .map(|(field, value)| (BulkString(field), BulkString(value)))
+                            .collect(),
+                        client.resp3,
+                    ),
+                    Command::HKeys(_) => RedisResponse::array(
+                        entries
+                            .into_iter()
+                            .map(|(field, _)| BulkString(field))
+                            .collect(),
+                    ),
+                    _ => RedisResponse::array(
+                        entries
+                            .into_iter()
+                            .map(|(_, value)| BulkString(value))
+                            .collect(),
+                    ),
+                }
+            }
+            Command::HGet(map_key, field_key) => match storage.lock(&map_key).entry(&map_key) {
+                Some(ValueRef::Hash(hash)) => match hash.get(&field_key) {
+                    Some(value) => RedisResponse::single(BulkString(value.to_vec())),
                     None => RedisResponse::single(Nil),
+                },
+                None => RedisResponse::single(Nil),
+                Some(_) => RedisResponse::error(RedisCommandError::WrongTypeOperation),
+            },
+            Command::HExpire(key, expiry, condition, fields)
+            | Command::HPExpire(key, expiry, condition, fields) => {
+                let mut storage = storage.lock(&key);
+                let keytype = storage.type_of(&key);
+                if keytype != "hash".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+                let mut replies = Vec::with_capacity(fields.len());
+                let mut changed = false;
+                for field in &fields {
+                    let current = match storage.hexpiry(&key, field) {
+                        Some(current) => current,
+                        None => {
+                            replies.push(Integer(-2));
+                            continue;
+                        }
+                    };
+                    let met = match (condition, current) {
+                        (None, _) => true,
+                        (Some(ExpireCondition::NoExpiry), current) => current.is_none(),
+                        (Some(ExpireCondition::HasExpiry), current) => current.is_some(),
+                        (Some(ExpireCondition::Greater), current) => {
+                            current.is_some_and(|current| expiry.timestamp > current.timestamp)
+                        }
+                        (Some(ExpireCondition::Less), current) => {
+                            current.is_none_or(|current| expiry.timestamp < current.timestamp)
+                        }
+                    };
+                    if !met {
+                        replies.push(Integer(0));
+                        continue;
+                    }
+                    storage.hset_expiry(&key, field, Some(expiry));
+                    changed = true;
+                    // a field expiring at once is deleted
+                    match storage.hexpiry(&key, field) {
+                        Some(_) => replies.push(Integer(1)),
+                        None => replies.push(Integer(2)),
+                    }
+                }
+                if changed {
+                    notify(context, client.db, EventClass::Hash, "hexpire", &key);
                 }
+                RedisResponse::array(replies)
+            }
+            Command::HTtl(key, fields) => {
+                let mut storage = storage.lock(&key);
+                let keytype = storage.type_of(&key);
+                if keytype != "hash".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+                let replies = fields
+                    .iter()
+                    .map(|field| match storage.hexpiry(&key, field) {
+                        // rounded to the closest second, as the ttl of a key
+                        Some(Some(expiry)) => Integer((expiry.duration_left_millis() + 500) / 1000),
+                        Some(None) => Integer(-1),
+                        None => Integer(-2),
+                    })
+                    .collect();
+                RedisResponse::array(replies)
+            }
+            Command::HPersist(key, fields) => {
+                let mut storage = storage.lock(&key);
+                let keytype = storage.type_of(&key);
+                if keytype != "hash".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+                let mut replies = Vec::with_capacity(fields.len());
+                let mut changed = false;
+                for field in &fields {
+                    match storage.hexpiry(&key, field) {
+                        Some(Some(_)) => {
+                            storage.hset_expiry(&key, field, None);
+                            changed = true;
+                            replies.push(Integer(1));
+                        }
+                        Some(None) => replies.push(Integer(-1)),
+                        None => replies.push(Integer(-2)),
+                    }
+                }
+                if changed {
+                    notify(context, client.db, EventClass::Hash, "hpersist", &key);
+                }
+                RedisResponse::array(replies)
             }
             Command::RPush(key, values) => {
-                let mut storage = lock_then_release(storage);
+                let mut storage = storage.lock(&key);
                 let keytype = storage.type_of(&key);
                 if keytype != "list".as_bytes() && keytype != "none".as_bytes() {
                     return RedisResponse::error(RedisCommandError::WrongTypeOperation);
                 }
                 let mut len = values.len();
-                let mut new_vals = values.to_vec();
+                let mut new_vals = values;
                 match storage.lread(&key) {
                     Some(vals) => {
                         let mut vals = vals.to_vec();
                         vals.append(&mut new_vals);
                         len = vals.len();
                         storage.lwrite(&key, vals);
+                        context.blocked[client.db].notify(&key);
                         RedisResponse::single(Integer(len as i64))
                     }
                     None => {
                         storage.lwrite(&key, new_vals);
+                        context.blocked[client.db].notify(&key);
                         RedisResponse::single(Integer(len as i64))
                     }
                 }
             }
             Command::LPush(key, values) => {
-                let mut storage = lock_then_release(storage);
+                let mut storage = storage.lock(&key);
                 let keytype = storage.type_of(&key);
                 if keytype != "list".as_bytes() && keytype != "none".as_bytes() {
                     return RedisResponse::error(RedisCommandError::WrongTypeOperation);
                 }
                 let mut len = values.len();
-                let mut values: Vec<RedisString> = values.to_vec().into_iter().rev().collect();
+                let mut values: Vec<RedisString> = values.into_iter().rev().collect();
                 match storage.lread(&key) {
                     Some(old_vals) => {
                         let mut old_vals = old_vals.to_vec();
                         values.append(&mut old_vals);
                         len = values.len();
                         storage.lwrite(&key, values);
+                        context.blocked[client.db].notify(&key);
                         RedisResponse::single(Integer(len as i64))
                     }
                     None => {
                         storage.lwrite(&key, values);
+                        context.blocked[client.db].notify(&key);
                         RedisResponse::single(Integer(len as i64))
                     }
                 }
             }
-            Command::LLen(key) => {
-                let mut storage = lock_then_release(storage);
-                let keytype = storage.type_of(&key);
-                if keytype != "list".as_bytes() && keytype != "none".as_bytes() {
-                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
-                }
-                match storage.lread(&key) {
-                    Some(vals) => RedisResponse::single(Integer(vals.len() as i64)),
-                    None => RedisResponse::single(Integer(0)),
-                }
-            }
+            Command::LLen(key) => match storage.lock(&key).entry(&key) {
+                Some(ValueRef::List(values)) => RedisResponse::single(Integer(values.len() as i64)),
+                None => RedisResponse::single(Integer(0)),
+                Some(_) => RedisResponse::error(RedisCommandError::WrongTypeOperation),
+            },
             Command::RPushx(key, values) => {
-                let mut storage = lock_then_release(storage);
+                let mut storage = storage.lock(&key);
                 let keytype = storage.type_of(&key);
                 if keytype == "none".as_bytes() {
                     return RedisResponse::single(Integer(0));
@@ -176,20 +599,21 @@ pub fn run_command_and_get_response<T: Storage>(
                 if keytype != "list".as_bytes() {
                     return RedisResponse::error(RedisCommandError::WrongTypeOperation);
                 }
-                let mut new_vals = values.to_vec();
+                let mut new_vals = values;
                 match storage.lread(&key) {
                     Some(vals) => {
                         let mut vals = vals.to_vec();
                         vals.append(&mut new_vals);
                         let len = vals.len();
                         storage.lwrite(&key, vals);
+                        context.blocked[client.db].notify(&key);
                         RedisResponse::single(Integer(len as i64))
                     }
                     None => RedisResponse::single(Integer(0)),
                 }
             }
             Command::LPushx(key, values) => {
-                let mut storage = lock_then_release(storage);
+                let mut storage = storage.lock(&key);
                 let keytype = storage.type_of(&key);
                 if keytype == "none".as_bytes() {
                     return RedisResponse::single(Integer(0));
@@ -197,79 +621,61 @@ pub fn run_command_and_get_response<T: Storage>(
                 if keytype != "list".as_bytes() {
                     return RedisResponse::error(RedisCommandError::WrongTypeOperation);
                 }
-                let mut values: Vec<RedisString> = values.to_vec().into_iter().rev().collect();
+                let mut values: Vec<RedisString> = values.into_iter().rev().collect();
                 match storage.lread(&key) {
                     Some(old_vals) => {
                         let mut old_vals = old_vals.to_vec();
                         values.append(&mut old_vals);
                         let len = values.len();
                         storage.lwrite(&key, values);
+                        context.blocked[client.db].notify(&key);
                         RedisResponse::single(Integer(len as i64))
                     }
                     None => RedisResponse::single(Integer(0)),
                 }
             }
-            Command::RPop(key) => {
-                let mut storage = lock_then_release(storage);
-                let keytype = storage.type_of(&key);
-                if keytype == "none".as_bytes() {
-                    return RedisResponse::single(Nil);
-                }
-                if keytype != "list".as_bytes() {
-                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
-                }
-                match storage.lread(&key) {
-                    Some(values) => {
-                        let mut values = values.to_vec();
-                        match values.pop() {
-                            Some(value) => {
-                                if values.is_empty() {
-                                    storage.remove(&key);
-                                } else {
-                                    storage.lwrite(&key, values);
-                                }
-                                RedisResponse::single(BulkString(value))
-                            }
-                            None => RedisResponse::single(Nil),
-                        }
-                    }
-                    None => RedisResponse::single(Nil),
-                }
-            }
-            Command::LPop(key) => {
-                let mut storage = lock_then_release(storage);
-                let keytype = storage.type_of(&key);
-                if keytype == "none".as_bytes() {
-                    return RedisResponse::single(Nil);
-                }
-                if keytype != "list".as_bytes() {
-                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+            Command::RPop(key, count) => list_pop(&mut *storage.lock(&key), &key, count, false),
+            Command::LPop(key, count) => list_pop(&mut *storage.lock(&key), &key, count, true),
+            Command::LPos(key, element, options) => {
+                let mut storage = storage.lock(&key);
+                let values = match storage.entry(&key) {
+                    Some(ValueRef::List(values)) => values,
+                    None => &[],
+                    Some(_) => return RedisResponse::error(RedisCommandError::WrongTypeOperation),
+                };
+
+                let rank = options.rank.unwrap_or(1);
+                let mut positions = values
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, value)| **value == element)
+                    .map(|(index, _)| index as i64)
+                    .collect::<Vec<_>>();
+                // a negative rank walks the list from the tail
+                if rank < 0 {
+                    positions.reverse();
                 }
-                match storage.lread(&key) {
-                    Some(values) => {
-                        let mut values = values.to_vec();
-                        let value = values.remove(0);
-                        if values.is_empty() {
-                            storage.remove(&key);
-                        } else {
-                            storage.lwrite(&key, values);
-                        }
-                        RedisResponse::single(BulkString(value))
+                let mut positions = positions.into_iter().skip(rank.unsigned_abs() as usize - 1);
+
+                match options.count {
+                    None => match positions.next() {
+                        Some(position) => RedisResponse::single(Integer(position)),
+                        None => RedisResponse::single(Nil),
+                    },
+                    Some(count) => {
+                        let count = if count == 0 { usize::MAX } else { count };
+                        RedisResponse::array(positions.take(count).map(Integer).collect())
                     }
-                    None => RedisResponse::single(Nil),
                 }
             }
             Command::LIndex(key, index) => {
-                let mut storage = lock_then_release(storage);
-                let keytype = storage.type_of(&key);
-                if keytype == "none".as_bytes() {
-                    return RedisResponse::single(Nil);
-                }
-                if keytype != "list".as_bytes() {
-                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
-                }
+                let mut storage = storage.lock(&key);
+                let values = match storage.entry(&key) {
+                    Some(ValueRef::List(values)) => values,
+                    None => return RedisResponse::single(Nil),
+                    Some(_) => return RedisResponse::error(RedisCommandError::WrongTypeOperation),
+                };
                 let mut index = index;
-                let values = storage.lread(&key).unwrap().to_vec();
                 let len = values.len() as i64;
                 if index < 0 {
                     index += len;
@@ -278,12 +684,12 @@ pub fn run_command_and_get_response<T: Storage>(
                     return RedisResponse::single(Nil);
                 }
                 match values.get(index as usize) {
-                    Some(value) => RedisResponse::single(SimpleString(value.to_vec())),
+                    Some(value) => RedisResponse::single(BulkString(value.to_vec())),
                     None => RedisResponse::single(Nil),
                 }
             }
             Command::LSet(key, index, value) => {
-                let mut storage = lock_then_release(storage);
+                let mut storage = storage.lock(&key);
                 let keytype = storage.type_of(&key);
                 if keytype == "none".as_bytes() {
                     return RedisResponse::error(RedisCommandError::NoSuchKey);
@@ -304,8 +710,8 @@ pub fn run_command_and_get_response<T: Storage>(
                 storage.lwrite(&key, values);
                 RedisResponse::okay()
             }
-            Command::LInsert(key, place, pivot, value) => {
-                let mut storage = lock_then_release(storage);
+            Command::LInsert(key, before, pivot, value) => {
+                let mut storage = storage.lock(&key);
                 let keytype = storage.type_of(&key);
                 if keytype == "none".as_bytes() {
                     return RedisResponse::single(Integer(0));
@@ -313,26 +719,24 @@ pub fn run_command_and_get_response<T: Storage>(
                 if keytype != "list".as_bytes() {
                     return RedisResponse::error(RedisCommandError::WrongTypeOperation);
                 }
-                if place != b"BEFORE" && place != b"AFTER" {
-                    return RedisResponse::error(RedisCommandError::SyntaxErr);
-                }
                 let mut values = storage.lread(&key).unwrap().to_vec();
                 let index = values.iter().position(|v| v == &pivot);
                 match index {
                     Some(mut i) => {
-                        if place == b"AFTER" {
+                        if !before {
                             i += 1;
                         }
                         values.insert(i, value);
                         let len = values.len();
                         storage.lwrite(&key, values);
+                        context.blocked[client.db].notify(&key);
                         RedisResponse::single(Integer(len as i64))
                     }
                     None => RedisResponse::single(Integer(-1)),
                 }
             }
             Command::LTrim(key, start, stop) => {
-                let mut storage = lock_then_release(storage);
+                let mut storage = storage.lock(&key);
                 let keytype = storage.type_of(&key);
                 if keytype == "none".as_bytes() {
                     return RedisResponse::okay();
@@ -359,15 +763,11 @@ pub fn run_command_and_get_response<T: Storage>(
                 }
                 stop = if stop >= len { len } else { stop + 1 };
                 let vals: Vec<_> = values.drain(start as usize..stop as usize).collect();
-                if vals.is_empty() {
-                    storage.remove(&key);
-                } else {
-                    storage.lwrite(&key, vals);
-                }
+                storage.lwrite(&key, vals);
                 RedisResponse::okay()
             }
             Command::LRem(key, count, value) => {
-                let mut storage = lock_then_release(storage);
+                let mut storage = storage.lock(&key);
                 let keytype = storage.type_of(&key);
                 if keytype == "none".as_bytes() {
                     return RedisResponse::single(Integer(0));
@@ -404,48 +804,43 @@ pub fn run_command_and_get_response<T: Storage>(
                     }
                     vals.push(v.clone());
                 }
-                if vals.is_empty() {
-                    storage.remove(&key);
-                } else {
-                    storage.lwrite(&key, vals);
-                }
+                storage.lwrite(&key, vals);
                 RedisResponse::single(Integer(rem))
             }
+            Command::BLPop(keys, timeout) => blocking_pop(context, client.db, keys, timeout, true),
+            Command::BRPop(keys, timeout) => blocking_pop(context, client.db, keys, timeout, false),
+            Command::WaitKey(key, timeout) => wait_key(context, client.db, key, timeout),
             Command::RPopLPush(src, dest) => {
-                let mut storage = lock_then_release(storage);
-                let src_type = storage.type_of(&src);
-                if src_type == "none".as_bytes() {
-                    return RedisResponse::single(Nil);
-                }
-                if src_type != "list".as_bytes() {
-                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
-                }
-                let dest_type = storage.type_of(&dest);
-                if dest_type != "list".as_bytes() && dest_type != "none".as_bytes() {
-                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                let mut storage = storage.lock_keys([&src, &dest]);
+                match lmove(&mut storage, &src, &dest, Side::Right, Side::Left) {
+                    Ok(Some(value)) => {
+                        context.blocked[client.db].notify(&dest);
+                        RedisResponse::single(BulkString(value))
+                    }
+                    Ok(None) => RedisResponse::single(Nil),
+                    Err(err) => RedisResponse::error(err),
                 }
-                let mut src_values = storage.lread(&src).unwrap().to_vec();
-                let mut dest_values = match storage.lread(&dest) {
-                    Some(vals) => vals.to_vec(),
-                    None => Vec::new(),
-                };
-                match src_values.pop() {
-                    Some(val) => {
-                        let value = val.clone();
-                        dest_values.insert(0, val);
-                        storage.lwrite(&dest, dest_values);
-                        if src_values.is_empty() {
-                            storage.remove(&src);
-                        } else {
-                            storage.lwrite(&src, src_values);
-                        }
+            }
+            Command::LMove(src, dest, from, to) => {
+                let mut storage = storage.lock_keys([&src, &dest]);
+                match lmove(&mut storage, &src, &dest, from, to) {
+                    Ok(Some(value)) => {
+                        context.blocked[client.db].notify(&dest);
                         RedisResponse::single(BulkString(value))
                     }
-                    None => RedisResponse::single(Nil),
+                    Ok(None) => RedisResponse::single(Nil),
+                    Err(err) => RedisResponse::error(err),
                 }
             }
+            Command::BLMove(src, dest, from, to, timeout) => {
+                blocking_move(context, client.db, src, dest, (from, to), timeout)
+            }
+            Command::BRPopLPush(src, dest, timeout) => {
+                let sides = (Side::Right, Side::Left);
+                blocking_move(context, client.db, src, dest, sides, timeout)
+            }
             Command::SAdd(key, values) => {
-                let mut storage = lock_then_release(storage);
+                let mut storage = storage.lock(&key);
                 let keytype = storage.type_of(&key);
                 if keytype != "set".as_bytes() && keytype != "none".as_bytes() {
                     return RedisResponse::error(RedisCommandError::WrongTypeOperation);
@@ -465,21 +860,101 @@ pub fn run_command_and_get_response<T: Storage>(
                     }
                 }
             }
-            Command::SCard(key) => {
-                let mut storage = lock_then_release(storage);
-                let keytype = storage.type_of(&key);
-                if keytype == "none".as_bytes() {
-                    return RedisResponse::single(Integer(0));
+            Command::SInterCard(keys, limit) => {
+                let mut storage = storage.lock_keys(&keys);
+                let mut smallest: Option<(usize, &RedisString)> = None;
+                for key in &keys {
+                    let keytype = storage.type_of(key);
+                    if keytype == "none".as_bytes() {
+                        // the intersection with an empty set is empty
+                        return RedisResponse::single(Integer(0));
+                    }
+                    if keytype != "set".as_bytes() {
+                        return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                    }
+                    let len = storage.sread(key).map(|values| values.len()).unwrap_or(0);
+                    if smallest
+                        .map(|(smallest_len, _)| len < smallest_len)
+                        .unwrap_or(true)
+                    {
+                        smallest = Some((len, key));
+                    }
                 }
-                if keytype != "set".as_bytes() {
-                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+
+                // only the members of the smallest set can be in the intersection
+                let smallest_key = match smallest {
+                    Some((_, key)) => key.clone(),
+                    None => return RedisResponse::single(Integer(0)),
+                };
+                let members = match storage.sread(&smallest_key) {
+                    Some(values) => values.iter().cloned().collect::<Vec<_>>(),
+                    None => vec![],
+                };
+
+                let mut count = 0;
+                for member in members {
+                    let in_all = keys.iter().filter(|key| **key != smallest_key).all(|key| {
+                        storage
+                            .sread(key)
+                            .map(|values| values.contains(&member))
+                            .unwrap_or(false)
+                    });
+                    if in_all {
+                        count += 1;
+                        if count == limit {
+                            break;
+                        }
+                    }
                 }
-                let values = storage.sread(&key).unwrap();
-                let len = values.len() as i64;
-                RedisResponse::single(Integer(len))
+                RedisResponse::single(Integer(count as i64))
+            }
+            Command::Sort(key, options) => {
+                let mut storage = storage.lock(&key);
+                let elements: Vec<RedisString> = match storage.type_of(&key) {
+                    b"list" => storage.lread(&key).cloned().unwrap_or_default(),
+                    b"set" => storage
+                        .sread(&key)
+                        .map(|set| set.iter().cloned().collect())
+                        .unwrap_or_default(),
+                    b"zset" => storage
+                        .zread(&key)
+                        .map(|zset| zset.iter().map(|(member, _)| member.clone()).collect())
+                        .unwrap_or_default(),
+                    b"none" => vec![],
+                    _ => return RedisResponse::error(RedisCommandError::WrongTypeOperation),
+                };
+                drop(storage);
+
+                let elements = match sort(elements, &options) {
+                    Ok(elements) => elements,
+                    Err(err) => return RedisResponse::error(err),
+                };
+                RedisResponse::array(elements.into_iter().map(BulkString).collect())
+            }
+            Command::SCard(key) => match storage.lock(&key).entry(&key) {
+                Some(ValueRef::Set(values)) => RedisResponse::single(Integer(values.len() as i64)),
+                None => RedisResponse::single(Integer(0)),
+                Some(_) => RedisResponse::error(RedisCommandError::WrongTypeOperation),
+            },
+            Command::SMISMember(key, members) => {
+                let mut storage = storage.lock(&key);
+                let set = match storage.entry(&key) {
+                    Some(ValueRef::Set(set)) => Some(set),
+                    None => None,
+                    Some(_) => return RedisResponse::error(RedisCommandError::WrongTypeOperation),
+                };
+                RedisResponse::array(
+                    members
+                        .iter()
+                        .map(|member| {
+                            let is_member = set.is_some_and(|set| set.contains(member));
+                            Integer(is_member as i64)
+                        })
+                        .collect(),
+                )
             }
             Command::SRem(key, values) => {
-                let mut storage = lock_then_release(storage);
+                let mut storage = storage.lock(&key);
                 let keytype = storage.type_of(&key);
                 if keytype == "none".as_bytes() {
                     return RedisResponse::single(Integer(0));
@@ -497,103 +972,2108 @@ pub fn run_command_and_get_response<T: Storage>(
                 storage.swrite(&key, vals);
                 RedisResponse::single(Integer(rem))
             }
-            Command::Del(k) => {
-                let d = lock_then_release(storage).remove(k.as_slice());
-                RedisResponse::single(Integer(d as i64))
-            }
-            Command::Incr(k) => {
-                let mut storage = lock_then_release(storage);
-
-                match storage.read(k.as_slice()) {
-                    Some(value) => {
-                        if let Ok(mut int_val) = std::str::from_utf8(value).unwrap().parse::<i64>()
-                        {
-                            int_val += 1;
-                            let new_value = int_val.to_string().into_bytes();
-                            storage.write(k.as_slice(), new_value.as_slice());
-                            RedisResponse::single(Integer(int_val as i64))
-                        } else {
-                            // handle this error
-                            unimplemented!()
-                        }
+            Command::SPop(key, count) => {
+                let mut storage = storage.lock(&key);
+                // drawn in a single pass over the set, without copying it
+                let popped = match storage.entry(&key) {
+                    Some(ValueRef::Set(members)) => {
+                        let mut rng = lock_then_release(&context.rng);
+                        let amount = count.unwrap_or(1) as usize;
+                        members
+                            .iter()
+                            .choose_multiple(&mut *rng, amount)
+                            .into_iter()
+                            .cloned()
+                            .collect::<Vec<_>>()
                     }
-                    None => {
-                        let val = "1";
-                        storage.write(&k, val.as_bytes());
-                        RedisResponse::single(Integer(1))
+                    None => vec![],
+                    Some(_) => return RedisResponse::error(RedisCommandError::WrongTypeOperation),
+                };
+
+                if !popped.is_empty() {
+                    storage.sremove(&key, &popped);
+                    notify(context, client.db, EventClass::Set, "spop", &key);
+                    if !storage.contains(&key) {
+                        notify(context, client.db, EventClass::Generic, "del", &key);
                     }
                 }
-            }
-            Command::IncrBy(k, increment) => {
-                let mut storage = lock_then_release(storage);
-
-                match storage.read(k.as_slice()) {
-                    Some(value) => {
-                        if let Ok(mut int_val) = std::str::from_utf8(value).unwrap().parse::<i64>()
-                        {
-                            int_val += increment;
-                            let new_value = int_val.to_string().into_bytes();
-                            storage.write(k.as_slice(), new_value.as_slice());
-                            RedisResponse::single(Integer(int_val as i64))
-                        } else {
-                            //RedisResponse::error(...)
-                            unimplemented!()
-                        }
-                    }
-                    None => {
-                        let val = increment.to_string();
-                        storage.write(&k, val.as_bytes());
-                        RedisResponse::single(Integer(increment))
-                    }
+                match count {
+                    Some(_) => RedisResponse::array(popped.into_iter().map(BulkString).collect()),
+                    None => match popped.into_iter().next() {
+                        Some(member) => RedisResponse::single(BulkString(member)),
+                        None => RedisResponse::single(Nil),
+                    },
                 }
             }
-            Command::Type(k) => {
-                let mut s = lock_then_release(storage);
-                let value_type = s.type_of(k.as_slice());
-                RedisResponse::single(SimpleString(value_type.to_vec()))
-            }
-            Command::Exists(k) => {
-                let exists = lock_then_release(storage).contains(&k);
-                let exists: i64 = match exists {
-                    true => 1,
-                    false => 0,
+            Command::SRandMember(key, count) => {
+                let mut storage = storage.lock(&key);
+                let members = match storage.entry(&key) {
+                    Some(ValueRef::Set(members)) => members,
+                    None if count.is_some() => return RedisResponse::array(vec![]),
+                    None => return RedisResponse::single(Nil),
+                    Some(_) => return RedisResponse::error(RedisCommandError::WrongTypeOperation),
                 };
-                RedisResponse::single(Integer(exists))
-            }
-            Command::Ttl(k) => {
-                let ttl = if let Some(meta) = lock_then_release(storage).meta(&k) {
-                    if let Some(expiry) = meta.expiry {
-                        expiry.duration_left_millis() / 1000
-                    } else {
-                        -1
+                let mut rng = lock_then_release(&context.rng);
+
+                let picked = match count {
+                    None => {
+                        return match sample_one(members.iter(), &mut *rng) {
+                            Some(member) => RedisResponse::single(BulkString(member.to_vec())),
+                            None => RedisResponse::single(Nil),
+                        };
                     }
-                } else {
-                    -2
+                    Some(count) => sample(members.iter(), count, &mut *rng),
                 };
-                RedisResponse::single(Integer(ttl))
+                RedisResponse::array(
+                    picked
+                        .into_iter()
+                        .map(|member| BulkString(member.to_vec()))
+                        .collect(),
+                )
             }
-            Command::Pttl(k) => {
-                let ttl = if let Some(meta) = lock_then_release(storage).meta(&k) {
-                    if let Some(expiry) = meta.expiry {
-                        expiry.duration_left_millis()
-                    } else {
-                        -1
-                    }
-                } else {
+            Command::ZAdd(key, options, members) => {
+                let mut storage = storage.lock(&key);
+                let keytype = storage.type_of(&key);
+                if keytype != "zset".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+
+                let mut values = storage.zread(&key).cloned().unwrap_or_default();
+                let mut added = 0;
+                let mut updated = 0;
+                // score of the member with INCR, `None` when the options prevented the update
+                let mut incr_score = None;
+
+                for (score, member) in members {
+                    let old_score = values.score(&member);
+                    match (options.condition, old_score) {
+                        (Some(SetCondition::NotExists), Some(_))
+                        | (Some(SetCondition::Exists), None) => continue,
+                        _ => {}
+                    }
+
+                    let score = match (options.incr, old_score) {
+                        (true, Some(old_score)) => old_score + score,
+                        _ => score,
+                    };
+                    if score.is_nan() {
+                        return RedisResponse::error(RedisCommandError::ScoreNaN);
+                    }
+
+                    match old_score {
+                        Some(old_score) => {
+                            let allowed = match options.comparison {
+                                Some(ScoreComparison::Greater) => score > old_score,
+                                Some(ScoreComparison::Less) => score < old_score,
+                                None => true,
+                            };
+                            if !allowed {
+                                continue;
+                            }
+                            if score != old_score {
+                                updated += 1;
+                            }
+                        }
+                        None => added += 1,
+                    }
+                    values.insert(&member, score);
+                    incr_score = Some(score);
+                }
+
+                storage.zwrite(&key, values);
+
+                match options.incr {
+                    true => match incr_score {
+                        Some(score) => {
+                            RedisResponse::single(RedisResponseType::double(score, client.resp3))
+                        }
+                        None => RedisResponse::single(Nil),
+                    },
+                    false if options.changed => RedisResponse::single(Integer(added + updated)),
+                    false => RedisResponse::single(Integer(added)),
+                }
+            }
+            Command::ZIncrBy(key, increment, member) => {
+                let mut storage = storage.lock(&key);
+                let keytype = storage.type_of(&key);
+                if keytype != "zset".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+
+                let mut values = storage.zread(&key).cloned().unwrap_or_default();
+                let score = values.score(&member).unwrap_or(0.0) + increment;
+                if score.is_nan() {
+                    return RedisResponse::error(RedisCommandError::ScoreNaN);
+                }
+                values.insert(&member, score);
+                storage.zwrite(&key, values);
+
+                RedisResponse::single(RedisResponseType::double(score, client.resp3))
+            }
+            Command::ZPopMin(key, count) => zpop(&mut *storage.lock(&key), &key, count, false),
+            Command::ZPopMax(key, count) => zpop(&mut *storage.lock(&key), &key, count, true),
+            Command::ZUnion(keys, options) => {
+                zcombine(&mut storage.lock_keys(&keys), &keys, &options, false)
+            }
+            Command::ZInter(keys, options) => {
+                zcombine(&mut storage.lock_keys(&keys), &keys, &options, true)
+            }
+            Command::ZDiff(keys, with_scores) => {
+                let mut sets = match zread_all(&mut storage.lock_keys(&keys), &keys) {
+                    Ok(sets) => sets.into_iter(),
+                    Err(err) => return RedisResponse::error(err),
+                };
+                let mut result = RedisSortedSet::new();
+                if let Some(first) = sets.next() {
+                    let others: Vec<_> = sets.collect();
+                    for (member, score) in first {
+                        if others.iter().all(|other| !other.contains_key(&member)) {
+                            result.insert(&member, score);
+                        }
+                    }
+                }
+                zset_reply(&result, with_scores)
+            }
+            Command::ZCount(key, min, max) => match storage.lock(&key).entry(&key) {
+                Some(ValueRef::SortedSet(values)) => {
+                    RedisResponse::single(Integer(values.count(min, max) as i64))
+                }
+                None => RedisResponse::single(Integer(0)),
+                Some(_) => RedisResponse::error(RedisCommandError::WrongTypeOperation),
+            },
+            Command::ZMScore(key, members) => {
+                let mut storage = storage.lock(&key);
+                let values = match storage.entry(&key) {
+                    Some(ValueRef::SortedSet(values)) => Some(values),
+                    None => None,
+                    Some(_) => return RedisResponse::error(RedisCommandError::WrongTypeOperation),
+                };
+                let scores = members
+                    .iter()
+                    .map(
+                        |member| match values.and_then(|values| values.score(member)) {
+                            Some(score) => RedisResponseType::double(score, client.resp3),
+                            None => Nil,
+                        },
+                    )
+                    .collect();
+                RedisResponse::array(scores)
+            }
+            Command::ZScore(key, member) => match storage.lock(&key).entry(&key) {
+                Some(ValueRef::SortedSet(values)) => match values.score(&member) {
+                    Some(score) => {
+                        RedisResponse::single(RedisResponseType::double(score, client.resp3))
+                    }
+                    None => RedisResponse::single(Nil),
+                },
+                None => RedisResponse::single(Nil),
+                Some(_) => RedisResponse::error(RedisCommandError::WrongTypeOperation),
+            },
+            Command::ZRandMember(key, count) => {
+                let mut storage = storage.lock(&key);
+                let members = match storage.entry(&key) {
+                    Some(ValueRef::SortedSet(members)) => members,
+                    None if count.is_some() => return RedisResponse::array(vec![]),
+                    None => return RedisResponse::single(Nil),
+                    Some(_) => return RedisResponse::error(RedisCommandError::WrongTypeOperation),
+                };
+                let mut rng = lock_then_release(&context.rng);
+
+                let (count, with_scores) = match count {
+                    Some(count) => count,
+                    None => {
+                        return match sample_one(members.iter(), &mut *rng) {
+                            Some((member, _)) => RedisResponse::single(BulkString(member.to_vec())),
+                            None => RedisResponse::single(Nil),
+                        };
+                    }
+                };
+                let picked = sample(members.iter(), count, &mut *rng);
+
+                let mut responses = Vec::with_capacity(picked.len() * 2);
+                for (member, score) in picked {
+                    responses.push(BulkString(member.to_vec()));
+                    if with_scores {
+                        responses.push(BulkString(score.to_string().into_bytes()));
+                    }
+                }
+                RedisResponse::array(responses)
+            }
+            Command::ZRangeStore(destination, source, options) => {
+                let mut storage = storage.lock_keys([&source, &destination]);
+                let keytype = storage.type_of(&source);
+                if keytype != "zset".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+                let mut range = RedisSortedSet::new();
+                if let Some(values) = storage.zread(&source) {
+                    for (member, score) in zrange(values, &options) {
+                        range.insert(member, score);
+                    }
+                }
+
+                // the destination is replaced whatever its type, and removed by an empty range
+                let len = range.len();
+                storage.remove(&destination);
+                storage.zwrite(&destination, range);
+                RedisResponse::single(Integer(len as i64))
+            }
+            Command::ZRangeByLex(key, min, max, limit) => {
+                let mut storage = storage.lock(&key);
+                let keytype = storage.type_of(&key);
+                if keytype != "zset".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+                let values = match storage.zread(&key) {
+                    Some(values) => values,
+                    None => return RedisResponse::array(vec![]),
+                };
+                let members = limited(values.range_by_lex(&min, &max), limit);
+                RedisResponse::array(
+                    members
+                        .into_iter()
+                        .map(|member| BulkString(member.clone()))
+                        .collect(),
+                )
+            }
+            Command::Del(k) => {
+                let d = storage.lock(&k).remove(k.as_slice());
+                if d == 1 {
+                    notify(context, client.db, EventClass::Generic, "del", &k);
+                }
+                RedisResponse::single(Integer(d as i64))
+            }
+            Command::Move(k, db) => {
+                let (from, to) = (client.db, db as usize);
+                if db >= context.databases.len() as u64 {
+                    return RedisResponse::error(RedisCommandError::DbIndexOutOfRange);
+                }
+                if from == to {
+                    return RedisResponse::error(RedisCommandError::SameObject);
+                }
+                let moved = {
+                    // the databases are locked in ascending order
+                    let (mut source, mut destination) = match from < to {
+                        true => {
+                            let source = storage.lock(&k);
+                            (source, context.database(to).lock(&k))
+                        }
+                        false => {
+                            let destination = context.database(to).lock(&k);
+                            (storage.lock(&k), destination)
+                        }
+                    };
+                    match (source.contains(&k), destination.contains(&k)) {
+                        (true, false) => {
+                            let expiry = source.meta(&k).and_then(|meta| meta.expiry);
+                            match source.value(&k) {
+                                Some(value) => {
+                                    destination.insert(&k, value, expiry);
+                                    source.remove(&k) == 1
+                                }
+                                None => false,
+                            }
+                        }
+                        _ => false,
+                    }
+                };
+                if moved {
+                    notify(context, from, EventClass::Generic, "move_from", &k);
+                    notify(context, to, EventClass::Generic, "move_to", &k);
+                }
+                RedisResponse::single(Integer(moved as i64))
+            }
+            Command::Dump(key) => {
+                let mut storage = storage.lock(&key);
+                match storage.contains(&key) {
+                    true => match storage.value(&key) {
+                        Some(value) => RedisResponse::single(BulkString(snapshot::dump(&value))),
+                        None => RedisResponse::single(Nil),
+                    },
+                    false => RedisResponse::single(Nil),
+                }
+            }
+            Command::Restore(key, ttl, payload, options) => {
+                let value = match snapshot::undump(&payload) {
+                    Ok(value) => value,
+                    Err(BadDump::VersionOrChecksum) => {
+                        return RedisResponse::error(RedisCommandError::BadPayload)
+                    }
+                    Err(BadDump::Format) => {
+                        return RedisResponse::error(RedisCommandError::BadDataFormat)
+                    }
+                };
+                let mut storage = storage.lock(&key);
+                if !options.replace && storage.contains(&key) {
+                    return RedisResponse::error(RedisCommandError::BusyKey);
+                }
+                let expiry = match (ttl, options.absttl) {
+                    (0, _) => None,
+                    (timestamp, true) => Some(Expiry {
+                        timestamp: i64::try_from(timestamp).unwrap_or(i64::MAX),
+                    }),
+                    (ttl, false) => match Expiry::new_from_millis(ttl) {
+                        Ok(expiry) => Some(expiry),
+                        Err(err) => return RedisResponse::error(err.into()),
+                    },
+                };
+                // as in Redis, a key restored already expired is only removed
+                if expiry.is_some_and(|expiry| expiry.duration_left_millis() <= 0) {
+                    if storage.remove(&key) == 1 {
+                        notify(context, client.db, EventClass::Generic, "del", &key);
+                    }
+                    return RedisResponse::okay();
+                }
+                storage.insert(&key, value, expiry);
+                // the access metadata migrated with the key keeps its eviction priority
+                if let Some(meta) = storage.meta_mut(&key) {
+                    if let Some(secs) = options.idletime {
+                        meta.set_idle_time_secs(secs);
+                    }
+                    if let Some(freq) = options.freq {
+                        meta.set_frequency(freq);
+                    }
+                }
+                notify(context, client.db, EventClass::Generic, "restore", &key);
+                RedisResponse::okay()
+            }
+            Command::Copy(source, destination, db, replace) => {
+                let (from, to) = (client.db, db.map_or(client.db, |db| db as usize));
+                if to >= context.databases.len() {
+                    return RedisResponse::error(RedisCommandError::DbIndexOutOfRange);
+                }
+                if from == to && source == destination {
+                    return RedisResponse::error(RedisCommandError::SameObject);
+                }
+                let copied = match from == to {
+                    true => {
+                        let mut storage = storage.lock_keys([&source, &destination]);
+                        let copy = copy_of(&mut storage, &source);
+                        paste(&mut storage, &destination, copy, replace)
+                    }
+                    // the databases are locked in ascending order
+                    false if from < to => {
+                        let mut source_storage = storage.lock(&source);
+                        let mut storage = context.database(to).lock(&destination);
+                        let copy = copy_of(&mut *source_storage, &source);
+                        paste(&mut *storage, &destination, copy, replace)
+                    }
+                    false => {
+                        let mut storage = context.database(to).lock(&destination);
+                        let copy = copy_of(&mut *context.database(from).lock(&source), &source);
+                        paste(&mut *storage, &destination, copy, replace)
+                    }
+                };
+                if copied {
+                    notify(context, to, EventClass::Generic, "copy_to", &destination);
+                    if to != from {
+                        // the keys of the command are only invalidated in the db of the client
+                        context.tracking.invalidate(to, &[destination]);
+                    }
+                }
+                RedisResponse::single(Integer(copied as i64))
+            }
+            Command::Incr(k) => {
+                let mut storage = storage.lock(&k);
+                incr_by(&mut *storage, &k, 1)
+            }
+            Command::IncrBy(k, increment) => {
+                let mut storage = storage.lock(&k);
+                incr_by(&mut *storage, &k, increment)
+            }
+            Command::Keys(pattern) => {
+                let mut storage = storage.lock_all();
+                let mut keys = vec![];
+                for (index, key) in storage.keys().into_iter().enumerate() {
+                    if index % DEADLINE_CHECK_INTERVAL == DEADLINE_CHECK_INTERVAL - 1
+                        && deadline.is_exceeded()
+                    {
+                        return RedisResponse::error(RedisCommandError::TimeLimitExceeded);
+                    }
+                    if glob_match(&pattern, &key) && storage.contains(&key) {
+                        keys.push(BulkString(key));
+                    }
+                }
+                RedisResponse::array(keys)
+            }
+            Command::Scan(cursor, options) => {
+                let (cursor, batch) = storage.scan(
+                    cursor,
+                    options.pattern.as_deref(),
+                    |storage, key| {
+                        // an unknown type simply matches no key
+                        let type_matches = match &options.key_type {
+                            Some(key_type) => storage.type_of(key).eq_ignore_ascii_case(key_type),
+                            None => true,
+                        };
+                        type_matches && storage.contains(key)
+                    },
+                    options.count,
+                );
+
+                let keys = batch.into_iter().map(BulkString).collect();
+                scan_response(cursor, keys)
+            }
+            Command::HScan(key, cursor, options) => {
+                let mut storage = storage.lock(&key);
+                let keytype = storage.type_of(&key);
+                if keytype != "hash".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+                let fields = match storage.hread_all(&key) {
+                    Some(fields) => fields.clone(),
+                    None => HashMap::new(),
+                };
+
+                let pattern = options.pattern.as_deref();
+                let (cursor, batch) = scan(fields, cursor, pattern, |_| true, options.count);
+                let fields = batch
+                    .into_iter()
+                    .flat_map(|(field, value)| vec![BulkString(field), BulkString(value)])
+                    .collect();
+                scan_response(cursor, fields)
+            }
+            Command::SScan(key, cursor, options) => {
+                let mut storage = storage.lock(&key);
+                let keytype = storage.type_of(&key);
+                if keytype != "set".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+                let members = match storage.sread(&key) {
+                    Some(members) => members.iter().map(|member| (member.clone(), ())).collect(),
+                    None => vec![],
+                };
+
+                let pattern = options.pattern.as_deref();
+                let (cursor, batch) = scan(members, cursor, pattern, |_| true, options.count);
+                let members = batch
+                    .into_iter()
+                    .map(|(member, _)| BulkString(member))
+                    .collect();
+                scan_response(cursor, members)
+            }
+            Command::ZScan(key, cursor, options) => {
+                let mut storage = storage.lock(&key);
+                let keytype = storage.type_of(&key);
+                if keytype != "zset".as_bytes() && keytype != "none".as_bytes() {
+                    return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+                }
+                let members = match storage.zread(&key) {
+                    Some(set) => set
+                        .iter()
+                        .map(|(member, score)| (member.clone(), score))
+                        .collect(),
+                    None => vec![],
+                };
+
+                let pattern = options.pattern.as_deref();
+                let (cursor, batch) = scan(members, cursor, pattern, |_| true, options.count);
+                let members = batch
+                    .into_iter()
+                    .flat_map(|(member, score)| {
+                        vec![
+                            BulkString(member),
+                            BulkString(score.to_string().into_bytes()),
+                        ]
+                    })
+                    .collect();
+                scan_response(cursor, members)
+            }
+            Command::Type(k) => {
+                let mut s = storage.lock(&k);
+                let value_type = s.type_of(k.as_slice());
+                RedisResponse::single(SimpleString(value_type.to_vec()))
+            }
+            Command::Exists(k) => {
+                let exists = storage.lock(&k).contains(&k);
+                let exists: i64 = match exists {
+                    true => 1,
+                    false => 0,
+                };
+                RedisResponse::single(Integer(exists))
+            }
+            Command::Ttl(k) => {
+                let ttl = if let Some(meta) = storage.lock(&k).meta(&k) {
+                    if let Some(expiry) = meta.expiry {
+                        // rounded to the closest second, as Redis does
+                        (expiry.duration_left_millis() + 500) / 1000
+                    } else {
+                        -1
+                    }
+                } else {
+                    -2
+                };
+                RedisResponse::single(Integer(ttl))
+            }
+            Command::Pttl(k) => {
+                let ttl = if let Some(meta) = storage.lock(&k).meta(&k) {
+                    if let Some(expiry) = meta.expiry {
+                        expiry.duration_left_millis()
+                    } else {
+                        -1
+                    }
+                } else {
                     -2
                 };
                 RedisResponse::single(Integer(ttl))
             }
-            Command::Info => RedisResponse::single(BulkString("".as_bytes().to_vec())),
+            Command::Object(subcommand) => {
+                let key = match &subcommand {
+                    ObjectSubcommand::Encoding(k)
+                    | ObjectSubcommand::RefCount(k)
+                    | ObjectSubcommand::IdleTime(k)
+                    | ObjectSubcommand::Freq(k) => k.clone(),
+                };
+                let mut storage = storage.lock(&key);
+                match subcommand {
+                    ObjectSubcommand::Encoding(k) => {
+                        if !storage.contains(&k) {
+                            return RedisResponse::error(RedisCommandError::NoSuchKey);
+                        }
+                        match storage.meta(&k) {
+                            Some(meta) => RedisResponse::single(BulkString(
+                                meta.encoding.name().as_bytes().to_vec(),
+                            )),
+                            None => RedisResponse::error(RedisCommandError::NoSuchKey),
+                        }
+                    }
+                    ObjectSubcommand::RefCount(k) => {
+                        if !storage.contains(&k) {
+                            return RedisResponse::error(RedisCommandError::NoSuchKey);
+                        }
+                        let value = storage.read(&k).map(|value| value.to_vec());
+                        let encoding = storage.meta(&k).map(|meta| meta.encoding);
+                        RedisResponse::single(Integer(refcount(encoding, value.as_deref())))
+                    }
+                    ObjectSubcommand::IdleTime(k) => {
+                        if !storage.contains(&k) {
+                            return RedisResponse::error(RedisCommandError::NoSuchKey);
+                        }
+                        match storage.meta(&k) {
+                            Some(meta) => {
+                                RedisResponse::single(Integer(meta.idle_time_millis() / 1000))
+                            }
+                            None => RedisResponse::error(RedisCommandError::NoSuchKey),
+                        }
+                    }
+                    ObjectSubcommand::Freq(k) => {
+                        if !context.config().maxmemory_policy.is_lfu() {
+                            return RedisResponse::error(RedisCommandError::LfuPolicyNotSelected);
+                        }
+                        if !storage.contains(&k) {
+                            return RedisResponse::error(RedisCommandError::NoSuchKey);
+                        }
+                        match storage.meta(&k) {
+                            Some(meta) => RedisResponse::single(Integer(meta.frequency() as i64)),
+                            None => RedisResponse::error(RedisCommandError::NoSuchKey),
+                        }
+                    }
+                }
+            }
+            Command::GetKeys(args) => match key_positions(&args) {
+                Ok(positions) => RedisResponse::array(
+                    positions
+                        .into_iter()
+                        .map(|position| BulkString(args[position].clone()))
+                        .collect(),
+                ),
+                Err(err) => RedisResponse::error(err),
+            },
+            Command::CommandInfo(names) => {
+                let specs = match names.is_empty() {
+                    true => command_specs().into_iter().map(Some).collect(),
+                    false => names
+                        .iter()
+                        .map(|name| command_spec(name))
+                        .collect::<Vec<_>>(),
+                };
+                RedisResponse::array(specs.into_iter().map(command_info).collect())
+            }
+            Command::CommandDocs(names) => {
+                // unknown commands are left out of the reply
+                let docs = match names.is_empty() {
+                    true => command_docs(),
+                    false => names.iter().filter_map(|name| command_doc(name)).collect(),
+                };
+                RedisResponse::map(
+                    docs.into_iter()
+                        .map(|doc| {
+                            (
+                                BulkString(doc.name.clone().into_bytes()),
+                                command_doc_entry(&doc, client.resp3),
+                            )
+                        })
+                        .collect(),
+                    client.resp3,
+                )
+            }
+            Command::Memory(MemorySubcommand::Usage(k)) => {
+                let mut storage = storage.lock(&k);
+                // an expired key is removed, and not reported
+                if !storage.contains(&k) {
+                    return RedisResponse::single(Nil);
+                }
+                match storage.memory_usage(&k) {
+                    Some(bytes) => RedisResponse::single(Integer(bytes as i64)),
+                    None => RedisResponse::single(Nil),
+                }
+            }
+            Command::Memory(MemorySubcommand::Stats) => {
+                let stats = MemoryStats::of(context);
+                let percentage = match stats.total {
+                    0 => 0.0,
+                    total => stats.dataset() as f64 * 100.0 / total as f64,
+                };
+                let entry = |name: &str, value| (BulkString(name.as_bytes().to_vec()), value);
+                RedisResponse::map(
+                    vec![
+                        entry("total.allocated", Integer(stats.total as i64)),
+                        entry("overhead.total", Integer(stats.overhead as i64)),
+                        entry("keys.count", Integer(stats.keys as i64)),
+                        entry(
+                            "keys.bytes-per-key",
+                            Integer(stats.total.checked_div(stats.keys).unwrap_or(0) as i64),
+                        ),
+                        entry("dataset.bytes", Integer(stats.dataset() as i64)),
+                        entry(
+                            "dataset.percentage",
+                            BulkString(percentage.to_string().into_bytes()),
+                        ),
+                    ],
+                    client.resp3,
+                )
+            }
+            Command::Memory(MemorySubcommand::Doctor) => {
+                let report = memory_doctor(context).into_bytes();
+                RedisResponse::single(RedisResponseType::verbatim(b"txt", report, client.resp3))
+            }
+            Command::Client(ClientSubcommand::Tracking(on)) => {
+                client.tracking = on;
+                // the invalidations are sent to the message queue of the client
+                let subscriber = subscriber(context, client);
+                if !on {
+                    context.tracking.forget(subscriber);
+                }
+                RedisResponse::okay()
+            }
+            Command::Client(ClientSubcommand::Id) => {
+                RedisResponse::single(RedisResponseType::Integer(client.id as i64))
+            }
+            Command::Client(ClientSubcommand::Kill(filter)) => {
+                let killed = context.clients.kill(&filter, client.id);
+                // the connection of the client is closed after the reply
+                client.killed = killed.contains(&client.id);
+                RedisResponse::single(RedisResponseType::Integer(killed.len() as i64))
+            }
+            Command::Client(ClientSubcommand::Compress(threshold)) => {
+                client.compression = threshold;
+                RedisResponse::okay()
+            }
+            Command::Config(ConfigSubcommand::Set(parameters)) => {
+                let mut config = context.config().clone();
+                for (name, value) in &parameters {
+                    if let Err(err) = config.set(name, value) {
+                        return RedisResponse::error(err);
+                    }
+                }
+                context.set_config(config);
+                RedisResponse::okay()
+            }
+            Command::Config(ConfigSubcommand::Get(pattern)) => {
+                let pattern = pattern.to_ascii_lowercase();
+                let parameters = context
+                    .config()
+                    .parameters()
+                    .into_iter()
+                    .filter(|(name, _)| glob_match(&pattern, name.as_bytes()))
+                    .map(|(name, value)| {
+                        (
+                            BulkString(name.as_bytes().to_vec()),
+                            BulkString(value.into_bytes()),
+                        )
+                    })
+                    .collect();
+                RedisResponse::map(parameters, client.resp3)
+            }
+            Command::Help(command) => RedisResponse::array(
+                help(command)
+                    .into_iter()
+                    .map(|line| SimpleString(line.into_bytes()))
+                    .collect(),
+            ),
+            Command::Acl(AclSubcommand::SetUser(username, rules)) => {
+                context.acl.set_user(&username, rules);
+                RedisResponse::okay()
+            }
+            Command::Acl(AclSubcommand::GetUser(username)) => {
+                let user = match context.acl.user(&username) {
+                    Some(user) => user,
+                    None => return RedisResponse::single(Nil),
+                };
+                let flags = user
+                    .flags()
+                    .into_iter()
+                    .map(|flag| BulkString(flag.as_bytes().to_vec()))
+                    .collect();
+                // the passwords are not reported, Redis only gives their hashes
+                RedisResponse::map(
+                    vec![
+                        (BulkString(b"flags".to_vec()), Array(flags)),
+                        (
+                            BulkString(b"commands".to_vec()),
+                            BulkString(user.commands().into_bytes()),
+                        ),
+                    ],
+                    client.resp3,
+                )
+            }
+            Command::Acl(AclSubcommand::List) => RedisResponse::array(
+                context
+                    .acl
+                    .users()
+                    .into_iter()
+                    .map(|(name, user)| BulkString(user.describe(&name).into_bytes()))
+                    .collect(),
+            ),
+            Command::Acl(AclSubcommand::WhoAmI) => match context.acl.whoami(client) {
+                Some(name) => RedisResponse::single(BulkString(name.into_bytes())),
+                None => RedisResponse::error(RedisCommandError::NoAuth),
+            },
+            Command::Debug(DebugSubcommand::Object(k)) => {
+                let mut storage = storage.lock(&k);
+                if !storage.contains(&k) {
+                    return RedisResponse::error(RedisCommandError::NoSuchKey);
+                }
+                let (meta, value) = match (storage.meta(&k), storage.value(&k)) {
+                    (Some(meta), Some(value)) => (meta, value),
+                    _ => return RedisResponse::error(RedisCommandError::NoSuchKey),
+                };
+                let refcount = match &value {
+                    RedisValue::String(value) => refcount(Some(meta.encoding), Some(value)),
+                    _ => 1,
+                };
+                let mut line = format!(
+                    "Value at:0x0 refcount:{} encoding:{} serializedlength:{} lru_seconds_idle:{}",
+                    refcount,
+                    meta.encoding.name(),
+                    snapshot::serialized_len(&value),
+                    meta.idle_time_millis() / 1000
+                );
+                if let (RedisValue::List(values), Encoding::Quicklist) = (&value, meta.encoding) {
+                    let limits = context.config().encoding_limits();
+                    let nodes = Encoding::quicklist_nodes(values, &limits);
+                    let average = values.len() as f64 / nodes.max(1) as f64;
+                    line.push_str(&format!(" ql_nodes:{} ql_avg_node:{:.2}", nodes, average));
+                }
+                RedisResponse::single(BulkString(line.into_bytes()))
+            }
+            Command::Debug(DebugSubcommand::Reload) => {
+                let path = context.config().snapshot_path();
+                let dirty = context.dirty.load(Ordering::SeqCst);
+                match context.reload_snapshot(&path) {
+                    Ok(_) => {
+                        context.saved(dirty);
+                        RedisResponse::okay()
+                    }
+                    Err(err) => RedisResponse::error(err.into()),
+                }
+            }
+            Command::Debug(DebugSubcommand::SetActiveExpire(enabled)) => {
+                context.active_expire.store(enabled, Ordering::SeqCst);
+                RedisResponse::okay()
+            }
+            Command::Debug(DebugSubcommand::Sleep(secs)) => {
+                thread::sleep(Duration::from_secs_f64(secs));
+                RedisResponse::okay()
+            }
+            Command::Debug(DebugSubcommand::StringMatch(pattern, string)) => RedisResponse::single(
+                RedisResponseType::Integer(glob_match(&pattern, &string) as i64),
+            ),
+            Command::Debug(DebugSubcommand::InternalStats) => {
+                if !context.config().debug_internal_stats {
+                    return RedisResponse::error(RedisCommandError::InternalStatsDisabled);
+                }
+                // the stripes of the db of the client
+                let sizes = storage.stripe_sizes();
+                let blocked = context
+                    .blocked
+                    .iter()
+                    .map(|blocked| blocked.count())
+                    .sum::<usize>();
+                RedisResponse::map(
+                    vec![
+                        (BulkString(b"shards".to_vec()), Integer(sizes.len() as i64)),
+                        (
+                            BulkString(b"shard_keys".to_vec()),
+                            Array(sizes.into_iter().map(|size| Integer(size as i64)).collect()),
+                        ),
+                        (
+                            BulkString(b"blocked_clients".to_vec()),
+                            Integer(blocked as i64),
+                        ),
+                        (
+                            BulkString(b"transactions".to_vec()),
+                            Integer(context.transactions.load(Ordering::SeqCst) as i64),
+                        ),
+                    ],
+                    client.resp3,
+                )
+            }
+            Command::Debug(DebugSubcommand::NoOp) => RedisResponse::okay(),
+            Command::Latency(LatencySubcommand::Latest) => RedisResponse::array(
+                context
+                    .latency
+                    .latest()
+                    .into_iter()
+                    .map(|sample| {
+                        Array(vec![
+                            BulkString(sample.event.into_bytes()),
+                            Integer(sample.timestamp),
+                            Integer(sample.latency as i64),
+                            Integer(sample.max as i64),
+                        ])
+                    })
+                    .collect(),
+            ),
+            Command::Latency(LatencySubcommand::History(event)) => RedisResponse::array(
+                context
+                    .latency
+                    .history(&event)
+                    .into_iter()
+                    .map(|(timestamp, latency)| {
+                        Array(vec![Integer(timestamp), Integer(latency as i64)])
+                    })
+                    .collect(),
+            ),
+            Command::Latency(LatencySubcommand::Reset(events)) => {
+                RedisResponse::single(Integer(context.latency.reset(&events) as i64))
+            }
+            Command::Save => match context.save() {
+                Ok(_) => RedisResponse::okay(),
+                Err(err) => RedisResponse::error(err.into()),
+            },
+            Command::Shutdown(mode) => {
+                let save = match mode {
+                    ShutdownMode::Default => !context.config().save.is_empty(),
+                    ShutdownMode::NoSave => false,
+                    ShutdownMode::Save => true,
+                };
+                if save {
+                    if let Err(err) = context.save() {
+                        log::warn!("saving the snapshot before the shutdown failed: {}", err);
+                        return RedisResponse::error(RedisCommandError::ShutdownSaveFailed);
+                    }
+                }
+                // as in Redis the other clients are disconnected, and this one once the
+                // commands it sent before are replied
+                context.clients.kill(&KillFilter::default(), client.id);
+                context.shutdown.store(true, Ordering::SeqCst);
+                client.shutdown = true;
+                RedisResponse::okay()
+            }
+            Command::BgSave => {
+                if context.bgsave_in_progress.swap(true, Ordering::SeqCst) {
+                    return RedisResponse::error(RedisCommandError::BackgroundSaveInProgress);
+                }
+
+                // copy the entries while holding the lock so the snapshot is consistent,
+                // encoding and writing them happens in the background
+                let dirty_count = context.dirty.load(Ordering::SeqCst);
+                let entries = context.take_snapshot();
+                let path = context.config().snapshot_path();
+                let last_save = context.last_save.clone();
+                let dirty = context.dirty.clone();
+                let bgsave_in_progress = context.bgsave_in_progress.clone();
+
+                let _ = thread::spawn(move || {
+                    // see `ServerContext::saved`
+                    if snapshot::save(&path, &entries).is_ok() {
+                        last_save.store(Utc::now().timestamp(), Ordering::SeqCst);
+                        dirty.fetch_sub(dirty_count, Ordering::SeqCst);
+                    }
+                    bgsave_in_progress.store(false, Ordering::SeqCst);
+                });
+
+                RedisResponse::single(SimpleString(b"Background saving started".to_vec()))
+            }
+            Command::BgRewriteAof => match aof::rewrite(context) {
+                Ok(()) => RedisResponse::single(SimpleString(
+                    b"Background append only file rewriting started".to_vec(),
+                )),
+                Err(err) => RedisResponse::error(err),
+            },
+            // the writes are flushed at once rather than waited for, and the replicas do not
+            // report their fsyncs, so none of them is counted
+            Command::WaitAof(numlocal, _, _) => match context.replication.aof.fsync() {
+                None if numlocal > 0 => {
+                    RedisResponse::error(RedisCommandError::WaitAofNoAppendOnly)
+                }
+                Some(Err(err)) => {
+                    log::warn!("flushing the append only file failed: {}", err);
+                    RedisResponse::array(vec![Integer(0), Integer(0)])
+                }
+                synced => RedisResponse::array(vec![Integer(synced.is_some() as i64), Integer(0)]),
+            },
+            Command::LastSave => {
+                RedisResponse::single(Integer(context.last_save.load(Ordering::SeqCst)))
+            }
+            Command::Info(sections) => {
+                let info = info(context, &sections).into_bytes();
+                RedisResponse::single(RedisResponseType::verbatim(b"txt", info, client.resp3))
+            }
+            Command::Lolwut => {
+                let version = format!("RedisLess ver. {}\n", env!("CARGO_PKG_VERSION"));
+                let version = version.into_bytes();
+                RedisResponse::single(RedisResponseType::verbatim(b"txt", version, client.resp3))
+            }
             Command::Ping => RedisResponse::pong(),
+            Command::Auth(username, password) => {
+                match context
+                    .acl
+                    .authenticate(client, username.as_deref(), &password)
+                {
+                    Ok(_) => RedisResponse::okay(),
+                    Err(err) => RedisResponse::error(err),
+                }
+            }
+            Command::Hello(options) => {
+                match &options.auth {
+                    Some((username, password)) => {
+                        let authenticated =
+                            context.acl.authenticate(client, Some(username), password);
+                        if let Err(err) = authenticated {
+                            return RedisResponse::error(err);
+                        }
+                    }
+                    None if context.acl.whoami(client).is_none() => {
+                        return RedisResponse::error(RedisCommandError::HelloNoAuth);
+                    }
+                    None => {}
+                }
+                if let Some(name) = options.setname {
+                    if name.chars().any(|c| !('!'..='~').contains(&c)) {
+                        return RedisResponse::error(RedisCommandError::InvalidClientName);
+                    }
+                    client.name = Some(name);
+                }
+                if let Some(protocol) = options.protocol {
+                    client.resp3 = protocol == 3;
+                }
+
+                let role = match context.replication.is_replica() {
+                    true => "replica",
+                    false => "master",
+                };
+                let field = |name: &str| BulkString(name.as_bytes().to_vec());
+                RedisResponse::map(
+                    vec![
+                        (field("server"), field("redisless")),
+                        (field("version"), field(env!("CARGO_PKG_VERSION"))),
+                        (field("proto"), Integer(if client.resp3 { 3 } else { 2 })),
+                        (field("id"), Integer(client.id as i64)),
+                        (field("mode"), field("standalone")),
+                        (field("role"), field(role)),
+                        (field("modules"), Array(vec![])),
+                    ],
+                    client.resp3,
+                )
+            }
             Command::Dbsize => {
-                let storage = lock_then_release(storage);
+                // the sum of the counts of the stripes, which scans no key, see `Storage::size`
+                let storage = storage.lock_all();
                 let size = storage.size() as i64;
                 RedisResponse::single(Integer(size))
             }
+            Command::RandomKey => {
+                let mut storage = storage.lock_all();
+                let mut rng = lock_then_release(&context.rng);
+                // an expired key is removed, and another one drawn
+                let mut expired = HashSet::new();
+                loop {
+                    match sample_keys(&storage, &mut rng, 1, &expired).pop() {
+                        Some(key) if storage.contains(&key) => {
+                            break RedisResponse::single(BulkString(key))
+                        }
+                        Some(key) => {
+                            expired.insert(key);
+                        }
+                        None => break RedisResponse::single(Nil),
+                    }
+                }
+            }
+            Command::Select(index) => {
+                // one database per `databases` of the configuration, checked at start
+                if index >= context.databases.len() {
+                    return RedisResponse::error(RedisCommandError::DbIndexOutOfRange);
+                }
+                client.db = index;
+                RedisResponse::okay()
+            }
+            Command::ReplicaOf(host, port) => {
+                context.replication.set_primary(Some((host, port)));
+                RedisResponse::okay()
+            }
+            Command::ReplicaOfNoOne => {
+                context.replication.set_primary(None);
+                RedisResponse::okay()
+            }
+            Command::Sync | Command::PSync => {
+                let (databases, commands) =
+                    context.replication.add_replica(|| context.take_snapshot());
+                client.replica = Some(commands);
+
+                let mut reply = vec![];
+                if let Command::PSync = command {
+                    let line = format!("+FULLRESYNC {} 0\r\n", context.replication.replid);
+                    reply.extend_from_slice(line.as_bytes());
+                }
+                reply.append(&mut snapshot::encode_framed(&databases));
+                RedisResponse::raw(reply)
+            }
+            // the replication offset is not tracked, and stays at 0 as in `FULLRESYNC`
+            Command::Role => match context.replication.primary() {
+                Some((host, port, link_up)) => RedisResponse::array(vec![
+                    BulkString(b"slave".to_vec()),
+                    BulkString(host.into_bytes()),
+                    Integer(port as i64),
+                    BulkString(if link_up {
+                        b"connected".to_vec()
+                    } else {
+                        b"connecting".to_vec()
+                    }),
+                    Integer(0),
+                ]),
+                // the address of the replicas is not known, so they are not listed
+                None => RedisResponse::array(vec![
+                    BulkString(b"master".to_vec()),
+                    Integer(0),
+                    Array(vec![]),
+                ]),
+            },
+            Command::Subscribe(channels) => {
+                let resp3 = client.resp3;
+                let subscriber = subscriber(context, client);
+                RedisResponse::raw(context.pubsub.subscribe(subscriber, channels, resp3))
+            }
+            Command::PSubscribe(patterns) => {
+                let resp3 = client.resp3;
+                let subscriber = subscriber(context, client);
+                RedisResponse::raw(context.pubsub.psubscribe(subscriber, patterns, resp3))
+            }
+            Command::Unsubscribe(channels) => {
+                let resp3 = client.resp3;
+                let subscriber = subscriber(context, client);
+                RedisResponse::raw(context.pubsub.unsubscribe(subscriber, channels, resp3))
+            }
+            Command::PUnsubscribe(patterns) => {
+                let resp3 = client.resp3;
+                let subscriber = subscriber(context, client);
+                RedisResponse::raw(context.pubsub.punsubscribe(subscriber, patterns, resp3))
+            }
+            Command::Publish(channel, message) => {
+                let received = context.pubsub.publish(&channel, &message);
+                RedisResponse::single(Integer(received as i64))
+            }
+            Command::PubSub(subcommand) => match subcommand {
+                PubSubSubcommand::Channels(pattern) => RedisResponse::array(
+                    context
+                        .pubsub
+                        .channels(pattern.as_deref())
+                        .into_iter()
+                        .map(BulkString)
+                        .collect(),
+                ),
+                PubSubSubcommand::NumSub(channels) => RedisResponse::array(
+                    channels
+                        .into_iter()
+                        .flat_map(|channel| {
+                            let count = context.pubsub.numsub(&channel);
+                            vec![BulkString(channel), Integer(count as i64)]
+                        })
+                        .collect(),
+                ),
+                PubSubSubcommand::NumPat => {
+                    RedisResponse::single(Integer(context.pubsub.numpat() as i64))
+                }
+            },
+            // ASYNC and SYNC flush the same way for now
+            Command::FlushDb(mode) => {
+                flush(std::iter::once(storage), mode);
+                context.tracking.invalidate_all(Some(client.db));
+                RedisResponse::okay()
+            }
+            Command::FlushAll(mode) => {
+                flush(&context.databases, mode);
+                context.tracking.invalidate_all(None);
+                RedisResponse::okay()
+            }
             Command::Quit => RedisResponse::quit(),
+            Command::Multi => match client.transaction {
+                Some(_) => RedisResponse::error(RedisCommandError::NestedMulti),
+                None => {
+                    client.transaction = Some(Transaction::default());
+                    context.transactions.fetch_add(1, Ordering::SeqCst);
+                    RedisResponse::okay()
+                }
+            },
+            // the queued commands are run by `handle_request`, left is an `EXEC` out of a
+            // transaction
+            Command::Exec => RedisResponse::error(RedisCommandError::WithoutMulti("EXEC")),
+            Command::Discard => match client.transaction.take() {
+                Some(_) => {
+                    context.transactions.fetch_sub(1, Ordering::SeqCst);
+                    RedisResponse::okay()
+                }
+                None => RedisResponse::error(RedisCommandError::WithoutMulti("DISCARD")),
+            },
+            Command::Monitor => {
+                client.monitor = true;
+                // the commands are fed to the message queue of the client
+                context.monitors.add(subscriber(context, client));
+                RedisResponse::okay()
+            }
         },
         Err(err) => RedisResponse::error(err),
     };
     response
 }
+
+// refcount Redis reports for a string value of the given encoding, or for no value. Only
+// the values encoded as integers are shared.
+fn refcount(encoding: Option<Encoding>, value: Option<&[u8]>) -> i64 {
+    let shared = value
+        .filter(|_| encoding == Some(Encoding::Int))
+        .and_then(parse_i64)
+        .map(|value| (0..SHARED_INTEGERS).contains(&value))
+        .unwrap_or(false);
+    if shared {
+        SHARED_REFCOUNT
+    } else {
+        1
+    }
+}
+
+/// Substring of value between the start and end offsets, both inclusive. Negative offsets
+/// count from the end of the string, and out of range offsets are clamped as in Redis.
+// the ranges of a match of an `LCS`, as `((start, end), (start, end))` in each string
+type LcsMatch = ((usize, usize), (usize, usize));
+
+/// Longest common subsequence of a and b along with its matches, the runs of consecutive
+/// bytes found in both, from the last one to the first as Redis replies them
+fn lcs(a: &[u8], b: &[u8]) -> (Vec<u8>, Vec<LcsMatch>) {
+    let width = b.len() + 1;
+    // lengths[i * width + j]: length of the subsequence of the first i bytes of a and the
+    // first j bytes of b
+    let mut lengths = vec![0u32; (a.len() + 1) * width];
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            lengths[i * width + j] = match a[i - 1] == b[j - 1] {
+                true => lengths[(i - 1) * width + j - 1] + 1,
+                false => lengths[(i - 1) * width + j].max(lengths[i * width + j - 1]),
+            };
+        }
+    }
+
+    let mut common = Vec::with_capacity(lengths[a.len() * width + b.len()] as usize);
+    let mut matches = vec![];
+    let mut current: Option<LcsMatch> = None;
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 && j > 0 {
+        if a[i - 1] == b[j - 1] {
+            common.push(a[i - 1]);
+            // walking back diagonally, the byte extends the current match
+            current = Some(match current {
+                Some(((_, a_end), (_, b_end))) => ((i - 1, a_end), (j - 1, b_end)),
+                None => ((i - 1, i - 1), (j - 1, j - 1)),
+            });
+            i -= 1;
+            j -= 1;
+        } else {
+            if lengths[(i - 1) * width + j] > lengths[i * width + j - 1] {
+                i -= 1;
+            } else {
+                j -= 1;
+            }
+            matches.extend(current.take());
+        }
+    }
+    matches.extend(current);
+    common.reverse();
+    (common, matches)
+}
+
+fn get_range(value: &[u8], start: i64, end: i64) -> &[u8] {
+    if start < 0 && end < 0 && start > end {
+        return &[];
+    }
+
+    match byte_range(value.len(), start, end) {
+        Some((start, end)) => &value[start..=end],
+        None => &[],
+    }
+}
+
+/// Resolve the inclusive start and end byte offsets of a string of len bytes, negative
+/// offsets counting from the end. `None` when the range is empty.
+fn byte_range(len: usize, start: i64, end: i64) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 { len + start } else { start }.max(0);
+    let end = if end < 0 { len + end } else { end }.max(0).min(len - 1);
+
+    if len == 0 || start > end {
+        return None;
+    }
+    Some((start as usize, end as usize))
+}
+
+/// Position of the first bit set to bit within the start and end bytes of value
+fn bit_pos(value: &[u8], bit: u8, start: Option<i64>, end: Option<i64>) -> i64 {
+    let explicit_end = end.is_some();
+    let range = byte_range(value.len(), start.unwrap_or(0), end.unwrap_or(-1));
+    let (start, end) = match range {
+        Some(range) => range,
+        // an empty string is only made of clear bits
+        None if value.is_empty() && bit == 0 => return 0,
+        None => return -1,
+    };
+
+    for (index, byte) in value[start..=end].iter().enumerate() {
+        // a byte is skipped at once when none of its bits is the one we look for
+        let byte = if bit == 1 { *byte } else { !*byte };
+        if byte != 0 {
+            return ((start + index) * 8) as i64 + byte.leading_zeros() as i64;
+        }
+    }
+
+    // the string is seen as padded with clear bits on the right, unless an explicit end
+    // restricts the search
+    match bit == 0 && !explicit_end {
+        true => ((end + 1) * 8) as i64,
+        false => -1,
+    }
+}
+
+/// Apply the operation byte by byte, shorter values being padded with zero bytes
+fn bit_op(operation: BitOperation, values: &[Vec<u8>]) -> Vec<u8> {
+    let len = values.iter().map(|value| value.len()).max().unwrap_or(0);
+
+    (0..len)
+        .map(|index| {
+            let mut bytes = values
+                .iter()
+                .map(|value| value.get(index).copied().unwrap_or(0));
+            let first = bytes.next().unwrap_or(0);
+            match operation {
+                BitOperation::And => bytes.fold(first, |acc, byte| acc & byte),
+                BitOperation::Or => bytes.fold(first, |acc, byte| acc | byte),
+                BitOperation::Xor => bytes.fold(first, |acc, byte| acc ^ byte),
+                BitOperation::Not => !first,
+            }
+        })
+        .collect()
+}
+
+/// Remove the keys of databases. With `ASYNC` they are gone as well once it returns, but
+/// their memory is reclaimed by a thread of its own.
+fn flush<'a, T: Storage + 'a>(
+    databases: impl IntoIterator<Item = &'a Database<T>>,
+    mode: FlushMode,
+) {
+    let mut keyspaces = vec![];
+    for db in databases {
+        let mut storage = db.lock_all();
+        match mode {
+            FlushMode::Sync => storage.clear(),
+            FlushMode::Async => keyspaces.push(storage.take_all()),
+        }
+    }
+    if !keyspaces.is_empty() {
+        thread::spawn(move || drop(keyspaces));
+    }
+}
+
+/// Add increment to the integer stored at key, under the lock of the caller so concurrent
+/// increments are never lost. The expiry of the key is kept.
+///
+/// Integers are stored as i64, so a result out of its range is an `IncrOverflow` error and
+/// the value is left as it is. It never wraps, and is not replied as a RESP3 big number.
+fn incr_by<T: Storage>(storage: &mut T, key: &[u8], increment: i64) -> RedisResponse {
+    let keytype = storage.type_of(key);
+    if keytype != "string".as_bytes() && keytype != "none".as_bytes() {
+        return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+    }
+
+    let current = match storage.read(key) {
+        Some(value) => match parse_i64(value) {
+            Some(value) => value,
+            None => return RedisResponse::error(RedisCommandError::NotAnInteger),
+        },
+        None => 0,
+    };
+    let value = match current.checked_add(increment) {
+        Some(value) => value,
+        None => return RedisResponse::error(RedisCommandError::IncrOverflow),
+    };
+
+    let expiry = storage.meta(key).and_then(|meta| meta.expiry);
+    storage.insert(
+        key,
+        RedisValue::String(value.to_string().into_bytes()),
+        expiry,
+    );
+    RedisResponse::single(RedisResponseType::Integer(value))
+}
+
+// sections of `INFO` among server, keyspace and commandstats, only keyspace by default
+fn info<T: Storage>(context: &ServerContext<T>, sections: &[String]) -> String {
+    let all = sections
+        .iter()
+        .any(|section| section == "all" || section == "everything");
+    let selected = |section: &str| all || sections.iter().any(|s| s == section);
+    let mut info = vec![];
+    if selected("server") {
+        info.push(info_server(context));
+    }
+    if selected("stats") {
+        info.push(context.expire_stats.info());
+    }
+    if selected("replication") {
+        info.push(info_replication(context));
+    }
+    if sections.is_empty() || sections.iter().any(|s| s == "default") || selected("keyspace") {
+        info.push(info_keyspace(context));
+    }
+    if selected("commandstats") {
+        info.push(context.stats.info());
+    }
+    info.join("\r\n")
+}
+
+// `Server` section of `INFO`, the version being the one of Redis this server passes for.
+// Each connection is served by its own thread, there is neither an event loop nor I/O
+// threads.
+fn info_server<T: Storage>(context: &ServerContext<T>) -> String {
+    let config = context.config();
+    format!(
+        "# Server\r\nredis_version:{}\r\nredisless_version:{}\r\nredis_mode:standalone\r\n\
+         multiplexing_api:threads\r\natomicvar_api:c11-builtin\r\nprocess_id:{}\r\n\
+         run_id:{}\r\ntcp_port:{}\r\nio_threads_active:0\r\n",
+        config.redis_version,
+        env!("CARGO_PKG_VERSION"),
+        std::process::id(),
+        context.run_id,
+        config.port
+    )
+}
+
+// `Replication` section of `INFO`. Partial resynchronization is not supported, so no
+// offset is kept and it is reported as 0.
+fn info_replication<T: Storage>(context: &ServerContext<T>) -> String {
+    let replication = &context.replication;
+    let mut info = String::from("# Replication\r\n");
+    match replication.primary() {
+        Some((host, port, link_up)) => info.push_str(&format!(
+            "role:slave\r\nmaster_host:{}\r\nmaster_port:{}\r\nmaster_link_status:{}\r\n",
+            host,
+            port,
+            if link_up { "up" } else { "down" }
+        )),
+        None => info.push_str("role:master\r\n"),
+    }
+    info.push_str(&format!(
+        "connected_slaves:{}\r\nmaster_replid:{}\r\nmaster_repl_offset:0\r\n",
+        replication.replica_count(),
+        replication.replid
+    ));
+    info
+}
+
+// Estimated bytes used by the keys of every database, as accounted by the storage
+struct MemoryStats {
+    total: u64,
+    // taken by the structures holding the keys
+    overhead: u64,
+    keys: u64,
+}
+
+impl MemoryStats {
+    fn of<T: Storage>(context: &ServerContext<T>) -> Self {
+        let mut stats = Self {
+            total: 0,
+            overhead: 0,
+            keys: 0,
+        };
+        for db in &context.databases {
+            let storage = db.lock_all();
+            stats.total += storage.used_memory();
+            stats.overhead += storage.keys_overhead();
+            stats.keys += storage.size();
+        }
+        stats
+    }
+
+    // taken by the keys and their values
+    fn dataset(&self) -> u64 {
+        self.total - self.overhead
+    }
+}
+
+// Report of `MEMORY DOCTOR`, in the words of Redis
+fn memory_doctor<T: Storage>(context: &ServerContext<T>) -> String {
+    let stats = MemoryStats::of(context);
+    if stats.keys == 0 {
+        return String::from(
+            "Hi Sam, this instance is empty or is using very little memory, my issues detector \
+             can't be used in these conditions. Please, leave for your mission on Earth and fill \
+             it with some data. The new Sam and I will be back to our programming as soon as I \
+             finished rebooting.",
+        );
+    }
+
+    let mut issues = vec![];
+    let maxmemory = context.config().maxmemory;
+    if maxmemory > 0 && stats.total > maxmemory / 10 * 9 {
+        issues.push(format!(
+            "High memory usage: the {} bytes used are over 90% of the maxmemory of {} bytes, \
+             so keys are about to be evicted, or writes refused, per the maxmemory-policy.",
+            stats.total, maxmemory
+        ));
+    }
+    if stats.overhead > stats.dataset() {
+        issues.push(String::from(
+            "High overhead: most of the memory is taken by the structures holding the keys \
+             rather than by the keys and their values. Holding small values together in \
+             hashes instead of in keys of their own would use less memory.",
+        ));
+    }
+
+    match issues.is_empty() {
+        true => String::from(
+            "Hi Sam, I can't find any memory issue in your instance. I can only account for \
+             what occurs on this base.",
+        ),
+        false => format!(
+            "Sam, I detected a few issues in this Redis instance memory implants:\n\n{}\n\
+             I'm here to keep you safe, Sam. I want to help you.\n",
+            issues
+                .iter()
+                .map(|issue| format!(" * {}\n", issue))
+                .collect::<String>()
+        ),
+    }
+}
+
+// `Keyspace` section of `INFO`, a line per database holding keys. The average ttl is not
+// tracked, and reported as 0.
+fn info_keyspace<T: Storage>(context: &ServerContext<T>) -> String {
+    let mut info = String::from("# Keyspace\r\n");
+    for (index, db) in context.databases.iter().enumerate() {
+        let storage = db.lock_all();
+        let keys = storage.size();
+        if keys > 0 {
+            info.push_str(&format!(
+                "db{}:keys={},expires={},avg_ttl=0\r\n",
+                index,
+                keys,
+                storage.expires()
+            ));
+        }
+    }
+    info
+}
+
+/// Entry of a command in the reply of `COMMAND INFO`, nil for an unknown command
+fn command_info(spec: Option<CommandSpec>) -> RedisResponseType {
+    use protocol::response::RedisResponseType::*;
+    match spec {
+        Some(spec) => Array(vec![
+            BulkString(spec.name.into_bytes()),
+            Integer(spec.arity as i64),
+            Array(
+                spec.flags
+                    .into_iter()
+                    .map(|flag| SimpleString(flag.as_bytes().to_vec()))
+                    .collect(),
+            ),
+            Integer(spec.first_key),
+            Integer(spec.last_key),
+            Integer(spec.step),
+        ]),
+        None => Nil,
+    }
+}
+
+/// Entry of a command in the reply of `COMMAND DOCS`
+fn command_doc_entry(doc: &CommandDoc, resp3: bool) -> RedisResponseType {
+    use protocol::response::RedisResponseType::*;
+    RedisResponseType::map(
+        vec![
+            (
+                BulkString(b"summary".to_vec()),
+                BulkString(doc.summary.as_bytes().to_vec()),
+            ),
+            (
+                BulkString(b"group".to_vec()),
+                BulkString(doc.group.as_bytes().to_vec()),
+            ),
+            (
+                BulkString(b"arguments".to_vec()),
+                Array(
+                    doc.arguments
+                        .iter()
+                        .map(|argument| argument_doc(argument, resp3))
+                        .collect(),
+                ),
+            ),
+        ],
+        resp3,
+    )
+}
+
+// schema of an argument, the token, flags and nested arguments only when it has some
+fn argument_doc(argument: &Argument, resp3: bool) -> RedisResponseType {
+    use protocol::response::RedisResponseType::*;
+    let field = |name: &str, value| (BulkString(name.as_bytes().to_vec()), value);
+    let mut entries = vec![
+        field("name", BulkString(argument.name.as_bytes().to_vec())),
+        field("type", BulkString(argument.kind.name().as_bytes().to_vec())),
+    ];
+    if let Some(token) = argument.token {
+        entries.push(field("token", BulkString(token.as_bytes().to_vec())));
+    }
+    let flags = argument.flags();
+    if !flags.is_empty() {
+        entries.push(field(
+            "flags",
+            Array(
+                flags
+                    .into_iter()
+                    .map(|flag| SimpleString(flag.as_bytes().to_vec()))
+                    .collect(),
+            ),
+        ));
+    }
+    if !argument.arguments.is_empty() {
+        entries.push(field(
+            "arguments",
+            Array(
+                argument
+                    .arguments
+                    .iter()
+                    .map(|argument| argument_doc(argument, resp3))
+                    .collect(),
+            ),
+        ));
+    }
+    RedisResponseType::map(entries, resp3)
+}
+
+/// Reply of the `SCAN` family, the next cursor followed by the batch
+fn scan_response(cursor: u64, batch: Vec<RedisResponseType>) -> RedisResponse {
+    RedisResponse::array(vec![
+        RedisResponseType::BulkString(cursor.to_string().into_bytes()),
+        RedisResponseType::Array(batch),
+    ])
+}
+
+/// `BLPOP` / `BRPOP`, popping from the left when left is set. The pop is run and streamed to
+/// the replicas as an `LPOP` / `RPOP`, so they never block.
+fn blocking_pop<T: Storage>(
+    context: &ServerContext<T>,
+    db: usize,
+    keys: Vec<RedisString>,
+    timeout: f64,
+    left: bool,
+) -> RedisResponse {
+    use protocol::response::RedisResponseType::*;
+    if rejects_writes(context) {
+        return RedisResponse::error(RedisCommandError::ReadOnlyReplica);
+    }
+
+    let storage = context.database(db);
+    let deadline = match timeout > 0.0 {
+        true => Some(Instant::now() + Duration::from_secs_f64(timeout)),
+        false => None,
+    };
+    loop {
+        let mut guard = storage.lock_keys(&keys);
+        let mut ready = None;
+        for key in &keys {
+            let keytype = guard.type_of(key);
+            if keytype != "list".as_bytes() && keytype != "none".as_bytes() {
+                return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+            }
+            if guard.lread(key).is_some() {
+                ready = Some(key);
+                break;
+            }
+        }
+
+        match ready {
+            Some(key) => {
+                // the storage is released first, like for any write streamed to replicas
+                drop(guard);
+                let name: &[u8] = if left { b"LPOP" } else { b"RPOP" };
+                let request = encode_request(&[Resp::BulkString(name), Resp::BulkString(key)]);
+                let mut popped = None;
+                context.replication.run_write(db, &request, || {
+                    popped = pop(&mut *storage.lock(key), key, left);
+                    RedisResponse::single(Nil)
+                });
+                if let Some(value) = popped {
+                    return RedisResponse::array(vec![BulkString(key.clone()), BulkString(value)]);
+                }
+                // another client popped the list first
+            }
+            None => {
+                let timeout = match deadline {
+                    Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                        Some(timeout) if !timeout.is_zero() => Some(timeout),
+                        _ => return RedisResponse::single(Nil),
+                    },
+                    None => None,
+                };
+                context.blocked[db].wait(&keys, guard, timeout);
+            }
+        }
+    }
+}
+
+/// `BLMOVE`, and `BRPOPLPUSH` from the right to the left, moving an element between the ends
+/// of sides once the source has one. The move is run and streamed to the replicas as an
+/// `LMOVE`, so they never block.
+fn blocking_move<T: Storage>(
+    context: &ServerContext<T>,
+    db: usize,
+    source: RedisString,
+    destination: RedisString,
+    (from, to): (Side, Side),
+    timeout: f64,
+) -> RedisResponse {
+    use protocol::response::RedisResponseType::*;
+    if rejects_writes(context) {
+        return RedisResponse::error(RedisCommandError::ReadOnlyReplica);
+    }
+
+    let storage = context.database(db);
+    let deadline = match timeout > 0.0 {
+        true => Some(Instant::now() + Duration::from_secs_f64(timeout)),
+        false => None,
+    };
+    let side = |side: Side| -> &[u8] {
+        match side {
+            Side::Left => b"LEFT",
+            Side::Right => b"RIGHT",
+        }
+    };
+    loop {
+        let mut guard = storage.lock_keys([&source, &destination]);
+        for key in [&source, &destination] {
+            let keytype = guard.type_of(key);
+            if keytype != "list".as_bytes() && keytype != "none".as_bytes() {
+                return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+            }
+        }
+        if guard.lread(&source).is_some() {
+            // the storage is released first, like for any write streamed to replicas
+            drop(guard);
+            let request = encode_request(&[
+                Resp::BulkString(b"LMOVE"),
+                Resp::BulkString(&source),
+                Resp::BulkString(&destination),
+                Resp::BulkString(side(from)),
+                Resp::BulkString(side(to)),
+            ]);
+            let mut moved = Ok(None);
+            context.replication.run_write(db, &request, || {
+                let mut storage = storage.lock_keys([&source, &destination]);
+                moved = lmove(&mut storage, &source, &destination, from, to);
+                if moved.as_ref().is_ok_and(|moved| moved.is_some()) {
+                    context.blocked[db].notify(&destination);
+                }
+                RedisResponse::single(Nil)
+            });
+            match moved {
+                Ok(Some(value)) => return RedisResponse::single(BulkString(value)),
+                Err(err) => return RedisResponse::error(err),
+                // another client popped the list first
+                Ok(None) => continue,
+            }
+        }
+
+        let timeout = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(timeout) if !timeout.is_zero() => Some(timeout),
+                _ => return RedisResponse::single(Nil),
+            },
+            None => None,
+        };
+        context.blocked[db].wait(std::slice::from_ref(&source), guard, timeout);
+    }
+}
+
+/// `WAITKEY`, replying the string at key once a write sets it, or nil at the timeout. Every
+/// write notifies the clients blocked on its key, which read it again.
+fn wait_key<T: Storage>(
+    context: &ServerContext<T>,
+    db: usize,
+    key: RedisString,
+    timeout: f64,
+) -> RedisResponse {
+    use protocol::response::RedisResponseType::*;
+    let storage = context.database(db);
+    let deadline = match timeout > 0.0 {
+        true => Some(Instant::now() + Duration::from_secs_f64(timeout)),
+        false => None,
+    };
+    loop {
+        let mut guard = storage.lock(&key);
+        let keytype = guard.type_of(&key);
+        if keytype != "string".as_bytes() && keytype != "none".as_bytes() {
+            return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+        }
+        if let Some(value) = guard.read(&key) {
+            return RedisResponse::single(BulkString(value.to_vec()));
+        }
+
+        let timeout = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(timeout) if !timeout.is_zero() => Some(timeout),
+                _ => return RedisResponse::single(Nil),
+            },
+            None => None,
+        };
+        context.blocked[db].wait(std::slice::from_ref(&key), guard, timeout);
+    }
+}
+
+/// Members and scores of values selected by a `ZRANGE`-style range, in the order of the
+/// range
+fn zrange<'a>(
+    values: &'a RedisSortedSet,
+    options: &'a ZRangeOptions,
+) -> Vec<(&'a RedisString, f64)> {
+    let mut members: Vec<_> = match &options.by {
+        ZRangeBy::Index(..) => values.iter().collect(),
+        ZRangeBy::Score(min, max) => values.range_by_score(*min, *max).collect(),
+        ZRangeBy::Lex(min, max) => values
+            .range_by_lex(min, max)
+            .filter_map(|member| Some((member, values.score(member)?)))
+            .collect(),
+    };
+    if options.rev {
+        members.reverse();
+    }
+    match options.by {
+        ZRangeBy::Index(start, stop) => {
+            let len = members.len() as i64;
+            let index = |index: i64| match index < 0 {
+                true => len + index,
+                false => index,
+            };
+            let (start, stop) = (index(start).max(0), index(stop).min(len - 1));
+            match start <= stop {
+                true => members.drain(start as usize..=stop as usize).collect(),
+                false => vec![],
+            }
+        }
+        _ => limited(members.into_iter(), options.limit),
+    }
+}
+
+/// Items of a range selected by its `LIMIT offset count`. As in Redis, a negative offset
+/// selects nothing and a negative count selects every item after the offset.
+fn limited<I: Iterator>(items: I, limit: Option<(i64, i64)>) -> Vec<I::Item> {
+    match limit {
+        Some((offset, _)) if offset < 0 => vec![],
+        Some((offset, count)) => {
+            let count = match count < 0 {
+                true => usize::MAX,
+                false => count as usize,
+            };
+            items.skip(offset as usize).take(count).collect()
+        }
+        None => items.collect(),
+    }
+}
+
+/// `ZPOPMIN` / `ZPOPMAX`, popping the highest scores when max is set. The members and
+/// their scores are replied as a flat array, and the key is removed once the set is empty.
+fn zpop<T: Storage>(storage: &mut T, key: &[u8], count: Option<u64>, max: bool) -> RedisResponse {
+    use protocol::response::RedisResponseType::*;
+    let keytype = storage.type_of(key);
+    if keytype != "zset".as_bytes() && keytype != "none".as_bytes() {
+        return RedisResponse::error(RedisCommandError::WrongTypeOperation);
+    }
+    let mut values = match storage.zread(key) {
+        Some(values) => values.clone(),
+        None => return RedisResponse::array(vec![]),
+    };
+
+    let mut popped = vec![];
+    for _ in 0..count.unwrap_or(1) {
+        match values.pop(max) {
+            Some((member, score)) => {
+                popped.push(BulkString(member));
+                popped.push(BulkString(score.to_string().into_bytes()));
+            }
+            None => break,
+        }
+    }
+    storage.zwrite(key, values);
+    RedisResponse::array(popped)
+}
+
+/// Members and scores of the sorted sets at keys, in order. As in Redis, a set counts as a
+/// sorted set whose members all score 1, and a missing key as an empty one.
+fn zread_all<T: Storage>(
+    storage: &mut T,
+    keys: &[RedisString],
+) -> Result<Vec<HashMap<RedisString, f64>>, RedisCommandError> {
+    keys.iter()
+        .map(|key| match storage.type_of(key).to_vec().as_slice() {
+            b"zset" => Ok(storage
+                .zread(key)
+                .map(|values| {
+                    values
+                        .iter()
+                        .map(|(member, score)| (member.clone(), score))
+                        .collect()
+                })
+                .unwrap_or_default()),
+            b"set" => Ok(storage
+                .sread(key)
+                .map(|values| values.iter().map(|member| (member.clone(), 1.0)).collect())
+                .unwrap_or_default()),
+            b"none" => Ok(HashMap::new()),
+            _ => Err(RedisCommandError::WrongTypeOperation),
+        })
+        .collect()
+}
+
+/// `ZUNION` / `ZINTER`, keeping only the members in every sorted set when inter is set. The
+/// scores are weighted before they are aggregated.
+fn zcombine<T: Storage>(
+    storage: &mut T,
+    keys: &[RedisString],
+    options: &ZCombineOptions,
+    inter: bool,
+) -> RedisResponse {
+    let sets = match zread_all(storage, keys) {
+        Ok(sets) => sets,
+        Err(err) => return RedisResponse::error(err),
+    };
+    let weighted = |index: usize, score: f64| {
+        let weight = options
+            .weights
+            .as_ref()
+            .map_or(1.0, |weights| weights[index]);
+        // as in Redis, `0 * inf` counts as 0
+        match score * weight {
+            score if score.is_nan() => 0.0,
+            score => score,
+        }
+    };
+    let aggregate = |total: f64, score: f64| match options.aggregate {
+        Aggregate::Sum => match total + score {
+            // `+inf` + `-inf`
+            sum if sum.is_nan() => 0.0,
+            sum => sum,
+        },
+        Aggregate::Min => total.min(score),
+        Aggregate::Max => total.max(score),
+    };
+
+    let mut scores: HashMap<RedisString, f64> = HashMap::new();
+    for (index, set) in sets.iter().enumerate() {
+        for (member, score) in set {
+            if inter && sets.iter().any(|other| !other.contains_key(member)) {
+                continue;
+            }
+            let score = weighted(index, *score);
+            scores
+                .entry(member.clone())
+                .and_modify(|total| *total = aggregate(*total, score))
+                .or_insert(score);
+        }
+    }
+
+    let mut result = RedisSortedSet::new();
+    for (member, score) in scores {
+        result.insert(&member, score);
+    }
+    zset_reply(&result, options.with_scores)
+}
+
+/// Members of a sorted set by score, each followed by its score when with_scores is set
+fn zset_reply(values: &RedisSortedSet, with_scores: bool) -> RedisResponse {
+    use protocol::response::RedisResponseType::*;
+    let mut reply = vec![];
+    for (member, score) in values.iter() {
+        reply.push(BulkString(member.clone()));
+        if with_scores {
+            reply.push(BulkString(score.to_string().into_bytes()));
+        }
+    }
+    RedisResponse::array(reply)
+}
+
+/// Copy of the value at key and its expiry, for a `COPY`
+fn copy_of<T: Storage>(storage: &mut T, key: &[u8]) -> Option<(RedisValue, Option<Expiry>)> {
+    if !storage.contains(key) {
+        return None;
+    }
+    let expiry = storage.meta(key).and_then(|meta| meta.expiry);
+    storage.value(key).map(|value| (value, expiry))
+}
+
+/// Write a copy made by `copy_of` at key, unless the key exists and replace is not set.
+/// Returns whether the copy was written.
+fn paste<T: Storage>(
+    storage: &mut T,
+    key: &[u8],
+    copy: Option<(RedisValue, Option<Expiry>)>,
+    replace: bool,
+) -> bool {
+    match copy {
+        Some((value, expiry)) if replace || !storage.contains(key) => {
+            storage.insert(key, value, expiry);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// `LPOP` with left, else `RPOP`: a single element without a count, else an array of up to
+/// count of them, empty for a count of 0. A missing key is nil either way.
+fn list_pop<T: Storage>(
+    storage: &mut T,
+    key: &[u8],
+    count: Option<u64>,
+    left: bool,
+) -> RedisResponse {
+    use protocol::response::RedisResponseType::*;
+    match storage.type_of(key) {
+        b"none" => return RedisResponse::single(Nil),
+        b"list" => {}
+        _ => return RedisResponse::error(RedisCommandError::WrongTypeOperation),
+    }
+    let count = match count {
+        Some(count) => count,
+        None => {
+            let popped = pop(storage, key, left);
+            return RedisResponse::single(popped.map_or(Nil, BulkString));
+        }
+    };
+    let mut values = storage.lread(key).cloned().unwrap_or_default();
+    let count = values
+        .len()
+        .min(usize::try_from(count).unwrap_or(usize::MAX));
+    let popped: Vec<_> = match left {
+        true => values.drain(..count).collect(),
+        // the last element first, as popped one at a time
+        false => values.drain(values.len() - count..).rev().collect(),
+    };
+    if count > 0 {
+        storage.lwrite(key, values);
+    }
+    RedisResponse::array(popped.into_iter().map(BulkString).collect())
+}
+
+/// Pop an element of the list at key, removing the key once the list is empty
+fn pop<T: Storage>(storage: &mut T, key: &[u8], left: bool) -> Option<RedisString> {
+    let mut values = storage.lread(key)?.to_vec();
+    if values.is_empty() {
+        return None;
+    }
+    let value = match left {
+        true => values.remove(0),
+        false => values.pop()?,
+    };
+    storage.lwrite(key, values);
+    Some(value)
+}
+
+/// `LMOVE`, moving the element at the from end of the list at source to the to end of the
+/// list at destination, which may be the same list. `None` when there is no source list.
+fn lmove<T: Storage>(
+    storage: &mut T,
+    source: &[u8],
+    destination: &[u8],
+    from: Side,
+    to: Side,
+) -> Result<Option<RedisString>, RedisCommandError> {
+    match storage.type_of(source) {
+        b"none" => return Ok(None),
+        b"list" => {}
+        _ => return Err(RedisCommandError::WrongTypeOperation),
+    }
+    let keytype = storage.type_of(destination);
+    if keytype != "list".as_bytes() && keytype != "none".as_bytes() {
+        return Err(RedisCommandError::WrongTypeOperation);
+    }
+
+    let value = match pop(storage, source, from == Side::Left) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    // read once popped, for a destination which is the source
+    let mut values = storage.lread(destination).cloned().unwrap_or_default();
+    match to {
+        Side::Left => values.insert(0, value.clone()),
+        Side::Right => values.push(value.clone()),
+    }
+    storage.lwrite(destination, values);
+    Ok(Some(value))
+}
+
+/// Order the elements of a `SORT`, then keep the ones within its `LIMIT`
+fn sort(
+    mut elements: Vec<RedisString>,
+    options: &SortOptions,
+) -> Result<Vec<RedisString>, RedisCommandError> {
+    if options.alpha {
+        elements.sort_unstable();
+    } else {
+        let mut scored = elements
+            .into_iter()
+            .map(|element| {
+                let score = std::str::from_utf8(&element)
+                    .ok()
+                    .and_then(|score| score.trim().parse::<f64>().ok())
+                    .filter(|score| !score.is_nan())
+                    .ok_or(RedisCommandError::SortNotNumeric)?;
+                Ok((score, element))
+            })
+            .collect::<Result<Vec<_>, RedisCommandError>>()?;
+        // equal numbers are ordered as strings, so the order does not depend on the storage
+        scored.sort_unstable_by(|(a, a_element), (b, b_element)| {
+            a.total_cmp(b).then_with(|| a_element.cmp(b_element))
+        });
+        elements = scored.into_iter().map(|(_, element)| element).collect();
+    }
+    if options.descending {
+        elements.reverse();
+    }
+
+    if let Some((offset, count)) = options.limit {
+        let offset = offset.max(0) as usize;
+        let count = match count < 0 {
+            true => usize::MAX,
+            false => count as usize,
+        };
+        elements = elements.into_iter().skip(offset).take(count).collect();
+    }
+    Ok(elements)
+}
+
+// the subscriptions of the client, created by its first subscribing command
+fn subscriber<'a, T: Storage>(
+    context: &ServerContext<T>,
+    client: &'a mut Client,
+) -> &'a mut Subscriber {
+    client.subscriber.get_or_insert_with(|| {
+        let limit = context.config().client_output_buffer_limit_pubsub;
+        context.pubsub.new_subscriber(limit)
+    })
+}
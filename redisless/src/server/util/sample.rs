@@ -0,0 +1,30 @@
+use rand::seq::{IteratorRandom, SliceRandom};
+use rand::Rng;
+
+/// Random members of a collection, as drawn by `HRANDFIELD`, `SRANDMEMBER` and
+/// `ZRANDMEMBER`, so the three commands draw alike from hashes, sets and sorted sets.
+///
+/// A positive count draws distinct members without replacement, in a single pass over
+/// the collection and never more than its members. A negative count draws its absolute
+/// value of members with replacement, so a member may be drawn several times.
+pub fn sample<I>(members: I, count: i64, rng: &mut impl Rng) -> Vec<I::Item>
+where
+    I: Iterator,
+    I::Item: Clone,
+{
+    if count >= 0 {
+        return members.choose_multiple(rng, count as usize);
+    }
+    let members = members.collect::<Vec<_>>();
+    if members.is_empty() {
+        return vec![];
+    }
+    (0..count.unsigned_abs())
+        .filter_map(|_| members.choose(rng).cloned())
+        .collect()
+}
+
+/// A single random member of a collection, as drawn without a count
+pub fn sample_one<I: Iterator>(members: I, rng: &mut impl Rng) -> Option<I::Item> {
+    members.choose(rng)
+}
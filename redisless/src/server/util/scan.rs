@@ -0,0 +1,70 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use super::glob_match;
+use crate::storage::models::RedisString;
+
+/// One call of a cursor based iteration such as `SCAN`, returning the next cursor, `0` once
+/// the iteration is over, and a batch of up to count entries.
+///
+/// Entries are visited in the order of a hash of their key, and the cursor is the hash
+/// the next batch starts from. That order does not depend on the other entries, so an
+/// entry present during the whole iteration is always returned, whatever is added or
+/// removed between two calls. Unlike the bucket index of Redis, the cursor does not depend
+/// on the size of the maps holding the entries either, so it needs no reverse binary
+/// increment to survive their resizes. Entries whose hashes collide may be returned twice.
+///
+/// The pattern is matched against the key first, then filter is applied, and only the
+/// entries left are counted in the batch.
+pub fn scan<T>(
+    entries: impl IntoIterator<Item = (RedisString, T)>,
+    cursor: u64,
+    pattern: Option<&[u8]>,
+    filter: impl FnMut(&RedisString) -> bool,
+    count: usize,
+) -> (u64, Vec<(RedisString, T)>) {
+    scan_by(entries, cursor, pattern, filter, count, |key| {
+        key_hash(key).max(1)
+    })
+}
+
+/// `scan`, with the entries visited in the order of their position, which must never be 0
+pub fn scan_by<T>(
+    entries: impl IntoIterator<Item = (RedisString, T)>,
+    cursor: u64,
+    pattern: Option<&[u8]>,
+    mut filter: impl FnMut(&RedisString) -> bool,
+    count: usize,
+    position: impl Fn(&[u8]) -> u64,
+) -> (u64, Vec<(RedisString, T)>) {
+    let mut batch: Vec<_> = entries
+        .into_iter()
+        .map(|(key, value)| (position(&key), key, value))
+        .filter(|(position, key, _)| {
+            *position >= cursor && pattern.is_none_or(|pattern| glob_match(pattern, key))
+        })
+        .filter(|(_, key, _)| filter(key))
+        .collect();
+
+    let mut next_cursor = 0;
+    if batch.len() > count {
+        batch.select_nth_unstable_by_key(count, |(position, _, _)| *position);
+        next_cursor = batch[count].0;
+        batch.truncate(count);
+    }
+    batch.sort_unstable_by_key(|(position, _, _)| *position);
+
+    let batch = batch
+        .into_iter()
+        .map(|(_, key, value)| (key, value))
+        .collect();
+    (next_cursor, batch)
+}
+
+/// Hash of key from which the positions of the iterations are taken. A position is never 0,
+/// which is the cursor of a new iteration and the one of a finished one.
+pub fn key_hash(key: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
@@ -1,40 +1,117 @@
 use std::collections::{HashMap, HashSet};
+use std::mem::{size_of, take};
 
 use prost::bytes::BufMut;
 
 use super::models::*;
 use crate::storage::Storage;
 
+#[derive(Default)]
 pub struct InMemoryStorage {
     data_mapper: HashMap<RedisString, RedisMeta>,
     string_store: HashMap<RedisString, RedisString>,
     list_store: HashMap<RedisString, Vec<RedisString>>,
     set_store: HashMap<RedisString, HashSet<RedisString>>,
     hash_store: HashMap<RedisString, RedisHashMap>,
+    sorted_set_store: HashMap<RedisString, RedisSortedSet>,
+    // estimated bytes of all the keys, see `memory_usage`
+    used_memory: u64,
+    encoding_limits: EncodingLimits,
+    // whether the hashes preserve the order their fields were added in
+    ordered_hashes: bool,
 }
 
-impl Default for InMemoryStorage {
-    fn default() -> Self {
-        Self {
-            data_mapper: HashMap::new(),
-            string_store: HashMap::new(),
-            list_store: HashMap::new(),
-            set_store: HashMap::new(),
-            hash_store: HashMap::new(),
+// The estimates count the bytes of the keys and values plus the fixed size of the structures
+// holding them, not the spare capacity of the vectors nor the overhead of the hash tables.
+// A key is held both by the data mapper, with its meta, and by the store of its type.
+const KEY_OVERHEAD: u64 = (2 * size_of::<RedisString>() + size_of::<RedisMeta>()) as u64;
+const STRING_OVERHEAD: u64 = size_of::<RedisString>() as u64;
+// a member of a sorted set is held both by its score and by the ordered members
+const SCORE_OVERHEAD: u64 = (2 * size_of::<f64>() + size_of::<RedisString>()) as u64;
+
+fn string_usage(value: &[u8]) -> u64 {
+    value.len() as u64 + STRING_OVERHEAD
+}
+
+impl InMemoryStorage {
+    // estimated bytes of key and its value, 0 if it does not exist
+    fn usage_of(&self, key: &[u8]) -> u64 {
+        use RedisType::*;
+        let value = match self.data_mapper.get(key) {
+            Some(meta) => match meta.data_type {
+                String => self.string_store.get(key).map(|value| string_usage(value)),
+                List => self
+                    .list_store
+                    .get(key)
+                    .map(|values| values.iter().map(|value| string_usage(value)).sum()),
+                Set => self
+                    .set_store
+                    .get(key)
+                    .map(|values| values.iter().map(|value| string_usage(value)).sum()),
+                Hash => self.hash_store.get(key).map(|hash| {
+                    let order: u64 = hash.ordered_fields().iter().map(|f| string_usage(f)).sum();
+                    hash.data
+                        .iter()
+                        .map(|(field, value)| string_usage(field) + string_usage(value))
+                        .sum::<u64>()
+                        + order
+                }),
+                SortedSet => self.sorted_set_store.get(key).map(|zset| {
+                    zset.iter()
+                        .map(|(member, _)| 2 * string_usage(member) + SCORE_OVERHEAD)
+                        .sum()
+                }),
+            },
+            None => return 0,
+        };
+        2 * key.len() as u64 + KEY_OVERHEAD + value.unwrap_or(0)
+    }
+
+    // run a change of the value stored at key, keeping the used memory up to date
+    fn update<R>(&mut self, key: &[u8], change: impl FnOnce(&mut Self) -> R) -> R {
+        let before = self.usage_of(key);
+        let result = change(self);
+        self.used_memory = self.used_memory - before + self.usage_of(key);
+        result
+    }
+
+    // remove the expired fields of the hash at key, and the key with the last of them
+    fn expire_fields(&mut self, key: &[u8]) {
+        match self.hash_store.get(key) {
+            Some(hash) if hash.has_expired_fields() => {}
+            _ => return,
+        }
+        let empty = self.update(key, |storage| {
+            let hash = storage.hash_store.get_mut(key).unwrap();
+            hash.remove_expired();
+            hash.data.is_empty()
+        });
+        if empty {
+            self.remove(key);
         }
     }
 }
 
 impl Storage for InMemoryStorage {
     fn write(&mut self, key: &[u8], value: &[u8]) {
-        let meta = RedisMeta::new(RedisType::String, None);
-        self.data_mapper.insert(key.to_vec(), meta);
-        self.string_store.insert(key.to_vec(), value.to_vec());
+        // the previous value goes with its meta, expiry included, whatever its type
+        self.remove(key);
+        self.update(key, |storage| {
+            let mut meta = RedisMeta::new(RedisType::String, None);
+            meta.encoding = Encoding::of_string(value, &storage.encoding_limits);
+            storage.data_mapper.insert(key.to_vec(), meta);
+            storage.string_store.insert(key.to_vec(), value.to_vec());
+        })
     }
     fn extend(&mut self, key: &[u8], tail: &[u8]) -> u64 {
         match self.string_store.get_mut(key) {
             Some(v) => {
                 v.put_slice(tail);
+                self.used_memory += tail.len() as u64;
+                // as in Redis, an appended string is no longer an integer nor embedded
+                if let Some(meta) = self.data_mapper.get_mut(key) {
+                    meta.encoding = Encoding::Raw;
+                }
                 v.len() as u64
             }
             None => {
@@ -44,6 +121,26 @@ impl Storage for InMemoryStorage {
         }
     }
 
+    fn set_range(&mut self, key: &[u8], offset: usize, value: &[u8]) -> u64 {
+        // an expired string is replaced, as is a value of another type
+        if !self.contains(key) || !self.string_store.contains_key(key) {
+            self.write(key, b"");
+        }
+        self.update(key, |storage| {
+            let string = storage.string_store.entry(key.to_vec()).or_default();
+            let end = offset + value.len();
+            if string.len() < end {
+                string.resize(end, 0);
+            }
+            string[offset..end].copy_from_slice(value);
+            // as for an append, the string is no longer an integer nor embedded
+            if let Some(meta) = storage.data_mapper.get_mut(key) {
+                meta.encoding = Encoding::Raw;
+            }
+            string.len() as u64
+        })
+    }
+
     fn expire(&mut self, key: &[u8], expiry: Expiry) -> u32 {
         if let Some(meta) = self.data_mapper.get_mut(key) {
             meta.expiry = Some(expiry);
@@ -53,14 +150,27 @@ impl Storage for InMemoryStorage {
         }
     }
 
+    fn persist(&mut self, key: &[u8]) -> u32 {
+        match self.data_mapper.get_mut(key) {
+            Some(meta) if !meta.is_expired() => match meta.expiry.take() {
+                Some(_) => 1, // timeout was removed
+                None => 0,
+            },
+            _ => 0,
+        }
+    }
+
     fn read(&mut self, key: &[u8]) -> Option<&[u8]> {
-        if let Some(value) = self.data_mapper.get(key) {
-            match value.is_expired() {
+        if let Some(meta) = self.data_mapper.get_mut(key) {
+            match meta.is_expired() {
                 true => {
                     self.remove(key);
                     None
                 }
-                false => Some(self.string_store.get(key).unwrap()),
+                false => {
+                    meta.touch();
+                    self.string_store.get(key).map(|value| value.as_slice())
+                }
             }
         } else {
             None
@@ -71,8 +181,13 @@ impl Storage for InMemoryStorage {
         self.data_mapper.get(key)
     }
 
+    fn meta_mut(&mut self, key: &[u8]) -> Option<&mut RedisMeta> {
+        self.data_mapper.get_mut(key)
+    }
+
     fn remove(&mut self, key: &[u8]) -> u32 {
         use RedisType::*;
+        self.used_memory -= self.usage_of(key);
         match self.data_mapper.remove_entry(key) {
             Some((key, meta)) => match meta.data_type {
                 String => match self.string_store.remove(&key) {
@@ -87,7 +202,14 @@ impl Storage for InMemoryStorage {
                     Some(_) => 1,
                     None => 0,
                 },
-                Set => unimplemented!(),
+                Set => match self.set_store.remove(&key) {
+                    Some(_) => 1,
+                    None => 0,
+                },
+                SortedSet => match self.sorted_set_store.remove(&key) {
+                    Some(_) => 1,
+                    None => 0,
+                },
             },
             None => 0,
         }
@@ -99,6 +221,7 @@ impl Storage for InMemoryStorage {
     ///
     /// If the key was not present at all, return `false`
     fn contains(&mut self, key: &[u8]) -> bool {
+        self.expire_fields(key);
         if let Some(meta) = self.data_mapper.get(key) {
             match meta.is_expired() {
                 true => {
@@ -130,25 +253,59 @@ impl Storage for InMemoryStorage {
                 data_type: RedisType::Hash,
                 ..
             }) => "hash",
+            Some(RedisMeta {
+                data_type: RedisType::SortedSet,
+                ..
+            }) => "zset",
             None => "none",
         };
         t.as_bytes()
     }
 
+    fn entry(&mut self, key: &[u8]) -> Option<ValueRef<'_>> {
+        self.expire_fields(key);
+        let meta = self.data_mapper.get_mut(key)?;
+        if meta.is_expired() {
+            self.remove(key);
+            return None;
+        }
+        meta.touch();
+        let value = match meta.data_type {
+            RedisType::String => ValueRef::String(self.string_store.get(key)?),
+            RedisType::List => ValueRef::List(self.list_store.get(key)?),
+            RedisType::Set => ValueRef::Set(self.set_store.get(key)?),
+            RedisType::Hash => ValueRef::Hash(&self.hash_store.get(key)?.data),
+            RedisType::SortedSet => ValueRef::SortedSet(self.sorted_set_store.get(key)?),
+        };
+        Some(value)
+    }
+
     fn lwrite(&mut self, key: &[u8], values: Vec<RedisString>) {
-        let meta = RedisMeta::new(RedisType::List, None);
-        self.data_mapper.insert(key.to_vec(), meta);
-        self.list_store.insert(key.to_vec(), values);
+        if values.is_empty() {
+            self.remove(key);
+            return;
+        }
+        self.update(key, |storage| {
+            let previous = match storage.data_mapper.get(key) {
+                Some(meta) if meta.data_type == RedisType::List => Some(meta.encoding),
+                _ => None,
+            };
+            let mut meta = RedisMeta::new(RedisType::List, None);
+            meta.encoding = Encoding::of_list(&values, previous, &storage.encoding_limits);
+            storage.data_mapper.insert(key.to_vec(), meta);
+            storage.list_store.insert(key.to_vec(), values);
+        })
     }
 
     fn lread(&mut self, key: &[u8]) -> Option<&Vec<RedisString>> {
-        if let Some(meta) = self.data_mapper.get(key) {
+        if let Some(meta) = self.data_mapper.get_mut(key) {
             match meta.is_expired() {
                 true => {
                     self.remove(key);
                     None
                 }
                 false => {
+                    meta.touch();
                     self.list_store.get(key)
                 }
             }
@@ -158,19 +315,31 @@ impl Storage for InMemoryStorage {
     }
 
     fn swrite(&mut self, key: &[u8], values: HashSet<RedisString>) {
-        let meta = RedisMeta::new(RedisType::Set, None);
-        self.data_mapper.insert(key.to_vec(), meta);
-        self.set_store.insert(key.to_vec(), values);
+        if values.is_empty() {
+            self.remove(key);
+            return;
+        }
+        self.update(key, |storage| {
+            let previous = match storage.data_mapper.get(key) {
+                Some(meta) if meta.data_type == RedisType::Set => Some(meta.encoding),
+                _ => None,
+            };
+            let mut meta = RedisMeta::new(RedisType::Set, None);
+            meta.encoding = Encoding::of_set(&values, previous, &storage.encoding_limits);
+            storage.data_mapper.insert(key.to_vec(), meta);
+            storage.set_store.insert(key.to_vec(), values);
+        })
     }
 
     fn sread(&mut self, key: &[u8]) -> Option<&HashSet<RedisString>> {
-        if let Some(meta) = self.data_mapper.get(key) {
+        if let Some(meta) = self.data_mapper.get_mut(key) {
             match meta.is_expired() {
                 true => {
                     self.remove(key);
                     None
                 }
                 false => {
+                    meta.touch();
                     self.set_store.get(key)
                 }
             }
@@ -179,15 +348,80 @@ impl Storage for InMemoryStorage {
         }
     }
 
+    fn sremove(&mut self, key: &[u8], members: &[RedisString]) -> u64 {
+        let set = match self.set_store.get_mut(key) {
+            Some(set) => set,
+            None => return 0,
+        };
+        let mut removed = 0;
+        for member in members {
+            if set.remove(member) {
+                self.used_memory -= string_usage(member);
+                removed += 1;
+            }
+        }
+        // removing members never changes the encoding of a set back
+        if set.is_empty() {
+            self.remove(key);
+        }
+        removed
+    }
+
     fn hwrite(&mut self, key: &[u8], value: HashMap<RedisString, RedisString>) {
-        let meta = RedisMeta::new(RedisType::Hash, None);
-        self.data_mapper.insert(key.to_vec(), meta);
-        self.hash_store
-            .insert(key.to_vec(), RedisHashMap::new(value));
+        if value.is_empty() {
+            self.remove(key);
+            return;
+        }
+        self.update(key, |storage| {
+            let previous = match storage.data_mapper.get(key) {
+                Some(meta) if meta.data_type == RedisType::Hash => Some(meta.encoding),
+                _ => None,
+            };
+            let mut meta = RedisMeta::new(RedisType::Hash, None);
+            meta.encoding = Encoding::of_hash(&value, previous, &storage.encoding_limits);
+            storage.data_mapper.insert(key.to_vec(), meta);
+            let hash = match storage.ordered_hashes {
+                true => RedisHashMap::ordered(value, storage.hash_store.get(key)),
+                false => RedisHashMap::new(value),
+            };
+            storage.hash_store.insert(key.to_vec(), hash);
+        })
+    }
+
+    fn hset(&mut self, key: &[u8], fields: Vec<(RedisString, RedisString)>) -> u64 {
+        // an expired hash is replaced, as is a value of another type
+        self.expire_fields(key);
+        if !self.contains(key) || self.type_of(key) != b"hash" {
+            self.remove(key);
+        }
+        self.update(key, |storage| {
+            let ordered = storage.ordered_hashes;
+            let hash = storage
+                .hash_store
+                .entry(key.to_vec())
+                .or_insert_with(|| match ordered {
+                    true => RedisHashMap::ordered(HashMap::new(), None),
+                    false => RedisHashMap::new(HashMap::new()),
+                });
+            let added = fields
+                .into_iter()
+                .filter(|(field, value)| hash.insert(field.clone(), value.clone()))
+                .count();
+
+            let previous = storage.data_mapper.get(key).map(|meta| meta.encoding);
+            let meta = storage
+                .data_mapper
+                .entry(key.to_vec())
+                .or_insert_with(|| RedisMeta::new(RedisType::Hash, None));
+            meta.encoding = Encoding::of_hash(&hash.data, previous, &storage.encoding_limits);
+            meta.touch();
+            added as u64
+        })
     }
 
     fn hread(&mut self, key: &[u8], field_key: &[u8]) -> Option<&[u8]> {
-        if let Some(meta) = self.data_mapper.get(key) {
+        self.expire_fields(key);
+        if let Some(meta) = self.data_mapper.get_mut(key) {
             match meta.is_expired() {
                 true => {
                     self.remove(key);
@@ -195,6 +429,7 @@ impl Storage for InMemoryStorage {
                 }
                 // good to go
                 false => {
+                    meta.touch();
                     // will never panic since we already checked if the key existed in data_mapper
                     if let Some(field_value) = self.hash_store.get(key).unwrap().data.get(field_key)
                     {
@@ -209,7 +444,178 @@ impl Storage for InMemoryStorage {
         }
     }
 
+    fn hread_all(&mut self, key: &[u8]) -> Option<&HashMap<RedisString, RedisString>> {
+        self.expire_fields(key);
+        if let Some(meta) = self.data_mapper.get_mut(key) {
+            match meta.is_expired() {
+                true => {
+                    self.remove(key);
+                    None
+                }
+                false => {
+                    meta.touch();
+                    self.hash_store.get(key).map(|hash| &hash.data)
+                }
+            }
+        } else {
+            None
+        }
+    }
+
+    fn hentries(&mut self, key: &[u8]) -> Option<Vec<(RedisString, RedisString)>> {
+        // removes the key once expired, and counts the access
+        self.hread_all(key)?;
+        self.hash_store.get(key).map(|hash| {
+            hash.entries()
+                .into_iter()
+                .map(|(field, value)| (field.clone(), value.clone()))
+                .collect()
+        })
+    }
+
+    fn hexpiry(&mut self, key: &[u8], field: &[u8]) -> Option<Option<Expiry>> {
+        if !self.contains(key) {
+            return None;
+        }
+        let hash = self.hash_store.get(key)?;
+        match hash.data.contains_key(field) {
+            true => Some(hash.expiry(field)),
+            false => None,
+        }
+    }
+
+    fn hset_expiry(&mut self, key: &[u8], field: &[u8], expiry: Option<Expiry>) {
+        if let Some(hash) = self.hash_store.get_mut(key) {
+            if hash.data.contains_key(field) {
+                hash.set_expiry(field, expiry);
+            }
+        }
+        self.expire_fields(key);
+    }
+
+    fn zwrite(&mut self, key: &[u8], value: RedisSortedSet) {
+        if value.is_empty() {
+            self.remove(key);
+            return;
+        }
+        self.update(key, |storage| {
+            let previous = match storage.data_mapper.get(key) {
+                Some(meta) if meta.data_type == RedisType::SortedSet => Some(meta.encoding),
+                _ => None,
+            };
+            let mut meta = RedisMeta::new(RedisType::SortedSet, None);
+            meta.encoding = Encoding::of_sorted_set(&value, previous, &storage.encoding_limits);
+            storage.data_mapper.insert(key.to_vec(), meta);
+            storage.sorted_set_store.insert(key.to_vec(), value);
+        })
+    }
+
+    fn zread(&mut self, key: &[u8]) -> Option<&RedisSortedSet> {
+        if let Some(meta) = self.data_mapper.get_mut(key) {
+            match meta.is_expired() {
+                true => {
+                    self.remove(key);
+                    None
+                }
+                false => {
+                    meta.touch();
+                    self.sorted_set_store.get(key)
+                }
+            }
+        } else {
+            None
+        }
+    }
+
     fn size(&self) -> u64 {
         self.data_mapper.len() as u64
     }
+
+    fn expires(&self) -> u64 {
+        self.data_mapper
+            .values()
+            .filter(|meta| meta.expiry.is_some())
+            .count() as u64
+    }
+
+    fn memory_usage(&self, key: &[u8]) -> Option<u64> {
+        match self.data_mapper.contains_key(key) {
+            true => Some(self.usage_of(key)),
+            false => None,
+        }
+    }
+
+    fn used_memory(&self) -> u64 {
+        self.used_memory
+    }
+
+    fn keys_overhead(&self) -> u64 {
+        self.data_mapper.len() as u64 * KEY_OVERHEAD
+    }
+
+    fn keys(&self) -> Vec<RedisString> {
+        self.data_mapper.keys().cloned().collect()
+    }
+
+    fn value(&self, key: &[u8]) -> Option<RedisValue> {
+        use RedisType::*;
+        match self.data_mapper.get(key)?.data_type {
+            String => self.string_store.get(key).cloned().map(RedisValue::String),
+            List => self.list_store.get(key).cloned().map(RedisValue::List),
+            Set => self.set_store.get(key).cloned().map(RedisValue::Set),
+            Hash => self
+                .hash_store
+                .get(key)
+                .map(|hash| RedisValue::Hash(hash.data.clone())),
+            SortedSet => self
+                .sorted_set_store
+                .get(key)
+                .cloned()
+                .map(RedisValue::SortedSet),
+        }
+    }
+
+    fn insert(&mut self, key: &[u8], value: RedisValue, expiry: Option<Expiry>) {
+        self.remove(key);
+        match value {
+            RedisValue::String(value) => self.write(key, &value),
+            RedisValue::List(values) => self.lwrite(key, values),
+            RedisValue::Set(values) => self.swrite(key, values),
+            RedisValue::Hash(values) => self.hwrite(key, values),
+            RedisValue::SortedSet(values) => self.zwrite(key, values),
+        }
+        if let Some(expiry) = expiry {
+            self.expire(key, expiry);
+        }
+    }
+
+    fn set_encoding_limits(&mut self, limits: EncodingLimits) {
+        self.encoding_limits = limits;
+    }
+
+    fn set_ordered_hashes(&mut self, ordered: bool) {
+        self.ordered_hashes = ordered;
+    }
+
+    fn clear(&mut self) {
+        self.data_mapper.clear();
+        self.string_store.clear();
+        self.list_store.clear();
+        self.set_store.clear();
+        self.hash_store.clear();
+        self.sorted_set_store.clear();
+        self.used_memory = 0;
+    }
+
+    fn take_all(&mut self) -> Box<dyn Send> {
+        self.used_memory = 0;
+        Box::new((
+            take(&mut self.data_mapper),
+            take(&mut self.string_store),
+            take(&mut self.list_store),
+            take(&mut self.set_store),
+            take(&mut self.hash_store),
+            take(&mut self.sorted_set_store),
+        ))
+    }
 }
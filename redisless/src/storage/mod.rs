@@ -3,28 +3,92 @@ mod tests;
 
 pub mod in_memory;
 pub mod models;
+pub mod snapshot;
 
 use std::collections::{HashMap, HashSet};
 
 use models::expiry::Expiry;
 use models::RedisString;
 
-use self::models::RedisMeta;
+use self::models::{EncodingLimits, RedisMeta, RedisSortedSet, RedisValue, ValueRef};
 
+/// Keys and values of a database. The commands are run against any implementation of it,
+/// the server being generic over its storage, such as `InMemoryStorage`.
 pub trait Storage {
+    /// Replace whatever is stored at key by the string value, without an expiry
     fn write(&mut self, key: &[u8], value: &[u8]);
     fn extend(&mut self, key: &[u8], value: &[u8]) -> u64;
+    /// Overwrite the string at key from offset with value, padding it with zero bytes when
+    /// it is shorter, and return its new length. A missing string is created.
+    fn set_range(&mut self, key: &[u8], offset: usize, value: &[u8]) -> u64;
     fn expire(&mut self, key: &[u8], expiry: Expiry) -> u32;
+    /// Remove the expiry of key, returning 1 if it had one
+    fn persist(&mut self, key: &[u8]) -> u32;
     fn read(&mut self, key: &[u8]) -> Option<&[u8]>;
     fn remove(&mut self, key: &[u8]) -> u32;
     fn contains(&mut self, key: &[u8]) -> bool;
     fn type_of(&mut self, key: &[u8]) -> &[u8];
+    /// Value stored at key with its type, `None` when the key does not exist or is expired,
+    /// in which case it is removed. Reads the value as the `*read` of its type does.
+    fn entry(&mut self, key: &[u8]) -> Option<ValueRef<'_>>;
+    /// Replace whatever is stored at key by the list values. As for the `*write` of the other
+    /// collections, and `insert`, an empty collection removes the key instead, so a command
+    /// emptying a collection never leaves it behind for `EXISTS`, `TYPE` or `DBSIZE`.
     fn lwrite(&mut self, key: &[u8], values: Vec<RedisString>);
     fn lread(&mut self, key: &[u8]) -> Option<&Vec<RedisString>>;
     fn swrite(&mut self, key: &[u8], values: HashSet<RedisString>);
     fn sread(&mut self, key: &[u8]) -> Option<&HashSet<RedisString>>;
+    /// Remove members from the set at key in place, the key with the last of them. Returns
+    /// the number of members removed, in time proportional to the number of members given.
+    fn sremove(&mut self, key: &[u8], members: &[RedisString]) -> u64;
     fn hwrite(&mut self, key: &[u8], value: HashMap<RedisString, RedisString>);
     fn hread(&mut self, key: &[u8], field_key: &[u8]) -> Option<&[u8]>;
+    fn hread_all(&mut self, key: &[u8]) -> Option<&HashMap<RedisString, RedisString>>;
+    /// Set the fields of the hash at key, created or replacing a value of another type while
+    /// keeping the expiry of a hash. Returns the number of fields added.
+    fn hset(&mut self, key: &[u8], fields: Vec<(RedisString, RedisString)>) -> u64;
+    /// Fields and values of the hash at key, in the order the fields were added when the
+    /// hashes preserve it
+    fn hentries(&mut self, key: &[u8]) -> Option<Vec<(RedisString, RedisString)>>;
+    /// Expiry of field in the hash at key, `Some(None)` when the field has none and `None`
+    /// when it does not exist. Fields are removed on access once expired, the hash with the
+    /// last of them. The expiries of the fields are not kept by snapshots nor by `DUMP`.
+    fn hexpiry(&mut self, key: &[u8], field: &[u8]) -> Option<Option<Expiry>>;
+    /// Set or, with `None`, remove the expiry of an existing field of the hash at key. A
+    /// field expiring at once is removed.
+    fn hset_expiry(&mut self, key: &[u8], field: &[u8], expiry: Option<Expiry>);
+    fn zwrite(&mut self, key: &[u8], value: RedisSortedSet);
+    fn zread(&mut self, key: &[u8]) -> Option<&RedisSortedSet>;
+    /// Number of keys, in constant time. As in Redis, an expired key is counted until the
+    /// reaper or an access to it removes it, so the count may be a little over the number of
+    /// live keys, never under it.
     fn size(&self) -> u64;
+    /// Number of keys with an expiry, including the ones which are expired but not removed yet
+    fn expires(&self) -> u64;
     fn meta(&self, key: &[u8]) -> Option<&RedisMeta>;
+    /// Meta of key, to change its access metadata. The type and encoding must be left as
+    /// they are.
+    fn meta_mut(&mut self, key: &[u8]) -> Option<&mut RedisMeta>;
+    /// Estimated bytes used by key and its value, including the ones of an expired key
+    fn memory_usage(&self, key: &[u8]) -> Option<u64>;
+    /// Estimated bytes used by all the keys, the sum of their `memory_usage`
+    fn used_memory(&self) -> u64;
+    /// Part of the used memory taken by the structures holding the keys rather than by the
+    /// keys and their values
+    fn keys_overhead(&self) -> u64;
+    /// All the keys, including the ones which are expired but not removed yet
+    fn keys(&self) -> Vec<RedisString>;
+    /// Owned copy of the value stored at key, regardless of its type
+    fn value(&self, key: &[u8]) -> Option<RedisValue>;
+    /// Replace whatever is stored at key by value
+    fn insert(&mut self, key: &[u8], value: RedisValue, expiry: Option<Expiry>);
+    /// Thresholds of the compact encodings of the values written from now on
+    fn set_encoding_limits(&mut self, limits: EncodingLimits);
+    /// Whether the hashes written from now on preserve the order their fields are added in
+    fn set_ordered_hashes(&mut self, ordered: bool);
+    /// Remove every key
+    fn clear(&mut self);
+    /// Remove all the keys, as `clear` does, but return them rather than dropping them: the
+    /// memory of a large keyspace can then be reclaimed by another thread
+    fn take_all(&mut self) -> Box<dyn Send>;
 }
@@ -0,0 +1,214 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{parse_i64, RedisSortedSet, RedisString, RedisType};
+
+/// Thresholds past which the compact encoding of a small value is converted to the general
+/// one. As in Redis the conversion is one-way, a value does not go back to the compact
+/// encoding once it shrinks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncodingLimits {
+    /// Max bytes of a string reported as an `embstr`, 44 as in Redis which stores such a
+    /// string embedded in its object
+    pub embstr_size_limit: usize,
+    /// Max fields of a hash encoded as a listpack (`hash-max-listpack-entries`)
+    pub hash_max_listpack_entries: usize,
+    /// Max bytes of each field and value of a hash encoded as a listpack
+    /// (`hash-max-listpack-value`)
+    pub hash_max_listpack_value: usize,
+    /// Max members of a set of integers encoded as an intset (`set-max-intset-entries`)
+    pub set_max_intset_entries: usize,
+    /// Max members of a set encoded as a listpack (`set-max-listpack-entries`)
+    pub set_max_listpack_entries: usize,
+    /// Max bytes of each member of a set encoded as a listpack (`set-max-listpack-value`)
+    pub set_max_listpack_value: usize,
+    /// Max members of a sorted set encoded as a listpack (`zset-max-listpack-entries`)
+    pub zset_max_listpack_entries: usize,
+    /// Max bytes of each member of a sorted set encoded as a listpack
+    /// (`zset-max-listpack-value`)
+    pub zset_max_listpack_value: usize,
+    /// Max entries of a list encoded as a listpack when positive, and when negative its max
+    /// bytes, from -1 for 4KB to -5 for 64KB (`list-max-listpack-size`)
+    pub list_max_listpack_size: i64,
+}
+
+impl Default for EncodingLimits {
+    fn default() -> Self {
+        EncodingLimits {
+            embstr_size_limit: 44,
+            hash_max_listpack_entries: 128,
+            hash_max_listpack_value: 64,
+            set_max_intset_entries: 512,
+            set_max_listpack_entries: 128,
+            set_max_listpack_value: 64,
+            zset_max_listpack_entries: 128,
+            zset_max_listpack_value: 64,
+            list_max_listpack_size: -2,
+        }
+    }
+}
+
+/// Encoding of a value, as reported by `OBJECT ENCODING`. The values are not encoded
+/// differently here, the encoding is the one Redis would use.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Encoding {
+    Int,
+    Intset,
+    EmbStr,
+    Raw,
+    Quicklist,
+    Listpack,
+    Hashtable,
+    Skiplist,
+}
+
+impl Encoding {
+    /// Encoding of a string when it is set, as an integer when it is one
+    pub fn of_string(value: &[u8], limits: &EncodingLimits) -> Self {
+        match value.len() {
+            _ if parse_i64(value).is_some() => Encoding::Int,
+            len if len <= limits.embstr_size_limit => Encoding::EmbStr,
+            _ => Encoding::Raw,
+        }
+    }
+
+    /// Encoding of a hash written with these fields, past the limits of a listpack or
+    /// when it is already a hashtable
+    pub fn of_hash(
+        value: &HashMap<RedisString, RedisString>,
+        previous: Option<Encoding>,
+        limits: &EncodingLimits,
+    ) -> Self {
+        let max_len = limits.hash_max_listpack_value;
+        let fits = value.len() <= limits.hash_max_listpack_entries
+            && value
+                .iter()
+                .all(|(field, value)| field.len() <= max_len && value.len() <= max_len);
+        match previous {
+            Some(Encoding::Hashtable) => Encoding::Hashtable,
+            _ if fits => Encoding::Listpack,
+            _ => Encoding::Hashtable,
+        }
+    }
+
+    /// Encoding of a set written with these members: an intset while they are all integers,
+    /// then a listpack within its limits, and a hashtable once past them
+    pub fn of_set(
+        values: &HashSet<RedisString>,
+        previous: Option<Encoding>,
+        limits: &EncodingLimits,
+    ) -> Self {
+        let integers = || {
+            values.len() <= limits.set_max_intset_entries
+                && values.iter().all(|member| parse_i64(member).is_some())
+        };
+        let fits = || {
+            values.len() <= limits.set_max_listpack_entries
+                && values
+                    .iter()
+                    .all(|member| member.len() <= limits.set_max_listpack_value)
+        };
+        match previous {
+            Some(Encoding::Hashtable) => Encoding::Hashtable,
+            None | Some(Encoding::Intset) if integers() => Encoding::Intset,
+            _ if fits() => Encoding::Listpack,
+            _ => Encoding::Hashtable,
+        }
+    }
+
+    /// Encoding of a sorted set written with these members, past the limits of a listpack
+    /// or when it is already a skiplist
+    pub fn of_sorted_set(
+        values: &RedisSortedSet,
+        previous: Option<Encoding>,
+        limits: &EncodingLimits,
+    ) -> Self {
+        let fits = || {
+            values.len() <= limits.zset_max_listpack_entries
+                && values
+                    .iter()
+                    .all(|(member, _)| member.len() <= limits.zset_max_listpack_value)
+        };
+        match previous {
+            Some(Encoding::Skiplist) => Encoding::Skiplist,
+            _ if fits() => Encoding::Listpack,
+            _ => Encoding::Skiplist,
+        }
+    }
+
+    /// Encoding of a list written with these elements: a listpack while it fits in a single
+    /// quicklist node, then a quicklist. The bytes of the listpack are counted as the ones
+    /// of its elements.
+    pub fn of_list(
+        values: &[RedisString],
+        previous: Option<Encoding>,
+        limits: &EncodingLimits,
+    ) -> Self {
+        let fits = || match node_fill(limits) {
+            NodeFill::Entries(max) => values.len() <= max,
+            NodeFill::Bytes(max) => values.iter().map(|value| value.len()).sum::<usize>() <= max,
+        };
+        match previous {
+            Some(Encoding::Quicklist) => Encoding::Quicklist,
+            _ if fits() => Encoding::Listpack,
+            _ => Encoding::Quicklist,
+        }
+    }
+
+    /// Number of nodes of the quicklist of these elements, as reported by `DEBUG OBJECT`: each
+    /// node is filled with elements as long as it fits `list-max-listpack-size`, and an
+    /// element too large for a node is a node of its own
+    pub fn quicklist_nodes(values: &[RedisString], limits: &EncodingLimits) -> usize {
+        let fill = node_fill(limits);
+        let (mut nodes, mut entries, mut bytes) = (0, 0, 0);
+        for value in values {
+            let full = match fill {
+                NodeFill::Entries(max) => entries + 1 > max,
+                NodeFill::Bytes(max) => bytes + value.len() > max,
+            };
+            if nodes == 0 || (full && entries > 0) {
+                nodes += 1;
+                entries = 0;
+                bytes = 0;
+            }
+            entries += 1;
+            bytes += value.len();
+        }
+        nodes
+    }
+
+    pub fn of_type(data_type: RedisType) -> Self {
+        match data_type {
+            RedisType::String => Encoding::Raw,
+            RedisType::List => Encoding::Quicklist,
+            RedisType::Set | RedisType::Hash => Encoding::Hashtable,
+            RedisType::SortedSet => Encoding::Skiplist,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Int => "int",
+            Encoding::Intset => "intset",
+            Encoding::EmbStr => "embstr",
+            Encoding::Raw => "raw",
+            Encoding::Quicklist => "quicklist",
+            Encoding::Listpack => "listpack",
+            Encoding::Hashtable => "hashtable",
+            Encoding::Skiplist => "skiplist",
+        }
+    }
+}
+
+// how much a quicklist node holds, by `list-max-listpack-size`
+enum NodeFill {
+    Entries(usize),
+    Bytes(usize),
+}
+
+fn node_fill(limits: &EncodingLimits) -> NodeFill {
+    match limits.list_max_listpack_size {
+        size if size > 0 => NodeFill::Entries(size as usize),
+        // as in Redis, a size below -5 is -5, and 0 is taken as -1 rather than shifting by -1
+        size => NodeFill::Bytes(4096 << (-size.clamp(-5, -1) - 1)),
+    }
+}
@@ -1,26 +1,50 @@
+use std::convert::TryFrom;
+
 use chrono::{offset::Utc, Duration};
 
+/// Instant a key expires at, whether its ttl was given in seconds or in milliseconds
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Expiry {
+    // milliseconds since the Unix epoch
     pub timestamp: i64,
 }
 
+/// A duration too long to expire at a representable instant
 #[derive(Debug)]
 pub struct TimeOverflow {}
 
 impl Expiry {
     pub fn new_from_millis(duration: u64) -> Result<Self, TimeOverflow> {
-        Utc::now()
-            .checked_add_signed(Duration::milliseconds(duration as i64))
-            .map(|t| Self {
-                timestamp: t.timestamp_millis(),
-            })
-            .ok_or(TimeOverflow {})
+        Self::after(
+            i64::try_from(duration)
+                .ok()
+                .and_then(Duration::try_milliseconds),
+        )
     }
 
     pub fn new_from_secs(duration: u64) -> Result<Self, TimeOverflow> {
-        Utc::now()
-            .checked_add_signed(Duration::seconds(duration as i64))
+        Self::after(i64::try_from(duration).ok().and_then(Duration::try_seconds))
+    }
+
+    /// Expiry at the Unix time timestamp, in milliseconds
+    pub fn at_millis(timestamp: u64) -> Result<Self, TimeOverflow> {
+        i64::try_from(timestamp)
+            .map(|timestamp| Self { timestamp })
+            .map_err(|_| TimeOverflow {})
+    }
+
+    /// Expiry at the Unix time timestamp, in seconds
+    pub fn at_secs(timestamp: u64) -> Result<Self, TimeOverflow> {
+        timestamp
+            .checked_mul(1000)
+            .ok_or(TimeOverflow {})
+            .and_then(Self::at_millis)
+    }
+
+    // `None` is a duration which does not fit a `Duration`
+    fn after(duration: Option<Duration>) -> Result<Self, TimeOverflow> {
+        duration
+            .and_then(|duration| Utc::now().checked_add_signed(duration))
             .map(|t| Self {
                 timestamp: t.timestamp_millis(),
             })
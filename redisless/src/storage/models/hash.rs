@@ -1,13 +1,119 @@
-use super::RedisString;
+use super::{Expiry, RedisString};
 use std::collections::HashMap;
 
 #[derive(Debug, PartialEq)]
 pub struct RedisHashMap {
     pub data: HashMap<RedisString, RedisString>,
+    // fields in the order they were added, only kept when the hashes preserve it
+    order: Option<Vec<RedisString>>,
+    // expiry of the fields which have one, set by `HEXPIRE`
+    expiries: HashMap<RedisString, Expiry>,
 }
 
 impl RedisHashMap {
     pub fn new(data: HashMap<RedisString, RedisString>) -> Self {
-        Self { data }
+        Self {
+            data,
+            order: None,
+            expiries: HashMap::new(),
+        }
+    }
+
+    /// Hash of data preserving the order of its fields: the ones of previous first, in their
+    /// order, then the new ones sorted
+    pub fn ordered(data: HashMap<RedisString, RedisString>, previous: Option<&Self>) -> Self {
+        let mut order: Vec<_> = previous
+            .and_then(|previous| previous.order.as_ref())
+            .map(|order| {
+                order
+                    .iter()
+                    .filter(|field| data.contains_key(*field))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut added: Vec<_> = match previous {
+            Some(previous) => data
+                .keys()
+                .filter(|field| !previous.data.contains_key(*field))
+                .cloned()
+                .collect(),
+            None => data.keys().cloned().collect(),
+        };
+        added.sort();
+        order.append(&mut added);
+        Self {
+            data,
+            order: Some(order),
+            expiries: HashMap::new(),
+        }
+    }
+
+    /// Set field to value, returning whether the field is new. A new field goes last when
+    /// the order is preserved, a field set again loses its expiry.
+    pub fn insert(&mut self, field: RedisString, value: RedisString) -> bool {
+        self.expiries.remove(&field);
+        match self.data.insert(field.clone(), value) {
+            Some(_) => false,
+            None => {
+                if let Some(order) = &mut self.order {
+                    order.push(field);
+                }
+                true
+            }
+        }
+    }
+
+    /// Fields and values, in the order the fields were added when it is preserved
+    pub fn entries(&self) -> Vec<(&RedisString, &RedisString)> {
+        match &self.order {
+            Some(order) => order
+                .iter()
+                .filter_map(|field| self.data.get_key_value(field))
+                .collect(),
+            None => self.data.iter().collect(),
+        }
+    }
+
+    /// Fields held a second time to preserve their order
+    pub fn ordered_fields(&self) -> &[RedisString] {
+        self.order.as_deref().unwrap_or_default()
+    }
+
+    /// Expiry of field, `None` when it has none
+    pub fn expiry(&self, field: &[u8]) -> Option<Expiry> {
+        self.expiries.get(field).copied()
+    }
+
+    /// Set or, with `None`, remove the expiry of field, which must exist
+    pub fn set_expiry(&mut self, field: &[u8], expiry: Option<Expiry>) {
+        match expiry {
+            Some(expiry) => self.expiries.insert(field.to_vec(), expiry),
+            None => self.expiries.remove(field),
+        };
+    }
+
+    pub fn has_expired_fields(&self) -> bool {
+        self.expiries
+            .values()
+            .any(|expiry| expiry.duration_left_millis() <= 0)
+    }
+
+    /// Remove the fields whose expiry is past
+    pub fn remove_expired(&mut self) {
+        let expired: Vec<_> = self
+            .expiries
+            .iter()
+            .filter(|(_, expiry)| expiry.duration_left_millis() <= 0)
+            .map(|(field, _)| field.clone())
+            .collect();
+        for field in &expired {
+            self.expiries.remove(field);
+            self.data.remove(field);
+        }
+        if let Some(order) = &mut self.order {
+            let data = &self.data;
+            order.retain(|field| data.contains_key(field));
+        }
     }
 }
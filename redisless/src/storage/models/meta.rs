@@ -1,13 +1,38 @@
-use super::{Expiry, RedisType};
+use std::convert::TryFrom;
+
+use chrono::Utc;
+
+use super::{Encoding, Expiry, RedisType};
+
+// LFU counter of a new value, so it is not evicted before it had a chance to be accessed
+const LFU_INIT_VAL: u8 = 5;
+// the higher the factor, the more accesses it takes to saturate the counter
+const LFU_LOG_FACTOR: f64 = 10.0;
+// minutes after which an idle counter is decremented by one
+const LFU_DECAY_TIME_MINUTES: i64 = 1;
 
 pub struct RedisMeta {
     pub data_type: RedisType,
     pub expiry: Option<Expiry>,
+    pub encoding: Encoding,
+    // unix timestamp in millis of the last time the value was accessed
+    pub last_access: i64,
+    // logarithmic access frequency counter, as in the Redis LFU policies
+    lfu_counter: u8,
+    // unix timestamp in minutes of the last time lfu_counter was decremented
+    lfu_decrement_time: i64,
 }
 
 impl RedisMeta {
     pub fn new(data_type: RedisType, expiry: Option<Expiry>) -> Self {
-        Self { data_type, expiry }
+        Self {
+            data_type,
+            expiry,
+            encoding: Encoding::of_type(data_type),
+            last_access: Utc::now().timestamp_millis(),
+            lfu_counter: LFU_INIT_VAL,
+            lfu_decrement_time: Utc::now().timestamp() / 60,
+        }
     }
 
     pub fn is_expired(&self) -> bool {
@@ -17,4 +42,44 @@ impl RedisMeta {
             false
         }
     }
+
+    /// Mark the value as accessed right now
+    pub fn touch(&mut self) {
+        self.last_access = Utc::now().timestamp_millis();
+
+        self.lfu_counter = self.frequency();
+        self.lfu_decrement_time = Utc::now().timestamp() / 60;
+        // the more the counter grows, the less likely it is to be incremented
+        if self.lfu_counter < u8::MAX {
+            let base = self.lfu_counter.saturating_sub(LFU_INIT_VAL) as f64;
+            if rand::random::<f64>() < 1.0 / (base * LFU_LOG_FACTOR + 1.0) {
+                self.lfu_counter += 1;
+            }
+        }
+    }
+
+    /// Access frequency counter, decremented by one for every decay period the value
+    /// was not accessed
+    pub fn frequency(&self) -> u8 {
+        let idle_minutes = Utc::now().timestamp() / 60 - self.lfu_decrement_time;
+        let periods = idle_minutes / LFU_DECAY_TIME_MINUTES;
+        self.lfu_counter
+            .saturating_sub(periods.clamp(0, u8::MAX as i64) as u8)
+    }
+
+    pub fn idle_time_millis(&self) -> i64 {
+        Utc::now().timestamp_millis() - self.last_access
+    }
+
+    /// Make the value idle for secs, as `RESTORE ... IDLETIME` does
+    pub fn set_idle_time_secs(&mut self, secs: u64) {
+        let millis = i64::try_from(secs.saturating_mul(1000)).unwrap_or(i64::MAX);
+        self.last_access = Utc::now().timestamp_millis().saturating_sub(millis);
+    }
+
+    /// Set the access frequency counter, as `RESTORE ... FREQ` does
+    pub fn set_frequency(&mut self, frequency: u8) {
+        self.lfu_counter = frequency;
+        self.lfu_decrement_time = Utc::now().timestamp() / 60;
+    }
 }
@@ -1,18 +1,54 @@
+pub mod encoding;
 pub mod expiry;
 pub mod hash;
 pub mod meta;
+pub mod sorted_set;
+pub mod value;
 
 // re-export so one can use with models::Expiry
 // rather than models::expiry::Expiry
+pub use encoding::{Encoding, EncodingLimits};
 pub use expiry::Expiry;
 pub use hash::RedisHashMap;
 pub use meta::RedisMeta;
+pub use sorted_set::{LexBound, RedisSortedSet, ScoreBound};
+pub use value::{RedisValue, ValueRef};
 
 pub type RedisString = Vec<u8>;
 
+/// Integer held by a string, only in the format Redis accepts: an optional `-` then digits,
+/// without a `+`, whitespace or leading zeros, and in the range of an `i64`
+pub fn parse_i64(bytes: &[u8]) -> Option<i64> {
+    match bytes {
+        [b'-', digits @ ..] if is_canonical(digits) && digits != b"0" => {}
+        digits if is_canonical(digits) => {}
+        _ => return None,
+    }
+    std::str::from_utf8(bytes).ok()?.parse::<i64>().ok()
+}
+
+/// Unsigned integer held by a string, in the format of [`parse_i64`] without the sign
+pub fn parse_u64(bytes: &[u8]) -> Option<u64> {
+    match is_canonical(bytes) {
+        true => std::str::from_utf8(bytes).ok()?.parse::<u64>().ok(),
+        false => None,
+    }
+}
+
+// digits without leading zeros, or a single zero
+fn is_canonical(digits: &[u8]) -> bool {
+    match digits {
+        [b'0'] => true,
+        [b'1'..=b'9', rest @ ..] => rest.iter().all(u8::is_ascii_digit),
+        _ => false,
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum RedisType {
     String,
     List,
     Set,
     Hash,
+    SortedSet,
 }
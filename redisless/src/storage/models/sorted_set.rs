@@ -0,0 +1,181 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+
+use super::RedisString;
+
+/// Members of a sorted set with their score, ordered by score then by member
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RedisSortedSet {
+    scores: HashMap<RedisString, f64>,
+    ordered: BTreeSet<(Score, RedisString)>,
+}
+
+/// One end of a score range, e.g. `(1.5` or `+inf`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreBound {
+    pub score: f64,
+    // `(` prefix: the score itself is not part of the range
+    pub exclusive: bool,
+}
+
+/// One end of a lexicographic range, e.g. `[a`, `(b`, `-` or `+`
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexBound {
+    // `-`, below every member
+    Min,
+    // `+`, above every member
+    Max,
+    Inclusive(RedisString),
+    Exclusive(RedisString),
+}
+
+/// `f64` with a total order, so scores can be kept in a `BTreeSet`
+#[derive(Debug, Clone, Copy)]
+struct Score(f64);
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl ScoreBound {
+    /// Whether score is above this bound taken as the min of a range
+    pub fn is_below(&self, score: f64) -> bool {
+        match self.exclusive {
+            true => score > self.score,
+            false => score >= self.score,
+        }
+    }
+
+    /// Whether score is under this bound taken as the max of a range
+    pub fn is_above(&self, score: f64) -> bool {
+        match self.exclusive {
+            true => score < self.score,
+            false => score <= self.score,
+        }
+    }
+}
+
+impl LexBound {
+    /// Whether member is above this bound taken as the min of a range
+    pub fn is_below(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Min => true,
+            LexBound::Max => false,
+            LexBound::Inclusive(bound) => member >= bound.as_slice(),
+            LexBound::Exclusive(bound) => member > bound.as_slice(),
+        }
+    }
+
+    /// Whether member is under this bound taken as the max of a range
+    pub fn is_above(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(bound) => member <= bound.as_slice(),
+            LexBound::Exclusive(bound) => member < bound.as_slice(),
+        }
+    }
+}
+
+impl RedisSortedSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Set the score of member, return `true` if member was not in the set
+    pub fn insert(&mut self, member: &[u8], score: f64) -> bool {
+        // -0.0 and 0.0 are the same score
+        let score = score + 0.0;
+        let added = match self.scores.insert(member.to_vec(), score) {
+            Some(old_score) => {
+                self.ordered.remove(&(Score(old_score), member.to_vec()));
+                false
+            }
+            None => true,
+        };
+        self.ordered.insert((Score(score), member.to_vec()));
+        added
+    }
+
+    /// Return `true` if member was in the set
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => self.ordered.remove(&(Score(score), member.to_vec())),
+            None => false,
+        }
+    }
+
+    /// Remove the member of the lowest score, or of the highest one when max is set
+    pub fn pop(&mut self, max: bool) -> Option<(RedisString, f64)> {
+        let (score, member) = match max {
+            true => self.ordered.pop_last()?,
+            false => self.ordered.pop_first()?,
+        };
+        self.scores.remove(&member);
+        Some((member, score.0))
+    }
+
+    /// Members and scores by increasing score
+    pub fn iter(&self) -> impl Iterator<Item = (&RedisString, f64)> {
+        self.ordered.iter().map(|(score, member)| (member, score.0))
+    }
+
+    /// Members and scores within min and max, by increasing score
+    pub fn range_by_score(
+        &self,
+        min: ScoreBound,
+        max: ScoreBound,
+    ) -> impl Iterator<Item = (&RedisString, f64)> {
+        self.ordered
+            .range((Score(min.score), RedisString::new())..)
+            .map(|(score, member)| (member, score.0))
+            .skip_while(move |(_, score)| !min.is_below(*score))
+            .take_while(move |(_, score)| max.is_above(*score))
+    }
+
+    /// Members within min and max, in order. As in Redis, the members are expected to all
+    /// have the same score, so that they are ordered lexicographically.
+    pub fn range_by_lex<'a>(
+        &'a self,
+        min: &'a LexBound,
+        max: &'a LexBound,
+    ) -> impl Iterator<Item = &'a RedisString> {
+        self.ordered
+            .iter()
+            .map(|(_, member)| member)
+            .skip_while(move |member| !min.is_below(member))
+            .take_while(move |member| max.is_above(member))
+    }
+
+    pub fn count(&self, min: ScoreBound, max: ScoreBound) -> usize {
+        self.range_by_score(min, max).count()
+    }
+}
@@ -0,0 +1,48 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{RedisSortedSet, RedisString, RedisType};
+
+/// Owned copy of a value of any type, used to move values in and out of a storage
+#[derive(Debug, PartialEq, Clone)]
+pub enum RedisValue {
+    String(RedisString),
+    List(Vec<RedisString>),
+    Set(HashSet<RedisString>),
+    Hash(HashMap<RedisString, RedisString>),
+    SortedSet(RedisSortedSet),
+}
+
+impl RedisValue {
+    pub fn data_type(&self) -> RedisType {
+        match self {
+            RedisValue::String(_) => RedisType::String,
+            RedisValue::List(_) => RedisType::List,
+            RedisValue::Set(_) => RedisType::Set,
+            RedisValue::Hash(_) => RedisType::Hash,
+            RedisValue::SortedSet(_) => RedisType::SortedSet,
+        }
+    }
+}
+
+/// Value stored at a key, borrowed from the storage along with its type, so that a command
+/// checks the type and reads the value with a single lookup
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ValueRef<'a> {
+    String(&'a [u8]),
+    List(&'a [RedisString]),
+    Set(&'a HashSet<RedisString>),
+    Hash(&'a HashMap<RedisString, RedisString>),
+    SortedSet(&'a RedisSortedSet),
+}
+
+impl ValueRef<'_> {
+    pub fn data_type(&self) -> RedisType {
+        match self {
+            ValueRef::String(_) => RedisType::String,
+            ValueRef::List(_) => RedisType::List,
+            ValueRef::Set(_) => RedisType::Set,
+            ValueRef::Hash(_) => RedisType::Hash,
+            ValueRef::SortedSet(_) => RedisType::SortedSet,
+        }
+    }
+}
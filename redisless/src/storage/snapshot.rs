@@ -0,0 +1,404 @@
+//! Point-in-time snapshot of the databases, and its binary encoding.
+//!
+//! The layout is:
+//!
+//! ```text
+//! "REDISLESS" <version: u8> (<SELECTDB: 0xFE> <db: u32> <entry>*)* <EOF: 0xFF>
+//! entry := <type: u8> <expiry: i64, -1 for none> <key> <value>
+//! ```
+//!
+//! All integers are little-endian, and strings are prefixed by their length as a `u32`.
+//! Sorted set scores are written as the little-endian bits of their `f64`.
+//!
+//! Sent over a connection, e.g. to a replica, the snapshot is framed as `$<len>\r\n` followed
+//! by the `len` bytes of the snapshot, and unlike a RESP bulk string no trailing CRLF.
+//!
+//! A single value is serialized by `DUMP` as `<type: u8> <value> <version: u8> <crc64: u64>`,
+//! the checksum being the CRC-64/Jones of Redis over the bytes before it.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Result, Write};
+use std::path::Path;
+
+use super::models::{Expiry, RedisSortedSet, RedisString, RedisValue};
+use super::Storage;
+
+const MAGIC: &[u8; 9] = b"REDISLESS";
+const VERSION: u8 = 1;
+
+const TYPE_STRING: u8 = 0;
+const TYPE_LIST: u8 = 1;
+const TYPE_SET: u8 = 2;
+const TYPE_HASH: u8 = 3;
+const TYPE_SORTED_SET: u8 = 4;
+const SELECTDB: u8 = 0xFE;
+const EOF: u8 = 0xFF;
+
+const NO_EXPIRY: i64 = -1;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SnapshotEntry {
+    pub key: RedisString,
+    pub value: RedisValue,
+    pub expiry: Option<Expiry>,
+}
+
+/// Copy every non expired entry of the storage
+pub fn take<T: Storage>(storage: &T) -> Vec<SnapshotEntry> {
+    storage
+        .keys()
+        .into_iter()
+        .filter_map(|key| {
+            let meta = storage.meta(&key)?;
+            if meta.is_expired() {
+                return None;
+            }
+            let expiry = meta.expiry;
+            let value = storage.value(&key)?;
+            Some(SnapshotEntry { key, value, expiry })
+        })
+        .collect()
+}
+
+/// Insert every entry into the storage, replacing existing keys
+pub fn restore<T: Storage>(storage: &mut T, entries: Vec<SnapshotEntry>) {
+    for entry in entries {
+        storage.insert(&entry.key, entry.value, entry.expiry);
+    }
+}
+
+/// Encode the entries of every database, `databases[i]` being the entries of the db `i`
+pub fn encode(databases: &[Vec<SnapshotEntry>]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(512);
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+
+    for (db, entries) in databases.iter().enumerate() {
+        if entries.is_empty() {
+            continue;
+        }
+        bytes.push(SELECTDB);
+        put_len(&mut bytes, db);
+        entries
+            .iter()
+            .for_each(|entry| put_entry(&mut bytes, entry));
+    }
+
+    bytes.push(EOF);
+    bytes
+}
+
+fn type_of(value: &RedisValue) -> u8 {
+    match value {
+        RedisValue::String(_) => TYPE_STRING,
+        RedisValue::List(_) => TYPE_LIST,
+        RedisValue::Set(_) => TYPE_SET,
+        RedisValue::Hash(_) => TYPE_HASH,
+        RedisValue::SortedSet(_) => TYPE_SORTED_SET,
+    }
+}
+
+fn put_entry(bytes: &mut Vec<u8>, entry: &SnapshotEntry) {
+    bytes.push(type_of(&entry.value));
+
+    let expiry = entry.expiry.map(|e| e.timestamp).unwrap_or(NO_EXPIRY);
+    bytes.extend_from_slice(&expiry.to_le_bytes());
+    put_string(bytes, &entry.key);
+
+    put_value(bytes, &entry.value);
+}
+
+/// Encode value alone, as it is in an entry
+pub fn encode_value(value: &RedisValue) -> Vec<u8> {
+    let mut bytes = vec![];
+    put_value(&mut bytes, value);
+    bytes
+}
+
+/// Length of `encode_value`, computed without encoding the value. It is the size model of
+/// the `serializedlength` of `DEBUG OBJECT`.
+pub fn serialized_len(value: &RedisValue) -> usize {
+    const LEN: usize = std::mem::size_of::<u32>();
+    const SCORE: usize = std::mem::size_of::<u64>();
+    let string_len = |value: &[u8]| LEN + value.len();
+    LEN + match value {
+        RedisValue::String(value) => value.len(),
+        RedisValue::List(values) => values.iter().map(|value| string_len(value)).sum(),
+        RedisValue::Set(values) => values.iter().map(|value| string_len(value)).sum(),
+        RedisValue::Hash(values) => values
+            .iter()
+            .map(|(field, value)| string_len(field) + string_len(value))
+            .sum(),
+        RedisValue::SortedSet(values) => values
+            .iter()
+            .map(|(member, _)| string_len(member) + SCORE)
+            .sum(),
+    }
+}
+
+fn put_value(bytes: &mut Vec<u8>, value: &RedisValue) {
+    match value {
+        RedisValue::String(value) => put_string(bytes, value),
+        RedisValue::List(values) => {
+            put_len(bytes, values.len());
+            values.iter().for_each(|value| put_string(bytes, value));
+        }
+        RedisValue::Set(values) => {
+            put_len(bytes, values.len());
+            values.iter().for_each(|value| put_string(bytes, value));
+        }
+        RedisValue::Hash(values) => {
+            put_len(bytes, values.len());
+            for (field, value) in values {
+                put_string(bytes, field);
+                put_string(bytes, value);
+            }
+        }
+        RedisValue::SortedSet(values) => {
+            put_len(bytes, values.len());
+            for (member, score) in values.iter() {
+                put_string(bytes, member);
+                bytes.extend_from_slice(&score.to_bits().to_le_bytes());
+            }
+        }
+    }
+}
+
+/// Decode the entries of every database, the result is indexed by db
+pub fn decode(bytes: &[u8]) -> Result<Vec<Vec<SnapshotEntry>>> {
+    let mut reader = Reader { bytes };
+
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(bad_format());
+    }
+    if reader.u8()? != VERSION {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "unsupported snapshot version",
+        ));
+    }
+
+    let mut databases: Vec<Vec<SnapshotEntry>> = vec![];
+    let mut db = None;
+    loop {
+        let data_type = reader.u8()?;
+        if data_type == EOF {
+            break;
+        }
+        if data_type == SELECTDB {
+            let index = reader.len()?;
+            if databases.len() <= index {
+                databases.resize_with(index + 1, Vec::new);
+            }
+            db = Some(index);
+            continue;
+        }
+        // every entry belongs to the db selected before it
+        let db = db.ok_or_else(bad_format)?;
+
+        let expiry = match reader.i64()? {
+            NO_EXPIRY => None,
+            timestamp => Some(Expiry { timestamp }),
+        };
+        let key = reader.string()?;
+        let value = reader.value(data_type)?;
+
+        databases[db].push(SnapshotEntry { key, value, expiry });
+    }
+
+    Ok(databases)
+}
+
+/// Serialize value as `DUMP` does
+pub fn dump(value: &RedisValue) -> Vec<u8> {
+    let mut bytes = vec![type_of(value)];
+    put_value(&mut bytes, value);
+    bytes.push(VERSION);
+    let checksum = crc64(&bytes);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+    bytes
+}
+
+/// Why the payload of a `RESTORE` is refused
+#[derive(Debug, PartialEq)]
+pub enum BadDump {
+    // not a payload of this version, or altered since its `DUMP`
+    VersionOrChecksum,
+    // of the right version and checksum, but not the encoding of a value
+    Format,
+}
+
+/// Deserialize the payload of a `DUMP`, checking its version and checksum
+pub fn undump(payload: &[u8]) -> std::result::Result<RedisValue, BadDump> {
+    if payload.len() < 10 {
+        return Err(BadDump::VersionOrChecksum);
+    }
+    let (bytes, checksum) = payload.split_at(payload.len() - 8);
+    let mut buf = [0; 8];
+    buf.copy_from_slice(checksum);
+    if bytes[bytes.len() - 1] != VERSION || crc64(bytes) != u64::from_le_bytes(buf) {
+        return Err(BadDump::VersionOrChecksum);
+    }
+
+    let mut reader = Reader {
+        bytes: &bytes[..bytes.len() - 1],
+    };
+    let value = reader
+        .u8()
+        .and_then(|data_type| reader.value(data_type))
+        .map_err(|_| BadDump::Format)?;
+    match reader.bytes.is_empty() {
+        true => Ok(value),
+        false => Err(BadDump::Format),
+    }
+}
+
+// CRC-64/Jones, reflected, as in Redis
+pub(crate) fn crc64(bytes: &[u8]) -> u64 {
+    const POLY: u64 = 0x95ac_9329_ac4b_c9b5;
+    let mut crc = 0u64;
+    for byte in bytes {
+        crc ^= *byte as u64;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ POLY,
+                _ => crc >> 1,
+            };
+        }
+    }
+    crc
+}
+
+/// Encode the databases with the framing of a transfer over a connection
+pub fn encode_framed(databases: &[Vec<SnapshotEntry>]) -> Vec<u8> {
+    let payload = encode(databases);
+    let mut bytes = format!("${}\r\n", payload.len()).into_bytes();
+    bytes.extend_from_slice(&payload);
+    bytes
+}
+
+/// Parse the `$<len>\r\n` header of a framed snapshot, returning the length of the header
+/// and the one of the snapshot which follows it, or `None` if the header is incomplete
+pub fn decode_frame_header(bytes: &[u8]) -> Result<Option<(usize, usize)>> {
+    match bytes.first() {
+        Some(b'$') => {}
+        Some(_) => return Err(bad_format()),
+        None => return Ok(None),
+    }
+    let end = match bytes.windows(2).position(|crlf| crlf == b"\r\n") {
+        Some(end) => end,
+        None => return Ok(None),
+    };
+    let len = std::str::from_utf8(&bytes[1..end])
+        .ok()
+        .and_then(|len| len.parse::<usize>().ok())
+        .ok_or_else(bad_format)?;
+    Ok(Some((end + 2, len)))
+}
+
+/// Write the snapshot to a temporary file first, then rename it to path so an existing
+/// snapshot is never left half written
+pub fn save(path: &Path, databases: &[Vec<SnapshotEntry>]) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(&encode(databases))?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)
+}
+
+pub fn load(path: &Path) -> Result<Vec<Vec<SnapshotEntry>>> {
+    decode(&fs::read(path)?)
+}
+
+fn put_len(bytes: &mut Vec<u8>, len: usize) {
+    bytes.extend_from_slice(&(len as u32).to_le_bytes());
+}
+
+fn put_string(bytes: &mut Vec<u8>, value: &[u8]) {
+    put_len(bytes, value.len());
+    bytes.extend_from_slice(value);
+}
+
+fn bad_format() -> Error {
+    Error::new(ErrorKind::InvalidData, "bad snapshot format")
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(bad_format());
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(i64::from_le_bytes(buf))
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf) as usize)
+    }
+
+    fn string(&mut self) -> Result<RedisString> {
+        let len = self.len()?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn value(&mut self, data_type: u8) -> Result<RedisValue> {
+        let value = match data_type {
+            TYPE_STRING => RedisValue::String(self.string()?),
+            TYPE_LIST => {
+                let len = self.len()?;
+                let mut values = Vec::with_capacity(len);
+                for _ in 0..len {
+                    values.push(self.string()?);
+                }
+                RedisValue::List(values)
+            }
+            TYPE_SET => {
+                let len = self.len()?;
+                let mut values = HashSet::with_capacity(len);
+                for _ in 0..len {
+                    values.insert(self.string()?);
+                }
+                RedisValue::Set(values)
+            }
+            TYPE_HASH => {
+                let len = self.len()?;
+                let mut values = HashMap::with_capacity(len);
+                for _ in 0..len {
+                    let field = self.string()?;
+                    values.insert(field, self.string()?);
+                }
+                RedisValue::Hash(values)
+            }
+            TYPE_SORTED_SET => {
+                let len = self.len()?;
+                let mut values = RedisSortedSet::new();
+                for _ in 0..len {
+                    let member = self.string()?;
+                    let score = f64::from_bits(self.i64()? as u64);
+                    values.insert(&member, score);
+                }
+                RedisValue::SortedSet(values)
+            }
+            _ => return Err(bad_format()),
+        };
+        Ok(value)
+    }
+}
@@ -52,6 +52,36 @@ fn test_expire() {
     }
 }
 
+#[test]
+fn write_replaces_value() {
+    let mut mem = InMemoryStorage::default();
+
+    mem.write(b"key", b"xxx");
+    mem.expire(b"key", Expiry::new_from_secs(100).unwrap());
+    mem.write(b"key", b"yyy");
+    assert!(mem.meta(b"key").unwrap().expiry.is_none());
+
+    mem.lwrite(b"list", vec![b"a".to_vec()]);
+    mem.write(b"list", b"xxx");
+    assert_eq!(mem.type_of(b"list"), b"string");
+    assert_eq!(mem.lread(b"list"), None);
+    mem.remove(b"list");
+    mem.remove(b"key");
+    assert_eq!(mem.used_memory(), 0);
+}
+
+#[test]
+fn test_expiry_overflow() {
+    assert!(Expiry::new_from_secs(u64::MAX).is_err());
+    assert!(Expiry::new_from_millis(u64::MAX).is_err());
+    // fits an i64 of secs, but not once in millis
+    assert!(Expiry::new_from_secs(i64::MAX as u64).is_err());
+    assert!(Expiry::new_from_secs(9_999_999_999_999).is_err());
+
+    let expiry = Expiry::new_from_secs(100 * 365 * 24 * 3600).unwrap();
+    assert!(expiry.duration_left_millis() > 0);
+}
+
 #[test]
 fn contains() {
     let mut mem = InMemoryStorage::default();
@@ -75,3 +105,328 @@ fn extend() {
     assert_eq!(len, 8);
     assert_eq!(x, b"value222");
 }
+
+#[test]
+fn used_memory() {
+    let mut mem = InMemoryStorage::default();
+    assert_eq!(mem.used_memory(), 0);
+
+    mem.write(b"key", b"value");
+    let string = mem.memory_usage(b"key").unwrap();
+    assert!(string > b"keyvalue".len() as u64);
+    assert_eq!(mem.used_memory(), string);
+
+    // overwriting or extending a key replaces its usage
+    mem.write(b"key", b"a longer value");
+    assert!(mem.memory_usage(b"key").unwrap() > string);
+    mem.extend(b"key", b"tail");
+    assert_eq!(mem.used_memory(), mem.memory_usage(b"key").unwrap());
+
+    mem.lwrite(b"list", vec![b"a".to_vec(), b"b".to_vec()]);
+    let list = mem.memory_usage(b"list").unwrap();
+    let total = mem.used_memory();
+    assert_eq!(total, mem.memory_usage(b"key").unwrap() + list);
+
+    assert_eq!(mem.remove(b"key"), 1);
+    assert_eq!(mem.used_memory(), list);
+    assert_eq!(mem.memory_usage(b"key"), None);
+
+    // an expired key is released once it is noticed
+    mem.expire(b"list", Expiry::new_from_millis(1).unwrap());
+    sleep(Duration::from_millis(5));
+    assert!(!mem.contains(b"list"));
+    assert_eq!(mem.used_memory(), 0);
+
+    // members removed in place are released, the set with the last of them
+    let set = ["a", "b", "c"]
+        .iter()
+        .map(|m| m.as_bytes().to_vec())
+        .collect();
+    mem.swrite(b"set", set);
+    assert_eq!(mem.sremove(b"set", &[b"a".to_vec(), b"x".to_vec()]), 1);
+    assert_eq!(mem.used_memory(), mem.memory_usage(b"set").unwrap());
+    assert_eq!(mem.sremove(b"set", &[b"b".to_vec(), b"c".to_vec()]), 2);
+    assert!(!mem.contains(b"set"));
+    assert_eq!(mem.used_memory(), 0);
+
+    mem.write(b"key", b"value");
+    mem.clear();
+    assert_eq!(mem.used_memory(), 0);
+}
+
+#[test]
+fn snapshot_round_trip() {
+    use std::collections::{HashMap, HashSet};
+
+    use crate::storage::models::RedisValue;
+    use crate::storage::snapshot;
+
+    let mut mem = InMemoryStorage::default();
+    mem.write(b"string", b"value");
+    mem.expire(b"string", Expiry::new_from_secs(100).unwrap());
+    mem.lwrite(b"list", vec![b"a".to_vec(), b"b".to_vec()]);
+    mem.swrite(
+        b"set",
+        [b"a".to_vec()].iter().cloned().collect::<HashSet<_>>(),
+    );
+    let mut hash = HashMap::new();
+    hash.insert(b"field".to_vec(), b"value".to_vec());
+    mem.hwrite(b"hash", hash);
+
+    let entries = snapshot::take(&mem);
+    assert_eq!(entries.len(), 4);
+    // the entries land back in the db they were taken from
+    let mut decoded = snapshot::decode(&snapshot::encode(&[vec![], entries.clone()])).unwrap();
+    assert_eq!(decoded, vec![vec![], entries]);
+
+    let mut restored = InMemoryStorage::default();
+    snapshot::restore(&mut restored, decoded.remove(1));
+    assert_eq!(restored.read(b"string"), Some(&b"value"[..]));
+    assert!(restored.meta(b"string").unwrap().expiry.is_some());
+    assert_eq!(
+        restored.value(b"list"),
+        Some(RedisValue::List(vec![b"a".to_vec(), b"b".to_vec()]))
+    );
+    assert_eq!(restored.hread(b"hash", b"field"), Some(&b"value"[..]));
+    assert_eq!(restored.size(), 4);
+
+    assert!(snapshot::decode(b"garbage").is_err());
+}
+
+#[test]
+fn serialized_len() {
+    use std::collections::{HashMap, HashSet};
+
+    use crate::storage::models::{RedisSortedSet, RedisValue};
+    use crate::storage::snapshot;
+
+    let mut hash = HashMap::new();
+    hash.insert(b"field".to_vec(), b"value".to_vec());
+    hash.insert(b"other".to_vec(), b"".to_vec());
+    let mut sorted_set = RedisSortedSet::new();
+    sorted_set.insert(b"member", 1.5);
+    let values = vec![
+        RedisValue::Hash(hash),
+        RedisValue::String(b"value".to_vec()),
+        RedisValue::List(vec![b"a".to_vec(), b"bc".to_vec()]),
+        RedisValue::Set([b"a".to_vec()].iter().cloned().collect::<HashSet<_>>()),
+        RedisValue::SortedSet(sorted_set),
+        RedisValue::List(vec![]),
+    ];
+    for value in &values {
+        assert_eq!(
+            snapshot::serialized_len(value),
+            snapshot::encode_value(value).len()
+        );
+    }
+}
+
+#[test]
+fn snapshot_framing() {
+    use crate::storage::snapshot;
+
+    let mut mem = InMemoryStorage::default();
+    mem.write(b"key", b"value");
+    let databases = vec![snapshot::take(&mem)];
+
+    let framed = snapshot::encode_framed(&databases);
+    let (header_len, len) = snapshot::decode_frame_header(&framed).unwrap().unwrap();
+    assert_eq!(header_len + len, framed.len());
+    assert_eq!(snapshot::decode(&framed[header_len..]).unwrap(), databases);
+
+    // the header is only known once its CRLF is received
+    assert_eq!(snapshot::decode_frame_header(b"").unwrap(), None);
+    assert_eq!(snapshot::decode_frame_header(b"$12").unwrap(), None);
+    assert!(snapshot::decode_frame_header(b"+OK\r\n").is_err());
+    assert!(snapshot::decode_frame_header(b"$x\r\n").is_err());
+}
+
+#[test]
+fn hash_encoding() {
+    use crate::storage::models::{Encoding, EncodingLimits, RedisString};
+    use std::collections::HashMap;
+
+    let limits = EncodingLimits {
+        hash_max_listpack_entries: 2,
+        hash_max_listpack_value: 4,
+        ..EncodingLimits::default()
+    };
+    let mut mem = InMemoryStorage::default();
+    mem.set_encoding_limits(limits);
+    let hash = |fields: &[(&str, &str)]| -> HashMap<RedisString, RedisString> {
+        fields
+            .iter()
+            .map(|(field, value)| (field.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            .collect()
+    };
+
+    mem.hwrite(b"hash", hash(&[("a", "1"), ("b", "2")]));
+    assert_eq!(mem.meta(b"hash").unwrap().encoding, Encoding::Listpack);
+    mem.hwrite(b"hash", hash(&[("a", "1"), ("b", "2"), ("c", "3")]));
+    assert_eq!(mem.meta(b"hash").unwrap().encoding, Encoding::Hashtable);
+    // the conversion is one-way
+    mem.hwrite(b"hash", hash(&[("a", "1")]));
+    assert_eq!(mem.meta(b"hash").unwrap().encoding, Encoding::Hashtable);
+
+    mem.hwrite(b"long", hash(&[("a", "12345")]));
+    assert_eq!(mem.meta(b"long").unwrap().encoding, Encoding::Hashtable);
+}
+
+#[test]
+fn set_encoding() {
+    use crate::storage::models::{Encoding, EncodingLimits};
+    use std::collections::HashSet;
+
+    let limits = EncodingLimits {
+        set_max_intset_entries: 3,
+        set_max_listpack_entries: 2,
+        set_max_listpack_value: 4,
+        ..EncodingLimits::default()
+    };
+    let mut mem = InMemoryStorage::default();
+    mem.set_encoding_limits(limits);
+    let set = |members: &[&str]| -> HashSet<Vec<u8>> {
+        members
+            .iter()
+            .map(|member| member.as_bytes().to_vec())
+            .collect()
+    };
+    let encoding = |mem: &InMemoryStorage, key: &[u8]| mem.meta(key).unwrap().encoding;
+
+    mem.swrite(b"set", set(&["1", "2", "-3"]));
+    assert_eq!(encoding(&mem, b"set"), Encoding::Intset);
+    mem.swrite(b"set", set(&["1", "a"]));
+    assert_eq!(encoding(&mem, b"set"), Encoding::Listpack);
+    // the conversion is one-way
+    mem.swrite(b"set", set(&["1"]));
+    assert_eq!(encoding(&mem, b"set"), Encoding::Listpack);
+    mem.swrite(b"set", set(&["1", "a", "b"]));
+    assert_eq!(encoding(&mem, b"set"), Encoding::Hashtable);
+
+    // too many integers for an intset
+    mem.swrite(b"integers", set(&["1", "2", "3", "4"]));
+    assert_eq!(encoding(&mem, b"integers"), Encoding::Hashtable);
+    mem.swrite(b"long", set(&["abcde"]));
+    assert_eq!(encoding(&mem, b"long"), Encoding::Hashtable);
+}
+
+#[test]
+fn list_encoding() {
+    use crate::storage::models::{Encoding, EncodingLimits};
+
+    let mut mem = InMemoryStorage::default();
+    let list = |len: usize, value: &str| vec![value.as_bytes().to_vec(); len];
+    let encoding = |mem: &InMemoryStorage, key: &[u8]| mem.meta(key).unwrap().encoding;
+
+    // 8KB by default
+    mem.lwrite(b"list", list(2, &"x".repeat(4096)));
+    assert_eq!(encoding(&mem, b"list"), Encoding::Listpack);
+    mem.lwrite(b"list", list(3, &"x".repeat(4096)));
+    assert_eq!(encoding(&mem, b"list"), Encoding::Quicklist);
+    // the conversion is one-way
+    mem.lwrite(b"list", list(1, "x"));
+    assert_eq!(encoding(&mem, b"list"), Encoding::Quicklist);
+
+    mem.set_encoding_limits(EncodingLimits {
+        list_max_listpack_size: 2,
+        ..EncodingLimits::default()
+    });
+    mem.lwrite(b"short", list(2, "x"));
+    assert_eq!(encoding(&mem, b"short"), Encoding::Listpack);
+    mem.lwrite(b"short", list(3, "x"));
+    assert_eq!(encoding(&mem, b"short"), Encoding::Quicklist);
+}
+
+#[test]
+fn parse_i64() {
+    use crate::storage::models::{parse_i64, parse_u64};
+
+    assert_eq!(parse_i64(b"0"), Some(0));
+    assert_eq!(parse_i64(b"10"), Some(10));
+    assert_eq!(parse_i64(b"-10"), Some(-10));
+    assert_eq!(parse_i64(b"9223372036854775807"), Some(i64::MAX));
+    assert_eq!(parse_i64(b"-9223372036854775808"), Some(i64::MIN));
+    for rejected in [
+        &b""[..],
+        b" 10",
+        b"10 ",
+        b"+10",
+        b"010",
+        b"-0",
+        b"-",
+        b"1e3",
+        b"9223372036854775808",
+    ] {
+        assert_eq!(parse_i64(rejected), None);
+    }
+    assert_eq!(parse_u64(b"18446744073709551615"), Some(u64::MAX));
+    assert_eq!(parse_u64(b"-1"), None);
+    assert_eq!(parse_u64(b" 1"), None);
+}
+
+#[test]
+fn entry() {
+    use std::collections::HashMap;
+
+    use crate::storage::models::{RedisSortedSet, RedisType, ValueRef};
+
+    let mut mem = InMemoryStorage::default();
+    mem.write(b"string", b"value");
+    mem.lwrite(b"list", vec![b"a".to_vec()]);
+    let mut hash = HashMap::new();
+    hash.insert(b"field".to_vec(), b"value".to_vec());
+    mem.hwrite(b"hash", hash.clone());
+    let mut zset = RedisSortedSet::new();
+    zset.insert(b"member", 1.5);
+    mem.zwrite(b"zset", zset.clone());
+
+    assert_eq!(mem.entry(b"string"), Some(ValueRef::String(b"value")));
+    assert_eq!(mem.entry(b"list"), Some(ValueRef::List(&[b"a".to_vec()])));
+    assert_eq!(mem.entry(b"hash"), Some(ValueRef::Hash(&hash)));
+    assert_eq!(mem.entry(b"zset"), Some(ValueRef::SortedSet(&zset)));
+    assert_eq!(
+        mem.entry(b"zset").map(|value| value.data_type()),
+        Some(RedisType::SortedSet)
+    );
+    assert_eq!(mem.entry(b"missing"), None);
+
+    // an expired key is removed as it is looked up
+    mem.expire(b"string", Expiry::new_from_millis(1).unwrap());
+    sleep(Duration::from_millis(2));
+    assert_eq!(mem.entry(b"string"), None);
+    assert!(mem.meta(b"string").is_none());
+}
+
+#[test]
+fn dump_payload() {
+    use std::collections::HashMap;
+
+    use crate::storage::models::RedisValue;
+    use crate::storage::snapshot;
+
+    let mut hash = HashMap::new();
+    hash.insert(b"field".to_vec(), b"value".to_vec());
+    for value in [
+        RedisValue::String(b"value".to_vec()),
+        RedisValue::List(vec![b"a".to_vec(), b"b".to_vec()]),
+        RedisValue::Hash(hash),
+    ] {
+        let payload = snapshot::dump(&value);
+        assert_eq!(snapshot::undump(&payload).unwrap(), value);
+
+        // a payload altered anywhere is refused
+        for index in 0..payload.len() {
+            let mut corrupted = payload.clone();
+            corrupted[index] ^= 0x10;
+            assert!(snapshot::undump(&corrupted).is_err());
+        }
+        assert!(snapshot::undump(&payload[..payload.len() - 1]).is_err());
+    }
+    assert!(snapshot::undump(b"").is_err());
+
+    // an unknown type, with the version and checksum of a payload
+    let mut payload = vec![0x7f, 1];
+    let checksum = snapshot::crc64(&payload);
+    payload.extend_from_slice(&checksum.to_le_bytes());
+    assert_eq!(snapshot::undump(&payload), Err(snapshot::BadDump::Format));
+}
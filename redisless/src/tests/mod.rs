@@ -32,9 +32,9 @@ fn start_and_stop_server_from_c_binding() {
 
         // run command `GET mykey`
         let _ = stream.write(b"*2\r\n$3\r\nGET\r\n$5\r\nmykey\r\n");
-        let mut get_res = [0; 8];
+        let mut get_res = [0; 11];
         let _ = stream.read(&mut get_res);
-        assert_eq!(get_res, b"+value\r\n"[..]);
+        assert_eq!(get_res, b"$5\r\nvalue\r\n"[..]);
 
         // run command `DEL mykey`
         let _ = stream.write(b"*2\r\n$3\r\nDEL\r\n$5\r\nmykey\r\n");
@@ -42,11 +42,11 @@ fn start_and_stop_server_from_c_binding() {
         let _ = stream.read(&mut del_res);
         assert_eq!(del_res, b":1\r\n"[..]);
 
-        // run command `INFO`
+        // run command `INFO`, with every database empty
         let _ = stream.write(b"*1\r\n$4\r\nINFO\r\n");
-        let mut info_res = [0; 6];
-        let _ = stream.read(&mut info_res);
-        assert_eq!(info_res, b"$0\r\n\r\n"[..]);
+        let mut info_res = [0; 19];
+        let _ = stream.read_exact(&mut info_res);
+        assert_eq!(info_res, b"$12\r\n# Keyspace\r\n\r\n"[..]);
     }
 
     unsafe {